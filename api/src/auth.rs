@@ -1,11 +1,14 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use uuid::Uuid;
 
 use crate::error::{ApiError, extract_error_reason};
 
+#[cfg(feature = "keyring")]
 const KEYRING_SERVICE: &str = "unisrv-cli";
+#[cfg(feature = "keyring")]
 const KEYRING_USER: &str = "auth_session";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -53,6 +56,18 @@ impl AuthSession {
         }
     }
 
+    /// Create a test session whose access token has expired but whose refresh
+    /// token is still good, so the next authenticated call refreshes first.
+    #[cfg(any(test, feature = "test-support"))]
+    pub fn test_session_needing_refresh(token: &str) -> Self {
+        let now = Utc::now();
+        AuthSession {
+            access_token_expiry: now - chrono::Duration::minutes(1),
+            refresh_token_expiry: now + chrono::Duration::hours(1),
+            ..Self::test_session(token, chrono::Duration::hours(1))
+        }
+    }
+
     pub(crate) fn from_login_response(resp: LoginResponse) -> Self {
         AuthSession {
             user_id: resp.user_id,
@@ -77,15 +92,62 @@ impl AuthSession {
         Utc::now() > self.access_token_expiry
     }
 
+    /// Refresh the access token if it has expired, saving the result to
+    /// `store`.
+    ///
+    /// Other `unisrv` processes may share the session (a CI matrix runs
+    /// several at once), and the server rotates the refresh token on every
+    /// refresh, so whichever refreshes second would be turned away. The
+    /// refresh is done holding `store`'s lock, after re-reading the store: a
+    /// session another process has rotated in the meantime is taken over
+    /// rather than refreshed again. A rejected refresh re-reads it once more
+    /// before giving up, for a process that refreshed without the lock.
     pub async fn refresh(
         &mut self,
         client: &reqwest::Client,
         base_url: &str,
+        store: &AuthStore,
     ) -> Result<(), ApiError> {
         if self.access_token_expiry > Utc::now() {
             return Ok(());
         }
-        self.force_refresh(client, base_url).await
+        let _lock = store.lock();
+        self.take_over_rotated(store);
+        if !self.access_token_expired() {
+            return Ok(());
+        }
+        match self.force_refresh(client, base_url).await {
+            Ok(()) => store.save(self).map_err(ApiError::Other),
+            Err(err @ ApiError::AuthRequired(_)) => {
+                if self.take_over_rotated(store) && !self.access_token_expired() {
+                    Ok(())
+                } else {
+                    Err(err)
+                }
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Replace this session with the one in `store` if that one's refresh
+    /// token has been rotated since this was loaded. Whether it was.
+    fn take_over_rotated(&mut self, store: &AuthStore) -> bool {
+        match store.load() {
+            Some(stored) if stored.rotated_from(self) => {
+                tracing::debug!("Auth session was refreshed by another process; using its tokens");
+                *self = stored;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether this session came from refreshing (or logging in again after)
+    /// `earlier`, which makes `earlier`'s refresh token spent.
+    fn rotated_from(&self, earlier: &AuthSession) -> bool {
+        // Another process may have switched accounts meanwhile; that's a
+        // different session, not this one rotated.
+        self.user_id == earlier.user_id && self.refresh_token != earlier.refresh_token
     }
 
     pub async fn force_refresh(
@@ -131,49 +193,262 @@ impl AuthSession {
 
 // ── Storage ──
 
-fn auth_file_path() -> Option<PathBuf> {
-    Some(crate::config_dir()?.join("auth.json"))
+const AUTH_FILE: &str = "auth.json";
+/// Held while refreshing, so concurrent processes refresh one at a time.
+const LOCK_FILE: &str = "auth.lock";
+
+/// A logged-in account: a session on `api_host`, as `username`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Account {
+    pub api_host: String,
+    pub username: String,
+    pub session: AuthSession,
+}
+
+/// Every account logged into, and which is in use on each API host. Kept
+/// whole in the one keyring entry (or file), so someone working for several
+/// customers can switch between them without logging in again.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Accounts {
+    #[serde(default)]
+    pub accounts: Vec<Account>,
+    /// API host → username of the account in use there.
+    #[serde(default)]
+    pub active: BTreeMap<String, String>,
+}
+
+impl Accounts {
+    /// Parse what the store holds. A bare session, saved before there could
+    /// be several, becomes the account in use on `api_host`, named by its user
+    /// id since the username wasn't kept.
+    fn parse(data: &str, api_host: &str) -> Option<Self> {
+        if let Ok(session) = serde_json::from_str::<AuthSession>(data) {
+            let mut accounts = Accounts::default();
+            accounts.insert(api_host, &session.user_id.to_string(), session);
+            return Some(accounts);
+        }
+        serde_json::from_str(data).ok()
+    }
+
+    /// The account in use on `api_host`, if any.
+    pub fn active(&self, api_host: &str) -> Option<&Account> {
+        let username = self.active.get(api_host)?;
+        self.find(api_host, username)
+    }
+
+    fn find(&self, api_host: &str, username: &str) -> Option<&Account> {
+        self.accounts
+            .iter()
+            .find(|a| a.api_host == api_host && a.username == username)
+    }
+
+    /// Add `username`'s session on `api_host`, replacing an earlier one, and
+    /// put it in use there.
+    fn insert(&mut self, api_host: &str, username: &str, session: AuthSession) {
+        match self
+            .accounts
+            .iter_mut()
+            .find(|a| a.api_host == api_host && a.username == username)
+        {
+            Some(account) => account.session = session,
+            None => self.accounts.push(Account {
+                api_host: api_host.to_string(),
+                username: username.to_string(),
+                session,
+            }),
+        }
+        self.active
+            .insert(api_host.to_string(), username.to_string());
+    }
+
+    /// Store a refreshed `session` with the account it belongs to: the one
+    /// in use on `api_host` if that's the same user, else another of that
+    /// user's there.
+    fn update(&mut self, api_host: &str, session: AuthSession) {
+        let active = self.active.get(api_host);
+        let found = self
+            .accounts
+            .iter()
+            .enumerate()
+            .filter(|(_, a)| a.api_host == api_host && a.session.user_id == session.user_id)
+            .min_by_key(|(_, a)| Some(&a.username) != active)
+            .map(|(i, _)| i);
+        match found {
+            Some(i) => self.accounts[i].session = session,
+            None => self.insert(api_host, &session.user_id.to_string(), session),
+        }
+    }
 }
 
 /// Persistent auth storage that tries keyring first, then falls back to a JSON file.
 /// The keyring entry is created once and cached to avoid repeated OS prompts.
+/// Without the `keyring` feature only the file is used.
+///
+/// The store holds every [`Account`]; `load` and `save` act on the one in use
+/// on the store's API host.
 pub struct AuthStore {
+    #[cfg(feature = "keyring")]
     keyring_entry: Option<keyring::Entry>,
+    /// False for a store that never touches the keyring or disk (tests).
+    persistent: bool,
+    /// Where the auth file and its lock live: `~/.unisrv`.
+    dir: Option<PathBuf>,
+    /// Whose accounts `load` and `save` pick from.
+    api_host: String,
+}
+
+/// Exclusive use of the session for one process, until dropped.
+pub struct AuthLock {
+    _file: std::fs::File,
 }
 
 impl AuthStore {
-    pub fn new() -> Self {
-        let keyring_entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
-            .inspect_err(|e| tracing::debug!("Keyring unavailable: {e}"))
-            .ok();
+    pub fn new(api_host: &str) -> Self {
+        AuthStore {
+            #[cfg(feature = "keyring")]
+            keyring_entry: keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+                .inspect_err(|e| tracing::debug!("Keyring unavailable: {e}"))
+                .ok(),
+            persistent: true,
+            dir: crate::config_dir(),
+            api_host: api_host.to_string(),
+        }
+    }
+
+    /// A store that loads nothing and discards saves, so a client using an
+    /// API token, or under test, neither reads nor clobbers the real session.
+    pub fn in_memory() -> Self {
+        AuthStore {
+            #[cfg(feature = "keyring")]
+            keyring_entry: None,
+            persistent: false,
+            dir: None,
+            api_host: String::new(),
+        }
+    }
 
-        AuthStore { keyring_entry }
+    /// A store kept only in files under `dir`, never the keyring.
+    #[cfg(any(test, feature = "test-support"))]
+    pub fn in_dir(dir: impl Into<PathBuf>, api_host: &str) -> Self {
+        AuthStore {
+            #[cfg(feature = "keyring")]
+            keyring_entry: None,
+            persistent: true,
+            dir: Some(dir.into()),
+            api_host: api_host.to_string(),
+        }
     }
 
+    /// Lock the session against other processes until the returned guard is
+    /// dropped, waiting for one that holds it. `None` for an in-memory store,
+    /// or when the lock file can't be opened; the caller then goes ahead
+    /// unlocked, as before locking existed.
+    pub fn lock(&self) -> Option<AuthLock> {
+        if !self.persistent {
+            return None;
+        }
+        let dir = self.dir.as_ref()?;
+        let locked = std::fs::create_dir_all(dir).and_then(|()| {
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(dir.join(LOCK_FILE))?;
+            file.lock()?;
+            Ok(file)
+        });
+        match locked {
+            Ok(file) => Some(AuthLock { _file: file }),
+            Err(e) => {
+                tracing::debug!("Couldn't lock the auth session, refreshing unlocked: {e}");
+                None
+            }
+        }
+    }
+
+    fn file_path(&self) -> Option<PathBuf> {
+        Some(self.dir.as_ref()?.join(AUTH_FILE))
+    }
+
+    /// The session of the account in use on this store's API host.
     pub fn load(&self) -> Option<AuthSession> {
-        self.load_from_keyring().or_else(|| self.load_from_file())
+        let accounts = self.accounts();
+        Some(accounts.active(&self.api_host)?.session.clone())
     }
 
+    /// Every account logged into, on any API host.
+    pub fn accounts(&self) -> Accounts {
+        if !self.persistent {
+            return Accounts::default();
+        }
+        self.load_from_keyring()
+            .or_else(|| self.load_from_file())
+            .and_then(|data| Accounts::parse(&data, &self.api_host))
+            .unwrap_or_default()
+    }
+
+    /// Save a refreshed session with the account it belongs to. Must be
+    /// called holding [`lock`](Self::lock), as [`AuthSession::refresh`] does.
     pub fn save(&self, session: &AuthSession) -> Result<(), anyhow::Error> {
-        let serialized = serde_json::to_string(session)?;
+        if !self.persistent {
+            return Ok(());
+        }
+        let mut accounts = self.accounts();
+        accounts.update(&self.api_host, session.clone());
+        self.save_accounts(&accounts)
+    }
 
-        if let Some(entry) = &self.keyring_entry {
-            match entry.set_password(&serialized) {
-                Ok(()) => {
-                    tracing::debug!("Auth session saved to keyring");
-                    if let Some(path) = auth_file_path() {
-                        let _ = std::fs::remove_file(&path);
-                    }
-                    return Ok(());
-                }
-                Err(e) => tracing::debug!("Failed to save to keyring, falling back to file: {e}"),
-            }
+    /// Keep a new login as `username` and put it in use on this store's API
+    /// host, alongside any other accounts.
+    pub fn add(&self, username: &str, session: &AuthSession) -> Result<(), anyhow::Error> {
+        if !self.persistent {
+            return Ok(());
         }
+        let _lock = self.lock();
+        let mut accounts = self.accounts();
+        accounts.insert(&self.api_host, username, session.clone());
+        self.save_accounts(&accounts)
+    }
 
-        self.save_to_file(&serialized)
+    /// Put `username`'s account in use on this store's API host.
+    pub fn switch(&self, username: &str) -> Result<Account, anyhow::Error> {
+        let _lock = self.lock();
+        let mut accounts = self.accounts();
+        let Some(account) = accounts.find(&self.api_host, username).cloned() else {
+            anyhow::bail!(
+                "not logged in as {username:?} on {}; log in with `unisrv login --username {username}`",
+                self.api_host
+            );
+        };
+        accounts
+            .active
+            .insert(self.api_host.clone(), username.to_string());
+        self.save_accounts(&accounts)?;
+        Ok(account)
     }
 
+    /// Forget the account in use on this store's API host. No other account
+    /// is put in use in its place: that takes an explicit switch, so leaving
+    /// one customer's account never lands in another's.
     pub fn delete(&self) {
+        if !self.persistent {
+            return;
+        }
+        let _lock = self.lock();
+        let mut accounts = self.accounts();
+        if let Some(username) = accounts.active.remove(&self.api_host) {
+            accounts
+                .accounts
+                .retain(|a| a.api_host != self.api_host || a.username != username);
+        }
+        if !accounts.accounts.is_empty() {
+            if let Err(e) = self.save_accounts(&accounts) {
+                tracing::debug!("Failed to save the remaining accounts: {e}");
+            }
+            return;
+        }
+
+        #[cfg(feature = "keyring")]
         if let Some(entry) = &self.keyring_entry {
             if let Err(e) = entry.delete_credential() {
                 tracing::debug!("Failed to delete from keyring: {e}");
@@ -182,27 +457,56 @@ impl AuthStore {
             }
         }
 
-        if let Some(path) = auth_file_path() {
-            if std::fs::remove_file(&path).is_ok() {
-                tracing::debug!("Auth session deleted from file");
+        if let Some(path) = self.file_path()
+            && std::fs::remove_file(&path).is_ok()
+        {
+            tracing::debug!("Auth session deleted from file");
+        }
+    }
+
+    fn save_accounts(&self, accounts: &Accounts) -> Result<(), anyhow::Error> {
+        let serialized = serde_json::to_string(accounts)?;
+
+        #[cfg(feature = "keyring")]
+        if let Some(entry) = &self.keyring_entry {
+            match entry.set_password(&serialized) {
+                Ok(()) => {
+                    tracing::debug!("Auth session saved to keyring");
+                    if let Some(path) = self.file_path() {
+                        let _ = std::fs::remove_file(&path);
+                    }
+                    return Ok(());
+                }
+                Err(e) => {
+                    // Windows' Credential Manager refuses secrets over 2560
+                    // bytes, which a session with long tokens can be. Drop
+                    // whatever an earlier save left there, or load() would
+                    // keep preferring it over the file written below.
+                    tracing::debug!("Failed to save to keyring, falling back to file: {e}");
+                    let _ = entry.delete_credential();
+                }
             }
         }
+
+        self.save_to_file(&serialized)
+    }
+
+    #[cfg(feature = "keyring")]
+    fn load_from_keyring(&self) -> Option<String> {
+        self.keyring_entry.as_ref()?.get_password().ok()
     }
 
-    fn load_from_keyring(&self) -> Option<AuthSession> {
-        let entry = self.keyring_entry.as_ref()?;
-        let password = entry.get_password().ok()?;
-        serde_json::from_str(&password).ok()
+    #[cfg(not(feature = "keyring"))]
+    fn load_from_keyring(&self) -> Option<String> {
+        None
     }
 
-    fn load_from_file(&self) -> Option<AuthSession> {
-        let path = auth_file_path()?;
-        let data = std::fs::read_to_string(&path).ok()?;
-        serde_json::from_str(&data).ok()
+    fn load_from_file(&self) -> Option<String> {
+        std::fs::read_to_string(self.file_path()?).ok()
     }
 
     fn save_to_file(&self, serialized: &str) -> Result<(), anyhow::Error> {
-        let path = auth_file_path().ok_or_else(|| {
+        let path = self.file_path().ok_or_else(|| {
             anyhow::anyhow!("Could not determine home directory for auth storage")
         })?;
         if let Some(parent) = path.parent() {
@@ -232,8 +536,54 @@ impl AuthStore {
     }
 }
 
-impl Default for AuthStore {
-    fn default() -> Self {
-        Self::new()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOST: &str = "https://api.unisrv.io";
+
+    fn session(token: &str) -> AuthSession {
+        AuthSession::test_session(token, chrono::Duration::hours(1))
+    }
+
+    #[test]
+    fn a_session_saved_before_accounts_is_read_as_the_active_one() {
+        let old = session("old");
+        let data = serde_json::to_string(&old).unwrap();
+        let accounts = Accounts::parse(&data, HOST).unwrap();
+        let active = accounts.active(HOST).unwrap();
+        assert_eq!(active.username, old.user_id.to_string());
+        assert_eq!(active.session.access_token(), "old");
+    }
+
+    #[test]
+    fn accounts_are_kept_side_by_side_and_switched_between() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = AuthStore::in_dir(dir.path(), HOST);
+        store.add("alice@acme", &session("acme")).unwrap();
+        store.add("alice@globex", &session("globex")).unwrap();
+        assert_eq!(store.load().unwrap().access_token(), "globex");
+
+        store.switch("alice@acme").unwrap();
+        assert_eq!(store.load().unwrap().access_token(), "acme");
+        assert!(store.switch("bob").is_err());
+
+        // Another host has its own account in use.
+        let staging = AuthStore::in_dir(dir.path(), "https://staging.unisrv.io");
+        assert!(staging.load().is_none());
+        assert_eq!(staging.accounts().accounts.len(), 2);
+
+        // A refresh lands on the account it came from.
+        let mut refreshed = store.load().unwrap();
+        refreshed.access_token = "acme-2".into();
+        store.save(&refreshed).unwrap();
+        store.switch("alice@globex").unwrap();
+        store.switch("alice@acme").unwrap();
+        assert_eq!(store.load().unwrap().access_token(), "acme-2");
+
+        // Logging out of one doesn't log into the other.
+        store.delete();
+        assert!(store.load().is_none());
+        assert_eq!(store.accounts().accounts.len(), 1);
     }
 }
@@ -0,0 +1,742 @@
+//! A read-through cache in front of another [`ApiClient`], for sessions that
+//! issue many commands in a row (`unisrv shell`).
+//!
+//! Only the list calls commands resolve names against are cached: an
+//! environment's instances, networks, services and deployments, and the
+//! environments themselves. Entries expire after a fixed age so changes made
+//! elsewhere show up, and every call that may change something (creating,
+//! updating, deleting, logging in) drops the whole cache, so a command never
+//! resolves against what the previous one just changed. Errors are never
+//! cached.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::auth::AuthSession;
+use crate::client::{ApiClient, LogStream};
+use crate::error::Result;
+use crate::models::*;
+
+/// Which list a cached response answers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Key {
+    Environments,
+    Instances(Uuid),
+    Networks(Uuid, bool),
+    Services(Uuid),
+    Deployments(Uuid),
+}
+
+type Entry = (Instant, Box<dyn Any + Send + Sync>);
+
+pub struct CachingClient<'a> {
+    inner: &'a dyn ApiClient,
+    max_age: Duration,
+    entries: Mutex<HashMap<Key, Entry>>,
+}
+
+impl<'a> CachingClient<'a> {
+    /// Cache `inner`'s list responses for up to `max_age`.
+    pub fn new(inner: &'a dyn ApiClient, max_age: Duration) -> Self {
+        Self {
+            inner,
+            max_age,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Forget everything, e.g. when the user asks for fresh data.
+    pub fn invalidate(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    async fn read<T>(&self, key: Key, fetch: impl Future<Output = Result<T>>) -> Result<T>
+    where
+        T: Clone + Send + Sync + 'static,
+    {
+        if let Some(hit) = self.lookup(key) {
+            return Ok(hit);
+        }
+        let value = fetch.await?;
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key, (Instant::now(), Box::new(value.clone())));
+        Ok(value)
+    }
+
+    fn lookup<T: Clone + 'static>(&self, key: Key) -> Option<T> {
+        let entries = self.entries.lock().unwrap();
+        let (at, value) = entries.get(&key)?;
+        if at.elapsed() > self.max_age {
+            return None;
+        }
+        value.downcast_ref::<T>().cloned()
+    }
+
+    /// Run a call that may change state, then drop the cache. A failed call
+    /// may still have applied part of a change, so it invalidates too.
+    async fn write<T>(&self, call: impl Future<Output = Result<T>>) -> Result<T> {
+        let result = call.await;
+        self.invalidate();
+        result
+    }
+}
+
+#[async_trait]
+impl ApiClient for CachingClient<'_> {
+    // ── Auth ──
+
+    async fn login(&self, username: &str, password: &str) -> Result<()> {
+        self.write(self.inner.login(username, password)).await
+    }
+
+    async fn access_token(&self) -> Result<String> {
+        self.inner.access_token().await
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.inner.is_read_only()
+    }
+
+    async fn auth_session(&self) -> Result<AuthSession> {
+        self.inner.auth_session().await
+    }
+
+    async fn create_api_token(&self, req: CreateApiTokenRequest) -> Result<CreatedApiToken> {
+        self.write(self.inner.create_api_token(req)).await
+    }
+
+    async fn list_api_tokens(&self) -> Result<ApiTokenListResponse> {
+        self.inner.list_api_tokens().await
+    }
+
+    async fn revoke_api_token(&self, id: Uuid) -> Result<()> {
+        self.write(self.inner.revoke_api_token(id)).await
+    }
+
+    async fn create_service_account(
+        &self,
+        req: CreateServiceAccountRequest,
+    ) -> Result<CreatedServiceAccount> {
+        self.write(self.inner.create_service_account(req)).await
+    }
+
+    async fn list_service_accounts(&self) -> Result<ServiceAccountListResponse> {
+        self.inner.list_service_accounts().await
+    }
+
+    async fn delete_service_account(&self, id: Uuid) -> Result<()> {
+        self.write(self.inner.delete_service_account(id)).await
+    }
+
+    // ── Environments ──
+
+    async fn create_environment(
+        &self,
+        req: CreateEnvironmentRequest,
+    ) -> Result<EnvironmentResponse> {
+        self.write(self.inner.create_environment(req)).await
+    }
+
+    async fn list_environments(&self) -> Result<EnvironmentListResponse> {
+        self.read(Key::Environments, self.inner.list_environments())
+            .await
+    }
+
+    async fn update_environment(
+        &self,
+        id: Uuid,
+        req: UpdateEnvironmentRequest,
+    ) -> Result<EnvironmentResponse> {
+        self.write(self.inner.update_environment(id, req)).await
+    }
+
+    async fn delete_environment(&self, id: Uuid) -> Result<()> {
+        self.write(self.inner.delete_environment(id)).await
+    }
+
+    // ── Instances ──
+
+    async fn provision_instance(
+        &self,
+        env_id: Uuid,
+        req: InstanceProvisionRequest,
+    ) -> Result<InstanceProvisionResponse> {
+        self.write(self.inner.provision_instance(env_id, req)).await
+    }
+
+    async fn provision_worker(
+        &self,
+        env_id: Uuid,
+        req: WorkerProvisionRequest,
+    ) -> Result<InstanceProvisionResponse> {
+        self.write(self.inner.provision_worker(env_id, req)).await
+    }
+
+    async fn deprovision_instance(
+        &self,
+        env_id: Uuid,
+        instance_id: Uuid,
+        req: Option<InstanceDeprovisionRequest>,
+    ) -> Result<()> {
+        self.write(self.inner.deprovision_instance(env_id, instance_id, req))
+            .await
+    }
+
+    async fn restore_instance(&self, env_id: Uuid, instance_id: Uuid) -> Result<InstanceListEntry> {
+        self.write(self.inner.restore_instance(env_id, instance_id))
+            .await
+    }
+
+    async fn get_instance(
+        &self,
+        env_id: Uuid,
+        instance_id: Uuid,
+        include_service_targets: bool,
+        include_proxied_ports: bool,
+    ) -> Result<InstanceDetailResponse> {
+        self.inner
+            .get_instance(
+                env_id,
+                instance_id,
+                include_service_targets,
+                include_proxied_ports,
+            )
+            .await
+    }
+
+    async fn list_instances(&self, env_id: Uuid) -> Result<InstanceListResponse> {
+        self.read(Key::Instances(env_id), self.inner.list_instances(env_id))
+            .await
+    }
+
+    async fn find_instances_by_name(
+        &self,
+        env_id: Uuid,
+        name: &str,
+    ) -> Result<InstanceListResponse> {
+        self.inner.find_instances_by_name(env_id, name).await
+    }
+
+    async fn get_instance_logs(&self, env_id: Uuid, instance_id: Uuid) -> Result<Vec<LogMessage>> {
+        self.inner.get_instance_logs(env_id, instance_id).await
+    }
+
+    async fn search_instance_logs(
+        &self,
+        env_id: Uuid,
+        instance_id: Uuid,
+        search: &LogSearch,
+    ) -> Result<Vec<LogMessage>> {
+        self.inner
+            .search_instance_logs(env_id, instance_id, search)
+            .await
+    }
+
+    async fn stream_instance_logs(&self, env_id: Uuid, instance_id: Uuid) -> Result<LogStream> {
+        self.inner.stream_instance_logs(env_id, instance_id).await
+    }
+
+    async fn create_tcp_proxy(
+        &self,
+        env_id: Uuid,
+        instance_id: Uuid,
+        req: CreateInstanceTCPProxyRequest,
+    ) -> Result<CreateInstanceTCPProxyResponse> {
+        self.write(self.inner.create_tcp_proxy(env_id, instance_id, req))
+            .await
+    }
+
+    // ── Networks ──
+
+    async fn create_network(
+        &self,
+        env_id: Uuid,
+        req: CreateInternalNetworkRequest,
+    ) -> Result<NetworkResponse> {
+        self.write(self.inner.create_network(env_id, req)).await
+    }
+
+    async fn delete_network(&self, env_id: Uuid, network_id: Uuid) -> Result<()> {
+        self.write(self.inner.delete_network(env_id, network_id))
+            .await
+    }
+
+    async fn list_networks(
+        &self,
+        env_id: Uuid,
+        include_instance_count: bool,
+    ) -> Result<NetworkListResponse> {
+        self.read(
+            Key::Networks(env_id, include_instance_count),
+            self.inner.list_networks(env_id, include_instance_count),
+        )
+        .await
+    }
+
+    async fn get_network(&self, env_id: Uuid, network_id: Uuid) -> Result<NetworkResponse> {
+        self.inner.get_network(env_id, network_id).await
+    }
+
+    async fn attach_instance_network(
+        &self,
+        env_id: Uuid,
+        instance_id: Uuid,
+        req: InstanceNetworkConfig,
+    ) -> Result<()> {
+        self.write(self.inner.attach_instance_network(env_id, instance_id, req))
+            .await
+    }
+
+    async fn detach_instance_network(&self, env_id: Uuid, instance_id: Uuid) -> Result<()> {
+        self.write(self.inner.detach_instance_network(env_id, instance_id))
+            .await
+    }
+
+    async fn adopt_instance(
+        &self,
+        env_id: Uuid,
+        instance_id: Uuid,
+        req: AdoptInstanceRequest,
+    ) -> Result<DeploymentInfo> {
+        self.write(self.inner.adopt_instance(env_id, instance_id, req))
+            .await
+    }
+
+    async fn list_ip_reservations(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+    ) -> Result<IpReservationListResponse> {
+        self.inner.list_ip_reservations(env_id, network_id).await
+    }
+
+    async fn create_ip_reservation(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        req: CreateIpReservationRequest,
+    ) -> Result<IpReservation> {
+        self.write(self.inner.create_ip_reservation(env_id, network_id, req))
+            .await
+    }
+
+    async fn list_network_policies(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+    ) -> Result<NetworkPolicyListResponse> {
+        self.inner.list_network_policies(env_id, network_id).await
+    }
+
+    async fn create_network_policy(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        req: CreateNetworkPolicyRequest,
+    ) -> Result<NetworkPolicyRule> {
+        self.write(self.inner.create_network_policy(env_id, network_id, req))
+            .await
+    }
+
+    async fn delete_network_policy(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        rule_id: Uuid,
+    ) -> Result<()> {
+        self.write(
+            self.inner
+                .delete_network_policy(env_id, network_id, rule_id),
+        )
+        .await
+    }
+
+    async fn list_network_peerings(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+    ) -> Result<NetworkPeeringListResponse> {
+        self.inner.list_network_peerings(env_id, network_id).await
+    }
+
+    async fn create_network_peering(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        req: CreateNetworkPeeringRequest,
+    ) -> Result<NetworkPeering> {
+        self.write(self.inner.create_network_peering(env_id, network_id, req))
+            .await
+    }
+
+    async fn delete_network_peering(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        peering_id: Uuid,
+    ) -> Result<()> {
+        self.write(
+            self.inner
+                .delete_network_peering(env_id, network_id, peering_id),
+        )
+        .await
+    }
+
+    async fn list_wireguard_peers(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+    ) -> Result<WireGuardPeerListResponse> {
+        self.inner.list_wireguard_peers(env_id, network_id).await
+    }
+
+    async fn create_wireguard_peer(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        req: CreateWireGuardPeerRequest,
+    ) -> Result<WireGuardPeerConfig> {
+        self.write(self.inner.create_wireguard_peer(env_id, network_id, req))
+            .await
+    }
+
+    async fn delete_wireguard_peer(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        peer_id: Uuid,
+    ) -> Result<()> {
+        self.write(
+            self.inner
+                .delete_wireguard_peer(env_id, network_id, peer_id),
+        )
+        .await
+    }
+
+    // ── Services ──
+
+    async fn provision_service(
+        &self,
+        env_id: Uuid,
+        req: ServiceProvisionRequest,
+    ) -> Result<ServiceProvisionResponse> {
+        self.write(self.inner.provision_service(env_id, req)).await
+    }
+
+    async fn list_services(&self, env_id: Uuid) -> Result<ServiceListResponse> {
+        self.read(Key::Services(env_id), self.inner.list_services(env_id))
+            .await
+    }
+
+    async fn get_service(&self, env_id: Uuid, service_id: Uuid) -> Result<ServiceDetailResponse> {
+        self.inner.get_service(env_id, service_id).await
+    }
+
+    async fn get_service_metrics(
+        &self,
+        env_id: Uuid,
+        service_id: Uuid,
+        window: &str,
+    ) -> Result<ServiceMetricsResponse> {
+        self.inner
+            .get_service_metrics(env_id, service_id, window)
+            .await
+    }
+
+    async fn update_service(
+        &self,
+        env_id: Uuid,
+        service_id: Uuid,
+        req: HTTPServiceConfig,
+    ) -> Result<()> {
+        self.write(self.inner.update_service(env_id, service_id, req))
+            .await
+    }
+
+    async fn delete_service(&self, env_id: Uuid, service_id: Uuid) -> Result<()> {
+        self.write(self.inner.delete_service(env_id, service_id))
+            .await
+    }
+
+    async fn create_service_target(
+        &self,
+        env_id: Uuid,
+        service_id: Uuid,
+        req: ServiceInstanceTarget,
+    ) -> Result<CreateTargetResponse> {
+        self.write(self.inner.create_service_target(env_id, service_id, req))
+            .await
+    }
+
+    async fn delete_service_target(
+        &self,
+        env_id: Uuid,
+        service_id: Uuid,
+        target_id: Uuid,
+    ) -> Result<()> {
+        self.write(
+            self.inner
+                .delete_service_target(env_id, service_id, target_id),
+        )
+        .await
+    }
+
+    // ── Regions ──
+
+    async fn list_regions(&self) -> Result<RegionListResponse> {
+        self.inner.list_regions().await
+    }
+
+    // ── Events ──
+
+    async fn list_events(
+        &self,
+        since: DateTime<Utc>,
+        cursor: Option<&str>,
+    ) -> Result<EventListResponse> {
+        self.inner.list_events(since, cursor).await
+    }
+
+    // ── Service Hosts ──
+
+    async fn claim_host(&self, req: ClaimHostRequest) -> Result<HostResponse> {
+        self.write(self.inner.claim_host(req)).await
+    }
+
+    async fn list_hosts(&self) -> Result<Vec<HostResponse>> {
+        self.inner.list_hosts().await
+    }
+
+    async fn delete_host(&self, id: Uuid) -> Result<()> {
+        self.write(self.inner.delete_host(id)).await
+    }
+
+    async fn request_host_cert(&self, id: Uuid) -> Result<HostResponse> {
+        self.write(self.inner.request_host_cert(id)).await
+    }
+
+    async fn get_hosts_dns_config(&self) -> Result<DnsConfigResponse> {
+        self.inner.get_hosts_dns_config().await
+    }
+
+    async fn get_host_certificate(&self, id: Uuid) -> Result<HostCertificate> {
+        self.inner.get_host_certificate(id).await
+    }
+
+    async fn start_dns_challenge(&self, id: Uuid) -> Result<DnsChallenge> {
+        self.write(self.inner.start_dns_challenge(id)).await
+    }
+
+    async fn get_dns_challenge(&self, id: Uuid) -> Result<DnsChallenge> {
+        self.inner.get_dns_challenge(id).await
+    }
+
+    async fn complete_dns_challenge(&self, id: Uuid) -> Result<HostResponse> {
+        self.write(self.inner.complete_dns_challenge(id)).await
+    }
+
+    async fn link_host_to_service(&self, id: Uuid, service_id: Uuid) -> Result<HostResponse> {
+        self.write(self.inner.link_host_to_service(id, service_id))
+            .await
+    }
+
+    async fn unlink_host_from_service(&self, id: Uuid, service_id: Uuid) -> Result<HostResponse> {
+        self.write(self.inner.unlink_host_from_service(id, service_id))
+            .await
+    }
+
+    async fn list_host_records(&self, id: Uuid) -> Result<Vec<DnsRecord>> {
+        self.inner.list_host_records(id).await
+    }
+
+    async fn create_host_record(&self, id: Uuid, req: CreateDnsRecordRequest) -> Result<DnsRecord> {
+        self.write(self.inner.create_host_record(id, req)).await
+    }
+
+    async fn delete_host_record(&self, id: Uuid, record_id: Uuid) -> Result<()> {
+        self.write(self.inner.delete_host_record(id, record_id))
+            .await
+    }
+
+    // ── Deployments ──
+
+    async fn create_deployment(
+        &self,
+        env_id: Uuid,
+        req: CreateDeploymentRequest,
+    ) -> Result<CreateDeploymentResponse> {
+        self.write(self.inner.create_deployment(env_id, req)).await
+    }
+
+    async fn list_deployments(&self, env_id: Uuid) -> Result<DeploymentListResponse> {
+        self.read(
+            Key::Deployments(env_id),
+            self.inner.list_deployments(env_id),
+        )
+        .await
+    }
+
+    async fn get_deployment(
+        &self,
+        env_id: Uuid,
+        deployment_id: Uuid,
+    ) -> Result<DeploymentDetailResponse> {
+        self.inner.get_deployment(env_id, deployment_id).await
+    }
+
+    async fn update_deployment(
+        &self,
+        env_id: Uuid,
+        deployment_id: Uuid,
+        req: UpdateDeploymentRequest,
+    ) -> Result<()> {
+        self.write(self.inner.update_deployment(env_id, deployment_id, req))
+            .await
+    }
+
+    async fn delete_deployment(&self, env_id: Uuid, deployment_id: Uuid) -> Result<()> {
+        self.write(self.inner.delete_deployment(env_id, deployment_id))
+            .await
+    }
+
+    async fn set_managed_by(
+        &self,
+        env_id: Uuid,
+        kind: ManagedKind,
+        id: Uuid,
+        req: SetManagedByRequest,
+    ) -> Result<()> {
+        self.write(self.inner.set_managed_by(env_id, kind, id, req))
+            .await
+    }
+
+    // ── Container Registries ──
+
+    async fn create_registry(
+        &self,
+        req: CreateRegistryRequest,
+        validate: bool,
+    ) -> Result<RegistryResponse> {
+        self.write(self.inner.create_registry(req, validate)).await
+    }
+
+    async fn list_registries(&self) -> Result<RegistryListResponse> {
+        self.inner.list_registries().await
+    }
+
+    async fn find_registries_by_hostname(&self, hostname: &str) -> Result<RegistryListResponse> {
+        self.inner.find_registries_by_hostname(hostname).await
+    }
+
+    async fn update_registry(
+        &self,
+        id: Uuid,
+        req: UpdateRegistryRequest,
+        validate: bool,
+    ) -> Result<RegistryResponse> {
+        self.write(self.inner.update_registry(id, req, validate))
+            .await
+    }
+
+    async fn delete_registry(&self, id: Uuid) -> Result<()> {
+        self.write(self.inner.delete_registry(id)).await
+    }
+
+    async fn test_registry(&self, id: Uuid) -> Result<TestRegistryResponse> {
+        self.inner.test_registry(id).await
+    }
+
+    async fn get_scoped_token(
+        &self,
+        id: Uuid,
+        repository: &str,
+        push: bool,
+    ) -> Result<ScopedTokenResponse> {
+        self.inner.get_scoped_token(id, repository, push).await
+    }
+
+    async fn list_hosted_repositories(&self) -> Result<HostedRepositoryListResponse> {
+        self.inner.list_hosted_repositories().await
+    }
+
+    async fn list_hosted_images(&self, repository: &str) -> Result<HostedImageListResponse> {
+        self.inner.list_hosted_images(repository).await
+    }
+
+    async fn delete_hosted_image(&self, repository: &str, tag: &str) -> Result<()> {
+        self.write(self.inner.delete_hosted_image(repository, tag))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ApiError;
+    use crate::test_support::MockApiClient;
+
+    fn networks() -> Result<NetworkListResponse> {
+        Ok(NetworkListResponse { networks: vec![] })
+    }
+
+    // The mock's list slots answer once; a second backend call without
+    // re-arming them panics, so a hit is proven by not re-arming.
+
+    #[tokio::test]
+    async fn repeated_lists_hit_the_backend_once() {
+        let mock = MockApiClient::logged_in().with_list_networks(networks());
+        let client = CachingClient::new(&mock, Duration::from_secs(60));
+        let env = Uuid::new_v4();
+
+        client.list_networks(env, false).await.unwrap();
+        client.list_networks(env, false).await.unwrap();
+        assert_eq!(mock.calls.lock().unwrap().list_networks_calls.len(), 1);
+
+        // A different query is a different entry.
+        mock.list_networks_response.set(networks());
+        client.list_networks(env, true).await.unwrap();
+        assert_eq!(mock.calls.lock().unwrap().list_networks_calls.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn a_change_drops_the_cache() {
+        let mock = MockApiClient::logged_in()
+            .with_list_networks(networks())
+            .push_delete_network(Ok(()));
+        let client = CachingClient::new(&mock, Duration::from_secs(60));
+        let env = Uuid::new_v4();
+
+        client.list_networks(env, false).await.unwrap();
+        client.delete_network(env, Uuid::new_v4()).await.unwrap();
+        mock.list_networks_response.set(networks());
+        client.list_networks(env, false).await.unwrap();
+        assert_eq!(mock.calls.lock().unwrap().list_networks_calls.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn stale_entries_and_errors_are_refetched() {
+        let mock = MockApiClient::logged_in().with_list_networks(networks());
+        let client = CachingClient::new(&mock, Duration::ZERO);
+        let env = Uuid::new_v4();
+        client.list_networks(env, false).await.unwrap();
+        std::thread::sleep(Duration::from_millis(1));
+        mock.list_networks_response.set(networks());
+        client.list_networks(env, false).await.unwrap();
+        assert_eq!(mock.calls.lock().unwrap().list_networks_calls.len(), 2);
+
+        let mock = MockApiClient::logged_in().with_list_networks(Err(ApiError::not_logged_in()));
+        let client = CachingClient::new(&mock, Duration::from_secs(60));
+        assert!(client.list_networks(env, false).await.is_err());
+        mock.list_networks_response.set(networks());
+        assert!(client.list_networks(env, false).await.is_ok());
+    }
+}
@@ -1,17 +1,26 @@
 use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
 use futures_util::stream::BoxStream;
 use uuid::Uuid;
 
 use crate::auth::{AuthSession, AuthStore, LoginResponse};
 use crate::error::{ApiError, Result, extract_error_reason};
 use crate::models::*;
+use crate::session::Tape;
+use crate::trace::{TRACEPARENT_HEADER, TraceContext};
+use crate::version::{API_VERSION_HEADER, ApiVersion, mismatch_warning};
 
 pub const DEFAULT_API_HOST: &str = "https://api.unisrv.io";
 pub const API_HOST_ENV: &str = "UNISRV_API_HOST";
+/// An API token (from `unisrv auth create-token`, or a service account's
+/// from `unisrv auth service-account create`) to authenticate with
+/// instead of the login session, for CI where nobody can log in.
+pub const TOKEN_ENV: &str = "UNISRV_TOKEN";
 
 /// A live stream of log frames. Each item is one parsed [`LogMessage`], or an
-/// error if a frame failed to parse or the transport broke. The stream ends
-/// when the server closes the connection (e.g. the instance stopped).
+/// error if a frame failed to parse, the transport broke, or the connection
+/// went silent (see [`KeepAlive`]). The stream ends when the server closes the
+/// connection (e.g. the instance stopped).
 pub type LogStream = BoxStream<'static, Result<LogMessage>>;
 
 #[async_trait]
@@ -20,6 +29,30 @@ pub trait ApiClient: Send + Sync {
     async fn login(&self, username: &str, password: &str) -> Result<()>;
     async fn access_token(&self) -> Result<String>;
     async fn auth_session(&self) -> Result<AuthSession>;
+    /// Whether requests that would change something are refused (see
+    /// [`HttpApiClient::with_read_only`]). Code that writes elsewhere with
+    /// credentials from this client, like a registry push, checks it too.
+    fn is_read_only(&self) -> bool {
+        false
+    }
+    /// Create a long-lived API token (`POST /auth/tokens`).
+    async fn create_api_token(&self, req: CreateApiTokenRequest) -> Result<CreatedApiToken>;
+    /// The user's API tokens, without their secrets (`GET /auth/tokens`).
+    async fn list_api_tokens(&self) -> Result<ApiTokenListResponse>;
+    /// Revoke an API token (`DELETE /auth/tokens/{id}`).
+    async fn revoke_api_token(&self, id: Uuid) -> Result<()>;
+    /// Create a scoped service account and its token
+    /// (`POST /auth/service-accounts`).
+    async fn create_service_account(
+        &self,
+        req: CreateServiceAccountRequest,
+    ) -> Result<CreatedServiceAccount>;
+    /// The account's service accounts, without their secrets
+    /// (`GET /auth/service-accounts`).
+    async fn list_service_accounts(&self) -> Result<ServiceAccountListResponse>;
+    /// Delete a service account, revoking its token
+    /// (`DELETE /auth/service-accounts/{id}`).
+    async fn delete_service_account(&self, id: Uuid) -> Result<()>;
 
     // ── Environments ──
     async fn create_environment(
@@ -40,12 +73,21 @@ pub trait ApiClient: Send + Sync {
         env_id: Uuid,
         req: InstanceProvisionRequest,
     ) -> Result<InstanceProvisionResponse>;
+    /// Start a WebAssembly worker (`POST /environment/{env}/worker`).
+    async fn provision_worker(
+        &self,
+        env_id: Uuid,
+        req: WorkerProvisionRequest,
+    ) -> Result<InstanceProvisionResponse>;
     async fn deprovision_instance(
         &self,
         env_id: Uuid,
         instance_id: Uuid,
         req: Option<InstanceDeprovisionRequest>,
     ) -> Result<()>;
+    /// Bring back an instance stopped with a retention window
+    /// (`POST /environment/{env}/instance/{id}/restore`).
+    async fn restore_instance(&self, env_id: Uuid, instance_id: Uuid) -> Result<InstanceListEntry>;
     async fn get_instance(
         &self,
         env_id: Uuid,
@@ -54,7 +96,24 @@ pub trait ApiClient: Send + Sync {
         include_proxied_ports: bool,
     ) -> Result<InstanceDetailResponse>;
     async fn list_instances(&self, env_id: Uuid) -> Result<InstanceListResponse>;
+    /// List only the instances named exactly `name` (`GET …/instances?name=`).
+    /// Optional server-side filter: an older backend rejects it (see
+    /// [`ApiError::is_unsupported_endpoint`]) or ignores it and returns the
+    /// full list, so callers must still match names themselves.
+    async fn find_instances_by_name(
+        &self,
+        env_id: Uuid,
+        name: &str,
+    ) -> Result<InstanceListResponse>;
     async fn get_instance_logs(&self, env_id: Uuid, instance_id: Uuid) -> Result<Vec<LogMessage>>;
+    /// Search an instance's log history on the server
+    /// (GET /environment/{env_id}/instance/{instance_id}/logs/search).
+    async fn search_instance_logs(
+        &self,
+        env_id: Uuid,
+        instance_id: Uuid,
+        search: &LogSearch,
+    ) -> Result<Vec<LogMessage>>;
     /// Open a live log stream for an instance. The server replays the existing
     /// log history, then follows new frames until the connection closes.
     async fn stream_instance_logs(&self, env_id: Uuid, instance_id: Uuid) -> Result<LogStream>;
@@ -78,6 +137,104 @@ pub trait ApiClient: Send + Sync {
         include_instance_count: bool,
     ) -> Result<NetworkListResponse>;
     async fn get_network(&self, env_id: Uuid, network_id: Uuid) -> Result<NetworkResponse>;
+    /// Join a running instance to a network
+    /// (PUT /environment/{env_id}/instance/{instance_id}/network).
+    async fn attach_instance_network(
+        &self,
+        env_id: Uuid,
+        instance_id: Uuid,
+        req: InstanceNetworkConfig,
+    ) -> Result<()>;
+    /// Take a running instance off its network
+    /// (DELETE /environment/{env_id}/instance/{instance_id}/network).
+    async fn detach_instance_network(&self, env_id: Uuid, instance_id: Uuid) -> Result<()>;
+    /// Make a standalone instance the first replica of a new deployment
+    /// (POST /environment/{env_id}/instance/{instance_id}/adopt).
+    async fn adopt_instance(
+        &self,
+        env_id: Uuid,
+        instance_id: Uuid,
+        req: AdoptInstanceRequest,
+    ) -> Result<DeploymentInfo>;
+    /// Addresses held on a network
+    /// (GET /environment/{env_id}/network/{network_id}/reservations).
+    async fn list_ip_reservations(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+    ) -> Result<IpReservationListResponse>;
+    /// Hold an address on a network for a future instance
+    /// (POST /environment/{env_id}/network/{network_id}/reservation).
+    async fn create_ip_reservation(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        req: CreateIpReservationRequest,
+    ) -> Result<IpReservation>;
+    /// Firewall rules on a network
+    /// (GET /environment/{env_id}/network/{network_id}/policies).
+    async fn list_network_policies(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+    ) -> Result<NetworkPolicyListResponse>;
+    /// (POST /environment/{env_id}/network/{network_id}/policy)
+    async fn create_network_policy(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        req: CreateNetworkPolicyRequest,
+    ) -> Result<NetworkPolicyRule>;
+    /// (DELETE /environment/{env_id}/network/{network_id}/policy/{rule_id})
+    async fn delete_network_policy(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        rule_id: Uuid,
+    ) -> Result<()>;
+    /// Networks routed to this one
+    /// (GET /environment/{env_id}/network/{network_id}/peerings).
+    async fn list_network_peerings(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+    ) -> Result<NetworkPeeringListResponse>;
+    /// (POST /environment/{env_id}/network/{network_id}/peering)
+    async fn create_network_peering(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        req: CreateNetworkPeeringRequest,
+    ) -> Result<NetworkPeering>;
+    /// Removes the peering from both sides
+    /// (DELETE /environment/{env_id}/network/{network_id}/peering/{peering_id}).
+    async fn delete_network_peering(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        peering_id: Uuid,
+    ) -> Result<()>;
+    /// Machines tunnelled into a network
+    /// (GET /environment/{env_id}/network/{network_id}/wireguard-peers).
+    async fn list_wireguard_peers(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+    ) -> Result<WireGuardPeerListResponse>;
+    /// (POST /environment/{env_id}/network/{network_id}/wireguard-peer)
+    async fn create_wireguard_peer(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        req: CreateWireGuardPeerRequest,
+    ) -> Result<WireGuardPeerConfig>;
+    /// (DELETE /environment/{env_id}/network/{network_id}/wireguard-peer/{peer_id})
+    async fn delete_wireguard_peer(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        peer_id: Uuid,
+    ) -> Result<()>;
 
     // ── Services ──
     async fn provision_service(
@@ -87,6 +244,13 @@ pub trait ApiClient: Send + Sync {
     ) -> Result<ServiceProvisionResponse>;
     async fn list_services(&self, env_id: Uuid) -> Result<ServiceListResponse>;
     async fn get_service(&self, env_id: Uuid, service_id: Uuid) -> Result<ServiceDetailResponse>;
+    /// Request metrics over the trailing `window` (e.g. `15m`, `1h`, `1d`).
+    async fn get_service_metrics(
+        &self,
+        env_id: Uuid,
+        service_id: Uuid,
+        window: &str,
+    ) -> Result<ServiceMetricsResponse>;
     async fn update_service(
         &self,
         env_id: Uuid,
@@ -107,16 +271,47 @@ pub trait ApiClient: Send + Sync {
         target_id: Uuid,
     ) -> Result<()>;
 
+    // ── Regions ──
+    /// Where workloads can run (`GET /regions`).
+    async fn list_regions(&self) -> Result<RegionListResponse>;
+
+    // ── Events ──
+    /// One page of the account's activity since `since`, oldest first
+    /// (`GET /events?since=&cursor=`); pass the previous page's
+    /// `next_cursor` for the next.
+    async fn list_events(
+        &self,
+        since: DateTime<Utc>,
+        cursor: Option<&str>,
+    ) -> Result<EventListResponse>;
+
     // ── Service Hosts ──
     async fn claim_host(&self, req: ClaimHostRequest) -> Result<HostResponse>;
     async fn list_hosts(&self) -> Result<Vec<HostResponse>>;
     async fn delete_host(&self, id: Uuid) -> Result<()>;
     async fn request_host_cert(&self, id: Uuid) -> Result<HostResponse>;
     async fn get_hosts_dns_config(&self) -> Result<DnsConfigResponse>;
+    /// The host's issued certificate (GET /hosts/{id}/cert); 404 until one
+    /// is issued.
+    async fn get_host_certificate(&self, id: Uuid) -> Result<HostCertificate>;
+    /// Start validating the host's certificate over DNS instead of HTTP
+    /// (POST /hosts/{id}/cert/dns-challenge).
+    async fn start_dns_challenge(&self, id: Uuid) -> Result<DnsChallenge>;
+    /// Where the DNS-01 validation stands (GET /hosts/{id}/cert/dns-challenge).
+    async fn get_dns_challenge(&self, id: Uuid) -> Result<DnsChallenge>;
+    /// Issue the certificate once the challenge is valid
+    /// (POST /hosts/{id}/cert/dns-challenge/complete).
+    async fn complete_dns_challenge(&self, id: Uuid) -> Result<HostResponse>;
     /// Link a claimed host to a service (PUT /hosts/{id}/service/{service_id}).
     async fn link_host_to_service(&self, id: Uuid, service_id: Uuid) -> Result<HostResponse>;
     /// Unlink a host from a service (DELETE /hosts/{id}/service/{service_id}).
     async fn unlink_host_from_service(&self, id: Uuid, service_id: Uuid) -> Result<HostResponse>;
+    /// Records published under a claimed host (GET /hosts/{id}/records).
+    async fn list_host_records(&self, id: Uuid) -> Result<Vec<DnsRecord>>;
+    /// Publish a record under a claimed host (POST /hosts/{id}/records).
+    async fn create_host_record(&self, id: Uuid, req: CreateDnsRecordRequest) -> Result<DnsRecord>;
+    /// Remove a record (DELETE /hosts/{id}/records/{record_id}).
+    async fn delete_host_record(&self, id: Uuid, record_id: Uuid) -> Result<()>;
 
     // ── Deployments ──
     async fn create_deployment(
@@ -138,6 +333,17 @@ pub trait ApiClient: Send + Sync {
     ) -> Result<()>;
     async fn delete_deployment(&self, env_id: Uuid, deployment_id: Uuid) -> Result<()>;
 
+    // ── Ownership ──
+    /// Set which project's `up` owns a network, service or deployment
+    /// (`PUT /environment/{env_id}/{kind}/{id}/managed-by`).
+    async fn set_managed_by(
+        &self,
+        env_id: Uuid,
+        kind: ManagedKind,
+        id: Uuid,
+        req: SetManagedByRequest,
+    ) -> Result<()>;
+
     // ── Container Registries ──
     async fn create_registry(
         &self,
@@ -145,6 +351,9 @@ pub trait ApiClient: Send + Sync {
         validate: bool,
     ) -> Result<RegistryResponse>;
     async fn list_registries(&self) -> Result<RegistryListResponse>;
+    /// List only the registries for `hostname` (`GET /registries?hostname=`).
+    /// Same optional-filter contract as [`Self::find_instances_by_name`].
+    async fn find_registries_by_hostname(&self, hostname: &str) -> Result<RegistryListResponse>;
     async fn update_registry(
         &self,
         id: Uuid,
@@ -153,6 +362,24 @@ pub trait ApiClient: Send + Sync {
     ) -> Result<RegistryResponse>;
     async fn delete_registry(&self, id: Uuid) -> Result<()>;
     async fn test_registry(&self, id: Uuid) -> Result<TestRegistryResponse>;
+    /// A token for `repository` on registry `id`, for talking to the registry
+    /// itself: pull-only, or pull and push with `push`
+    /// (`GET /registries/{id}/token?repository=&push=true`).
+    async fn get_scoped_token(
+        &self,
+        id: Uuid,
+        repository: &str,
+        push: bool,
+    ) -> Result<ScopedTokenResponse>;
+
+    // ── Hosted Registry ──
+    /// The repositories in the platform's own registry, with their storage
+    /// (`GET /registry/repositories`).
+    async fn list_hosted_repositories(&self) -> Result<HostedRepositoryListResponse>;
+    /// The tags in `repository` (`GET /registry/images?repository=`).
+    async fn list_hosted_images(&self, repository: &str) -> Result<HostedImageListResponse>;
+    /// Delete `repository:tag` (`DELETE /registry/images?repository=&tag=`).
+    async fn delete_hosted_image(&self, repository: &str, tag: &str) -> Result<()>;
 }
 
 pub struct HttpApiClient {
@@ -160,31 +387,141 @@ pub struct HttpApiClient {
     base_url: String,
     auth_store: AuthStore,
     session: tokio::sync::RwLock<Option<AuthSession>>,
+    /// An API token, used as-is in place of the session.
+    token: Option<String>,
+    tape: Option<Tape>,
+    trace: Option<TraceContext>,
+    read_only: bool,
+    api_version: ApiVersion,
+    /// What the server said it serves, from the first response that said.
+    server_api_version: std::sync::OnceLock<ApiVersion>,
 }
 
 impl HttpApiClient {
     pub fn new(base_url: impl Into<String>) -> Self {
-        let auth_store = AuthStore::new();
+        let base_url = base_url.into();
+        let auth_store = AuthStore::new(&base_url);
         let session = auth_store.load();
 
         HttpApiClient {
             client: reqwest::Client::new(),
-            base_url: base_url.into(),
+            base_url,
             auth_store,
             session: tokio::sync::RwLock::new(session),
+            token: None,
+            tape: None,
+            trace: None,
+            read_only: false,
+            api_version: ApiVersion::CURRENT,
+            server_api_version: std::sync::OnceLock::new(),
         }
     }
 
+    /// A client for `base_url` starting from `session`, with an in-memory auth
+    /// store — for driving the real HTTP paths against a
+    /// [`MockServer`](crate::test_support::server::MockServer).
+    #[cfg(any(test, feature = "test-support"))]
+    pub fn with_session(base_url: impl Into<String>, session: Option<AuthSession>) -> Self {
+        HttpApiClient {
+            client: reqwest::Client::new(),
+            base_url: base_url.into(),
+            auth_store: AuthStore::in_memory(),
+            session: tokio::sync::RwLock::new(session),
+            token: None,
+            tape: None,
+            trace: None,
+            read_only: false,
+            api_version: ApiVersion::CURRENT,
+            server_api_version: std::sync::OnceLock::new(),
+        }
+    }
+
+    /// A client for `base_url` authenticating with the API token `token`. No
+    /// session is loaded or saved, so a stray login on the machine is
+    /// neither used nor overwritten.
+    pub fn with_token(base_url: impl Into<String>, token: impl Into<String>) -> Self {
+        HttpApiClient {
+            client: reqwest::Client::new(),
+            base_url: base_url.into(),
+            auth_store: AuthStore::in_memory(),
+            session: tokio::sync::RwLock::new(None),
+            token: Some(token.into()),
+            tape: None,
+            trace: None,
+            read_only: false,
+            api_version: ApiVersion::CURRENT,
+            server_api_version: std::sync::OnceLock::new(),
+        }
+    }
+
+    /// A client for `$UNISRV_API_HOST`, authenticating with `$UNISRV_TOKEN`
+    /// when it's set and the login session otherwise.
     pub fn from_env() -> Self {
         let base_url = std::env::var(API_HOST_ENV).unwrap_or_else(|_| DEFAULT_API_HOST.to_string());
-        Self::new(base_url)
+        match std::env::var(TOKEN_ENV).ok().filter(|t| !t.is_empty()) {
+            Some(token) => Self::with_token(base_url, token),
+            None => Self::new(base_url),
+        }
+    }
+
+    /// Record traffic to, or replay it from, a session file (see
+    /// [`crate::session`]).
+    pub fn with_tape(mut self, tape: Tape) -> Self {
+        self.tape = Some(tape);
+        self
+    }
+
+    /// Send `trace` as the `traceparent` of every request, WebSocket upgrades
+    /// and token refreshes included (see [`crate::trace`]).
+    pub fn with_trace(mut self, trace: TraceContext) -> Self {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(
+            TRACEPARENT_HEADER,
+            reqwest::header::HeaderValue::from_str(&trace.to_string())
+                .expect("a traceparent is a valid header value"),
+        );
+        self.client = reqwest::Client::builder()
+            .default_headers(headers)
+            .build()
+            .expect("failed to build the HTTP client");
+        self.trace = Some(trace);
+        self
+    }
+
+    /// Refuse every request but GET and HEAD before it's sent, so nothing can
+    /// be changed. Logging in and refreshing the session still work.
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Ask for API `version` instead of [`ApiVersion::CURRENT`] (see
+    /// [`crate::version`]).
+    pub fn with_api_version(mut self, version: ApiVersion) -> Self {
+        self.api_version = version;
+        self
+    }
+
+    /// The API version the server said it serves, once a response has said.
+    pub fn server_api_version(&self) -> Option<ApiVersion> {
+        self.server_api_version.get().copied()
+    }
+
+    /// The trace id requests carry, if tracing is on.
+    pub fn trace_id(&self) -> Option<String> {
+        self.trace.as_ref().map(TraceContext::trace_id)
+    }
+
+    fn replaying(&self) -> bool {
+        self.tape.as_ref().is_some_and(Tape::is_replay)
     }
 
     pub(crate) async fn set_session(
         &self,
+        username: &str,
         session: AuthSession,
     ) -> std::result::Result<(), anyhow::Error> {
-        self.auth_store.save(&session)?;
+        self.auth_store.add(username, &session)?;
         *self.session.write().await = Some(session);
         Ok(())
     }
@@ -199,6 +536,9 @@ impl HttpApiClient {
     }
 
     async fn ensure_access_token(&self) -> Result<String> {
+        if let Some(token) = &self.token {
+            return Ok(token.clone());
+        }
         // Fast path: token is still valid.
         {
             let guard = self.session.read().await;
@@ -218,12 +558,11 @@ impl HttpApiClient {
 
         // Slow path: acquire write lock and re-check before refreshing.
         let mut guard = self.session.write().await;
-        let session = guard.as_mut().ok_or_else(|| ApiError::not_logged_in())?;
+        let session = guard.as_mut().ok_or_else(ApiError::not_logged_in)?;
 
-        if session.access_token_expired() {
-            session.refresh(&self.client, &self.base_url).await?;
-            self.auth_store.save(session).map_err(ApiError::Other)?;
-        }
+        session
+            .refresh(&self.client, &self.base_url, &self.auth_store)
+            .await?;
 
         Ok(session.access_token().to_string())
     }
@@ -241,11 +580,59 @@ impl HttpApiClient {
     }
 
     async fn send(&self, builder: reqwest::RequestBuilder) -> Result<reqwest::Response> {
-        let token = self.ensure_access_token().await?;
-        let resp = builder.bearer_auth(&token).send().await?;
+        // A replayed session needs no login: nothing reaches the server.
+        let builder = if self.replaying() {
+            builder
+        } else {
+            builder.bearer_auth(self.ensure_access_token().await?)
+        };
+        let request = builder
+            .header(API_VERSION_HEADER, self.api_version.to_string())
+            .build()?;
+        if self.read_only
+            && !matches!(
+                *request.method(),
+                reqwest::Method::GET | reqwest::Method::HEAD
+            )
+        {
+            return Err(ApiError::ReadOnly {
+                method: request.method().to_string(),
+                path: request.url().path().to_string(),
+            });
+        }
+        let resp = self.execute(request).await?;
+        self.negotiate(&resp);
         Self::check_response(resp).await
     }
 
+    /// Compare the server's API version with ours, on the first response
+    /// that carries one.
+    fn negotiate(&self, resp: &reqwest::Response) {
+        if self.server_api_version.get().is_some() {
+            return;
+        }
+        let Some(server) = resp
+            .headers()
+            .get(API_VERSION_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<ApiVersion>().ok())
+        else {
+            return;
+        };
+        if self.server_api_version.set(server).is_ok()
+            && let Some(warning) = mismatch_warning(self.api_version, server)
+        {
+            tracing::warn!("{warning}");
+        }
+    }
+
+    async fn execute(&self, request: reqwest::Request) -> Result<reqwest::Response> {
+        match &self.tape {
+            Some(tape) => tape.execute(&self.client, &self.base_url, request).await,
+            None => Ok(self.client.execute(request).await?),
+        }
+    }
+
     fn url(&self, path: &str) -> String {
         format!("{}{path}", self.base_url)
     }
@@ -258,6 +645,19 @@ impl HttpApiClient {
             .await?)
     }
 
+    /// GET with URL-encoded query parameters.
+    async fn get_with_query<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T> {
+        Ok(self
+            .send(self.client.get(self.url(path)).query(query))
+            .await?
+            .json()
+            .await?)
+    }
+
     async fn post_for_json<T: serde::de::DeserializeOwned>(&self, path: &str) -> Result<T> {
         Ok(self
             .send(self.client.post(self.url(path)))
@@ -319,6 +719,12 @@ impl HttpApiClient {
         Ok(())
     }
 
+    async fn delete_with_query(&self, path: &str, query: &[(&str, &str)]) -> Result<()> {
+        self.send(self.client.delete(self.url(path)).query(query))
+            .await?;
+        Ok(())
+    }
+
     async fn delete_with_body<B: serde::Serialize>(&self, path: &str, body: &B) -> Result<()> {
         self.send(self.client.delete(self.url(path)).json(body))
             .await?;
@@ -342,18 +748,60 @@ impl ApiClient for HttpApiClient {
         let login_resp: LoginResponse = resp.json().await?;
 
         let session = AuthSession::from_login_response(login_resp);
-        self.set_session(session).await.map_err(ApiError::Other)?;
+        self.set_session(username, session)
+            .await
+            .map_err(ApiError::Other)?;
         Ok(())
     }
 
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
     async fn access_token(&self) -> Result<String> {
+        if self.replaying() {
+            return Ok("replay".to_string());
+        }
         self.ensure_access_token().await
     }
 
     async fn auth_session(&self) -> Result<AuthSession> {
+        if self.token.is_some() {
+            return Err(ApiError::AuthRequired(format!(
+                "{TOKEN_ENV} is set; an API token has no login session"
+            )));
+        }
         self.ensure_access_token().await?;
         let guard = self.session.read().await;
-        guard.clone().ok_or_else(|| ApiError::not_logged_in())
+        guard.clone().ok_or_else(ApiError::not_logged_in)
+    }
+
+    async fn create_api_token(&self, req: CreateApiTokenRequest) -> Result<CreatedApiToken> {
+        self.post("/auth/tokens", &req).await
+    }
+
+    async fn list_api_tokens(&self) -> Result<ApiTokenListResponse> {
+        self.get("/auth/tokens").await
+    }
+
+    async fn revoke_api_token(&self, id: Uuid) -> Result<()> {
+        self.delete_req(&format!("/auth/tokens/{id}")).await
+    }
+
+    async fn create_service_account(
+        &self,
+        req: CreateServiceAccountRequest,
+    ) -> Result<CreatedServiceAccount> {
+        self.post("/auth/service-accounts", &req).await
+    }
+
+    async fn list_service_accounts(&self) -> Result<ServiceAccountListResponse> {
+        self.get("/auth/service-accounts").await
+    }
+
+    async fn delete_service_account(&self, id: Uuid) -> Result<()> {
+        self.delete_req(&format!("/auth/service-accounts/{id}"))
+            .await
     }
 
     // ── Environments ──
@@ -392,6 +840,15 @@ impl ApiClient for HttpApiClient {
             .await
     }
 
+    async fn provision_worker(
+        &self,
+        env_id: Uuid,
+        req: WorkerProvisionRequest,
+    ) -> Result<InstanceProvisionResponse> {
+        self.post(&format!("/environment/{env_id}/worker"), &req)
+            .await
+    }
+
     async fn deprovision_instance(
         &self,
         env_id: Uuid,
@@ -405,6 +862,13 @@ impl ApiClient for HttpApiClient {
         }
     }
 
+    async fn restore_instance(&self, env_id: Uuid, instance_id: Uuid) -> Result<InstanceListEntry> {
+        self.post_for_json(&format!(
+            "/environment/{env_id}/instance/{instance_id}/restore"
+        ))
+        .await
+    }
+
     async fn get_instance(
         &self,
         env_id: Uuid,
@@ -431,17 +895,72 @@ impl ApiClient for HttpApiClient {
         self.get(&format!("/environment/{env_id}/instances")).await
     }
 
-    async fn get_instance_logs(&self, env_id: Uuid, instance_id: Uuid) -> Result<Vec<LogMessage>> {
-        self.get(&format!(
-            "/environment/{env_id}/instance/{instance_id}/logs"
-        ))
+    async fn find_instances_by_name(
+        &self,
+        env_id: Uuid,
+        name: &str,
+    ) -> Result<InstanceListResponse> {
+        self.get_with_query(
+            &format!("/environment/{env_id}/instances"),
+            &[("name", name)],
+        )
         .await
     }
 
+    async fn get_instance_logs(&self, env_id: Uuid, instance_id: Uuid) -> Result<Vec<LogMessage>> {
+        let frames: Vec<serde_json::Value> = self
+            .get(&format!(
+                "/environment/{env_id}/instance/{instance_id}/logs"
+            ))
+            .await?;
+        Ok(frames.into_iter().filter_map(log_frame).collect())
+    }
+
+    async fn search_instance_logs(
+        &self,
+        env_id: Uuid,
+        instance_id: Uuid,
+        search: &LogSearch,
+    ) -> Result<Vec<LogMessage>> {
+        let (from, to) = (
+            search.from_ms.map(|ms| ms.to_string()),
+            search.to_ms.map(|ms| ms.to_string()),
+        );
+        let query: Vec<(&str, &str)> = [
+            ("from", from.as_deref()),
+            ("to", to.as_deref()),
+            ("q", search.grep.as_deref()),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some((key, value?)))
+        .collect();
+        let frames: Vec<serde_json::Value> = self
+            .get_with_query(
+                &format!("/environment/{env_id}/instance/{instance_id}/logs/search"),
+                &query,
+            )
+            .await?;
+        Ok(frames.into_iter().filter_map(log_frame).collect())
+    }
+
+    #[cfg(not(feature = "websocket"))]
+    async fn stream_instance_logs(&self, _env_id: Uuid, _instance_id: Uuid) -> Result<LogStream> {
+        Err(ApiError::Other(anyhow::anyhow!(
+            "this build can't stream logs (it leaves out WebSocket support); drop --follow to print the history"
+        )))
+    }
+
+    #[cfg(feature = "websocket")]
     async fn stream_instance_logs(&self, env_id: Uuid, instance_id: Uuid) -> Result<LogStream> {
         use futures_util::StreamExt;
         use reqwest_websocket::RequestBuilderExt;
 
+        if self.replaying() {
+            return Err(ApiError::Other(anyhow::anyhow!(
+                "log streams aren't part of recorded sessions"
+            )));
+        }
+
         // The upgrade request carries auth like any other call, but bypasses the
         // JSON `send`/`check_response` helpers since the response is a 101 switch.
         let token = self.ensure_access_token().await?;
@@ -461,19 +980,8 @@ impl ApiClient for HttpApiClient {
         // failure, since the WS path bypasses the JSON `check_response` helper.
         let websocket = response.into_websocket().await.map_err(map_upgrade_error)?;
 
-        // Classify each frame: text → parsed log, abnormal close → error (so a
-        // server-side failure isn't reported as a clean end), transport break →
-        // error. A normal close ends the stream cleanly.
-        let stream = websocket.filter_map(|message| async move {
-            match message {
-                Ok(frame) => classify_frame(frame),
-                Err(e) => Some(Err(ApiError::Other(anyhow::anyhow!(
-                    "log stream error: {e}"
-                )))),
-            }
-        });
-
-        Ok(stream.boxed())
+        let (pings, frames) = websocket.split();
+        Ok(keep_alive(frames, pings, KeepAlive::DEFAULT))
     }
 
     async fn create_tcp_proxy(
@@ -523,6 +1031,171 @@ impl ApiClient for HttpApiClient {
             .await
     }
 
+    async fn attach_instance_network(
+        &self,
+        env_id: Uuid,
+        instance_id: Uuid,
+        req: InstanceNetworkConfig,
+    ) -> Result<()> {
+        self.put_empty(
+            &format!("/environment/{env_id}/instance/{instance_id}/network"),
+            &req,
+        )
+        .await
+    }
+
+    async fn detach_instance_network(&self, env_id: Uuid, instance_id: Uuid) -> Result<()> {
+        self.delete_req(&format!(
+            "/environment/{env_id}/instance/{instance_id}/network"
+        ))
+        .await
+    }
+
+    async fn adopt_instance(
+        &self,
+        env_id: Uuid,
+        instance_id: Uuid,
+        req: AdoptInstanceRequest,
+    ) -> Result<DeploymentInfo> {
+        self.post(
+            &format!("/environment/{env_id}/instance/{instance_id}/adopt"),
+            &req,
+        )
+        .await
+    }
+
+    async fn list_ip_reservations(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+    ) -> Result<IpReservationListResponse> {
+        self.get(&format!(
+            "/environment/{env_id}/network/{network_id}/reservations"
+        ))
+        .await
+    }
+
+    async fn create_ip_reservation(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        req: CreateIpReservationRequest,
+    ) -> Result<IpReservation> {
+        self.post(
+            &format!("/environment/{env_id}/network/{network_id}/reservation"),
+            &req,
+        )
+        .await
+    }
+
+    async fn list_network_policies(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+    ) -> Result<NetworkPolicyListResponse> {
+        self.get(&format!(
+            "/environment/{env_id}/network/{network_id}/policies"
+        ))
+        .await
+    }
+
+    async fn create_network_policy(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        req: CreateNetworkPolicyRequest,
+    ) -> Result<NetworkPolicyRule> {
+        self.post(
+            &format!("/environment/{env_id}/network/{network_id}/policy"),
+            &req,
+        )
+        .await
+    }
+
+    async fn delete_network_policy(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        rule_id: Uuid,
+    ) -> Result<()> {
+        self.delete_req(&format!(
+            "/environment/{env_id}/network/{network_id}/policy/{rule_id}"
+        ))
+        .await
+    }
+
+    async fn list_network_peerings(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+    ) -> Result<NetworkPeeringListResponse> {
+        self.get(&format!(
+            "/environment/{env_id}/network/{network_id}/peerings"
+        ))
+        .await
+    }
+
+    async fn create_network_peering(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        req: CreateNetworkPeeringRequest,
+    ) -> Result<NetworkPeering> {
+        self.post(
+            &format!("/environment/{env_id}/network/{network_id}/peering"),
+            &req,
+        )
+        .await
+    }
+
+    async fn delete_network_peering(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        peering_id: Uuid,
+    ) -> Result<()> {
+        self.delete_req(&format!(
+            "/environment/{env_id}/network/{network_id}/peering/{peering_id}"
+        ))
+        .await
+    }
+
+    async fn list_wireguard_peers(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+    ) -> Result<WireGuardPeerListResponse> {
+        self.get(&format!(
+            "/environment/{env_id}/network/{network_id}/wireguard-peers"
+        ))
+        .await
+    }
+
+    async fn create_wireguard_peer(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        req: CreateWireGuardPeerRequest,
+    ) -> Result<WireGuardPeerConfig> {
+        self.post(
+            &format!("/environment/{env_id}/network/{network_id}/wireguard-peer"),
+            &req,
+        )
+        .await
+    }
+
+    async fn delete_wireguard_peer(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        peer_id: Uuid,
+    ) -> Result<()> {
+        self.delete_req(&format!(
+            "/environment/{env_id}/network/{network_id}/wireguard-peer/{peer_id}"
+        ))
+        .await
+    }
+
     // ── Services ──
 
     async fn provision_service(
@@ -543,6 +1216,19 @@ impl ApiClient for HttpApiClient {
             .await
     }
 
+    async fn get_service_metrics(
+        &self,
+        env_id: Uuid,
+        service_id: Uuid,
+        window: &str,
+    ) -> Result<ServiceMetricsResponse> {
+        self.get_with_query(
+            &format!("/environment/{env_id}/service/{service_id}/metrics"),
+            &[("window", window)],
+        )
+        .await
+    }
+
     async fn update_service(
         &self,
         env_id: Uuid,
@@ -583,6 +1269,27 @@ impl ApiClient for HttpApiClient {
         .await
     }
 
+    // ── Regions ──
+
+    async fn list_regions(&self) -> Result<RegionListResponse> {
+        self.get("/regions").await
+    }
+
+    // ── Events ──
+
+    async fn list_events(
+        &self,
+        since: DateTime<Utc>,
+        cursor: Option<&str>,
+    ) -> Result<EventListResponse> {
+        let since = since.to_rfc3339_opts(SecondsFormat::Secs, true);
+        let mut query = vec![("since", since.as_str())];
+        if let Some(cursor) = cursor {
+            query.push(("cursor", cursor));
+        }
+        self.get_with_query("/events", &query).await
+    }
+
     // ── Service Hosts ──
 
     async fn claim_host(&self, req: ClaimHostRequest) -> Result<HostResponse> {
@@ -605,6 +1312,24 @@ impl ApiClient for HttpApiClient {
         self.get("/hosts/dns-config").await
     }
 
+    async fn get_host_certificate(&self, id: Uuid) -> Result<HostCertificate> {
+        self.get(&format!("/hosts/{id}/cert")).await
+    }
+
+    async fn start_dns_challenge(&self, id: Uuid) -> Result<DnsChallenge> {
+        self.post_for_json(&format!("/hosts/{id}/cert/dns-challenge"))
+            .await
+    }
+
+    async fn get_dns_challenge(&self, id: Uuid) -> Result<DnsChallenge> {
+        self.get(&format!("/hosts/{id}/cert/dns-challenge")).await
+    }
+
+    async fn complete_dns_challenge(&self, id: Uuid) -> Result<HostResponse> {
+        self.post_for_json(&format!("/hosts/{id}/cert/dns-challenge/complete"))
+            .await
+    }
+
     async fn link_host_to_service(&self, id: Uuid, service_id: Uuid) -> Result<HostResponse> {
         self.put_for_json(&format!("/hosts/{id}/service/{service_id}"))
             .await
@@ -615,6 +1340,19 @@ impl ApiClient for HttpApiClient {
             .await
     }
 
+    async fn list_host_records(&self, id: Uuid) -> Result<Vec<DnsRecord>> {
+        self.get(&format!("/hosts/{id}/records")).await
+    }
+
+    async fn create_host_record(&self, id: Uuid, req: CreateDnsRecordRequest) -> Result<DnsRecord> {
+        self.post(&format!("/hosts/{id}/records"), &req).await
+    }
+
+    async fn delete_host_record(&self, id: Uuid, record_id: Uuid) -> Result<()> {
+        self.delete_req(&format!("/hosts/{id}/records/{record_id}"))
+            .await
+    }
+
     // ── Deployments ──
 
     async fn create_deployment(
@@ -658,6 +1396,22 @@ impl ApiClient for HttpApiClient {
             .await
     }
 
+    // ── Ownership ──
+
+    async fn set_managed_by(
+        &self,
+        env_id: Uuid,
+        kind: ManagedKind,
+        id: Uuid,
+        req: SetManagedByRequest,
+    ) -> Result<()> {
+        self.put_empty(
+            &format!("/environment/{env_id}/{}/{id}/managed-by", kind.as_str()),
+            &req,
+        )
+        .await
+    }
+
     // ── Container Registries ──
 
     async fn create_registry(
@@ -673,6 +1427,11 @@ impl ApiClient for HttpApiClient {
         self.get("/registries").await
     }
 
+    async fn find_registries_by_hostname(&self, hostname: &str) -> Result<RegistryListResponse> {
+        self.get_with_query("/registries", &[("hostname", hostname)])
+            .await
+    }
+
     async fn update_registry(
         &self,
         id: Uuid,
@@ -690,6 +1449,39 @@ impl ApiClient for HttpApiClient {
     async fn test_registry(&self, id: Uuid) -> Result<TestRegistryResponse> {
         self.post_for_json(&format!("/registries/{id}/test")).await
     }
+
+    async fn get_scoped_token(
+        &self,
+        id: Uuid,
+        repository: &str,
+        push: bool,
+    ) -> Result<ScopedTokenResponse> {
+        let mut query = vec![("repository", repository)];
+        if push {
+            query.push(("push", "true"));
+        }
+        self.get_with_query(&format!("/registries/{id}/token"), &query)
+            .await
+    }
+
+    // ── Hosted Registry ──
+
+    async fn list_hosted_repositories(&self) -> Result<HostedRepositoryListResponse> {
+        self.get("/registry/repositories").await
+    }
+
+    async fn list_hosted_images(&self, repository: &str) -> Result<HostedImageListResponse> {
+        self.get_with_query("/registry/images", &[("repository", repository)])
+            .await
+    }
+
+    async fn delete_hosted_image(&self, repository: &str, tag: &str) -> Result<()> {
+        self.delete_with_query(
+            "/registry/images",
+            &[("repository", repository), ("tag", tag)],
+        )
+        .await
+    }
 }
 
 fn registries_path_with_validate(base: &str, validate: bool) -> String {
@@ -700,18 +1492,35 @@ fn registries_path_with_validate(base: &str, validate: bool) -> String {
     }
 }
 
+/// A log frame as this CLI reads them, or `None` for one it can't — a shape a
+/// newer server sends — which is skipped rather than failing the logs around
+/// it.
+fn log_frame(value: serde_json::Value) -> Option<LogMessage> {
+    match serde_json::from_value(value.clone()) {
+        Ok(frame) => Some(frame),
+        Err(e) => {
+            tracing::debug!("skipping a log frame this CLI can't read ({e}): {value}");
+            None
+        }
+    }
+}
+
 /// Turn one WebSocket frame into a log-stream item.
 ///
-/// Text frames carry the log JSON. A *normal* close ends the stream cleanly
-/// (`None`). An *abnormal* close becomes an error so a server-side failure isn't
-/// silently reported as a successful end of follow. All other control/binary
-/// frames carry nothing to show and are ignored.
+/// Text frames carry the log JSON; one that isn't JSON at all is an error,
+/// while JSON of an unknown shape is skipped (see [`log_frame`]). A *normal*
+/// close ends the stream cleanly (`None`). An *abnormal* close becomes an
+/// error so a server-side failure isn't silently reported as a successful end
+/// of follow. All other control/binary frames carry nothing to show and are
+/// ignored.
+#[cfg(feature = "websocket")]
 fn classify_frame(frame: reqwest_websocket::Message) -> Option<Result<LogMessage>> {
     use reqwest_websocket::{CloseCode, Message};
     match frame {
-        Message::Text(text) => {
-            Some(serde_json::from_str::<LogMessage>(&text).map_err(ApiError::from))
-        }
+        Message::Text(text) => match serde_json::from_str(&text) {
+            Ok(value) => log_frame(value).map(Ok),
+            Err(e) => Some(Err(e.into())),
+        },
         Message::Close { code, reason } if code != CloseCode::Normal => Some(Err(ApiError::Other(
             anyhow::anyhow!("log stream closed abnormally ({code}): {reason}"),
         ))),
@@ -719,10 +1528,106 @@ fn classify_frame(frame: reqwest_websocket::Message) -> Option<Result<LogMessage
     }
 }
 
+/// How a log stream checks its connection is still there. Nothing may be
+/// logged for minutes, and a connection a sleeping laptop left behind can stay
+/// open without ever delivering another frame, so the server is pinged and the
+/// stream gives up once it's heard nothing at all — frames or pongs — for
+/// `silent_after`.
+#[cfg(feature = "websocket")]
+#[derive(Clone, Copy)]
+pub struct KeepAlive {
+    pub ping_every: std::time::Duration,
+    pub silent_after: std::time::Duration,
+}
+
+#[cfg(feature = "websocket")]
+impl KeepAlive {
+    pub const DEFAULT: Self = Self {
+        ping_every: std::time::Duration::from_secs(15),
+        silent_after: std::time::Duration::from_secs(45),
+    };
+}
+
+/// The log stream read from `frames`, with `pings` sent down the same
+/// connection per `policy`. Each frame is classified (see [`classify_frame`]);
+/// a transport break is an error, as is silence, after which the stream ends.
+/// Both are transient, so a follower reconnects.
+#[cfg(feature = "websocket")]
+fn keep_alive<F, P, E>(frames: F, pings: P, policy: KeepAlive) -> LogStream
+where
+    F: futures_util::Stream<Item = std::result::Result<reqwest_websocket::Message, E>>
+        + Send
+        + Unpin
+        + 'static,
+    P: futures_util::Sink<reqwest_websocket::Message> + Send + Unpin + 'static,
+    P::Error: std::fmt::Display + Send,
+    E: std::fmt::Display + Send,
+{
+    use futures_util::{SinkExt, StreamExt};
+    use reqwest_websocket::Message;
+    use tokio::time::{Instant, MissedTickBehavior, interval_at};
+
+    struct Live<F, P> {
+        frames: F,
+        pings: P,
+        ticks: tokio::time::Interval,
+        heard: Instant,
+        over: bool,
+    }
+
+    let mut ticks = interval_at(Instant::now() + policy.ping_every, policy.ping_every);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let live = Live {
+        frames,
+        pings,
+        ticks,
+        heard: Instant::now(),
+        over: false,
+    };
+    let broken =
+        |e: &dyn std::fmt::Display| Err(ApiError::Other(anyhow::anyhow!("log stream error: {e}")));
+    futures_util::stream::unfold(live, move |mut live| async move {
+        if live.over {
+            return None;
+        }
+        loop {
+            tokio::select! {
+                frame = live.frames.next() => {
+                    live.heard = Instant::now();
+                    // The connection closing ends the stream.
+                    let item = match frame? {
+                        Ok(frame) => classify_frame(frame),
+                        Err(e) => Some(broken(&e)),
+                    };
+                    if let Some(item) = item {
+                        return Some((item, live));
+                    }
+                }
+                _ = live.ticks.tick() => {
+                    if let Err(e) = live.pings.send(Message::Ping(Vec::new().into())).await {
+                        live.over = true;
+                        return Some((broken(&e), live));
+                    }
+                }
+                () = tokio::time::sleep_until(live.heard + policy.silent_after) => {
+                    live.over = true;
+                    let silent = ApiError::Other(anyhow::anyhow!(
+                        "no word from the log stream in {}s; the connection looks dead",
+                        policy.silent_after.as_secs()
+                    ));
+                    return Some((Err(silent), live));
+                }
+            }
+        }
+    })
+    .boxed()
+}
+
 /// Map a failed WebSocket upgrade onto a meaningful error. A non-101 status is
 /// the common real failure (expired session, missing instance); surface its
 /// class rather than a generic "failed to upgrade". The server's response body
 /// is already consumed by the handshake, so only the status is available.
+#[cfg(feature = "websocket")]
 fn map_upgrade_error(e: reqwest_websocket::Error) -> ApiError {
     use reqwest_websocket::{Error, HandshakeError};
     if let Error::Handshake(HandshakeError::UnexpectedStatusCode(status)) = &e {
@@ -745,7 +1650,7 @@ fn map_upgrade_error(e: reqwest_websocket::Error) -> ApiError {
     ApiError::Other(anyhow::anyhow!("failed to upgrade to WebSocket: {e}"))
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "websocket"))]
 mod stream_tests {
     use super::*;
     use reqwest_websocket::{CloseCode, Message};
@@ -768,6 +1673,12 @@ mod stream_tests {
         );
     }
 
+    #[test]
+    fn a_frame_of_an_unknown_shape_is_skipped() {
+        let json = r#"{"log_type":"metrics","timestamp_ms":1,"state":{"cpu":0.5}}"#;
+        assert!(classify_frame(Message::Text(json.to_string())).is_none());
+    }
+
     #[test]
     fn normal_close_ends_the_stream_cleanly() {
         let frame = Message::Close {
@@ -800,4 +1711,441 @@ mod stream_tests {
         assert!(classify_frame(Message::Pong(Vec::new().into())).is_none());
         assert!(classify_frame(Message::Binary(Vec::new().into())).is_none());
     }
+
+    const QUICK: KeepAlive = KeepAlive {
+        ping_every: std::time::Duration::from_millis(10),
+        silent_after: std::time::Duration::from_millis(50),
+    };
+
+    #[tokio::test]
+    async fn a_silent_connection_is_pinged_then_given_up_on() {
+        use futures_util::StreamExt;
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let sent = Arc::new(AtomicUsize::new(0));
+        let counter = sent.clone();
+        let pings = Box::pin(futures_util::sink::unfold((), move |(), _: Message| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            async { Ok::<_, std::convert::Infallible>(()) }
+        }));
+        let frames = futures_util::stream::pending::<std::result::Result<Message, String>>();
+
+        let mut stream = keep_alive(frames, pings, QUICK);
+        let err = stream.next().await.unwrap().unwrap_err();
+        assert!(err.to_string().contains("looks dead"), "{err}");
+        assert!(err.is_transient());
+        assert!(stream.next().await.is_none());
+        assert!(sent.load(Ordering::Relaxed) >= 2);
+    }
+
+    #[tokio::test]
+    async fn frames_pass_through_and_a_normal_close_ends_the_stream() {
+        use futures_util::StreamExt;
+
+        let json = r#"{"log_type":"stdout","timestamp_ms":1,"message":"hi"}"#;
+        let frames = futures_util::stream::iter(vec![
+            Ok::<_, String>(Message::Pong(Vec::new().into())),
+            Ok(Message::Text(json.to_string())),
+            Ok(Message::Close {
+                code: CloseCode::Normal,
+                reason: String::new(),
+            }),
+        ]);
+        let pings = futures_util::sink::drain();
+
+        let items: Vec<_> = keep_alive(frames, pings, QUICK).collect().await;
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].as_ref().unwrap().message.as_deref(), Some("hi"));
+    }
+}
+
+#[cfg(test)]
+mod http_tests {
+    use super::*;
+    use crate::test_support::server::{MockServer, Reply};
+    use serde_json::json;
+
+    fn logged_in(server: &MockServer) -> HttpApiClient {
+        let session = AuthSession::test_session("test-token", chrono::Duration::hours(1));
+        HttpApiClient::with_session(server.url(), Some(session))
+    }
+
+    fn login_response(token: &str) -> serde_json::Value {
+        let later = chrono::Utc::now() + chrono::Duration::hours(1);
+        json!({
+            "user_id": Uuid::nil(),
+            "token": token,
+            "expires_at": later,
+            "refresh_session_id": Uuid::nil(),
+            "refresh_token": format!("{token}-refresh"),
+            "refresh_expires_at": later,
+        })
+    }
+
+    #[tokio::test]
+    async fn authenticated_get_sends_the_bearer_token_to_the_base_url() {
+        let server = MockServer::start().await;
+        server.on(
+            "GET",
+            "/environments",
+            Reply::json(200, &json!({ "environments": [] })),
+        );
+
+        let resp = logged_in(&server).list_environments().await.unwrap();
+
+        assert!(resp.environments.is_empty());
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(
+            requests[0].authorization.as_deref(),
+            Some("Bearer test-token")
+        );
+    }
+
+    #[tokio::test]
+    async fn an_api_token_is_sent_as_is_and_has_no_session() {
+        let server = MockServer::start().await;
+        server.on(
+            "GET",
+            "/environments",
+            Reply::json(200, &json!({ "environments": [] })),
+        );
+
+        let client = HttpApiClient::with_token(server.url(), "usk_ci");
+        client.list_environments().await.unwrap();
+
+        assert_eq!(
+            server.requests()[0].authorization.as_deref(),
+            Some("Bearer usk_ci")
+        );
+        assert!(matches!(
+            client.auth_session().await,
+            Err(ApiError::AuthRequired(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn traced_client_sends_traceparent_on_every_request() {
+        let server = MockServer::start().await;
+        server.on(
+            "GET",
+            "/environments",
+            Reply::json(200, &json!({ "environments": [] })),
+        );
+        let trace = TraceContext::generate();
+
+        let client = logged_in(&server).with_trace(trace);
+        client.list_environments().await.unwrap();
+
+        assert_eq!(
+            server.requests()[0].traceparent.as_deref(),
+            Some(trace.to_string().as_str())
+        );
+        assert_eq!(client.trace_id(), Some(trace.trace_id()));
+    }
+
+    #[tokio::test]
+    async fn requests_name_the_api_version_and_learn_the_servers() {
+        let server = MockServer::start().await;
+        server.on(
+            "GET",
+            "/environments",
+            Reply::json(200, &json!({ "environments": [] }))
+                .with_header("Unisrv-Api-Version", "2.1"),
+        );
+        server.on(
+            "GET",
+            "/environments",
+            Reply::json(200, &json!({ "environments": [] }))
+                .with_header("Unisrv-Api-Version", "1.0"),
+        );
+        let client = logged_in(&server).with_api_version("1.2".parse().unwrap());
+        assert_eq!(client.server_api_version(), None);
+
+        client.list_environments().await.unwrap();
+        client.list_environments().await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].api_version.as_deref(), Some("1.2"));
+        // Only the first answer counts.
+        assert_eq!(client.server_api_version(), Some("2.1".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn log_history_skips_frames_of_an_unknown_shape() {
+        let server = MockServer::start().await;
+        let (env_id, id) = (Uuid::nil(), Uuid::nil());
+        server.on(
+            "GET",
+            &format!("/environment/{env_id}/instance/{id}/logs"),
+            Reply::json(
+                200,
+                &json!([
+                    { "log_type": "stdout", "timestamp_ms": 1, "message": "a" },
+                    { "log_type": "metrics", "timestamp_ms": 2, "state": { "cpu": 0.5 } },
+                    { "log_type": "stdout", "timestamp_ms": 3, "message": "b" },
+                ]),
+            ),
+        );
+
+        let frames = logged_in(&server)
+            .get_instance_logs(env_id, id)
+            .await
+            .unwrap();
+
+        let messages: Vec<_> = frames.iter().map(|f| f.message.as_deref()).collect();
+        assert_eq!(messages, [Some("a"), Some("b")]);
+    }
+
+    #[tokio::test]
+    async fn read_only_client_refuses_changes_before_sending() {
+        let server = MockServer::start().await;
+        server.on(
+            "GET",
+            "/environments",
+            Reply::json(200, &json!({ "environments": [] })),
+        );
+        let client = logged_in(&server).with_read_only(true);
+
+        client.list_environments().await.unwrap();
+        let err = client.delete_environment(Uuid::nil()).await.unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "read-only mode: refusing to send DELETE /environment/{}",
+                Uuid::nil()
+            )
+        );
+        assert!(!err.is_transient());
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn post_sends_the_request_body_as_json() {
+        let server = MockServer::start().await;
+        let env_id = Uuid::new_v4();
+        server.on(
+            "POST",
+            "/environment",
+            Reply::json(
+                200,
+                &json!({
+                    "id": env_id, "project": "demo", "name": "prod", "slug": "ab12",
+                    "display_name": null, "description": null,
+                    "created_at": "2025-01-01T00:00:00", "updated_at": "2025-01-01T00:00:00",
+                }),
+            ),
+        );
+
+        let created = logged_in(&server)
+            .create_environment(CreateEnvironmentRequest {
+                project: "demo".into(),
+                name: "prod".into(),
+                display_name: None,
+                description: None,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(created.id, env_id);
+        assert_eq!(
+            server.requests()[0].json(),
+            json!({ "project": "demo", "name": "prod" })
+        );
+    }
+
+    #[tokio::test]
+    async fn expired_access_token_is_refreshed_against_the_same_base_url() {
+        let server = MockServer::start().await;
+        server
+            .on(
+                "POST",
+                "/auth/refresh",
+                Reply::json(200, &login_response("fresh")),
+            )
+            .on(
+                "GET",
+                "/environments",
+                Reply::json(200, &json!({ "environments": [] })),
+            );
+        let session = AuthSession::test_session_needing_refresh("stale");
+        let client = HttpApiClient::with_session(server.url(), Some(session));
+
+        client.list_environments().await.unwrap();
+        client.list_environments().await.unwrap();
+
+        let requests = server.requests();
+        let paths = requests.iter().map(|r| r.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(paths, ["/auth/refresh", "/environments", "/environments"]);
+        assert_eq!(
+            requests[0].authorization.as_deref(),
+            Some("Bearer stale-refresh")
+        );
+        assert_eq!(requests[1].authorization.as_deref(), Some("Bearer fresh"));
+    }
+
+    #[tokio::test]
+    async fn a_session_another_process_refreshed_is_taken_over() {
+        let server = MockServer::start().await;
+        server
+            .on(
+                "POST",
+                "/auth/refresh",
+                Reply::json(200, &login_response("fresh")),
+            )
+            // The stale refresh token has been rotated away by then.
+            .on(
+                "POST",
+                "/auth/refresh",
+                Reply::json(401, &json!({ "reason": "refresh token reused" })),
+            )
+            .on(
+                "GET",
+                "/environments",
+                Reply::json(200, &json!({ "environments": [] })),
+            );
+        let dir = tempfile::tempdir().unwrap();
+        let session = AuthSession::test_session_needing_refresh("stale");
+        // Two processes that loaded the same session.
+        let process = || {
+            let mut client = HttpApiClient::with_session(server.url(), Some(session.clone()));
+            client.auth_store = AuthStore::in_dir(dir.path(), &server.url());
+            client
+        };
+        let (first, second) = (process(), process());
+
+        first.list_environments().await.unwrap();
+        second.list_environments().await.unwrap();
+
+        let requests = server.requests();
+        let paths = requests.iter().map(|r| r.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(paths, ["/auth/refresh", "/environments", "/environments"]);
+        assert_eq!(requests[2].authorization.as_deref(), Some("Bearer fresh"));
+    }
+
+    #[tokio::test]
+    async fn rejected_refresh_requires_login_without_calling_the_api() {
+        let server = MockServer::start().await;
+        server.on(
+            "POST",
+            "/auth/refresh",
+            Reply::json(401, &json!({ "reason": "revoked" })),
+        );
+        let session = AuthSession::test_session_needing_refresh("stale");
+        let client = HttpApiClient::with_session(server.url(), Some(session));
+
+        let err = client.list_environments().await.unwrap_err();
+
+        assert!(
+            matches!(&err, ApiError::AuthRequired(msg) if msg.contains("revoked")),
+            "{err:?}"
+        );
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn error_statuses_map_to_server_errors_with_the_reason() {
+        let server = MockServer::start().await;
+        server
+            .on(
+                "GET",
+                "/environments",
+                Reply::json(503, &json!({ "reason": "maintenance" })),
+            )
+            .on(
+                "GET",
+                "/environments",
+                Reply::json(200, &json!({ "environments": [] })),
+            );
+        let client = logged_in(&server);
+
+        let err = client.list_environments().await.unwrap_err();
+        assert!(
+            matches!(&err, ApiError::Server { status: 503, reason } if reason == "maintenance"),
+            "{err:?}"
+        );
+        assert!(err.is_transient());
+        // The route moves on to its next scripted reply.
+        client.list_environments().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn unknown_route_reads_as_an_unsupported_endpoint() {
+        let server = MockServer::start().await;
+        let env_id = Uuid::new_v4();
+
+        let err = logged_in(&server)
+            .find_instances_by_name(env_id, "web")
+            .await
+            .unwrap_err();
+
+        assert!(err.is_unsupported_endpoint(), "{err:?}");
+        assert!(err.is_unsupported_route(), "{err:?}");
+        let request = &server.requests()[0];
+        assert_eq!(request.path, format!("/environment/{env_id}/instances"));
+        assert_eq!(request.query.as_deref(), Some("name=web"));
+    }
+
+    #[tokio::test]
+    async fn no_session_fails_before_any_request() {
+        let server = MockServer::start().await;
+        let client = HttpApiClient::with_session(server.url(), None);
+
+        let err = client.list_environments().await.unwrap_err();
+
+        assert!(matches!(err, ApiError::AuthRequired(_)), "{err:?}");
+        assert!(server.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn recorded_session_replays_without_network_or_login() {
+        use crate::session::Tape;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("session.json");
+        let server = MockServer::start().await;
+        server
+            .on(
+                "GET",
+                "/environments",
+                Reply::json(200, &json!({ "environments": [] })),
+            )
+            .on(
+                "POST",
+                "/registries",
+                Reply::json(422, &json!({ "reason": "bad credentials" })),
+            );
+        let recording = logged_in(&server).with_tape(Tape::record(&file).unwrap());
+        recording.list_environments().await.unwrap();
+        let req = CreateRegistryRequest {
+            hostname: "ghcr.io".into(),
+            kind: RegistryKind::Userpass,
+            config: json!({ "username": "me" }),
+            secret: json!({ "password": "hunter2" }),
+        };
+        let recorded_err = recording
+            .create_registry(req.clone(), false)
+            .await
+            .unwrap_err();
+
+        let tape = std::fs::read_to_string(&file).unwrap();
+        assert!(!tape.contains("hunter2"), "{tape}");
+        assert!(tape.contains("bad credentials"), "{tape}");
+
+        // Nothing listens here and there's no session: every answer must come
+        // from the tape.
+        let replaying = HttpApiClient::with_session("http://127.0.0.1:9", None)
+            .with_tape(Tape::replay(&file).unwrap());
+        replaying.list_environments().await.unwrap();
+        let replayed_err = replaying.create_registry(req, false).await.unwrap_err();
+        assert_eq!(replayed_err.to_string(), recorded_err.to_string());
+
+        let err = replaying.list_environments().await.unwrap_err();
+        assert!(
+            err.to_string().contains("no recorded response left"),
+            "{err}"
+        );
+    }
 }
@@ -0,0 +1,911 @@
+//! Just enough of the OCI distribution API to list a repository's tags
+//! (`GET /v2/<name>/tags/list`, following `Link: <…>; rel="next"` pages), to
+//! read which platforms a multi-platform image is built for, to read the
+//! artifacts (SBOMs, attestations) attached to an image through the referrers
+//! API, and to push an image: blobs through chunked uploads, then its
+//! manifest.
+//!
+//! Unlike [`HttpApiClient`](crate::HttpApiClient) this talks to the registry
+//! itself, not the unisrv API. It sends the bearer token it's given — a
+//! registry's scoped token from
+//! [`ApiClient::get_scoped_token`](crate::ApiClient::get_scoped_token) — or,
+//! without one, answers the registry's `WWW-Authenticate` challenge: with the
+//! username and secret from
+//! [`DistributionClient::with_credentials`] where it has them (say, from a
+//! Docker credential helper), otherwise anonymously, which is how public
+//! repositories are read.
+
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{ApiError, Result};
+
+/// Tags asked for per page; registries may send fewer.
+const PAGE_SIZE: usize = 1000;
+
+/// One page of a repository's tags.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagPage {
+    pub tags: Vec<String>,
+    /// Where the next page is, if there is one.
+    pub next: Option<String>,
+}
+
+/// Somewhere to read tags from a page at a time.
+#[async_trait]
+pub trait TagSource: Send + Sync {
+    /// The first page when `next` is `None`, otherwise the page at a previous
+    /// page's [`TagPage::next`].
+    async fn tags_page(&self, next: Option<&str>) -> Result<TagPage>;
+}
+
+/// An image index entry's platform, as in `linux/arm64/v8`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ManifestPlatform {
+    pub os: String,
+    pub architecture: String,
+    #[serde(default)]
+    pub variant: Option<String>,
+}
+
+impl std::fmt::Display for ManifestPlatform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.os, self.architecture)?;
+        match &self.variant {
+            Some(variant) => write!(f, "/{variant}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Somewhere to read an image's manifests from.
+#[async_trait]
+pub trait ManifestSource: Send + Sync {
+    /// The platforms the image at `reference` (a tag or digest) is built for
+    /// when it's a multi-platform index; `None` for a single image manifest,
+    /// whose platform only its config blob records.
+    async fn index_platforms(&self, reference: &str) -> Result<Option<Vec<ManifestPlatform>>>;
+    /// The digest of the manifest (or index) `reference` names, as in
+    /// `sha256:…`, which pins what a tag points at now.
+    async fn digest(&self, reference: &str) -> Result<String>;
+}
+
+/// A content descriptor, as an index or the referrers API lists them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Descriptor {
+    pub media_type: String,
+    pub digest: String,
+    pub size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artifact_type: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+}
+
+/// Somewhere to read the artifacts attached to an image from.
+#[async_trait]
+pub trait ArtifactSource: Send + Sync {
+    /// The manifests that refer to the manifest `digest`: its SBOMs,
+    /// signatures and other attestations.
+    async fn referrers(&self, digest: &str) -> Result<Vec<Descriptor>>;
+    /// The layers of the manifest `digest`.
+    async fn layers(&self, digest: &str) -> Result<Vec<Descriptor>>;
+    /// The blob `digest`'s content.
+    async fn blob(&self, digest: &str) -> Result<Vec<u8>>;
+}
+
+/// Somewhere to push an image's blobs and manifests to.
+#[async_trait]
+pub trait ImageSink: Send + Sync {
+    /// Whether the repository already has the blob `digest`.
+    async fn blob_exists(&self, digest: &str) -> Result<bool>;
+    /// Open an upload session; returns where to send its chunks.
+    async fn start_upload(&self) -> Result<String>;
+    /// Send the bytes at `offset` of the blob being uploaded to `location`;
+    /// returns where to send the next chunk.
+    async fn upload_chunk(&self, location: &str, offset: u64, chunk: Vec<u8>) -> Result<String>;
+    /// Close the upload at `location` as the blob `digest`, which the registry
+    /// checks against what it received.
+    async fn finish_upload(&self, location: &str, digest: &str) -> Result<()>;
+    /// Store a manifest under `reference`, a tag or its digest.
+    async fn put_manifest(&self, reference: &str, media_type: &str, body: Vec<u8>) -> Result<()>;
+}
+
+pub struct DistributionClient {
+    client: reqwest::Client,
+    base_url: String,
+    repository: String,
+    token: tokio::sync::Mutex<Option<String>>,
+    /// Username and secret to fetch tokens with, or to send as basic auth to
+    /// a registry that asks for it.
+    credentials: Option<(String, String)>,
+}
+
+impl DistributionClient {
+    pub fn new(
+        base_url: impl Into<String>,
+        repository: impl Into<String>,
+        token: Option<String>,
+    ) -> Self {
+        DistributionClient {
+            client: reqwest::Client::new(),
+            base_url: base_url.into(),
+            repository: repository.into(),
+            token: tokio::sync::Mutex::new(token),
+            credentials: None,
+        }
+    }
+
+    /// Authenticate with `username` and `secret` rather than anonymously.
+    pub fn with_credentials(
+        mut self,
+        username: impl Into<String>,
+        secret: impl Into<String>,
+    ) -> Self {
+        self.credentials = Some((username.into(), secret.into()));
+        self
+    }
+
+    /// Get a token for this repository ahead of requests that don't answer
+    /// challenges themselves, as a push's do: pull access, and push access
+    /// too if `push`. A client that was given a token, or a registry that
+    /// wants none (or basic auth, which every request then sends), needs
+    /// nothing more.
+    pub async fn authorize(&self, push: bool) -> Result<()> {
+        if self.token.lock().await.is_some() {
+            return Ok(());
+        }
+        let resp = self
+            .request(reqwest::Method::GET, self.absolute("/v2/")?)
+            .await
+            .send()
+            .await?;
+        if resp.status() != reqwest::StatusCode::UNAUTHORIZED {
+            check(resp).await?;
+            return Ok(());
+        }
+        let Some(mut challenge) = resp
+            .headers()
+            .get(reqwest::header::WWW_AUTHENTICATE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(BearerChallenge::parse)
+        else {
+            return Err(check(resp).await.err().unwrap_or_else(|| {
+                ApiError::Serialization("the registry refused the request".into())
+            }));
+        };
+        let actions = if push { "pull,push" } else { "pull" };
+        challenge.scope = Some(format!("repository:{}:{actions}", self.repository));
+        let token = self.challenge_token(&challenge).await?;
+        *self.token.lock().await = Some(token);
+        Ok(())
+    }
+
+    /// A client for the registry at `host`, e.g. `ghcr.io`. Docker Hub's
+    /// `docker.io` is served from `registry-1.docker.io`.
+    pub fn for_host(host: &str, repository: impl Into<String>, token: Option<String>) -> Self {
+        let host = match host {
+            "docker.io" | "index.docker.io" => "registry-1.docker.io",
+            other => other,
+        };
+        Self::new(format!("https://{host}"), repository, token)
+    }
+
+    /// `url` resolved against the registry, for the relative `Location` and
+    /// `Link` targets registries hand back.
+    fn absolute(&self, url: &str) -> Result<reqwest::Url> {
+        reqwest::Url::parse(&self.base_url)
+            .and_then(|base| base.join(url))
+            .map_err(|e| ApiError::Serialization(format!("bad registry URL {url:?}: {e}")))
+    }
+
+    async fn request(&self, method: reqwest::Method, url: reqwest::Url) -> reqwest::RequestBuilder {
+        let request = self.client.request(method, url);
+        match (self.token.lock().await.as_deref(), &self.credentials) {
+            (Some(token), _) => request.bearer_auth(token),
+            (None, Some((username, secret))) => request.basic_auth(username, Some(secret)),
+            (None, None) => request,
+        }
+    }
+
+    /// The `Location` a registry answered with, resolved.
+    fn location(&self, resp: &reqwest::Response) -> Result<String> {
+        let location = resp
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| {
+                ApiError::Serialization("the registry's upload response had no Location".into())
+            })?;
+        Ok(self.absolute(location)?.to_string())
+    }
+
+    /// `GET url`, with an `Accept` header when given, answering a bearer
+    /// challenge if there's no token.
+    async fn get(&self, url: &str, accept: Option<&str>) -> Result<reqwest::Response> {
+        self.read(reqwest::Method::GET, url, accept).await
+    }
+
+    /// A `GET` or `HEAD` of `url`, as [`DistributionClient::get`].
+    async fn read(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+        accept: Option<&str>,
+    ) -> Result<reqwest::Response> {
+        let tokenless = self.token.lock().await.is_none();
+        let with_accept = |request: reqwest::RequestBuilder| match accept {
+            Some(accept) => request.header(reqwest::header::ACCEPT, accept),
+            None => request,
+        };
+        let resp = with_accept(self.request(method.clone(), self.absolute(url)?).await)
+            .send()
+            .await?;
+        if resp.status() != reqwest::StatusCode::UNAUTHORIZED || !tokenless {
+            return Ok(resp);
+        }
+        let Some(challenge) = resp
+            .headers()
+            .get(reqwest::header::WWW_AUTHENTICATE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(BearerChallenge::parse)
+        else {
+            return Ok(resp);
+        };
+        let token = self.challenge_token(&challenge).await?;
+        *self.token.lock().await = Some(token.clone());
+        Ok(
+            with_accept(self.client.request(method, self.absolute(url)?))
+                .bearer_auth(token)
+                .send()
+                .await?,
+        )
+    }
+
+    /// A token from the challenge's realm, asked for with the client's
+    /// credentials if it has any.
+    async fn challenge_token(&self, challenge: &BearerChallenge) -> Result<String> {
+        #[derive(Deserialize)]
+        struct TokenResponse {
+            token: Option<String>,
+            access_token: Option<String>,
+        }
+        let mut query = Vec::new();
+        if let Some(service) = &challenge.service {
+            query.push(("service", service.as_str()));
+        }
+        if let Some(scope) = &challenge.scope {
+            query.push(("scope", scope.as_str()));
+        }
+        let mut request = self.client.get(&challenge.realm).query(&query);
+        if let Some((username, secret)) = &self.credentials {
+            request = request.basic_auth(username, Some(secret));
+        }
+        let resp = check(request.send().await?).await?;
+        let body: TokenResponse = resp.json().await?;
+        body.token.or(body.access_token).ok_or_else(|| {
+            ApiError::Serialization("the registry's token response had no token".into())
+        })
+    }
+}
+
+#[async_trait]
+impl TagSource for DistributionClient {
+    async fn tags_page(&self, next: Option<&str>) -> Result<TagPage> {
+        #[derive(Deserialize)]
+        struct TagList {
+            #[serde(default)]
+            tags: Option<Vec<String>>,
+        }
+        let url = match next {
+            Some(link) => self.absolute(link)?.to_string(),
+            None => format!(
+                "{}/v2/{}/tags/list?n={PAGE_SIZE}",
+                self.base_url, self.repository
+            ),
+        };
+        let resp = check(self.get(&url, None).await?).await?;
+        let next = resp
+            .headers()
+            .get(reqwest::header::LINK)
+            .and_then(|v| v.to_str().ok())
+            .and_then(next_link);
+        let body: TagList = resp.json().await?;
+        Ok(TagPage {
+            tags: body.tags.unwrap_or_default(),
+            next,
+        })
+    }
+}
+
+/// Every manifest media type, for `Accept`.
+fn accept_manifests() -> String {
+    INDEX_TYPES
+        .iter()
+        .chain(&MANIFEST_TYPES)
+        .copied()
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Index media types, which list a manifest per platform.
+const INDEX_TYPES: [&str; 2] = [
+    "application/vnd.oci.image.index.v1+json",
+    "application/vnd.docker.distribution.manifest.list.v2+json",
+];
+
+/// Single-platform manifest media types.
+const MANIFEST_TYPES: [&str; 2] = [
+    "application/vnd.oci.image.manifest.v1+json",
+    "application/vnd.docker.distribution.manifest.v2+json",
+];
+
+#[async_trait]
+impl ManifestSource for DistributionClient {
+    async fn index_platforms(&self, reference: &str) -> Result<Option<Vec<ManifestPlatform>>> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Index {
+            #[serde(default)]
+            media_type: Option<String>,
+            #[serde(default)]
+            manifests: Option<Vec<Entry>>,
+        }
+        #[derive(Deserialize)]
+        struct Entry {
+            #[serde(default)]
+            platform: Option<ManifestPlatform>,
+        }
+        let url = format!("/v2/{}/manifests/{reference}", self.repository);
+        let resp = check(self.get(&url, Some(&accept_manifests())).await?).await?;
+        let content_type = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.split(';').next().unwrap_or_default().trim().to_string())
+            .filter(|v| INDEX_TYPES.contains(&v.as_str()) || MANIFEST_TYPES.contains(&v.as_str()));
+        let body: Index = resp.json().await?;
+        // Not every registry sends a manifest Content-Type; OCI documents
+        // carry their own, and only an index has `manifests`.
+        let media_type = content_type.or(body.media_type);
+        let is_index = match media_type.as_deref() {
+            Some(media_type) => INDEX_TYPES.contains(&media_type),
+            None => body.manifests.is_some(),
+        };
+        if !is_index {
+            return Ok(None);
+        }
+        Ok(Some(
+            body.manifests
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|entry| entry.platform)
+                .collect(),
+        ))
+    }
+
+    async fn digest(&self, reference: &str) -> Result<String> {
+        let url = format!("/v2/{}/manifests/{reference}", self.repository);
+        let resp = self
+            .read(reqwest::Method::HEAD, &url, Some(&accept_manifests()))
+            .await?;
+        let resp = check(resp).await?;
+        resp.headers()
+            .get("docker-content-digest")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+            .ok_or_else(|| {
+                ApiError::Serialization(format!(
+                    "the registry didn't report a digest for {reference}"
+                ))
+            })
+    }
+}
+
+/// The referrers API's answer, and the index registries without it keep
+/// under the fallback tag: both are an image index.
+#[derive(Deserialize)]
+struct ReferrersIndex {
+    #[serde(default)]
+    manifests: Vec<Descriptor>,
+}
+
+#[async_trait]
+impl ArtifactSource for DistributionClient {
+    async fn referrers(&self, digest: &str) -> Result<Vec<Descriptor>> {
+        let url = format!("/v2/{}/referrers/{digest}", self.repository);
+        let resp = self.get(&url, Some(INDEX_TYPES[0])).await?;
+        if resp.status() != reqwest::StatusCode::NOT_FOUND {
+            let index: ReferrersIndex = check(resp).await?.json().await?;
+            return Ok(index.manifests);
+        }
+        // Registries without the API keep referrers in an index tagged
+        // `<algorithm>-<hex>`, which doesn't exist until something refers.
+        let url = format!(
+            "/v2/{}/manifests/{}",
+            self.repository,
+            digest.replacen(':', "-", 1)
+        );
+        let resp = self.get(&url, Some(&accept_manifests())).await?;
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(Vec::new());
+        }
+        let index: ReferrersIndex = check(resp).await?.json().await?;
+        Ok(index.manifests)
+    }
+
+    async fn layers(&self, digest: &str) -> Result<Vec<Descriptor>> {
+        #[derive(Deserialize)]
+        struct Manifest {
+            #[serde(default)]
+            layers: Vec<Descriptor>,
+        }
+        let url = format!("/v2/{}/manifests/{digest}", self.repository);
+        let resp = check(self.get(&url, Some(&accept_manifests())).await?).await?;
+        let manifest: Manifest = resp.json().await?;
+        Ok(manifest.layers)
+    }
+
+    async fn blob(&self, digest: &str) -> Result<Vec<u8>> {
+        let url = format!("/v2/{}/blobs/{digest}", self.repository);
+        let resp = check(self.get(&url, None).await?).await?;
+        Ok(resp.bytes().await?.to_vec())
+    }
+}
+
+#[async_trait]
+impl ImageSink for DistributionClient {
+    async fn blob_exists(&self, digest: &str) -> Result<bool> {
+        let url = self.absolute(&format!("/v2/{}/blobs/{digest}", self.repository))?;
+        let resp = self
+            .request(reqwest::Method::HEAD, url)
+            .await
+            .send()
+            .await?;
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        check(resp).await?;
+        Ok(true)
+    }
+
+    async fn start_upload(&self) -> Result<String> {
+        let url = self.absolute(&format!("/v2/{}/blobs/uploads/", self.repository))?;
+        let resp = check(
+            self.request(reqwest::Method::POST, url)
+                .await
+                .send()
+                .await?,
+        )
+        .await?;
+        self.location(&resp)
+    }
+
+    async fn upload_chunk(&self, location: &str, offset: u64, chunk: Vec<u8>) -> Result<String> {
+        let end = offset + chunk.len() as u64;
+        let resp = self
+            .request(reqwest::Method::PATCH, self.absolute(location)?)
+            .await
+            .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+            .header(
+                reqwest::header::CONTENT_RANGE,
+                format!("{offset}-{}", end - 1),
+            )
+            .body(chunk)
+            .send()
+            .await?;
+        let resp = check(resp).await?;
+        self.location(&resp)
+    }
+
+    async fn finish_upload(&self, location: &str, digest: &str) -> Result<()> {
+        let mut url = self.absolute(location)?;
+        url.query_pairs_mut().append_pair("digest", digest);
+        let resp = self
+            .request(reqwest::Method::PUT, url)
+            .await
+            .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+            .send()
+            .await?;
+        check(resp).await?;
+        Ok(())
+    }
+
+    async fn put_manifest(&self, reference: &str, media_type: &str, body: Vec<u8>) -> Result<()> {
+        let url = self.absolute(&format!("/v2/{}/manifests/{reference}", self.repository))?;
+        let resp = self
+            .request(reqwest::Method::PUT, url)
+            .await
+            .header(reqwest::header::CONTENT_TYPE, media_type)
+            .body(body)
+            .send()
+            .await?;
+        check(resp).await?;
+        Ok(())
+    }
+}
+
+/// A registry error response as [`ApiError::Server`], with the reason taken
+/// from the distribution API's `{"errors": [{"message": …}]}` body.
+async fn check(resp: reqwest::Response) -> Result<reqwest::Response> {
+    #[derive(Deserialize)]
+    struct Errors {
+        errors: Vec<ErrorEntry>,
+    }
+    #[derive(Deserialize)]
+    struct ErrorEntry {
+        message: String,
+    }
+    let status = resp.status();
+    if status.is_success() {
+        return Ok(resp);
+    }
+    let body = resp.text().await.unwrap_or_default();
+    let reason = match serde_json::from_str::<Errors>(&body) {
+        Ok(errors) if !errors.errors.is_empty() => errors
+            .errors
+            .into_iter()
+            .map(|e| e.message)
+            .collect::<Vec<_>>()
+            .join("; "),
+        _ => status.canonical_reason().unwrap_or("request failed").into(),
+    };
+    Err(ApiError::Server {
+        status: status.as_u16(),
+        reason,
+    })
+}
+
+/// The `rel="next"` target of a `Link` header.
+fn next_link(header: &str) -> Option<String> {
+    header.split(',').find_map(|link| {
+        let (target, params) = link.split_once(';')?;
+        let is_next = params
+            .split(';')
+            .any(|p| p.trim().trim_start_matches("rel=").trim_matches('"') == "next");
+        let target = target.trim().strip_prefix('<')?.strip_suffix('>')?;
+        is_next.then(|| target.to_string())
+    })
+}
+
+/// `WWW-Authenticate: Bearer realm="…",service="…",scope="…"`.
+#[derive(Debug, PartialEq)]
+struct BearerChallenge {
+    realm: String,
+    service: Option<String>,
+    scope: Option<String>,
+}
+
+impl BearerChallenge {
+    fn parse(header: &str) -> Option<Self> {
+        let params = header.strip_prefix("Bearer ")?;
+        let mut realm = None;
+        let mut service = None;
+        let mut scope = None;
+        // Values are quoted and may themselves contain commas (a scope can
+        // list several actions), so split on `",` rather than `,`.
+        for param in params.split("\",") {
+            let (key, value) = param.trim().split_once('=')?;
+            let value = value.trim_matches('"').to_string();
+            match key {
+                "realm" => realm = Some(value),
+                "service" => service = Some(value),
+                "scope" => scope = Some(value),
+                _ => {}
+            }
+        }
+        Some(BearerChallenge {
+            realm: realm?,
+            service,
+            scope,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::server::{MockServer, Reply};
+    use serde_json::json;
+
+    #[test]
+    fn parses_bearer_challenges_and_next_links() {
+        let challenge = BearerChallenge::parse(
+            r#"Bearer realm="https://auth.docker.io/token",service="registry.docker.io",scope="repository:library/nginx:pull,push""#,
+        )
+        .unwrap();
+        assert_eq!(
+            challenge,
+            BearerChallenge {
+                realm: "https://auth.docker.io/token".into(),
+                service: Some("registry.docker.io".into()),
+                scope: Some("repository:library/nginx:pull,push".into()),
+            }
+        );
+        assert_eq!(BearerChallenge::parse("Basic realm=\"x\""), None);
+
+        assert_eq!(
+            next_link(r#"</v2/app/tags/list?last=b&n=2>; rel="next""#),
+            Some("/v2/app/tags/list?last=b&n=2".into())
+        );
+        assert_eq!(next_link(r#"</v2/app/tags/list>; rel="prev""#), None);
+    }
+
+    #[tokio::test]
+    async fn follows_pages_with_the_given_token() {
+        let server = MockServer::start().await;
+        server
+            .on(
+                "GET",
+                "/v2/acme/app/tags/list",
+                Reply::json(200, &json!({"name": "acme/app", "tags": ["a", "b"]}))
+                    .with_header("link", r#"</v2/acme/app/tags/list?last=b&n=2>; rel="next""#),
+            )
+            .on(
+                "GET",
+                "/v2/acme/app/tags/list",
+                Reply::json(200, &json!({"name": "acme/app", "tags": ["c"]})),
+            );
+        let client = DistributionClient::new(server.url(), "acme/app", Some("scoped".into()));
+
+        let first = client.tags_page(None).await.unwrap();
+        assert_eq!(first.tags, ["a", "b"]);
+        let second = client.tags_page(first.next.as_deref()).await.unwrap();
+        assert_eq!(
+            second,
+            TagPage {
+                tags: vec!["c".into()],
+                next: None
+            }
+        );
+
+        let requests = server.requests();
+        assert_eq!(requests[1].query.as_deref(), Some("last=b&n=2"));
+        assert!(
+            requests
+                .iter()
+                .all(|r| r.authorization.as_deref() == Some("Bearer scoped"))
+        );
+    }
+
+    #[tokio::test]
+    async fn answers_a_challenge_anonymously_without_a_token() {
+        let server = MockServer::start().await;
+        let challenge = format!(
+            r#"Bearer realm="{}/token",service="registry",scope="repository:library/nginx:pull""#,
+            server.url()
+        );
+        server
+            .on(
+                "GET",
+                "/v2/library/nginx/tags/list",
+                Reply::json(
+                    401,
+                    &json!({"errors": [{"message": "authentication required"}]}),
+                )
+                .with_header("www-authenticate", &challenge),
+            )
+            .on(
+                "GET",
+                "/v2/library/nginx/tags/list",
+                Reply::json(200, &json!({"tags": ["1.27"]})),
+            )
+            .on("GET", "/token", Reply::json(200, &json!({"token": "anon"})));
+        let client = DistributionClient::new(server.url(), "library/nginx", None);
+
+        let page = client.tags_page(None).await.unwrap();
+        assert_eq!(page.tags, ["1.27"]);
+        let requests = server.requests();
+        assert_eq!(
+            requests[1].query.as_deref(),
+            Some("service=registry&scope=repository%3Alibrary%2Fnginx%3Apull")
+        );
+        assert_eq!(requests[2].authorization.as_deref(), Some("Bearer anon"));
+    }
+
+    #[tokio::test]
+    async fn authorizes_a_push_with_credentials() {
+        let server = MockServer::start().await;
+        let challenge = format!(
+            r#"Bearer realm="{}/token",service="registry""#,
+            server.url()
+        );
+        server
+            .on(
+                "GET",
+                "/v2/",
+                Reply::empty(401).with_header("www-authenticate", &challenge),
+            )
+            .on(
+                "GET",
+                "/token",
+                Reply::json(200, &json!({"token": "pushy"})),
+            )
+            .on("HEAD", "/v2/acme/app/blobs/sha256:abc", Reply::empty(200));
+        let client = DistributionClient::new(server.url(), "acme/app", None)
+            .with_credentials("ci", "s3cret");
+
+        client.authorize(true).await.unwrap();
+        assert!(client.blob_exists("sha256:abc").await.unwrap());
+
+        let requests = server.requests();
+        assert_eq!(
+            requests[1].query.as_deref(),
+            Some("service=registry&scope=repository%3Aacme%2Fapp%3Apull%2Cpush")
+        );
+        // base64("ci:s3cret")
+        assert_eq!(
+            requests[1].authorization.as_deref(),
+            Some("Basic Y2k6czNjcmV0")
+        );
+        assert_eq!(requests[2].authorization.as_deref(), Some("Bearer pushy"));
+    }
+
+    #[tokio::test]
+    async fn reports_the_registry_error_message() {
+        let server = MockServer::start().await;
+        server.on(
+            "GET",
+            "/v2/acme/gone/tags/list",
+            Reply::json(
+                404,
+                &json!({"errors": [{"code": "NAME_UNKNOWN", "message": "repository name not known to registry"}]}),
+            ),
+        );
+        let client = DistributionClient::new(server.url(), "acme/gone", Some("t".into()));
+        let err = client.tags_page(None).await.unwrap_err();
+        assert!(
+            matches!(&err, ApiError::Server { status: 404, reason } if reason == "repository name not known to registry"),
+            "{err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn reads_a_tag_digest() {
+        let server = MockServer::start().await;
+        server
+            .on(
+                "HEAD",
+                "/v2/acme/app/manifests/1.0",
+                Reply::empty(200).with_header("docker-content-digest", "sha256:abc"),
+            )
+            .on("HEAD", "/v2/acme/app/manifests/gone", Reply::empty(404));
+        let client = DistributionClient::new(server.url(), "acme/app", Some("t".into()));
+
+        assert_eq!(client.digest("1.0").await.unwrap(), "sha256:abc");
+        assert!(client.digest("gone").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn reads_an_index_platforms() {
+        let server = MockServer::start().await;
+        server
+            .on(
+                "GET",
+                "/v2/acme/app/manifests/1.0",
+                Reply::json(
+                    200,
+                    &json!({
+                        "mediaType": "application/vnd.oci.image.index.v1+json",
+                        "manifests": [
+                            {"digest": "sha256:a", "platform": {"os": "linux", "architecture": "amd64"}},
+                            {"digest": "sha256:b", "platform": {"os": "linux", "architecture": "arm64", "variant": "v8"}},
+                        ]
+                    }),
+                ),
+            )
+            .on(
+                "GET",
+                "/v2/acme/app/manifests/single",
+                Reply::json(
+                    200,
+                    &json!({
+                        "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                        "config": {"digest": "sha256:c"},
+                        "layers": []
+                    }),
+                ),
+            );
+        let client = DistributionClient::new(server.url(), "acme/app", Some("t".into()));
+
+        let platforms = client.index_platforms("1.0").await.unwrap().unwrap();
+        let names: Vec<String> = platforms.iter().map(ToString::to_string).collect();
+        assert_eq!(names, ["linux/amd64", "linux/arm64/v8"]);
+        assert_eq!(client.index_platforms("single").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn lists_referrers_falling_back_to_the_referrers_tag() {
+        let sbom = json!({
+            "mediaType": "application/vnd.oci.image.manifest.v1+json",
+            "digest": "sha256:s",
+            "size": 512,
+            "artifactType": "application/spdx+json",
+        });
+        let server = MockServer::start().await;
+        server
+            .on(
+                "GET",
+                "/v2/acme/app/referrers/sha256:a",
+                Reply::json(200, &json!({ "manifests": [sbom] })),
+            )
+            .on(
+                "GET",
+                "/v2/acme/app/manifests/sha256-b",
+                Reply::json(200, &json!({ "manifests": [sbom] })),
+            )
+            .on(
+                "GET",
+                "/v2/acme/app/manifests/sha256:s",
+                Reply::json(
+                    200,
+                    &json!({
+                        "layers": [{"mediaType": "application/spdx+json", "digest": "sha256:l", "size": 2}]
+                    }),
+                ),
+            );
+        let client = DistributionClient::new(server.url(), "acme/app", Some("t".into()));
+
+        let referrers = client.referrers("sha256:a").await.unwrap();
+        assert_eq!(
+            referrers[0].artifact_type.as_deref(),
+            Some("application/spdx+json")
+        );
+        assert_eq!(client.referrers("sha256:b").await.unwrap(), referrers);
+        assert!(client.referrers("sha256:c").await.unwrap().is_empty());
+        let layers = client.layers("sha256:s").await.unwrap();
+        assert_eq!(layers[0].digest, "sha256:l");
+    }
+
+    #[tokio::test]
+    async fn pushes_a_blob_in_chunks_then_the_manifest() {
+        let server = MockServer::start().await;
+        server
+            .on("HEAD", "/v2/acme/app/blobs/sha256:abc", Reply::empty(404))
+            .on(
+                "POST",
+                "/v2/acme/app/blobs/uploads/",
+                Reply::empty(202).with_header("location", "/v2/acme/app/blobs/uploads/u1"),
+            )
+            .on(
+                "PATCH",
+                "/v2/acme/app/blobs/uploads/u1",
+                Reply::empty(202).with_header("location", "/v2/acme/app/blobs/uploads/u1?_state=2"),
+            )
+            .on("PUT", "/v2/acme/app/blobs/uploads/u1", Reply::empty(201))
+            .on("PUT", "/v2/acme/app/manifests/1.0", Reply::empty(201));
+        let client = DistributionClient::new(server.url(), "acme/app", Some("push".into()));
+
+        assert!(!client.blob_exists("sha256:abc").await.unwrap());
+        let location = client.start_upload().await.unwrap();
+        let location = client
+            .upload_chunk(&location, 0, b"layer".to_vec())
+            .await
+            .unwrap();
+        client.finish_upload(&location, "sha256:abc").await.unwrap();
+        client
+            .put_manifest(
+                "1.0",
+                "application/vnd.oci.image.manifest.v1+json",
+                b"{}".to_vec(),
+            )
+            .await
+            .unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[2].body, "layer");
+        assert_eq!(
+            requests[3].query.as_deref(),
+            Some("_state=2&digest=sha256%3Aabc")
+        );
+        assert_eq!(requests[4].body, "{}");
+        assert!(
+            requests
+                .iter()
+                .all(|r| r.authorization.as_deref() == Some("Bearer push"))
+        );
+    }
+}
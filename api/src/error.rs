@@ -10,6 +10,9 @@ pub enum ApiError {
     AuthRequired(String),
     /// Serialization/deserialization error
     Serialization(String),
+    /// A request that could change something, refused in read-only mode
+    /// before it was sent
+    ReadOnly { method: String, path: String },
     /// Other errors
     Other(anyhow::Error),
 }
@@ -21,6 +24,9 @@ impl fmt::Display for ApiError {
             ApiError::Server { status, reason } => write!(f, "Server error ({status}): {reason}"),
             ApiError::AuthRequired(msg) => write!(f, "Authentication required: {msg}"),
             ApiError::Serialization(msg) => write!(f, "Serialization error: {msg}"),
+            ApiError::ReadOnly { method, path } => {
+                write!(f, "read-only mode: refusing to send {method} {path}")
+            }
             ApiError::Other(e) => write!(f, "{e}"),
         }
     }
@@ -52,6 +58,62 @@ impl ApiError {
     pub fn not_logged_in() -> Self {
         ApiError::AuthRequired("Not logged in.".into())
     }
+
+    /// True for failures worth retrying as-is: the connection broke, or the
+    /// server was briefly unable to answer (5xx, 429). Auth, client and parse
+    /// errors will fail the same way again.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            ApiError::Request(_) | ApiError::Other(_) => true,
+            ApiError::Server { status, .. } => *status == 429 || *status >= 500,
+            ApiError::AuthRequired(_) | ApiError::Serialization(_) | ApiError::ReadOnly { .. } => {
+                false
+            }
+        }
+    }
+
+    /// True when the server rejected the request shape itself (unknown route,
+    /// method, or query) rather than failing it — how an older backend answers
+    /// an optional endpoint it doesn't implement. Callers use this to fall back
+    /// to a universally supported request.
+    pub fn is_unsupported_endpoint(&self) -> bool {
+        matches!(
+            self,
+            ApiError::Server {
+                status: 400 | 404 | 405 | 501,
+                ..
+            }
+        )
+    }
+
+    /// True when the server has no such route or method at all. Unlike
+    /// [`is_unsupported_endpoint`](Self::is_unsupported_endpoint) a 400
+    /// doesn't count: answering a POST, it means the body was rejected, and
+    /// the reason is worth showing rather than reading as "not supported".
+    pub fn is_unsupported_route(&self) -> bool {
+        matches!(
+            self,
+            ApiError::Server {
+                status: 404 | 405 | 501,
+                ..
+            }
+        )
+    }
+
+    /// True when provisioning lost a race for a network address: another
+    /// instance took it between our lookup and the request.
+    pub fn is_address_in_use(&self) -> bool {
+        match self {
+            ApiError::Server {
+                status: 409,
+                reason,
+            } => {
+                let reason = reason.to_ascii_lowercase();
+                reason.contains("in use") || reason.contains("already assigned")
+            }
+            _ => false,
+        }
+    }
 }
 
 /// Extract a human-readable error reason from an HTTP error response body.
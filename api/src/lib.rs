@@ -1,14 +1,23 @@
 pub mod auth;
+pub mod cache;
 pub mod client;
+pub mod distribution;
 pub mod error;
 pub mod models;
+pub mod session;
+pub mod timestamp;
+pub mod trace;
+pub mod version;
 
-#[cfg(feature = "test-support")]
+#[cfg(any(test, feature = "test-support"))]
 pub mod test_support;
 
-pub use auth::{AuthSession, AuthStore};
-pub use client::{API_HOST_ENV, ApiClient, DEFAULT_API_HOST, HttpApiClient};
+pub use auth::{Account, Accounts, AuthSession, AuthStore};
+pub use cache::CachingClient;
+pub use client::{API_HOST_ENV, ApiClient, DEFAULT_API_HOST, HttpApiClient, TOKEN_ENV};
 pub use error::{ApiError, Result};
+pub use trace::TraceContext;
+pub use version::ApiVersion;
 
 /// The unisrv config directory, `~/.unisrv` — the single home for the auth store,
 /// remembered preferences, and any other per-user state. `None` if the home
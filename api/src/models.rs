@@ -1,9 +1,161 @@
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::net::{Ipv4Addr, Ipv6Addr};
 use uuid::Uuid;
 
+use crate::timestamp;
+
+// ── API Tokens ──
+
+/// What an API token may do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TokenScope {
+    /// Everything the user who created it can do.
+    #[default]
+    Full,
+    /// Reads only; the server refuses anything else.
+    ReadOnly,
+}
+
+impl TokenScope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TokenScope::Full => "full",
+            TokenScope::ReadOnly => "read-only",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreateApiTokenRequest {
+    pub name: String,
+    pub scope: TokenScope,
+    /// `None` for a token that never expires.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// A newly created token. `token` is the only time its secret is shown.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreatedApiToken {
+    pub id: Uuid,
+    pub name: String,
+    pub token: String,
+    pub scope: TokenScope,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiTokenListItem {
+    pub id: Uuid,
+    pub name: String,
+    pub scope: TokenScope,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiTokenListResponse {
+    pub tokens: Vec<ApiTokenListItem>,
+}
+
+// ── Service Accounts ──
+
+/// A non-personal identity for a pipeline, limited to `scopes` such as
+/// `instances:write` or `services:read`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreateServiceAccountRequest {
+    pub name: String,
+    pub scopes: Vec<String>,
+    /// `None` for a token that never expires.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// A newly created service account. `token` is the only time its secret is
+/// shown.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreatedServiceAccount {
+    pub id: Uuid,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub token: String,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServiceAccountListItem {
+    pub id: Uuid,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServiceAccountListResponse {
+    pub service_accounts: Vec<ServiceAccountListItem>,
+}
+
+// ── Regions ──
+
+/// Somewhere instances, workers and services can run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Region {
+    /// What provision requests name it by, e.g. `eu-central`.
+    pub name: String,
+    /// Where it is, e.g. `Frankfurt, DE`.
+    #[serde(default)]
+    pub location: Option<String>,
+    /// False while it takes no new workloads.
+    #[serde(default = "region_available")]
+    pub available: bool,
+    /// Its edge, e.g. `https://edge.eu-central.unisrv.io`, which
+    /// `region ping` measures the round trip to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+}
+
+fn region_available() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegionListResponse {
+    pub regions: Vec<Region>,
+}
+
+// ── Events ──
+
+/// Something that happened in the account, from the audit log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActivityEvent {
+    pub id: Uuid,
+    /// What happened, e.g. `instance.created`, `instance.stopped`,
+    /// `deployment.rolled_out` or `certificate.issued`.
+    pub kind: String,
+    pub at: DateTime<Utc>,
+    #[serde(default)]
+    pub environment_id: Option<Uuid>,
+    /// The instance, deployment or host it happened to.
+    #[serde(default)]
+    pub resource_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventListResponse {
+    pub events: Vec<ActivityEvent>,
+    /// Set when there are more events; pass it back for the next page.
+    #[serde(default)]
+    pub next_cursor: Option<String>,
+}
+
 // ── Environments ──
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -71,12 +223,41 @@ pub struct InstanceConfiguration {
     pub args: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub env: Option<BTreeMap<String, String>>,
+    /// Guest hostname. The server defaults it to the instance name, or the id.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    /// Free-form key/values served back to the guest in
+    /// [`GuestMetadata::metadata`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<BTreeMap<String, String>>,
+    /// Run to completion, in order, before `container_image` starts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub init_containers: Option<Vec<AuxContainer>>,
+    /// Run alongside `container_image` for the instance's lifetime.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sidecars: Option<Vec<AuxContainer>>,
+    /// Which of a multi-platform image's manifests to run, as
+    /// `os/arch[/variant]`. The server picks `linux/amd64` without one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platform: Option<String>,
+}
+
+/// An init or sidecar container, sharing the instance's VM, network and
+/// environment with the main container.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuxContainer {
+    pub image: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub args: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct InstanceNetworkConfig {
     pub network_id: Uuid,
-    pub instance_ip: String,
+    /// Address to join at. Omitted to have the server allocate one from the
+    /// network's pool, on backends that support it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance_ip: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -86,12 +267,50 @@ pub struct InstanceProvisionRequest {
     pub region: String,
     pub vcpu_ratio: f64,
     pub vcpu_count: u8,
+    /// The memory limit.
     pub memory_mb: u32,
+    /// Guaranteed memory, when less than the limit; the rest is burst
+    /// capacity the instance may be reclaimed from under pressure.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_request_mb: Option<u32>,
     pub configuration: InstanceConfiguration,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub container_registry_token: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub network: Option<InstanceNetworkConfig>,
+    /// Notes about how the instance was started, kept with it and shown by
+    /// `instance show`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<BTreeMap<String, String>>,
+}
+
+/// Where a guest reads [`GuestMetadata`] about itself. Link-local, so it
+/// needs no credentials and is unreachable from outside the instance.
+pub const GUEST_METADATA_URL: &str = "http://169.254.169.254/v1/instance";
+
+/// The in-guest metadata document: a plain `GET` of [`GUEST_METADATA_URL`]
+/// from inside an instance returns this as JSON. Fields are only ever added,
+/// so guests should ignore ones they don't know.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GuestMetadata {
+    pub instance_id: Uuid,
+    pub name: Option<String>,
+    pub hostname: String,
+    pub environment_id: Uuid,
+    pub region: String,
+    /// Every private network the instance is on, with its address there.
+    #[serde(default)]
+    pub networks: Vec<GuestNetwork>,
+    /// The key/values given at launch (`--metadata`).
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GuestNetwork {
+    pub network_id: Uuid,
+    pub name: String,
+    pub ip: String,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -99,10 +318,33 @@ pub struct InstanceProvisionResponse {
     pub id: Uuid,
 }
 
+/// Start a WebAssembly module on the lightweight isolate runtime rather than
+/// in a VM. The result is an instance like any other, so it's listed, shown
+/// and logged the same way.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkerProvisionRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub region: String,
+    /// The module's bytes, base64-encoded.
+    pub module: String,
+    pub memory_mb: u32,
+    /// CPU time the worker may use before it's stopped.
+    pub cpu_time_ms: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub args: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<BTreeMap<String, String>>,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct InstanceDeprovisionRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub timeout_ms: Option<u32>,
+    /// Keep the stopped instance's configuration and disk this long, so it
+    /// can be restored; gone as soon as it stops when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retain_secs: Option<u64>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -114,6 +356,13 @@ pub struct DeploymentInfo {
     pub name: String,
 }
 
+/// Bring a standalone instance under a deployment of this name, created with
+/// the instance's own configuration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AdoptInstanceRequest {
+    pub deployment: String,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct InstanceListEntry {
     pub id: Uuid,
@@ -122,6 +371,9 @@ pub struct InstanceListEntry {
     pub container_image: String,
     pub created_at: NaiveDateTime,
     pub deployment: Option<DeploymentInfo>,
+    /// A stopped instance that can be restored until then.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retained_until: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -161,6 +413,24 @@ pub struct InstanceDetailResponse {
     pub deployment: Option<DeploymentInfo>,
     pub service_targets: Option<Vec<ServiceTargetInfo>>,
     pub proxied_ports: Option<Vec<ProxiedPortInfo>>,
+    /// Absent from older backends.
+    #[serde(default)]
+    pub resources: Option<InstanceResources>,
+    /// What the instance was provisioned with in
+    /// [`InstanceProvisionRequest::annotations`].
+    #[serde(default)]
+    pub annotations: Option<BTreeMap<String, String>>,
+}
+
+/// What an instance was sized with. CPU is guaranteed as `vcpu_ratio` of
+/// each of its `vcpu_count` vCPUs, which it may burst to in full.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstanceResources {
+    pub vcpu_count: u8,
+    pub vcpu_ratio: f64,
+    pub memory_mb: u32,
+    #[serde(default)]
+    pub memory_request_mb: Option<u32>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -171,6 +441,15 @@ pub struct LogMessage {
     pub message: Option<String>,
 }
 
+/// A historical log search. Bounds are epoch milliseconds, `to_ms`
+/// exclusive; `grep` matches a substring of the frame's text.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LogSearch {
+    pub from_ms: Option<u64>,
+    pub to_ms: Option<u64>,
+    pub grep: Option<String>,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CreateInstanceTCPProxyRequest {
     pub port: u16,
@@ -188,6 +467,9 @@ pub struct CreateInstanceTCPProxyResponse {
 pub struct CreateInternalNetworkRequest {
     pub name: String,
     pub ipv4_cidr: String,
+    /// Marks the resource as owned by this project's `unisrv up`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub managed_by: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -196,6 +478,10 @@ pub struct NetworkListItem {
     pub name: String,
     pub ipv4_cidr: String,
     pub instance_count: Option<usize>,
+    /// The project whose `unisrv up` owns this, if any. Resources made any
+    /// other way (`network create`, the dashboard) have none.
+    #[serde(default)]
+    pub managed_by: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -219,13 +505,181 @@ pub struct NetworkResponse {
     pub instances: Vec<InstanceInfo>,
 }
 
+/// An address held on a network for a future instance. Auto-assignment skips
+/// it; naming it explicitly still works.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IpReservation {
+    pub id: Uuid,
+    pub ip: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreateIpReservationRequest {
+    pub ip: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IpReservationListResponse {
+    pub reservations: Vec<IpReservation>,
+}
+
+/// Whether traffic matching a policy rule is let through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyAction {
+    Allow,
+    Deny,
+    /// An action this CLI version doesn't recognize.
+    #[serde(other)]
+    Unknown,
+}
+
+impl std::fmt::Display for PolicyAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            PolicyAction::Allow => "allow",
+            PolicyAction::Deny => "deny",
+            PolicyAction::Unknown => "?",
+        })
+    }
+}
+
+/// Where traffic a policy rule matches comes from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PolicySource {
+    Cidr {
+        cidr: String,
+    },
+    Instance {
+        instance_id: Uuid,
+    },
+    /// A kind of source this CLI version doesn't recognize.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Where traffic a policy rule matches goes: an instance, a port on every
+/// instance, or a port on one instance. At least one is set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PolicyDestination {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance_id: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetworkPolicyRule {
+    pub id: Uuid,
+    pub source: PolicySource,
+    pub destination: PolicyDestination,
+    pub action: PolicyAction,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreateNetworkPolicyRequest {
+    pub source: PolicySource,
+    pub destination: PolicyDestination,
+    pub action: PolicyAction,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetworkPolicyListResponse {
+    pub rules: Vec<NetworkPolicyRule>,
+}
+
+/// A route between two networks, seen from one side: `peer_network_id` is
+/// the other. Peering is symmetric, so both networks list it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetworkPeering {
+    pub id: Uuid,
+    pub peer_network_id: Uuid,
+    pub peer_network_name: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreateNetworkPeeringRequest {
+    pub peer_network_id: Uuid,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetworkPeeringListResponse {
+    pub peerings: Vec<NetworkPeering>,
+}
+
+/// A machine outside the platform given a WireGuard tunnel into a network.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WireGuardPeer {
+    pub id: Uuid,
+    pub name: String,
+    /// The peer's tunnel address, e.g. `10.0.255.2/32`.
+    pub address: String,
+    pub public_key: String,
+    pub created_at: NaiveDateTime,
+    /// `None` until the peer has connected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_handshake_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreateWireGuardPeerRequest {
+    pub name: String,
+    /// The peer's own key. Without one the server generates a key pair and
+    /// returns the private half, once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+}
+
+/// A created peer and what its tunnel config needs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WireGuardPeerConfig {
+    #[serde(flatten)]
+    pub peer: WireGuardPeer,
+    /// The network's gateway, `host:port`.
+    pub endpoint: String,
+    pub server_public_key: String,
+    /// Ranges to route through the tunnel: the network and its peers.
+    pub allowed_ips: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dns: Option<String>,
+    /// Only when the server generated the key pair.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub private_key: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WireGuardPeerListResponse {
+    pub peers: Vec<WireGuardPeer>,
+}
+
 // ── Services ──
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum HTTPLocationTarget {
-    Instance { group: String },
-    Url { url: String },
+    Instance {
+        group: String,
+        /// Replaces the matched location prefix before forwarding, e.g. "/" so
+        /// `/api/users` reaches the backend as `/users`. `None` forwards the
+        /// path unchanged. (URL targets always strip the prefix.)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        rewrite: Option<String>,
+    },
+    Url {
+        url: String,
+    },
+    /// Answer at the edge with a redirect to `url` instead of proxying.
+    Redirect {
+        url: String,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -233,13 +687,114 @@ pub struct HTTPLocation {
     pub path: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub override_404: Option<String>,
+    /// Response headers set by the edge on every response from this location
+    /// (e.g. HSTS, CORS). Omitted when empty so older backends see no change.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    /// Let WebSocket upgrades through to the target. Off, the edge answers an
+    /// `Upgrade: websocket` request as plain HTTP.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub websocket: bool,
+    /// Speak HTTP/2 to the target so gRPC calls pass through intact.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub grpc: bool,
+    /// Cache responses at the edge. Omitted when unset so older backends see
+    /// no change.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<CachePolicy>,
     pub target: HTTPLocationTarget,
 }
 
+/// How the edge caches a location's responses.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CachePolicy {
+    /// How long a response is served from the cache before it's fetched
+    /// again.
+    pub ttl_secs: u64,
+    /// How much longer an expired response may still be served while a fresh
+    /// one is fetched in the background.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stale_while_revalidate_secs: Option<u64>,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct HTTPServiceConfig {
     pub locations: Vec<HTTPLocation>,
     pub allow_http: bool,
+    /// Credentials the edge requires (HTTP basic auth) before serving any
+    /// request. Empty means no authentication.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub basic_auth: Vec<BasicAuthCredential>,
+    /// Source IPs/CIDRs allowed to reach the service; everyone else gets a
+    /// 403 at the edge. Empty means no restriction.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_ips: Vec<String>,
+    /// TLS behaviour at the edge. The default leaves everything to the
+    /// platform.
+    #[serde(default, skip_serializing_if = "TlsPolicy::is_default")]
+    pub tls: TlsPolicy,
+    /// Compress text responses at the edge (gzip or brotli, whichever the
+    /// client accepts). `None` leaves it to the platform.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TlsPolicy {
+    /// Lowest TLS version the edge accepts; `None` means the platform default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_version: Option<TlsVersion>,
+    /// Send `Strict-Transport-Security` on HTTPS responses.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub hsts: bool,
+}
+
+impl TlsPolicy {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum TlsVersion {
+    #[serde(rename = "1.2")]
+    Tls12,
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+impl TlsVersion {
+    pub const ALL: [TlsVersion; 2] = [TlsVersion::Tls12, TlsVersion::Tls13];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TlsVersion::Tls12 => "1.2",
+            TlsVersion::Tls13 => "1.3",
+        }
+    }
+}
+
+impl std::fmt::Display for TlsVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for TlsVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        TlsVersion::ALL
+            .into_iter()
+            .find(|v| v.as_str() == s)
+            .ok_or_else(|| format!("unsupported TLS version {s:?} (expected 1.2 or 1.3)"))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BasicAuthCredential {
+    pub username: String,
+    pub password: String,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -255,6 +810,9 @@ pub struct ServiceProvisionRequest {
     pub name: String,
     pub configuration: HTTPServiceConfig,
     pub instance_targets: Vec<ServiceInstanceTarget>,
+    /// Marks the resource as owned by this project's `unisrv up`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub managed_by: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -270,6 +828,10 @@ pub struct ServiceListItem {
     pub base_host: String,
     /// Custom hosts bound to this service (excludes the derived base host).
     pub custom_hosts: Vec<String>,
+    /// The project whose `unisrv up` owns this, if any. Resources made any
+    /// other way (`network create`, the dashboard) have none.
+    #[serde(default)]
+    pub managed_by: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -281,7 +843,8 @@ pub struct ServiceListResponse {
 pub struct ServiceProviderDetail {
     pub id: Uuid,
     pub node_id: Uuid,
-    pub created_at: NaiveDateTime,
+    #[serde(deserialize_with = "timestamp::deserialize")]
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -291,6 +854,18 @@ pub struct ServiceTargetDetail {
     pub target_group: String,
     pub instance_port: u16,
     pub created_at: NaiveDateTime,
+    /// The edge's latest health check. Absent from backends that don't report
+    /// target health.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health: Option<TargetHealth>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TargetHealth {
+    /// `healthy`, `unhealthy` or `draining`; kept as a string so a new status
+    /// still displays.
+    pub status: String,
+    pub last_checked_at: Option<NaiveDateTime>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -309,13 +884,59 @@ pub struct ServiceDetailResponse {
     pub custom_hosts: Vec<String>,
     pub configuration: serde_json::Value,
     pub environment_id: Uuid,
-    pub created_at: NaiveDateTime,
-    pub updated_at: NaiveDateTime,
+    #[serde(deserialize_with = "timestamp::deserialize")]
+    pub created_at: DateTime<Utc>,
+    #[serde(deserialize_with = "timestamp::deserialize")]
+    pub updated_at: DateTime<Utc>,
     pub providers: Vec<ServiceProviderDetail>,
     pub targets: Vec<ServiceTargetDetail>,
     pub statistics: Option<ServiceStatistics>,
 }
 
+/// Request metrics for a service over a trailing window, broken down by
+/// location and by target.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServiceMetricsResponse {
+    /// The window the figures cover, in seconds.
+    pub window_seconds: u64,
+    #[serde(default)]
+    pub locations: Vec<LocationMetrics>,
+    #[serde(default)]
+    pub targets: Vec<TargetMetrics>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LocationMetrics {
+    pub path: String,
+    #[serde(flatten)]
+    pub metrics: RequestMetrics,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TargetMetrics {
+    pub group: String,
+    /// The instance serving the group, when the target is a single instance.
+    #[serde(default)]
+    pub instance_id: Option<Uuid>,
+    #[serde(flatten)]
+    pub metrics: RequestMetrics,
+}
+
+/// Counts and latency percentiles for one slice of traffic. Percentiles are
+/// absent when there were no requests to measure.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RequestMetrics {
+    pub requests: u64,
+    /// Requests answered with a 5xx status.
+    pub errors: u64,
+    #[serde(default)]
+    pub p50_ms: Option<f64>,
+    #[serde(default)]
+    pub p95_ms: Option<f64>,
+    #[serde(default)]
+    pub p99_ms: Option<f64>,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CreateTargetResponse {
     pub target_id: Uuid,
@@ -352,9 +973,12 @@ pub struct HostResponse {
     pub user_id: Uuid,
     pub service_id: Option<Uuid>,
     pub certificate_type: Option<CertificateType>,
-    pub certificate_valid_until: Option<NaiveDateTime>,
-    pub created_at: NaiveDateTime,
-    pub updated_at: NaiveDateTime,
+    #[serde(default, deserialize_with = "timestamp::option::deserialize")]
+    pub certificate_valid_until: Option<DateTime<Utc>>,
+    #[serde(deserialize_with = "timestamp::deserialize")]
+    pub created_at: DateTime<Utc>,
+    #[serde(deserialize_with = "timestamp::deserialize")]
+    pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -363,6 +987,84 @@ pub struct DnsConfigResponse {
     pub ipv6_addresses: Vec<Ipv6Addr>,
 }
 
+/// The certificate a host is served with, as issued.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HostCertificate {
+    /// The issuer's common name, e.g. `R11`.
+    pub issuer: String,
+    /// Every name the certificate covers.
+    #[serde(default)]
+    pub sans: Vec<String>,
+    #[serde(deserialize_with = "timestamp::deserialize")]
+    pub not_before: DateTime<Utc>,
+    #[serde(deserialize_with = "timestamp::deserialize")]
+    pub not_after: DateTime<Utc>,
+}
+
+/// A DNS-01 challenge for a host's certificate, which is how a wildcard host
+/// is validated: the TXT record to publish, and how validation is going.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DnsChallenge {
+    /// e.g. `_acme-challenge.example.com`.
+    pub record_name: String,
+    pub record_value: String,
+    pub status: ChallengeStatus,
+    /// Why validation failed, for an `invalid` challenge.
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChallengeStatus {
+    /// The certificate authority hasn't seen the record yet.
+    Pending,
+    Valid,
+    Invalid,
+    /// A status this CLI version doesn't recognize.
+    #[serde(other)]
+    Unknown,
+}
+
+/// A DNS record published under a claimed host.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DnsRecord {
+    pub id: Uuid,
+    /// Relative to the host: `api` for `api.example.com`, `@` for the host
+    /// itself.
+    pub name: String,
+    #[serde(rename = "type")]
+    pub record_type: DnsRecordType,
+    pub value: String,
+    #[serde(default)]
+    pub ttl: Option<u32>,
+    #[serde(deserialize_with = "timestamp::deserialize")]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreateDnsRecordRequest {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub record_type: DnsRecordType,
+    pub value: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum DnsRecordType {
+    A,
+    Aaaa,
+    Cname,
+    Mx,
+    Txt,
+    /// A record type this CLI version doesn't recognize.
+    #[serde(other)]
+    Unknown,
+}
+
 // ── Deployments ──
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -379,6 +1081,10 @@ pub struct DeploymentConfiguration {
     pub memory_mb: u32,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub instance_port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub init_containers: Option<Vec<AuxContainer>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sidecars: Option<Vec<AuxContainer>>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -397,6 +1103,9 @@ pub struct CreateDeploymentRequest {
     #[serde(default)]
     pub network_id: Option<Uuid>,
     pub configuration: DeploymentConfiguration,
+    /// Marks the resource as owned by this project's `unisrv up`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub managed_by: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -425,6 +1134,10 @@ pub struct DeploymentListEntry {
     pub replicas: u32,
     pub container_image: String,
     pub created_at: NaiveDateTime,
+    /// The project whose `unisrv up` owns this, if any. Resources made any
+    /// other way (`network create`, the dashboard) have none.
+    #[serde(default)]
+    pub managed_by: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -465,12 +1178,43 @@ pub struct DeploymentDetailResponse {
     pub updated_at: NaiveDateTime,
 }
 
+// ── Ownership ──
+
+/// The kinds of resource `unisrv up` declares and can own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ManagedKind {
+    Network,
+    Service,
+    Deployment,
+}
+
+impl ManagedKind {
+    /// The kind's path segment under `/environment/{env_id}`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ManagedKind::Network => "network",
+            ManagedKind::Service => "service",
+            ManagedKind::Deployment => "deployment",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SetManagedByRequest {
+    /// The owning project; `None` releases the resource.
+    pub managed_by: Option<String>,
+}
+
 // ── Container Registries ──
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum RegistryKind {
     Userpass,
+    /// A kind of registry this CLI version doesn't recognize.
+    #[serde(other)]
+    Unknown,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -514,6 +1258,45 @@ pub struct RegistryListResponse {
     pub registries: Vec<RegistryResponse>,
 }
 
+/// A short-lived bearer token for pulling (or pushing) one repository on a
+/// registry, minted by the server from the stored credentials.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScopedTokenResponse {
+    pub token: String,
+    #[serde(default)]
+    pub expires_in_seconds: Option<u64>,
+}
+
+/// A repository in the platform's own registry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HostedRepository {
+    pub name: String,
+    pub tag_count: u64,
+    /// Storage the repository's blobs take, counting each blob once.
+    pub size_bytes: u64,
+    #[serde(default)]
+    pub last_pushed_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HostedRepositoryListResponse {
+    pub repositories: Vec<HostedRepository>,
+}
+
+/// A tag in one of the platform registry's repositories.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HostedImage {
+    pub tag: String,
+    pub digest: String,
+    pub size_bytes: u64,
+    pub pushed_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HostedImageListResponse {
+    pub images: Vec<HostedImage>,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TestRegistryResponse {
     pub ok: bool,
@@ -557,6 +1340,43 @@ mod tests {
         assert_eq!(d.custom_hosts, vec!["shop.acme.com".to_string()]);
     }
 
+    #[test]
+    fn policy_rules_from_a_newer_server_still_parse() {
+        let json = serde_json::json!({
+            "id": "00000000-0000-0000-0000-000000000001",
+            "source": { "type": "service_account", "account": "ci" },
+            "destination": { "port": 22 },
+            "action": "log",
+            "created_at": "2024-01-01T00:00:00",
+            "priority": 10
+        });
+        let rule: NetworkPolicyRule = serde_json::from_value(json).unwrap();
+        assert_eq!(rule.source, PolicySource::Unknown);
+        assert_eq!(rule.action, PolicyAction::Unknown);
+    }
+
+    #[test]
+    fn guest_metadata_matches_the_documented_shape() {
+        let json = serde_json::json!({
+            "instance_id": "00000000-0000-0000-0000-000000000001",
+            "name": "worker",
+            "hostname": "worker-1",
+            "environment_id": "00000000-0000-0000-0000-000000000002",
+            "region": "dev",
+            "networks": [{
+                "network_id": "00000000-0000-0000-0000-000000000003",
+                "name": "backend",
+                "ip": "10.0.0.4"
+            }],
+            "metadata": { "tier": "batch" },
+            "added_later": true
+        });
+        let m: GuestMetadata = serde_json::from_value(json).unwrap();
+        assert_eq!(m.hostname, "worker-1");
+        assert_eq!(m.networks[0].ip, "10.0.0.4");
+        assert_eq!(m.metadata["tier"], "batch");
+    }
+
     #[test]
     fn environment_response_carries_slug() {
         let json = serde_json::json!({
@@ -643,6 +1463,8 @@ mod tests {
                 vcpu_count: 1,
                 memory_mb: 256,
                 instance_port: None,
+                init_containers: None,
+                sidecars: None,
             },
         };
         let v = serde_json::to_value(&req).unwrap();
@@ -661,6 +1483,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn http_location_omits_empty_headers_and_parses_redirect() {
+        // Locations without headers must serialize exactly as before, and a
+        // config stored without the field must still parse.
+        let loc = HTTPLocation {
+            path: "/".into(),
+            override_404: None,
+            headers: BTreeMap::new(),
+            websocket: false,
+            grpc: false,
+            cache: None,
+            target: HTTPLocationTarget::Redirect {
+                url: "https://example.com".into(),
+            },
+        };
+        let v = serde_json::to_value(&loc).unwrap();
+        for field in ["headers", "websocket", "grpc", "cache"] {
+            assert!(!v.as_object().unwrap().contains_key(field), "{v}");
+        }
+        assert_eq!(v["target"]["type"], "redirect");
+
+        let parsed: HTTPLocation = serde_json::from_value(v).unwrap();
+        assert_eq!(parsed, loc);
+    }
+
     #[test]
     fn certificate_type_round_trips_snake_case() {
         assert_eq!(
@@ -0,0 +1,304 @@
+//! Record-and-replay of API traffic, for reproducible bug reports.
+//!
+//! `--record FILE` tapes every request [`HttpApiClient`] sends and the
+//! response it got back; `--replay FILE` answers from the tape instead of the
+//! network, so a maintainer can re-run a user's failing `up` without their
+//! account. Replay matches each request to the first unused exchange with the
+//! same method and path, not strictly in order, because independent calls run
+//! concurrently and may interleave differently from run to run.
+//!
+//! Tapes are meant to be attached to bug reports, so credentials never reach
+//! the file: tokens, passwords and secrets are blanked by key, and deployment
+//! `env` values are blanked wholesale. Headers aren't recorded at all. Log
+//! streams (WebSocket) aren't taped.
+//!
+//! [`HttpApiClient`]: crate::HttpApiClient
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{ApiError, Result};
+
+const REDACTED: &str = "[redacted]";
+
+/// Keys whose values are always blanked, matched case-insensitively as
+/// substrings so `refresh_token` and `registry_password` are caught too.
+const SENSITIVE_KEYS: &[&str] = &["token", "password", "secret", "authorization"];
+
+/// One request and what came back.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Exchange {
+    pub method: String,
+    /// Path and query, relative to the API base URL.
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<Value>,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Outcome {
+    Response {
+        status: u16,
+        body: String,
+    },
+    /// The request never got a response (connection refused, timeout, ...).
+    TransportError {
+        error: String,
+    },
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SessionFile {
+    exchanges: Vec<Exchange>,
+}
+
+/// Where [`HttpApiClient`](crate::HttpApiClient) sends its traffic besides
+/// (or instead of) the network.
+pub enum Tape {
+    /// Pass through to the network and append each exchange to `path`.
+    Record {
+        path: PathBuf,
+        exchanges: Mutex<Vec<Exchange>>,
+    },
+    /// Answer from recorded exchanges without touching the network.
+    Replay {
+        exchanges: Mutex<VecDeque<Exchange>>,
+    },
+}
+
+impl Tape {
+    /// Start recording to `path`, truncating it now so a bad path fails
+    /// before any request is made.
+    pub fn record(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+        write_session(&path, &[])?;
+        Ok(Tape::Record {
+            path,
+            exchanges: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Load a session recorded with [`Tape::record`].
+    pub fn replay(path: &Path) -> anyhow::Result<Self> {
+        let data = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("cannot read session {}: {e}", path.display()))?;
+        let session: SessionFile = serde_json::from_str(&data)
+            .map_err(|e| anyhow::anyhow!("{} is not a recorded session: {e}", path.display()))?;
+        Ok(Tape::Replay {
+            exchanges: Mutex::new(session.exchanges.into()),
+        })
+    }
+
+    pub fn is_replay(&self) -> bool {
+        matches!(self, Tape::Replay { .. })
+    }
+
+    /// Execute `request` (relative to `base_url`) through the tape.
+    pub(crate) async fn execute(
+        &self,
+        client: &reqwest::Client,
+        base_url: &str,
+        request: reqwest::Request,
+    ) -> Result<reqwest::Response> {
+        let method = request.method().to_string();
+        let url = request.url().as_str();
+        let path = url.strip_prefix(base_url).unwrap_or(url).to_string();
+
+        match self {
+            Tape::Replay { exchanges } => {
+                let mut exchanges = exchanges.lock().unwrap();
+                let index = exchanges
+                    .iter()
+                    .position(|e| e.method == method && e.path == path)
+                    .ok_or_else(|| {
+                        ApiError::Other(anyhow::anyhow!(
+                            "replay: no recorded response left for {method} {path}"
+                        ))
+                    })?;
+                let exchange = exchanges.remove(index).expect("index is in range");
+                into_response(exchange.outcome)
+            }
+            Tape::Record {
+                path: file,
+                exchanges,
+            } => {
+                let request_body = request
+                    .body()
+                    .and_then(|b| b.as_bytes())
+                    .and_then(|b| serde_json::from_slice::<Value>(b).ok())
+                    .map(redact);
+                let (outcome, result) = match client.execute(request).await {
+                    Ok(resp) => {
+                        let status = resp.status().as_u16();
+                        let body = resp.text().await?;
+                        let outcome = Outcome::Response {
+                            status,
+                            body: redact_text(&body),
+                        };
+                        (outcome, build_response(status, body))
+                    }
+                    Err(e) => {
+                        let outcome = Outcome::TransportError {
+                            error: e.to_string(),
+                        };
+                        (outcome, Err(e.into()))
+                    }
+                };
+                let exchange = Exchange {
+                    method,
+                    path,
+                    request: request_body,
+                    outcome,
+                };
+                let mut exchanges = exchanges.lock().unwrap();
+                exchanges.push(exchange);
+                // Rewritten after every exchange so a crash mid-run still
+                // leaves a usable session behind.
+                if let Err(e) = write_session(file, &exchanges) {
+                    tracing::warn!("failed to write session {}: {e}", file.display());
+                }
+                result
+            }
+        }
+    }
+}
+
+fn write_session(path: &Path, exchanges: &[Exchange]) -> anyhow::Result<()> {
+    let session = SessionFile {
+        exchanges: exchanges.to_vec(),
+    };
+    std::fs::write(path, serde_json::to_string_pretty(&session)?)
+        .map_err(|e| anyhow::anyhow!("cannot write session {}: {e}", path.display()))
+}
+
+fn into_response(outcome: Outcome) -> Result<reqwest::Response> {
+    match outcome {
+        Outcome::Response { status, body } => build_response(status, body),
+        Outcome::TransportError { error } => {
+            Err(ApiError::Other(anyhow::anyhow!("{error} (replayed)")))
+        }
+    }
+}
+
+fn build_response(status: u16, body: String) -> Result<reqwest::Response> {
+    let response = http::Response::builder()
+        .status(status)
+        .header(http::header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .map_err(|e| ApiError::Other(e.into()))?;
+    Ok(reqwest::Response::from(response))
+}
+
+/// Redact a response body if it's JSON; other bodies (plain-text errors) are
+/// kept as they are.
+fn redact_text(body: &str) -> String {
+    match serde_json::from_str::<Value>(body) {
+        Ok(value) => redact(value).to_string(),
+        Err(_) => body.to_string(),
+    }
+}
+
+/// Blank sensitive values anywhere in `value`. Also used to keep credentials
+/// out of what commands print as JSON.
+pub fn redact(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| {
+                    let lower = key.to_ascii_lowercase();
+                    let value = if SENSITIVE_KEYS.iter().any(|k| lower.contains(k)) {
+                        Value::String(REDACTED.into())
+                    } else if lower == "env" {
+                        blank_values(value)
+                    } else {
+                        redact(value)
+                    };
+                    (key, value)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(redact).collect()),
+        other => other,
+    }
+}
+
+/// Keep an env map's names (they help debugging) but not its values.
+fn blank_values(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, _)| (key, Value::String(REDACTED.into())))
+                .collect(),
+        ),
+        Value::Null => Value::Null,
+        _ => Value::String(REDACTED.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn redacts_credentials_and_env_values_but_keeps_structure() {
+        let body = json!({
+            "token": "abc",
+            "refresh_token": "def",
+            "user_id": "u1",
+            "registry": {"hostname": "ghcr.io", "password": "hunter2"},
+            "deployments": [{"configuration": {"env": {"DATABASE_URL": "postgres://x"}}}],
+        });
+        assert_eq!(
+            redact(body),
+            json!({
+                "token": REDACTED,
+                "refresh_token": REDACTED,
+                "user_id": "u1",
+                "registry": {"hostname": "ghcr.io", "password": REDACTED},
+                "deployments": [{"configuration": {"env": {"DATABASE_URL": REDACTED}}}],
+            })
+        );
+    }
+
+    #[test]
+    fn non_json_bodies_pass_through() {
+        assert_eq!(redact_text("Bad Gateway"), "Bad Gateway");
+    }
+
+    #[test]
+    fn session_file_round_trips_responses_and_transport_errors() {
+        let exchanges = vec![
+            Exchange {
+                method: "GET".into(),
+                path: "/environments".into(),
+                request: None,
+                outcome: Outcome::Response {
+                    status: 200,
+                    body: "{}".into(),
+                },
+            },
+            Exchange {
+                method: "POST".into(),
+                path: "/environment".into(),
+                request: Some(json!({"name": "prod"})),
+                outcome: Outcome::TransportError {
+                    error: "connection reset".into(),
+                },
+            },
+        ];
+        let file = SessionFile {
+            exchanges: exchanges.clone(),
+        };
+        let parsed: SessionFile =
+            serde_json::from_str(&serde_json::to_string(&file).unwrap()).unwrap();
+        assert_eq!(parsed.exchanges, exchanges);
+    }
+}
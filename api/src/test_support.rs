@@ -1,7 +1,7 @@
 //! Test doubles for [`ApiClient`], available behind the `test-support` feature.
 
 use async_trait::async_trait;
-use chrono::Duration;
+use chrono::{DateTime, Duration, Utc};
 use futures_util::StreamExt;
 use std::collections::VecDeque;
 use std::sync::Mutex;
@@ -12,6 +12,8 @@ use crate::client::{ApiClient, LogStream};
 use crate::error::{ApiError, Result};
 use crate::models::*;
 
+pub mod server;
+
 /// Scripted outcome for a [`MockApiClient::stream_instance_logs`] call.
 pub enum StreamLogsResponse {
     /// The upgrade failed before any frame arrived (e.g. instance not found).
@@ -34,22 +36,55 @@ pub struct CallLog {
     pub claim_host_calls: Vec<ClaimHostRequest>,
     pub get_hosts_dns_config_calls: u32,
     pub request_host_cert_calls: Vec<Uuid>,
+    pub get_host_certificate_calls: Vec<Uuid>,
+    pub start_dns_challenge_calls: Vec<Uuid>,
+    pub get_dns_challenge_calls: Vec<Uuid>,
+    pub create_service_target_calls: Vec<(Uuid, Uuid, ServiceInstanceTarget)>,
+    pub delete_service_target_calls: Vec<(Uuid, Uuid, Uuid)>,
+    pub complete_dns_challenge_calls: Vec<Uuid>,
     pub link_host_calls: Vec<(Uuid, Uuid)>,
     pub unlink_host_calls: Vec<(Uuid, Uuid)>,
+    pub list_host_records_calls: Vec<Uuid>,
+    pub create_host_record_calls: Vec<(Uuid, CreateDnsRecordRequest)>,
+    pub delete_host_record_calls: Vec<(Uuid, Uuid)>,
+    pub delete_host_calls: Vec<Uuid>,
     pub list_hosts_calls: u32,
+    pub list_regions_calls: u32,
+    pub list_events_calls: Vec<(DateTime<Utc>, Option<String>)>,
     pub list_environments_calls: u32,
     pub create_environment_calls: Vec<CreateEnvironmentRequest>,
     pub delete_environment_calls: Vec<Uuid>,
     pub list_instances_calls: Vec<Uuid>,
+    pub find_instances_by_name_calls: Vec<(Uuid, String)>,
     pub get_instance_logs_calls: Vec<(Uuid, Uuid)>,
+    pub search_instance_logs_calls: Vec<(Uuid, Uuid, LogSearch)>,
     pub stream_instance_logs_calls: Vec<(Uuid, Uuid)>,
+    pub provision_instance_calls: Vec<(Uuid, InstanceProvisionRequest)>,
+    pub provision_worker_calls: Vec<(Uuid, WorkerProvisionRequest)>,
     pub deprovision_instance_calls: Vec<(Uuid, Uuid, Option<InstanceDeprovisionRequest>)>,
+    pub restore_instance_calls: Vec<(Uuid, Uuid)>,
     pub create_network_calls: Vec<(Uuid, CreateInternalNetworkRequest)>,
     pub delete_network_calls: Vec<(Uuid, Uuid)>,
     pub list_networks_calls: Vec<Uuid>,
     pub get_network_calls: Vec<(Uuid, Uuid)>,
+    pub get_instance_calls: Vec<(Uuid, Uuid)>,
+    pub attach_instance_network_calls: Vec<(Uuid, Uuid, InstanceNetworkConfig)>,
+    pub detach_instance_network_calls: Vec<(Uuid, Uuid)>,
+    pub adopt_instance_calls: Vec<(Uuid, Uuid, AdoptInstanceRequest)>,
+    pub list_ip_reservations_calls: Vec<(Uuid, Uuid)>,
+    pub create_ip_reservation_calls: Vec<(Uuid, Uuid, CreateIpReservationRequest)>,
+    pub list_network_policies_calls: Vec<(Uuid, Uuid)>,
+    pub create_network_policy_calls: Vec<(Uuid, Uuid, CreateNetworkPolicyRequest)>,
+    pub delete_network_policy_calls: Vec<(Uuid, Uuid, Uuid)>,
+    pub list_network_peerings_calls: Vec<(Uuid, Uuid)>,
+    pub create_network_peering_calls: Vec<(Uuid, Uuid, CreateNetworkPeeringRequest)>,
+    pub delete_network_peering_calls: Vec<(Uuid, Uuid, Uuid)>,
+    pub list_wireguard_peers_calls: Vec<(Uuid, Uuid)>,
+    pub create_wireguard_peer_calls: Vec<(Uuid, Uuid, CreateWireGuardPeerRequest)>,
+    pub delete_wireguard_peer_calls: Vec<(Uuid, Uuid, Uuid)>,
     pub list_services_calls: Vec<Uuid>,
     pub get_service_calls: Vec<(Uuid, Uuid)>,
+    pub get_service_metrics_calls: Vec<(Uuid, Uuid, String)>,
     pub list_deployments_calls: Vec<Uuid>,
     pub get_deployment_calls: Vec<(Uuid, Uuid)>,
     pub provision_service_calls: Vec<(Uuid, ServiceProvisionRequest)>,
@@ -58,11 +93,23 @@ pub struct CallLog {
     pub update_deployment_calls: Vec<(Uuid, Uuid, UpdateDeploymentRequest)>,
     pub delete_service_calls: Vec<(Uuid, Uuid)>,
     pub delete_deployment_calls: Vec<(Uuid, Uuid)>,
+    pub set_managed_by_calls: Vec<(Uuid, ManagedKind, Uuid, SetManagedByRequest)>,
     pub create_registry_calls: Vec<(CreateRegistryRequest, bool)>,
     pub list_registries_calls: u32,
+    pub find_registries_by_hostname_calls: Vec<String>,
     pub update_registry_calls: Vec<(Uuid, UpdateRegistryRequest, bool)>,
     pub delete_registry_calls: Vec<Uuid>,
     pub test_registry_calls: Vec<Uuid>,
+    pub get_scoped_token_calls: Vec<(Uuid, String, bool)>,
+    pub create_api_token_calls: Vec<CreateApiTokenRequest>,
+    pub list_api_tokens_calls: u32,
+    pub revoke_api_token_calls: Vec<Uuid>,
+    pub create_service_account_calls: Vec<CreateServiceAccountRequest>,
+    pub list_service_accounts_calls: u32,
+    pub delete_service_account_calls: Vec<Uuid>,
+    pub list_hosted_repositories_calls: u32,
+    pub list_hosted_images_calls: Vec<String>,
+    pub delete_hosted_image_calls: Vec<(String, String)>,
 }
 
 /// One-shot response slot for a mocked endpoint. Configure with `set`, consume with `take`.
@@ -97,24 +144,81 @@ pub struct MockApiClient {
     pub link_host_responses: Mutex<VecDeque<std::result::Result<HostResponse, ApiError>>>,
     pub unlink_host_responses: Mutex<VecDeque<std::result::Result<HostResponse, ApiError>>>,
     pub list_hosts_response: ResponseSlot<Vec<HostResponse>>,
+    pub list_regions_response: ResponseSlot<RegionListResponse>,
+    pub list_events_responses: Mutex<VecDeque<std::result::Result<EventListResponse, ApiError>>>,
     pub list_environments_response: ResponseSlot<EnvironmentListResponse>,
     pub create_environment_response: ResponseSlot<EnvironmentResponse>,
     pub delete_environment_responses: Mutex<VecDeque<std::result::Result<(), ApiError>>>,
     pub list_instances_responses:
         Mutex<VecDeque<std::result::Result<InstanceListResponse, ApiError>>>,
+    /// Unlike most queues, an empty one doesn't panic: it answers 404, like a
+    /// backend without the filter, so tests that only script the full list
+    /// exercise the fallback path.
+    pub find_instances_by_name_responses:
+        Mutex<VecDeque<std::result::Result<InstanceListResponse, ApiError>>>,
     pub get_instance_logs_responses:
         Mutex<VecDeque<std::result::Result<Vec<LogMessage>, ApiError>>>,
+    /// Answers 404 when empty, like a backend without server-side search.
+    pub search_instance_logs_responses:
+        Mutex<VecDeque<std::result::Result<Vec<LogMessage>, ApiError>>>,
     pub stream_logs_responses: Mutex<VecDeque<StreamLogsResponse>>,
+    pub provision_instance_responses:
+        Mutex<VecDeque<std::result::Result<InstanceProvisionResponse, ApiError>>>,
+    pub provision_worker_responses:
+        Mutex<VecDeque<std::result::Result<InstanceProvisionResponse, ApiError>>>,
+    pub get_instance_responses:
+        Mutex<VecDeque<std::result::Result<InstanceDetailResponse, ApiError>>>,
+    pub attach_instance_network_responses: Mutex<VecDeque<std::result::Result<(), ApiError>>>,
+    pub detach_instance_network_responses: Mutex<VecDeque<std::result::Result<(), ApiError>>>,
+    pub adopt_instance_responses: Mutex<VecDeque<std::result::Result<DeploymentInfo, ApiError>>>,
     pub deprovision_instance_responses: Mutex<VecDeque<std::result::Result<(), ApiError>>>,
+    pub restore_instance_responses:
+        Mutex<VecDeque<std::result::Result<InstanceListEntry, ApiError>>>,
     pub create_network_responses: Mutex<VecDeque<std::result::Result<NetworkResponse, ApiError>>>,
     pub delete_network_responses: Mutex<VecDeque<std::result::Result<(), ApiError>>>,
     pub list_networks_response: ResponseSlot<NetworkListResponse>,
     /// Queue popped FIFO by each `get_network` call — a queue (not a one-shot
     /// slot) because the network drain poll gets the same network repeatedly.
     pub get_network_responses: Mutex<VecDeque<std::result::Result<NetworkResponse, ApiError>>>,
+    /// Answers 404 when empty, like a backend without reservations, so tests
+    /// that resolve a network needn't script it.
+    pub list_ip_reservations_responses:
+        Mutex<VecDeque<std::result::Result<IpReservationListResponse, ApiError>>>,
+    pub create_ip_reservation_responses:
+        Mutex<VecDeque<std::result::Result<IpReservation, ApiError>>>,
+    pub list_network_policies_response: ResponseSlot<NetworkPolicyListResponse>,
+    pub create_network_policy_responses:
+        Mutex<VecDeque<std::result::Result<NetworkPolicyRule, ApiError>>>,
+    pub delete_network_policy_responses: Mutex<VecDeque<std::result::Result<(), ApiError>>>,
+    /// Answers 404 when empty, like a backend without peering.
+    pub list_network_peerings_responses:
+        Mutex<VecDeque<std::result::Result<NetworkPeeringListResponse, ApiError>>>,
+    pub create_network_peering_responses:
+        Mutex<VecDeque<std::result::Result<NetworkPeering, ApiError>>>,
+    pub delete_network_peering_responses: Mutex<VecDeque<std::result::Result<(), ApiError>>>,
+    pub list_wireguard_peers_responses:
+        Mutex<VecDeque<std::result::Result<WireGuardPeerListResponse, ApiError>>>,
+    pub create_wireguard_peer_responses:
+        Mutex<VecDeque<std::result::Result<WireGuardPeerConfig, ApiError>>>,
+    pub delete_wireguard_peer_responses: Mutex<VecDeque<std::result::Result<(), ApiError>>>,
+    pub get_host_certificate_responses:
+        Mutex<VecDeque<std::result::Result<HostCertificate, ApiError>>>,
+    pub start_dns_challenge_responses: Mutex<VecDeque<std::result::Result<DnsChallenge, ApiError>>>,
+    pub create_service_target_responses:
+        Mutex<VecDeque<std::result::Result<CreateTargetResponse, ApiError>>>,
+    pub delete_service_target_responses: Mutex<VecDeque<std::result::Result<(), ApiError>>>,
+    pub get_dns_challenge_responses: Mutex<VecDeque<std::result::Result<DnsChallenge, ApiError>>>,
+    pub complete_dns_challenge_responses:
+        Mutex<VecDeque<std::result::Result<HostResponse, ApiError>>>,
+    pub list_host_records_responses: Mutex<VecDeque<std::result::Result<Vec<DnsRecord>, ApiError>>>,
+    pub create_host_record_responses: Mutex<VecDeque<std::result::Result<DnsRecord, ApiError>>>,
+    pub delete_host_record_responses: Mutex<VecDeque<std::result::Result<(), ApiError>>>,
+    pub delete_host_responses: Mutex<VecDeque<std::result::Result<(), ApiError>>>,
     pub list_services_response: ResponseSlot<ServiceListResponse>,
     pub get_service_responses:
         Mutex<VecDeque<std::result::Result<ServiceDetailResponse, ApiError>>>,
+    pub get_service_metrics_responses:
+        Mutex<VecDeque<std::result::Result<ServiceMetricsResponse, ApiError>>>,
     /// Queue of responses popped FIFO by each `list_deployments` call. A queue
     /// (not a one-shot slot) because `destroy`'s drain poll lists repeatedly.
     pub list_deployments_responses:
@@ -129,12 +233,30 @@ pub struct MockApiClient {
     pub update_deployment_responses: Mutex<VecDeque<std::result::Result<(), ApiError>>>,
     pub delete_service_responses: Mutex<VecDeque<std::result::Result<(), ApiError>>>,
     pub delete_deployment_responses: Mutex<VecDeque<std::result::Result<(), ApiError>>>,
+    pub set_managed_by_responses: Mutex<VecDeque<std::result::Result<(), ApiError>>>,
     pub create_registry_responses: Mutex<VecDeque<std::result::Result<RegistryResponse, ApiError>>>,
     pub list_registries_response: ResponseSlot<RegistryListResponse>,
+    /// Answers 404 when empty; see `find_instances_by_name_responses`.
+    pub find_registries_by_hostname_responses:
+        Mutex<VecDeque<std::result::Result<RegistryListResponse, ApiError>>>,
     pub update_registry_responses: Mutex<VecDeque<std::result::Result<RegistryResponse, ApiError>>>,
     pub delete_registry_responses: Mutex<VecDeque<std::result::Result<(), ApiError>>>,
     pub test_registry_responses:
         Mutex<VecDeque<std::result::Result<TestRegistryResponse, ApiError>>>,
+    pub get_scoped_token_responses:
+        Mutex<VecDeque<std::result::Result<ScopedTokenResponse, ApiError>>>,
+    pub create_api_token_responses: Mutex<VecDeque<std::result::Result<CreatedApiToken, ApiError>>>,
+    pub list_api_tokens_response: ResponseSlot<ApiTokenListResponse>,
+    pub revoke_api_token_responses: Mutex<VecDeque<std::result::Result<(), ApiError>>>,
+    pub create_service_account_responses:
+        Mutex<VecDeque<std::result::Result<CreatedServiceAccount, ApiError>>>,
+    pub list_service_accounts_response: ResponseSlot<ServiceAccountListResponse>,
+    pub delete_service_account_responses: Mutex<VecDeque<std::result::Result<(), ApiError>>>,
+    pub list_hosted_repositories_response: ResponseSlot<HostedRepositoryListResponse>,
+    pub list_hosted_images_response: ResponseSlot<HostedImageListResponse>,
+    pub delete_hosted_image_responses: Mutex<VecDeque<std::result::Result<(), ApiError>>>,
+    /// What `is_read_only` answers; the mock itself refuses nothing.
+    pub read_only: bool,
     pub calls: Mutex<CallLog>,
 }
 
@@ -149,19 +271,52 @@ impl Default for MockApiClient {
             link_host_responses: Mutex::new(VecDeque::new()),
             unlink_host_responses: Mutex::new(VecDeque::new()),
             list_hosts_response: ResponseSlot::default(),
+            list_regions_response: ResponseSlot::default(),
+            list_events_responses: Mutex::new(VecDeque::new()),
             list_environments_response: ResponseSlot::default(),
             create_environment_response: ResponseSlot::default(),
             delete_environment_responses: Mutex::new(VecDeque::new()),
             list_instances_responses: Mutex::new(VecDeque::new()),
+            find_instances_by_name_responses: Mutex::new(VecDeque::new()),
             get_instance_logs_responses: Mutex::new(VecDeque::new()),
+            search_instance_logs_responses: Mutex::new(VecDeque::new()),
             stream_logs_responses: Mutex::new(VecDeque::new()),
+            provision_instance_responses: Mutex::new(VecDeque::new()),
+            provision_worker_responses: Mutex::new(VecDeque::new()),
+            get_instance_responses: Mutex::new(VecDeque::new()),
+            attach_instance_network_responses: Mutex::new(VecDeque::new()),
+            detach_instance_network_responses: Mutex::new(VecDeque::new()),
+            adopt_instance_responses: Mutex::new(VecDeque::new()),
             deprovision_instance_responses: Mutex::new(VecDeque::new()),
+            restore_instance_responses: Mutex::new(VecDeque::new()),
             create_network_responses: Mutex::new(VecDeque::new()),
             delete_network_responses: Mutex::new(VecDeque::new()),
             list_networks_response: ResponseSlot::default(),
             get_network_responses: Mutex::new(VecDeque::new()),
+            list_ip_reservations_responses: Mutex::new(VecDeque::new()),
+            create_ip_reservation_responses: Mutex::new(VecDeque::new()),
+            list_network_policies_response: ResponseSlot::default(),
+            create_network_policy_responses: Mutex::new(VecDeque::new()),
+            delete_network_policy_responses: Mutex::new(VecDeque::new()),
+            list_network_peerings_responses: Mutex::new(VecDeque::new()),
+            create_network_peering_responses: Mutex::new(VecDeque::new()),
+            delete_network_peering_responses: Mutex::new(VecDeque::new()),
+            list_wireguard_peers_responses: Mutex::new(VecDeque::new()),
+            create_wireguard_peer_responses: Mutex::new(VecDeque::new()),
+            delete_wireguard_peer_responses: Mutex::new(VecDeque::new()),
+            get_host_certificate_responses: Mutex::new(VecDeque::new()),
+            start_dns_challenge_responses: Mutex::new(VecDeque::new()),
+            create_service_target_responses: Mutex::new(VecDeque::new()),
+            delete_service_target_responses: Mutex::new(VecDeque::new()),
+            get_dns_challenge_responses: Mutex::new(VecDeque::new()),
+            complete_dns_challenge_responses: Mutex::new(VecDeque::new()),
+            list_host_records_responses: Mutex::new(VecDeque::new()),
+            create_host_record_responses: Mutex::new(VecDeque::new()),
+            delete_host_record_responses: Mutex::new(VecDeque::new()),
+            delete_host_responses: Mutex::new(VecDeque::new()),
             list_services_response: ResponseSlot::default(),
             get_service_responses: Mutex::new(VecDeque::new()),
+            get_service_metrics_responses: Mutex::new(VecDeque::new()),
             list_deployments_responses: Mutex::new(VecDeque::new()),
             get_deployment_responses: Mutex::new(VecDeque::new()),
             provision_service_responses: Mutex::new(VecDeque::new()),
@@ -170,11 +325,24 @@ impl Default for MockApiClient {
             update_deployment_responses: Mutex::new(VecDeque::new()),
             delete_service_responses: Mutex::new(VecDeque::new()),
             delete_deployment_responses: Mutex::new(VecDeque::new()),
+            set_managed_by_responses: Mutex::new(VecDeque::new()),
             create_registry_responses: Mutex::new(VecDeque::new()),
             list_registries_response: ResponseSlot::default(),
+            find_registries_by_hostname_responses: Mutex::new(VecDeque::new()),
             update_registry_responses: Mutex::new(VecDeque::new()),
             delete_registry_responses: Mutex::new(VecDeque::new()),
             test_registry_responses: Mutex::new(VecDeque::new()),
+            get_scoped_token_responses: Mutex::new(VecDeque::new()),
+            create_api_token_responses: Mutex::new(VecDeque::new()),
+            list_api_tokens_response: ResponseSlot::default(),
+            revoke_api_token_responses: Mutex::new(VecDeque::new()),
+            create_service_account_responses: Mutex::new(VecDeque::new()),
+            list_service_accounts_response: ResponseSlot::default(),
+            delete_service_account_responses: Mutex::new(VecDeque::new()),
+            list_hosted_repositories_response: ResponseSlot::default(),
+            list_hosted_images_response: ResponseSlot::default(),
+            delete_hosted_image_responses: Mutex::new(VecDeque::new()),
+            read_only: false,
             calls: Mutex::new(CallLog::default()),
         }
     }
@@ -203,6 +371,12 @@ impl MockApiClient {
         }
     }
 
+    /// Report itself as read-only, like a client built with `--read-only`.
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
     /// Configure the response that the next `claim_host` call will return.
     pub fn with_claim_host(self, resp: std::result::Result<HostResponse, ApiError>) -> Self {
         self.claim_host_response.set(resp);
@@ -221,6 +395,19 @@ impl MockApiClient {
         self
     }
 
+    pub fn with_list_regions(
+        self,
+        resp: std::result::Result<RegionListResponse, ApiError>,
+    ) -> Self {
+        self.list_regions_response.set(resp);
+        self
+    }
+
+    pub fn push_list_events(self, resp: std::result::Result<EventListResponse, ApiError>) -> Self {
+        self.list_events_responses.lock().unwrap().push_back(resp);
+        self
+    }
+
     /// Configure the response that the next `list_hosts` call will return.
     pub fn with_list_hosts(self, resp: std::result::Result<Vec<HostResponse>, ApiError>) -> Self {
         self.list_hosts_response.set(resp);
@@ -274,6 +461,208 @@ impl MockApiClient {
         self
     }
 
+    pub fn push_list_ip_reservations(
+        self,
+        resp: std::result::Result<IpReservationListResponse, ApiError>,
+    ) -> Self {
+        self.list_ip_reservations_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    pub fn with_list_network_policies(
+        self,
+        resp: std::result::Result<NetworkPolicyListResponse, ApiError>,
+    ) -> Self {
+        self.list_network_policies_response.set(resp);
+        self
+    }
+
+    pub fn push_create_network_policy(
+        self,
+        resp: std::result::Result<NetworkPolicyRule, ApiError>,
+    ) -> Self {
+        self.create_network_policy_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    pub fn push_delete_network_policy(self, resp: std::result::Result<(), ApiError>) -> Self {
+        self.delete_network_policy_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    pub fn push_list_network_peerings(
+        self,
+        resp: std::result::Result<NetworkPeeringListResponse, ApiError>,
+    ) -> Self {
+        self.list_network_peerings_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    pub fn push_create_network_peering(
+        self,
+        resp: std::result::Result<NetworkPeering, ApiError>,
+    ) -> Self {
+        self.create_network_peering_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    pub fn push_delete_network_peering(self, resp: std::result::Result<(), ApiError>) -> Self {
+        self.delete_network_peering_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    pub fn push_list_wireguard_peers(
+        self,
+        resp: std::result::Result<WireGuardPeerListResponse, ApiError>,
+    ) -> Self {
+        self.list_wireguard_peers_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    pub fn push_create_wireguard_peer(
+        self,
+        resp: std::result::Result<WireGuardPeerConfig, ApiError>,
+    ) -> Self {
+        self.create_wireguard_peer_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    pub fn push_delete_wireguard_peer(self, resp: std::result::Result<(), ApiError>) -> Self {
+        self.delete_wireguard_peer_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    pub fn push_create_service_target(
+        self,
+        resp: std::result::Result<CreateTargetResponse, ApiError>,
+    ) -> Self {
+        self.create_service_target_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    pub fn push_delete_service_target(self, resp: std::result::Result<(), ApiError>) -> Self {
+        self.delete_service_target_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    pub fn push_get_host_certificate(
+        self,
+        resp: std::result::Result<HostCertificate, ApiError>,
+    ) -> Self {
+        self.get_host_certificate_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    pub fn push_start_dns_challenge(
+        self,
+        resp: std::result::Result<DnsChallenge, ApiError>,
+    ) -> Self {
+        self.start_dns_challenge_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    /// Queue one poll's answer; `get_dns_challenge` answers in order.
+    pub fn push_get_dns_challenge(self, resp: std::result::Result<DnsChallenge, ApiError>) -> Self {
+        self.get_dns_challenge_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    pub fn push_complete_dns_challenge(
+        self,
+        resp: std::result::Result<HostResponse, ApiError>,
+    ) -> Self {
+        self.complete_dns_challenge_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    pub fn push_list_host_records(
+        self,
+        resp: std::result::Result<Vec<DnsRecord>, ApiError>,
+    ) -> Self {
+        self.list_host_records_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    pub fn push_create_host_record(self, resp: std::result::Result<DnsRecord, ApiError>) -> Self {
+        self.create_host_record_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    pub fn push_delete_host_record(self, resp: std::result::Result<(), ApiError>) -> Self {
+        self.delete_host_record_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    pub fn push_delete_host(self, resp: std::result::Result<(), ApiError>) -> Self {
+        self.delete_host_responses.lock().unwrap().push_back(resp);
+        self
+    }
+
+    pub fn push_create_ip_reservation(
+        self,
+        resp: std::result::Result<IpReservation, ApiError>,
+    ) -> Self {
+        self.create_ip_reservation_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
     pub fn with_list_services(
         self,
         resp: std::result::Result<ServiceListResponse, ApiError>,
@@ -290,6 +679,19 @@ impl MockApiClient {
         self
     }
 
+    /// Queue one `get_service_metrics` response; each call pops the next, so a
+    /// `--watch` refresh can be scripted.
+    pub fn push_get_service_metrics(
+        self,
+        resp: std::result::Result<ServiceMetricsResponse, ApiError>,
+    ) -> Self {
+        self.get_service_metrics_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
     /// Queue one `list_deployments` response. Each call pops the next, so chain
     /// multiple to script a drain sequence (e.g. non-empty, non-empty, empty).
     pub fn with_list_deployments(
@@ -322,6 +724,18 @@ impl MockApiClient {
         self
     }
 
+    /// Queue one `find_instances_by_name` response.
+    pub fn push_find_instances_by_name(
+        self,
+        resp: std::result::Result<InstanceListResponse, ApiError>,
+    ) -> Self {
+        self.find_instances_by_name_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
     /// Queue one `get_instance_logs` response.
     pub fn push_instance_logs(self, resp: std::result::Result<Vec<LogMessage>, ApiError>) -> Self {
         self.get_instance_logs_responses
@@ -331,6 +745,17 @@ impl MockApiClient {
         self
     }
 
+    pub fn push_search_instance_logs(
+        self,
+        resp: std::result::Result<Vec<LogMessage>, ApiError>,
+    ) -> Self {
+        self.search_instance_logs_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
     /// Queue a log stream that yields these frames (each as a success) and then
     /// closes — the common "history replays, then the instance stops" case.
     pub fn push_stream_logs(self, frames: Vec<LogMessage>) -> Self {
@@ -362,6 +787,60 @@ impl MockApiClient {
         self
     }
 
+    pub fn push_provision_instance(
+        self,
+        resp: std::result::Result<InstanceProvisionResponse, ApiError>,
+    ) -> Self {
+        self.provision_instance_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    pub fn push_provision_worker(
+        self,
+        resp: std::result::Result<InstanceProvisionResponse, ApiError>,
+    ) -> Self {
+        self.provision_worker_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    pub fn push_get_instance(
+        self,
+        resp: std::result::Result<InstanceDetailResponse, ApiError>,
+    ) -> Self {
+        self.get_instance_responses.lock().unwrap().push_back(resp);
+        self
+    }
+
+    pub fn push_attach_instance_network(self, resp: std::result::Result<(), ApiError>) -> Self {
+        self.attach_instance_network_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    pub fn push_detach_instance_network(self, resp: std::result::Result<(), ApiError>) -> Self {
+        self.detach_instance_network_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    pub fn push_adopt_instance(self, resp: std::result::Result<DeploymentInfo, ApiError>) -> Self {
+        self.adopt_instance_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
     pub fn push_deprovision_instance(self, resp: std::result::Result<(), ApiError>) -> Self {
         self.deprovision_instance_responses
             .lock()
@@ -370,6 +849,17 @@ impl MockApiClient {
         self
     }
 
+    pub fn push_restore_instance(
+        self,
+        resp: std::result::Result<InstanceListEntry, ApiError>,
+    ) -> Self {
+        self.restore_instance_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
     pub fn push_get_deployment(
         self,
         resp: std::result::Result<DeploymentDetailResponse, ApiError>,
@@ -445,6 +935,14 @@ impl MockApiClient {
         self
     }
 
+    pub fn push_set_managed_by(self, resp: std::result::Result<(), ApiError>) -> Self {
+        self.set_managed_by_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
     pub fn push_create_registry(
         self,
         resp: std::result::Result<RegistryResponse, ApiError>,
@@ -464,6 +962,18 @@ impl MockApiClient {
         self
     }
 
+    /// Queue one `find_registries_by_hostname` response.
+    pub fn push_find_registries_by_hostname(
+        self,
+        resp: std::result::Result<RegistryListResponse, ApiError>,
+    ) -> Self {
+        self.find_registries_by_hostname_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
     pub fn push_update_registry(
         self,
         resp: std::result::Result<RegistryResponse, ApiError>,
@@ -491,14 +1001,112 @@ impl MockApiClient {
         self
     }
 
-    fn require_session(&self) -> Result<AuthSession> {
-        self.session
+    pub fn push_get_scoped_token(
+        self,
+        resp: std::result::Result<ScopedTokenResponse, ApiError>,
+    ) -> Self {
+        self.get_scoped_token_responses
             .lock()
             .unwrap()
-            .clone()
-            .ok_or_else(ApiError::not_logged_in)
+            .push_back(resp);
+        self
     }
-}
+
+    pub fn push_create_api_token(
+        self,
+        resp: std::result::Result<CreatedApiToken, ApiError>,
+    ) -> Self {
+        self.create_api_token_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    pub fn with_list_api_tokens(
+        self,
+        resp: std::result::Result<ApiTokenListResponse, ApiError>,
+    ) -> Self {
+        self.list_api_tokens_response.set(resp);
+        self
+    }
+
+    pub fn push_revoke_api_token(self, resp: std::result::Result<(), ApiError>) -> Self {
+        self.revoke_api_token_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    pub fn push_create_service_account(
+        self,
+        resp: std::result::Result<CreatedServiceAccount, ApiError>,
+    ) -> Self {
+        self.create_service_account_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    pub fn with_list_service_accounts(
+        self,
+        resp: std::result::Result<ServiceAccountListResponse, ApiError>,
+    ) -> Self {
+        self.list_service_accounts_response.set(resp);
+        self
+    }
+
+    pub fn push_delete_service_account(self, resp: std::result::Result<(), ApiError>) -> Self {
+        self.delete_service_account_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    pub fn with_list_hosted_repositories(
+        self,
+        resp: std::result::Result<HostedRepositoryListResponse, ApiError>,
+    ) -> Self {
+        self.list_hosted_repositories_response.set(resp);
+        self
+    }
+
+    pub fn with_list_hosted_images(
+        self,
+        resp: std::result::Result<HostedImageListResponse, ApiError>,
+    ) -> Self {
+        self.list_hosted_images_response.set(resp);
+        self
+    }
+
+    pub fn push_delete_hosted_image(self, resp: std::result::Result<(), ApiError>) -> Self {
+        self.delete_hosted_image_responses
+            .lock()
+            .unwrap()
+            .push_back(resp);
+        self
+    }
+
+    /// What an unscripted optional endpoint answers: the 404 an older
+    /// backend gives for a route/query it doesn't know.
+    fn unsupported_endpoint() -> ApiError {
+        ApiError::Server {
+            status: 404,
+            reason: "not found".into(),
+        }
+    }
+
+    fn require_session(&self) -> Result<AuthSession> {
+        self.session
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(ApiError::not_logged_in)
+    }
+}
 
 #[async_trait]
 impl ApiClient for MockApiClient {
@@ -513,6 +1121,10 @@ impl ApiClient for MockApiClient {
         self.login_result.lock().unwrap().take().unwrap_or(Ok(()))
     }
 
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
     async fn access_token(&self) -> Result<String> {
         {
             let mut calls = self.calls.lock().unwrap();
@@ -531,6 +1143,81 @@ impl ApiClient for MockApiClient {
         self.require_session()
     }
 
+    async fn create_api_token(&self, req: CreateApiTokenRequest) -> Result<CreatedApiToken> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("create_api_token");
+            calls.create_api_token_calls.push(req);
+        }
+        self.create_api_token_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("create_api_token_response not configured"))
+    }
+
+    async fn list_api_tokens(&self) -> Result<ApiTokenListResponse> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("list_api_tokens");
+            calls.list_api_tokens_calls += 1;
+        }
+        self.list_api_tokens_response
+            .take("list_api_tokens_response")
+    }
+
+    async fn revoke_api_token(&self, id: Uuid) -> Result<()> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("revoke_api_token");
+            calls.revoke_api_token_calls.push(id);
+        }
+        self.revoke_api_token_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("revoke_api_token_response not configured"))
+    }
+
+    async fn create_service_account(
+        &self,
+        req: CreateServiceAccountRequest,
+    ) -> Result<CreatedServiceAccount> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("create_service_account");
+            calls.create_service_account_calls.push(req);
+        }
+        self.create_service_account_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("create_service_account_response not configured"))
+    }
+
+    async fn list_service_accounts(&self) -> Result<ServiceAccountListResponse> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("list_service_accounts");
+            calls.list_service_accounts_calls += 1;
+        }
+        self.list_service_accounts_response
+            .take("list_service_accounts_response")
+    }
+
+    async fn delete_service_account(&self, id: Uuid) -> Result<()> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("delete_service_account");
+            calls.delete_service_account_calls.push(id);
+        }
+        self.delete_service_account_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("delete_service_account_response not configured"))
+    }
+
     async fn create_environment(
         &self,
         req: CreateEnvironmentRequest,
@@ -573,10 +1260,36 @@ impl ApiClient for MockApiClient {
     }
     async fn provision_instance(
         &self,
-        _: Uuid,
-        _: InstanceProvisionRequest,
+        env_id: Uuid,
+        req: InstanceProvisionRequest,
     ) -> Result<InstanceProvisionResponse> {
-        unimplemented!()
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("provision_instance");
+            calls.provision_instance_calls.push((env_id, req));
+        }
+        self.provision_instance_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("provision_instance_response not configured"))
+    }
+
+    async fn provision_worker(
+        &self,
+        env_id: Uuid,
+        req: WorkerProvisionRequest,
+    ) -> Result<InstanceProvisionResponse> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("provision_worker");
+            calls.provision_worker_calls.push((env_id, req));
+        }
+        self.provision_worker_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("provision_worker_response not configured"))
     }
     async fn deprovision_instance(
         &self,
@@ -597,14 +1310,35 @@ impl ApiClient for MockApiClient {
             .pop_front()
             .unwrap_or_else(|| panic!("deprovision_instance_response not configured"))
     }
+    async fn restore_instance(&self, env_id: Uuid, instance_id: Uuid) -> Result<InstanceListEntry> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("restore_instance");
+            calls.restore_instance_calls.push((env_id, instance_id));
+        }
+        self.restore_instance_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("restore_instance_response not configured"))
+    }
     async fn get_instance(
         &self,
-        _: Uuid,
-        _: Uuid,
+        env_id: Uuid,
+        instance_id: Uuid,
         _: bool,
         _: bool,
     ) -> Result<InstanceDetailResponse> {
-        unimplemented!()
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("get_instance");
+            calls.get_instance_calls.push((env_id, instance_id));
+        }
+        self.get_instance_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("get_instance_response not configured"))
     }
     async fn list_instances(&self, env_id: Uuid) -> Result<InstanceListResponse> {
         {
@@ -618,6 +1352,24 @@ impl ApiClient for MockApiClient {
             .pop_front()
             .unwrap_or_else(|| panic!("list_instances_response not configured"))
     }
+    async fn find_instances_by_name(
+        &self,
+        env_id: Uuid,
+        name: &str,
+    ) -> Result<InstanceListResponse> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("find_instances_by_name");
+            calls
+                .find_instances_by_name_calls
+                .push((env_id, name.to_string()));
+        }
+        self.find_instances_by_name_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| Err(Self::unsupported_endpoint()))
+    }
     async fn get_instance_logs(&self, env_id: Uuid, instance_id: Uuid) -> Result<Vec<LogMessage>> {
         {
             let mut calls = self.calls.lock().unwrap();
@@ -630,6 +1382,25 @@ impl ApiClient for MockApiClient {
             .pop_front()
             .unwrap_or_else(|| panic!("get_instance_logs_response not configured"))
     }
+    async fn search_instance_logs(
+        &self,
+        env_id: Uuid,
+        instance_id: Uuid,
+        search: &LogSearch,
+    ) -> Result<Vec<LogMessage>> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("search_instance_logs");
+            calls
+                .search_instance_logs_calls
+                .push((env_id, instance_id, search.clone()));
+        }
+        self.search_instance_logs_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| Err(Self::unsupported_endpoint()))
+    }
     async fn stream_instance_logs(&self, env_id: Uuid, instance_id: Uuid) -> Result<LogStream> {
         {
             let mut calls = self.calls.lock().unwrap();
@@ -703,6 +1474,250 @@ impl ApiClient for MockApiClient {
             .pop_front()
             .unwrap_or_else(|| panic!("get_network_response not configured"))
     }
+    async fn attach_instance_network(
+        &self,
+        env_id: Uuid,
+        instance_id: Uuid,
+        req: InstanceNetworkConfig,
+    ) -> Result<()> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("attach_instance_network");
+            calls
+                .attach_instance_network_calls
+                .push((env_id, instance_id, req));
+        }
+        self.attach_instance_network_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("attach_instance_network_response not configured"))
+    }
+    async fn detach_instance_network(&self, env_id: Uuid, instance_id: Uuid) -> Result<()> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("detach_instance_network");
+            calls
+                .detach_instance_network_calls
+                .push((env_id, instance_id));
+        }
+        self.detach_instance_network_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("detach_instance_network_response not configured"))
+    }
+    async fn adopt_instance(
+        &self,
+        env_id: Uuid,
+        instance_id: Uuid,
+        req: AdoptInstanceRequest,
+    ) -> Result<DeploymentInfo> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("adopt_instance");
+            calls.adopt_instance_calls.push((env_id, instance_id, req));
+        }
+        self.adopt_instance_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("adopt_instance_response not configured"))
+    }
+    async fn list_ip_reservations(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+    ) -> Result<IpReservationListResponse> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("list_ip_reservations");
+            calls.list_ip_reservations_calls.push((env_id, network_id));
+        }
+        self.list_ip_reservations_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| Err(Self::unsupported_endpoint()))
+    }
+    async fn create_ip_reservation(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        req: CreateIpReservationRequest,
+    ) -> Result<IpReservation> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("create_ip_reservation");
+            calls
+                .create_ip_reservation_calls
+                .push((env_id, network_id, req));
+        }
+        self.create_ip_reservation_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("create_ip_reservation_response not configured"))
+    }
+    async fn list_network_policies(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+    ) -> Result<NetworkPolicyListResponse> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("list_network_policies");
+            calls.list_network_policies_calls.push((env_id, network_id));
+        }
+        self.list_network_policies_response
+            .take("list_network_policies_response")
+    }
+    async fn create_network_policy(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        req: CreateNetworkPolicyRequest,
+    ) -> Result<NetworkPolicyRule> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("create_network_policy");
+            calls
+                .create_network_policy_calls
+                .push((env_id, network_id, req));
+        }
+        self.create_network_policy_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("create_network_policy_response not configured"))
+    }
+    async fn delete_network_policy(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        rule_id: Uuid,
+    ) -> Result<()> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("delete_network_policy");
+            calls
+                .delete_network_policy_calls
+                .push((env_id, network_id, rule_id));
+        }
+        self.delete_network_policy_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("delete_network_policy_response not configured"))
+    }
+    async fn list_network_peerings(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+    ) -> Result<NetworkPeeringListResponse> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("list_network_peerings");
+            calls.list_network_peerings_calls.push((env_id, network_id));
+        }
+        self.list_network_peerings_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| Err(Self::unsupported_endpoint()))
+    }
+    async fn create_network_peering(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        req: CreateNetworkPeeringRequest,
+    ) -> Result<NetworkPeering> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("create_network_peering");
+            calls
+                .create_network_peering_calls
+                .push((env_id, network_id, req));
+        }
+        self.create_network_peering_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("create_network_peering_response not configured"))
+    }
+    async fn delete_network_peering(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        peering_id: Uuid,
+    ) -> Result<()> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("delete_network_peering");
+            calls
+                .delete_network_peering_calls
+                .push((env_id, network_id, peering_id));
+        }
+        self.delete_network_peering_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("delete_network_peering_response not configured"))
+    }
+    async fn list_wireguard_peers(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+    ) -> Result<WireGuardPeerListResponse> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("list_wireguard_peers");
+            calls.list_wireguard_peers_calls.push((env_id, network_id));
+        }
+        self.list_wireguard_peers_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| Err(Self::unsupported_endpoint()))
+    }
+    async fn create_wireguard_peer(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        req: CreateWireGuardPeerRequest,
+    ) -> Result<WireGuardPeerConfig> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("create_wireguard_peer");
+            calls
+                .create_wireguard_peer_calls
+                .push((env_id, network_id, req));
+        }
+        self.create_wireguard_peer_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| Err(Self::unsupported_endpoint()))
+    }
+    async fn delete_wireguard_peer(
+        &self,
+        env_id: Uuid,
+        network_id: Uuid,
+        peer_id: Uuid,
+    ) -> Result<()> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("delete_wireguard_peer");
+            calls
+                .delete_wireguard_peer_calls
+                .push((env_id, network_id, peer_id));
+        }
+        self.delete_wireguard_peer_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("delete_wireguard_peer_response not configured"))
+    }
     async fn provision_service(
         &self,
         env_id: Uuid,
@@ -739,6 +1754,25 @@ impl ApiClient for MockApiClient {
             .pop_front()
             .unwrap_or_else(|| panic!("get_service_response not configured"))
     }
+    async fn get_service_metrics(
+        &self,
+        env_id: Uuid,
+        service_id: Uuid,
+        window: &str,
+    ) -> Result<ServiceMetricsResponse> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("get_service_metrics");
+            calls
+                .get_service_metrics_calls
+                .push((env_id, service_id, window.to_string()));
+        }
+        self.get_service_metrics_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("get_service_metrics_response not configured"))
+    }
     async fn update_service(
         &self,
         env_id: Uuid,
@@ -770,14 +1804,41 @@ impl ApiClient for MockApiClient {
     }
     async fn create_service_target(
         &self,
-        _: Uuid,
-        _: Uuid,
-        _: ServiceInstanceTarget,
+        env_id: Uuid,
+        service_id: Uuid,
+        req: ServiceInstanceTarget,
     ) -> Result<CreateTargetResponse> {
-        unimplemented!()
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("create_service_target");
+            calls
+                .create_service_target_calls
+                .push((env_id, service_id, req));
+        }
+        self.create_service_target_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("create_service_target_response not configured"))
     }
-    async fn delete_service_target(&self, _: Uuid, _: Uuid, _: Uuid) -> Result<()> {
-        unimplemented!()
+    async fn delete_service_target(
+        &self,
+        env_id: Uuid,
+        service_id: Uuid,
+        target_id: Uuid,
+    ) -> Result<()> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("delete_service_target");
+            calls
+                .delete_service_target_calls
+                .push((env_id, service_id, target_id));
+        }
+        self.delete_service_target_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("delete_service_target_response not configured"))
     }
     async fn claim_host(&self, req: ClaimHostRequest) -> Result<HostResponse> {
         {
@@ -787,6 +1848,32 @@ impl ApiClient for MockApiClient {
         }
         self.claim_host_response.take("claim_host_response")
     }
+    async fn list_regions(&self) -> Result<RegionListResponse> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("list_regions");
+            calls.list_regions_calls += 1;
+        }
+        self.list_regions_response.take("list_regions_response")
+    }
+    async fn list_events(
+        &self,
+        since: DateTime<Utc>,
+        cursor: Option<&str>,
+    ) -> Result<EventListResponse> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("list_events");
+            calls
+                .list_events_calls
+                .push((since, cursor.map(str::to_string)));
+        }
+        self.list_events_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("list_events_response not configured"))
+    }
     async fn list_hosts(&self) -> Result<Vec<HostResponse>> {
         {
             let mut calls = self.calls.lock().unwrap();
@@ -795,8 +1882,17 @@ impl ApiClient for MockApiClient {
         }
         self.list_hosts_response.take("list_hosts_response")
     }
-    async fn delete_host(&self, _: Uuid) -> Result<()> {
-        unimplemented!()
+    async fn delete_host(&self, id: Uuid) -> Result<()> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("delete_host");
+            calls.delete_host_calls.push(id);
+        }
+        self.delete_host_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("delete_host_response not configured"))
     }
     async fn request_host_cert(&self, id: Uuid) -> Result<HostResponse> {
         {
@@ -807,6 +1903,54 @@ impl ApiClient for MockApiClient {
         self.request_host_cert_response
             .take("request_host_cert_response")
     }
+    async fn get_host_certificate(&self, id: Uuid) -> Result<HostCertificate> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("get_host_certificate");
+            calls.get_host_certificate_calls.push(id);
+        }
+        self.get_host_certificate_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| Err(Self::unsupported_endpoint()))
+    }
+    async fn start_dns_challenge(&self, id: Uuid) -> Result<DnsChallenge> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("start_dns_challenge");
+            calls.start_dns_challenge_calls.push(id);
+        }
+        self.start_dns_challenge_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| Err(Self::unsupported_endpoint()))
+    }
+    async fn get_dns_challenge(&self, id: Uuid) -> Result<DnsChallenge> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("get_dns_challenge");
+            calls.get_dns_challenge_calls.push(id);
+        }
+        self.get_dns_challenge_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| Err(Self::unsupported_endpoint()))
+    }
+    async fn complete_dns_challenge(&self, id: Uuid) -> Result<HostResponse> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("complete_dns_challenge");
+            calls.complete_dns_challenge_calls.push(id);
+        }
+        self.complete_dns_challenge_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .expect("complete_dns_challenge called without a queued response")
+    }
     async fn get_hosts_dns_config(&self) -> Result<DnsConfigResponse> {
         {
             let mut calls = self.calls.lock().unwrap();
@@ -839,6 +1983,42 @@ impl ApiClient for MockApiClient {
             .pop_front()
             .unwrap_or_else(|| panic!("unlink_host_response not configured"))
     }
+    async fn list_host_records(&self, id: Uuid) -> Result<Vec<DnsRecord>> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("list_host_records");
+            calls.list_host_records_calls.push(id);
+        }
+        self.list_host_records_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| Err(Self::unsupported_endpoint()))
+    }
+    async fn create_host_record(&self, id: Uuid, req: CreateDnsRecordRequest) -> Result<DnsRecord> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("create_host_record");
+            calls.create_host_record_calls.push((id, req));
+        }
+        self.create_host_record_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("create_host_record_response not configured"))
+    }
+    async fn delete_host_record(&self, id: Uuid, record_id: Uuid) -> Result<()> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("delete_host_record");
+            calls.delete_host_record_calls.push((id, record_id));
+        }
+        self.delete_host_record_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("delete_host_record_response not configured"))
+    }
     async fn create_deployment(
         &self,
         env_id: Uuid,
@@ -915,6 +2095,25 @@ impl ApiClient for MockApiClient {
             .unwrap_or_else(|| panic!("delete_deployment_response not configured"))
     }
 
+    async fn set_managed_by(
+        &self,
+        env_id: Uuid,
+        kind: ManagedKind,
+        id: Uuid,
+        req: SetManagedByRequest,
+    ) -> Result<()> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("set_managed_by");
+            calls.set_managed_by_calls.push((env_id, kind, id, req));
+        }
+        self.set_managed_by_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("set_managed_by_response not configured"))
+    }
+
     async fn create_registry(
         &self,
         req: CreateRegistryRequest,
@@ -942,6 +2141,21 @@ impl ApiClient for MockApiClient {
             .take("list_registries_response")
     }
 
+    async fn find_registries_by_hostname(&self, hostname: &str) -> Result<RegistryListResponse> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("find_registries_by_hostname");
+            calls
+                .find_registries_by_hostname_calls
+                .push(hostname.to_string());
+        }
+        self.find_registries_by_hostname_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| Err(Self::unsupported_endpoint()))
+    }
+
     async fn update_registry(
         &self,
         id: Uuid,
@@ -985,4 +2199,59 @@ impl ApiClient for MockApiClient {
             .pop_front()
             .unwrap_or_else(|| panic!("test_registry_response not configured"))
     }
+
+    async fn get_scoped_token(
+        &self,
+        id: Uuid,
+        repository: &str,
+        push: bool,
+    ) -> Result<ScopedTokenResponse> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("get_scoped_token");
+            calls
+                .get_scoped_token_calls
+                .push((id, repository.to_string(), push));
+        }
+        self.get_scoped_token_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("get_scoped_token_response not configured"))
+    }
+
+    async fn list_hosted_repositories(&self) -> Result<HostedRepositoryListResponse> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("list_hosted_repositories");
+            calls.list_hosted_repositories_calls += 1;
+        }
+        self.list_hosted_repositories_response
+            .take("list_hosted_repositories_response")
+    }
+
+    async fn list_hosted_images(&self, repository: &str) -> Result<HostedImageListResponse> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("list_hosted_images");
+            calls.list_hosted_images_calls.push(repository.to_string());
+        }
+        self.list_hosted_images_response
+            .take("list_hosted_images_response")
+    }
+
+    async fn delete_hosted_image(&self, repository: &str, tag: &str) -> Result<()> {
+        {
+            let mut calls = self.calls.lock().unwrap();
+            calls.call_order.push("delete_hosted_image");
+            calls
+                .delete_hosted_image_calls
+                .push((repository.to_string(), tag.to_string()));
+        }
+        self.delete_hosted_image_responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("delete_hosted_image_response not configured"))
+    }
 }
@@ -0,0 +1,250 @@
+//! A scripted HTTP server for driving [`HttpApiClient`] end to end — URL
+//! building, bearer auth, token refresh and error mapping — without a live
+//! backend.
+//!
+//! Routes match on method and path (the query string is recorded but not
+//! matched). Each route answers with its scripted replies in order and keeps
+//! repeating the last one, so a poll loop can be scripted as "two pending, then
+//! ready". Unscripted requests get a 404 naming the route, which surfaces in a
+//! failing test as an [`ApiError::Server`](crate::ApiError::Server).
+//!
+//! [`HttpApiClient`]: crate::HttpApiClient
+
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+/// One scripted response.
+#[derive(Debug, Clone)]
+pub struct Reply {
+    status: u16,
+    body: String,
+    headers: Vec<(String, String)>,
+}
+
+impl Reply {
+    /// Respond with `status` and `body` serialized as JSON.
+    pub fn json(status: u16, body: &impl Serialize) -> Self {
+        Self {
+            status,
+            body: serde_json::to_string(body).expect("mock reply serializes"),
+            headers: Vec::new(),
+        }
+    }
+
+    /// Respond with `status` and no body.
+    pub fn empty(status: u16) -> Self {
+        Self {
+            status,
+            body: String::new(),
+            headers: Vec::new(),
+        }
+    }
+
+    /// Also send the header `name: value`.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// A request the server received, for asserting on what the client sent.
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    pub authorization: Option<String>,
+    pub traceparent: Option<String>,
+    pub api_version: Option<String>,
+    pub body: String,
+}
+
+impl RecordedRequest {
+    /// The request body parsed as JSON (`Null` when empty).
+    pub fn json(&self) -> serde_json::Value {
+        if self.body.is_empty() {
+            return serde_json::Value::Null;
+        }
+        serde_json::from_str(&self.body).expect("request body is JSON")
+    }
+}
+
+struct Route {
+    method: String,
+    path: String,
+    replies: VecDeque<Reply>,
+}
+
+#[derive(Default)]
+struct State {
+    routes: Vec<Route>,
+    requests: Vec<RecordedRequest>,
+}
+
+impl State {
+    fn reply_for(&mut self, method: &str, path: &str) -> Reply {
+        let Some(route) = self
+            .routes
+            .iter_mut()
+            .find(|r| r.method == method && r.path == path)
+        else {
+            return Reply::json(
+                404,
+                &serde_json::json!({ "reason": format!("no mock for {method} {path}") }),
+            );
+        };
+        if route.replies.len() > 1 {
+            route.replies.pop_front().expect("non-empty")
+        } else {
+            route.replies[0].clone()
+        }
+    }
+}
+
+/// A local HTTP server answering from scripted routes. Stops when dropped.
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    task: JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Bind an ephemeral localhost port and start serving.
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind mock server");
+        let addr = listener.local_addr().expect("mock server address");
+        let state = Arc::new(Mutex::new(State::default()));
+        let task = tokio::spawn({
+            let state = state.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(serve(stream, state.clone()));
+                }
+            }
+        });
+        Self { addr, state, task }
+    }
+
+    /// The base URL to hand to [`HttpApiClient`](crate::HttpApiClient).
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Script `reply` for `method path`, after any replies already scripted.
+    pub fn on(&self, method: &str, path: &str, reply: Reply) -> &Self {
+        let mut state = self.state.lock().unwrap();
+        match state
+            .routes
+            .iter_mut()
+            .find(|r| r.method == method && r.path == path)
+        {
+            Some(route) => route.replies.push_back(reply),
+            None => state.routes.push(Route {
+                method: method.to_string(),
+                path: path.to_string(),
+                replies: VecDeque::from([reply]),
+            }),
+        }
+        self
+    }
+
+    /// Every request received so far, in arrival order.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Answer a single request, then close. `Connection: close` keeps the client
+/// from pooling, so every request arrives on a fresh connection.
+async fn serve(stream: TcpStream, state: Arc<Mutex<State>>) {
+    let mut reader = BufReader::new(stream);
+    let Some(request) = read_request(&mut reader).await else {
+        return;
+    };
+    let reply = {
+        let mut state = state.lock().unwrap();
+        let reply = state.reply_for(&request.method, &request.path);
+        state.requests.push(request);
+        reply
+    };
+    let reason = reqwest::StatusCode::from_u16(reply.status)
+        .ok()
+        .and_then(|s| s.canonical_reason())
+        .unwrap_or("");
+    let extra: String = reply
+        .headers
+        .iter()
+        .map(|(name, value)| format!("{name}: {value}\r\n"))
+        .collect();
+    let head = format!(
+        "HTTP/1.1 {} {reason}\r\ncontent-type: application/json\r\ncontent-length: {}\r\n{extra}connection: close\r\n\r\n",
+        reply.status,
+        reply.body.len()
+    );
+    let stream = reader.get_mut();
+    let _ = stream.write_all(head.as_bytes()).await;
+    let _ = stream.write_all(reply.body.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+async fn read_request(reader: &mut BufReader<TcpStream>) -> Option<RecordedRequest> {
+    let mut line = String::new();
+    reader.read_line(&mut line).await.ok()?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let target = parts.next()?;
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), Some(query.to_string())),
+        None => (target.to_string(), None),
+    };
+
+    let mut content_length = 0;
+    let mut authorization = None;
+    let mut traceparent = None;
+    let mut api_version = None;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).await.ok()?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let (name, value) = header.split_once(':')?;
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = value.parse().ok()?;
+        } else if name.eq_ignore_ascii_case("authorization") {
+            authorization = Some(value.to_string());
+        } else if name.eq_ignore_ascii_case("traceparent") {
+            traceparent = Some(value.to_string());
+        } else if name.eq_ignore_ascii_case("unisrv-api-version") {
+            api_version = Some(value.to_string());
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).await.ok()?;
+    Some(RecordedRequest {
+        method,
+        path,
+        query,
+        authorization,
+        traceparent,
+        api_version,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
@@ -0,0 +1,86 @@
+//! Tolerant deserialization of API timestamps into `DateTime<Utc>`.
+//!
+//! The API isn't consistent about how it writes a timestamp: RFC 3339 with an
+//! offset (`2025-03-01T12:00:00Z`, `…+02:00`), or a bare UTC time without one
+//! (`2025-03-01T12:00:00.123456`, sometimes with a space for the `T`). All of
+//! them parse here; a bare time is taken to be UTC. Serialization stays
+//! chrono's RFC 3339.
+//!
+//! Use with `#[serde(deserialize_with = "timestamp::deserialize")]`, or
+//! `timestamp::option::deserialize` for an `Option` (with `#[serde(default)]`
+//! so a missing field is `None`).
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Deserializer, de::Error};
+
+/// The offset-less layouts accepted, all read as UTC.
+const NAIVE_FORMATS: &[&str] = &["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"];
+
+/// Parse one API timestamp.
+pub fn parse(s: &str) -> Option<DateTime<Utc>> {
+    let s = s.trim();
+    if let Ok(at) = DateTime::parse_from_rfc3339(s) {
+        return Some(at.with_timezone(&Utc));
+    }
+    NAIVE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+        .map(|naive| naive.and_utc())
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    parse(&s).ok_or_else(|| D::Error::custom(format!("unrecognized timestamp {s:?}")))
+}
+
+pub mod option {
+    use super::*;
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<DateTime<Utc>>, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(s) => super::parse(&s)
+                .map(Some)
+                .ok_or_else(|| D::Error::custom(format!("unrecognized timestamp {s:?}"))),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn parses_every_format_the_api_sends() {
+        let noon = Utc.with_ymd_and_hms(2025, 3, 1, 12, 0, 0).unwrap();
+        for s in [
+            "2025-03-01T12:00:00Z",
+            "2025-03-01T14:00:00+02:00",
+            "2025-03-01T12:00:00",
+            "2025-03-01 12:00:00",
+            " 2025-03-01T12:00:00.000000 ",
+        ] {
+            assert_eq!(parse(s), Some(noon), "{s}");
+        }
+        assert_eq!(parse("yesterday"), None);
+    }
+
+    #[test]
+    fn optional_timestamps_accept_null() {
+        #[derive(Deserialize)]
+        struct At {
+            #[serde(default, deserialize_with = "option::deserialize")]
+            at: Option<DateTime<Utc>>,
+        }
+        let at: At = serde_json::from_str(r#"{"at": null}"#).unwrap();
+        assert_eq!(at.at, None);
+        let at: At = serde_json::from_str("{}").unwrap();
+        assert_eq!(at.at, None);
+        let at: At = serde_json::from_str(r#"{"at": "2025-03-01T12:00:00"}"#).unwrap();
+        assert!(at.at.is_some());
+        assert!(serde_json::from_str::<At>(r#"{"at": "soon"}"#).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+//! W3C trace context, so a CLI run can be found in the platform's traces.
+//!
+//! When enabled, every request carries a `traceparent` header naming one
+//! trace. The CLI joins the trace in `$TRACEPARENT` if its caller started one
+//! (a CI job or a wrapper script exporting OpenTelemetry context), and
+//! otherwise starts its own. Either way the whole run is a single span, and
+//! its trace id is what a support engineer searches for.
+
+use std::fmt;
+
+/// Environment variable carrying a parent `traceparent`, per the OpenTelemetry
+/// environment-carrier convention.
+pub const TRACEPARENT_ENV: &str = "TRACEPARENT";
+
+/// Header name requests carry the context in.
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceContext {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    sampled: bool,
+}
+
+impl TraceContext {
+    /// A new, sampled trace.
+    pub fn generate() -> Self {
+        Self {
+            trace_id: *uuid::Uuid::new_v4().as_bytes(),
+            span_id: new_span_id(),
+            sampled: true,
+        }
+    }
+
+    /// A span within this context's trace, as the caller's child.
+    pub fn child(&self) -> Self {
+        Self {
+            span_id: new_span_id(),
+            ..*self
+        }
+    }
+
+    /// Parse a `traceparent` value, `00-<trace id>-<parent id>-<flags>`.
+    /// Returns `None` for anything malformed, including the all-zero ids the
+    /// spec declares invalid.
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut parts = s.trim().split('-');
+        let (version, trace_id, span_id, flags) =
+            (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+        // Version 00 has exactly four fields; later versions may append more.
+        if version.len() != 2 || version == "ff" || (version == "00" && parts.next().is_some()) {
+            return None;
+        }
+        let [_] = hex::<1>(version)?;
+        let trace_id = hex::<16>(trace_id)?;
+        let span_id = hex::<8>(span_id)?;
+        let [flags] = hex::<1>(flags)?;
+        if trace_id == [0; 16] || span_id == [0; 8] {
+            return None;
+        }
+        Some(Self {
+            trace_id,
+            span_id,
+            sampled: flags & 1 == 1,
+        })
+    }
+
+    /// The context in `$TRACEPARENT`, if it's set. A malformed value is
+    /// ignored with a warning rather than failing the command.
+    pub fn from_env() -> Option<Self> {
+        let value = std::env::var(TRACEPARENT_ENV).ok()?;
+        let parsed = Self::parse(&value);
+        if parsed.is_none() {
+            tracing::warn!("ignoring malformed {TRACEPARENT_ENV} {value:?}");
+        }
+        parsed
+    }
+
+    /// The trace id as 32 lowercase hex digits.
+    pub fn trace_id(&self) -> String {
+        to_hex(&self.trace_id)
+    }
+}
+
+/// The `traceparent` header value.
+impl fmt::Display for TraceContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "00-{}-{}-{:02x}",
+            to_hex(&self.trace_id),
+            to_hex(&self.span_id),
+            u8::from(self.sampled)
+        )
+    }
+}
+
+fn new_span_id() -> [u8; 8] {
+    let bytes = uuid::Uuid::new_v4().into_bytes();
+    let mut id = [0; 8];
+    id.copy_from_slice(&bytes[..8]);
+    id
+}
+
+/// Exactly `N` bytes of lowercase hex (the spec forbids uppercase).
+fn hex<const N: usize>(s: &str) -> Option<[u8; N]> {
+    if s.len() != N * 2
+        || !s
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+    {
+        return None;
+    }
+    let mut out = [0; N];
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(out)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn round_trips_a_traceparent() {
+        let ctx = TraceContext::parse(EXAMPLE).unwrap();
+        assert_eq!(ctx.trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(ctx.to_string(), EXAMPLE);
+    }
+
+    #[test]
+    fn child_keeps_the_trace_and_sampling_but_not_the_span() {
+        let parent = TraceContext::parse(EXAMPLE).unwrap();
+        let child = parent.child();
+        assert_eq!(child.trace_id(), parent.trace_id());
+        assert!(child.to_string().ends_with("-01"));
+        assert_ne!(child, parent);
+    }
+
+    #[test]
+    fn rejects_malformed_values() {
+        for bad in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+        ] {
+            assert_eq!(TraceContext::parse(bad), None, "{bad}");
+        }
+    }
+
+    #[test]
+    fn generated_contexts_are_valid_and_sampled() {
+        let ctx = TraceContext::generate();
+        assert_eq!(TraceContext::parse(&ctx.to_string()), Some(ctx));
+    }
+}
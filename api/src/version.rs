@@ -0,0 +1,123 @@
+//! API version negotiation.
+//!
+//! Every request names the API version the CLI speaks in the
+//! `Unisrv-Api-Version` header — [`ApiVersion::CURRENT`] unless pinned with
+//! `--api-version` — and the server answers with the version it serves. The
+//! first answer is compared once per run: within a major version changes are
+//! additive, and response structs ignore fields and variants they don't know
+//! (see the `Unknown` variants in [`crate::models`]), so only a different
+//! major version, or a server older than the one asked for, is worth a
+//! warning. A server that doesn't send the header isn't checked.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Request and response header carrying an [`ApiVersion`].
+pub const API_VERSION_HEADER: &str = "unisrv-api-version";
+
+/// A `major.minor` API version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ApiVersion {
+    pub major: u16,
+    pub minor: u16,
+}
+
+impl ApiVersion {
+    /// The newest version this CLI was built against.
+    pub const CURRENT: ApiVersion = ApiVersion { major: 1, minor: 4 };
+    /// The oldest version this CLI still works with.
+    pub const OLDEST: ApiVersion = ApiVersion { major: 1, minor: 0 };
+
+    /// Whether this CLI can speak `self`.
+    pub fn is_supported(self) -> bool {
+        (Self::OLDEST..=Self::CURRENT).contains(&self)
+    }
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+impl FromStr for ApiVersion {
+    type Err = String;
+
+    /// `1`, `1.4` or `v1.4`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        let digits = trimmed.strip_prefix('v').unwrap_or(trimmed);
+        let (major, minor) = digits.split_once('.').unwrap_or((digits, "0"));
+        match (major.parse(), minor.parse()) {
+            (Ok(major), Ok(minor)) => Ok(ApiVersion { major, minor }),
+            _ => Err(format!("{s:?} is not an API version (expected e.g. 1.4)")),
+        }
+    }
+}
+
+/// What to tell the user when the server serves `server` and the CLI asked
+/// for `requested`, if anything.
+pub fn mismatch_warning(requested: ApiVersion, server: ApiVersion) -> Option<String> {
+    if server.major > requested.major {
+        Some(format!(
+            "the API server speaks version {server}, newer than the {requested} this CLI speaks; \
+             some responses may not be understood — upgrade unisrv"
+        ))
+    } else if server.major < requested.major || server < ApiVersion::OLDEST {
+        Some(format!(
+            "the API server speaks version {server}, older than this CLI supports \
+             ({} to {}); commands may fail",
+            ApiVersion::OLDEST,
+            ApiVersion::CURRENT
+        ))
+    } else if server.minor < requested.minor {
+        Some(format!(
+            "the API server speaks version {server}, older than the {requested} asked for; \
+             newer features may be unavailable (pin an older one with --api-version {server})"
+        ))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(s: &str) -> ApiVersion {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_versions() {
+        assert_eq!(v("1.4"), ApiVersion { major: 1, minor: 4 });
+        assert_eq!(v("v2"), ApiVersion { major: 2, minor: 0 });
+        assert_eq!(v(" 1.10 ").to_string(), "1.10");
+        assert!("one".parse::<ApiVersion>().is_err());
+        assert!("1.x".parse::<ApiVersion>().is_err());
+    }
+
+    #[test]
+    fn only_breaking_or_missing_versions_warn() {
+        // A newer minor only adds things the structs already tolerate.
+        assert_eq!(mismatch_warning(v("1.2"), v("1.7")), None);
+        assert_eq!(mismatch_warning(v("1.2"), v("1.2")), None);
+
+        let newer = mismatch_warning(v("1.2"), v("2.0")).unwrap();
+        assert!(newer.contains("upgrade unisrv"), "{newer}");
+        let older_major = mismatch_warning(v("1.2"), v("0.9")).unwrap();
+        assert!(
+            older_major.contains("older than this CLI supports"),
+            "{older_major}"
+        );
+        let older_minor = mismatch_warning(v("1.4"), v("1.1")).unwrap();
+        assert!(older_minor.contains("--api-version 1.1"), "{older_minor}");
+    }
+
+    #[test]
+    fn supported_range_is_inclusive() {
+        assert!(ApiVersion::OLDEST.is_supported());
+        assert!(ApiVersion::CURRENT.is_supported());
+        assert!(!v("2.0").is_supported());
+    }
+}
@@ -0,0 +1,131 @@
+//! `unisrv account` — the accounts logged into on this machine, and which one
+//! commands run as.
+//!
+//! `unisrv login` adds an account rather than replacing the last one, so
+//! someone managing several customers' accounts logs into each once and then
+//! `account switch`es between them. Each API host has its own account in use
+//! (see [`unisrv_api::Accounts`]); `switch` changes the current host's.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use comfy_table::{Attribute, Cell};
+use serde::Serialize;
+use unisrv_api::{Accounts, AuthStore};
+use uuid::Uuid;
+
+use crate::commands::output::Output;
+use crate::commands::ui::{format_relative, new_table};
+
+/// An account as listed: never its tokens.
+#[derive(Debug, Serialize)]
+struct Listed<'a> {
+    username: &'a str,
+    api_host: &'a str,
+    user_id: Uuid,
+    active: bool,
+    /// When the session lapses and a new login is needed.
+    session_expires_at: DateTime<Utc>,
+}
+
+fn listed(accounts: &Accounts) -> Vec<Listed<'_>> {
+    accounts
+        .accounts
+        .iter()
+        .map(|account| Listed {
+            username: &account.username,
+            api_host: &account.api_host,
+            user_id: account.session.user_id,
+            active: accounts.active.get(&account.api_host) == Some(&account.username),
+            session_expires_at: account.session.refresh_token_expiry,
+        })
+        .collect()
+}
+
+/// `unisrv account list`.
+pub fn list(store: &AuthStore, output: &Output) -> Result<()> {
+    let accounts = store.accounts();
+    let listed = listed(&accounts);
+    match output {
+        Output::Json | Output::Jq(_) => return output.print_json(&listed),
+        Output::Template(template) => {
+            print!("{}", template.render_all(&listed)?);
+            return Ok(());
+        }
+        Output::Table => {}
+    }
+    if listed.is_empty() {
+        println!("No accounts. Log in with `unisrv login`.");
+        return Ok(());
+    }
+    println!("{}", render_table(&listed, Utc::now()));
+    Ok(())
+}
+
+fn render_table(accounts: &[Listed], now: DateTime<Utc>) -> String {
+    let mut table = new_table();
+    table.set_header(vec![
+        Cell::new("").add_attribute(Attribute::Bold),
+        Cell::new("ACCOUNT").add_attribute(Attribute::Bold),
+        Cell::new("API HOST").add_attribute(Attribute::Bold),
+        Cell::new("SESSION").add_attribute(Attribute::Bold),
+    ]);
+    for account in accounts {
+        let session = if account.session_expires_at <= now {
+            "expired; log in again".to_string()
+        } else {
+            format!(
+                "expires {}",
+                format_relative(account.session_expires_at, now)
+            )
+        };
+        table.add_row(vec![
+            Cell::new(if account.active { "*" } else { "" }),
+            Cell::new(account.username),
+            Cell::new(account.api_host),
+            Cell::new(session),
+        ]);
+    }
+    table.to_string()
+}
+
+/// `unisrv account switch <username>`.
+pub fn switch(store: &AuthStore, username: &str) -> Result<()> {
+    let account = store.switch(username)?;
+    println!(
+        "\u{2713} Switched to {} on {}.",
+        account.username, account.api_host
+    );
+    if account.session.expired() {
+        eprintln!(
+            "{}",
+            console::style("Its session has expired; log in again with `unisrv login`.").dim()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use unisrv_api::AuthSession;
+
+    #[test]
+    fn listing_marks_the_account_in_use_and_leaves_out_tokens() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = AuthStore::in_dir(dir.path(), "https://api.unisrv.io");
+        let session = AuthSession::test_session("secret", chrono::Duration::hours(1));
+        store.add("ops@acme", &session).unwrap();
+        store.add("ops@globex", &session).unwrap();
+        switch(&store, "ops@acme").unwrap();
+
+        let accounts = store.accounts();
+        let listed = listed(&accounts);
+        let active: Vec<_> = listed.iter().map(|a| (a.username, a.active)).collect();
+        assert_eq!(active, [("ops@acme", true), ("ops@globex", false)]);
+        let json = serde_json::to_string(&listed).unwrap();
+        assert!(!json.contains("secret"), "{json}");
+
+        let table = render_table(&listed, Utc::now());
+        assert!(table.contains("ops@globex"), "{table}");
+    }
+}
@@ -1,7 +1,28 @@
-use anyhow::Result;
+//! `unisrv auth` — the session's access token, and long-lived API tokens for
+//! CI, where nobody can log in interactively.
+//!
+//! `create-token` prints a token's secret once; a CI job then authenticates
+//! by setting `UNISRV_TOKEN` to it, which takes the place of the login
+//! session (see [`unisrv_api::TOKEN_ENV`]). Tokens expire after `--expires`
+//! (90 days unless told otherwise, or `never`), and a `read-only` one can
+//! only read. Pipelines that should hold narrower permissions than their
+//! creator use a service account instead (see [`super::service_account`]).
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{Result, bail};
 use chrono::{DateTime, Utc};
+use comfy_table::{Attribute, Cell};
 use serde::Serialize;
-use unisrv_api::ApiClient;
+use unisrv_api::models::{ApiTokenListItem, CreateApiTokenRequest, TokenScope};
+use unisrv_api::{ApiClient, TOKEN_ENV};
+use uuid::Uuid;
+
+use crate::commands::confirm::Guard;
+use crate::commands::output::Output;
+use crate::commands::ui::{format_relative, new_table};
+use crate::commands::up::ready::parse_duration;
 
 #[derive(Serialize)]
 struct JsonToken {
@@ -9,8 +30,8 @@ struct JsonToken {
     expires_at: DateTime<Utc>,
 }
 
-pub async fn token(client: &dyn ApiClient, json: bool) -> Result<()> {
-    if !json {
+pub async fn token(client: &dyn ApiClient, output: &Output) -> Result<()> {
+    if !output.is_machine() {
         let token = client.access_token().await?;
         print!("{token}");
         return Ok(());
@@ -21,20 +42,186 @@ pub async fn token(client: &dyn ApiClient, json: bool) -> Result<()> {
         token: session.access_token().to_string(),
         expires_at: session.access_token_expiry,
     };
-    println!("{}", serde_json::to_string(&json_token)?);
+    match output {
+        Output::Jq(_) => output.print_json(&json_token),
+        _ => {
+            println!("{}", serde_json::to_string(&json_token)?);
+            Ok(())
+        }
+    }
+}
+
+/// `--expires`: a duration like `90d`, or `never`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Expiry(Option<Duration>);
+
+impl Expiry {
+    /// When a token created at `now` expires; `None` for never.
+    pub fn at(self, now: DateTime<Utc>) -> Result<Option<DateTime<Utc>>> {
+        match self.0 {
+            Some(after) => Ok(Some(now + chrono::Duration::from_std(after)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl FromStr for Expiry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "never" => Ok(Expiry(None)),
+            s => parse_duration(s).map(|d| Expiry(Some(d))),
+        }
+    }
+}
+
+/// `--scope`: `full` or `read-only`.
+pub fn parse_scope(s: &str) -> Result<TokenScope, String> {
+    match s {
+        "full" => Ok(TokenScope::Full),
+        "read-only" => Ok(TokenScope::ReadOnly),
+        _ => Err(format!("invalid scope {s:?}: expected full or read-only")),
+    }
+}
+
+/// `unisrv auth create-token`.
+pub async fn create_token(
+    client: &dyn ApiClient,
+    name: &str,
+    expires: Expiry,
+    scope: TokenScope,
+    output: &Output,
+) -> Result<()> {
+    let expires_at = expires.at(Utc::now())?;
+    let created = client
+        .create_api_token(CreateApiTokenRequest {
+            name: name.to_string(),
+            scope,
+            expires_at,
+        })
+        .await?;
+    if output.is_machine() {
+        return output.print_json(&created);
+    }
+    let expiry = match created.expires_at {
+        Some(at) => format!("expires {}", at.format("%Y-%m-%d")),
+        None => "never expires".to_string(),
+    };
+    println!(
+        "\u{2713} Created {} token {} ({expiry}).",
+        created.scope.as_str(),
+        created.name
+    );
+    println!("  Set {TOKEN_ENV} to it; it won't be shown again:");
+    println!("{}", created.token);
+    Ok(())
+}
+
+/// `unisrv auth tokens list`.
+pub async fn list_tokens(client: &dyn ApiClient, output: &Output) -> Result<()> {
+    let resp = client.list_api_tokens().await?;
+    match output {
+        Output::Json | Output::Jq(_) => return output.print_json(&resp.tokens),
+        Output::Template(template) => {
+            print!("{}", template.render_all(&resp.tokens)?);
+            return Ok(());
+        }
+        Output::Table => {}
+    }
+    if resp.tokens.is_empty() {
+        println!("No API tokens. Create one with `unisrv auth create-token --name <name>`.");
+        return Ok(());
+    }
+    println!("{}", render_table(&resp.tokens, Utc::now()));
     Ok(())
 }
 
+fn render_table(tokens: &[ApiTokenListItem], now: DateTime<Utc>) -> String {
+    let mut table = new_table();
+    table.set_header(vec![
+        Cell::new("NAME").add_attribute(Attribute::Bold),
+        Cell::new("SCOPE").add_attribute(Attribute::Bold),
+        Cell::new("CREATED").add_attribute(Attribute::Bold),
+        Cell::new("EXPIRES").add_attribute(Attribute::Bold),
+        Cell::new("LAST USED").add_attribute(Attribute::Bold),
+    ]);
+    for token in tokens {
+        let expires = match token.expires_at {
+            Some(at) if at <= now => "expired".to_string(),
+            Some(at) => format_relative(at, now),
+            None => "never".to_string(),
+        };
+        let last_used = token
+            .last_used_at
+            .map_or_else(|| "never".to_string(), |at| format_relative(at, now));
+        table.add_row(vec![
+            Cell::new(&token.name),
+            Cell::new(token.scope.as_str()),
+            Cell::new(format_relative(token.created_at, now)),
+            Cell::new(expires),
+            Cell::new(last_used),
+        ]);
+    }
+    table.to_string()
+}
+
+/// `unisrv auth revoke <name-or-id>`.
+pub async fn revoke(client: &dyn ApiClient, reference: &str, yes: bool) -> Result<()> {
+    let tokens = client.list_api_tokens().await?.tokens;
+    let token = resolve_token(&tokens, reference)?;
+    // Tokens belong to the account, not an environment, so they're never
+    // protected.
+    let confirmed = Guard::unprotected().confirm_or_ask(
+        &format!("revoke the API token {}", token.name),
+        &token.name,
+        &format!(
+            "Revoke API token {}? Anything using it stops working.",
+            token.name
+        ),
+        yes,
+    )?;
+    if !confirmed {
+        println!("Aborted.");
+        return Ok(());
+    }
+    client.revoke_api_token(token.id).await?;
+    println!("\u{2713} Revoked {}.", token.name);
+    Ok(())
+}
+
+/// The token `reference` names, by id or by name.
+fn resolve_token<'a>(
+    tokens: &'a [ApiTokenListItem],
+    reference: &str,
+) -> Result<&'a ApiTokenListItem> {
+    if let Ok(id) = reference.parse::<Uuid>()
+        && let Some(token) = tokens.iter().find(|t| t.id == id)
+    {
+        return Ok(token);
+    }
+    let named: Vec<&ApiTokenListItem> = tokens.iter().filter(|t| t.name == reference).collect();
+    match named.as_slice() {
+        [token] => Ok(token),
+        [] => bail!("no API token named {reference:?}; see `unisrv auth tokens list`"),
+        _ => bail!(
+            "{} API tokens are named {reference:?}; revoke one by its id",
+            named.len()
+        ),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use unisrv_api::ApiError;
+    use unisrv_api::models::{ApiTokenListResponse, CreatedApiToken};
     use unisrv_api::test_support::MockApiClient;
 
     #[tokio::test]
     async fn token_returns_access_token() {
         let mock = MockApiClient::logged_in();
-        let result = token(&mock, false).await;
+        let result = token(&mock, &Output::Table).await;
         assert!(result.is_ok());
 
         let calls = mock.calls.lock().unwrap();
@@ -46,7 +233,7 @@ mod tests {
     #[tokio::test]
     async fn token_json_includes_expiry() {
         let mock = MockApiClient::logged_in();
-        let result = token(&mock, true).await;
+        let result = token(&mock, &Output::Json).await;
         assert!(result.is_ok());
 
         let calls = mock.calls.lock().unwrap();
@@ -57,7 +244,7 @@ mod tests {
     #[tokio::test]
     async fn token_fails_when_not_logged_in() {
         let mock = MockApiClient::logged_out();
-        let result = token(&mock, false).await;
+        let result = token(&mock, &Output::Table).await;
         assert!(result.is_err());
         let err = result.unwrap_err();
         assert!(err.downcast_ref::<ApiError>().is_some());
@@ -66,7 +253,77 @@ mod tests {
     #[tokio::test]
     async fn token_json_fails_when_not_logged_in() {
         let mock = MockApiClient::logged_out();
-        let result = token(&mock, true).await;
+        let result = token(&mock, &Output::Json).await;
         assert!(result.is_err());
     }
+
+    fn listed(name: &str) -> ApiTokenListItem {
+        ApiTokenListItem {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            scope: TokenScope::ReadOnly,
+            created_at: Utc::now(),
+            expires_at: None,
+            last_used_at: None,
+        }
+    }
+
+    #[test]
+    fn parses_expiries() {
+        assert_eq!(
+            "90d".parse::<Expiry>(),
+            Ok(Expiry(Some(Duration::from_secs(90 * 86_400))))
+        );
+        assert_eq!("never".parse::<Expiry>(), Ok(Expiry(None)));
+        assert!("soon".parse::<Expiry>().is_err());
+    }
+
+    #[tokio::test]
+    async fn create_token_asks_for_the_expiry_and_scope() {
+        let mock = MockApiClient::logged_in().push_create_api_token(Ok(CreatedApiToken {
+            id: Uuid::new_v4(),
+            name: "ci".into(),
+            token: "usk_secret".into(),
+            scope: TokenScope::ReadOnly,
+            expires_at: None,
+        }));
+        let expires = "90d".parse().unwrap();
+        create_token(&mock, "ci", expires, TokenScope::ReadOnly, &Output::Table)
+            .await
+            .unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let req = &calls.create_api_token_calls[0];
+        assert_eq!((req.name.as_str(), req.scope), ("ci", TokenScope::ReadOnly));
+        let days = (req.expires_at.unwrap() - Utc::now()).num_days();
+        assert!((89..=90).contains(&days), "{days}");
+        assert_eq!(
+            serde_json::to_value(req).unwrap()["scope"],
+            serde_json::json!("read-only")
+        );
+    }
+
+    #[tokio::test]
+    async fn revoke_finds_a_token_by_name_or_id() {
+        let ci = listed("ci");
+        let id = ci.id;
+        let mock = MockApiClient::logged_in()
+            .with_list_api_tokens(Ok(ApiTokenListResponse {
+                tokens: vec![listed("deploy"), ci],
+            }))
+            .push_revoke_api_token(Ok(()));
+        revoke(&mock, "ci", true).await.unwrap();
+        assert_eq!(mock.calls.lock().unwrap().revoke_api_token_calls, [id]);
+
+        let tokens = [listed("ci"), listed("ci")];
+        assert_eq!(
+            resolve_token(&tokens, &tokens[1].id.to_string())
+                .unwrap()
+                .id,
+            tokens[1].id
+        );
+        let err = resolve_token(&tokens, "ci").unwrap_err();
+        assert!(err.to_string().contains("revoke one by its id"), "{err:#}");
+        assert!(resolve_token(&tokens, "nope").is_err());
+    }
 }
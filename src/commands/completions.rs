@@ -0,0 +1,398 @@
+//! `unisrv completions bash|zsh|fish|powershell` — a tab-completion script
+//! for the shell, and the hidden `unisrv __complete` it calls for names.
+//!
+//! The script is clap_complete's, which knows every subcommand and flag, with
+//! a hook in front of it: where an argument names something (`unisrv instance
+//! stop <TAB>`), the hook runs `unisrv __complete -- <words before the
+//! cursor>` and offers the instance, network, service or deployment names it
+//! prints, falling back to the generated completion when it prints none.
+//! `instance run <TAB>` offers the images deployed most recently instead.
+//!
+//! That's a process and a few API calls per TAB, so the names are kept in the
+//! state directory for [`CACHE_MAX_AGE`], per working directory and `--env`;
+//! something created in the meantime takes that long to be offered.
+//! `__complete` never prompts and never fails: not logged in, or with no
+//! environment to pick, it prints nothing.
+//!
+//! ```text
+//! unisrv completions bash > ~/.local/share/bash-completion/completions/unisrv
+//! unisrv completions zsh > "${fpath[1]}/_unisrv"
+//! unisrv completions fish > ~/.config/fish/completions/unisrv.fish
+//! unisrv completions powershell >> $PROFILE
+//! ```
+
+use std::str::FromStr;
+
+use anyhow::{Result, bail};
+use clap::Command;
+use serde::{Deserialize, Serialize};
+use unisrv_api::ApiClient;
+
+use crate::commands::instance::run::current_environment;
+use crate::commands::up::plan::ResolvedEnvironment;
+use crate::state::StateDir;
+
+/// How long names fetched for completion are offered before being fetched
+/// again.
+pub const CACHE_MAX_AGE: chrono::Duration = chrono::Duration::seconds(30);
+
+/// The shells a script can be generated for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+impl FromStr for Shell {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            "powershell" => Ok(Shell::Powershell),
+            _ => Err(format!(
+                "unknown shell {s:?}: expected bash, zsh, fish or powershell"
+            )),
+        }
+    }
+}
+
+/// `unisrv completions <shell>`.
+pub fn print(shell: Shell, grammar: Command) -> Result<()> {
+    print!("{}", script(shell, grammar)?);
+    Ok(())
+}
+
+/// The generated script for `grammar`, with the names hook spliced in.
+fn script(shell: Shell, mut grammar: Command) -> Result<String> {
+    let name = grammar.get_name().to_string();
+    let mut buf = Vec::new();
+    let generator = match shell {
+        Shell::Bash => clap_complete::Shell::Bash,
+        Shell::Zsh => clap_complete::Shell::Zsh,
+        Shell::Fish => clap_complete::Shell::Fish,
+        Shell::Powershell => clap_complete::Shell::PowerShell,
+    };
+    clap_complete::generate(generator, &mut grammar, &name, &mut buf);
+    let generated = String::from_utf8(buf)?;
+    match shell {
+        Shell::Bash => Ok(generated + BASH_HOOK),
+        Shell::Fish => Ok(generated + FISH_HOOK),
+        Shell::Zsh => splice(&generated, ZSH_REGISTRATION, ZSH_HOOK),
+        Shell::Powershell => {
+            let script = splice(&generated, POWERSHELL_REGISTRATION, POWERSHELL_GENERATED)?;
+            Ok(script + POWERSHELL_HOOK)
+        }
+    }
+}
+
+/// `generated` with `from` replaced. Guards against a clap_complete release
+/// that words its script differently, which would leave the hook unused.
+fn splice(generated: &str, from: &str, to: &str) -> Result<String> {
+    if !generated.contains(from) {
+        bail!("the generated completion script has an unexpected shape; please report this");
+    }
+    Ok(generated.replacen(from, to, 1))
+}
+
+const BASH_HOOK: &str = r#"
+# Names (`unisrv instance stop <TAB>`) come from `unisrv __complete`.
+_unisrv_names() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" names
+    if [[ ${cur} != -* ]]; then
+        names=$(unisrv __complete -- "${COMP_WORDS[@]:1:COMP_CWORD-1}" 2>/dev/null)
+        if [[ -n ${names} ]]; then
+            COMPREPLY=( $(compgen -W "${names}" -- "${cur}") )
+            return 0
+        fi
+    fi
+    _unisrv "$@"
+}
+
+complete -F _unisrv_names -o bashdefault -o default unisrv
+"#;
+
+const ZSH_REGISTRATION: &str = r#"if [ "$funcstack[1]" = "_unisrv" ]; then
+    _unisrv "$@"
+else
+    compdef _unisrv unisrv
+fi
+"#;
+
+// Autoloaded from $fpath, the file runs as `_unisrv` on the first TAB and
+// redefines it, so the hook registers itself in its place from then on.
+const ZSH_HOOK: &str = r#"# Names (`unisrv instance stop <TAB>`) come from `unisrv __complete`.
+_unisrv_names() {
+    local -a names
+    if [[ ${PREFIX} != -* ]]; then
+        names=(${(f)"$(unisrv __complete -- "${(@)words[2,CURRENT-1]}" 2>/dev/null)"})
+    fi
+    if (( ${#names} )); then
+        compadd -a names
+    else
+        _unisrv "$@"
+    fi
+}
+
+if [ "$funcstack[1]" = "_unisrv" ]; then
+    compdef _unisrv_names unisrv
+    _unisrv_names "$@"
+else
+    compdef _unisrv_names unisrv
+fi
+"#;
+
+const FISH_HOOK: &str = r#"
+# Names (`unisrv instance stop <TAB>`) come from `unisrv __complete`.
+function __unisrv_names
+    set -l words (commandline -opc)
+    unisrv __complete -- $words[2..-1] 2>/dev/null
+end
+
+complete -c unisrv -a '(__unisrv_names)'
+"#;
+
+const POWERSHELL_REGISTRATION: &str =
+    "Register-ArgumentCompleter -Native -CommandName 'unisrv' -ScriptBlock {";
+
+const POWERSHELL_GENERATED: &str = "$global:__unisrvGenerated = {";
+
+const POWERSHELL_HOOK: &str = r#"
+# Names (`unisrv instance stop <TAB>`) come from `unisrv __complete`.
+Register-ArgumentCompleter -Native -CommandName 'unisrv' -ScriptBlock {
+    param($wordToComplete, $commandAst, $cursorPosition)
+
+    if (-not $wordToComplete.StartsWith('-')) {
+        $words = @($commandAst.CommandElements |
+            Select-Object -Skip 1 |
+            Where-Object { $_.Extent.EndOffset -lt $cursorPosition } |
+            ForEach-Object { $_.Extent.Text })
+        $names = @(unisrv __complete -- @words 2>$null)
+        if ($names.Count -gt 0) {
+            $names.Where{ $_ -like "$wordToComplete*" } | ForEach-Object {
+                [CompletionResult]::new($_, $_, [CompletionResultType]::ParameterValue, $_)
+            }
+            return
+        }
+    }
+    & $global:__unisrvGenerated $wordToComplete $commandAst $cursorPosition
+}
+"#;
+
+/// `unisrv __complete -- <words>`: one name per line for the argument after
+/// `before`, the words typed so far without the binary name.
+pub async fn complete(client: &dyn ApiClient, mut grammar: Command, before: &[String]) {
+    // Built, so global flags show up on every subcommand.
+    grammar.build();
+    let resources = cached_resources(client, env_flag(before)).await;
+    for name in names(&grammar, &resources, before) {
+        println!("{name}");
+    }
+}
+
+/// The names the next argument after `before` may take: none where it's a
+/// subcommand, a flag's value or nothing at all.
+fn names<'a>(grammar: &Command, resources: &'a Resources, before: &[String]) -> Vec<&'a str> {
+    let last = before.last().map(String::as_str);
+    if last == Some("--env") {
+        return resources.environments.iter().map(String::as_str).collect();
+    }
+    let mut command = grammar;
+    let mut path = Vec::new();
+    for word in before {
+        if let Some(sub) = command.find_subcommand(word) {
+            command = sub;
+            path.push(sub.get_name());
+        }
+    }
+    let takes_value = |word: &str| {
+        command.get_arguments().any(|arg| {
+            let named = match word.strip_prefix("--") {
+                Some(long) => arg.get_long() == Some(long),
+                None => word.strip_prefix('-').is_some_and(|short| {
+                    arg.get_short().map(String::from).as_deref() == Some(short)
+                }),
+            };
+            named && arg.get_action().takes_values()
+        })
+    };
+    if last.is_some_and(takes_value)
+        || command.has_subcommands()
+        || command.get_positionals().next().is_none()
+    {
+        return Vec::new();
+    }
+    resources.for_command(&path)
+}
+
+/// `--env <name>` or `--env=<name>` among `before`.
+fn env_flag(before: &[String]) -> Option<&str> {
+    before
+        .windows(2)
+        .find(|pair| pair[0] == "--env")
+        .map(|pair| pair[1].as_str())
+        .or_else(|| before.iter().find_map(|w| w.strip_prefix("--env=")))
+}
+
+/// The names for the current directory's environment, or `env`'s, from the
+/// state directory while they're fresh.
+async fn cached_resources(client: &dyn ApiClient, env: Option<&str>) -> Resources {
+    let state = StateDir::locate();
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let key = format!("{}\n{}", cwd.display(), env.unwrap_or_default());
+    if let Some(cached) = state
+        .as_ref()
+        .and_then(|state| state.cached_completion(&key, CACHE_MAX_AGE))
+        && let Ok(resources) = serde_json::from_value(cached)
+    {
+        return resources;
+    }
+    let env = current_environment(client, env).await.ok();
+    let resources = Resources::load(client, env.as_ref()).await;
+    // Only a convenience: failing to cache costs the next TAB a fetch.
+    if let (Some(state), Ok(value)) = (&state, serde_json::to_value(&resources)) {
+        let _ = state.cache_completion(&key, value);
+    }
+    resources
+}
+
+/// Names in the current environment, offered as arguments.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Resources {
+    pub environments: Vec<String>,
+    pub instances: Vec<String>,
+    pub networks: Vec<String>,
+    pub services: Vec<String>,
+    pub deployments: Vec<String>,
+    /// Recently deployed images, from the state directory rather than the
+    /// API, for `instance run`.
+    #[serde(default)]
+    pub images: Vec<String>,
+}
+
+impl Resources {
+    /// A list that fails to load just isn't offered. `unisrv shell` calls
+    /// this through its cache, so it only reaches the API when a list
+    /// expired or the last command changed something.
+    pub async fn load(client: &dyn ApiClient, env: Option<&ResolvedEnvironment>) -> Self {
+        let mut resources = Self {
+            images: StateDir::locate()
+                .map(|state| state.recent_images())
+                .unwrap_or_default(),
+            ..Self::default()
+        };
+        if let Ok(list) = client.list_environments().await {
+            resources.environments = list.environments.into_iter().map(|e| e.name).collect();
+        }
+        let Some(env) = env else {
+            return resources;
+        };
+        if let Ok(list) = client.list_instances(env.id).await {
+            resources.instances = list.instances.into_iter().filter_map(|i| i.name).collect();
+        }
+        if let Ok(list) = client.list_networks(env.id, false).await {
+            resources.networks = list.networks.into_iter().map(|n| n.name).collect();
+        }
+        if let Ok(list) = client.list_services(env.id).await {
+            resources.services = list.services.into_iter().map(|s| s.name).collect();
+        }
+        if let Ok(list) = client.list_deployments(env.id).await {
+            resources.deployments = list.deployments.into_iter().map(|d| d.name).collect();
+        }
+        resources
+    }
+
+    /// What an argument of the command at `path` may name.
+    pub fn for_command(&self, path: &[&str]) -> Vec<&str> {
+        let lists: Vec<&Vec<String>> = match path.first().copied() {
+            Some("instance") if path.get(1) == Some(&"run") => vec![&self.images],
+            Some("instance") => vec![&self.instances],
+            Some("network" | "net") => vec![&self.networks, &self.instances],
+            Some("service") => vec![&self.services, &self.instances],
+            Some("logs") => vec![&self.deployments, &self.services],
+            _ => vec![],
+        };
+        lists.into_iter().flatten().map(String::as_str).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, ArgAction};
+
+    fn grammar() -> Command {
+        let env = || Arg::new("env").long("env");
+        let mut grammar = Command::new("unisrv")
+            .subcommand(
+                Command::new("instance")
+                    .subcommand(
+                        Command::new("list")
+                            .arg(Arg::new("all").long("all").action(ArgAction::SetTrue))
+                            .arg(env()),
+                    )
+                    .subcommand(Command::new("run").arg(Arg::new("image")))
+                    .subcommand(
+                        Command::new("stop")
+                            .arg(Arg::new("reference"))
+                            .arg(Arg::new("timeout").short('t').long("timeout"))
+                            .arg(env()),
+                    ),
+            )
+            .subcommand(
+                Command::new("network").subcommand(Command::new("ips").arg(Arg::new("network"))),
+            );
+        grammar.build();
+        grammar
+    }
+
+    fn words(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn names_follow_the_command_at_the_cursor() {
+        let resources = Resources {
+            environments: vec!["prod".into()],
+            instances: vec!["api-1".into(), "worker".into()],
+            networks: vec!["backend".into()],
+            images: vec!["nginx:1".into()],
+            ..Resources::default()
+        };
+        let names = |line| names(&grammar(), &resources, &words(line));
+        assert_eq!(names("instance stop"), ["api-1", "worker"]);
+        assert_eq!(names("instance run"), ["nginx:1"]);
+        assert_eq!(names("instance stop --env"), ["prod"]);
+        assert_eq!(names("network ips"), ["backend", "api-1", "worker"]);
+        // A subcommand, a flag's value, or a command without arguments.
+        assert!(names("instance").is_empty());
+        assert!(names("instance stop --timeout").is_empty());
+        assert!(names("instance stop -t").is_empty());
+        assert!(names("instance list").is_empty());
+    }
+
+    #[test]
+    fn env_is_read_from_either_spelling() {
+        assert_eq!(env_flag(&words("instance stop --env prod")), Some("prod"));
+        assert_eq!(env_flag(&words("instance stop --env=prod")), Some("prod"));
+        assert_eq!(env_flag(&words("instance stop --env")), None);
+    }
+
+    #[test]
+    fn every_script_hands_names_to_the_hook() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::Powershell] {
+            let script = script(shell, grammar()).unwrap();
+            assert!(script.contains("unisrv __complete --"), "{shell:?}");
+            assert!(script.contains("instance"), "{shell:?}");
+        }
+        let zsh = script(Shell::Zsh, grammar()).unwrap();
+        assert!(!zsh.contains("compdef _unisrv unisrv"), "{zsh}");
+        let powershell = script(Shell::Powershell, grammar()).unwrap();
+        assert_eq!(powershell.matches("Register-ArgumentCompleter").count(), 1);
+        assert!(powershell.contains(POWERSHELL_GENERATED), "{powershell}");
+    }
+}
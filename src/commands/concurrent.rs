@@ -0,0 +1,138 @@
+//! Run independent API requests concurrently.
+//!
+//! Commands that gather several resources (the `up`/`destroy` state fetch is
+//! the big one: three listings plus a detail GET per service and deployment)
+//! spend nearly all their wall-clock time waiting on round trips, none of which
+//! depend on each other. Fixed sets of reads go through `tokio::try_join!`;
+//! per-item fan-outs go through [`fetch_each`]. Writes that don't depend on
+//! each other, like the resources one phase of `up` creates, go through
+//! [`apply_each`], which sees every one through so none goes unrecorded.
+
+use std::future::Future;
+
+use anyhow::Result;
+use futures_util::{StreamExt, TryStreamExt, stream};
+use unisrv_api::ApiClient;
+
+/// Upper bound on requests in flight for one [`fetch_each`], so a large
+/// environment doesn't open a connection per resource against the API.
+pub const MAX_IN_FLIGHT: usize = 8;
+
+/// Resolve the access token once before fanning out.
+///
+/// The client already serialises refreshes, but without this every request in
+/// the fan-out would find the token expired and queue behind the same refresh,
+/// and a logged-out user would get whichever request's error lost the race.
+pub async fn authenticate(client: &dyn ApiClient) -> Result<()> {
+    client.access_token().await?;
+    Ok(())
+}
+
+/// Run `fetch` for every item with at most [`MAX_IN_FLIGHT`] in flight,
+/// returning results in input order. Fails on the first error.
+pub async fn fetch_each<I, T, E, F, Fut>(items: I, fetch: F) -> Result<Vec<T>, E>
+where
+    I: IntoIterator,
+    F: FnMut(I::Item) -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    stream::iter(items)
+        .map(fetch)
+        .buffered(MAX_IN_FLIGHT)
+        .try_collect()
+        .await
+}
+
+/// Run `apply` for every item with at most [`MAX_IN_FLIGHT`] in flight,
+/// returning every outcome in input order. Unlike [`fetch_each`] a failure
+/// doesn't stop the rest: a caller journaling its changes for rollback must
+/// learn of each one that went through.
+pub async fn apply_each<I, T, F, Fut>(items: I, apply: F) -> Vec<T>
+where
+    I: IntoIterator,
+    F: FnMut(I::Item) -> Fut,
+    Fut: Future<Output = T>,
+{
+    stream::iter(items)
+        .map(apply)
+        .buffered(MAX_IN_FLIGHT)
+        .collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use unisrv_api::test_support::MockApiClient;
+
+    #[tokio::test]
+    async fn fetch_each_preserves_input_order() {
+        // Later items finish first; results must still line up with the input.
+        let got: Vec<u64> = fetch_each([30u64, 20, 10, 0], |ms| async move {
+            tokio::time::sleep(Duration::from_millis(ms)).await;
+            Ok::<_, anyhow::Error>(ms)
+        })
+        .await
+        .unwrap();
+        assert_eq!(got, vec![30, 20, 10, 0]);
+    }
+
+    #[tokio::test]
+    async fn fetch_each_bounds_requests_in_flight() {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        fetch_each(0..(MAX_IN_FLIGHT * 3), |_| {
+            let (in_flight, peak) = (in_flight.clone(), peak.clone());
+            async move {
+                let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(1)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                Ok::<_, anyhow::Error>(())
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(peak.load(Ordering::SeqCst), MAX_IN_FLIGHT);
+    }
+
+    #[tokio::test]
+    async fn fetch_each_surfaces_the_first_error() {
+        let err = fetch_each([1, 2, 3], |n| async move {
+            if n == 2 {
+                anyhow::bail!("item {n} failed")
+            }
+            Ok(n)
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(err.to_string(), "item 2 failed");
+    }
+
+    #[tokio::test]
+    async fn apply_each_sees_every_item_through_a_failure() {
+        let outcomes = apply_each([20u64, 0, 10], |ms| async move {
+            tokio::time::sleep(Duration::from_millis(ms)).await;
+            if ms == 0 {
+                anyhow::bail!("item {ms} failed")
+            }
+            Ok(ms)
+        })
+        .await;
+        let outcomes: Vec<String> = outcomes
+            .into_iter()
+            .map(|o| o.map_or_else(|e| e.to_string(), |ms| ms.to_string()))
+            .collect();
+        assert_eq!(outcomes, ["20", "item 0 failed", "10"]);
+    }
+
+    #[tokio::test]
+    async fn authenticate_fails_once_when_logged_out() {
+        let mock = MockApiClient::logged_out();
+        assert!(authenticate(&mock).await.is_err());
+        assert_eq!(mock.calls.lock().unwrap().access_token_calls, 1);
+    }
+}
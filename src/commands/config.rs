@@ -0,0 +1,481 @@
+//! `unisrv config show` — the settings this CLI runs with, and where each came
+//! from.
+//!
+//! Settings are layered, each layer overriding the one before: built-in
+//! defaults, `~/.unisrv/config.json`, environment variables, then global
+//! flags. The exception is the locale: the system locale (`LANG` and friends)
+//! is what's used when nothing else says, so `"locale"` in the config file
+//! still wins over it.
+//!
+//! `main` resolves an [`Effective`] once and configures the client, locale
+//! and progress output from it, so what `--effective` prints is exactly what
+//! the command runs with.
+
+use std::fmt;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use comfy_table::{Attribute, Cell, Color};
+use serde::{Serialize, Serializer};
+use unisrv_api::trace::TRACEPARENT_ENV;
+use unisrv_api::{API_HOST_ENV, ApiVersion, DEFAULT_API_HOST};
+
+use crate::commands::instance::platform::Platform;
+use crate::commands::locale::Locale;
+use crate::commands::output::Output;
+use crate::commands::prompt::NONINTERACTIVE_ENV;
+use crate::commands::ui::{NO_COLOR_ENV, cell_with_color, colors_enabled, new_table};
+use crate::commands::up::defaults::DEFAULT_REGION;
+use crate::settings::Settings;
+use crate::state::{STATE_DIR_ENV, StateDir};
+
+/// Where a setting's value came from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Source {
+    Default,
+    File,
+    Env(&'static str),
+    Flag(&'static str),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => f.write_str("default"),
+            Source::File => f.write_str("config file"),
+            Source::Env(name) => write!(f, "env {name}"),
+            Source::Flag(name) => write!(f, "flag {name}"),
+        }
+    }
+}
+
+impl Serialize for Source {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Setting<T> {
+    pub value: T,
+    pub source: Source,
+}
+
+impl<T> Setting<T> {
+    fn new(value: T, source: Source) -> Self {
+        Setting { value, source }
+    }
+}
+
+/// The global flags that override settings.
+#[derive(Debug, Default)]
+pub struct Flags {
+    pub api_version: Option<ApiVersion>,
+    pub trace: bool,
+    pub read_only: bool,
+    pub no_spinner: bool,
+    pub no_color: bool,
+    pub yes: bool,
+    pub locale: Option<Locale>,
+}
+
+/// Every setting after merging the layers.
+#[derive(Debug)]
+pub struct Effective {
+    /// The config file, whether or not it exists.
+    pub path: Option<PathBuf>,
+    pub api_host: Setting<String>,
+    pub api_version: Setting<ApiVersion>,
+    pub trace: Setting<bool>,
+    pub read_only: Setting<bool>,
+    pub no_spinner: Setting<bool>,
+    /// Whether output is left unstyled even on a terminal.
+    pub no_color: Setting<bool>,
+    /// Whether questions are answered without asking (`--yes`).
+    pub non_interactive: Setting<bool>,
+    pub locale: Setting<Locale>,
+    /// The image platform `instance run` asks for.
+    pub platform: Setting<Platform>,
+    /// The region things are provisioned in without `--region`.
+    pub region: Setting<String>,
+    /// The Docker credential helper registry requests authenticate with.
+    pub credential_helper: Setting<Option<String>>,
+    /// The registries images may come from; any when empty.
+    pub allowed_registries: Vec<String>,
+    /// Whether anonymous usage telemetry is sent.
+    pub telemetry: Setting<bool>,
+    pub state_dir: Setting<Option<PathBuf>>,
+    /// Environments marked protected, by `env` or `project/env`.
+    pub protected: Vec<String>,
+}
+
+impl Effective {
+    /// Merge `settings` (read from `path`), the environment as read by `env`,
+    /// and `flags`.
+    pub fn resolve(
+        path: Option<PathBuf>,
+        settings: &Settings,
+        flags: Flags,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Self {
+        let env_set = |name: &str| env(name).filter(|v| !v.is_empty());
+
+        let api_host = match env(API_HOST_ENV) {
+            Some(host) => Setting::new(host, Source::Env(API_HOST_ENV)),
+            None => Setting::new(DEFAULT_API_HOST.to_string(), Source::Default),
+        };
+        let api_version = match flags.api_version {
+            Some(version) => Setting::new(version, Source::Flag("--api-version")),
+            None => Setting::new(ApiVersion::CURRENT, Source::Default),
+        };
+        let trace = if flags.trace {
+            Setting::new(true, Source::Flag("--trace"))
+        } else if env_set(TRACEPARENT_ENV).is_some() {
+            Setting::new(true, Source::Env(TRACEPARENT_ENV))
+        } else {
+            Setting::new(false, Source::Default)
+        };
+        let non_interactive = if flags.yes {
+            Setting::new(true, Source::Flag("--yes"))
+        } else if env_set(NONINTERACTIVE_ENV).is_some_and(|v| v != "0" && v != "false") {
+            Setting::new(true, Source::Env(NONINTERACTIVE_ENV))
+        } else {
+            Setting::new(false, Source::Default)
+        };
+        let no_color = if flags.no_color {
+            Setting::new(true, Source::Flag("--no-color"))
+        } else if env_set(NO_COLOR_ENV).is_some() {
+            Setting::new(true, Source::Env(NO_COLOR_ENV))
+        } else {
+            Setting::new(false, Source::Default)
+        };
+        let state_dir = match env_set(STATE_DIR_ENV) {
+            Some(dir) => Setting::new(Some(PathBuf::from(dir)), Source::Env(STATE_DIR_ENV)),
+            None => Setting::new(
+                StateDir::locate().map(|dir| dir.root().to_path_buf()),
+                Source::Default,
+            ),
+        };
+        let locale = match (flags.locale, &settings.locale) {
+            (Some(locale), _) => Setting::new(locale, Source::Flag("--locale")),
+            (None, Some(locale)) => Setting::new(locale.clone(), Source::File),
+            (None, None) => match Locale::from_vars(&env) {
+                Some((locale, name)) => Setting::new(locale, Source::Env(name)),
+                None => Setting::new(Locale::default(), Source::Default),
+            },
+        };
+        let platform = match &settings.platform {
+            Some(platform) => Setting::new(platform.clone(), Source::File),
+            None => Setting::new(Platform::default(), Source::Default),
+        };
+        let region = match &settings.region {
+            Some(region) => Setting::new(region.clone(), Source::File),
+            None => Setting::new(DEFAULT_REGION.to_string(), Source::Default),
+        };
+        let credential_helper = match &settings.credential_helper {
+            Some(helper) => Setting::new(Some(helper.clone()), Source::File),
+            None => Setting::new(None, Source::Default),
+        };
+
+        Effective {
+            path,
+            api_host,
+            api_version,
+            trace,
+            read_only: switch(flags.read_only, "--read-only", settings.read_only),
+            no_spinner: switch(flags.no_spinner, "--no-spinner", settings.no_spinner),
+            no_color,
+            non_interactive,
+            locale,
+            platform,
+            region,
+            credential_helper,
+            allowed_registries: settings.allowed_registries.clone(),
+            telemetry: if settings.telemetry {
+                Setting::new(true, Source::File)
+            } else {
+                Setting::new(false, Source::Default)
+            },
+            state_dir,
+            protected: settings
+                .environments
+                .iter()
+                .filter(|(_, env)| env.protected)
+                .map(|(name, _)| name.clone())
+                .collect(),
+        }
+    }
+
+    /// One entry per setting, keyed as in the config file where it can be
+    /// set there.
+    pub fn entries(&self) -> Vec<Entry> {
+        fn entry<T: ToString>(key: &str, setting: &Setting<T>) -> Entry {
+            Entry {
+                key: key.to_string(),
+                value: setting.value.to_string(),
+                source: setting.source,
+            }
+        }
+        let state_dir = Setting::new(
+            self.state_dir.value.as_ref().map_or_else(
+                || "(unavailable)".to_string(),
+                |dir| dir.display().to_string(),
+            ),
+            self.state_dir.source,
+        );
+        let credential_helper = Setting::new(
+            self.credential_helper
+                .value
+                .clone()
+                .unwrap_or_else(|| "(none)".to_string()),
+            self.credential_helper.source,
+        );
+
+        let mut entries = vec![
+            entry("api_host", &self.api_host),
+            entry("api_version", &self.api_version),
+            entry("trace", &self.trace),
+            entry("read_only", &self.read_only),
+            entry("no_spinner", &self.no_spinner),
+            entry("no_color", &self.no_color),
+            entry("non_interactive", &self.non_interactive),
+            entry("locale", &self.locale),
+            entry("platform", &self.platform),
+            entry("region", &self.region),
+            entry("credential_helper", &credential_helper),
+            entry("telemetry", &self.telemetry),
+            entry("state_dir", &state_dir),
+        ];
+        if !self.allowed_registries.is_empty() {
+            entries.push(Entry {
+                key: "allowed_registries".into(),
+                value: self.allowed_registries.join(", "),
+                source: Source::File,
+            });
+        }
+        entries.extend(self.protected.iter().map(|name| Entry {
+            key: format!("environments.{name}.protected"),
+            value: "true".into(),
+            source: Source::File,
+        }));
+        entries
+    }
+}
+
+/// A boolean that the config file or a flag can turn on, but not off.
+fn switch(flag: bool, name: &'static str, file: bool) -> Setting<bool> {
+    if flag {
+        Setting::new(true, Source::Flag(name))
+    } else if file {
+        Setting::new(true, Source::File)
+    } else {
+        Setting::new(false, Source::Default)
+    }
+}
+
+/// One row of `config show`, and its `--json`.
+#[derive(Debug, Serialize)]
+pub struct Entry {
+    pub key: String,
+    pub value: String,
+    pub source: Source,
+}
+
+/// `unisrv config show`: the settings from the config file or, with
+/// `effective`, every setting and its source.
+pub fn show(config: &Effective, effective: bool, output: &Output) -> Result<()> {
+    let entries: Vec<Entry> = config
+        .entries()
+        .into_iter()
+        .filter(|e| effective || e.source == Source::File)
+        .collect();
+
+    match output {
+        Output::Json | Output::Jq(_) => output.print_json(&entries)?,
+        Output::Template(template) => print!("{}", template.render_all(&entries)?),
+        Output::Table => {
+            match &config.path {
+                Some(path) if path.exists() => println!("Config file: {}", path.display()),
+                Some(path) => println!("Config file: {} (not found)", path.display()),
+                None => println!("Config file: none (no home directory)"),
+            }
+            if entries.is_empty() {
+                println!(
+                    "Nothing is set in the config file; see `unisrv config show --effective`."
+                );
+            } else {
+                println!("{}", render_table(&entries, colors_enabled()));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn render_table(entries: &[Entry], use_color: bool) -> String {
+    let mut table = new_table();
+    table.set_header(vec![
+        Cell::new("KEY").add_attribute(Attribute::Bold),
+        Cell::new("VALUE").add_attribute(Attribute::Bold),
+        Cell::new("SOURCE").add_attribute(Attribute::Bold),
+    ]);
+    for entry in entries {
+        let color = (entry.source == Source::Default).then_some(Color::DarkGrey);
+        table.add_row(vec![
+            Cell::new(&entry.key),
+            cell_with_color(entry.value.clone(), color, use_color),
+            cell_with_color(entry.source.to_string(), color, use_color),
+        ]);
+    }
+    table.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::EnvironmentSettings;
+
+    fn vars(set: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        move |name| {
+            set.iter()
+                .find(|(k, _)| *k == name)
+                .map(|(_, v)| v.to_string())
+        }
+    }
+
+    fn value<'a>(entries: &'a [Entry], key: &str) -> (&'a str, String) {
+        let entry = entries.iter().find(|e| e.key == key).unwrap();
+        (entry.value.as_str(), entry.source.to_string())
+    }
+
+    #[test]
+    fn defaults_apply_when_nothing_is_set() {
+        let config = Effective::resolve(None, &Settings::default(), Flags::default(), vars(&[]));
+        let entries = config.entries();
+        assert_eq!(
+            value(&entries, "api_host"),
+            (DEFAULT_API_HOST, "default".into())
+        );
+        assert_eq!(value(&entries, "read_only"), ("false", "default".into()));
+        assert_eq!(value(&entries, "locale"), ("C", "default".into()));
+        assert_eq!(value(&entries, "trace"), ("false", "default".into()));
+        assert_eq!(
+            value(&entries, "non_interactive"),
+            ("false", "default".into())
+        );
+        assert_eq!(value(&entries, "telemetry"), ("false", "default".into()));
+        assert_eq!(value(&entries, "no_color"), ("false", "default".into()));
+        assert_eq!(
+            value(&entries, "platform"),
+            ("linux/amd64", "default".into())
+        );
+        assert_eq!(value(&entries, "region"), ("dev", "default".into()));
+    }
+
+    #[test]
+    fn each_layer_overrides_the_one_before() {
+        let settings = Settings {
+            read_only: true,
+            no_spinner: true,
+            platform: Some("linux/arm64".parse().unwrap()),
+            region: Some("eu-central".into()),
+            credential_helper: Some("pass".into()),
+            allowed_registries: vec!["ghcr.io/acme".into(), "docker.io/library".into()],
+            telemetry: true,
+            environments: [("prod".to_string(), EnvironmentSettings { protected: true })]
+                .into_iter()
+                .collect(),
+            ..Settings::default()
+        };
+        let flags = Flags {
+            no_spinner: true,
+            api_version: Some("1.2".parse().unwrap()),
+            ..Flags::default()
+        };
+        let env = vars(&[
+            (API_HOST_ENV, "http://localhost:8080"),
+            (STATE_DIR_ENV, "/tmp/unisrv"),
+            ("LANG", "de_DE.UTF-8"),
+            (NONINTERACTIVE_ENV, "1"),
+            (NO_COLOR_ENV, "1"),
+        ]);
+        let config = Effective::resolve(None, &settings, flags, env);
+        let entries = config.entries();
+
+        assert_eq!(
+            value(&entries, "api_host"),
+            ("http://localhost:8080", "env UNISRV_API_HOST".into())
+        );
+        assert_eq!(
+            value(&entries, "api_version"),
+            ("1.2", "flag --api-version".into())
+        );
+        assert_eq!(value(&entries, "read_only"), ("true", "config file".into()));
+        assert_eq!(
+            value(&entries, "no_spinner"),
+            ("true", "flag --no-spinner".into())
+        );
+        assert_eq!(value(&entries, "locale"), ("de-DE", "env LANG".into()));
+        assert_eq!(
+            value(&entries, "non_interactive"),
+            ("true", "env UNISRV_NONINTERACTIVE".into())
+        );
+        assert_eq!(value(&entries, "no_color"), ("true", "env NO_COLOR".into()));
+        assert_eq!(
+            value(&entries, "platform"),
+            ("linux/arm64", "config file".into())
+        );
+        assert_eq!(
+            value(&entries, "region"),
+            ("eu-central", "config file".into())
+        );
+        assert_eq!(
+            value(&entries, "credential_helper"),
+            ("pass", "config file".into())
+        );
+        assert_eq!(
+            value(&entries, "state_dir"),
+            ("/tmp/unisrv", "env UNISRV_STATE_DIR".into())
+        );
+        assert_eq!(value(&entries, "telemetry"), ("true", "config file".into()));
+        assert_eq!(
+            value(&entries, "allowed_registries"),
+            ("ghcr.io/acme, docker.io/library", "config file".into())
+        );
+        assert_eq!(
+            value(&entries, "environments.prod.protected"),
+            ("true", "config file".into())
+        );
+    }
+
+    #[test]
+    fn a_locale_in_the_config_file_wins_over_the_system_locale() {
+        let settings = Settings {
+            locale: Some("en-GB".parse().unwrap()),
+            ..Settings::default()
+        };
+        let env = vars(&[("LANG", "de_DE.UTF-8")]);
+        let config = Effective::resolve(None, &settings, Flags::default(), &env);
+        assert_eq!(config.locale.value.to_string(), "en-GB");
+        assert_eq!(config.locale.source, Source::File);
+
+        let flags = Flags {
+            locale: Some("fr".parse().unwrap()),
+            ..Flags::default()
+        };
+        let config = Effective::resolve(None, &settings, flags, &env);
+        assert_eq!(config.locale.source, Source::Flag("--locale"));
+    }
+
+    #[test]
+    fn json_names_each_source() {
+        let entry = Entry {
+            key: "read_only".into(),
+            value: "true".into(),
+            source: Source::Flag("--read-only"),
+        };
+        assert_eq!(
+            serde_json::to_value(&entry).unwrap(),
+            serde_json::json!({"key": "read_only", "value": "true", "source": "flag --read-only"})
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! The one confirmation step every delete, detach and stop goes through.
+//!
+//! In an environment marked protected in `~/.unisrv/config.json` (see
+//! [`crate::settings`]), the resource's name has to be typed back before the
+//! change goes ahead, the way GitHub asks before deleting a repository — and
+//! a command's own `--yes` doesn't skip that. Elsewhere each command confirms
+//! as it always has: a yes/no question for the ones that ask one, nothing for
+//! the ones that don't.
+
+use anyhow::Result;
+
+use crate::commands::prompt;
+use crate::commands::up::plan::ResolvedEnvironment;
+use crate::settings::Settings;
+
+/// How confirmations are asked, behind a trait so tests can answer them.
+pub trait Prompter {
+    fn confirm(&self, prompt: &str) -> Result<bool>;
+    fn input(&self, prompt: &str) -> Result<String>;
+}
+
+/// Production prompter: the terminal, which errors without one.
+pub struct TerminalPrompter;
+
+impl Prompter for TerminalPrompter {
+    fn confirm(&self, question: &str) -> Result<bool> {
+        prompt::confirm(question, "pass --yes to go ahead without asking")
+    }
+
+    fn input(&self, question: &str) -> Result<String> {
+        prompt::text(
+            question,
+            None,
+            "a protected environment is only changed with its name typed back at a terminal",
+        )
+    }
+}
+
+/// Confirms destructive changes in one environment.
+pub struct Guard<'a> {
+    /// The environment's name, when it's protected.
+    protected: Option<String>,
+    prompter: &'a dyn Prompter,
+}
+
+impl Guard<'static> {
+    /// The guard for `env`, protected if the settings say so.
+    pub fn for_env(env: &ResolvedEnvironment) -> Result<Self> {
+        Self::load(&env.project, &env.name)
+    }
+
+    /// The guard for `project`'s environment `env`.
+    pub fn load(project: &str, env: &str) -> Result<Self> {
+        let settings = match Settings::default_path() {
+            Some(path) => Settings::load(&path)?,
+            None => Settings::default(),
+        };
+        let protected = settings.is_protected(project, env).then(|| env.to_string());
+        Ok(Guard::new(protected, &TerminalPrompter))
+    }
+
+    /// For changes outside any environment: never protected.
+    pub fn unprotected() -> Self {
+        Guard::new(None, &TerminalPrompter)
+    }
+}
+
+impl<'a> Guard<'a> {
+    /// `protected` is the environment's name when it's protected.
+    pub fn new(protected: Option<String>, prompter: &'a dyn Prompter) -> Self {
+        Self {
+            protected,
+            prompter,
+        }
+    }
+
+    /// Before a change that goes ahead without asking: in a protected
+    /// environment, `name` must be typed back. `action` completes "This
+    /// will …".
+    pub fn confirm(&self, action: &str, name: &str) -> Result<bool> {
+        match &self.protected {
+            Some(env) => self.type_back(env, action, name),
+            None => Ok(true),
+        }
+    }
+
+    /// Before a change that asks `question` (unless `yes`): in a protected
+    /// environment typing `name` back replaces both.
+    pub fn confirm_or_ask(
+        &self,
+        action: &str,
+        name: &str,
+        question: &str,
+        yes: bool,
+    ) -> Result<bool> {
+        match &self.protected {
+            Some(env) => self.type_back(env, action, name),
+            None if yes => Ok(true),
+            None => self.prompter.confirm(question),
+        }
+    }
+
+    fn type_back(&self, env: &str, action: &str, name: &str) -> Result<bool> {
+        println!(
+            "{} Environment {env} is protected. This will {action}.",
+            console::style("!").yellow().bold()
+        );
+        let typed = self.prompter.input(&format!("Type {name} to confirm"))?;
+        if typed.trim() == name {
+            return Ok(true);
+        }
+        println!("That doesn't match {name}.");
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    /// Answers from a script, recording what was asked.
+    #[derive(Default)]
+    pub(crate) struct ScriptedPrompter {
+        pub confirm: bool,
+        pub typed: String,
+        pub asked: RefCell<Vec<String>>,
+    }
+
+    impl ScriptedPrompter {
+        pub fn typing(typed: &str) -> Self {
+            Self {
+                typed: typed.into(),
+                ..Self::default()
+            }
+        }
+    }
+
+    impl Prompter for ScriptedPrompter {
+        fn confirm(&self, prompt: &str) -> Result<bool> {
+            self.asked.borrow_mut().push(prompt.into());
+            Ok(self.confirm)
+        }
+
+        fn input(&self, prompt: &str) -> Result<String> {
+            self.asked.borrow_mut().push(prompt.into());
+            Ok(self.typed.clone())
+        }
+    }
+
+    #[test]
+    fn unprotected_environments_confirm_as_before() {
+        let prompter = ScriptedPrompter::default();
+        let guard = Guard::new(None, &prompter);
+
+        assert!(guard.confirm("detach api-1", "api-1").unwrap());
+        assert!(
+            guard
+                .confirm_or_ask("delete x", "x", "Delete x?", true)
+                .unwrap()
+        );
+        assert!(
+            !guard
+                .confirm_or_ask("delete x", "x", "Delete x?", false)
+                .unwrap()
+        );
+        assert_eq!(prompter.asked.borrow().as_slice(), ["Delete x?"]);
+    }
+
+    #[test]
+    fn protected_environments_need_the_name_typed_back() {
+        let prompter = ScriptedPrompter::typing(" api-1 \n");
+        let guard = Guard::new(Some("prod".into()), &prompter);
+        assert!(guard.confirm("detach api-1 from backend", "api-1").unwrap());
+        // --yes doesn't skip it.
+        assert!(
+            guard
+                .confirm_or_ask("delete api-1", "api-1", "Delete?", true)
+                .unwrap()
+        );
+        assert_eq!(
+            prompter.asked.borrow().as_slice(),
+            ["Type api-1 to confirm", "Type api-1 to confirm"]
+        );
+
+        let prompter = ScriptedPrompter::typing("api");
+        let guard = Guard::new(Some("prod".into()), &prompter);
+        assert!(!guard.confirm("detach api-1 from backend", "api-1").unwrap());
+    }
+}
@@ -4,9 +4,9 @@
 //! from an empty desired state, so the diff is all-deletes), then blocks until the
 //! backend has actually drained the deployments before removing the environment.
 //!
-//! Why block here when `up` is fire-and-forget: the backend's `delete_environment`
-//! uses `ON DELETE RESTRICT` and rejects (409) while any service, deployment, or
-//! active instance still exists. Deleting a deployment only marks it `Deleting` and
+//! Why block here when `up` only waits when asked to: the backend's
+//! `delete_environment` uses `ON DELETE RESTRICT` and rejects (409) while any
+//! service, deployment, or active instance still exists. Deleting a deployment only marks it `Deleting` and
 //! triggers async teardown, so we must wait for the drain before deleting the env.
 
 use std::time::Duration;
@@ -156,10 +156,19 @@ mod tests {
             locations: vec![HTTPLocation {
                 path: "/".into(),
                 override_404: None,
+                headers: Default::default(),
+                websocket: false,
+                grpc: false,
+                cache: None,
                 target: HTTPLocationTarget::Instance {
                     group: "default".into(),
+                    rewrite: None,
                 },
             }],
+            basic_auth: vec![],
+            allow_ips: vec![],
+            tls: Default::default(),
+            compression: None,
         }
     }
 
@@ -174,6 +183,8 @@ mod tests {
             vcpu_count: 1,
             memory_mb: 256,
             instance_port: Some(80),
+            init_containers: None,
+            sidecars: None,
         }
     }
 
@@ -288,6 +299,7 @@ mod tests {
                 replicas: 1,
                 container_image: "nginx:1".into(),
                 created_at: NaiveDateTime::default(),
+                managed_by: None,
             }],
         }
     }
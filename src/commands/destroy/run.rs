@@ -7,12 +7,13 @@
 use std::collections::BTreeMap;
 
 use anyhow::{Context, Result, anyhow};
-use dialoguer::Confirm;
 use unisrv_api::ApiClient;
 
 use super::execute::{RealWaiter, destroy_execute};
 use super::resolve::resolve_for_destroy;
 use super::stops::select_instance_stops;
+use crate::commands::confirm::Guard;
+use crate::commands::ui::colors_enabled;
 use crate::commands::up::config::UpConfig;
 use crate::commands::up::desired::DesiredState;
 use crate::commands::up::fetch::fetch_current_state;
@@ -63,7 +64,7 @@ pub async fn run(client: &dyn ApiClient, env_flag: Option<&str>) -> Result<()> {
     plan.instance_stops = instance_stops;
 
     // Render what's about to be destroyed (env shell included), then confirm.
-    let styles = if console::Term::stdout().features().colors_supported() {
+    let styles = if colors_enabled() {
         PlanStyles::colored()
     } else {
         PlanStyles::plain()
@@ -78,16 +79,14 @@ pub async fn run(client: &dyn ApiClient, env_flag: Option<&str>) -> Result<()> {
     }
     println!("  - environment {env_name} will be deleted");
 
-    let confirmed = Confirm::new()
-        .with_prompt(format!(
+    let confirmed = Guard::load(&project, &env_name)?.confirm_or_ask(
+        &format!("permanently delete environment {env_name} and everything in it"),
+        &env_name,
+        &format!(
             "Destroy environment {env_name:?}? This permanently deletes everything in it and cannot be undone."
-        ))
-        .default(false)
-        // Don't re-print the prompt+answer after confirming (dialoguer's default
-        // "report"): the long destroy prompt doubled on screen is just noise.
-        .report(false)
-        .interact()
-        .context("failed to read confirmation")?;
+        ),
+        false,
+    )?;
     if !confirmed {
         println!("Aborted.");
         return Ok(());
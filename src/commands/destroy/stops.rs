@@ -42,6 +42,7 @@ mod tests {
             container_image: "busybox".into(),
             created_at: NaiveDateTime::default(),
             deployment,
+            retained_until: None,
         }
     }
 
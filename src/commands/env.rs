@@ -0,0 +1,131 @@
+//! `unisrv env` — default environment variables for the account in use,
+//! merged into every `instance run` and `rollout deploy` (see
+//! [`default_env`](crate::default_env)): org-wide settings like a log format
+//! or a telemetry endpoint, set once rather than passed to every command.
+//!
+//! Without `--project` a default applies across the account's projects; with
+//! one, only in that project, over the account-wide value.
+
+use anyhow::Result;
+use comfy_table::{Attribute, Cell};
+use console::style;
+
+use crate::commands::ui::new_table;
+use crate::commands::up::vars::parse_assignment;
+use crate::default_env::DefaultEnvStore;
+
+/// `unisrv env set-default KEY=VALUE... [--project P]`.
+pub fn set_default(
+    store: &DefaultEnvStore,
+    account: &str,
+    project: Option<&str>,
+    assignments: &[String],
+) -> Result<()> {
+    let vars = assignments
+        .iter()
+        .map(|s| parse_assignment(s))
+        .collect::<Result<Vec<_>>>()?;
+    let keys = vars
+        .iter()
+        .map(|(key, _)| key.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    store.set(account, project, vars)?;
+    println!("\u{2713} Set {keys} by default {}.", scope(project));
+    Ok(())
+}
+
+/// `unisrv env unset-default KEY... [--project P]`.
+pub fn unset_default(
+    store: &DefaultEnvStore,
+    account: &str,
+    project: Option<&str>,
+    keys: &[String],
+) -> Result<()> {
+    let missing = store.unset(account, project, keys)?;
+    for key in &missing {
+        eprintln!(
+            "{} {key} has no default {}",
+            style("!").yellow().bold(),
+            scope(project)
+        );
+    }
+    let removed: Vec<&str> = keys
+        .iter()
+        .filter(|key| !missing.contains(key))
+        .map(String::as_str)
+        .collect();
+    if !removed.is_empty() {
+        println!(
+            "\u{2713} Removed the default {} {}.",
+            removed.join(", "),
+            scope(project)
+        );
+    }
+    Ok(())
+}
+
+/// `unisrv env defaults`: every default the account has, and where it applies.
+pub fn list_defaults(store: &DefaultEnvStore, account: &str) -> Result<()> {
+    let defaults = store.account(account)?;
+    let rows: Vec<(&str, &str, &str)> = defaults
+        .vars
+        .iter()
+        .map(|(key, value)| ("(all projects)", key.as_str(), value.as_str()))
+        .chain(defaults.projects.iter().flat_map(|(project, vars)| {
+            vars.iter()
+                .map(move |(key, value)| (project.as_str(), key.as_str(), value.as_str()))
+        }))
+        .collect();
+    if rows.is_empty() {
+        println!(
+            "No default environment variables. Set one with `unisrv env set-default KEY=VALUE`."
+        );
+        return Ok(());
+    }
+    println!("{}", render_table(&rows));
+    Ok(())
+}
+
+fn render_table(rows: &[(&str, &str, &str)]) -> String {
+    let mut table = new_table();
+    table.set_header(vec![
+        Cell::new("PROJECT").add_attribute(Attribute::Bold),
+        Cell::new("KEY").add_attribute(Attribute::Bold),
+        Cell::new("VALUE").add_attribute(Attribute::Bold),
+    ]);
+    for (project, key, value) in rows {
+        table.add_row(vec![Cell::new(project), Cell::new(key), Cell::new(value)]);
+    }
+    table.to_string()
+}
+
+fn scope(project: Option<&str>) -> String {
+    match project {
+        Some(project) => format!("in project {project}"),
+        None => "across all projects".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_and_unset_defaults_by_scope() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = DefaultEnvStore::new(tmp.path().join("default_env.json"));
+        set_default(&store, "ops@acme@api", None, &["LOG_FORMAT=json".into()]).unwrap();
+        set_default(&store, "ops@acme@api", Some("shop"), &["TZ=UTC".into()]).unwrap();
+        assert!(set_default(&store, "ops@acme@api", None, &["no-equals".into()]).is_err());
+
+        let defaults = store.account("ops@acme@api").unwrap();
+        assert_eq!(defaults.for_project("shop").len(), 2);
+        assert_eq!(defaults.for_project("blog").len(), 1);
+
+        unset_default(&store, "ops@acme@api", Some("shop"), &["TZ".into()]).unwrap();
+        let defaults = store.account("ops@acme@api").unwrap();
+        assert!(defaults.projects.is_empty());
+        assert_eq!(defaults.vars["LOG_FORMAT"], "json");
+    }
+}
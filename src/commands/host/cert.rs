@@ -0,0 +1,403 @@
+//! `unisrv host cert <host> [--wildcard]` — issue (or renew) a certificate
+//! for a host.
+//!
+//! Without `--wildcard` the host must already be claimed, and the certificate
+//! authority validates it over HTTP, so its DNS is checked first the way
+//! `host claim` does. A wildcard, `*.example.com`, can only be validated over
+//! DNS (ACME's DNS-01): the wildcard host is claimed if it isn't yet, the
+//! `_acme-challenge` TXT record to publish is printed, and validation is
+//! polled until the authority has seen the record, then the certificate is
+//! issued. A wildcard claimed just for this is released again if the server
+//! won't start the challenge.
+
+use std::time::Duration;
+
+use anyhow::{Result, anyhow, bail};
+use unisrv_api::ApiClient;
+use unisrv_api::models::{ChallengeStatus, ClaimHostRequest, DnsChallenge, HostResponse};
+
+use super::dns::Resolver;
+use super::{cert_in_lockout, check, is_unisrv_managed_domain, normalize_host, report_issued};
+use crate::commands::ui::colors_enabled;
+use crate::commands::up::apply::Waiter;
+use crate::progress::{Icon, Progress, Tone};
+
+/// How often to ask whether the TXT record has been seen.
+const CHALLENGE_POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// Polls before giving up: 15 minutes, long enough for most DNS providers to
+/// propagate a new record.
+const CHALLENGE_MAX_POLLS: usize = 90;
+
+pub async fn cert(
+    client: &dyn ApiClient,
+    hostname: &str,
+    wildcard: bool,
+    resolver: &dyn Resolver,
+    waiter: &dyn Waiter,
+    progress: &dyn Progress,
+) -> Result<()> {
+    let hostname = normalize_host(hostname);
+    if wildcard {
+        wildcard_cert(client, &hostname, waiter, progress).await
+    } else {
+        exact_cert(client, &hostname, resolver).await
+    }
+}
+
+/// HTTP validation of a claimed host, once its DNS points at the edge.
+async fn exact_cert(client: &dyn ApiClient, hostname: &str, resolver: &dyn Resolver) -> Result<()> {
+    if is_unisrv_managed_domain(hostname) {
+        bail!(
+            "{hostname} is served by the platform wildcard certificate; it needs no certificate of its own"
+        );
+    }
+    let host = find_claimed(client, hostname).await?.ok_or_else(|| {
+        anyhow!("{hostname} is not claimed; claim it with `unisrv host claim {hostname}`")
+    })?;
+
+    let edge = client.get_hosts_dns_config().await?;
+    let report = check::verify(resolver, &host.host, &edge).await?;
+    if !report.passed {
+        print!("{}", check::render(&report, colors_enabled()));
+        bail!(
+            "DNS for {} doesn't point at unisrv yet, so the certificate can't be validated",
+            host.host
+        );
+    }
+
+    let host = client.request_host_cert(host.id).await?;
+    report_issued(&host)
+}
+
+/// DNS-01 validation of `*.<hostname>`.
+async fn wildcard_cert(
+    client: &dyn ApiClient,
+    hostname: &str,
+    waiter: &dyn Waiter,
+    progress: &dyn Progress,
+) -> Result<()> {
+    let base = hostname.trim_start_matches("*.");
+    if base == "unisrv.dev" || is_unisrv_managed_domain(base) {
+        bail!("*.{base} is already covered by the platform wildcard certificate");
+    }
+    let wildcard = format!("*.{base}");
+
+    let (host, claimed_now) = match find_claimed(client, &wildcard).await? {
+        Some(host) => (host, false),
+        None => {
+            let host = client
+                .claim_host(ClaimHostRequest {
+                    host: wildcard.clone(),
+                })
+                .await?;
+            println!("\u{2713} Claimed {}.", host.host);
+            (host, true)
+        }
+    };
+    if cert_in_lockout(&host, chrono::Utc::now()) {
+        println!(
+            "\u{2713} {} is already provisioned. Certificate valid until {}.",
+            host.host,
+            host.certificate_valid_until
+                .expect("lockout requires a valid_until")
+        );
+        return Ok(());
+    }
+
+    let challenge = match client.start_dns_challenge(host.id).await {
+        Ok(challenge) => challenge,
+        Err(e) => {
+            if claimed_now {
+                release(client, &host).await;
+            }
+            if e.is_unsupported_route() {
+                bail!("this server doesn't support wildcard certificates");
+            }
+            return Err(e.into());
+        }
+    };
+    print_challenge(&challenge);
+
+    let step = progress.step(
+        Icon::Host,
+        &format!("Waiting for {} to be validated", challenge.record_name),
+    );
+    let mut challenge = challenge;
+    for poll in 0.. {
+        match challenge.status {
+            ChallengeStatus::Valid => break,
+            ChallengeStatus::Invalid => bail!(
+                "validation of {} failed{}; check the TXT record and re-run `unisrv host cert {base} --wildcard`",
+                host.host,
+                challenge
+                    .error
+                    .as_deref()
+                    .map(|e| format!(": {e}"))
+                    .unwrap_or_default()
+            ),
+            ChallengeStatus::Pending | ChallengeStatus::Unknown => {}
+        }
+        if poll == CHALLENGE_MAX_POLLS {
+            bail!(
+                "{} still hasn't been seen after {} minutes; re-run `unisrv host cert {base} --wildcard` once the record has propagated",
+                challenge.record_name,
+                CHALLENGE_POLL_INTERVAL.as_secs() * CHALLENGE_MAX_POLLS as u64 / 60
+            );
+        }
+        waiter.sleep(CHALLENGE_POLL_INTERVAL).await;
+        challenge = client.get_dns_challenge(host.id).await?;
+    }
+    step.finish(Tone::Add, &format!("Validated {}", host.host));
+
+    let host = client.complete_dns_challenge(host.id).await?;
+    report_issued(&host)?;
+    println!(
+        "The {} record can be removed now; renewal will ask for a new value.",
+        challenge.record_name
+    );
+    Ok(())
+}
+
+/// Give up a host claimed for a certificate that can't be had. Best-effort: a
+/// host left behind only holds its name.
+async fn release(client: &dyn ApiClient, host: &HostResponse) {
+    match client.delete_host(host.id).await {
+        Ok(()) => println!("Released {} again.", host.host),
+        Err(e) => tracing::warn!("failed to release {}: {e}", host.host),
+    }
+}
+
+/// The user's claimed host named `hostname`, if any.
+async fn find_claimed(client: &dyn ApiClient, hostname: &str) -> Result<Option<HostResponse>> {
+    Ok(client
+        .list_hosts()
+        .await?
+        .into_iter()
+        .find(|h| normalize_host(&h.host) == hostname))
+}
+
+fn print_challenge(challenge: &DnsChallenge) {
+    println!();
+    println!("Add this DNS record so the certificate authority can validate the wildcard:");
+    println!();
+    println!(
+        "  TXT   {}    \"{}\"",
+        challenge.record_name, challenge.record_value
+    );
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::host::check::tests::FakeResolver;
+    use crate::progress::SilentProgress;
+    use async_trait::async_trait;
+    use chrono::Utc;
+    use std::net::Ipv4Addr;
+    use std::sync::Mutex;
+    use unisrv_api::ApiError;
+    use unisrv_api::models::{CertificateType, DnsConfigResponse};
+    use unisrv_api::test_support::MockApiClient;
+    use uuid::Uuid;
+
+    #[derive(Default)]
+    struct CountingWaiter {
+        sleeps: Mutex<usize>,
+    }
+
+    #[async_trait]
+    impl Waiter for CountingWaiter {
+        async fn sleep(&self, _dur: Duration) {
+            *self.sleeps.lock().unwrap() += 1;
+        }
+    }
+
+    fn host(name: &str, issued: bool) -> HostResponse {
+        let now = Utc::now();
+        HostResponse {
+            id: Uuid::new_v4(),
+            host: name.into(),
+            user_id: Uuid::nil(),
+            service_id: None,
+            certificate_type: issued.then_some(CertificateType::LetsEncrypt),
+            certificate_valid_until: issued.then(|| now + chrono::Duration::days(90)),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn challenge(status: ChallengeStatus) -> DnsChallenge {
+        DnsChallenge {
+            record_name: "_acme-challenge.example.com".into(),
+            record_value: "gfj9Xq...Rg85nM".into(),
+            status,
+            error: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn wildcard_claims_publishes_polls_and_completes() {
+        let claimed = host("*.example.com", false);
+        let mock = MockApiClient::logged_in()
+            .with_list_hosts(Ok(vec![]))
+            .with_claim_host(Ok(claimed.clone()))
+            .push_start_dns_challenge(Ok(challenge(ChallengeStatus::Pending)))
+            .push_get_dns_challenge(Ok(challenge(ChallengeStatus::Pending)))
+            .push_get_dns_challenge(Ok(challenge(ChallengeStatus::Valid)))
+            .push_complete_dns_challenge(Ok(host("*.example.com", true)));
+        let waiter = CountingWaiter::default();
+
+        cert(
+            &mock,
+            "Example.com",
+            true,
+            &FakeResolver::default(),
+            &waiter,
+            &SilentProgress,
+        )
+        .await
+        .unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        assert_eq!(calls.claim_host_calls[0].host, "*.example.com");
+        assert_eq!(calls.get_dns_challenge_calls.len(), 2);
+        assert_eq!(calls.complete_dns_challenge_calls, vec![claimed.id]);
+        assert_eq!(*waiter.sleeps.lock().unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn an_invalid_challenge_stops_before_issuing() {
+        let claimed = host("*.example.com", false);
+        let mut failed = challenge(ChallengeStatus::Invalid);
+        failed.error = Some("incorrect TXT record found".into());
+        let mock = MockApiClient::logged_in()
+            .with_list_hosts(Ok(vec![claimed]))
+            .push_start_dns_challenge(Ok(challenge(ChallengeStatus::Pending)))
+            .push_get_dns_challenge(Ok(failed));
+
+        let err = cert(
+            &mock,
+            "example.com",
+            true,
+            &FakeResolver::default(),
+            &CountingWaiter::default(),
+            &SilentProgress,
+        )
+        .await
+        .unwrap_err();
+
+        assert!(err.to_string().contains("incorrect TXT record"), "{err:#}");
+        let calls = mock.calls.lock().unwrap();
+        assert!(calls.claim_host_calls.is_empty());
+        assert!(calls.complete_dns_challenge_calls.is_empty());
+    }
+
+    #[tokio::test]
+    async fn an_unsupported_challenge_releases_the_wildcard_it_claimed() {
+        let claimed = host("*.example.com", false);
+        let mock = MockApiClient::logged_in()
+            .with_list_hosts(Ok(vec![]))
+            .with_claim_host(Ok(claimed.clone()))
+            .push_start_dns_challenge(Err(ApiError::Server {
+                status: 404,
+                reason: "Not Found".into(),
+            }))
+            .push_delete_host(Ok(()));
+
+        let err = cert(
+            &mock,
+            "example.com",
+            true,
+            &FakeResolver::default(),
+            &CountingWaiter::default(),
+            &SilentProgress,
+        )
+        .await
+        .unwrap_err();
+
+        assert!(err.to_string().contains("doesn't support"), "{err:#}");
+        assert_eq!(
+            mock.calls.lock().unwrap().delete_host_calls,
+            vec![claimed.id]
+        );
+    }
+
+    #[tokio::test]
+    async fn a_rejected_challenge_shows_the_reason_and_keeps_an_earlier_claim() {
+        let mock = MockApiClient::logged_in()
+            .with_list_hosts(Ok(vec![host("*.example.com", false)]))
+            .push_start_dns_challenge(Err(ApiError::Server {
+                status: 400,
+                reason: "DNS provider not configured for example.com".into(),
+            }));
+
+        let err = cert(
+            &mock,
+            "example.com",
+            true,
+            &FakeResolver::default(),
+            &CountingWaiter::default(),
+            &SilentProgress,
+        )
+        .await
+        .unwrap_err();
+
+        assert!(
+            format!("{err:#}").contains("DNS provider not configured"),
+            "{err:#}"
+        );
+        assert!(mock.calls.lock().unwrap().delete_host_calls.is_empty());
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_the_poll_limit() {
+        let mut mock = MockApiClient::logged_in()
+            .with_list_hosts(Ok(vec![host("*.example.com", false)]))
+            .push_start_dns_challenge(Ok(challenge(ChallengeStatus::Pending)));
+        for _ in 0..CHALLENGE_MAX_POLLS {
+            mock = mock.push_get_dns_challenge(Ok(challenge(ChallengeStatus::Pending)));
+        }
+
+        let err = cert(
+            &mock,
+            "example.com",
+            true,
+            &FakeResolver::default(),
+            &CountingWaiter::default(),
+            &SilentProgress,
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("15 minutes"), "{err:#}");
+    }
+
+    #[tokio::test]
+    async fn exact_names_are_checked_before_the_http_request() {
+        let claimed = host("example.com", false);
+        let edge = DnsConfigResponse {
+            ipv4_addresses: vec![Ipv4Addr::new(198, 51, 100, 10)],
+            ipv6_addresses: vec![],
+        };
+        let mock = MockApiClient::logged_in()
+            .with_list_hosts(Ok(vec![claimed.clone()]))
+            .with_dns_config(Ok(edge))
+            .with_request_host_cert(Ok(host("example.com", true)));
+
+        cert(
+            &mock,
+            "example.com",
+            false,
+            &FakeResolver::pointing_at_edge("example.com"),
+            &CountingWaiter::default(),
+            &SilentProgress,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            mock.calls.lock().unwrap().request_host_cert_calls,
+            vec![claimed.id]
+        );
+    }
+}
@@ -0,0 +1,402 @@
+//! `unisrv host check <host>` — look the host up in DNS and compare the
+//! answers with the edge addresses the API says certificates are validated
+//! against, so a misconfigured record shows up before a certificate request
+//! fails on it.
+//!
+//! `host claim` runs the same check in place of asking whether DNS is set up.
+
+use std::fmt::Write;
+use std::net::IpAddr;
+
+use anyhow::{Result, bail};
+use comfy_table::{Attribute, Cell, Color};
+use serde::Serialize;
+use unisrv_api::ApiClient;
+use unisrv_api::models::DnsConfigResponse;
+
+use super::dns::{Answer, RecordType, Resolver};
+use super::normalize_host;
+use crate::commands::output::Output;
+use crate::commands::ui::{cell_with_color, colors_enabled, new_table};
+
+/// The `--json` document.
+#[derive(Debug, Serialize)]
+pub(super) struct DnsReport {
+    pub host: String,
+    pub passed: bool,
+    pub checks: Vec<DnsCheck>,
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct DnsCheck {
+    pub record: &'static str,
+    pub status: CheckStatus,
+    /// What DNS answered, or `—` for nothing.
+    pub found: String,
+    pub expected: String,
+    /// What to change, for a check that didn't pass.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum CheckStatus {
+    Pass,
+    Fail,
+}
+
+pub async fn check(
+    client: &dyn ApiClient,
+    resolver: &dyn Resolver,
+    host: &str,
+    output: &Output,
+) -> Result<()> {
+    let edge = client.get_hosts_dns_config().await?;
+    let report = verify(resolver, &normalize_host(host), &edge).await?;
+
+    match output {
+        Output::Json | Output::Jq(_) => output.print_json(&report)?,
+        Output::Template(template) => print!("{}", template.render_all(&report.checks)?),
+        Output::Table => print!("{}", render(&report, colors_enabled())),
+    }
+    if !report.passed {
+        bail!("DNS for {} doesn't point at unisrv yet", report.host);
+    }
+    Ok(())
+}
+
+/// Look `host` up and check each record type against the edge addresses.
+pub(super) async fn verify(
+    resolver: &dyn Resolver,
+    host: &str,
+    edge: &DnsConfigResponse,
+) -> Result<DnsReport> {
+    let v4: Vec<IpAddr> = addresses(resolver.lookup(host, RecordType::A).await?);
+    let v6: Vec<IpAddr> = addresses(resolver.lookup(host, RecordType::Aaaa).await?);
+    let chain: Vec<String> = resolver
+        .lookup(host, RecordType::Cname)
+        .await?
+        .into_iter()
+        .filter_map(|a| match a {
+            Answer::Cname(target) => Some(target),
+            _ => None,
+        })
+        .collect();
+
+    let edge_v4: Vec<IpAddr> = edge.ipv4_addresses.iter().map(|&ip| ip.into()).collect();
+    let edge_v6: Vec<IpAddr> = edge.ipv6_addresses.iter().map(|&ip| ip.into()).collect();
+    // Records behind a CNAME are changed where the CNAME points, not here.
+    let owner = chain.last().map(String::as_str).unwrap_or(host);
+
+    let checks = vec![
+        address_check("A", host, owner, &v4, &edge_v4, true),
+        address_check("AAAA", host, owner, &v6, &edge_v6, false),
+        cname_check(host, &chain, &v4),
+    ];
+    Ok(DnsReport {
+        host: host.to_string(),
+        passed: checks.iter().all(|c| c.status == CheckStatus::Pass),
+        checks,
+    })
+}
+
+fn addresses(answers: Vec<Answer>) -> Vec<IpAddr> {
+    answers
+        .into_iter()
+        .filter_map(|a| match a {
+            Answer::A(ip) => Some(ip.into()),
+            Answer::Aaaa(ip) => Some(ip.into()),
+            Answer::Cname(_) => None,
+        })
+        .collect()
+}
+
+/// An A or AAAA check. Every address found must be an edge address: a
+/// certificate authority may validate against any of them. Only A records
+/// are required; without AAAA records the host is simply IPv4-only.
+fn address_check(
+    record: &'static str,
+    host: &str,
+    owner: &str,
+    found: &[IpAddr],
+    edge: &[IpAddr],
+    required: bool,
+) -> DnsCheck {
+    let expected = if edge.is_empty() {
+        "\u{2014}".to_string()
+    } else {
+        join(edge)
+    };
+    let stray: Vec<IpAddr> = found
+        .iter()
+        .filter(|ip| !edge.contains(ip))
+        .copied()
+        .collect();
+    let (status, hint) = if !stray.is_empty() {
+        let hint = if edge.is_empty() {
+            format!("remove the {record} records for {owner}; unisrv doesn't serve {record} yet")
+        } else {
+            format!(
+                "point the {record} records for {owner} at {} instead of {}",
+                join(edge),
+                join(&stray)
+            )
+        };
+        (CheckStatus::Fail, Some(hint))
+    } else if found.is_empty() && required && !edge.is_empty() {
+        let hint = if owner == host {
+            format!("add {record} {host} {}", join(edge))
+        } else {
+            format!(
+                "{owner} has no {record} records; add {record} {owner} {}",
+                join(edge)
+            )
+        };
+        (CheckStatus::Fail, Some(hint))
+    } else {
+        (CheckStatus::Pass, None)
+    };
+    DnsCheck {
+        record,
+        status,
+        found: if found.is_empty() {
+            "\u{2014}".to_string()
+        } else {
+            join(found)
+        },
+        expected: if required {
+            expected
+        } else {
+            format!("{expected} or none")
+        },
+        hint,
+    }
+}
+
+/// A CNAME is fine as long as where it ends up resolves to the edge, which
+/// the A check covers; one whose target doesn't resolve at all fails here,
+/// since that's the record to fix.
+fn cname_check(host: &str, chain: &[String], v4: &[IpAddr]) -> DnsCheck {
+    let (found, hint) = match chain.last() {
+        None => ("\u{2014}".to_string(), None),
+        Some(target) if v4.is_empty() => (
+            chain.join(" \u{2192} "),
+            Some(format!(
+                "{host} is a CNAME for {target}, which doesn't resolve; fix {target} or replace the CNAME with A records"
+            )),
+        ),
+        Some(_) => (chain.join(" \u{2192} "), None),
+    };
+    DnsCheck {
+        record: "CNAME",
+        status: if hint.is_some() {
+            CheckStatus::Fail
+        } else {
+            CheckStatus::Pass
+        },
+        found,
+        expected: "none, or a name resolving to the edge".to_string(),
+        hint,
+    }
+}
+
+fn join(ips: &[IpAddr]) -> String {
+    ips.iter()
+        .map(IpAddr::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The checks as a table, then a hint per failed check. Pure so it can be
+/// asserted on without a terminal.
+pub(super) fn render(report: &DnsReport, use_color: bool) -> String {
+    let mut table = new_table();
+    table.set_header(vec![
+        Cell::new("RECORD").add_attribute(Attribute::Bold),
+        Cell::new("STATUS").add_attribute(Attribute::Bold),
+        Cell::new("FOUND").add_attribute(Attribute::Bold),
+        Cell::new("EXPECTED").add_attribute(Attribute::Bold),
+    ]);
+    for check in &report.checks {
+        let (status, color) = match check.status {
+            CheckStatus::Pass => ("pass", Color::Green),
+            CheckStatus::Fail => ("fail", Color::Red),
+        };
+        table.add_row(vec![
+            Cell::new(check.record),
+            cell_with_color(status.to_string(), Some(color), use_color),
+            Cell::new(&check.found),
+            Cell::new(&check.expected),
+        ]);
+    }
+
+    let mut out = format!("DNS for {}\n{table}\n", report.host);
+    for hint in report.checks.iter().filter_map(|c| c.hint.as_ref()) {
+        let _ = writeln!(out, "  \u{2192} {hint}");
+    }
+    if report.passed {
+        let _ = writeln!(out, "\u{2713} {} points at unisrv.", report.host);
+    }
+    out
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::collections::HashMap;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+    use unisrv_api::test_support::MockApiClient;
+
+    /// Answers from a fixed table; names it doesn't know have no records.
+    #[derive(Default)]
+    pub struct FakeResolver {
+        pub answers: HashMap<(String, &'static str), Vec<Answer>>,
+    }
+
+    impl FakeResolver {
+        pub fn with(mut self, name: &str, kind: RecordType, answers: Vec<Answer>) -> Self {
+            self.answers.insert((name.into(), kind_name(kind)), answers);
+            self
+        }
+
+        /// `host` pointing straight at the edge used by these tests.
+        pub fn pointing_at_edge(host: &str) -> Self {
+            Self::default().with(
+                host,
+                RecordType::A,
+                vec![Answer::A(Ipv4Addr::new(198, 51, 100, 10))],
+            )
+        }
+    }
+
+    fn kind_name(kind: RecordType) -> &'static str {
+        match kind {
+            RecordType::A => "A",
+            RecordType::Aaaa => "AAAA",
+            RecordType::Cname => "CNAME",
+        }
+    }
+
+    #[async_trait]
+    impl Resolver for FakeResolver {
+        async fn lookup(&self, name: &str, kind: RecordType) -> Result<Vec<Answer>> {
+            Ok(self
+                .answers
+                .get(&(name.to_string(), kind_name(kind)))
+                .cloned()
+                .unwrap_or_default())
+        }
+    }
+
+    fn edge() -> DnsConfigResponse {
+        DnsConfigResponse {
+            ipv4_addresses: vec![Ipv4Addr::new(198, 51, 100, 10)],
+            ipv6_addresses: vec![Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x10)],
+        }
+    }
+
+    fn statuses(report: &DnsReport) -> Vec<(&str, CheckStatus)> {
+        report.checks.iter().map(|c| (c.record, c.status)).collect()
+    }
+
+    #[tokio::test]
+    async fn passes_when_a_records_point_at_the_edge() {
+        let resolver = FakeResolver::pointing_at_edge("example.com");
+        let report = verify(&resolver, "example.com", &edge()).await.unwrap();
+        assert!(report.passed);
+        assert_eq!(
+            statuses(&report),
+            vec![
+                ("A", CheckStatus::Pass),
+                ("AAAA", CheckStatus::Pass),
+                ("CNAME", CheckStatus::Pass)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_and_stray_records_fail_with_hints() {
+        let resolver = FakeResolver::default().with(
+            "example.com",
+            RecordType::Aaaa,
+            vec![Answer::Aaaa("2001:db8::99".parse().unwrap())],
+        );
+        let report = verify(&resolver, "example.com", &edge()).await.unwrap();
+
+        assert!(!report.passed);
+        assert_eq!(report.checks[0].status, CheckStatus::Fail);
+        assert_eq!(
+            report.checks[0].hint.as_deref(),
+            Some("add A example.com 198.51.100.10")
+        );
+        assert_eq!(report.checks[1].status, CheckStatus::Fail);
+        assert!(
+            report.checks[1]
+                .hint
+                .as_deref()
+                .unwrap()
+                .contains("instead of 2001:db8::99"),
+            "{:?}",
+            report.checks[1].hint
+        );
+    }
+
+    #[tokio::test]
+    async fn a_cname_is_followed_to_its_target() {
+        let resolver = FakeResolver::default()
+            .with(
+                "www.example.com",
+                RecordType::Cname,
+                vec![Answer::Cname("lb.example.net".into())],
+            )
+            .with(
+                "www.example.com",
+                RecordType::A,
+                vec![
+                    Answer::Cname("lb.example.net".into()),
+                    Answer::A(Ipv4Addr::new(203, 0, 113, 5)),
+                ],
+            );
+        let report = verify(&resolver, "www.example.com", &edge()).await.unwrap();
+
+        assert_eq!(report.checks[2].found, "lb.example.net");
+        assert_eq!(report.checks[2].status, CheckStatus::Pass);
+        // The stray address is fixed at the CNAME's target.
+        assert!(
+            report.checks[0]
+                .hint
+                .as_deref()
+                .unwrap()
+                .starts_with("point the A records for lb.example.net"),
+            "{:?}",
+            report.checks[0].hint
+        );
+    }
+
+    #[tokio::test]
+    async fn check_renders_a_table_and_fails_the_command() {
+        let mock = MockApiClient::logged_in().with_dns_config(Ok(edge()));
+        let err = check(
+            &mock,
+            &FakeResolver::default(),
+            "Example.com.",
+            &Output::Table,
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("example.com"), "{err:#}");
+
+        let report = verify(&FakeResolver::default(), "example.com", &edge())
+            .await
+            .unwrap();
+        let out = render(&report, false);
+        assert!(out.starts_with("DNS for example.com\n"), "{out}");
+        assert!(out.contains("fail"), "{out}");
+        assert!(
+            out.contains("\u{2192} add A example.com 198.51.100.10"),
+            "{out}"
+        );
+    }
+}
@@ -0,0 +1,278 @@
+//! Just enough of a DNS stub resolver to ask the system's nameserver for a
+//! host's A, AAAA and CNAME records, so `host check` sees what a certificate
+//! authority will see rather than what a local hosts file says.
+//!
+//! Queries go over UDP to the first nameservers in `/etc/resolv.conf`
+//! (falling back to a public resolver) with recursion desired; a name that
+//! doesn't exist answers with no records rather than an error.
+
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use tokio::net::UdpSocket;
+
+/// How long to wait for each nameserver before trying the next.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Nameservers from resolv.conf tried before giving up.
+const MAX_NAMESERVERS: usize = 2;
+
+/// Used when resolv.conf lists no nameservers (or doesn't exist).
+const FALLBACK_NAMESERVER: &str = "1.1.1.1:53";
+
+/// Names nest through at most this many compression pointers.
+const MAX_POINTERS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordType {
+    A,
+    Aaaa,
+    Cname,
+}
+
+impl RecordType {
+    fn code(self) -> u16 {
+        match self {
+            RecordType::A => 1,
+            RecordType::Aaaa => 28,
+            RecordType::Cname => 5,
+        }
+    }
+}
+
+/// One answer record. Anything else a nameserver sends along is dropped.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Answer {
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    Cname(String),
+}
+
+/// DNS lookups, behind a trait so tests can answer without a network.
+#[async_trait]
+pub trait Resolver: Sync {
+    /// The answers for `name`; empty if it has none of that type or doesn't
+    /// exist.
+    async fn lookup(&self, name: &str, kind: RecordType) -> Result<Vec<Answer>>;
+}
+
+/// The system's nameservers.
+pub struct SystemResolver;
+
+#[async_trait]
+impl Resolver for SystemResolver {
+    async fn lookup(&self, name: &str, kind: RecordType) -> Result<Vec<Answer>> {
+        let id = query_id();
+        let query = encode_query(id, name, kind)?;
+        let mut last_err = None;
+        for server in nameservers() {
+            match ask(server, &query).await {
+                Ok(response) => return parse_response(id, &response),
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(last_err
+            .expect("there is always a nameserver to ask")
+            .context(format!("DNS lookup for {name} failed")))
+    }
+}
+
+async fn ask(server: SocketAddr, query: &[u8]) -> Result<Vec<u8>> {
+    let bind: SocketAddr = if server.is_ipv4() {
+        "0.0.0.0:0".parse().expect("valid bind address")
+    } else {
+        "[::]:0".parse().expect("valid bind address")
+    };
+    let socket = UdpSocket::bind(bind).await?;
+    socket.connect(server).await?;
+    socket.send(query).await?;
+    let mut buf = vec![0u8; 4096];
+    let len = tokio::time::timeout(QUERY_TIMEOUT, socket.recv(&mut buf))
+        .await
+        .with_context(|| format!("no answer from {server}"))??;
+    buf.truncate(len);
+    Ok(buf)
+}
+
+fn nameservers() -> Vec<SocketAddr> {
+    let mut servers: Vec<SocketAddr> = std::fs::read_to_string("/etc/resolv.conf")
+        .map(|conf| parse_resolv_conf(&conf))
+        .unwrap_or_default();
+    servers.truncate(MAX_NAMESERVERS);
+    if servers.is_empty() {
+        servers.push(FALLBACK_NAMESERVER.parse().expect("valid fallback"));
+    }
+    servers
+}
+
+fn parse_resolv_conf(conf: &str) -> Vec<SocketAddr> {
+    conf.lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .filter_map(|rest| {
+            // A scoped IPv6 address (`fe80::1%eth0`) can't be parsed; skip it.
+            let ip: std::net::IpAddr = rest.trim().parse().ok()?;
+            Some(SocketAddr::new(ip, 53))
+        })
+        .collect()
+}
+
+/// Not security-relevant (a spoofed answer only misleads a pre-check), so
+/// the clock is random enough.
+fn query_id() -> u16 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos() as u16)
+        .unwrap_or(0x5e5e)
+}
+
+fn encode_query(id: u16, name: &str, kind: RecordType) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(32 + name.len());
+    out.extend_from_slice(&id.to_be_bytes());
+    // Standard query, recursion desired; one question.
+    out.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            bail!("{name:?} is not a valid hostname");
+        }
+        out.push(label.len() as u8);
+        out.extend_from_slice(label.as_bytes());
+    }
+    out.push(0);
+    out.extend_from_slice(&kind.code().to_be_bytes());
+    out.extend_from_slice(&1u16.to_be_bytes()); // class IN
+    Ok(out)
+}
+
+fn parse_response(id: u16, buf: &[u8]) -> Result<Vec<Answer>> {
+    if buf.len() < 12 || u16::from_be_bytes([buf[0], buf[1]]) != id {
+        bail!("malformed DNS response");
+    }
+    match buf[3] & 0x0f {
+        0 => {}
+        // NXDOMAIN: the name doesn't exist, so it has no records.
+        3 => return Ok(vec![]),
+        2 => bail!("the nameserver failed to answer (SERVFAIL)"),
+        5 => bail!("the nameserver refused the query"),
+        code => bail!("the nameserver answered with error code {code}"),
+    }
+    let questions = u16::from_be_bytes([buf[4], buf[5]]);
+    let answers = u16::from_be_bytes([buf[6], buf[7]]);
+
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = read_name(buf, pos)?.1 + 4;
+    }
+    let mut out = Vec::new();
+    for _ in 0..answers {
+        pos = read_name(buf, pos)?.1;
+        let header = buf.get(pos..pos + 10).context("truncated DNS response")?;
+        let kind = u16::from_be_bytes([header[0], header[1]]);
+        let len = u16::from_be_bytes([header[8], header[9]]) as usize;
+        let start = pos + 10;
+        let data = buf
+            .get(start..start + len)
+            .context("truncated DNS response")?;
+        match kind {
+            1 if len == 4 => out.push(Answer::A(Ipv4Addr::new(data[0], data[1], data[2], data[3]))),
+            28 if len == 16 => {
+                let octets: [u8; 16] = data.try_into().expect("checked length");
+                out.push(Answer::Aaaa(Ipv6Addr::from(octets)));
+            }
+            5 => out.push(Answer::Cname(read_name(buf, start)?.0)),
+            _ => {}
+        }
+        pos = start + len;
+    }
+    Ok(out)
+}
+
+/// The (possibly compressed) name at `pos`, and the position just past it.
+fn read_name(buf: &[u8], mut pos: usize) -> Result<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut end = None;
+    for _ in 0..MAX_POINTERS {
+        loop {
+            let len = *buf.get(pos).context("truncated DNS name")? as usize;
+            if len & 0xc0 == 0xc0 {
+                let low = *buf.get(pos + 1).context("truncated DNS name")? as usize;
+                end.get_or_insert(pos + 2);
+                pos = ((len & 0x3f) << 8) | low;
+                break;
+            }
+            if len == 0 {
+                return Ok((labels.join("."), end.unwrap_or(pos + 1)));
+            }
+            let label = buf
+                .get(pos + 1..pos + 1 + len)
+                .context("truncated DNS name")?;
+            labels.push(String::from_utf8_lossy(label).to_ascii_lowercase());
+            pos += 1 + len;
+        }
+    }
+    bail!("DNS name has too many compression pointers")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A response to an A query for www.example.com: a CNAME to
+    /// edge.example.net, then its address, both using name compression.
+    fn cname_response(id: u16) -> Vec<u8> {
+        let mut buf = encode_query(id, "www.example.com", RecordType::A).unwrap();
+        buf[2] = 0x81;
+        buf[3] = 0x80;
+        buf[7] = 2;
+        // www.example.com (pointer to the question) CNAME edge.example.net
+        buf.extend_from_slice(&[0xc0, 12, 0, 5, 0, 1, 0, 0, 0, 60, 0, 18]);
+        buf.extend_from_slice(b"\x04edge\x07example\x03net\x00");
+        // edge.example.net (pointer to the CNAME's data) A 198.51.100.10
+        let target = 12 + 17 + 4 + 12;
+        buf.extend_from_slice(&[0xc0, target as u8, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4]);
+        buf.extend_from_slice(&[198, 51, 100, 10]);
+        buf
+    }
+
+    #[test]
+    fn encodes_a_recursive_query() {
+        let query = encode_query(0xabcd, "Example.com.", RecordType::Aaaa).unwrap();
+        assert_eq!(&query[..4], &[0xab, 0xcd, 0x01, 0x00]);
+        assert_eq!(&query[12..], b"\x07Example\x03com\x00\x00\x1c\x00\x01");
+        assert!(encode_query(1, "a..b", RecordType::A).is_err());
+    }
+
+    #[test]
+    fn parses_compressed_answers() {
+        let answers = parse_response(7, &cname_response(7)).unwrap();
+        assert_eq!(
+            answers,
+            vec![
+                Answer::Cname("edge.example.net".into()),
+                Answer::A(Ipv4Addr::new(198, 51, 100, 10)),
+            ]
+        );
+        assert!(parse_response(8, &cname_response(7)).is_err());
+    }
+
+    #[test]
+    fn nxdomain_means_no_records() {
+        let mut buf = encode_query(7, "nope.example.com", RecordType::A).unwrap();
+        buf[2] = 0x81;
+        buf[3] = 0x83;
+        assert_eq!(parse_response(7, &buf).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn reads_nameservers_from_resolv_conf() {
+        let conf = "# generated\nsearch lan\nnameserver 192.168.1.1\nnameserver fe80::1%eth0\nnameserver 2001:db8::53\n";
+        assert_eq!(
+            parse_resolv_conf(conf),
+            vec![
+                "192.168.1.1:53".parse().unwrap(),
+                "[2001:db8::53]:53".parse().unwrap(),
+            ]
+        );
+    }
+}
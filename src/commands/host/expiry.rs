@@ -0,0 +1,254 @@
+//! `unisrv host cert-status` — every host's certificate, soonest expiry first.
+//!
+//! Meant for cron and CI: the command fails when any certificate expires
+//! within `--expiring-within` (or already has), so a scheduled run alerts
+//! before a renewal is missed. Hosts on the platform wildcard certificate are
+//! renewed by the platform and left out.
+
+use anyhow::{Result, bail};
+use chrono::{DateTime, Duration, Utc};
+use chrono_humanize::HumanTime;
+use comfy_table::{Attribute, Cell, Color};
+use serde::Serialize;
+use unisrv_api::ApiClient;
+use unisrv_api::models::{CertificateType, HostResponse};
+
+use super::format_cert_type;
+use crate::commands::locale::{self, Locale};
+use crate::commands::output::Output;
+use crate::commands::ui::{cell_with_color, colors_enabled, new_table};
+
+/// One row of `host cert-status`, and its `--json`.
+#[derive(Debug, Serialize)]
+pub struct CertExpiry {
+    pub host: String,
+    pub certificate_type: Option<CertificateType>,
+    pub valid_until: DateTime<Utc>,
+    /// Expired, or expiring within the threshold.
+    pub expiring: bool,
+}
+
+pub async fn cert_status(
+    client: &dyn ApiClient,
+    expiring_within: &str,
+    output: &Output,
+) -> Result<()> {
+    let threshold = parse_threshold(expiring_within)?;
+    let now = Utc::now();
+    let certs = expiries(client.list_hosts().await?, threshold, now);
+
+    match output {
+        Output::Json | Output::Jq(_) => output.print_json(&certs)?,
+        Output::Template(template) => print!("{}", template.render_all(&certs)?),
+        Output::Table if certs.is_empty() => println!("No hosts have a certificate yet."),
+        Output::Table => println!(
+            "{}",
+            render_table(&certs, now, locale::current(), colors_enabled())
+        ),
+    }
+
+    let expiring = certs.iter().filter(|c| c.expiring).count();
+    if expiring > 0 {
+        bail!(
+            "{expiring} certificate{} expired or expiring within {expiring_within}",
+            if expiring == 1 { "" } else { "s" }
+        );
+    }
+    Ok(())
+}
+
+/// Hosts with a certificate of their own, soonest expiry first.
+fn expiries(hosts: Vec<HostResponse>, threshold: Duration, now: DateTime<Utc>) -> Vec<CertExpiry> {
+    let mut certs: Vec<CertExpiry> = hosts
+        .into_iter()
+        .filter(|h| h.certificate_type != Some(CertificateType::CommonWildcard))
+        .filter_map(|h| {
+            let valid_until = h.certificate_valid_until?;
+            Some(CertExpiry {
+                host: h.host,
+                certificate_type: h.certificate_type,
+                valid_until,
+                expiring: valid_until - now < threshold,
+            })
+        })
+        .collect();
+    certs.sort_by(|a, b| a.valid_until.cmp(&b.valid_until).then(a.host.cmp(&b.host)));
+    certs
+}
+
+/// Validate a threshold like `12h`, `30d` or `2w`.
+fn parse_threshold(threshold: &str) -> Result<Duration> {
+    let invalid =
+        || anyhow::anyhow!("invalid --expiring-within {threshold:?}: expected e.g. 12h, 30d or 2w");
+    let split = threshold.char_indices().last().map_or(0, |(i, _)| i);
+    let (digits, unit) = threshold.split_at(split);
+    let count: i64 = digits.parse().map_err(|_| invalid())?;
+    match unit {
+        "h" => Duration::try_hours(count),
+        "d" => Duration::try_days(count),
+        "w" => Duration::try_weeks(count),
+        _ => None,
+    }
+    .filter(|d| *d > Duration::zero())
+    .ok_or_else(invalid)
+}
+
+fn render_table(
+    certs: &[CertExpiry],
+    now: DateTime<Utc>,
+    locale: &Locale,
+    use_color: bool,
+) -> String {
+    let mut table = new_table();
+    table.set_header(vec![
+        Cell::new("HOST").add_attribute(Attribute::Bold),
+        Cell::new("CERT").add_attribute(Attribute::Bold),
+        Cell::new("VALID UNTIL").add_attribute(Attribute::Bold),
+        Cell::new("EXPIRES").add_attribute(Attribute::Bold),
+    ]);
+
+    for cert in certs {
+        let (cert_text, cert_color) = format_cert_type(cert.certificate_type);
+        let color = if cert.expiring {
+            Color::Red
+        } else {
+            Color::Green
+        };
+        table.add_row(vec![
+            cell_with_color(
+                cert.host.clone(),
+                cert.expiring.then_some(Color::Red),
+                use_color,
+            ),
+            cell_with_color(cert_text, cert_color, use_color),
+            Cell::new(locale.format_datetime(cert.valid_until)),
+            cell_with_color(
+                HumanTime::from(cert.valid_until - now).to_string(),
+                Some(color),
+                use_color,
+            ),
+        ]);
+    }
+    table.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use unisrv_api::test_support::MockApiClient;
+    use uuid::Uuid;
+
+    fn host(name: &str, kind: Option<CertificateType>, days_left: Option<i64>) -> HostResponse {
+        let now = Utc::now();
+        HostResponse {
+            id: Uuid::new_v4(),
+            host: name.into(),
+            user_id: Uuid::nil(),
+            service_id: None,
+            certificate_type: kind,
+            certificate_valid_until: days_left.map(|d| now + Duration::days(d)),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn parses_thresholds() {
+        assert_eq!(parse_threshold("30d").unwrap(), Duration::days(30));
+        assert_eq!(parse_threshold("12h").unwrap(), Duration::hours(12));
+        assert_eq!(parse_threshold("2w").unwrap(), Duration::weeks(2));
+        for bad in ["", "30", "d", "0d", "-1d", "30m", "1.5d"] {
+            assert!(parse_threshold(bad).is_err(), "{bad:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn sorts_by_expiry_and_flags_those_within_the_threshold() {
+        let hosts = vec![
+            host(
+                "later.example.com",
+                Some(CertificateType::LetsEncrypt),
+                Some(80),
+            ),
+            host("none.example.com", None, None),
+            host(
+                "demo.unisrv.dev",
+                Some(CertificateType::CommonWildcard),
+                Some(5),
+            ),
+            host("soon.example.com", Some(CertificateType::Custom), Some(10)),
+            host(
+                "gone.example.com",
+                Some(CertificateType::LetsEncrypt),
+                Some(-3),
+            ),
+        ];
+        let certs = expiries(hosts, Duration::days(30), Utc::now());
+
+        let order: Vec<(&str, bool)> = certs
+            .iter()
+            .map(|c| (c.host.as_str(), c.expiring))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                ("gone.example.com", true),
+                ("soon.example.com", true),
+                ("later.example.com", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn plain_table_lists_each_certificate() {
+        let now = Utc::now();
+        let certs = expiries(
+            vec![
+                host(
+                    "soon.example.com",
+                    Some(CertificateType::LetsEncrypt),
+                    Some(3),
+                ),
+                host(
+                    "fine.example.com",
+                    Some(CertificateType::LetsEncrypt),
+                    Some(60),
+                ),
+            ],
+            Duration::days(30),
+            now,
+        );
+        let plain = render_table(&certs, now, &Locale::default(), false);
+        assert!(plain.contains("soon.example.com"), "{plain}");
+        assert!(plain.contains("in 2 months"), "{plain}");
+        assert!(!plain.contains('\u{1b}'), "{plain}");
+    }
+
+    #[tokio::test]
+    async fn fails_when_any_certificate_is_within_the_threshold() {
+        let mock = MockApiClient::logged_in().with_list_hosts(Ok(vec![
+            host(
+                "soon.example.com",
+                Some(CertificateType::LetsEncrypt),
+                Some(3),
+            ),
+            host(
+                "fine.example.com",
+                Some(CertificateType::LetsEncrypt),
+                Some(60),
+            ),
+        ]));
+        let err = cert_status(&mock, "30d", &Output::Json).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "1 certificate expired or expiring within 30d"
+        );
+
+        let mock = MockApiClient::logged_in().with_list_hosts(Ok(vec![host(
+            "fine.example.com",
+            Some(CertificateType::LetsEncrypt),
+            Some(60),
+        )]));
+        assert!(cert_status(&mock, "30d", &Output::Json).await.is_ok());
+    }
+}
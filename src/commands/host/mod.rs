@@ -1,21 +1,35 @@
+//! `unisrv host` — claim hosts (domains) and provision their certificates,
+//! including wildcards, check their DNS, list and show them, watch their
+//! certificates' expiry, and publish DNS records under them.
+
+pub mod cert;
+pub mod check;
+pub mod dns;
+pub mod expiry;
+pub mod record;
+pub mod show;
+
 use anyhow::Result;
-use chrono::{Duration, NaiveDateTime};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
 use chrono_humanize::HumanTime;
-use comfy_table::{Attribute, Cell, Color, ContentArrangement, Table, presets::UTF8_FULL};
-use dialoguer::Confirm;
+use comfy_table::{Cell, Color};
 use unisrv_api::ApiClient;
-use unisrv_api::models::{CertificateType, ClaimHostRequest, DnsConfigResponse, HostResponse};
+use unisrv_api::models::{CertificateType, ClaimHostRequest, HostResponse};
 
-use super::ui::{cell_with_color, colors_enabled, format_relative};
+use self::dns::{Answer, RecordType, Resolver, SystemResolver};
+use crate::commands::output::Output;
+use crate::commands::table::{Column, Row, TableOptions, draw_table, id_text, sort};
+use crate::commands::ui::{cell_with_color, colors_enabled, emoji, format_relative};
 
 pub async fn claim(client: &dyn ApiClient, hostname: &str) -> Result<()> {
-    claim_with_confirm(client, hostname, prompt_dns_confirmation)
+    claim_with_check(client, hostname, &SystemResolver)
         .await
         .map(|_| ())
 }
 
 /// Claim and provision a `*.unisrv.dev` host non-interactively. DNS for these
-/// domains is preconfigured, so the claim flow never reaches the DNS prompt.
+/// domains is preconfigured, so the claim flow never reaches the DNS check.
 /// Used by `unisrv up` to auto-claim managed subdomains during preflight.
 pub(crate) async fn provision_managed_host(
     client: &dyn ApiClient,
@@ -25,30 +39,28 @@ pub(crate) async fn provision_managed_host(
         is_unisrv_managed_domain(hostname),
         "provision_managed_host is only valid for *.unisrv.dev hosts"
     );
-    claim_with_confirm(client, hostname, || {
+    claim_with_check(client, hostname, &NoLookups).await
+}
+
+/// For managed hosts, whose DNS is never checked: reaching a lookup means
+/// the API answered with a host that isn't managed after all.
+struct NoLookups;
+
+#[async_trait]
+impl Resolver for NoLookups {
+    async fn lookup(&self, _name: &str, _kind: RecordType) -> Result<Vec<Answer>> {
         Err(anyhow::anyhow!(
-            "claim for managed host unexpectedly required DNS confirmation; \
+            "claim for managed host unexpectedly required a DNS check; \
              the API returned an unrecognized hostname"
         ))
-    })
-    .await
-}
-
-fn prompt_dns_confirmation() -> Result<bool> {
-    Ok(Confirm::new()
-        .with_prompt("DNS records configured?")
-        .default(false)
-        .interact()?)
+    }
 }
 
-async fn claim_with_confirm<F>(
+async fn claim_with_check(
     client: &dyn ApiClient,
     hostname: &str,
-    confirm: F,
-) -> Result<HostResponse>
-where
-    F: FnOnce() -> Result<bool>,
-{
+    resolver: &dyn Resolver,
+) -> Result<HostResponse> {
     let host = client
         .claim_host(ClaimHostRequest {
             // Canonicalize: DNS is case-insensitive and the server stores hosts
@@ -78,7 +90,7 @@ where
         ));
     }
 
-    if cert_in_lockout(&host, chrono::Utc::now().naive_utc()) {
+    if cert_in_lockout(&host, Utc::now()) {
         let valid_until = host
             .certificate_valid_until
             .expect("lockout requires a valid_until");
@@ -93,28 +105,40 @@ where
     // here is an external host that needs DNS set up before a per-host cert.
     let cert_exists = host.certificate_valid_until.is_some();
 
+    // The certificate authority validates against DNS, so check it first
+    // rather than let the request fail on it.
     if !cert_exists {
-        let dns = client.get_hosts_dns_config().await?;
-        print_dns_records(&host.host, &dns);
-
-        if !confirm()? {
+        let edge = client.get_hosts_dns_config().await?;
+        let report = check::verify(resolver, &host.host, &edge).await?;
+        if !report.passed {
+            print!("{}", check::render(&report, colors_enabled()));
             println!(
-                "Aborted. Re-run `unisrv host claim {}` once DNS is configured.",
-                host.host
+                "Claimed {}, but no certificate yet. Re-run `unisrv host claim {}` once DNS is \
+                 configured (`unisrv host check {}` checks it).",
+                host.host, host.host, host.host
             );
             return Ok(host);
         }
+        println!("\u{2713} DNS for {} points at unisrv.", host.host);
     }
 
     let host = client.request_host_cert(host.id).await?;
+    report_issued(&host)?;
+    Ok(host)
+}
+
+/// Announce a freshly issued certificate.
+fn report_issued(host: &HostResponse) -> Result<()> {
     let valid_until = host
         .certificate_valid_until
         .ok_or_else(|| anyhow::anyhow!("Certificate request returned without expiry"))?;
     println!(
-        "\u{1f512} Certificate provisioned for {}. Valid until {}.",
-        host.host, valid_until
+        "{}Certificate provisioned for {}. Valid until {}.",
+        emoji("\u{1f512}"),
+        host.host,
+        valid_until
     );
-    Ok(host)
+    Ok(())
 }
 
 /// Canonical form for comparing hostnames: lowercased, trailing dot stripped.
@@ -124,11 +148,24 @@ pub(crate) fn normalize_host(host: &str) -> String {
     host.trim_end_matches('.').to_ascii_lowercase()
 }
 
+/// The claimed host named `hostname`, or an error saying how to claim it.
+pub(crate) async fn find_claimed(client: &dyn ApiClient, hostname: &str) -> Result<HostResponse> {
+    let wanted = normalize_host(hostname);
+    client
+        .list_hosts()
+        .await?
+        .into_iter()
+        .find(|h| normalize_host(&h.host) == wanted)
+        .ok_or_else(|| {
+            anyhow::anyhow!("{wanted} is not claimed; claim it with `unisrv host claim {wanted}`")
+        })
+}
+
 pub(crate) fn is_unisrv_managed_domain(host: &str) -> bool {
     normalize_host(host).ends_with(".unisrv.dev")
 }
 
-fn cert_in_lockout(host: &HostResponse, now: chrono::NaiveDateTime) -> bool {
+fn cert_in_lockout(host: &HostResponse, now: DateTime<Utc>) -> bool {
     // Without a certificate type there is no real cert, regardless of any
     // valid_until the API may report — so it cannot be in a renewal lockout.
     if host.certificate_type.is_none() {
@@ -143,65 +180,70 @@ fn cert_in_lockout(host: &HostResponse, now: chrono::NaiveDateTime) -> bool {
     now < earliest_renewal
 }
 
-fn print_dns_records(host: &str, dns: &DnsConfigResponse) {
-    println!();
-    println!("Configure these DNS records for {host}:");
-    println!();
-    for ip in &dns.ipv4_addresses {
-        println!("  A     {host}    {ip}");
-    }
-    for ip in &dns.ipv6_addresses {
-        println!("  AAAA  {host}    {ip}");
-    }
-    println!();
-}
+pub async fn list(client: &dyn ApiClient, output: &Output, table: &TableOptions) -> Result<()> {
+    let mut hosts = client.list_hosts().await?;
+    sort(&mut hosts, table.sort, "hosts")?;
 
-pub async fn list(client: &dyn ApiClient, json: bool) -> Result<()> {
-    let hosts = client.list_hosts().await?;
-
-    if json {
-        println!("{}", serde_json::to_string_pretty(&hosts)?);
-        return Ok(());
+    match output {
+        Output::Json | Output::Jq(_) => return output.print_json(&hosts),
+        Output::Template(template) => {
+            print!("{}", template.render_all(&hosts)?);
+            return Ok(());
+        }
+        Output::Table => {}
     }
 
+    let use_color = colors_enabled();
+    let now = Utc::now();
+    let rendered = render_table(&hosts, now, use_color, table)?;
     if hosts.is_empty() {
         println!("No hosts claimed yet. Run `unisrv host claim <hostname>` to add one.");
         return Ok(());
     }
 
-    let use_color = colors_enabled();
-    let now = chrono::Utc::now().naive_utc();
-    println!("{}", render_table(&hosts, now, use_color));
+    println!("{rendered}");
     Ok(())
 }
 
-fn render_table(hosts: &[HostResponse], now: NaiveDateTime, use_color: bool) -> String {
-    let mut table = Table::new();
-    table.load_preset(UTF8_FULL);
-    table.set_content_arrangement(ContentArrangement::Dynamic);
-    table.set_header(vec![
-        Cell::new("HOST").add_attribute(Attribute::Bold),
-        Cell::new("CERT").add_attribute(Attribute::Bold),
-        Cell::new("EXPIRES").add_attribute(Attribute::Bold),
-        Cell::new("ATTACHED").add_attribute(Attribute::Bold),
-        Cell::new("CREATED").add_attribute(Attribute::Bold),
-    ]);
-
-    for host in hosts {
-        let (cert_text, cert_color) = format_cert_type(host.certificate_type);
-        let (expires_text, expires_color) = format_expires(host.certificate_valid_until, now);
-        let (attached_text, attached_color) = format_attached(host.service_id.is_some());
-        let created = format_relative(host.created_at, now);
-
-        table.add_row(vec![
-            Cell::new(&host.host),
-            cell_with_color(cert_text, cert_color, use_color),
-            cell_with_color(expires_text, expires_color, use_color),
-            cell_with_color(attached_text, attached_color, use_color),
-            Cell::new(created),
-        ]);
-    }
-    table.to_string()
+impl Row for HostResponse {
+    fn name(&self) -> Option<&str> {
+        Some(&self.host)
+    }
+
+    fn created(&self) -> Option<DateTime<Utc>> {
+        Some(self.created_at)
+    }
+}
+
+fn render_table(
+    hosts: &[HostResponse],
+    now: DateTime<Utc>,
+    use_color: bool,
+    options: &TableOptions,
+) -> Result<String> {
+    let columns = [
+        Column::new("id", "ID", |h: &HostResponse| {
+            Cell::new(id_text(&h.id, options))
+        })
+        .shown_if(false),
+        Column::new("host", "HOST", |h: &HostResponse| Cell::new(&h.host)),
+        Column::new("cert", "CERT", |h: &HostResponse| {
+            let (text, color) = format_cert_type(h.certificate_type);
+            cell_with_color(text, color, use_color)
+        }),
+        Column::new("expires", "EXPIRES", |h: &HostResponse| {
+            let (text, color) = format_expires(h.certificate_valid_until, now);
+            cell_with_color(text, color, use_color)
+        }),
+        Column::new("attached", "ATTACHED", |h: &HostResponse| {
+            let (text, color) = format_attached(h.service_id.is_some());
+            cell_with_color(text, color, use_color)
+        }),
+        Column::new("created", "CREATED", |h: &HostResponse| {
+            Cell::new(format_relative(h.created_at, now))
+        }),
+    ];
+    draw_table(hosts, &columns, options)
 }
 
 fn format_cert_type(cert_type: Option<CertificateType>) -> (String, Option<Color>) {
@@ -215,8 +257,8 @@ fn format_cert_type(cert_type: Option<CertificateType>) -> (String, Option<Color
 }
 
 fn format_expires(
-    valid_until: Option<NaiveDateTime>,
-    now: NaiveDateTime,
+    valid_until: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
 ) -> (String, Option<Color>) {
     let Some(valid_until) = valid_until else {
         return ("\u{2014}".into(), Some(Color::DarkGrey));
@@ -242,10 +284,12 @@ fn format_attached(attached: bool) -> (String, Option<Color>) {
 
 #[cfg(test)]
 mod tests {
+    use super::check::tests::FakeResolver;
     use super::*;
     use chrono::{Duration, Utc};
     use std::net::{Ipv4Addr, Ipv6Addr};
     use unisrv_api::ApiError;
+    use unisrv_api::models::DnsConfigResponse;
     use unisrv_api::test_support::MockApiClient;
     use uuid::Uuid;
 
@@ -258,7 +302,7 @@ mod tests {
     }
 
     fn unprovisioned_host() -> HostResponse {
-        let now = Utc::now().naive_utc();
+        let now = Utc::now();
         HostResponse {
             id: host_id(),
             host: "example.com".into(),
@@ -272,7 +316,7 @@ mod tests {
     }
 
     fn provisioned_host(issued_days_ago: i64, lifetime_days: i64) -> HostResponse {
-        let now = Utc::now().naive_utc();
+        let now = Utc::now();
         let issued_at = now - Duration::days(issued_days_ago);
         let valid_until = issued_at + Duration::days(lifetime_days);
         HostResponse {
@@ -301,7 +345,12 @@ mod tests {
             .with_dns_config(Ok(dns_config()))
             .with_request_host_cert(Ok(provisioned_host(0, 90)));
 
-        let result = claim_with_confirm(&mock, "example.com", || Ok(true)).await;
+        let result = claim_with_check(
+            &mock,
+            "example.com",
+            &FakeResolver::pointing_at_edge("example.com"),
+        )
+        .await;
         assert!(result.is_ok(), "expected ok, got {result:?}");
 
         let calls = mock.calls.lock().unwrap();
@@ -317,7 +366,12 @@ mod tests {
         // stores hosts verbatim. Canonicalize so a claim matches what `up` links
         // (and so an uppercase *.unisrv.dev label doesn't 400 at claim).
         let mock = MockApiClient::logged_in().with_claim_host(Ok(provisioned_host(1, 90)));
-        let _ = claim_with_confirm(&mock, "Example.COM.", || Ok(true)).await;
+        let _ = claim_with_check(
+            &mock,
+            "Example.COM.",
+            &FakeResolver::pointing_at_edge("example.com"),
+        )
+        .await;
         let calls = mock.calls.lock().unwrap();
         assert_eq!(calls.claim_host_calls[0].host, "example.com");
     }
@@ -326,10 +380,7 @@ mod tests {
     async fn already_provisioned_host_skips_dns_and_cert() {
         let mock = MockApiClient::logged_in().with_claim_host(Ok(provisioned_host(1, 90)));
 
-        let result = claim_with_confirm(&mock, "example.com", || {
-            panic!("confirmation prompt should not be invoked for an already-provisioned host")
-        })
-        .await;
+        let result = claim_with_check(&mock, "example.com", &NoLookups).await;
         assert!(result.is_ok(), "expected ok, got {result:?}");
 
         let calls = mock.calls.lock().unwrap();
@@ -344,10 +395,7 @@ mod tests {
             .with_claim_host(Ok(provisioned_host(60, 90)))
             .with_request_host_cert(Ok(provisioned_host(0, 90)));
 
-        let result = claim_with_confirm(&mock, "example.com", || {
-            panic!("DNS prompt should be skipped when a cert already exists")
-        })
-        .await;
+        let result = claim_with_check(&mock, "example.com", &NoLookups).await;
         assert!(result.is_ok(), "expected ok, got {result:?}");
 
         let calls = mock.calls.lock().unwrap();
@@ -367,10 +415,7 @@ mod tests {
 
         let mock = MockApiClient::logged_in().with_claim_host(Ok(claimed));
 
-        let result = claim_with_confirm(&mock, "demo.unisrv.dev", || {
-            panic!("DNS prompt should be skipped for unisrv.dev subdomains")
-        })
-        .await;
+        let result = claim_with_check(&mock, "demo.unisrv.dev", &NoLookups).await;
         assert!(result.is_ok(), "expected ok, got {result:?}");
 
         let calls = mock.calls.lock().unwrap();
@@ -391,11 +436,9 @@ mod tests {
         claimed.host = "demo.unisrv.dev".into();
         let mock = MockApiClient::logged_in().with_claim_host(Ok(claimed));
 
-        let err = claim_with_confirm(&mock, "demo.unisrv.dev", || {
-            panic!("DNS prompt should be skipped for unisrv.dev subdomains")
-        })
-        .await
-        .unwrap_err();
+        let err = claim_with_check(&mock, "demo.unisrv.dev", &NoLookups)
+            .await
+            .unwrap_err();
         assert!(
             err.to_string().contains("wildcard"),
             "expected a wildcard-cert error, got: {err}"
@@ -424,12 +467,12 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn user_declining_dns_prompt_skips_cert_request() {
+    async fn failing_dns_check_skips_cert_request() {
         let mock = MockApiClient::logged_in()
             .with_claim_host(Ok(unprovisioned_host()))
             .with_dns_config(Ok(dns_config()));
 
-        let result = claim_with_confirm(&mock, "example.com", || Ok(false)).await;
+        let result = claim_with_check(&mock, "example.com", &FakeResolver::default()).await;
         assert!(result.is_ok(), "expected ok, got {result:?}");
 
         let calls = mock.calls.lock().unwrap();
@@ -445,10 +488,7 @@ mod tests {
             reason: "Hostname is already in use".into(),
         }));
 
-        let result = claim_with_confirm(&mock, "example.com", || {
-            panic!("confirm should not run when claim fails")
-        })
-        .await;
+        let result = claim_with_check(&mock, "example.com", &NoLookups).await;
         let err = result.unwrap_err();
         assert!(err.to_string().contains("409"));
         assert!(err.to_string().contains("already in use"));
@@ -468,7 +508,12 @@ mod tests {
                 reason: "DNS validation failed: A record does not point at allowed IP".into(),
             }));
 
-        let result = claim_with_confirm(&mock, "example.com", || Ok(true)).await;
+        let result = claim_with_check(
+            &mock,
+            "example.com",
+            &FakeResolver::pointing_at_edge("example.com"),
+        )
+        .await;
         let err = result.unwrap_err();
         assert!(err.to_string().contains("DNS validation failed"));
 
@@ -479,7 +524,7 @@ mod tests {
     #[tokio::test]
     async fn provision_managed_host_errors_when_claim_returns_unmanaged_host() {
         // Defensive: if the API ever returns a host whose name is not a managed
-        // *.unisrv.dev domain and carries no cert, the DNS-confirmation branch
+        // *.unisrv.dev domain and carries no cert, the DNS-check branch
         // is reached. It must surface a clean error, not panic the CLI.
         let mut unexpected = unprovisioned_host();
         unexpected.host = "elsewhere.example.com".into();
@@ -492,7 +537,7 @@ mod tests {
             .unwrap_err();
         assert!(
             err.to_string().contains("unexpected"),
-            "expected an unexpected-DNS-prompt error, got: {err}"
+            "expected an unexpected-DNS-check error, got: {err}"
         );
     }
 
@@ -503,16 +548,13 @@ mod tests {
         // would return a no-op success and leave the host without a cert) — we
         // must still request the certificate.
         let mut claimed = unprovisioned_host();
-        claimed.certificate_valid_until = Some(Utc::now().naive_utc() + Duration::days(30));
+        claimed.certificate_valid_until = Some(Utc::now() + Duration::days(30));
         // certificate_type stays None: no actual cert exists.
         let mock = MockApiClient::logged_in()
             .with_claim_host(Ok(claimed))
             .with_request_host_cert(Ok(provisioned_host(0, 90)));
 
-        let result = claim_with_confirm(&mock, "example.com", || {
-            panic!("DNS prompt should be skipped when a valid_until is already present")
-        })
-        .await;
+        let result = claim_with_check(&mock, "example.com", &NoLookups).await;
         assert!(result.is_ok(), "expected ok, got {result:?}");
 
         let calls = mock.calls.lock().unwrap();
@@ -522,7 +564,7 @@ mod tests {
     #[test]
     fn cert_in_lockout_handles_missing_expiry() {
         let host = unprovisioned_host();
-        assert!(!cert_in_lockout(&host, Utc::now().naive_utc()));
+        assert!(!cert_in_lockout(&host, Utc::now()));
     }
 
     #[test]
@@ -531,19 +573,19 @@ mod tests {
         // no real cert to be locked out of renewing.
         let mut host = provisioned_host(10, 90);
         host.certificate_type = None;
-        assert!(!cert_in_lockout(&host, Utc::now().naive_utc()));
+        assert!(!cert_in_lockout(&host, Utc::now()));
     }
 
     #[test]
     fn cert_in_lockout_locks_out_for_first_half_of_lifetime() {
         let host = provisioned_host(10, 90); // 10 days into 90-day cert; lockout until day 45
-        assert!(cert_in_lockout(&host, Utc::now().naive_utc()));
+        assert!(cert_in_lockout(&host, Utc::now()));
     }
 
     #[test]
     fn cert_in_lockout_releases_after_half_of_lifetime() {
         let host = provisioned_host(50, 90); // 50 days into 90-day cert; lockout was until day 45
-        assert!(!cert_in_lockout(&host, Utc::now().naive_utc()));
+        assert!(!cert_in_lockout(&host, Utc::now()));
     }
 
     // ── list ──
@@ -551,9 +593,9 @@ mod tests {
     fn host_with(
         name: &str,
         cert_type: Option<CertificateType>,
-        valid_until: Option<NaiveDateTime>,
+        valid_until: Option<DateTime<Utc>>,
         attached: bool,
-        created_at: NaiveDateTime,
+        created_at: DateTime<Utc>,
     ) -> HostResponse {
         HostResponse {
             id: Uuid::new_v4(),
@@ -570,7 +612,7 @@ mod tests {
     #[tokio::test]
     async fn list_calls_api_once() {
         let mock = MockApiClient::logged_in().with_list_hosts(Ok(vec![]));
-        let result = list(&mock, false).await;
+        let result = list(&mock, &Output::Table, &TableOptions::default()).await;
         assert!(result.is_ok(), "expected ok, got {result:?}");
         assert_eq!(mock.calls.lock().unwrap().list_hosts_calls, 1);
     }
@@ -578,7 +620,7 @@ mod tests {
     #[tokio::test]
     async fn list_json_with_empty_array() {
         let mock = MockApiClient::logged_in().with_list_hosts(Ok(vec![]));
-        let result = list(&mock, true).await;
+        let result = list(&mock, &Output::Json, &TableOptions::default()).await;
         assert!(result.is_ok());
     }
 
@@ -588,14 +630,14 @@ mod tests {
             status: 500,
             reason: "internal".into(),
         }));
-        let result = list(&mock, false).await;
+        let result = list(&mock, &Output::Table, &TableOptions::default()).await;
         let err = result.unwrap_err();
         assert!(err.to_string().contains("500"));
     }
 
     #[test]
     fn render_table_includes_host_and_status_columns() {
-        let now = Utc::now().naive_utc();
+        let now = Utc::now();
         let hosts = vec![
             host_with(
                 "healthy.example.com",
@@ -621,7 +663,7 @@ mod tests {
             host_with("fresh.example.com", None, None, false, now),
         ];
 
-        let rendered = render_table(&hosts, now, false);
+        let rendered = render_table(&hosts, now, false, &TableOptions::default()).unwrap();
 
         assert!(rendered.contains("HOST"));
         assert!(rendered.contains("CERT"));
@@ -645,7 +687,7 @@ mod tests {
 
     #[test]
     fn format_expires_buckets() {
-        let now = Utc::now().naive_utc();
+        let now = Utc::now();
 
         let (text, color) = format_expires(None, now);
         assert_eq!(text, "\u{2014}");
@@ -0,0 +1,387 @@
+//! `unisrv host record` — DNS records published under a claimed host, e.g.
+//! `api.example.com CNAME srvedge.net` once `example.com` is claimed.
+//!
+//! Record names are relative to the host (`api`, or `@` for the host itself);
+//! a fully-qualified name under the host is accepted and shortened.
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use anyhow::{Context, Result, bail};
+use comfy_table::{Attribute, Cell};
+use unisrv_api::ApiClient;
+use unisrv_api::models::{CreateDnsRecordRequest, DnsRecord, DnsRecordType, HostResponse};
+
+use super::{find_claimed, normalize_host};
+use crate::commands::output::Output;
+use crate::commands::ui::new_table;
+
+pub async fn add(
+    client: &dyn ApiClient,
+    hostname: &str,
+    name: &str,
+    record_type: &str,
+    value: &str,
+    ttl: Option<u32>,
+) -> Result<()> {
+    let record_type = parse_record_type(record_type)?;
+    let value = value.trim();
+    let host = find_claimed(client, hostname).await?;
+    let name = relative_name(name, &host.host)?;
+    validate(&name, record_type, value)?;
+
+    let existing = fetch(client, &host).await?;
+    if existing
+        .iter()
+        .any(|r| r.name == name && r.record_type == record_type && r.value == value)
+    {
+        println!(
+            "{} {} {value} already exists.",
+            fqdn(&name, &host.host),
+            type_name(record_type)
+        );
+        return Ok(());
+    }
+
+    let record = client
+        .create_host_record(
+            host.id,
+            CreateDnsRecordRequest {
+                name,
+                record_type,
+                value: value.to_string(),
+                ttl,
+            },
+        )
+        .await
+        .with_context(|| format!("failed to add the record under {}", host.host))?;
+    println!(
+        "\u{2713} Added {} {} {}.",
+        fqdn(&record.name, &host.host),
+        type_name(record.record_type),
+        record.value
+    );
+    Ok(())
+}
+
+pub async fn list(client: &dyn ApiClient, hostname: &str, output: &Output) -> Result<()> {
+    let host = find_claimed(client, hostname).await?;
+    let records = fetch(client, &host).await?;
+    match output {
+        Output::Json | Output::Jq(_) => output.print_json(&records),
+        Output::Template(template) => {
+            print!("{}", template.render_all(&records)?);
+            Ok(())
+        }
+        Output::Table if records.is_empty() => {
+            println!(
+                "No records under {0}. Add one with `unisrv host record add {0} <name> --type <type> --value <value>`.",
+                host.host
+            );
+            Ok(())
+        }
+        Output::Table => {
+            println!("{}", render_table(&records, &host.host));
+            Ok(())
+        }
+    }
+}
+
+/// Remove the record `reference` names: its name when only one record (of
+/// `record_type`, if given) has it, or its id or a unique prefix of it.
+pub async fn remove(
+    client: &dyn ApiClient,
+    hostname: &str,
+    reference: &str,
+    record_type: Option<&str>,
+) -> Result<()> {
+    let record_type = record_type.map(parse_record_type).transpose()?;
+    let host = find_claimed(client, hostname).await?;
+    let records = fetch(client, &host).await?;
+    let record = find_record(&records, reference, record_type, &host.host)?;
+    client
+        .delete_host_record(host.id, record.id)
+        .await
+        .with_context(|| format!("failed to remove the record under {}", host.host))?;
+    println!(
+        "\u{2713} Removed {} {} {}.",
+        fqdn(&record.name, &host.host),
+        type_name(record.record_type),
+        record.value
+    );
+    Ok(())
+}
+
+/// The host's records, or a clear error from a backend without them.
+async fn fetch(client: &dyn ApiClient, host: &HostResponse) -> Result<Vec<DnsRecord>> {
+    match client.list_host_records(host.id).await {
+        Ok(records) => Ok(records),
+        Err(e) if e.is_unsupported_endpoint() => {
+            bail!("this server doesn't manage DNS records for hosts")
+        }
+        Err(e) => Err(e).with_context(|| format!("failed to list the records under {}", host.host)),
+    }
+}
+
+fn parse_record_type(s: &str) -> Result<DnsRecordType> {
+    Ok(match s.to_ascii_uppercase().as_str() {
+        "A" => DnsRecordType::A,
+        "AAAA" => DnsRecordType::Aaaa,
+        "CNAME" => DnsRecordType::Cname,
+        "MX" => DnsRecordType::Mx,
+        "TXT" => DnsRecordType::Txt,
+        _ => bail!("unsupported record type {s:?}: expected A, AAAA, CNAME, MX or TXT"),
+    })
+}
+
+fn type_name(record_type: DnsRecordType) -> &'static str {
+    match record_type {
+        DnsRecordType::A => "A",
+        DnsRecordType::Aaaa => "AAAA",
+        DnsRecordType::Cname => "CNAME",
+        DnsRecordType::Mx => "MX",
+        DnsRecordType::Txt => "TXT",
+        DnsRecordType::Unknown => "?",
+    }
+}
+
+/// `name` relative to `host`: `api` and `api.example.com` are both `api`
+/// under `example.com`, and the host itself is `@`.
+fn relative_name(name: &str, host: &str) -> Result<String> {
+    let name = normalize_host(name.trim());
+    let host = normalize_host(host);
+    if name == "@" || name == host {
+        return Ok("@".to_string());
+    }
+    let relative = name.strip_suffix(&format!(".{host}")).unwrap_or(&name);
+    if relative.is_empty()
+        || relative
+            .split('.')
+            .any(|label| label.is_empty() || !label.chars().all(is_label_char))
+    {
+        bail!("invalid record name {name:?}: expected e.g. api, or @ for {host} itself");
+    }
+    Ok(relative.to_string())
+}
+
+fn is_label_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '*')
+}
+
+fn fqdn(name: &str, host: &str) -> String {
+    if name == "@" {
+        host.to_string()
+    } else {
+        format!("{name}.{host}")
+    }
+}
+
+/// Catch what DNS would refuse before the API does.
+fn validate(name: &str, record_type: DnsRecordType, value: &str) -> Result<()> {
+    if value.is_empty() {
+        bail!("--value is empty");
+    }
+    match record_type {
+        DnsRecordType::A if value.parse::<Ipv4Addr>().is_err() => {
+            bail!("an A record's value is an IPv4 address, not {value:?}")
+        }
+        DnsRecordType::Aaaa if value.parse::<Ipv6Addr>().is_err() => {
+            bail!("an AAAA record's value is an IPv6 address, not {value:?}")
+        }
+        DnsRecordType::Cname if name == "@" => {
+            bail!("a CNAME can't be published for the host itself; use A/AAAA records instead")
+        }
+        _ => Ok(()),
+    }
+}
+
+fn find_record<'a>(
+    records: &'a [DnsRecord],
+    reference: &str,
+    record_type: Option<DnsRecordType>,
+    host: &str,
+) -> Result<&'a DnsRecord> {
+    let input = reference.trim().to_ascii_lowercase();
+    if input.is_empty() {
+        bail!("no record given");
+    }
+    let of_type = |r: &&DnsRecord| record_type.is_none_or(|t| r.record_type == t);
+    // Names first: a short one like `db` could also be the start of an id.
+    let by_name: Vec<&DnsRecord> = match relative_name(&input, host) {
+        Ok(name) => records
+            .iter()
+            .filter(|r| r.name == name)
+            .filter(of_type)
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    let matching = if by_name.is_empty() {
+        records
+            .iter()
+            .filter(|r| r.id.to_string().starts_with(&input))
+            .filter(of_type)
+            .collect()
+    } else {
+        by_name
+    };
+    match matching.as_slice() {
+        [record] => Ok(record),
+        [] => bail!("no record {reference} under {host} (see `unisrv host record list {host}`)"),
+        _ => bail!(
+            "{reference} matches {} records under {host}; give its id or --type",
+            matching.len()
+        ),
+    }
+}
+
+fn render_table(records: &[DnsRecord], host: &str) -> String {
+    let mut table = new_table();
+    table.set_header(
+        ["ID", "NAME", "TYPE", "VALUE", "TTL"].map(|h| Cell::new(h).add_attribute(Attribute::Bold)),
+    );
+    for record in records {
+        table.add_row(vec![
+            Cell::new(&record.id.to_string()[..8]),
+            Cell::new(fqdn(&record.name, host)),
+            Cell::new(type_name(record.record_type)),
+            Cell::new(&record.value),
+            Cell::new(record.ttl.map_or("\u{2014}".to_string(), |t| t.to_string())),
+        ]);
+    }
+    table.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use unisrv_api::test_support::MockApiClient;
+    use uuid::Uuid;
+
+    fn host() -> HostResponse {
+        HostResponse {
+            id: Uuid::new_v4(),
+            host: "example.com".into(),
+            user_id: Uuid::nil(),
+            service_id: None,
+            certificate_type: None,
+            certificate_valid_until: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn record(name: &str, record_type: DnsRecordType, value: &str) -> DnsRecord {
+        DnsRecord {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            record_type,
+            value: value.into(),
+            ttl: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn names_are_relative_to_the_host() {
+        assert_eq!(relative_name("api", "example.com").unwrap(), "api");
+        assert_eq!(
+            relative_name("API.Example.com.", "example.com").unwrap(),
+            "api"
+        );
+        assert_eq!(relative_name("example.com", "example.com").unwrap(), "@");
+        assert_eq!(relative_name("a.b", "example.com").unwrap(), "a.b");
+        assert!(relative_name("bad name", "example.com").is_err());
+        assert!(relative_name("a..b", "example.com").is_err());
+        assert!(relative_name("a@b", "example.com").is_err());
+    }
+
+    #[tokio::test]
+    async fn adds_a_record_by_its_relative_name() {
+        let host = host();
+        let mock = MockApiClient::logged_in()
+            .with_list_hosts(Ok(vec![host.clone()]))
+            .push_list_host_records(Ok(vec![]))
+            .push_create_host_record(Ok(record("api", DnsRecordType::Cname, "srvedge.net")));
+
+        add(
+            &mock,
+            "example.com",
+            "api.example.com",
+            "cname",
+            "srvedge.net",
+            None,
+        )
+        .await
+        .unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        assert_eq!(
+            calls.create_host_record_calls,
+            vec![(
+                host.id,
+                CreateDnsRecordRequest {
+                    name: "api".into(),
+                    record_type: DnsRecordType::Cname,
+                    value: "srvedge.net".into(),
+                    ttl: None,
+                }
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_records_dns_would_refuse_before_creating_them() {
+        for (name, kind, value) in [
+            ("api", "A", "srvedge.net"),
+            ("api", "AAAA", "192.0.2.1"),
+            ("@", "CNAME", "srvedge.net"),
+            ("api", "SRV", "x"),
+        ] {
+            let mock = MockApiClient::logged_in()
+                .with_list_hosts(Ok(vec![host()]))
+                .push_list_host_records(Ok(vec![]));
+            let result = add(&mock, "example.com", name, kind, value, None).await;
+            assert!(result.is_err(), "{name} {kind} {value} should be rejected");
+            assert!(
+                mock.calls
+                    .lock()
+                    .unwrap()
+                    .create_host_record_calls
+                    .is_empty()
+            );
+        }
+    }
+
+    #[test]
+    fn removal_by_name_needs_the_type_when_ambiguous() {
+        let records = vec![
+            record("api", DnsRecordType::A, "192.0.2.1"),
+            record("api", DnsRecordType::Aaaa, "2001:db8::1"),
+            record("www", DnsRecordType::Cname, "example.com"),
+        ];
+        let err = find_record(&records, "api", None, "example.com").unwrap_err();
+        assert!(err.to_string().contains("matches 2 records"), "{err}");
+
+        let found = find_record(&records, "api", Some(DnsRecordType::Aaaa), "example.com");
+        assert_eq!(found.unwrap().value, "2001:db8::1");
+        let by_id = &records[2].id.to_string()[..8];
+        assert_eq!(
+            find_record(&records, by_id, None, "example.com")
+                .unwrap()
+                .name,
+            "www"
+        );
+    }
+
+    #[tokio::test]
+    async fn a_backend_without_records_says_so() {
+        let mock = MockApiClient::logged_in().with_list_hosts(Ok(vec![host()]));
+        let err = list(&mock, "example.com", &Output::Table)
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("doesn't manage DNS records"),
+            "{err}"
+        );
+    }
+}
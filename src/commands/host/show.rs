@@ -0,0 +1,382 @@
+//! `unisrv host show <host>` — everything about one claimed host: the service
+//! it's bound to, where its certificate stands, and the certificate itself.
+//!
+//! The issued certificate's issuer, names and validity come from the
+//! certificate endpoint; a backend without it still gets the summary
+//! `host list` has. A wildcard host waiting on DNS validation shows the TXT
+//! record it's waiting for.
+
+use std::fmt::Write;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use console::Style;
+use serde::Serialize;
+use unisrv_api::models::{
+    CertificateType, ChallengeStatus, DnsChallenge, HostCertificate, HostResponse,
+};
+use unisrv_api::{ApiClient, ApiError};
+use uuid::Uuid;
+
+use super::find_claimed;
+use crate::commands::locale::{self, Locale};
+use crate::commands::output::Output;
+use crate::commands::ui::{colors_enabled, format_relative};
+
+/// What `host show` prints, and its `--json`.
+#[derive(Debug, Serialize)]
+pub struct HostDetails {
+    #[serde(flatten)]
+    pub host: HostResponse,
+    pub service: Option<BoundService>,
+    pub certificate: Option<HostCertificate>,
+    pub challenge: Option<DnsChallenge>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BoundService {
+    pub id: Uuid,
+    /// `None` when the service isn't in any environment this user can see.
+    pub name: Option<String>,
+    /// `project/environment`.
+    pub environment: Option<String>,
+}
+
+pub async fn show(client: &dyn ApiClient, hostname: &str, output: &Output) -> Result<()> {
+    let details = fetch(client, hostname).await?;
+    match output {
+        Output::Json | Output::Jq(_) => output.print_json(&details),
+        Output::Template(template) => {
+            println!("{}", template.render(&details)?);
+            Ok(())
+        }
+        Output::Table => {
+            print!(
+                "{}",
+                render(&details, Utc::now(), locale::current(), colors_enabled())
+            );
+            Ok(())
+        }
+    }
+}
+
+async fn fetch(client: &dyn ApiClient, hostname: &str) -> Result<HostDetails> {
+    let host = find_claimed(client, hostname).await?;
+
+    let service = match host.service_id {
+        Some(id) => Some(find_service(client, id).await?),
+        None => None,
+    };
+    let certificate = match host.certificate_type {
+        None | Some(CertificateType::CommonWildcard) => None,
+        Some(_) => missing_as_none(client.get_host_certificate(host.id).await)?,
+    };
+    let challenge = if host.host.starts_with("*.") && host.certificate_valid_until.is_none() {
+        missing_as_none(client.get_dns_challenge(host.id).await)?
+    } else {
+        None
+    };
+    Ok(HostDetails {
+        host,
+        service,
+        certificate,
+        challenge,
+    })
+}
+
+/// A 404 means there's nothing to show — not issued yet, or a backend
+/// without the endpoint.
+fn missing_as_none<T>(result: unisrv_api::Result<T>) -> Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(ApiError::Server { status: 404, .. }) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Hosts are per user, services per environment: look through each
+/// environment for the one holding `id`.
+async fn find_service(client: &dyn ApiClient, id: Uuid) -> Result<BoundService> {
+    for env in client.list_environments().await?.environments {
+        let services = client.list_services(env.id).await?.services;
+        if let Some(service) = services.into_iter().find(|s| s.id == id) {
+            return Ok(BoundService {
+                id,
+                name: Some(service.name),
+                environment: Some(format!("{}/{}", env.project, env.name)),
+            });
+        }
+    }
+    Ok(BoundService {
+        id,
+        name: None,
+        environment: None,
+    })
+}
+
+/// Plain-text summary. Pure so it can be asserted on without a terminal.
+fn render(details: &HostDetails, now: DateTime<Utc>, locale: &Locale, use_color: bool) -> String {
+    let paint =
+        |style: Style, text: String| style.force_styling(use_color).apply_to(text).to_string();
+    let host = &details.host;
+    let mut out = String::new();
+    let _ = writeln!(out, "{} ({})", host.host, host.id);
+
+    let service = match &details.service {
+        Some(BoundService {
+            name: Some(name),
+            environment: Some(env),
+            ..
+        }) => format!("{name} ({env})"),
+        Some(service) => service.id.to_string(),
+        None => paint(Style::new().dim(), "not attached".into()),
+    };
+    let _ = writeln!(out, "  service:     {service}");
+
+    let (status, style) = status(details, now);
+    let _ = writeln!(out, "  status:      {}", paint(style, status));
+    let kind = match host.certificate_type {
+        Some(CertificateType::CommonWildcard) => "platform wildcard (*.unisrv.dev)",
+        Some(CertificateType::LetsEncrypt) => "Let's Encrypt",
+        Some(CertificateType::Custom) => "custom",
+        Some(CertificateType::Unknown) => "unknown",
+        None => "none",
+    };
+    let _ = writeln!(out, "  certificate: {kind}");
+
+    if let Some(cert) = &details.certificate {
+        let _ = writeln!(out, "  issuer:      {}", cert.issuer);
+        if !cert.sans.is_empty() {
+            let _ = writeln!(out, "  names:       {}", cert.sans.join(", "));
+        }
+        let _ = writeln!(
+            out,
+            "  not before:  {}",
+            locale.format_datetime(cert.not_before)
+        );
+        let (left, style) = countdown(cert.not_after, now);
+        let _ = writeln!(
+            out,
+            "  not after:   {} ({})",
+            locale.format_datetime(cert.not_after),
+            paint(style, left)
+        );
+    } else if let Some(until) = host.certificate_valid_until {
+        let (left, style) = countdown(until, now);
+        let _ = writeln!(
+            out,
+            "  not after:   {} ({})",
+            locale.format_datetime(until),
+            paint(style, left)
+        );
+    }
+    if let Some(challenge) = &details.challenge {
+        let _ = writeln!(
+            out,
+            "  dns record:  TXT {} \"{}\"",
+            challenge.record_name, challenge.record_value
+        );
+    }
+    let _ = writeln!(
+        out,
+        "  claimed:     {}",
+        format_relative(host.created_at, now)
+    );
+    out
+}
+
+/// Where the host's certificate stands, in a few words.
+fn status(details: &HostDetails, now: DateTime<Utc>) -> (String, Style) {
+    let host = &details.host;
+    if host.certificate_type == Some(CertificateType::CommonWildcard) {
+        return ("serving".into(), Style::new().green());
+    }
+    match (host.certificate_valid_until, &details.challenge) {
+        (Some(until), _) if until > now => ("serving".into(), Style::new().green()),
+        (Some(_), _) => ("certificate expired".into(), Style::new().red()),
+        (None, Some(challenge)) => match challenge.status {
+            ChallengeStatus::Invalid => (
+                match &challenge.error {
+                    Some(error) => format!("DNS validation failed: {error}"),
+                    None => "DNS validation failed".into(),
+                },
+                Style::new().red(),
+            ),
+            _ => ("waiting for DNS validation".into(), Style::new().yellow()),
+        },
+        (None, None) => ("claimed, no certificate yet".into(), Style::new().yellow()),
+    }
+}
+
+/// "in 42 days" / "expired 3 days ago", red once expired and yellow inside
+/// the 30 days before — the same thresholds as `host list`.
+fn countdown(not_after: DateTime<Utc>, now: DateTime<Utc>) -> (String, Style) {
+    let days = (not_after - now).num_days();
+    if not_after <= now {
+        let ago = (now - not_after).num_days();
+        let text = match ago {
+            0 => "expired today".to_string(),
+            1 => "expired 1 day ago".to_string(),
+            n => format!("expired {n} days ago"),
+        };
+        return (text, Style::new().red());
+    }
+    let text = match days {
+        0 => "expires today".to_string(),
+        1 => "1 day left".to_string(),
+        n => format!("{n} days left"),
+    };
+    let style = if days < 30 {
+        Style::new().yellow()
+    } else {
+        Style::new().green()
+    };
+    (text, style)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use unisrv_api::models::{
+        EnvironmentListEntry, EnvironmentListResponse, ServiceListItem, ServiceListResponse,
+    };
+    use unisrv_api::test_support::MockApiClient;
+
+    fn host(
+        name: &str,
+        cert: Option<CertificateType>,
+        valid_for_days: Option<i64>,
+    ) -> HostResponse {
+        let now = Utc::now();
+        HostResponse {
+            id: Uuid::new_v4(),
+            host: name.into(),
+            user_id: Uuid::nil(),
+            service_id: None,
+            certificate_type: cert,
+            certificate_valid_until: valid_for_days.map(|d| now + Duration::days(d)),
+            created_at: now - Duration::days(10),
+            updated_at: now - Duration::days(10),
+        }
+    }
+
+    fn certificate(not_after: DateTime<Utc>) -> HostCertificate {
+        HostCertificate {
+            issuer: "R11".into(),
+            sans: vec!["shop.example.com".into(), "www.shop.example.com".into()],
+            not_before: not_after - Duration::days(90),
+            not_after,
+        }
+    }
+
+    #[tokio::test]
+    async fn shows_the_bound_service_and_certificate() {
+        let service_id = Uuid::new_v4();
+        let env_id = Uuid::new_v4();
+        let mut claimed = host(
+            "shop.example.com",
+            Some(CertificateType::LetsEncrypt),
+            Some(45),
+        );
+        claimed.service_id = Some(service_id);
+        let not_after = claimed.certificate_valid_until.unwrap();
+        let mock = MockApiClient::logged_in()
+            .with_list_hosts(Ok(vec![claimed.clone()]))
+            .with_list_environments(Ok(EnvironmentListResponse {
+                environments: vec![EnvironmentListEntry {
+                    id: env_id,
+                    project: "shop".into(),
+                    name: "prod".into(),
+                    slug: "ab12".into(),
+                    display_name: None,
+                    description: None,
+                    instance_count: 0,
+                    service_count: 1,
+                    deployment_count: 0,
+                    network_count: 0,
+                    created_at: Default::default(),
+                }],
+            }))
+            .with_list_services(Ok(ServiceListResponse {
+                services: vec![ServiceListItem {
+                    id: service_id,
+                    name: "web".into(),
+                    base_host: "web-ab12.unisrv.dev".into(),
+                    custom_hosts: vec!["shop.example.com".into()],
+                    managed_by: None,
+                }],
+            }))
+            .push_get_host_certificate(Ok(certificate(not_after)));
+
+        let details = fetch(&mock, "Shop.Example.com.").await.unwrap();
+        let out = render(&details, Utc::now(), &Locale::default(), false);
+
+        assert!(out.contains("service:     web (shop/prod)"), "{out}");
+        assert!(out.contains("status:      serving"), "{out}");
+        assert!(out.contains("issuer:      R11"), "{out}");
+        assert!(
+            out.contains("names:       shop.example.com, www.shop.example.com"),
+            "{out}"
+        );
+        assert!(
+            out.contains("44 days left") || out.contains("45 days left"),
+            "{out}"
+        );
+    }
+
+    #[tokio::test]
+    async fn a_pending_wildcard_shows_the_record_it_waits_for() {
+        let mock = MockApiClient::logged_in()
+            .with_list_hosts(Ok(vec![host("*.example.com", None, None)]))
+            .push_get_dns_challenge(Ok(DnsChallenge {
+                record_name: "_acme-challenge.example.com".into(),
+                record_value: "abc".into(),
+                status: ChallengeStatus::Pending,
+                error: None,
+            }));
+
+        let details = fetch(&mock, "*.example.com").await.unwrap();
+        let out = render(&details, Utc::now(), &Locale::default(), false);
+
+        assert!(out.contains("service:     not attached"), "{out}");
+        assert!(out.contains("waiting for DNS validation"), "{out}");
+        assert!(
+            out.contains("TXT _acme-challenge.example.com \"abc\""),
+            "{out}"
+        );
+        assert!(
+            mock.calls
+                .lock()
+                .unwrap()
+                .get_host_certificate_calls
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn countdown_colours_by_days_left() {
+        let now = Utc::now();
+        assert_eq!(countdown(now + Duration::days(60), now).0, "60 days left");
+        assert_eq!(
+            countdown(now - Duration::days(3), now).0,
+            "expired 3 days ago"
+        );
+        let soon = countdown(now + Duration::days(5), now).1;
+        assert_eq!(
+            soon.force_styling(true).apply_to("x").to_string(),
+            Style::new()
+                .yellow()
+                .force_styling(true)
+                .apply_to("x")
+                .to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn an_unclaimed_host_is_an_error() {
+        let mock = MockApiClient::logged_in().with_list_hosts(Ok(vec![]));
+        let err = fetch(&mock, "nope.example.com").await.unwrap_err();
+        assert!(err.to_string().contains("not claimed"), "{err:#}");
+    }
+}
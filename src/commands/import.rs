@@ -0,0 +1,485 @@
+//! `unisrv import instance <ref> --as <name>` — bring an instance started by
+//! hand (`instance run`, the API) under `unisrv.hcl`, one resource at a time.
+//!
+//! The instance's configuration is written out as a one-replica
+//! `deployment "<name>"` block appended to the manifest, and the live instance
+//! is adopted by a deployment of that name, so the next `unisrv up` finds it
+//! already deployed instead of starting a second copy. The server is asked
+//! first: if it refuses, the manifest is left as it was.
+
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use unisrv_api::ApiClient;
+use unisrv_api::models::{
+    AdoptInstanceRequest, AuxContainer, InstanceConfiguration, InstanceDetailResponse,
+    NetworkResponse,
+};
+
+use crate::commands::instance::resolve::lookup_instance;
+use crate::commands::instance::run::{announce_environment, current_environment};
+use crate::commands::up::config::UpConfig;
+use crate::commands::up::plan::ResolvedEnvironment;
+use crate::config_locate::{CONFIG_FILE, find_config};
+
+/// `unisrv import instance`: adopt `reference` as deployment `name`.
+pub async fn instance(
+    client: &dyn ApiClient,
+    env_flag: Option<&str>,
+    reference: &str,
+    name: &str,
+) -> Result<()> {
+    let cwd = std::env::current_dir().context("failed to determine the current directory")?;
+    let Some(manifest) = find_config(&cwd, CONFIG_FILE) else {
+        bail!(
+            "no {CONFIG_FILE} in this directory; `unisrv import` adds to an existing project's manifest"
+        );
+    };
+    let env = current_environment(client, env_flag).await?;
+    announce_environment(&env);
+    let imported = import_instance(client, &env, &manifest.path, reference, name).await?;
+    for note in &imported.notes {
+        eprintln!("{} {note}", console::style("note:").yellow());
+    }
+    println!(
+        "Imported {} as deployment {name:?} in {CONFIG_FILE}. Run `unisrv up` to check it plans no changes.",
+        imported.instance
+    );
+    Ok(())
+}
+
+/// What [`import_instance`] did.
+#[derive(Debug)]
+struct Imported {
+    /// The instance, as the user would recognise it.
+    instance: String,
+    /// Settings the block couldn't carry over, and other things to check.
+    notes: Vec<String>,
+}
+
+async fn import_instance(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    manifest: &Path,
+    reference: &str,
+    name: &str,
+) -> Result<Imported> {
+    if name.trim().is_empty() {
+        bail!("--as needs a deployment name");
+    }
+    let source = std::fs::read_to_string(manifest)
+        .with_context(|| format!("failed to read {}", manifest.display()))?;
+    if UpConfig::block_labels_at(manifest, &source, "deployment")?
+        .iter()
+        .any(|label| label == name)
+    {
+        bail!(
+            "{CONFIG_FILE} already has a `deployment {name:?}` block; pick another name with --as"
+        );
+    }
+    let declared_networks = UpConfig::block_labels_at(manifest, &source, "network")?;
+
+    let entry = lookup_instance(client, env.id, reference).await?;
+    let detail = client.get_instance(env.id, entry.id, true, false).await?;
+    let instance = match &detail.name {
+        Some(n) => format!("instance {n:?}"),
+        None => format!("instance {}", detail.id),
+    };
+    if let Some(deployment) = &detail.deployment {
+        bail!(
+            "{instance} already belongs to deployment {:?}; only standalone instances can be imported",
+            deployment.name
+        );
+    }
+    let network = match detail.network_id {
+        Some(id) => Some(client.get_network(env.id, id).await?),
+        None => None,
+    };
+    let declare_network = network
+        .as_ref()
+        .is_some_and(|n| !declared_networks.contains(&n.name));
+    let (block, notes) = render_blocks(name, &detail, network.as_ref(), declare_network)?;
+
+    match client
+        .adopt_instance(
+            env.id,
+            detail.id,
+            AdoptInstanceRequest {
+                deployment: name.to_string(),
+            },
+        )
+        .await
+    {
+        Ok(_) => {}
+        Err(e) if e.is_unsupported_endpoint() => {
+            bail!(
+                "this server doesn't support adopting instances; {CONFIG_FILE} was left unchanged"
+            )
+        }
+        Err(e) => return Err(e).with_context(|| format!("failed to adopt {instance}")),
+    }
+
+    let mut updated = source;
+    if !updated.is_empty() && !updated.ends_with('\n') {
+        updated.push('\n');
+    }
+    updated.push('\n');
+    updated.push_str(&block);
+    std::fs::write(manifest, updated).with_context(|| {
+        format!(
+            "{instance} now belongs to deployment {name:?}, but {} couldn't be updated; add this by hand:\n\n{block}",
+            manifest.display()
+        )
+    })?;
+    Ok(Imported { instance, notes })
+}
+
+/// The `deployment` block (and, when the manifest doesn't declare it yet, the
+/// `network` block) reproducing `detail`, plus notes on anything left out.
+fn render_blocks(
+    name: &str,
+    detail: &InstanceDetailResponse,
+    network: Option<&NetworkResponse>,
+    declare_network: bool,
+) -> Result<(String, Vec<String>)> {
+    let Ok(config) = serde_json::from_value::<InstanceConfiguration>(detail.configuration.clone())
+    else {
+        bail!("the instance's configuration isn't one this CLI recognises; it can't be imported");
+    };
+    let mut notes = Vec::new();
+    let mut body = hcl::Body::builder();
+
+    if let Some(network) = network.filter(|_| declare_network) {
+        body = body.add_block(
+            hcl::Block::builder("network")
+                .add_label(network.name.as_str())
+                .add_attribute(("iprange", network.ipv4_cidr.as_str()))
+                .build(),
+        );
+    }
+
+    let mut deployment = hcl::Block::builder("deployment")
+        .add_label(name)
+        .add_attribute(("replicas", 1u64));
+    let mut ports: Vec<u16> = detail
+        .service_targets
+        .iter()
+        .flatten()
+        .map(|t| t.instance_port)
+        .collect();
+    ports.sort_unstable();
+    ports.dedup();
+    if let Some(port) = ports.first() {
+        deployment = deployment.add_attribute(("port", *port));
+        if ports.len() > 1 {
+            notes.push(format!(
+                "the instance serves ports {ports:?}; only port {port} was written"
+            ));
+        }
+    }
+    if let Some(network) = network {
+        deployment = deployment.add_attribute(("network", network.name.as_str()));
+    }
+    if let Some(resources) = &detail.resources {
+        deployment = deployment
+            .add_attribute(("vcpus", resources.vcpu_count))
+            .add_attribute(("vcpu_ratio", resources.vcpu_ratio))
+            .add_attribute(("memory", memory_spec(resources.memory_mb)));
+        if resources
+            .memory_request_mb
+            .is_some_and(|request| request < resources.memory_mb)
+        {
+            notes.push(format!(
+                "guaranteed memory below the {}MB limit can't be set in {CONFIG_FILE}; the deployment reserves all of it",
+                resources.memory_mb
+            ));
+        }
+    } else {
+        notes.push(
+            "the server didn't report the instance's sizing; the block uses the defaults".into(),
+        );
+    }
+
+    let mut container =
+        hcl::Block::builder("container").add_attribute(("image", config.container_image.as_str()));
+    if let Some(args) = &config.args {
+        container = container.add_attribute(("args", args.clone()));
+    }
+    if let Some(env) = config.env.as_ref().filter(|env| !env.is_empty()) {
+        let object: hcl::Object<hcl::ObjectKey, hcl::Expression> = env
+            .iter()
+            .map(|(k, v)| (hcl::ObjectKey::from(k.as_str()), v.as_str().into()))
+            .collect();
+        container = container.add_attribute(("env", object));
+        notes.push(format!(
+            "{} environment value{} written to {CONFIG_FILE} as-is; move secrets into ${{var.…}}",
+            env.len(),
+            if env.len() == 1 { " was" } else { "s were" }
+        ));
+    }
+    deployment = deployment.add_block(container.build());
+    for init in config.init_containers.iter().flatten() {
+        deployment = deployment.add_block(aux_block("init_container", init));
+    }
+    for sidecar in config.sidecars.iter().flatten() {
+        deployment = deployment.add_block(aux_block("sidecar", sidecar));
+    }
+
+    if config
+        .hostname
+        .as_ref()
+        .is_some_and(|h| Some(h) != detail.name.as_ref())
+    {
+        notes.push("the instance's custom hostname can't be set in a deployment".into());
+    }
+    if config.metadata.as_ref().is_some_and(|m| !m.is_empty()) {
+        notes.push("the instance's guest metadata can't be set in a deployment".into());
+    }
+
+    let body = body.add_block(deployment.build()).build();
+    let rendered = hcl::to_string(&body).context("failed to render the deployment block")?;
+    Ok((rendered, notes))
+}
+
+fn aux_block(kind: &str, container: &AuxContainer) -> hcl::Block {
+    let mut block = hcl::Block::builder(kind).add_attribute(("image", container.image.as_str()));
+    if let Some(args) = &container.args {
+        block = block.add_attribute(("args", args.clone()));
+    }
+    block.build()
+}
+
+/// Whole gigabytes as `"2GB"`, anything else in megabytes.
+fn memory_spec(memory_mb: u32) -> String {
+    if memory_mb >= 1024 && memory_mb.is_multiple_of(1024) {
+        format!("{}GB", memory_mb / 1024)
+    } else {
+        format!("{memory_mb}MB")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::up::plan::test_env;
+    use std::collections::BTreeMap;
+    use unisrv_api::ApiError;
+    use unisrv_api::models::{
+        DeploymentInfo, InstanceListEntry, InstanceListResponse, InstanceResources, InstanceState,
+        ServiceTargetInfo,
+    };
+    use unisrv_api::test_support::MockApiClient;
+    use uuid::Uuid;
+
+    const MANIFEST: &str = "project = \"shop\"\n";
+
+    fn detail(id: Uuid) -> InstanceDetailResponse {
+        let now = chrono::Utc::now().naive_utc();
+        InstanceDetailResponse {
+            id,
+            name: Some("api".into()),
+            node_id: Uuid::nil(),
+            state: InstanceState("running".into()),
+            exit_code: None,
+            exit_reason: None,
+            configuration: serde_json::to_value(InstanceConfiguration {
+                container_image: "ghcr.io/acme/api:1.4".into(),
+                args: Some(vec!["serve".into(), "--port=8080".into()]),
+                env: Some(BTreeMap::from([(
+                    "GREETING".to_string(),
+                    "hi ${USER}".to_string(),
+                )])),
+                hostname: None,
+                metadata: None,
+                init_containers: Some(vec![AuxContainer {
+                    image: "ghcr.io/acme/migrate:1.4".into(),
+                    args: None,
+                }]),
+                sidecars: None,
+                platform: None,
+            })
+            .unwrap(),
+            created_at: now,
+            updated_at: now,
+            network_id: None,
+            network_ip: None,
+            deployment: None,
+            service_targets: Some(vec![ServiceTargetInfo {
+                id: Uuid::new_v4(),
+                service_id: Uuid::new_v4(),
+                service_name: "web".into(),
+                instance_port: 8080,
+            }]),
+            proxied_ports: None,
+            resources: Some(InstanceResources {
+                vcpu_count: 2,
+                vcpu_ratio: 0.5,
+                memory_mb: 2048,
+                memory_request_mb: None,
+            }),
+            annotations: None,
+        }
+    }
+
+    fn listed(detail: &InstanceDetailResponse) -> InstanceListResponse {
+        InstanceListResponse {
+            instances: vec![InstanceListEntry {
+                id: detail.id,
+                name: detail.name.clone(),
+                state: detail.state.clone(),
+                container_image: "ghcr.io/acme/api:1.4".into(),
+                created_at: detail.created_at,
+                deployment: None,
+                retained_until: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn the_block_reads_back_as_the_instance() {
+        let (block, notes) = render_blocks("api", &detail(Uuid::new_v4()), None, false).unwrap();
+        let config = UpConfig::parse(&format!("{MANIFEST}\n{block}")).unwrap();
+
+        let deployment = &config.deployment["api"];
+        assert_eq!(deployment.replicas, Some(1));
+        assert_eq!(deployment.port, Some(8080));
+        assert_eq!(deployment.vcpus, Some(2));
+        assert_eq!(deployment.vcpu_ratio, Some(0.5));
+        assert_eq!(deployment.memory.as_ref().unwrap().to_mb(), Ok(2048));
+        assert_eq!(deployment.container.image, "ghcr.io/acme/api:1.4");
+        assert_eq!(
+            deployment.container.args.as_deref(),
+            Some(&["serve".to_string(), "--port=8080".to_string()][..])
+        );
+        // Taken literally, not as an interpolation.
+        assert_eq!(
+            deployment.container.env.as_ref().unwrap()["GREETING"],
+            "hi ${USER}"
+        );
+        assert_eq!(
+            deployment.init_containers[0].image,
+            "ghcr.io/acme/migrate:1.4"
+        );
+        assert_eq!(notes.len(), 1, "{notes:?}");
+    }
+
+    #[test]
+    fn an_undeclared_network_is_declared_alongside() {
+        let now = chrono::Utc::now().naive_utc();
+        let network = NetworkResponse {
+            id: Uuid::new_v4(),
+            environment_id: Uuid::new_v4(),
+            name: "backend".into(),
+            ipv4_cidr: "10.1.0.0/16".into(),
+            created_at: now,
+            instances: vec![],
+        };
+        let (block, _) =
+            render_blocks("api", &detail(Uuid::new_v4()), Some(&network), true).unwrap();
+        let config = UpConfig::parse(&format!("{MANIFEST}\n{block}")).unwrap();
+        assert_eq!(
+            config.network["backend"].iprange.as_deref(),
+            Some("10.1.0.0/16")
+        );
+        assert_eq!(config.deployment["api"].network.as_deref(), Some("backend"));
+    }
+
+    #[tokio::test]
+    async fn adopts_the_instance_then_appends_the_block() {
+        let tmp = tempfile::tempdir().unwrap();
+        let manifest = tmp.path().join(CONFIG_FILE);
+        std::fs::write(&manifest, MANIFEST).unwrap();
+        let detail = detail(Uuid::new_v4());
+        let mock = MockApiClient::logged_in()
+            .with_list_instances(Ok(listed(&detail)))
+            .push_get_instance(Ok(detail.clone()))
+            .push_adopt_instance(Ok(DeploymentInfo {
+                id: Uuid::new_v4(),
+                name: "api".into(),
+            }));
+
+        let env = test_env();
+        let imported = import_instance(&mock, &env, &manifest, &detail.id.to_string(), "api")
+            .await
+            .unwrap();
+
+        assert_eq!(imported.instance, "instance \"api\"");
+        assert_eq!(
+            mock.calls.lock().unwrap().adopt_instance_calls,
+            vec![(
+                env.id,
+                detail.id,
+                AdoptInstanceRequest {
+                    deployment: "api".into()
+                }
+            )]
+        );
+        let written = std::fs::read_to_string(&manifest).unwrap();
+        assert!(written.starts_with(MANIFEST), "{written}");
+        assert!(
+            UpConfig::parse(&written)
+                .unwrap()
+                .deployment
+                .contains_key("api")
+        );
+
+        // A second import under the same name is refused before any API call.
+        let err = import_instance(&mock, &env, &manifest, "api", "api")
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("already has a `deployment \"api\"`"),
+            "{err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn leaves_the_manifest_alone_when_the_server_cannot_adopt() {
+        let tmp = tempfile::tempdir().unwrap();
+        let manifest = tmp.path().join(CONFIG_FILE);
+        std::fs::write(&manifest, MANIFEST).unwrap();
+        let detail = detail(Uuid::new_v4());
+        let mock = MockApiClient::logged_in()
+            .with_list_instances(Ok(listed(&detail)))
+            .push_get_instance(Ok(detail.clone()))
+            .push_adopt_instance(Err(ApiError::Server {
+                status: 404,
+                reason: "not found".into(),
+            }));
+
+        let err = import_instance(&mock, &test_env(), &manifest, &detail.id.to_string(), "api")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "this server doesn't support adopting instances; unisrv.hcl was left unchanged"
+        );
+        assert_eq!(std::fs::read_to_string(&manifest).unwrap(), MANIFEST);
+    }
+
+    #[tokio::test]
+    async fn refuses_an_instance_that_already_belongs_to_a_deployment() {
+        let tmp = tempfile::tempdir().unwrap();
+        let manifest = tmp.path().join(CONFIG_FILE);
+        std::fs::write(&manifest, MANIFEST).unwrap();
+        let mut detail = detail(Uuid::new_v4());
+        detail.deployment = Some(DeploymentInfo {
+            id: Uuid::new_v4(),
+            name: "web".into(),
+        });
+        let mock = MockApiClient::logged_in()
+            .with_list_instances(Ok(listed(&detail)))
+            .push_get_instance(Ok(detail.clone()));
+
+        let err = import_instance(&mock, &test_env(), &manifest, &detail.id.to_string(), "api")
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("already belongs to deployment \"web\""),
+            "{err}"
+        );
+        assert!(mock.calls.lock().unwrap().adopt_instance_calls.is_empty());
+    }
+}
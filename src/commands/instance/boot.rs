@@ -0,0 +1,263 @@
+//! How long an instance took to boot, phase by phase, read from the `state`
+//! frames in its log history.
+//!
+//! Shown after `instance run --wait` and after a `rollout` brings up new
+//! replicas, e.g. "booted in 42s: queued 1.2s → pulling 35s → starting 5.8s".
+//! Each phase lasts from its state frame to the next; the last state is where
+//! the boot ended up, so it has no duration. When pulling the image takes most
+//! of the time, a warning points at what makes pulls faster.
+
+use std::time::Duration;
+
+use anyhow::{Result, bail};
+use console::style;
+use unisrv_api::ApiClient;
+use unisrv_api::models::LogMessage;
+use uuid::Uuid;
+
+use super::list::is_active;
+use crate::commands::up::apply::{Poll, PollOutcome, Waiter, poll_until};
+use crate::commands::up::ready::format_duration;
+use crate::progress::{Icon, Progress, Tone};
+
+/// How long `instance run --wait` waits for the instance to be running.
+pub const BOOT_TIMEOUT: Duration = Duration::from_secs(300);
+/// How often the instance is checked while it boots.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Pulls shorter than this aren't worth a warning, whatever their share.
+const SLOW_PULL: Duration = Duration::from_secs(5);
+
+/// One state the instance was in, and for how long.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Phase {
+    pub state: String,
+    pub took: Duration,
+}
+
+/// The phases `history`'s state frames describe, in order. A state repeated
+/// in consecutive frames is one phase.
+pub fn phases(history: &[LogMessage]) -> Vec<Phase> {
+    let mut transitions: Vec<(&str, u64)> = Vec::new();
+    let mut frames: Vec<&LogMessage> = history.iter().filter(|m| m.log_type == "state").collect();
+    frames.sort_by_key(|m| m.timestamp_ms);
+    for frame in frames {
+        let Some(state) = frame.state.as_deref().filter(|s| !s.is_empty()) else {
+            continue;
+        };
+        if transitions.last().is_none_or(|(last, _)| *last != state) {
+            transitions.push((state, frame.timestamp_ms));
+        }
+    }
+    transitions
+        .windows(2)
+        .map(|pair| Phase {
+            state: pair[0].0.to_string(),
+            took: Duration::from_millis(pair[1].1.saturating_sub(pair[0].1)),
+        })
+        .collect()
+}
+
+/// "booted in 42s: queued 1.2s → pulling 35s → starting 5.8s", or `None`
+/// with no phases to show.
+pub fn render(phases: &[Phase]) -> Option<String> {
+    if phases.is_empty() {
+        return None;
+    }
+    let total: Duration = phases.iter().map(|p| p.took).sum();
+    let steps: Vec<String> = phases
+        .iter()
+        .map(|p| format!("{} {}", p.state, took(p.took)))
+        .collect();
+    Some(format!("booted in {}: {}", took(total), steps.join(" → ")))
+}
+
+/// A warning when pulling the image took most of the boot.
+pub fn pull_warning(phases: &[Phase]) -> Option<String> {
+    let total: Duration = phases.iter().map(|p| p.took).sum();
+    let pulling: Duration = phases
+        .iter()
+        .filter(|p| p.state.contains("pull"))
+        .map(|p| p.took)
+        .sum();
+    if pulling < SLOW_PULL || pulling * 2 <= total {
+        return None;
+    }
+    Some(format!(
+        "pulling the image took {} of {}: a smaller image boots faster, and one \
+         pinned by digest (image@sha256:…) lets nodes reuse layers they already have",
+        took(pulling),
+        took(total)
+    ))
+}
+
+/// Seconds to a tenth under 10s, where the phases that matter are short.
+fn took(duration: Duration) -> String {
+    if duration < Duration::from_secs(10) {
+        format!("{:.1}s", duration.as_secs_f64())
+    } else {
+        format_duration(duration)
+    }
+}
+
+/// Print `instance_id`'s boot breakdown to stderr. Best-effort: without its
+/// history there's simply nothing to show.
+pub async fn report(client: &dyn ApiClient, env_id: Uuid, instance_id: Uuid) {
+    let history = match client.get_instance_logs(env_id, instance_id).await {
+        Ok(history) => history,
+        Err(e) => {
+            tracing::debug!("no boot breakdown for {instance_id}: {e}");
+            return;
+        }
+    };
+    let phases = phases(&history);
+    if let Some(line) = render(&phases) {
+        eprintln!("{}", style(line).dim());
+    }
+    if let Some(warning) = pull_warning(&phases) {
+        eprintln!("{} {warning}", style("!").yellow().bold());
+    }
+}
+
+/// Wait for `instance_id` to be running, then [`report`] how its boot went.
+/// Fails if it stops instead, or isn't running within [`BOOT_TIMEOUT`].
+pub async fn wait(
+    client: &dyn ApiClient,
+    env_id: Uuid,
+    instance_id: Uuid,
+    waiter: &dyn Waiter,
+    progress: &dyn Progress,
+) -> Result<()> {
+    let step = progress.step(Icon::Instance, "Waiting for the instance to boot");
+    let max_rounds = BOOT_TIMEOUT.as_secs().div_ceil(POLL_INTERVAL.as_secs()) as usize + 1;
+    let outcome = poll_until(waiter, POLL_INTERVAL, max_rounds, &step, async || {
+        let instance = client
+            .get_instance(env_id, instance_id, false, false)
+            .await?;
+        let state = instance.state.0;
+        if state == "running" {
+            return Ok(Poll::Done);
+        }
+        if !is_active(&state) {
+            let reason = instance
+                .exit_reason
+                .map(|r| format!(": {r}"))
+                .unwrap_or_default();
+            bail!("the instance is {state} instead of running{reason}");
+        }
+        Ok(Poll::Pending(format!("Instance {state}")))
+    })
+    .await?;
+    if outcome == PollOutcome::TimedOut {
+        step.finish(Tone::Warn, "instance not running yet");
+        bail!(
+            "the instance wasn't running after {}",
+            format_duration(BOOT_TIMEOUT)
+        );
+    }
+    step.finish(Tone::Add, "instance running");
+    report(client, env_id, instance_id).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::up::apply::NoSleep;
+    use crate::progress::SilentProgress;
+    use chrono::NaiveDateTime;
+    use unisrv_api::models::{InstanceDetailResponse, InstanceState};
+    use unisrv_api::test_support::MockApiClient;
+
+    fn instance(state: &str, exit_reason: Option<&str>) -> InstanceDetailResponse {
+        InstanceDetailResponse {
+            id: Uuid::nil(),
+            name: None,
+            node_id: Uuid::nil(),
+            state: InstanceState(state.into()),
+            exit_code: None,
+            exit_reason: exit_reason.map(String::from),
+            configuration: serde_json::Value::Null,
+            created_at: NaiveDateTime::default(),
+            updated_at: NaiveDateTime::default(),
+            network_id: None,
+            network_ip: None,
+            deployment: None,
+            service_targets: None,
+            proxied_ports: None,
+            resources: None,
+            annotations: None,
+        }
+    }
+
+    fn state(state: &str, at_ms: u64) -> LogMessage {
+        LogMessage {
+            log_type: "state".into(),
+            timestamp_ms: at_ms,
+            state: Some(state.into()),
+            message: None,
+        }
+    }
+
+    fn stdout(at_ms: u64) -> LogMessage {
+        LogMessage {
+            log_type: "stdout".into(),
+            timestamp_ms: at_ms,
+            state: None,
+            message: Some("listening".into()),
+        }
+    }
+
+    #[test]
+    fn phases_last_from_one_state_frame_to_the_next() {
+        let history = [
+            state("queued", 0),
+            state("pulling", 1_200),
+            state("pulling", 20_000),
+            stdout(30_000),
+            state("starting", 36_200),
+            state("running", 42_000),
+        ];
+        let phases = phases(&history);
+        assert_eq!(
+            render(&phases).unwrap(),
+            "booted in 42s: queued 1.2s → pulling 35s → starting 5.8s"
+        );
+        assert!(pull_warning(&phases).unwrap().contains("took 35s of 42s"));
+
+        assert!(render(&super::phases(&[state("running", 0)])).is_none());
+    }
+
+    #[test]
+    fn a_quick_or_minor_pull_is_not_warned_about() {
+        let quick = phases(&[state("pulling", 0), state("running", 3_000)]);
+        assert!(pull_warning(&quick).is_none());
+        let minor = phases(&[
+            state("pulling", 0),
+            state("starting", 6_000),
+            state("running", 20_000),
+        ]);
+        assert!(pull_warning(&minor).is_none());
+    }
+
+    #[tokio::test]
+    async fn waits_for_running_and_fails_on_an_instance_that_stops() {
+        let mock = MockApiClient::logged_in()
+            .push_get_instance(Ok(instance("provisioning", None)))
+            .push_get_instance(Ok(instance("running", None)))
+            .push_instance_logs(Ok(vec![state("queued", 0), state("running", 900)]));
+        wait(&mock, Uuid::nil(), Uuid::nil(), &NoSleep, &SilentProgress)
+            .await
+            .unwrap();
+        assert_eq!(mock.calls.lock().unwrap().get_instance_logs_calls.len(), 1);
+
+        let mock = MockApiClient::logged_in()
+            .push_get_instance(Ok(instance("stopped", Some("image not found"))));
+        let err = wait(&mock, Uuid::nil(), Uuid::nil(), &NoSleep, &SilentProgress)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the instance is stopped instead of running: image not found"
+        );
+    }
+}
@@ -0,0 +1,174 @@
+//! `unisrv instance connection-string <ref> [--port N]` — the public
+//! `host:port` of an instance's TCP proxies, to hand to a database client or
+//! a teammate.
+//!
+//! Otherwise the address is only a line of `instance show`. This prints just
+//! the address on stdout, so `$(unisrv instance connection-string db)` drops
+//! it into a script; an example client invocation for a well-known port
+//! (Postgres, MySQL, Redis, …) goes to stderr.
+
+use anyhow::{Result, bail};
+use serde::Serialize;
+use unisrv_api::ApiClient;
+use unisrv_api::models::InstanceDetailResponse;
+
+use super::resolve::lookup_instance;
+use crate::commands::output::Output;
+use crate::commands::up::plan::ResolvedEnvironment;
+
+/// One proxied port, as printed and as `--json` emits it.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ConnectionString {
+    /// The port inside the instance.
+    pub port: u16,
+    /// Where clients connect, `host:port`.
+    pub address: String,
+    /// A client command for a well-known port.
+    pub example: Option<String>,
+}
+
+pub async fn connection_string(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    reference: &str,
+    port: Option<u16>,
+    output: &Output,
+) -> Result<()> {
+    let instance = lookup_instance(client, env.id, reference).await?;
+    let detail = client
+        .get_instance(env.id, instance.id, false, true)
+        .await?;
+    let strings = connection_strings(&detail, port)?;
+
+    match output {
+        Output::Json | Output::Jq(_) => output.print_json(&strings),
+        Output::Template(template) => {
+            print!("{}", template.render_all(&strings)?);
+            Ok(())
+        }
+        Output::Table => {
+            for string in &strings {
+                println!("{}", string.address);
+                if let Some(example) = &string.example {
+                    eprintln!("{}", console::style(format!("  {example}")).dim());
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// The instance's proxied ports, or just `port`.
+fn connection_strings(
+    detail: &InstanceDetailResponse,
+    port: Option<u16>,
+) -> Result<Vec<ConnectionString>> {
+    let name = detail.name.clone().unwrap_or_else(|| detail.id.to_string());
+    let proxied = detail.proxied_ports.as_deref().unwrap_or_default();
+    if proxied.is_empty() {
+        bail!("{name} has no TCP proxy, so nothing outside its network can connect to it");
+    }
+    let strings: Vec<ConnectionString> = proxied
+        .iter()
+        .filter(|p| port.is_none_or(|port| p.port == port))
+        .map(|p| ConnectionString {
+            port: p.port,
+            address: p.external_address.clone(),
+            example: example(p.port, &p.external_address),
+        })
+        .collect();
+    if strings.is_empty() {
+        let ports: Vec<String> = proxied.iter().map(|p| p.port.to_string()).collect();
+        bail!(
+            "{name} has no TCP proxy for port {}; it proxies {}",
+            port.unwrap_or_default(),
+            ports.join(", ")
+        );
+    }
+    Ok(strings)
+}
+
+/// A client command for what usually listens on `port`, connecting to
+/// `address`.
+fn example(port: u16, address: &str) -> Option<String> {
+    let (host, external) = address.rsplit_once(':')?;
+    let example = match port {
+        5432 => format!("psql \"postgresql://USER@{host}:{external}/DATABASE\""),
+        3306 => format!("mysql -h {host} -P {external} -u USER -p"),
+        6379 => format!("redis-cli -h {host} -p {external}"),
+        27017 => format!("mongosh \"mongodb://{host}:{external}\""),
+        22 => format!("ssh -p {external} USER@{host}"),
+        _ => format!("nc {host} {external}"),
+    };
+    Some(example)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+    use unisrv_api::models::{InstanceState, ProxiedPortInfo};
+    use uuid::Uuid;
+
+    fn detail(ports: &[(u16, &str)]) -> InstanceDetailResponse {
+        InstanceDetailResponse {
+            id: Uuid::nil(),
+            name: Some("db".into()),
+            node_id: Uuid::nil(),
+            state: InstanceState("running".into()),
+            exit_code: None,
+            exit_reason: None,
+            configuration: serde_json::json!({}),
+            created_at: NaiveDateTime::default(),
+            updated_at: NaiveDateTime::default(),
+            network_id: None,
+            network_ip: None,
+            deployment: None,
+            service_targets: None,
+            proxied_ports: Some(
+                ports
+                    .iter()
+                    .map(|(port, address)| ProxiedPortInfo {
+                        id: Uuid::new_v4(),
+                        port: *port,
+                        external_address: address.to_string(),
+                        created_at: NaiveDateTime::default(),
+                    })
+                    .collect(),
+            ),
+            resources: None,
+            annotations: None,
+        }
+    }
+
+    #[test]
+    fn prints_the_proxy_with_a_client_for_its_port() {
+        let db = detail(&[
+            (5432, "tcp.eu.unisrv.dev:31544"),
+            (9187, "tcp.eu.unisrv.dev:31545"),
+        ]);
+        let all = connection_strings(&db, None).unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(
+            all[0].example.as_deref(),
+            Some("psql \"postgresql://USER@tcp.eu.unisrv.dev:31544/DATABASE\"")
+        );
+        assert_eq!(
+            all[1].example.as_deref(),
+            Some("nc tcp.eu.unisrv.dev 31545")
+        );
+
+        let one = connection_strings(&db, Some(9187)).unwrap();
+        assert_eq!(one[0].address, "tcp.eu.unisrv.dev:31545");
+    }
+
+    #[test]
+    fn says_what_is_proxied_when_nothing_matches() {
+        let db = detail(&[(5432, "tcp.eu.unisrv.dev:31544")]);
+        let err = connection_strings(&db, Some(6379)).unwrap_err();
+        assert!(err.to_string().contains("it proxies 5432"), "{err}");
+
+        let err = connection_strings(&detail(&[]), None).unwrap_err();
+        assert!(err.to_string().contains("db has no TCP proxy"), "{err}");
+    }
+}
@@ -0,0 +1,823 @@
+//! `unisrv instance run <image> [--network [ip@]net] [-- args...]` — start a
+//! one-off instance outside of `unisrv up`, e.g. a debugging shell or a
+//! migration job.
+//!
+//! A `--network` address is validated against the network's CIDR and the
+//! addresses its instances already hold before anything is provisioned; with
+//! no address, the lowest free one is picked, retrying past addresses lost to
+//! a concurrent run. `--ip-from-pool` sidesteps the race by letting the server
+//! allocate, where the backend supports it.
+//!
+//! `--hostname` and `--metadata key=value` shape what the guest sees of
+//! itself: the instance can `GET` [`GUEST_METADATA_URL`] for its id, name,
+//! hostname, network addresses and that metadata, as a [`GuestMetadata`]
+//! document, without any of it being threaded through environment variables.
+//!
+//! `--init-image` and `--sidecar` add containers to the same VM: init
+//! containers run to completion, in order, before the main one starts (a
+//! migration step); sidecars run alongside it (a log shipper). Both take
+//! `IMAGE[:ARGS]` — see [`parse_container`].
+//!
+//! `--platform linux/arm64` runs a multi-platform image's ARM build rather
+//! than the server's `linux/amd64` default — see [`platform`](super::platform).
+//!
+//! Images must be from a registry `allowed_registries` allows — see
+//! [`policy`](crate::commands::registry::policy). Every image is looked up in its registry first — see
+//! [`verify`](super::verify). `--skip-image-verify` starts the instance
+//! without that when the registry is down, with a warning, and records the
+//! skip on the instance. `--verify-signature` goes further and requires a
+//! cosign signature on each image — see [`signature`](super::signature).
+//! `--wait` waits for the instance to be running and shows how long each boot
+//! phase took — see [`boot`](super::boot).
+//!
+//! The account's default environment variables (`unisrv env set-default`)
+//! go under the `-e` ones, unless `--no-default-env` leaves them out.
+//!
+//! [`GuestMetadata`]: unisrv_api::models::GuestMetadata
+
+use std::collections::BTreeMap;
+use std::net::Ipv4Addr;
+
+use anyhow::{Context, Result, bail};
+use unisrv_api::models::{
+    AuxContainer, GUEST_METADATA_URL, InstanceConfiguration, InstanceNetworkConfig,
+    InstanceProvisionRequest, InstanceProvisionResponse,
+};
+use unisrv_api::{ApiClient, ApiError};
+use uuid::Uuid;
+
+use super::boot;
+use super::platform::{self, Platform};
+use super::signature::{self, SIGNATURE_ANNOTATION, Signer, SystemCosign};
+use super::sizing::SizingFlags;
+use super::verify;
+use crate::commands::networks::{self, NetworkSpec, NetworkUsage};
+use crate::commands::registry::policy;
+use crate::commands::up::apply::RealWaiter;
+use crate::commands::up::lock::Registries;
+use crate::commands::up::plan::ResolvedEnvironment;
+use crate::default_env::AccountDefaults;
+use crate::progress::SpinnerProgress;
+
+/// What to run, as given on the command line.
+pub struct LaunchOptions {
+    pub image: String,
+    pub name: Option<String>,
+    pub env_vars: BTreeMap<String, String>,
+    pub args: Vec<String>,
+    pub network: Option<NetworkSpec>,
+    /// Have the server allocate the network address.
+    pub ip_from_pool: bool,
+    pub sizing: SizingFlags,
+    pub hostname: Option<String>,
+    pub metadata: BTreeMap<String, String>,
+    pub init_containers: Vec<AuxContainer>,
+    pub sidecars: Vec<AuxContainer>,
+    /// From `--platform`, or the configured default.
+    pub platform: Option<Platform>,
+    /// From `--region`, or the configured default.
+    pub region: String,
+    /// Start without checking the images against their registries.
+    pub skip_image_verify: bool,
+    /// From `--verify-signature`: whose cosign signature every image needs.
+    pub signer: Option<Signer>,
+    /// Wait for it to be running and show how its boot went.
+    pub wait: bool,
+    /// The account's default environment variables, under `env_vars`; `None`
+    /// with `--no-default-env`.
+    pub default_env: Option<AccountDefaults>,
+}
+
+/// Where the instance ended up on its network.
+enum Joined {
+    Address(Ipv4Addr),
+    Pool,
+}
+
+pub async fn launch(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    opts: LaunchOptions,
+) -> Result<()> {
+    let wait = opts.wait;
+    let id = start(client, env, opts).await?;
+    if wait {
+        boot::wait(client, env.id, id, &RealWaiter, &SpinnerProgress::new()).await?;
+    }
+    Ok(())
+}
+
+/// Verify and start the instance, returning its id.
+async fn start(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    mut opts: LaunchOptions,
+) -> Result<Uuid> {
+    policy::enforce(images(&opts))?;
+    if opts.skip_image_verify {
+        eprintln!(
+            "WARNING: --skip-image-verify: starting without checking {} against its registry. \
+             If the image is wrong the instance won't start.",
+            images(&opts).join(", ")
+        );
+        return provision(client, env, opts, Some(verify::skipped())).await;
+    }
+    let digests = verify::verify_images(&Registries { client }, &images(&opts)).await?;
+    if let Some(platform) = &opts.platform {
+        platform::preflight(client, &opts.image, platform).await?;
+    }
+    let Some(signer) = opts.signer.clone() else {
+        return provision(client, env, opts, None).await;
+    };
+    signature::verify_signatures(&SystemCosign, &signer, &images(&opts), &digests)?;
+    // Run what was verified, even if a tag has moved since.
+    for (image, digest) in images_mut(&mut opts).into_iter().zip(&digests) {
+        *image = signature::by_digest(image, digest);
+    }
+    let annotations = BTreeMap::from([(SIGNATURE_ANNOTATION.to_string(), signer.identity)]);
+    provision(client, env, opts, Some(annotations)).await
+}
+
+/// The main image, then the init containers' and sidecars'.
+fn images(opts: &LaunchOptions) -> Vec<&str> {
+    std::iter::once(opts.image.as_str())
+        .chain(
+            opts.init_containers
+                .iter()
+                .chain(&opts.sidecars)
+                .map(|c| c.image.as_str()),
+        )
+        .collect()
+}
+
+fn images_mut(opts: &mut LaunchOptions) -> Vec<&mut String> {
+    std::iter::once(&mut opts.image)
+        .chain(
+            opts.init_containers
+                .iter_mut()
+                .chain(&mut opts.sidecars)
+                .map(|c| &mut c.image),
+        )
+        .collect()
+}
+
+/// Start the instance, its images already verified or deliberately not.
+async fn provision(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    opts: LaunchOptions,
+    annotations: Option<BTreeMap<String, String>>,
+) -> Result<Uuid> {
+    let sizing = opts.sizing.resolve()?;
+    if let Some(hostname) = &opts.hostname {
+        validate_hostname(hostname)?;
+    }
+    let request = InstanceProvisionRequest {
+        name: opts.name.clone(),
+        region: opts.region.clone(),
+        vcpu_ratio: sizing.vcpu_ratio,
+        vcpu_count: sizing.vcpu_count,
+        memory_mb: sizing.memory_mb,
+        memory_request_mb: sizing.memory_request_mb,
+        configuration: InstanceConfiguration {
+            container_image: opts.image.clone(),
+            args: (!opts.args.is_empty()).then(|| opts.args.clone()),
+            env: match &opts.default_env {
+                Some(defaults) => defaults.under(&env.project, Some(&opts.env_vars)),
+                None => (!opts.env_vars.is_empty()).then(|| opts.env_vars.clone()),
+            },
+            hostname: opts.hostname.clone(),
+            metadata: (!opts.metadata.is_empty()).then(|| opts.metadata.clone()),
+            init_containers: (!opts.init_containers.is_empty())
+                .then(|| opts.init_containers.clone()),
+            sidecars: (!opts.sidecars.is_empty()).then(|| opts.sidecars.clone()),
+            platform: opts.platform.as_ref().map(ToString::to_string),
+        },
+        container_registry_token: None,
+        network: None,
+        annotations,
+    };
+
+    let (created, network) = match &opts.network {
+        None => {
+            let created = client
+                .provision_instance(env.id, request)
+                .await
+                .with_context(|| format!("failed to start {}", opts.image))?;
+            (created, None)
+        }
+        Some(spec) => {
+            if opts.ip_from_pool && spec.ip.is_some() {
+                bail!("--ip-from-pool can't be combined with an explicit address in --network");
+            }
+            let mut usage = networks::resolve(client, env.id, &spec.network).await?;
+            let (created, joined) =
+                join_network(client, env, &opts, request, &mut usage, spec.ip).await?;
+            (created, Some((usage.name, joined)))
+        }
+    };
+
+    println!("\u{2713} Started {} ({}).", opts.image, created.id);
+    match network {
+        Some((name, Joined::Address(ip))) => println!("  network {name}: {ip}"),
+        Some((name, Joined::Pool)) => println!("  network {name}: address allocated by the server"),
+        None => {}
+    }
+    if let Some(hostname) = &opts.hostname {
+        println!("  hostname: {hostname}");
+    }
+    if !opts.metadata.is_empty() {
+        println!("  metadata: {GUEST_METADATA_URL} (from inside the instance)");
+    }
+    Ok(created.id)
+}
+
+/// Parse a `--metadata key=value`. Keys are looser than environment variable
+/// names, since guests look them up in JSON: letters, digits, `.`, `_`, `-`
+/// and `/`, so namespaced keys like `acme.com/team` work.
+pub fn parse_metadata(s: &str) -> Result<(String, String)> {
+    let Some((key, value)) = s.split_once('=') else {
+        bail!("invalid metadata {s:?}: expected KEY=VALUE");
+    };
+    let key = key.trim();
+    if key.is_empty()
+        || !key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '/'))
+    {
+        bail!("invalid metadata key {key:?}: use letters, digits, '.', '_', '-' or '/'");
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// Parse an `--init-image` / `--sidecar` value, `IMAGE[:ARGS]`. The image
+/// keeps its own colons — a registry port, a tag, a digest — so arguments
+/// start at the colon after those; an untagged image passing arguments must
+/// therefore name its tag (`busybox:latest:sleep 5`). Arguments are split on
+/// whitespace, without shell quoting.
+pub fn parse_container(s: &str) -> Result<AuxContainer> {
+    let s = s.trim();
+    // `host:5000/…`: the only colon before a `/` is a registry port.
+    let path_start = match (s.find(':'), s.find('/')) {
+        (Some(colon), Some(slash))
+            if colon < slash && s[colon + 1..slash].bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            slash + 1
+        }
+        _ => 0,
+    };
+    let mut end = path_start
+        + s[path_start..]
+            .find([':', '@'])
+            .unwrap_or(s.len() - path_start);
+    if end == path_start {
+        bail!("invalid container {s:?}: expected IMAGE[:ARGS]");
+    }
+    let mut tag = None;
+    if s[end..].starts_with(':') {
+        let tag_len = s[end + 1..].find([':', '@']).unwrap_or(s.len() - end - 1);
+        tag = Some(&s[end + 1..end + 1 + tag_len]);
+        end += 1 + tag_len;
+    }
+    if s[end..].starts_with('@') {
+        // `@sha256:<hex>`: the digest's own colon is part of the image.
+        let digest = &s[end..];
+        let hex_start = digest.find(':').map_or(digest.len(), |c| c + 1);
+        end += hex_start
+            + digest[hex_start..]
+                .find(':')
+                .unwrap_or(digest.len() - hex_start);
+    }
+    let image = &s[..end];
+    if tag == Some("") {
+        bail!("invalid container {s:?}: empty image tag");
+    }
+    if let Some(tag) = tag
+        && tag.contains(char::is_whitespace)
+    {
+        bail!(
+            "invalid container {s:?}: {tag:?} isn't an image tag; to pass arguments, \
+             tag the image first (e.g. {}:latest:{tag})",
+            &s[..end - tag.len() - 1]
+        );
+    }
+    let args: Vec<String> = s[end..]
+        .strip_prefix(':')
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(str::to_string)
+        .collect();
+    Ok(AuxContainer {
+        image: image.to_string(),
+        args: (!args.is_empty()).then_some(args),
+    })
+}
+
+/// A hostname per RFC 1123: dot-separated labels of letters, digits and
+/// inner hyphens, at most 63 characters each and 253 in all.
+fn validate_hostname(hostname: &str) -> Result<()> {
+    let label_ok = |label: &str| {
+        (1..=63).contains(&label.len())
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+    };
+    if hostname.len() > 253 || !hostname.split('.').all(label_ok) {
+        bail!(
+            "invalid hostname {hostname:?}: use letters, digits and hyphens \
+             (not leading or trailing), in dot-separated parts of at most 63 characters"
+        );
+    }
+    Ok(())
+}
+
+/// Provision `request` on `usage`'s network: from the server's pool if asked
+/// (and supported), otherwise at `requested` or the next free address,
+/// retrying past addresses lost to concurrent runs.
+async fn join_network(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    opts: &LaunchOptions,
+    mut request: InstanceProvisionRequest,
+    usage: &mut NetworkUsage,
+    requested: Option<Ipv4Addr>,
+) -> Result<(InstanceProvisionResponse, Joined)> {
+    let usage_id = usage.id;
+    if opts.ip_from_pool {
+        request.network = Some(InstanceNetworkConfig {
+            network_id: usage.id,
+            instance_ip: None,
+        });
+        match client.provision_instance(env.id, request.clone()).await {
+            Ok(created) => return Ok((created, Joined::Pool)),
+            Err(e) if pool_unsupported(&e) => {
+                eprintln!(
+                    "{}",
+                    console::style(
+                        "! the server can't allocate network addresses; picking one locally"
+                    )
+                    .yellow()
+                );
+            }
+            Err(e) => return Err(e).with_context(|| format!("failed to start {}", opts.image)),
+        }
+    }
+
+    let (created, ip) = networks::claim_address(usage, requested, |ip| {
+        let mut request = request.clone();
+        request.network = Some(InstanceNetworkConfig {
+            network_id: usage_id,
+            instance_ip: Some(ip.to_string()),
+        });
+        async move {
+            client
+                .provision_instance(env.id, request)
+                .await
+                .with_context(|| format!("failed to start {}", opts.image))
+        }
+    })
+    .await?;
+    Ok((created, Joined::Address(ip)))
+}
+
+/// Whether `e` says the server can't allocate from the pool: a backend
+/// without it requires `instance_ip` (a 400 naming the field) or doesn't take
+/// the request at all. Any other 400 is a real failure to report.
+fn pool_unsupported(e: &ApiError) -> bool {
+    match e {
+        ApiError::Server {
+            status: 405 | 501, ..
+        } => true,
+        ApiError::Server {
+            status: 400,
+            reason,
+        } => reason.contains("instance_ip"),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::up::plan::test_env;
+    use chrono::NaiveDateTime;
+    use unisrv_api::models::{
+        InstanceInfo, InstanceProvisionResponse, IpReservation, IpReservationListResponse,
+        NetworkListItem, NetworkListResponse, NetworkResponse,
+    };
+    use unisrv_api::test_support::MockApiClient;
+    use uuid::Uuid;
+
+    fn opts(network: Option<&str>) -> LaunchOptions {
+        LaunchOptions {
+            image: "alpine:3".into(),
+            name: Some("debug".into()),
+            env_vars: BTreeMap::new(),
+            args: vec!["sleep".into(), "3600".into()],
+            network: network.map(|n| NetworkSpec::parse(n).unwrap()),
+            ip_from_pool: false,
+            sizing: SizingFlags::default(),
+            hostname: None,
+            metadata: BTreeMap::new(),
+            init_containers: Vec::new(),
+            sidecars: Vec::new(),
+            platform: None,
+            region: "dev".into(),
+            skip_image_verify: false,
+            signer: None,
+            wait: false,
+            default_env: None,
+        }
+    }
+
+    fn backend(id: Uuid, used: &[&str]) -> MockApiClient {
+        MockApiClient::logged_in()
+            .with_list_networks(Ok(NetworkListResponse {
+                networks: vec![NetworkListItem {
+                    id,
+                    name: "backend".into(),
+                    ipv4_cidr: "10.0.0.0/24".into(),
+                    instance_count: None,
+                    managed_by: None,
+                }],
+            }))
+            .push_get_network(Ok(NetworkResponse {
+                id,
+                environment_id: Uuid::nil(),
+                name: "backend".into(),
+                ipv4_cidr: "10.0.0.0/24".into(),
+                created_at: NaiveDateTime::default(),
+                instances: used
+                    .iter()
+                    .map(|ip| InstanceInfo {
+                        id: Uuid::new_v4(),
+                        internal_ip: ip.to_string(),
+                    })
+                    .collect(),
+            }))
+    }
+
+    #[tokio::test]
+    async fn joins_the_network_at_the_requested_address() {
+        let net = Uuid::new_v4();
+        let mock = backend(net, &["10.0.0.1"])
+            .push_provision_instance(Ok(InstanceProvisionResponse { id: Uuid::new_v4() }));
+
+        provision(&mock, &test_env(), opts(Some("10.0.0.9@backend")), None)
+            .await
+            .unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let (_, req) = &calls.provision_instance_calls[0];
+        assert_eq!(
+            req.network,
+            Some(InstanceNetworkConfig {
+                network_id: net,
+                instance_ip: Some("10.0.0.9".into()),
+            })
+        );
+        assert_eq!(
+            req.configuration.args,
+            Some(vec!["sleep".into(), "3600".into()])
+        );
+    }
+
+    #[tokio::test]
+    async fn hostname_and_metadata_reach_the_guest_configuration() {
+        let mock = MockApiClient::logged_in()
+            .push_provision_instance(Ok(InstanceProvisionResponse { id: Uuid::new_v4() }));
+        let opts = LaunchOptions {
+            hostname: Some("worker-1".into()),
+            metadata: BTreeMap::from([parse_metadata("acme.com/team=data").unwrap()]),
+            ..opts(None)
+        };
+
+        provision(&mock, &test_env(), opts, None).await.unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let config = &calls.provision_instance_calls[0].1.configuration;
+        assert_eq!(config.hostname.as_deref(), Some("worker-1"));
+        assert_eq!(
+            config.metadata,
+            Some(BTreeMap::from([("acme.com/team".into(), "data".into())]))
+        );
+    }
+
+    #[tokio::test]
+    async fn env_vars_are_set_over_the_accounts_defaults() {
+        let mock = MockApiClient::logged_in()
+            .push_provision_instance(Ok(InstanceProvisionResponse { id: Uuid::new_v4() }));
+        let defaults = AccountDefaults {
+            vars: BTreeMap::from([("LOG_FORMAT".into(), "json".into())]),
+            projects: BTreeMap::from([(
+                "demo".into(),
+                BTreeMap::from([("TZ".into(), "UTC".into())]),
+            )]),
+        };
+        let opts = LaunchOptions {
+            env_vars: BTreeMap::from([("LOG_FORMAT".into(), "text".into())]),
+            default_env: Some(defaults),
+            ..opts(None)
+        };
+
+        provision(&mock, &test_env(), opts, None).await.unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        assert_eq!(
+            calls.provision_instance_calls[0].1.configuration.env,
+            Some(BTreeMap::from([
+                ("LOG_FORMAT".into(), "text".into()),
+                ("TZ".into(), "UTC".into()),
+            ]))
+        );
+    }
+
+    #[tokio::test]
+    async fn skipping_image_verify_is_recorded_on_the_instance() {
+        let mock = MockApiClient::logged_in()
+            .push_provision_instance(Ok(InstanceProvisionResponse { id: Uuid::new_v4() }));
+        let opts = LaunchOptions {
+            skip_image_verify: true,
+            ..opts(None)
+        };
+
+        // Nothing is asked of a registry, so this runs offline.
+        launch(&mock, &test_env(), opts).await.unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        assert_eq!(
+            calls.provision_instance_calls[0].1.annotations,
+            Some(verify::skipped())
+        );
+    }
+
+    #[tokio::test]
+    async fn requests_and_limits_reach_the_provision_request() {
+        let mock = MockApiClient::logged_in()
+            .push_provision_instance(Ok(InstanceProvisionResponse { id: Uuid::new_v4() }));
+        let opts = LaunchOptions {
+            sizing: SizingFlags {
+                cpu_limit: Some(4),
+                cpu_request: Some(1.0),
+                memory_limit_mb: Some(2048),
+                memory_request_mb: Some(1024),
+            },
+            ..opts(None)
+        };
+
+        provision(&mock, &test_env(), opts, None).await.unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let req = &calls.provision_instance_calls[0].1;
+        assert_eq!((req.vcpu_count, req.vcpu_ratio), (4, 0.25));
+        assert_eq!((req.memory_mb, req.memory_request_mb), (2048, Some(1024)));
+    }
+
+    #[tokio::test]
+    async fn invalid_hostname_is_refused_before_provisioning() {
+        let mock = MockApiClient::logged_in();
+        for bad in ["-worker", "worker_1", "a..b", &"x".repeat(64)] {
+            let opts = LaunchOptions {
+                hostname: Some(bad.to_string()),
+                ..opts(None)
+            };
+            let err = provision(&mock, &test_env(), opts, None).await.unwrap_err();
+            assert!(
+                err.to_string().contains("invalid hostname"),
+                "{bad}: {err:#}"
+            );
+        }
+        assert!(
+            mock.calls
+                .lock()
+                .unwrap()
+                .provision_instance_calls
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn container_args_follow_the_image_reference() {
+        let parsed = |s| {
+            let c = parse_container(s).unwrap();
+            (c.image, c.args.unwrap_or_default())
+        };
+        assert_eq!(parsed("busybox"), ("busybox".into(), vec![]));
+        assert_eq!(parsed("alpine:3"), ("alpine:3".into(), vec![]));
+        assert_eq!(
+            parsed("fluent/fluent-bit:2.2:-c /etc/fb.conf"),
+            (
+                "fluent/fluent-bit:2.2".into(),
+                vec!["-c".into(), "/etc/fb.conf".into()]
+            )
+        );
+        assert_eq!(
+            parsed("registry.local:5000/tools/migrate:v4:up --all"),
+            (
+                "registry.local:5000/tools/migrate:v4".into(),
+                vec!["up".into(), "--all".into()]
+            )
+        );
+        assert_eq!(
+            parsed("app@sha256:abc123:serve"),
+            ("app@sha256:abc123".into(), vec!["serve".into()])
+        );
+        assert_eq!(
+            parsed("app:1@sha256:abc123"),
+            ("app:1@sha256:abc123".into(), vec![])
+        );
+    }
+
+    #[test]
+    fn untagged_image_with_arguments_is_explained() {
+        let err = parse_container("busybox:sleep 5").unwrap_err();
+        assert!(
+            err.to_string().contains("busybox:latest:sleep 5"),
+            "{err:#}"
+        );
+        assert!(parse_container(":x").is_err());
+    }
+
+    #[tokio::test]
+    async fn init_and_sidecar_containers_join_the_configuration() {
+        let mock = MockApiClient::logged_in()
+            .push_provision_instance(Ok(InstanceProvisionResponse { id: Uuid::new_v4() }));
+        let opts = LaunchOptions {
+            init_containers: vec![parse_container("myapp:1:migrate").unwrap()],
+            sidecars: vec![parse_container("fluent-bit:2").unwrap()],
+            ..opts(None)
+        };
+
+        provision(&mock, &test_env(), opts, None).await.unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let config = &calls.provision_instance_calls[0].1.configuration;
+        let init = config.init_containers.as_deref().unwrap();
+        assert_eq!(init[0].args, Some(vec!["migrate".to_string()]));
+        assert_eq!(config.sidecars.as_deref().unwrap()[0].image, "fluent-bit:2");
+    }
+
+    #[test]
+    fn metadata_keys_allow_namespaces_but_not_spaces() {
+        assert_eq!(
+            parse_metadata("tier=a=b").unwrap(),
+            ("tier".to_string(), "a=b".to_string())
+        );
+        assert!(parse_metadata("my key=x").is_err());
+        assert!(parse_metadata("=x").is_err());
+        assert!(parse_metadata("novalue").is_err());
+    }
+
+    #[tokio::test]
+    async fn auto_assignment_skips_reserved_addresses() {
+        let mock = backend(Uuid::new_v4(), &[])
+            .push_list_ip_reservations(Ok(IpReservationListResponse {
+                reservations: vec![IpReservation {
+                    id: Uuid::new_v4(),
+                    ip: "10.0.0.3".into(),
+                    name: Some("db-vip".into()),
+                    created_at: NaiveDateTime::default(),
+                }],
+            }))
+            .push_provision_instance(Ok(InstanceProvisionResponse { id: Uuid::new_v4() }));
+
+        provision(&mock, &test_env(), opts(Some("backend")), None)
+            .await
+            .unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let (_, req) = &calls.provision_instance_calls[0];
+        let ip = req.network.as_ref().unwrap().instance_ip.as_deref();
+        assert_eq!(ip, Some("10.0.0.4"));
+    }
+
+    #[tokio::test]
+    async fn taken_address_is_refused_before_provisioning() {
+        let mock = backend(Uuid::new_v4(), &["10.0.0.9"]);
+
+        let err = provision(&mock, &test_env(), opts(Some("10.0.0.9@backend")), None)
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains("already assigned"), "{err:#}");
+        assert!(
+            mock.calls
+                .lock()
+                .unwrap()
+                .provision_instance_calls
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_network_lists_the_known_ones() {
+        let mock = MockApiClient::logged_in().with_list_networks(Ok(NetworkListResponse {
+            networks: vec![NetworkListItem {
+                id: Uuid::new_v4(),
+                name: "backend".into(),
+                ipv4_cidr: "10.0.0.0/24".into(),
+                instance_count: None,
+                managed_by: None,
+            }],
+        }));
+
+        let err = provision(&mock, &test_env(), opts(Some("frontend")), None)
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains("(networks: backend)"), "{err}");
+    }
+
+    fn conflict() -> ApiError {
+        ApiError::Server {
+            status: 409,
+            reason: "address 10.0.0.3 is already in use".into(),
+        }
+    }
+
+    fn started() -> Result<InstanceProvisionResponse, ApiError> {
+        Ok(InstanceProvisionResponse { id: Uuid::new_v4() })
+    }
+
+    fn requested_ips(mock: &MockApiClient) -> Vec<Option<String>> {
+        mock.calls
+            .lock()
+            .unwrap()
+            .provision_instance_calls
+            .iter()
+            .map(|(_, req)| req.network.as_ref().unwrap().instance_ip.clone())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn lost_address_race_moves_to_the_next_candidate() {
+        let mock = backend(Uuid::new_v4(), &[])
+            .push_provision_instance(Err(conflict()))
+            .push_provision_instance(started());
+
+        provision(&mock, &test_env(), opts(Some("backend")), None)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            requested_ips(&mock),
+            vec![Some("10.0.0.3".into()), Some("10.0.0.4".into())]
+        );
+    }
+
+    #[tokio::test]
+    async fn explicit_address_lost_to_a_race_is_not_silently_replaced() {
+        let mock = backend(Uuid::new_v4(), &[]).push_provision_instance(Err(conflict()));
+
+        let err = provision(&mock, &test_env(), opts(Some("10.0.0.3@backend")), None)
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains("was taken"), "{err}");
+        assert_eq!(requested_ips(&mock).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn pool_allocation_omits_the_address() {
+        let mock = backend(Uuid::new_v4(), &[]).push_provision_instance(started());
+        let mut options = opts(Some("backend"));
+        options.ip_from_pool = true;
+
+        provision(&mock, &test_env(), options, None).await.unwrap();
+
+        assert_eq!(requested_ips(&mock), vec![None]);
+    }
+
+    #[tokio::test]
+    async fn pool_allocation_falls_back_when_unsupported() {
+        let mock = backend(Uuid::new_v4(), &[])
+            .push_provision_instance(Err(ApiError::Server {
+                status: 400,
+                reason: "instance_ip: missing field".into(),
+            }))
+            .push_provision_instance(started());
+        let mut options = opts(Some("backend"));
+        options.ip_from_pool = true;
+
+        provision(&mock, &test_env(), options, None).await.unwrap();
+
+        assert_eq!(requested_ips(&mock), vec![None, Some("10.0.0.3".into())]);
+    }
+
+    #[tokio::test]
+    async fn pool_allocation_returns_other_bad_requests_as_is() {
+        let mock = backend(Uuid::new_v4(), &[]).push_provision_instance(Err(ApiError::Server {
+            status: 400,
+            reason: "memory_mb: must be at least 128".into(),
+        }));
+        let mut options = opts(Some("backend"));
+        options.ip_from_pool = true;
+
+        let err = provision(&mock, &test_env(), options, None)
+            .await
+            .unwrap_err();
+
+        assert!(format!("{err:#}").contains("memory_mb"), "{err:#}");
+        assert_eq!(requested_ips(&mock), vec![None]);
+    }
+}
@@ -1,52 +1,101 @@
 //! `unisrv instance ls` — tabulate an environment's instances.
 
+use std::time::Duration;
+
 use anyhow::Result;
-use chrono::NaiveDateTime;
-use comfy_table::{Attribute, Cell, Color, ContentArrangement, Table, presets::UTF8_FULL};
+use chrono::{DateTime, Utc};
+use comfy_table::{Cell, Color};
 use unisrv_api::ApiClient;
 use unisrv_api::models::{InstanceListEntry, InstanceListResponse};
 
+use crate::commands::output::Output;
+use crate::commands::table::{Column, Row, TableOptions, draw_table, id_text, sort};
 use crate::commands::ui::{cell_with_color, colors_enabled, format_relative};
 use crate::commands::up::plan::ResolvedEnvironment;
+use crate::commands::watch::watch;
 
 /// List the instances of `env`. Hides stopped instances unless `all`; emits the
-/// (filtered) list as JSON when `json`, otherwise a human table.
+/// (filtered) list as JSON or through a `--format` template when asked,
+/// otherwise a human table laid out by `table`, redrawn every `watch_every`
+/// when given.
 pub async fn list(
     client: &dyn ApiClient,
     env: &ResolvedEnvironment,
     all: bool,
-    json: bool,
+    output: &Output,
+    table: &TableOptions,
+    watch_every: Option<Duration>,
 ) -> Result<()> {
-    let resp = client.list_instances(env.id).await?;
-    let shown = filter(resp.instances, all);
-
-    if json {
-        let payload = InstanceListResponse { instances: shown };
-        println!("{}", serde_json::to_string_pretty(&payload)?);
-        return Ok(());
+    if let Some(interval) = watch_every {
+        return watch(interval, async || {
+            let shown = fetch(client, env, all, table).await?;
+            view(&shown, env, all, table)
+        })
+        .await;
+    }
+    let shown = fetch(client, env, all, table).await?;
+    match output {
+        Output::Json | Output::Jq(_) => {
+            output.print_json(&InstanceListResponse { instances: shown })
+        }
+        Output::Template(template) => {
+            print!("{}", template.render_all(&shown)?);
+            Ok(())
+        }
+        Output::Table => {
+            print!("{}", view(&shown, env, all, table)?);
+            Ok(())
+        }
     }
+}
+
+/// The instances to list, filtered and sorted.
+async fn fetch(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    all: bool,
+    table: &TableOptions,
+) -> Result<Vec<InstanceListEntry>> {
+    let resp = client.list_instances(env.id).await?;
+    let mut shown = filter(resp.instances, all);
+    sort(&mut shown, table.sort, "instances")?;
+    Ok(shown)
+}
 
+/// The table, or a line saying why there's nothing in it.
+fn view(
+    shown: &[InstanceListEntry],
+    env: &ResolvedEnvironment,
+    all: bool,
+    table: &TableOptions,
+) -> Result<String> {
     if shown.is_empty() {
-        if all {
-            println!("No instances in environment {}.", env.name);
+        return Ok(if all {
+            format!("No instances in environment {}.\n", env.name)
         } else {
-            println!(
-                "No active instances in environment {} (pass -a to include stopped ones).",
+            format!(
+                "No active instances in environment {} (pass -a to include stopped ones).\n",
                 env.name
-            );
-        }
-        return Ok(());
+            )
+        });
+    }
+    let rendered = render_table(shown, chrono::Utc::now(), colors_enabled(), table)?;
+    Ok(format!("{rendered}\n"))
+}
+
+impl Row for InstanceListEntry {
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
     }
 
-    let use_color = colors_enabled();
-    let now = chrono::Utc::now().naive_utc();
-    println!("{}", render_table(&shown, now, use_color));
-    Ok(())
+    fn created(&self) -> Option<DateTime<Utc>> {
+        Some(self.created_at.and_utc())
+    }
 }
 
 /// States considered "live". Everything else (exited, failed, stopped, …) is
 /// hidden unless `--all` is given, mirroring `docker ps`.
-fn is_active(state: &str) -> bool {
+pub fn is_active(state: &str) -> bool {
     matches!(state, "running" | "provisioning")
 }
 
@@ -60,43 +109,58 @@ fn filter(instances: Vec<InstanceListEntry>, all: bool) -> Vec<InstanceListEntry
 }
 
 /// Render the instances as a bordered table. Pure so it can be asserted on
-/// without a terminal; colour is gated by the caller.
-fn render_table(instances: &[InstanceListEntry], now: NaiveDateTime, use_color: bool) -> String {
-    let mut table = Table::new();
-    table.load_preset(UTF8_FULL);
-    table.set_content_arrangement(ContentArrangement::Dynamic);
-    table.set_header(vec![
-        Cell::new("ID").add_attribute(Attribute::Bold),
-        Cell::new("NAME").add_attribute(Attribute::Bold),
-        Cell::new("IMAGE").add_attribute(Attribute::Bold),
-        Cell::new("STATE").add_attribute(Attribute::Bold),
-        Cell::new("DEPLOYMENT").add_attribute(Attribute::Bold),
-        Cell::new("CREATED").add_attribute(Attribute::Bold),
-    ]);
-
-    for instance in instances {
-        let short_id = instance.id.to_string()[..8].to_string();
-        let (name, name_color) = match instance.name.as_deref() {
-            Some(n) => (n.to_string(), None),
-            None => ("\u{2014}".to_string(), Some(Color::DarkGrey)),
-        };
-        let (state_text, state_color) = format_state(&instance.state.0);
-        let (deployment, deployment_color) = match &instance.deployment {
-            Some(d) => (d.name.clone(), None),
-            None => ("\u{2014}".to_string(), Some(Color::DarkGrey)),
-        };
-        let created = format_relative(instance.created_at, now);
-
-        table.add_row(vec![
-            Cell::new(short_id),
-            cell_with_color(name, name_color, use_color),
-            Cell::new(&instance.container_image),
-            cell_with_color(state_text, state_color, use_color),
-            cell_with_color(deployment, deployment_color, use_color),
-            Cell::new(created),
-        ]);
-    }
-    table.to_string()
+/// without a terminal; colour is gated by the caller. A RETAINED-UNTIL column
+/// is shown when any instance was stopped with `--keep`.
+fn render_table(
+    instances: &[InstanceListEntry],
+    now: DateTime<Utc>,
+    use_color: bool,
+    options: &TableOptions,
+) -> Result<String> {
+    let retained = instances.iter().any(|i| i.retained_until.is_some());
+    let dash = || cell_with_color("\u{2014}".to_string(), Some(Color::DarkGrey), use_color);
+    let columns = [
+        Column::new("id", "ID", |i: &InstanceListEntry| {
+            Cell::new(id_text(&i.id, options))
+        }),
+        Column::new("name", "NAME", |i: &InstanceListEntry| match &i.name {
+            Some(name) => cell_with_color(name.clone(), None, use_color),
+            None => dash(),
+        }),
+        Column::new("image", "IMAGE", |i: &InstanceListEntry| {
+            Cell::new(&i.container_image)
+        }),
+        Column::new("state", "STATE", |i: &InstanceListEntry| {
+            let (text, color) = format_state(&i.state.0);
+            cell_with_color(text, color, use_color)
+        }),
+        Column::new(
+            "deployment",
+            "DEPLOYMENT",
+            |i: &InstanceListEntry| match &i.deployment {
+                Some(d) => cell_with_color(d.name.clone(), None, use_color),
+                None => dash(),
+            },
+        ),
+        Column::new("created", "CREATED", |i: &InstanceListEntry| {
+            Cell::new(format_relative(i.created_at, now.naive_utc()))
+        }),
+        Column::new(
+            "retained-until",
+            "RETAINED-UNTIL",
+            |i: &InstanceListEntry| match i.retained_until {
+                Some(until) if until > now => Cell::new(format!(
+                    "{} ({})",
+                    until.format("%Y-%m-%d %H:%M UTC"),
+                    format_relative(until, now)
+                )),
+                Some(_) => cell_with_color("lapsed".into(), Some(Color::DarkGrey), use_color),
+                None => dash(),
+            },
+        )
+        .shown_if(retained),
+    ];
+    draw_table(instances, &columns, options)
 }
 
 /// State → (display, colour): live states green/yellow, terminal states dimmed,
@@ -116,20 +180,13 @@ fn format_state(state: &str) -> (String, Option<Color>) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::commands::up::plan::test_env;
+    use chrono::NaiveDateTime;
     use unisrv_api::ApiError;
     use unisrv_api::models::{DeploymentInfo, InstanceState};
     use unisrv_api::test_support::MockApiClient;
     use uuid::Uuid;
 
-    fn env() -> ResolvedEnvironment {
-        ResolvedEnvironment {
-            id: Uuid::new_v4(),
-            name: "prod".to_string(),
-            project: "demo".to_string(),
-            slug: "ab12".to_string(),
-        }
-    }
-
     fn instance(name: &str, state: &str) -> InstanceListEntry {
         InstanceListEntry {
             id: Uuid::new_v4(),
@@ -138,6 +195,7 @@ mod tests {
             container_image: "nginx:latest".to_string(),
             created_at: NaiveDateTime::default(),
             deployment: None,
+            retained_until: None,
         }
     }
 
@@ -165,7 +223,7 @@ mod tests {
 
     #[test]
     fn render_table_has_columns_and_marks_standalone_with_dash() {
-        let now = NaiveDateTime::default();
+        let now = DateTime::<Utc>::default();
         let mut deployed = instance("api-0", "running");
         deployed.deployment = Some(DeploymentInfo {
             id: Uuid::new_v4(),
@@ -173,7 +231,13 @@ mod tests {
         });
         let standalone = instance("scratch", "running");
 
-        let rendered = render_table(&[deployed, standalone], now, false);
+        let rendered = render_table(
+            &[deployed, standalone],
+            now,
+            false,
+            &TableOptions::default(),
+        )
+        .unwrap();
 
         for header in ["ID", "NAME", "IMAGE", "STATE", "DEPLOYMENT", "CREATED"] {
             assert!(
@@ -190,14 +254,53 @@ mod tests {
         );
     }
 
+    #[test]
+    fn retained_instances_show_when_they_can_be_restored_until() {
+        let now = DateTime::<Utc>::default();
+        let options = TableOptions::default();
+        let running = render_table(&[instance("web", "running")], now, false, &options).unwrap();
+        assert!(!running.contains("RETAINED-UNTIL"), "{running}");
+
+        let mut kept = instance("old", "stopped");
+        kept.retained_until = Some(now + chrono::Duration::hours(24));
+        let rendered =
+            render_table(&[instance("web", "running"), kept], now, false, &options).unwrap();
+        assert!(rendered.contains("RETAINED-UNTIL"), "{rendered}");
+        assert!(rendered.contains("1970-01-02 00:00 UTC"), "{rendered}");
+    }
+
+    #[test]
+    fn columns_pick_what_the_table_shows() {
+        let web = instance("web", "running");
+        let id = web.id.to_string();
+        let options = TableOptions {
+            columns: Some(vec!["id".into(), "state".into()]),
+            no_trunc: true,
+            ..TableOptions::default()
+        };
+        let rendered = render_table(&[web], DateTime::<Utc>::default(), false, &options).unwrap();
+        assert!(rendered.contains(&id), "full id: {rendered}");
+        assert!(rendered.contains("running"), "{rendered}");
+        assert!(!rendered.contains("IMAGE"), "{rendered}");
+        assert!(!rendered.contains("web"), "{rendered}");
+    }
+
     #[tokio::test]
     async fn list_queries_the_selected_environment() {
-        let env = env();
+        let env = test_env();
         let mock = MockApiClient::logged_in().with_list_instances(Ok(InstanceListResponse {
             instances: vec![instance("web", "running")],
         }));
 
-        let result = list(&mock, &env, false, false).await;
+        let result = list(
+            &mock,
+            &env,
+            false,
+            &Output::Table,
+            &TableOptions::default(),
+            None,
+        )
+        .await;
 
         assert!(result.is_ok(), "expected ok, got {result:?}");
         assert_eq!(
@@ -210,7 +313,18 @@ mod tests {
     async fn list_json_renders_without_error() {
         let mock = MockApiClient::logged_in()
             .with_list_instances(Ok(InstanceListResponse { instances: vec![] }));
-        assert!(list(&mock, &env(), false, true).await.is_ok());
+        assert!(
+            list(
+                &mock,
+                &test_env(),
+                false,
+                &Output::Json,
+                &TableOptions::default(),
+                None
+            )
+            .await
+            .is_ok()
+        );
     }
 
     #[tokio::test]
@@ -219,7 +333,16 @@ mod tests {
             status: 500,
             reason: "boom".into(),
         }));
-        let err = list(&mock, &env(), false, false).await.unwrap_err();
+        let err = list(
+            &mock,
+            &test_env(),
+            false,
+            &Output::Table,
+            &TableOptions::default(),
+            None,
+        )
+        .await
+        .unwrap_err();
         assert!(err.to_string().contains("500"));
     }
 }
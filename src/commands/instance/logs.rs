@@ -1,57 +1,359 @@
 //! `unisrv instance logs <ref>` — print or follow an instance's logs.
 //!
+//! `--between START/END` and `--grep TEXT` search the history instead. The
+//! server does the search where it can; an older backend gets the whole
+//! history fetched and filtered here. `--table` prints the result as a
+//! TIME / STREAM / MESSAGE table rather than raw lines. `--previous` reads
+//! the instance that a replaced one had before it under the same name, like
+//! `kubectl logs --previous`, for looking into why the last one died.
+//!
 //! Log frames are routed by type so the output pipes cleanly: application
 //! stdout goes to our stdout verbatim, application stderr to our stderr, and
 //! platform `system`/`state` frames to stderr (dimmed, timestamped). That way
 //! `unisrv instance logs web | grep ...` sees only the program's stdout.
-
-use anyhow::Result;
-use unisrv_api::ApiClient;
-use unisrv_api::models::LogMessage;
+//!
+//! `--follow` survives a dropped connection: it resubscribes with backoff and,
+//! since every subscription replays the full history first, skips the frames
+//! already printed (see [`ResumeCursor`]) so output continues seamlessly.
+//! The WebSocket upgrade itself is retried the same way when it fails
+//! transiently (see [`connect`]), which `worker run --follow` and the merged
+//! `logs` command share.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{NaiveDateTime, NaiveTime};
+use comfy_table::{Attribute, Cell};
+use unisrv_api::client::LogStream;
+use unisrv_api::models::{LogMessage, LogSearch};
+use unisrv_api::{ApiClient, ApiError};
 use uuid::Uuid;
 
-use super::resolve::resolve_instance;
+use super::resolve::{lookup_instance, lookup_previous_instance};
+use crate::commands::interrupt::until_interrupted;
+use crate::commands::ui::new_table;
 use crate::commands::up::plan::ResolvedEnvironment;
 
 /// Print or follow the logs of the instance referenced by `reference` within
-/// `env`. Without `follow`, prints the current log history and returns. With
-/// `follow`, streams until the server closes the connection or errors.
+/// `env`, or with `previous` of the one it replaced. Without `follow`, prints the current log history (or the frames
+/// matching `search`) and returns. With `follow`, streams until the server
+/// closes the connection or errors.
 pub async fn logs(
     client: &dyn ApiClient,
     env: &ResolvedEnvironment,
     reference: &str,
+    previous: bool,
     follow: bool,
+    search: Option<LogSearch>,
+    table: bool,
 ) -> Result<()> {
-    let instances = client.list_instances(env.id).await?;
-    let instance_id = resolve_instance(reference, &instances.instances)?.id;
+    let instance_id = if previous {
+        let instance = lookup_previous_instance(client, env.id, reference).await?;
+        eprintln!(
+            "{}",
+            console::style(format!(
+                "Showing logs of the previous instance {} ({}, created {})",
+                &instance.id.to_string()[..8],
+                instance.state.0,
+                instance.created_at.format("%Y-%m-%d %H:%M:%S")
+            ))
+            .dim()
+        );
+        instance.id
+    } else {
+        lookup_instance(client, env.id, reference).await?.id
+    };
 
     if follow {
-        follow_logs(client, env.id, instance_id).await
+        return until_interrupted(follow_logs(
+            client,
+            env.id,
+            instance_id,
+            &Resubscribe::DEFAULT,
+        ))
+        .await;
+    }
+    let history = match &search {
+        Some(search) => search_history(client, env.id, instance_id, search).await?,
+        None => client.get_instance_logs(env.id, instance_id).await?,
+    };
+    if history.is_empty() && search.is_some() {
+        eprintln!("No log lines matched.");
+    } else if table {
+        println!("{}", render_table(&history));
     } else {
-        let history = client.get_instance_logs(env.id, instance_id).await?;
         for msg in &history {
             emit(route(msg));
         }
-        Ok(())
+    }
+    Ok(())
+}
+
+/// Run `search` on the server, or filter the full history here when the
+/// backend has no search endpoint.
+async fn search_history(
+    client: &dyn ApiClient,
+    env_id: Uuid,
+    instance_id: Uuid,
+    search: &LogSearch,
+) -> Result<Vec<LogMessage>> {
+    match client
+        .search_instance_logs(env_id, instance_id, search)
+        .await
+    {
+        Ok(found) => Ok(found),
+        Err(e) if e.is_unsupported_endpoint() => {
+            tracing::debug!("server-side log search unavailable ({e}); filtering locally");
+            let history = client.get_instance_logs(env_id, instance_id).await?;
+            Ok(history
+                .into_iter()
+                .filter(|msg| matches(search, msg))
+                .collect())
+        }
+        Err(e) => Err(e).context("log search failed"),
+    }
+}
+
+/// The client-side equivalent of the server's search: inside the window, and
+/// containing `grep` in the message or state.
+fn matches(search: &LogSearch, msg: &LogMessage) -> bool {
+    search.from_ms.is_none_or(|from| msg.timestamp_ms >= from)
+        && search.to_ms.is_none_or(|to| msg.timestamp_ms < to)
+        && search.grep.as_deref().is_none_or(|needle| {
+            [msg.message.as_deref(), msg.state.as_deref()]
+                .into_iter()
+                .flatten()
+                .any(|text| text.contains(needle))
+        })
+}
+
+/// Parse `--between START/END` into epoch milliseconds, UTC, end exclusive.
+/// START is a date and time (`2024-06-01T10:00`); END is another, or just a
+/// time on START's date (`11:00`).
+pub fn parse_between(s: &str) -> Result<(u64, u64)> {
+    let invalid = |why: String| anyhow!("invalid --between {s:?}: {why}");
+    let Some((start, end)) = s.split_once('/') else {
+        return Err(invalid(
+            "expected START/END, e.g. 2024-06-01T10:00/11:00".into(),
+        ));
+    };
+    let (start, end) = (start.trim(), end.trim());
+    let start = parse_datetime(start).ok_or_else(|| {
+        invalid(format!(
+            "{start:?} is not a date and time like 2024-06-01T10:00"
+        ))
+    })?;
+    let end = parse_datetime(end)
+        .or_else(|| parse_time(end).map(|time| start.date().and_time(time)))
+        .ok_or_else(|| {
+            invalid(format!(
+                "{end:?} is not a time like 11:00 or a date and time"
+            ))
+        })?;
+    if end <= start {
+        bail!("invalid --between {s:?}: the end must be after the start");
+    }
+    let ms = |at: NaiveDateTime| {
+        u64::try_from(at.and_utc().timestamp_millis())
+            .map_err(|_| invalid(format!("{at} is before 1970")))
+    };
+    Ok((ms(start)?, ms(end)?))
+}
+
+fn parse_datetime(s: &str) -> Option<NaiveDateTime> {
+    [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+}
+
+fn parse_time(s: &str) -> Option<NaiveTime> {
+    ["%H:%M:%S", "%H:%M"]
+        .iter()
+        .find_map(|format| NaiveTime::parse_from_str(s, format).ok())
+}
+
+/// Pure so it can be asserted on without a terminal.
+fn render_table(frames: &[LogMessage]) -> String {
+    let mut table = new_table();
+    table.set_header(vec![
+        Cell::new("TIME").add_attribute(Attribute::Bold),
+        Cell::new("STREAM").add_attribute(Attribute::Bold),
+        Cell::new("MESSAGE").add_attribute(Attribute::Bold),
+    ]);
+    for msg in frames {
+        let text = match (&msg.message, &msg.state) {
+            (Some(message), _) => message.clone(),
+            (None, Some(state)) => format!("state: {state}"),
+            (None, None) => String::new(),
+        };
+        table.add_row(vec![
+            Cell::new(fmt_ts(msg.timestamp_ms)),
+            Cell::new(&msg.log_type),
+            Cell::new(text),
+        ]);
+    }
+    table.to_string()
+}
+
+/// How a broken log stream is resubscribed.
+pub struct Resubscribe {
+    /// Consecutive reconnects without a new frame before giving up.
+    max_attempts: u32,
+    /// Delay before the first reconnect; doubles per attempt up to `max_delay`.
+    base_delay: Duration,
+    max_delay: Duration,
+}
+
+impl Resubscribe {
+    pub const DEFAULT: Self = Self {
+        max_attempts: 5,
+        base_delay: Duration::from_millis(500),
+        max_delay: Duration::from_secs(8),
+    };
+
+    /// For streams that ride along with something else (a worker run, one
+    /// of several merged instances): a few quick tries, so a dead stream
+    /// doesn't hold up the rest.
+    pub const ALONGSIDE: Self = Self {
+        max_attempts: 3,
+        base_delay: Duration::from_millis(250),
+        max_delay: Duration::from_secs(1),
+    };
+
+    fn delay(&self, attempt: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(1 << (attempt - 1).min(16))
+            .min(self.max_delay)
+    }
+}
+
+/// Open an instance's log stream, retrying a transiently failed upgrade (a
+/// reset, a 502 from the edge) with `policy`'s backoff. A non-transient
+/// refusal, or the last transient one, is returned as is.
+pub async fn connect(
+    client: &dyn ApiClient,
+    env_id: Uuid,
+    instance_id: Uuid,
+    policy: &Resubscribe,
+) -> Result<LogStream, ApiError> {
+    let mut attempts = 0;
+    loop {
+        match client.stream_instance_logs(env_id, instance_id).await {
+            Ok(stream) => return Ok(stream),
+            Err(e) if e.is_transient() && attempts < policy.max_attempts => {
+                attempts += 1;
+                tracing::debug!("log stream upgrade failed ({e}); retry {attempts}");
+                tokio::time::sleep(policy.delay(attempts)).await;
+            }
+            Err(e) => return Err(e),
+        }
     }
 }
 
 /// Stream until the server closes the connection (a normal end, e.g. the
-/// instance stopped) or a transport error occurs. A clean close is success.
-async fn follow_logs(client: &dyn ApiClient, env_id: Uuid, instance_id: Uuid) -> Result<()> {
+/// instance stopped). A transient break resubscribes and resumes after the
+/// last printed frame; a refused connect, non-transient errors, and running
+/// out of attempts are errors. A clean close is success.
+async fn follow_logs(
+    client: &dyn ApiClient,
+    env_id: Uuid,
+    instance_id: Uuid,
+    policy: &Resubscribe,
+) -> Result<()> {
     use futures_util::StreamExt;
 
-    let mut stream = client.stream_instance_logs(env_id, instance_id).await?;
-    while let Some(frame) = stream.next().await {
-        emit(route(&frame?));
+    let mut stream = connect(client, env_id, instance_id, policy).await?;
+    let mut cursor = ResumeCursor::default();
+    let mut attempts = 0;
+    loop {
+        let mut err = loop {
+            match stream.next().await {
+                None => {
+                    eprintln!("{}", console::style("stream closed").dim());
+                    return Ok(());
+                }
+                Some(Ok(frame)) => {
+                    if cursor.admit(frame.timestamp_ms) {
+                        // Only progress past the cursor counts: a server that
+                        // replays history and drops again mustn't loop forever.
+                        attempts = 0;
+                        emit(route(&frame));
+                    }
+                }
+                Some(Err(e)) => break e,
+            }
+        };
+        stream = loop {
+            if !err.is_transient() || attempts >= policy.max_attempts {
+                return Err(err).context("log stream interrupted");
+            }
+            attempts += 1;
+            eprintln!(
+                "{}",
+                console::style(format!("stream interrupted ({err}); reconnecting…")).dim()
+            );
+            tokio::time::sleep(policy.delay(attempts)).await;
+            match client.stream_instance_logs(env_id, instance_id).await {
+                Ok(stream) => break stream,
+                Err(e) => err = e,
+            }
+        };
+        cursor.resubscribed();
+    }
+}
+
+/// Tracks the last printed frame so a resubscription, which replays the whole
+/// history, prints only what's new. The stream carries no event ids, so the
+/// position is the newest timestamp plus how many frames at that exact
+/// millisecond were already shown.
+#[derive(Debug, Default)]
+struct ResumeCursor {
+    last_ms: Option<u64>,
+    shown_at_last: usize,
+    /// While replaying after a resubscribe: frames seen at `last_ms` so far.
+    replayed_at_last: Option<usize>,
+}
+
+impl ResumeCursor {
+    /// Whether a frame is new and should be printed; records it if so.
+    fn admit(&mut self, timestamp_ms: u64) -> bool {
+        if let (Some(replayed), Some(last)) = (self.replayed_at_last.as_mut(), self.last_ms) {
+            if timestamp_ms < last {
+                return false;
+            }
+            if timestamp_ms == last {
+                *replayed += 1;
+                if *replayed <= self.shown_at_last {
+                    return false;
+                }
+            }
+            self.replayed_at_last = None;
+        }
+        match self.last_ms {
+            Some(last) if timestamp_ms == last => self.shown_at_last += 1,
+            Some(last) if timestamp_ms < last => {}
+            _ => {
+                self.last_ms = Some(timestamp_ms);
+                self.shown_at_last = 1;
+            }
+        }
+        true
+    }
+
+    /// A new subscription started; its history replay must be skipped.
+    fn resubscribed(&mut self) {
+        self.replayed_at_last = Some(0);
     }
-    eprintln!("{}", console::style("stream closed").dim());
-    Ok(())
 }
 
 /// Write a routed line to the appropriate stream, dimming platform chatter when
 /// stderr is an interactive terminal (no ANSI in pipes).
-fn emit(line: Option<RoutedLine>) {
+pub fn emit(line: Option<RoutedLine>) {
     let Some(line) = line else { return };
     match line.sink {
         Sink::Out => println!("{}", line.text),
@@ -64,7 +366,7 @@ fn emit(line: Option<RoutedLine>) {
 
 /// Which of our output streams a routed log line is written to.
 #[derive(Debug, PartialEq, Eq)]
-enum Sink {
+pub enum Sink {
     Out,
     Err,
 }
@@ -72,15 +374,15 @@ enum Sink {
 /// A log frame routed to a stream, with the text to print and whether it should
 /// be dimmed (platform chatter, not application output).
 #[derive(Debug, PartialEq, Eq)]
-struct RoutedLine {
-    sink: Sink,
-    text: String,
-    dim: bool,
+pub struct RoutedLine {
+    pub sink: Sink,
+    pub text: String,
+    pub dim: bool,
 }
 
 /// Decide where a log frame goes and how it reads. Returns `None` for frames
 /// that carry nothing to show. Pure, so routing is testable without a terminal.
-fn route(msg: &LogMessage) -> Option<RoutedLine> {
+pub fn route(msg: &LogMessage) -> Option<RoutedLine> {
     match msg.log_type.as_str() {
         // Application output is forwarded verbatim, including a genuinely blank
         // line (`Some("")`). A frame carrying no `message` field at all has
@@ -143,6 +445,7 @@ fn fmt_ts(timestamp_ms: u64) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::commands::up::plan::test_env;
 
     use unisrv_api::ApiError;
     use unisrv_api::models::{InstanceListEntry, InstanceListResponse, InstanceState};
@@ -157,15 +460,6 @@ mod tests {
         }
     }
 
-    fn env() -> ResolvedEnvironment {
-        ResolvedEnvironment {
-            id: Uuid::new_v4(),
-            name: "prod".to_string(),
-            project: "demo".to_string(),
-            slug: "ab12".to_string(),
-        }
-    }
-
     fn instance(id: Uuid, name: &str) -> InstanceListEntry {
         InstanceListEntry {
             id,
@@ -174,6 +468,7 @@ mod tests {
             container_image: "nginx:latest".to_string(),
             created_at: chrono::NaiveDateTime::default(),
             deployment: None,
+            retained_until: None,
         }
     }
 
@@ -240,13 +535,13 @@ mod tests {
 
     #[tokio::test]
     async fn non_follow_resolves_ref_and_fetches_that_instances_logs() {
-        let env = env();
+        let env = test_env();
         let id = Uuid::new_v4();
         let mock = MockApiClient::logged_in()
             .with_list_instances(Ok(list_of(vec![instance(id, "web")])))
             .push_instance_logs(Ok(vec![msg("stdout", Some("hi"), None)]));
 
-        let result = logs(&mock, &env, "web", false).await;
+        let result = logs(&mock, &env, "web", false, false, None, false).await;
 
         assert!(result.is_ok(), "expected ok, got {result:?}");
         assert_eq!(
@@ -255,12 +550,105 @@ mod tests {
         );
     }
 
+    #[test]
+    fn between_accepts_a_bare_end_time_on_the_start_date() {
+        let (from, to) = parse_between("2024-06-01T10:00/11:00").unwrap();
+        assert_eq!(from, 1_717_236_000_000);
+        assert_eq!(to - from, 3_600_000);
+        let (_, to) = parse_between("2024-06-01T23:00/2024-06-02T01:30:15").unwrap();
+        assert_eq!(to - from, 15 * 3_600_000 + 30 * 60_000 + 15_000);
+    }
+
+    #[test]
+    fn between_rejects_malformed_or_backwards_windows() {
+        let cases = [
+            ("2024-06-01T10:00", "expected START/END"),
+            ("10:00/11:00", "is not a date and time"),
+            ("2024-06-01T10:00/noon", "is not a time"),
+            ("2024-06-01T10:00/09:00", "end must be after the start"),
+        ];
+        for (input, expected) in cases {
+            let err = parse_between(input).unwrap_err();
+            assert!(err.to_string().contains(expected), "{input}: {err}");
+        }
+    }
+
+    #[test]
+    fn local_filter_matches_window_and_text() {
+        let search = LogSearch {
+            from_ms: Some(10),
+            to_ms: Some(20),
+            grep: Some("traceid=abc".into()),
+        };
+        assert!(matches(&search, &at(10, "GET / traceid=abc")));
+        assert!(
+            !matches(&search, &at(20, "GET / traceid=abc")),
+            "end is exclusive"
+        );
+        assert!(!matches(&search, &at(15, "GET / traceid=xyz")));
+    }
+
+    #[tokio::test]
+    async fn search_runs_on_the_server_when_supported() {
+        let env = test_env();
+        let id = Uuid::new_v4();
+        let search = LogSearch {
+            grep: Some("traceid=abc".into()),
+            ..Default::default()
+        };
+        let mock = MockApiClient::logged_in()
+            .with_list_instances(Ok(list_of(vec![instance(id, "web")])))
+            .push_search_instance_logs(Ok(vec![at(1, "traceid=abc")]));
+
+        logs(&mock, &env, "web", false, false, Some(search.clone()), true)
+            .await
+            .unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        assert_eq!(calls.search_instance_logs_calls, vec![(env.id, id, search)]);
+        assert!(calls.get_instance_logs_calls.is_empty());
+    }
+
+    #[tokio::test]
+    async fn search_falls_back_to_filtering_the_history() {
+        let id = Uuid::new_v4();
+        let mock = MockApiClient::logged_in().push_instance_logs(Ok(vec![
+            at(5, "early"),
+            at(15, "inside"),
+            at(25, "late"),
+        ]));
+        let search = LogSearch {
+            from_ms: Some(10),
+            to_ms: Some(20),
+            grep: None,
+        };
+
+        let found = search_history(&mock, Uuid::nil(), id, &search)
+            .await
+            .unwrap();
+
+        assert_eq!(found, vec![at(15, "inside")]);
+    }
+
+    #[test]
+    fn table_lists_time_stream_and_message() {
+        let out = render_table(&[
+            at(1_700_000_000_000, "hello"),
+            msg("state", None, Some("online")),
+        ]);
+        assert!(out.contains("2023-11-14 22:13:20"), "{out}");
+        assert!(out.contains("stdout"), "{out}");
+        assert!(out.contains("state: online"), "{out}");
+    }
+
     #[tokio::test]
     async fn unknown_ref_errors_before_fetching_logs() {
         let mock = MockApiClient::logged_in()
             .with_list_instances(Ok(list_of(vec![instance(Uuid::new_v4(), "web")])));
 
-        let err = logs(&mock, &env(), "ghost", false).await.unwrap_err();
+        let err = logs(&mock, &test_env(), "ghost", false, false, None, false)
+            .await
+            .unwrap_err();
 
         assert!(format!("{err:#}").contains("ghost"));
         assert!(
@@ -275,7 +663,7 @@ mod tests {
 
     #[tokio::test]
     async fn follow_drains_the_stream_until_close_and_succeeds() {
-        let env = env();
+        let env = test_env();
         let id = Uuid::new_v4();
         let mock = MockApiClient::logged_in()
             .with_list_instances(Ok(list_of(vec![instance(id, "web")])))
@@ -284,7 +672,7 @@ mod tests {
                 msg("stdout", Some("ready"), None),
             ]);
 
-        let result = logs(&mock, &env, "web", true).await;
+        let result = logs(&mock, &env, "web", false, true, None, false).await;
 
         assert!(
             result.is_ok(),
@@ -308,21 +696,149 @@ mod tests {
                 reason: "instance not found".into(),
             });
 
-        let err = logs(&mock, &env(), "web", true).await.unwrap_err();
+        let err = logs(&mock, &test_env(), "web", false, true, None, false)
+            .await
+            .unwrap_err();
         assert!(format!("{err:#}").contains("instance not found"), "{err:#}");
     }
 
     #[tokio::test]
-    async fn follow_propagates_a_mid_stream_transport_error() {
+    async fn follow_retries_a_transiently_failed_upgrade() {
+        let id = Uuid::new_v4();
+        let mock = MockApiClient::logged_in()
+            .push_stream_connect_error(ApiError::Server {
+                status: 502,
+                reason: "bad gateway".into(),
+            })
+            .push_stream_connect_error(reset())
+            .push_stream_logs(vec![at(1, "a")]);
+
+        follow_logs(&mock, Uuid::new_v4(), id, &NO_DELAY)
+            .await
+            .unwrap();
+        assert_eq!(
+            mock.calls.lock().unwrap().stream_instance_logs_calls.len(),
+            3
+        );
+
+        let mock = MockApiClient::logged_in()
+            .push_stream_connect_error(reset())
+            .push_stream_connect_error(reset())
+            .push_stream_connect_error(reset());
+        let err = connect(&mock, Uuid::new_v4(), id, &NO_DELAY)
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("connection reset"), "{err}");
+    }
+
+    const NO_DELAY: Resubscribe = Resubscribe {
+        max_attempts: 2,
+        base_delay: Duration::ZERO,
+        max_delay: Duration::ZERO,
+    };
+
+    fn at(timestamp_ms: u64, text: &str) -> LogMessage {
+        LogMessage {
+            timestamp_ms,
+            ..msg("stdout", Some(text), None)
+        }
+    }
+
+    fn reset() -> ApiError {
+        ApiError::Other(anyhow::anyhow!("connection reset"))
+    }
+
+    #[test]
+    fn cursor_skips_the_replayed_history_after_resubscribing() {
+        let mut cursor = ResumeCursor::default();
+        // First subscription: two frames share a millisecond.
+        assert!(cursor.admit(10));
+        assert!(cursor.admit(20));
+        assert!(cursor.admit(20));
+
+        cursor.resubscribed();
+        assert!(!cursor.admit(10), "older than the cursor");
+        assert!(!cursor.admit(20), "first already-shown frame at 20");
+        assert!(!cursor.admit(20), "second already-shown frame at 20");
+        assert!(cursor.admit(20), "a third frame at 20 is new");
+        assert!(cursor.admit(30));
+    }
+
+    #[test]
+    fn cursor_admits_out_of_order_frames_outside_a_replay() {
+        let mut cursor = ResumeCursor::default();
+        assert!(cursor.admit(20));
+        assert!(cursor.admit(15), "live frames are never dropped");
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let policy = Resubscribe::DEFAULT;
+        assert_eq!(policy.delay(1), Duration::from_millis(500));
+        assert_eq!(policy.delay(2), Duration::from_secs(1));
+        assert_eq!(policy.delay(10), Duration::from_secs(8));
+    }
+
+    #[tokio::test]
+    async fn follow_resubscribes_after_a_transport_error_and_resumes() {
+        let (env_id, id) = (Uuid::new_v4(), Uuid::new_v4());
+        let mock = MockApiClient::logged_in()
+            .push_stream_logs_frames(vec![Ok(at(1, "a")), Ok(at(2, "b")), Err(reset())])
+            .push_stream_connect_error(ApiError::Server {
+                status: 503,
+                reason: "unavailable".into(),
+            })
+            .push_stream_logs(vec![at(1, "a"), at(2, "b"), at(3, "c")]);
+
+        follow_logs(&mock, env_id, id, &NO_DELAY).await.unwrap();
+
+        assert_eq!(
+            mock.calls.lock().unwrap().stream_instance_logs_calls.len(),
+            3
+        );
+    }
+
+    #[tokio::test]
+    async fn follow_gives_up_after_repeated_failures_without_progress() {
+        let mock = MockApiClient::logged_in()
+            .push_stream_logs_frames(vec![Ok(at(1, "a")), Err(reset())])
+            .push_stream_logs_frames(vec![Ok(at(1, "a")), Err(reset())])
+            .push_stream_logs_frames(vec![Ok(at(1, "a")), Err(reset())]);
+
+        let err = follow_logs(&mock, Uuid::new_v4(), Uuid::new_v4(), &NO_DELAY)
+            .await
+            .unwrap_err();
+
+        let msg = format!("{err:#}");
+        assert!(
+            msg.contains("interrupted") && msg.contains("connection reset"),
+            "{msg}"
+        );
+        // The replayed frame is not progress, so the budget of 2 isn't reset.
+        assert_eq!(
+            mock.calls.lock().unwrap().stream_instance_logs_calls.len(),
+            3
+        );
+    }
+
+    #[tokio::test]
+    async fn follow_does_not_retry_a_non_transient_error() {
         let id = Uuid::new_v4();
         let mock = MockApiClient::logged_in()
             .with_list_instances(Ok(list_of(vec![instance(id, "web")])))
             .push_stream_logs_frames(vec![
                 Ok(msg("stdout", Some("line"), None)),
-                Err(ApiError::Other(anyhow::anyhow!("connection reset"))),
+                Err(ApiError::AuthRequired("session expired".into())),
             ]);
 
-        let err = logs(&mock, &env(), "web", true).await.unwrap_err();
-        assert!(format!("{err:#}").contains("connection reset"));
+        let err = logs(&mock, &test_env(), "web", false, true, None, false)
+            .await
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("session expired"));
+        assert_eq!(
+            mock.calls.lock().unwrap().stream_instance_logs_calls.len(),
+            1
+        );
     }
 }
@@ -1,7 +1,17 @@
-//! `unisrv instance` — list and inspect instances within an environment.
+//! `unisrv instance` — list, inspect, start, stop and restore instances
+//! within an environment.
 
+pub mod boot;
+pub mod connection;
+pub mod launch;
 pub mod list;
 pub mod logs;
+pub mod platform;
 pub mod resolve;
 pub mod run;
 pub mod select_env;
+pub mod show;
+pub mod signature;
+pub mod sizing;
+pub mod stop;
+pub mod verify;
@@ -0,0 +1,194 @@
+//! `--platform os/arch[/variant]`: which of a multi-platform image's
+//! manifests an instance runs. Without one the server picks `linux/amd64`;
+//! `platform` in `~/.unisrv/config.json` sets a different default.
+//!
+//! The server only reports a missing platform as a failed start, so before
+//! provisioning the image's index is read from its registry and a platform it
+//! wasn't built for is refused with the ones it was. That check is best
+//! effort: an index that can't be read is left for the server to judge.
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Deserializer};
+use unisrv_api::ApiClient;
+use unisrv_api::distribution::{ManifestPlatform, ManifestSource};
+
+use crate::commands::registry::{self, tags::Repository};
+
+/// The platform the server runs when none is asked for.
+pub const DEFAULT_PLATFORM: &str = "linux/amd64";
+
+/// A validated `os/arch[/variant]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Platform(String);
+
+impl Platform {
+    /// Whether the index entry `entry` is this platform. Without a variant
+    /// any of the architecture's variants will do.
+    fn matches(&self, entry: &ManifestPlatform) -> bool {
+        let mut parts = self.0.split('/');
+        parts.next() == Some(entry.os.as_str())
+            && parts.next() == Some(entry.architecture.as_str())
+            && parts
+                .next()
+                .is_none_or(|variant| entry.variant.as_deref() == Some(variant))
+    }
+}
+
+impl Default for Platform {
+    fn default() -> Self {
+        Platform(DEFAULT_PLATFORM.to_string())
+    }
+}
+
+impl FromStr for Platform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split('/').collect();
+        let valid = |part: &&str| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        };
+        if !(2..=3).contains(&parts.len()) || !parts.iter().all(valid) {
+            return Err(format!(
+                "invalid platform {s:?}: expected os/arch[/variant], e.g. linux/arm64"
+            ));
+        }
+        Ok(Platform(s.to_string()))
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Platform {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Refuse `platform` if `image`'s index at `reference` doesn't list it.
+async fn check(
+    source: &dyn ManifestSource,
+    image: &str,
+    reference: &str,
+    platform: &Platform,
+) -> Result<()> {
+    let available = match source.index_platforms(reference).await {
+        Ok(Some(available)) => available,
+        // A single-platform image: only its config says which, so the server
+        // decides.
+        Ok(None) => return Ok(()),
+        Err(err) => {
+            tracing::debug!("couldn't read the index of {image}: {err}");
+            return Ok(());
+        }
+    };
+    // Attestation manifests are listed as `unknown/unknown`.
+    let available: Vec<&ManifestPlatform> = available
+        .iter()
+        .filter(|p| p.os != "unknown" && p.architecture != "unknown")
+        .collect();
+    if available.iter().any(|p| platform.matches(p)) {
+        return Ok(());
+    }
+    let names: Vec<String> = available.iter().map(ToString::to_string).collect();
+    bail!(
+        "{image} isn't built for {platform}; it's available for: {}",
+        names.join(", ")
+    )
+}
+
+/// Refuse `platform` before provisioning if `image` is a multi-platform image
+/// without it.
+pub async fn preflight(client: &dyn ApiClient, image: &str, platform: &Platform) -> Result<()> {
+    let Ok((repo, reference)) = Repository::parse_manifest_reference(image) else {
+        return Ok(());
+    };
+    let source = match registry::distribution_client(client, &repo.host, &repo.name, false).await {
+        Ok(source) => source,
+        Err(err) => {
+            tracing::debug!("can't read {image} from its registry: {err:#}");
+            return Ok(());
+        }
+    };
+    check(&source, image, &reference, platform).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+
+    #[test]
+    fn parses_platforms() {
+        let arm: Platform = "linux/arm64/v8".parse().unwrap();
+        assert_eq!(arm.to_string(), "linux/arm64/v8");
+        assert_eq!(
+            "linux/amd64".parse::<Platform>().unwrap().to_string(),
+            DEFAULT_PLATFORM
+        );
+        for bad in ["linux", "linux/", "linux/arm64/v8/x", "Linux/ARM64"] {
+            assert!(bad.parse::<Platform>().is_err(), "{bad}");
+        }
+    }
+
+    struct Index(Option<Vec<ManifestPlatform>>);
+
+    #[async_trait]
+    impl ManifestSource for Index {
+        async fn index_platforms(
+            &self,
+            _reference: &str,
+        ) -> unisrv_api::Result<Option<Vec<ManifestPlatform>>> {
+            Ok(self.0.clone())
+        }
+
+        async fn digest(&self, _reference: &str) -> unisrv_api::Result<String> {
+            unreachable!("the platform check doesn't read digests")
+        }
+    }
+
+    fn entry(os: &str, architecture: &str, variant: Option<&str>) -> ManifestPlatform {
+        ManifestPlatform {
+            os: os.into(),
+            architecture: architecture.into(),
+            variant: variant.map(String::from),
+        }
+    }
+
+    #[tokio::test]
+    async fn refuses_a_platform_the_index_lacks_listing_the_others() {
+        let index = Index(Some(vec![
+            entry("linux", "amd64", None),
+            entry("linux", "arm64", Some("v8")),
+            entry("unknown", "unknown", None),
+        ]));
+        for arm in ["linux/arm64", "linux/arm64/v8"] {
+            let arm: Platform = arm.parse().unwrap();
+            check(&index, "app:1", "1", &arm).await.unwrap();
+        }
+        let v7: Platform = "linux/arm64/v7".parse().unwrap();
+        assert!(check(&index, "app:1", "1", &v7).await.is_err());
+
+        let riscv: Platform = "linux/riscv64".parse().unwrap();
+        let err = check(&index, "app:1", "1", &riscv).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "app:1 isn't built for linux/riscv64; it's available for: linux/amd64, linux/arm64/v8"
+        );
+
+        // A single-platform image is left to the server.
+        check(&Index(None), "app:1", "1", &riscv).await.unwrap();
+    }
+}
@@ -5,11 +5,101 @@
 //! already-selected environment, so a name need only be unique within that env.
 //! Ambiguity (a name shared by replicas, or a prefix matching several ids) is an
 //! error that lists the candidates rather than a silent pick.
+//!
+//! Unlike a registry's, a resolved instance isn't kept in the state
+//! directory's resolution cache: a replacement takes over its name at any
+//! time, and only the current candidates show whether a reference is still
+//! unique.
+//!
+//! [`lookup_instance`] fetches the candidates: an exact name goes through the
+//! server-side name filter first, so large environments don't ship their whole
+//! instance list just to resolve one name.
+//!
+//! [`previous_instance`] looks one step back instead: the instance a replaced
+//! one had before it under the same name, for `instance logs --previous`.
 
 use anyhow::{Result, anyhow, bail};
+use unisrv_api::ApiClient;
 use unisrv_api::models::InstanceListEntry;
 use uuid::Uuid;
 
+/// Resolve `input` within environment `env_id`, fetching only what's needed.
+///
+/// A reference that could be a name is first looked up with the name filter;
+/// anything that can't settle — a UUID or prefix, no exact match, or a backend
+/// without the filter — falls back to scanning the full instance list, so the
+/// result is always the same as [`resolve_instance`] over every instance.
+pub async fn lookup_instance(
+    client: &dyn ApiClient,
+    env_id: Uuid,
+    input: &str,
+) -> Result<InstanceListEntry> {
+    let name = input.trim();
+    if !name.is_empty() && Uuid::parse_str(name).is_err() {
+        match client.find_instances_by_name(env_id, name).await {
+            // A backend that ignores the filter returns everything, which
+            // `resolve_instance` handles just the same.
+            Ok(found)
+                if found
+                    .instances
+                    .iter()
+                    .any(|i| i.name.as_deref() == Some(name)) =>
+            {
+                return resolve_instance(name, &found.instances).cloned();
+            }
+            Ok(_) => {}
+            Err(e) if e.is_unsupported_endpoint() => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    let all = client.list_instances(env_id).await?;
+    resolve_instance(input, &all.instances).cloned()
+}
+
+/// The instance `input`'s instance replaced, from the full list (which keeps
+/// stopped instances). See [`previous_instance`].
+pub async fn lookup_previous_instance(
+    client: &dyn ApiClient,
+    env_id: Uuid,
+    input: &str,
+) -> Result<InstanceListEntry> {
+    let all = client.list_instances(env_id).await?;
+    previous_instance(input, &all.instances).cloned()
+}
+
+/// The newest instance created before `input`'s under the same name. A name
+/// the current instance shares with those it replaced means the newest of
+/// them, rather than being ambiguous.
+pub fn previous_instance<'a>(
+    input: &str,
+    instances: &'a [InstanceListEntry],
+) -> Result<&'a InstanceListEntry> {
+    let input = input.trim();
+    let current = match instances
+        .iter()
+        .filter(|i| i.name.as_deref() == Some(input))
+        .max_by_key(|i| i.created_at)
+    {
+        Some(newest) => newest,
+        None => resolve_instance(input, instances)?,
+    };
+    let Some(name) = current.name.as_deref() else {
+        bail!(
+            "instance {} has no name, so there's no previous instance to find",
+            &current.id.to_string()[..8]
+        );
+    };
+    instances
+        .iter()
+        .filter(|i| {
+            i.name.as_deref() == Some(name)
+                && i.id != current.id
+                && i.created_at <= current.created_at
+        })
+        .max_by_key(|i| i.created_at)
+        .ok_or_else(|| anyhow!("no previous instance named {name:?} in this environment"))
+}
+
 /// Resolve `input` against `instances`, returning the matched instance.
 pub fn resolve_instance<'a>(
     input: &str,
@@ -90,7 +180,9 @@ fn describe(instance: &InstanceListEntry) -> String {
 mod tests {
     use super::*;
     use chrono::NaiveDateTime;
-    use unisrv_api::models::InstanceState;
+    use unisrv_api::ApiError;
+    use unisrv_api::models::{InstanceListResponse, InstanceState};
+    use unisrv_api::test_support::MockApiClient;
 
     fn instance(id: Uuid, name: Option<&str>, state: &str) -> InstanceListEntry {
         InstanceListEntry {
@@ -100,6 +192,7 @@ mod tests {
             container_image: "nginx:latest".to_string(),
             created_at: NaiveDateTime::default(),
             deployment: None,
+            retained_until: None,
         }
     }
 
@@ -213,6 +306,46 @@ mod tests {
         assert_eq!(got.id, a);
     }
 
+    #[test]
+    fn previous_is_the_newest_older_instance_under_the_same_name() {
+        let at = |minute| {
+            chrono::NaiveDate::from_ymd_opt(2026, 10, 1)
+                .unwrap()
+                .and_hms_opt(12, minute, 0)
+                .unwrap()
+        };
+        let created = |id, name, state, minute| InstanceListEntry {
+            created_at: at(minute),
+            ..instance(uuid(id), Some(name), state)
+        };
+        let instances = vec![
+            created(0xA1, "worker", "stopped", 1),
+            created(0xA2, "worker", "stopped", 2),
+            created(0xA3, "worker", "running", 3),
+            created(0xB1, "web", "running", 4),
+            instance(uuid(0xC1), None, "running"),
+        ];
+
+        assert_eq!(
+            previous_instance("worker", &instances).unwrap().id,
+            uuid(0xA2)
+        );
+        let by_id = uuid(0xA2).to_string();
+        assert_eq!(
+            previous_instance(&by_id, &instances).unwrap().id,
+            uuid(0xA1)
+        );
+
+        let err = previous_instance("web", &instances).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("no previous instance named"),
+            "{err:#}"
+        );
+        let unnamed = uuid(0xC1).to_string();
+        let err = previous_instance(&unnamed, &instances).unwrap_err();
+        assert!(format!("{err:#}").contains("has no name"), "{err:#}");
+    }
+
     #[test]
     fn full_uuid_absent_from_env_errors() {
         // logs is environment-scoped: a real UUID that isn't in this env's list
@@ -222,4 +355,70 @@ mod tests {
         let err = resolve_instance(&absent.to_string(), &instances).unwrap_err();
         assert!(format!("{err:#}").contains(&absent.to_string()));
     }
+
+    #[tokio::test]
+    async fn lookup_by_name_uses_the_filter_without_listing_everything() {
+        let env = uuid(0xE);
+        let mock =
+            MockApiClient::logged_in().push_find_instances_by_name(Ok(InstanceListResponse {
+                instances: vec![instance(uuid(0xA1), Some("api"), "running")],
+            }));
+
+        let got = lookup_instance(&mock, env, "api").await.unwrap();
+
+        assert_eq!(got.id, uuid(0xA1));
+        let calls = mock.calls.lock().unwrap();
+        assert_eq!(
+            calls.find_instances_by_name_calls,
+            vec![(env, "api".to_string())]
+        );
+        assert!(calls.list_instances_calls.is_empty());
+    }
+
+    #[tokio::test]
+    async fn lookup_falls_back_to_the_full_list_without_the_filter() {
+        // The unscripted filter answers 404, as an older backend would; a UUID
+        // prefix can't be answered by the filter either.
+        let a = Uuid::parse_str("aaaaaaaa-0000-0000-0000-000000000000").unwrap();
+        let mock = MockApiClient::logged_in().with_list_instances(Ok(InstanceListResponse {
+            instances: vec![instance(a, Some("web"), "running")],
+        }));
+
+        let got = lookup_instance(&mock, uuid(0xE), "aaaa").await.unwrap();
+
+        assert_eq!(got.id, a);
+        assert_eq!(mock.calls.lock().unwrap().list_instances_calls.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn lookup_by_full_uuid_skips_the_name_filter() {
+        let a = uuid(0xA1);
+        let mock = MockApiClient::logged_in().with_list_instances(Ok(InstanceListResponse {
+            instances: vec![instance(a, Some("web"), "running")],
+        }));
+
+        lookup_instance(&mock, uuid(0xE), &a.to_string())
+            .await
+            .unwrap();
+
+        assert!(
+            mock.calls
+                .lock()
+                .unwrap()
+                .find_instances_by_name_calls
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn lookup_surfaces_real_filter_failures() {
+        let mock = MockApiClient::logged_in().push_find_instances_by_name(Err(ApiError::Server {
+            status: 500,
+            reason: "boom".into(),
+        }));
+
+        let err = lookup_instance(&mock, uuid(0xE), "api").await.unwrap_err();
+
+        assert!(format!("{err:#}").contains("boom"), "{err:#}");
+    }
 }
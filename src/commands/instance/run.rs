@@ -1,23 +1,62 @@
 //! Entry point for the `instance` command group: resolve the environment
 //! (manifest → project → remembered/picked env), announce it, then dispatch to
-//! the list or logs handler.
+//! the list, show, logs, launch, stop or restore handler.
 
 use std::io::IsTerminal;
+use std::time::Duration;
 
 use anyhow::{Context, Result, bail};
 use unisrv_api::ApiClient;
-use unisrv_api::models::EnvironmentListEntry;
+use unisrv_api::models::{EnvironmentListEntry, LogSearch};
 
+use super::launch::{self, LaunchOptions};
 use super::select_env::{EnvPicker, select_environment};
-use super::{list, logs};
+use super::{connection, list, logs, show, stop};
+use crate::commands::confirm::Guard;
+use crate::commands::output::Output;
+use crate::commands::prompt;
+use crate::commands::table::TableOptions;
 use crate::commands::up::config::UpConfig;
+use crate::commands::up::plan::ResolvedEnvironment;
 use crate::config_locate::{CONFIG_FILE, find_config};
 use crate::preferences::{FilePreferenceStore, NullPreferenceStore, PreferenceStore};
+use crate::state::StateDir;
 
 /// What the user asked the instance group to do.
 pub enum InstanceAction {
-    List { all: bool, json: bool },
-    Logs { reference: String, follow: bool },
+    List {
+        all: bool,
+        output: Output,
+        table: TableOptions,
+        /// Redraw this often until interrupted.
+        watch: Option<Duration>,
+    },
+    Show {
+        reference: String,
+        output: Output,
+    },
+    Logs {
+        reference: String,
+        previous: bool,
+        follow: bool,
+        search: Option<LogSearch>,
+        table: bool,
+    },
+    Run(Box<LaunchOptions>),
+    Stop {
+        reference: String,
+        /// Retain the stopped instance this long, to be restored.
+        keep: Option<Duration>,
+        yes: bool,
+    },
+    Restore {
+        reference: String,
+    },
+    ConnectionString {
+        reference: String,
+        port: Option<u16>,
+        output: Output,
+    },
 }
 
 /// Resolve the target environment and run `action` against it. `env_flag` is the
@@ -27,6 +66,70 @@ pub async fn run(
     env_flag: Option<&str>,
     action: InstanceAction,
 ) -> Result<()> {
+    let env = current_environment(client, env_flag).await?;
+
+    // Always tell the user which environment we landed on — but keep stdout
+    // clean for machine output, so the banner goes to stderr and is skipped
+    // entirely for `--json` / `--format`.
+    let machine = match &action {
+        InstanceAction::List { output, .. }
+        | InstanceAction::Show { output, .. }
+        | InstanceAction::ConnectionString { output, .. } => output.is_machine(),
+        _ => false,
+    };
+    if !machine {
+        announce_environment(&env);
+    }
+
+    match action {
+        InstanceAction::List {
+            all,
+            output,
+            table,
+            watch,
+        } => list::list(client, &env, all, &output, &table, watch).await,
+        InstanceAction::Show { reference, output } => {
+            show::show(client, &env, &reference, &output).await
+        }
+        InstanceAction::Logs {
+            reference,
+            previous,
+            follow,
+            search,
+            table,
+        } => logs::logs(client, &env, &reference, previous, follow, search, table).await,
+        InstanceAction::Run(opts) => {
+            let image = opts.image.clone();
+            launch::launch(client, &env, *opts).await?;
+            // Offered by `instance run <TAB>`; failing to record it is harmless.
+            if let Some(state) = StateDir::locate() {
+                let _ = state.remember_images([image.as_str()]);
+            }
+            Ok(())
+        }
+        InstanceAction::Stop {
+            reference,
+            keep,
+            yes,
+        } => {
+            let guard = Guard::for_env(&env)?;
+            stop::stop(client, &env, &reference, keep, yes, &guard).await
+        }
+        InstanceAction::Restore { reference } => stop::restore(client, &env, &reference).await,
+        InstanceAction::ConnectionString {
+            reference,
+            port,
+            output,
+        } => connection::connection_string(client, &env, &reference, port, &output).await,
+    }
+}
+
+/// The environment an env-scoped command (`instance`, `service`) acts on:
+/// manifest → project → `--env` / remembered / picked environment.
+pub async fn current_environment(
+    client: &dyn ApiClient,
+    env_flag: Option<&str>,
+) -> Result<ResolvedEnvironment> {
     let cwd = std::env::current_dir().context("failed to determine the current directory")?;
     let manifest = find_config(&cwd, CONFIG_FILE);
     let project = match &manifest {
@@ -44,9 +147,9 @@ pub async fn run(
         Some(path) => Box::new(FilePreferenceStore::new(path)),
         None => Box::new(NullPreferenceStore),
     };
-    let picker = DialoguerEnvPicker;
+    let picker = TerminalEnvPicker;
 
-    let env = select_environment(
+    select_environment(
         client,
         project.as_deref(),
         &pref_dir,
@@ -54,32 +157,22 @@ pub async fn run(
         prefs.as_mut(),
         &picker,
     )
-    .await?;
-
-    // Always tell the user which environment we landed on — but keep stdout
-    // clean for machine output, so the banner goes to stderr and is skipped
-    // entirely for `--json`.
-    let json = matches!(action, InstanceAction::List { json: true, .. });
-    if !json {
-        eprintln!(
-            "{}",
-            console::style(format!("→ env: {} (project {})", env.name, env.project)).dim()
-        );
-    }
+    .await
+}
 
-    match action {
-        InstanceAction::List { all, json } => list::list(client, &env, all, json).await,
-        InstanceAction::Logs { reference, follow } => {
-            logs::logs(client, &env, &reference, follow).await
-        }
-    }
+/// The dimmed `→ env: …` banner, on stderr so stdout stays clean.
+pub fn announce_environment(env: &ResolvedEnvironment) {
+    eprintln!(
+        "{}",
+        console::style(format!("→ env: {} (project {})", env.name, env.project)).dim()
+    );
 }
 
-/// Production environment picker: a dialoguer select that refuses to guess when
+/// Production environment picker: a terminal select that refuses to guess when
 /// there's no terminal to prompt at.
-struct DialoguerEnvPicker;
+struct TerminalEnvPicker;
 
-impl EnvPicker for DialoguerEnvPicker {
+impl EnvPicker for TerminalEnvPicker {
     fn pick(&self, candidates: &[EnvironmentListEntry]) -> Result<EnvironmentListEntry> {
         if !std::io::stdin().is_terminal() {
             bail!(
@@ -90,12 +183,7 @@ impl EnvPicker for DialoguerEnvPicker {
             .iter()
             .map(|e| format!("{} (project {})", e.name, e.project))
             .collect();
-        let index = dialoguer::Select::new()
-            .with_prompt("Select an environment")
-            .items(&items)
-            .default(0)
-            .interact()
-            .context("failed to read environment selection")?;
+        let index = prompt::select("Select an environment", &items, "re-run with --env <name>")?;
         Ok(candidates[index].clone())
     }
 }
@@ -27,7 +27,7 @@ use crate::commands::up::plan::ResolvedEnvironment;
 use crate::preferences::{EnvRef, PreferenceStore};
 
 /// Interactive chooser over candidate environments. Production uses a
-/// dialoguer select that errors when there's no TTY; tests script the choice.
+/// terminal select that errors when there's no TTY; tests script the choice.
 pub trait EnvPicker {
     fn pick(&self, candidates: &[EnvironmentListEntry]) -> Result<EnvironmentListEntry>;
 }
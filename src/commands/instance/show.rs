@@ -0,0 +1,296 @@
+//! `unisrv instance show <ref>` — one instance's state, containers, sizing
+//! and placement at a glance.
+
+use std::fmt::Write;
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use unisrv_api::ApiClient;
+use unisrv_api::models::{AuxContainer, InstanceConfiguration, InstanceDetailResponse};
+use uuid::Uuid;
+
+use super::resolve::lookup_instance;
+use super::sizing::{cpu_summary, memory_summary};
+use crate::commands::output::Output;
+use crate::commands::ui::format_relative;
+use crate::commands::up::plan::ResolvedEnvironment;
+
+pub async fn show(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    reference: &str,
+    output: &Output,
+) -> Result<()> {
+    let detail = match Uuid::parse_str(reference.trim()) {
+        // With a full id the detail needn't wait on the lookup. The lookup
+        // still runs, and its error wins, so a miss reads as it always has.
+        Ok(id) => {
+            let (found, detail) = tokio::join!(
+                lookup_instance(client, env.id, reference),
+                client.get_instance(env.id, id, true, true)
+            );
+            found?;
+            detail?
+        }
+        Err(_) => {
+            let instance = lookup_instance(client, env.id, reference).await?;
+            client.get_instance(env.id, instance.id, true, true).await?
+        }
+    };
+    match output {
+        Output::Json | Output::Jq(_) => output.print_json(&detail),
+        Output::Template(template) => {
+            println!("{}", template.render(&detail)?);
+            Ok(())
+        }
+        Output::Table => {
+            print!("{}", render(&detail, chrono::Utc::now().naive_utc()));
+            Ok(())
+        }
+    }
+}
+
+/// Plain-text summary. Pure so it can be asserted on without a terminal.
+fn render(detail: &InstanceDetailResponse, now: NaiveDateTime) -> String {
+    let mut out = String::new();
+    match &detail.name {
+        Some(name) => {
+            let _ = writeln!(out, "{name} ({})", detail.id);
+        }
+        None => {
+            let _ = writeln!(out, "{}", detail.id);
+        }
+    }
+    let mut state = detail.state.0.clone();
+    if let Some(code) = detail.exit_code {
+        let _ = write!(state, " (exit {code})");
+    }
+    if let Some(reason) = &detail.exit_reason {
+        let _ = write!(state, ": {reason}");
+    }
+    let _ = writeln!(out, "  state:       {state}");
+    // An unparseable configuration (a newer backend shape) still shows the
+    // rest rather than failing the whole command.
+    match serde_json::from_value::<InstanceConfiguration>(detail.configuration.clone()) {
+        Ok(config) => render_configuration(&mut out, &config),
+        Err(_) => {
+            let _ = writeln!(out, "  image:       (unrecognised configuration)");
+        }
+    }
+    if let Some(resources) = &detail.resources {
+        let _ = writeln!(
+            out,
+            "  cpu:         {}",
+            cpu_summary(resources.vcpu_count, resources.vcpu_ratio)
+        );
+        let _ = writeln!(
+            out,
+            "  memory:      {}",
+            memory_summary(resources.memory_mb, resources.memory_request_mb)
+        );
+    }
+    if let Some(ip) = &detail.network_ip {
+        let _ = writeln!(out, "  network:     {ip}");
+    }
+    if let Some(deployment) = &detail.deployment {
+        let _ = writeln!(out, "  deployment:  {}", deployment.name);
+    }
+    for target in detail.service_targets.iter().flatten() {
+        let _ = writeln!(
+            out,
+            "  service:     {} \u{2192} :{}",
+            target.service_name, target.instance_port
+        );
+    }
+    for port in detail.proxied_ports.iter().flatten() {
+        let _ = writeln!(
+            out,
+            "  tcp proxy:   {} \u{2192} :{}",
+            port.external_address, port.port
+        );
+    }
+    for (key, value) in detail.annotations.iter().flatten() {
+        let _ = writeln!(out, "  annotation:  {key}={value}");
+    }
+    let _ = writeln!(
+        out,
+        "  created:     {}",
+        format_relative(detail.created_at, now)
+    );
+    out
+}
+
+fn render_configuration(out: &mut String, config: &InstanceConfiguration) {
+    let _ = writeln!(out, "  image:       {}", config.container_image);
+    if let Some(args) = &config.args {
+        let _ = writeln!(out, "  args:        {args:?}");
+    }
+    if let Some(hostname) = &config.hostname {
+        let _ = writeln!(out, "  hostname:    {hostname}");
+    }
+    render_containers(out, "init", config.init_containers.as_deref());
+    render_containers(out, "sidecars", config.sidecars.as_deref());
+}
+
+/// One line per container, init containers in the order they run.
+fn render_containers(out: &mut String, label: &str, containers: Option<&[AuxContainer]>) {
+    let Some(containers) = containers.filter(|c| !c.is_empty()) else {
+        return;
+    };
+    let _ = writeln!(out, "  {label}:");
+    for container in containers {
+        match &container.args {
+            Some(args) => {
+                let _ = writeln!(out, "    {} {args:?}", container.image);
+            }
+            None => {
+                let _ = writeln!(out, "    {}", container.image);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::up::plan::test_env;
+    use serde_json::json;
+    use std::collections::BTreeMap;
+    use unisrv_api::ApiError;
+    use unisrv_api::models::{
+        DeploymentInfo, InstanceListEntry, InstanceListResponse, InstanceResources, InstanceState,
+    };
+    use unisrv_api::test_support::MockApiClient;
+
+    fn detail(configuration: serde_json::Value) -> InstanceDetailResponse {
+        InstanceDetailResponse {
+            id: Uuid::nil(),
+            name: Some("api-1".into()),
+            node_id: Uuid::nil(),
+            state: InstanceState("running".into()),
+            exit_code: None,
+            exit_reason: None,
+            configuration,
+            created_at: NaiveDateTime::default(),
+            updated_at: NaiveDateTime::default(),
+            network_id: None,
+            network_ip: Some("10.0.0.4".into()),
+            deployment: Some(DeploymentInfo {
+                id: Uuid::nil(),
+                name: "api".into(),
+            }),
+            service_targets: None,
+            proxied_ports: None,
+            resources: None,
+            annotations: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn a_full_id_fetches_the_detail_alongside_the_lookup() {
+        let id = Uuid::new_v4();
+        let mock = MockApiClient::logged_in()
+            .with_list_instances(Ok(InstanceListResponse {
+                instances: vec![InstanceListEntry {
+                    id,
+                    name: Some("api-1".into()),
+                    state: InstanceState("running".into()),
+                    container_image: "myapp:1".into(),
+                    created_at: NaiveDateTime::default(),
+                    deployment: None,
+                    retained_until: None,
+                }],
+            }))
+            .push_get_instance(Ok(detail(json!({}))));
+        show(&mock, &test_env(), &id.to_string(), &Output::Json)
+            .await
+            .unwrap();
+        let calls = mock.calls.lock().unwrap();
+        assert_eq!(calls.get_instance_calls.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn an_unknown_id_reads_as_a_lookup_miss() {
+        let id = Uuid::new_v4();
+        let mock = MockApiClient::logged_in()
+            .with_list_instances(Ok(InstanceListResponse { instances: vec![] }))
+            .push_get_instance(Err(ApiError::Server {
+                status: 404,
+                reason: "not found".into(),
+            }));
+        let err = show(&mock, &test_env(), &id.to_string(), &Output::Json)
+            .await
+            .unwrap_err();
+        assert!(
+            format!("{err:#}").contains(&format!("no instance with id {id}")),
+            "{err:#}"
+        );
+    }
+
+    #[test]
+    fn lists_init_containers_in_order_and_sidecars() {
+        let out = render(
+            &detail(json!({
+                "container_image": "myapp:1",
+                "init_containers": [
+                    {"image": "myapp:1", "args": ["migrate"]},
+                    {"image": "busybox:1"},
+                ],
+                "sidecars": [{"image": "fluent-bit:2"}],
+            })),
+            NaiveDateTime::default(),
+        );
+
+        assert!(out.starts_with("api-1 (00000000-"), "{out}");
+        assert!(out.contains("image:       myapp:1\n"), "{out}");
+        assert!(
+            out.contains("  init:\n    myapp:1 [\"migrate\"]\n    busybox:1\n"),
+            "{out}"
+        );
+        assert!(out.contains("  sidecars:\n    fluent-bit:2\n"), "{out}");
+        assert!(out.contains("network:     10.0.0.4"), "{out}");
+        assert!(out.contains("deployment:  api"), "{out}");
+    }
+
+    #[test]
+    fn plain_instance_omits_container_sections() {
+        let mut d = detail(json!({ "container_image": "alpine:3" }));
+        d.state = InstanceState("exited".into());
+        d.exit_code = Some(1);
+        d.annotations = Some(BTreeMap::from([(
+            "unisrv.dev/image-verify".into(),
+            "skipped".into(),
+        )]));
+        let out = render(&d, NaiveDateTime::default());
+        assert!(out.contains("state:       exited (exit 1)"), "{out}");
+        assert!(
+            out.contains("annotation:  unisrv.dev/image-verify=skipped"),
+            "{out}"
+        );
+        assert!(!out.contains("init"), "{out}");
+        assert!(!out.contains("sidecars"), "{out}");
+
+        let out = render(&detail(json!({ "image": 3 })), NaiveDateTime::default());
+        assert!(out.contains("(unrecognised configuration)"), "{out}");
+    }
+
+    #[test]
+    fn shows_guaranteed_and_burst_resources() {
+        let mut d = detail(json!({ "container_image": "alpine:3" }));
+        d.resources = Some(InstanceResources {
+            vcpu_count: 2,
+            vcpu_ratio: 0.25,
+            memory_mb: 2048,
+            memory_request_mb: Some(512),
+        });
+        let out = render(&d, NaiveDateTime::default());
+        assert!(
+            out.contains("cpu:         0.5 guaranteed, bursts to 2 vCPUs"),
+            "{out}"
+        );
+        assert!(
+            out.contains("memory:      512MB guaranteed, bursts to 2048MB"),
+            "{out}"
+        );
+    }
+}
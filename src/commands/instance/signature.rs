@@ -0,0 +1,145 @@
+//! `--verify-signature`: refuse images without a cosign signature from an
+//! expected signer before provisioning.
+//!
+//! Each image's tag is resolved to a digest while it's verified against its
+//! registry, and `cosign verify` checks that digest's keyless signature: its
+//! certificate must name `--certificate-identity`, issued through
+//! `--certificate-oidc-issuer`. The instance is then started from the digests
+//! rather than the tags, so what runs is what was verified even if a tag
+//! moves in between. cosign itself must be installed; the signer is recorded
+//! on the instance as [`SIGNATURE_ANNOTATION`].
+
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+
+/// The annotation naming who signed an instance's images.
+pub const SIGNATURE_ANNOTATION: &str = "unisrv.dev/image-signer";
+
+/// Whose signature images must carry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signer {
+    /// The identity in the signing certificate, e.g. the workflow that
+    /// signed it or an email address.
+    pub identity: String,
+    /// The OIDC issuer that vouched for `identity`.
+    pub issuer: String,
+}
+
+/// Runs `cosign verify`. A trait so tests don't need cosign or a registry.
+pub trait Cosign {
+    fn verify(&self, reference: &str, signer: &Signer) -> Result<()>;
+}
+
+pub struct SystemCosign;
+
+impl Cosign for SystemCosign {
+    fn verify(&self, reference: &str, signer: &Signer) -> Result<()> {
+        let output = Command::new("cosign")
+            .args(["verify", "--certificate-identity", &signer.identity])
+            .args(["--certificate-oidc-issuer", &signer.issuer])
+            .arg(reference)
+            .stdin(Stdio::null())
+            .output()
+            .context("failed to run cosign; is it installed?")?;
+        if output.status.success() {
+            return Ok(());
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr
+            .lines()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+            .unwrap_or("cosign verify failed");
+        bail!("{reason}")
+    }
+}
+
+/// `image` (with or without a tag) pinned to `digest`, as cosign wants it.
+pub fn by_digest(image: &str, digest: &str) -> String {
+    let name = image.split('@').next().unwrap_or_default();
+    let name = match name.rsplit_once(':') {
+        Some((name, tag)) if !tag.contains('/') => name,
+        _ => name,
+    };
+    format!("{name}@{digest}")
+}
+
+/// Refuse unless each of `images` is signed by `signer` at its digest in
+/// `digests`.
+pub fn verify_signatures(
+    cosign: &dyn Cosign,
+    signer: &Signer,
+    images: &[&str],
+    digests: &[String],
+) -> Result<()> {
+    for (image, digest) in images.iter().zip(digests) {
+        cosign
+            .verify(&by_digest(image, digest), signer)
+            .with_context(|| {
+                format!(
+                    "{image} isn't signed by {} (issuer {})",
+                    signer.identity, signer.issuer
+                )
+            })?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Trusts only what's listed, recording every reference it's asked about.
+    struct Signed(&'static [&'static str], Mutex<Vec<String>>);
+
+    impl Cosign for Signed {
+        fn verify(&self, reference: &str, _signer: &Signer) -> Result<()> {
+            self.1.lock().unwrap().push(reference.to_string());
+            if self.0.contains(&reference) {
+                return Ok(());
+            }
+            bail!("no matching signatures")
+        }
+    }
+
+    fn signer() -> Signer {
+        Signer {
+            identity: "ci@acme.com".into(),
+            issuer: "https://accounts.google.com".into(),
+        }
+    }
+
+    #[test]
+    fn pins_references_to_their_digest() {
+        assert_eq!(
+            by_digest("ghcr.io/acme/app:1", "sha256:a"),
+            "ghcr.io/acme/app@sha256:a"
+        );
+        assert_eq!(
+            by_digest("localhost:5000/app", "sha256:a"),
+            "localhost:5000/app@sha256:a"
+        );
+        assert_eq!(by_digest("app:1@sha256:a", "sha256:a"), "app@sha256:a");
+    }
+
+    #[test]
+    fn verifies_each_image_at_its_digest() {
+        let cosign = Signed(&["app@sha256:a"], Mutex::default());
+        let digests = ["sha256:a".to_string(), "sha256:b".to_string()];
+        verify_signatures(&cosign, &signer(), &["app:1"], &digests[..1]).unwrap();
+
+        let err =
+            verify_signatures(&cosign, &signer(), &["app:1", "shipper:2"], &digests).unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "shipper:2 isn't signed by ci@acme.com (issuer https://accounts.google.com): \
+             no matching signatures"
+        );
+        assert_eq!(
+            *cosign.1.lock().unwrap(),
+            ["app@sha256:a", "app@sha256:a", "shipper@sha256:b"]
+        );
+    }
+}
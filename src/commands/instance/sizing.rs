@@ -0,0 +1,220 @@
+//! Sizing for `unisrv instance run`: what an instance is guaranteed
+//! (`--cpu-request`, `--memory-request`) next to what it may burst to
+//! (`--cpu-limit`, `--memory-limit`, aliases of `--vcpus` and `--memory`).
+//!
+//! The platform guarantees CPU as a share of every vCPU, from a fixed set of
+//! tiers, so a CPU request is only valid if it's one of those shares of the
+//! limit: with 2 vCPUs, 0.25, 0.5, 1 or 2 cores. Memory is guaranteed
+//! directly. Both are checked here so a bad pairing fails before anything is
+//! provisioned.
+
+use anyhow::{Result, bail};
+
+use crate::commands::up::config::{
+    MAX_MEMORY_MB, MAX_VCPUS, MIN_MEMORY_MB, MIN_VCPUS, VCPU_RATIO_TIERS,
+};
+use crate::commands::up::defaults::{DEFAULT_MEMORY_MB, DEFAULT_VCPU_COUNT, DEFAULT_VCPU_RATIO};
+
+/// Sizing as given on the command line.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SizingFlags {
+    pub cpu_limit: Option<u8>,
+    /// Guaranteed cores, across all vCPUs.
+    pub cpu_request: Option<f64>,
+    pub memory_limit_mb: Option<u32>,
+    pub memory_request_mb: Option<u32>,
+}
+
+/// Sizing as the provision request takes it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sizing {
+    pub vcpu_count: u8,
+    pub vcpu_ratio: f64,
+    pub memory_mb: u32,
+    /// `None` when the whole limit is guaranteed.
+    pub memory_request_mb: Option<u32>,
+}
+
+impl SizingFlags {
+    /// Validate the flags against each other and the scheduler's bounds,
+    /// filling in defaults for what wasn't given.
+    pub fn resolve(&self) -> Result<Sizing> {
+        let vcpu_count = self.cpu_limit.unwrap_or(DEFAULT_VCPU_COUNT);
+        if !(MIN_VCPUS..=MAX_VCPUS).contains(&u64::from(vcpu_count)) {
+            bail!(
+                "--cpu-limit must be between {MIN_VCPUS} and {MAX_VCPUS} vCPUs, got {vcpu_count}"
+            );
+        }
+        let vcpu_ratio = match self.cpu_request {
+            None => DEFAULT_VCPU_RATIO,
+            Some(request) => cpu_ratio(request, vcpu_count)?,
+        };
+
+        let memory_mb = self.memory_limit_mb.unwrap_or(DEFAULT_MEMORY_MB);
+        if !(MIN_MEMORY_MB..=MAX_MEMORY_MB).contains(&u64::from(memory_mb)) {
+            bail!("--memory-limit must be between 128MB and 32GB, got {memory_mb}MB");
+        }
+        let memory_request_mb = match self.memory_request_mb {
+            Some(request) if request > memory_mb => bail!(
+                "--memory-request {request}MB exceeds the {memory_mb}MB memory limit; \
+                 raise it with --memory-limit"
+            ),
+            Some(request) if u64::from(request) < MIN_MEMORY_MB => {
+                bail!("--memory-request must be at least {MIN_MEMORY_MB}MB, got {request}MB")
+            }
+            // Guaranteeing the whole limit is the platform's default.
+            Some(request) if request == memory_mb => None,
+            request => request,
+        };
+
+        Ok(Sizing {
+            vcpu_count,
+            vcpu_ratio,
+            memory_mb,
+            memory_request_mb,
+        })
+    }
+}
+
+/// The per-vCPU share that guarantees `request` cores out of `limit`.
+fn cpu_ratio(request: f64, limit: u8) -> Result<f64> {
+    let limit_cores = f64::from(limit);
+    if !request.is_finite() || request <= 0.0 {
+        bail!("--cpu-request must be a positive number of cores, got {request}");
+    }
+    if request > limit_cores {
+        bail!("--cpu-request {request} exceeds the {limit} vCPU limit; raise it with --cpu-limit");
+    }
+    // A user-typed decimal times a tier isn't always exact in binary.
+    if let Some(ratio) = VCPU_RATIO_TIERS
+        .into_iter()
+        .find(|ratio| (ratio * limit_cores - request).abs() < 1e-9)
+    {
+        return Ok(ratio);
+    }
+    let allowed: Vec<String> = VCPU_RATIO_TIERS
+        .iter()
+        .map(|ratio| (ratio * limit_cores).to_string())
+        .collect();
+    bail!(
+        "--cpu-request {request} isn't a guaranteed share the platform offers; with a \
+         {limit} vCPU limit it must be one of {}",
+        allowed.join(", ")
+    )
+}
+
+/// One-line CPU summary for `instance show`, e.g. `0.5 guaranteed, bursts to
+/// 2 vCPUs`.
+pub fn cpu_summary(vcpu_count: u8, vcpu_ratio: f64) -> String {
+    let vcpus = if vcpu_count == 1 { "vCPU" } else { "vCPUs" };
+    if vcpu_ratio >= 1.0 {
+        return format!("{vcpu_count} dedicated {vcpus}");
+    }
+    let guaranteed = f64::from(vcpu_count) * vcpu_ratio;
+    format!("{guaranteed} guaranteed, bursts to {vcpu_count} {vcpus}")
+}
+
+/// One-line memory summary for `instance show`.
+pub fn memory_summary(memory_mb: u32, memory_request_mb: Option<u32>) -> String {
+    match memory_request_mb {
+        Some(request) if request < memory_mb => {
+            format!("{request}MB guaranteed, bursts to {memory_mb}MB")
+        }
+        _ => format!("{memory_mb}MB"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flags(cpu: (Option<u8>, Option<f64>), memory: (Option<u32>, Option<u32>)) -> SizingFlags {
+        SizingFlags {
+            cpu_limit: cpu.0,
+            cpu_request: cpu.1,
+            memory_limit_mb: memory.0,
+            memory_request_mb: memory.1,
+        }
+    }
+
+    #[test]
+    fn defaults_match_a_plain_run() {
+        let sizing = SizingFlags::default().resolve().unwrap();
+        assert_eq!(
+            sizing,
+            Sizing {
+                vcpu_count: DEFAULT_VCPU_COUNT,
+                vcpu_ratio: DEFAULT_VCPU_RATIO,
+                memory_mb: DEFAULT_MEMORY_MB,
+                memory_request_mb: None,
+            }
+        );
+    }
+
+    #[test]
+    fn cpu_request_becomes_the_share_of_each_vcpu() {
+        let sizing = flags((Some(2), Some(0.5)), (None, None)).resolve().unwrap();
+        assert_eq!((sizing.vcpu_count, sizing.vcpu_ratio), (2, 0.25));
+        let sizing = flags((Some(3), Some(0.375)), (None, None))
+            .resolve()
+            .unwrap();
+        assert_eq!(sizing.vcpu_ratio, 0.125);
+        let sizing = flags((Some(4), Some(4.0)), (None, None)).resolve().unwrap();
+        assert_eq!(sizing.vcpu_ratio, 1.0);
+    }
+
+    #[test]
+    fn cpu_request_must_fit_the_limit_and_a_tier() {
+        let err = flags((None, Some(2.0)), (None, None))
+            .resolve()
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("exceeds the 1 vCPU limit"),
+            "{err}"
+        );
+
+        let err = flags((Some(2), Some(0.3)), (None, None))
+            .resolve()
+            .unwrap_err();
+        assert!(err.to_string().contains("one of 0.25, 0.5, 1, 2"), "{err}");
+        assert!(flags((Some(2), Some(0.0)), (None, None)).resolve().is_err());
+        assert!(flags((Some(33), None), (None, None)).resolve().is_err());
+    }
+
+    #[test]
+    fn memory_request_must_fit_the_limit() {
+        let sizing = flags((None, None), (Some(2048), Some(512)))
+            .resolve()
+            .unwrap();
+        assert_eq!(
+            (sizing.memory_mb, sizing.memory_request_mb),
+            (2048, Some(512))
+        );
+
+        let err = flags((None, None), (Some(512), Some(1024)))
+            .resolve()
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("exceeds the 512MB memory limit"),
+            "{err}"
+        );
+        assert!(flags((None, None), (None, Some(64))).resolve().is_err());
+
+        // Requesting the whole limit is the same as not asking.
+        let sizing = flags((None, None), (Some(1024), Some(1024)))
+            .resolve()
+            .unwrap();
+        assert_eq!(sizing.memory_request_mb, None);
+    }
+
+    #[test]
+    fn summaries_read_naturally() {
+        assert_eq!(cpu_summary(2, 0.25), "0.5 guaranteed, bursts to 2 vCPUs");
+        assert_eq!(cpu_summary(1, 1.0), "1 dedicated vCPU");
+        assert_eq!(
+            memory_summary(2048, Some(512)),
+            "512MB guaranteed, bursts to 2048MB"
+        );
+        assert_eq!(memory_summary(512, None), "512MB");
+    }
+}
@@ -0,0 +1,194 @@
+//! `unisrv instance stop` and `unisrv instance restore` — stop an instance,
+//! optionally keeping it around to bring back.
+//!
+//! A plain stop is final: the instance's configuration and disk go with it,
+//! so it asks first. `--keep 24h` stops it the same way but has the server
+//! retain both for that long; until then the instance is listed (with `-a`)
+//! with a RETAINED-UNTIL time, and `restore` starts it again as it was, under
+//! the same id. After the window it's gone as if stopped plainly.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use chrono::Utc;
+use unisrv_api::ApiClient;
+use unisrv_api::models::{InstanceDeprovisionRequest, InstanceListEntry};
+
+use super::resolve::lookup_instance;
+use crate::commands::confirm::Guard;
+use crate::commands::ui::format_relative;
+use crate::commands::up::plan::ResolvedEnvironment;
+use crate::commands::up::ready::format_duration;
+
+/// The longest the server retains a stopped instance.
+const MAX_KEEP: Duration = Duration::from_secs(30 * 86_400);
+
+/// `unisrv instance stop <ref> [--keep <duration>]`.
+pub async fn stop(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    reference: &str,
+    keep: Option<Duration>,
+    yes: bool,
+    guard: &Guard<'_>,
+) -> Result<()> {
+    if let Some(keep) = keep
+        && keep > MAX_KEEP
+    {
+        bail!(
+            "--keep can be at most {}, got {}",
+            format_duration(MAX_KEEP),
+            format_duration(keep)
+        );
+    }
+    let instance = lookup_instance(client, env.id, reference).await?;
+    let label = label(&instance);
+    let confirmed = match keep {
+        // Nothing is lost until the window ends, so only a protected
+        // environment asks.
+        Some(_) => guard.confirm(&format!("stop {label}"), &label)?,
+        None => guard.confirm_or_ask(
+            &format!("stop {label} and discard its disk"),
+            &label,
+            &format!(
+                "Stop {label}? Its configuration and disk are discarded; pass --keep to be able to restore it."
+            ),
+            yes,
+        )?,
+    };
+    if !confirmed {
+        println!("Aborted.");
+        return Ok(());
+    }
+
+    let req = keep.map(|keep| InstanceDeprovisionRequest {
+        timeout_ms: None,
+        retain_secs: Some(keep.as_secs()),
+    });
+    client
+        .deprovision_instance(env.id, instance.id, req)
+        .await
+        .with_context(|| format!("failed to stop {label}"))?;
+    match keep {
+        Some(keep) => println!(
+            "\u{2713} Stopped {label}; restorable for {} with `unisrv instance restore {}`.",
+            format_duration(keep),
+            instance.id
+        ),
+        None => println!("\u{2713} Stopped {label}."),
+    }
+    Ok(())
+}
+
+/// `unisrv instance restore <ref>`.
+pub async fn restore(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    reference: &str,
+) -> Result<()> {
+    let instance = lookup_instance(client, env.id, reference).await?;
+    let label = label(&instance);
+    let now = Utc::now();
+    match instance.retained_until {
+        None => bail!(
+            "{label} can't be restored: it's {} and wasn't stopped with --keep",
+            instance.state.0
+        ),
+        Some(until) if until <= now => bail!(
+            "{label} can't be restored: it was kept until {}",
+            format_relative(until, now)
+        ),
+        Some(_) => {}
+    }
+    let restored = client
+        .restore_instance(env.id, instance.id)
+        .await
+        .with_context(|| format!("failed to restore {label}"))?;
+    println!("\u{2713} Restored {label} ({}).", restored.state.0);
+    Ok(())
+}
+
+/// The instance's name, or its short id when it has none.
+fn label(instance: &InstanceListEntry) -> String {
+    instance
+        .name
+        .clone()
+        .unwrap_or_else(|| instance.id.to_string()[..8].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::up::plan::test_env;
+    use chrono::NaiveDateTime;
+    use unisrv_api::models::{InstanceListResponse, InstanceState};
+    use unisrv_api::test_support::MockApiClient;
+    use uuid::Uuid;
+
+    fn instance(state: &str, retained_until: Option<chrono::DateTime<Utc>>) -> InstanceListEntry {
+        InstanceListEntry {
+            id: Uuid::new_v4(),
+            name: Some("api-1".into()),
+            state: InstanceState(state.into()),
+            container_image: "api:1".into(),
+            created_at: NaiveDateTime::default(),
+            deployment: None,
+            retained_until,
+        }
+    }
+
+    fn listing(instance: &InstanceListEntry) -> InstanceListResponse {
+        InstanceListResponse {
+            instances: vec![instance.clone()],
+        }
+    }
+
+    #[tokio::test]
+    async fn keep_asks_the_server_to_retain_the_instance() {
+        let running = instance("running", None);
+        let mock = MockApiClient::logged_in()
+            .push_find_instances_by_name(Ok(listing(&running)))
+            .push_deprovision_instance(Ok(()));
+        let guard = Guard::unprotected();
+        let too_long = Some(Duration::from_secs(90 * 86_400));
+        let err = stop(&mock, &test_env(), "api-1", too_long, true, &guard)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("at most 720h"), "{err:#}");
+
+        let keep = Some(Duration::from_secs(24 * 3600));
+        stop(&mock, &test_env(), "api-1", keep, false, &guard)
+            .await
+            .unwrap();
+        let calls = mock.calls.lock().unwrap();
+        let (_, id, req) = &calls.deprovision_instance_calls[0];
+        assert_eq!(*id, running.id);
+        assert_eq!(req.as_ref().unwrap().retain_secs, Some(86_400));
+    }
+
+    #[tokio::test]
+    async fn only_a_retained_instance_is_restored() {
+        let kept = instance("stopped", Some(Utc::now() + chrono::Duration::hours(3)));
+        let mock = MockApiClient::logged_in()
+            .push_find_instances_by_name(Ok(listing(&kept)))
+            .push_restore_instance(Ok(instance("provisioning", None)));
+        restore(&mock, &test_env(), "api-1").await.unwrap();
+        assert_eq!(
+            mock.calls.lock().unwrap().restore_instance_calls[0].1,
+            kept.id
+        );
+
+        let gone = instance("stopped", None);
+        let mock = MockApiClient::logged_in().push_find_instances_by_name(Ok(listing(&gone)));
+        let err = restore(&mock, &test_env(), "api-1").await.unwrap_err();
+        assert!(
+            err.to_string().contains("wasn't stopped with --keep"),
+            "{err:#}"
+        );
+
+        let lapsed = instance("stopped", Some(Utc::now() - chrono::Duration::hours(1)));
+        let mock = MockApiClient::logged_in().push_find_instances_by_name(Ok(listing(&lapsed)));
+        let err = restore(&mock, &test_env(), "api-1").await.unwrap_err();
+        assert!(err.to_string().contains("was kept until"), "{err:#}");
+    }
+}
@@ -0,0 +1,94 @@
+//! Checking `instance run`'s images against their registries before anything
+//! is provisioned, so a mistyped tag is refused here rather than surfacing as
+//! an instance that never starts.
+//!
+//! A registry that can't be reached fails the check too, since it can't say
+//! the image is there. `--skip-image-verify` starts the instance anyway, for
+//! redeploying a known-good image through a registry incident; the skip is
+//! recorded on the instance as [`IMAGE_VERIFY_ANNOTATION`], where
+//! `instance show` lists it.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Result, anyhow, bail};
+use unisrv_api::ApiError;
+
+use crate::commands::up::lock::DigestResolver;
+
+/// The annotation recording that an instance's images went unverified.
+pub const IMAGE_VERIFY_ANNOTATION: &str = "unisrv.dev/image-verify";
+
+/// The annotations for a run with `--skip-image-verify`.
+pub fn skipped() -> BTreeMap<String, String> {
+    BTreeMap::from([(IMAGE_VERIFY_ANNOTATION.to_string(), "skipped".to_string())])
+}
+
+/// Refuse to go on unless every one of `images` resolves in its registry,
+/// returning the digests they resolve to.
+pub async fn verify_images(resolver: &dyn DigestResolver, images: &[&str]) -> Result<Vec<String>> {
+    let mut digests = Vec::with_capacity(images.len());
+    for image in images {
+        let err = match resolver.digest(image).await {
+            Ok(digest) => {
+                digests.push(digest);
+                continue;
+            }
+            Err(err) => err,
+        };
+        if let Some(ApiError::Server { status: 404, .. }) = err.downcast_ref::<ApiError>() {
+            bail!("{image} isn't in its registry; check the name and tag");
+        }
+        return Err(anyhow!(
+            "couldn't verify {image} with its registry: {err:#}; if the registry is down and \
+             the image is known to be good, rerun with --skip-image-verify"
+        ));
+    }
+    Ok(digests)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+
+    /// Knows `app:1`, has no `app:2`, and is unreachable for anything on
+    /// `down.example.com`.
+    struct Registry;
+
+    #[async_trait]
+    impl DigestResolver for Registry {
+        async fn digest(&self, image: &str) -> Result<String> {
+            match image {
+                "app:1" => Ok("sha256:abc".into()),
+                "app:2" => Err(ApiError::Server {
+                    status: 404,
+                    reason: "manifest unknown".into(),
+                }
+                .into()),
+                _ => Err(ApiError::Other(anyhow!("connection refused")).into()),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn refuses_missing_images_and_suggests_skipping_an_outage() {
+        assert_eq!(
+            verify_images(&Registry, &["app:1"]).await.unwrap(),
+            ["sha256:abc"]
+        );
+
+        let err = verify_images(&Registry, &["app:1", "app:2"])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "app:2 isn't in its registry; check the name and tag"
+        );
+
+        let err = verify_images(&Registry, &["down.example.com/app:1"])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("connection refused"), "{err:#}");
+        assert!(err.to_string().contains("--skip-image-verify"), "{err:#}");
+    }
+}
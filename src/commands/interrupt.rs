@@ -0,0 +1,55 @@
+//! Ctrl-C for commands that run until stopped (`logs --follow`,
+//! `service stats --watch`).
+//!
+//! Left to the default handler, Ctrl-C kills the process mid-write: on
+//! Windows the console can be left without its cursor or with a half-printed
+//! line, and the exit status reads as a failure. Racing the stream against
+//! the signal instead lets it stop like the end of the stream would.
+
+use std::future::Future;
+
+use anyhow::Result;
+
+/// Runs `stream` until it finishes or Ctrl-C is pressed, which counts as
+/// finishing successfully.
+pub async fn until_interrupted(stream: impl Future<Output = Result<()>>) -> Result<()> {
+    until(stream, async {
+        // If the handler can't be installed, fall back to the default one.
+        if tokio::signal::ctrl_c().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    })
+    .await
+}
+
+async fn until(
+    stream: impl Future<Output = Result<()>>,
+    interrupt: impl Future<Output = ()>,
+) -> Result<()> {
+    tokio::select! {
+        result = stream => result,
+        () = interrupt => {
+            console::Term::stderr().show_cursor().ok();
+            eprintln!();
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn an_interrupt_ends_the_stream_cleanly_but_errors_still_surface() {
+        let interrupted = until(std::future::pending(), async {}).await;
+        assert!(interrupted.is_ok());
+
+        let failed = until(
+            async { Err(anyhow::anyhow!("connection lost")) },
+            std::future::pending(),
+        )
+        .await;
+        assert_eq!(failed.unwrap_err().to_string(), "connection lost");
+    }
+}
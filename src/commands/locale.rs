@@ -0,0 +1,249 @@
+//! Locale-aware dates and numbers for human-readable output.
+//!
+//! Tables and summaries format absolute dates and large numbers the way the
+//! reader's locale writes them — `03/14/2025` and `7,560` for `en-US`,
+//! `14.03.2025` and `7.560` for `de` — chosen with `--locale`, `"locale"` in
+//! `~/.unisrv/config.json`, or else the system locale (`LC_ALL`,
+//! `LC_NUMERIC`, `LC_TIME`, `LANG`). JSON and templated output are never
+//! localized; they're for machines.
+//!
+//! Only the conventions a table needs are kept, for a fixed set of languages
+//! rather than a full locale database. Without a locale, or with `C`/`POSIX`,
+//! dates are ISO 8601 and numbers ungrouped.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer};
+
+static CURRENT: OnceLock<Locale> = OnceLock::new();
+
+/// Use `locale` for the rest of the process. Only the first call counts.
+pub fn set(locale: Locale) {
+    let _ = CURRENT.set(locale);
+}
+
+/// The locale chosen with [`set`], or the default `C` locale before then.
+pub fn current() -> &'static Locale {
+    CURRENT.get_or_init(Locale::default)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Locale {
+    tag: String,
+    /// Thousands separator; `None` leaves numbers ungrouped.
+    grouping: Option<char>,
+    decimal: char,
+    /// `strftime` formats.
+    date: &'static str,
+    time: &'static str,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale {
+            tag: "C".into(),
+            grouping: None,
+            decimal: '.',
+            date: "%Y-%m-%d",
+            time: "%H:%M",
+        }
+    }
+}
+
+impl Locale {
+    /// The system locale, from the first of `LC_ALL`, `LC_NUMERIC`, `LC_TIME`
+    /// and `LANG` that's set, and the variable it came from. `None` when none
+    /// is set or it names a locale this CLI doesn't know.
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Option<(Self, &'static str)> {
+        let (name, value) = ["LC_ALL", "LC_NUMERIC", "LC_TIME", "LANG"]
+            .into_iter()
+            .filter_map(|name| var(name).map(|value| (name, value)))
+            .find(|(_, value)| !value.is_empty())?;
+        Some((value.parse().ok()?, name))
+    }
+
+    /// An integer with thousands separators, e.g. `7,560`.
+    pub fn format_int(&self, n: u64) -> String {
+        self.group(&n.to_string())
+    }
+
+    /// A number with `places` decimals, e.g. `1.234,50`.
+    pub fn format_decimal(&self, n: f64, places: usize) -> String {
+        let fixed = format!("{:.*}", places, n.abs());
+        let (int, frac) = fixed.split_once('.').unwrap_or((&fixed, ""));
+        let mut out = String::new();
+        if n.is_sign_negative() && fixed.chars().any(|c| c.is_ascii_digit() && c != '0') {
+            out.push('-');
+        }
+        out.push_str(&self.group(int));
+        if !frac.is_empty() {
+            out.push(self.decimal);
+            out.push_str(frac);
+        }
+        out
+    }
+
+    /// A date and time of day in UTC, e.g. `2025-03-14 09:30 UTC`.
+    pub fn format_datetime(&self, when: DateTime<Utc>) -> String {
+        format!("{} {} UTC", when.format(self.date), when.format(self.time))
+    }
+
+    fn group(&self, digits: &str) -> String {
+        let Some(sep) = self.grouping else {
+            return digits.to_string();
+        };
+        let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+        for (i, c) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                out.push(sep);
+            }
+            out.push(c);
+        }
+        out
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.tag)
+    }
+}
+
+impl FromStr for Locale {
+    type Err = String;
+
+    /// A BCP 47 tag (`de-CH`) or a POSIX locale name (`de_CH.UTF-8`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.split(['.', '@']).next().unwrap_or_default();
+        if matches!(name, "C" | "POSIX") {
+            return Ok(Locale::default());
+        }
+        let mut parts = name.split(['-', '_']);
+        let language = parts.next().unwrap_or_default().to_ascii_lowercase();
+        let region = parts.next().map(str::to_ascii_uppercase);
+        let Some((grouping, decimal, date, time)) = conventions(&language, region.as_deref())
+        else {
+            return Err(format!(
+                "unsupported locale {s:?} (expected e.g. en-US, en-GB, de, fr or ja)"
+            ));
+        };
+        Ok(Locale {
+            tag: match region {
+                Some(region) => format!("{language}-{region}"),
+                None => language,
+            },
+            grouping: Some(grouping),
+            decimal,
+            date,
+            time,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Locale {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Thousands separator, decimal mark, date and time formats.
+type Conventions = (char, char, &'static str, &'static str);
+
+fn conventions(language: &str, region: Option<&str>) -> Option<Conventions> {
+    // Much of Europe groups with a space; one that won't wrap a number apart.
+    const SPACE: char = '\u{a0}';
+    Some(match (language, region) {
+        ("en", None | Some("US")) => (',', '.', "%m/%d/%Y", "%-I:%M %p"),
+        ("en", Some("CA")) => (',', '.', "%Y-%m-%d", "%-I:%M %p"),
+        ("en", _) => (',', '.', "%d/%m/%Y", "%H:%M"),
+        ("de", Some("CH" | "LI")) => ('\'', '.', "%d.%m.%Y", "%H:%M"),
+        ("de", _) => ('.', ',', "%d.%m.%Y", "%H:%M"),
+        ("nl", _) => ('.', ',', "%d-%m-%Y", "%H:%M"),
+        ("da", _) => ('.', ',', "%d.%m.%Y", "%H.%M"),
+        ("es" | "it" | "pt", _) => ('.', ',', "%d/%m/%Y", "%H:%M"),
+        ("fr", _) => (SPACE, ',', "%d/%m/%Y", "%H:%M"),
+        ("sv", _) => (SPACE, ',', "%Y-%m-%d", "%H:%M"),
+        ("nb" | "nn" | "no" | "fi" | "pl" | "cs" | "ru" | "uk", _) => {
+            (SPACE, ',', "%d.%m.%Y", "%H:%M")
+        }
+        ("ja" | "zh", _) => (',', '.', "%Y/%m/%d", "%H:%M"),
+        ("ko", _) => (',', '.', "%Y. %m. %d.", "%H:%M"),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn locale(tag: &str) -> Locale {
+        tag.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_bcp47_tags_and_posix_names() {
+        assert_eq!(locale("de_CH.UTF-8").to_string(), "de-CH");
+        assert_eq!(locale("EN-gb").to_string(), "en-GB");
+        assert_eq!(locale("POSIX"), Locale::default());
+        assert_eq!(locale("C.UTF-8"), Locale::default());
+        assert!("tlh".parse::<Locale>().is_err());
+    }
+
+    #[test]
+    fn groups_thousands_and_marks_decimals() {
+        assert_eq!(locale("en-US").format_int(1_234_567), "1,234,567");
+        assert_eq!(locale("de").format_int(7560), "7.560");
+        assert_eq!(locale("de-CH").format_int(7560), "7'560");
+        assert_eq!(locale("fr").format_int(999), "999");
+        assert_eq!(locale("de").format_decimal(1234.5, 2), "1.234,50");
+        assert_eq!(locale("en").format_decimal(-0.001, 1), "0.0");
+        assert_eq!(locale("en").format_decimal(-1500.0, 0), "-1,500");
+        assert_eq!(Locale::default().format_decimal(12345.678, 2), "12345.68");
+    }
+
+    #[test]
+    fn formats_dates_in_the_locales_order() {
+        let when = Utc.with_ymd_and_hms(2025, 3, 14, 15, 30, 0).unwrap();
+        assert_eq!(
+            Locale::default().format_datetime(when),
+            "2025-03-14 15:30 UTC"
+        );
+        assert_eq!(
+            locale("en-US").format_datetime(when),
+            "03/14/2025 3:30 PM UTC"
+        );
+        assert_eq!(
+            locale("en-GB").format_datetime(when),
+            "14/03/2025 15:30 UTC"
+        );
+        assert_eq!(locale("de").format_datetime(when), "14.03.2025 15:30 UTC");
+        assert_eq!(locale("ja").format_datetime(when), "2025/03/14 15:30 UTC");
+    }
+
+    #[test]
+    fn system_locale_falls_back_to_c() {
+        let vars = |set: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                set.iter()
+                    .find(|(k, _)| *k == name)
+                    .map(|(_, v)| v.to_string())
+            }
+        };
+        assert_eq!(
+            Locale::from_vars(vars(&[("LANG", "de_DE.UTF-8"), ("LC_ALL", "")])),
+            Some((locale("de-DE"), "LANG"))
+        );
+        assert_eq!(
+            Locale::from_vars(vars(&[("LANG", "en_US"), ("LC_NUMERIC", "fr_FR")])),
+            Some((locale("fr-FR"), "LC_NUMERIC"))
+        );
+        assert_eq!(Locale::from_vars(vars(&[("LANG", "tlh")])), None);
+        assert_eq!(Locale::from_vars(vars(&[])), None);
+    }
+}
@@ -1,5 +1,8 @@
 use anyhow::Result;
 use unisrv_api::ApiClient;
+
+use crate::commands::prompt;
+use crate::commands::ui::emoji;
 use yapp::PasswordReader;
 
 pub async fn run(
@@ -9,9 +12,10 @@ pub async fn run(
 ) -> Result<()> {
     let username = match username {
         Some(u) => u.to_string(),
-        None => dialoguer::Input::new()
-            .with_prompt("Username")
-            .interact_text()?,
+        None => prompt::required_text(
+            "Username",
+            "pass --username, or set UNISRV_TOKEN to an API token instead of logging in",
+        )?,
     };
 
     let password = match password {
@@ -28,7 +32,10 @@ pub async fn run(
     };
 
     client.login(&username, &password).await?;
-    println!("\u{1f512} Successfully logged in as user: {username}");
+    println!(
+        "{}Successfully logged in as user: {username}",
+        emoji("\u{1f512}")
+    );
     Ok(())
 }
 
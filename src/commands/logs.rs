@@ -0,0 +1,465 @@
+//! `unisrv logs --app <deployment> | --service <service> [-f]` — the logs of
+//! every instance behind an app or service, merged into one time-ordered
+//! view for debugging the application as a whole.
+//!
+//! Each line is prefixed with its instance, and routed to stdout or stderr the
+//! same way `instance logs` routes it. History is fetched per instance and
+//! sorted by `timestamp_ms`. When following, frames from different instances
+//! arrive interleaved by network timing rather than by time, so they're held
+//! for a moment ([`HOLD`]) and released in timestamp order.
+
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, bail};
+use futures_util::StreamExt;
+use unisrv_api::ApiClient;
+use unisrv_api::models::{InstanceListEntry, LogMessage};
+use uuid::Uuid;
+
+use super::concurrent::{authenticate, fetch_each};
+use super::instance::list::is_active;
+use super::instance::logs::{Resubscribe, connect, emit, route};
+use super::instance::run::{announce_environment, current_environment};
+use super::interrupt::until_interrupted;
+use super::service::resolve::resolve_service;
+use super::up::plan::ResolvedEnvironment;
+
+/// How long a followed frame waits for earlier-stamped frames from other
+/// instances before it's printed.
+const HOLD: Duration = Duration::from_millis(500);
+
+/// Whose logs to show.
+pub enum Source {
+    /// Instances of the deployment with this name.
+    App(String),
+    /// Instances targeted by this service (name or UUID).
+    Service(String),
+}
+
+/// An instance whose logs are being shown, and the prefix its lines carry.
+struct Tail {
+    id: Uuid,
+    label: String,
+}
+
+pub async fn run(
+    client: &dyn ApiClient,
+    env_flag: Option<&str>,
+    source: Source,
+    follow: bool,
+) -> Result<()> {
+    let env = current_environment(client, env_flag).await?;
+    announce_environment(&env);
+
+    let tails = discover(client, &env, &source).await?;
+    let width = tails.iter().map(|t| t.label.len()).max().unwrap_or(0);
+    let prefixes: Vec<String> = tails
+        .iter()
+        .map(|t| format!("{:<width$} |", t.label))
+        .collect();
+    eprintln!(
+        "{}",
+        console::style(format!(
+            "Showing logs from {}",
+            tails
+                .iter()
+                .map(|t| t.label.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+        .dim()
+    );
+
+    if follow {
+        until_interrupted(follow_merged(client, env.id, &tails, &prefixes)).await
+    } else {
+        authenticate(client).await?;
+        let histories = fetch_each(&tails, |t| client.get_instance_logs(env.id, t.id)).await?;
+        for (source, msg) in merge(histories) {
+            print_prefixed(&prefixes[source], &msg);
+        }
+        Ok(())
+    }
+}
+
+/// The running instances behind `source`, labelled by name (or short id).
+async fn discover(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    source: &Source,
+) -> Result<Vec<Tail>> {
+    let instances: Vec<InstanceListEntry> = client
+        .list_instances(env.id)
+        .await?
+        .instances
+        .into_iter()
+        .filter(|i| is_active(&i.state.0))
+        .collect();
+
+    let chosen: Vec<Tail> = match source {
+        Source::App(app) => {
+            let chosen: Vec<Tail> = instances
+                .iter()
+                .filter(|i| i.deployment.as_ref().is_some_and(|d| &d.name == app))
+                .map(tail)
+                .collect();
+            if chosen.is_empty() {
+                let mut apps: Vec<&str> = instances
+                    .iter()
+                    .filter_map(|i| Some(i.deployment.as_ref()?.name.as_str()))
+                    .collect();
+                apps.sort_unstable();
+                apps.dedup();
+                if apps.is_empty() {
+                    bail!("no running instances in app {app:?}: nothing is deployed");
+                }
+                bail!(
+                    "no running instances in app {app:?} (apps: {})",
+                    apps.join(", ")
+                );
+            }
+            chosen
+        }
+        Source::Service(reference) => {
+            let service = resolve_service(client, env.id, reference).await?;
+            let detail = client.get_service(env.id, service.id).await?;
+            let mut ids: Vec<Uuid> = detail.targets.iter().map(|t| t.instance_id).collect();
+            ids.sort_unstable();
+            ids.dedup();
+            // A target whose instance isn't running has no live logs to show.
+            let chosen: Vec<Tail> = instances
+                .iter()
+                .filter(|i| ids.binary_search(&i.id).is_ok())
+                .map(tail)
+                .collect();
+            if chosen.is_empty() {
+                bail!("{} has no running instance targets", service.name);
+            }
+            chosen
+        }
+    };
+    Ok(chosen)
+}
+
+fn tail(instance: &InstanceListEntry) -> Tail {
+    Tail {
+        id: instance.id,
+        label: instance
+            .name
+            .clone()
+            .unwrap_or_else(|| instance.id.to_string()[..8].to_string()),
+    }
+}
+
+/// Interleave per-instance histories by timestamp, tagging each frame with
+/// the index of the instance it came from. Frames sharing a millisecond keep
+/// their per-instance order.
+fn merge(histories: Vec<Vec<LogMessage>>) -> Vec<(usize, LogMessage)> {
+    let mut merged: Vec<(usize, LogMessage)> = histories
+        .into_iter()
+        .enumerate()
+        .flat_map(|(source, frames)| frames.into_iter().map(move |msg| (source, msg)))
+        .collect();
+    merged.sort_by_key(|(_, msg)| msg.timestamp_ms);
+    merged
+}
+
+fn print_prefixed(prefix: &str, msg: &LogMessage) {
+    if let Some(mut line) = route(msg) {
+        line.text = format!("{prefix} {}", line.text);
+        emit(Some(line));
+    }
+}
+
+/// Stream every instance at once until all streams end. An instance whose
+/// stream still fails to connect after a few quick retries, or breaks, is
+/// reported and dropped; the rest carry on. Fails only if no stream could be
+/// opened.
+async fn follow_merged(
+    client: &dyn ApiClient,
+    env_id: Uuid,
+    tails: &[Tail],
+    prefixes: &[String],
+) -> Result<()> {
+    let mut streams = Vec::new();
+    let mut first_err = None;
+    for (source, t) in tails.iter().enumerate() {
+        match connect(client, env_id, t.id, &Resubscribe::ALONGSIDE).await {
+            Ok(stream) => streams.push(stream.map(move |frame| (source, frame))),
+            Err(e) => {
+                eprintln!(
+                    "{}",
+                    console::style(format!("! can't stream {}: {e}", t.label)).yellow()
+                );
+                first_err.get_or_insert(e);
+            }
+        }
+    }
+    if streams.is_empty()
+        && let Some(e) = first_err
+    {
+        return Err(e.into());
+    }
+
+    let mut merged = futures_util::stream::select_all(streams);
+    let mut reorder = Reorder::new(HOLD);
+    let mut tick = tokio::time::interval(HOLD / 2);
+    loop {
+        tokio::select! {
+            next = merged.next() => match next {
+                Some((source, Ok(msg))) => reorder.push(Instant::now(), source, msg),
+                Some((source, Err(e))) => eprintln!(
+                    "{}",
+                    console::style(format!("! {} stream interrupted: {e}", tails[source].label))
+                        .yellow()
+                ),
+                None => break,
+            },
+            _ = tick.tick() => {
+                for (source, msg) in reorder.release(Instant::now()) {
+                    print_prefixed(&prefixes[source], &msg);
+                }
+            }
+        }
+    }
+    for (source, msg) in reorder.drain() {
+        print_prefixed(&prefixes[source], &msg);
+    }
+    eprintln!("{}", console::style("streams closed").dim());
+    Ok(())
+}
+
+/// Buffers streamed frames so those from different instances print in
+/// timestamp order. Once a frame has been held for `hold`, it's released
+/// along with every pending frame stamped no later than it.
+struct Reorder {
+    hold: Duration,
+    pending: Vec<(Instant, usize, LogMessage)>,
+}
+
+impl Reorder {
+    fn new(hold: Duration) -> Self {
+        Self {
+            hold,
+            pending: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, received: Instant, source: usize, msg: LogMessage) {
+        self.pending.push((received, source, msg));
+    }
+
+    /// Frames ready to print at `now`, in timestamp order.
+    fn release(&mut self, now: Instant) -> Vec<(usize, LogMessage)> {
+        let cutoff = self
+            .pending
+            .iter()
+            .filter(|(received, _, _)| now.saturating_duration_since(*received) >= self.hold)
+            .map(|(_, _, msg)| msg.timestamp_ms)
+            .max();
+        let Some(cutoff) = cutoff else {
+            return Vec::new();
+        };
+        let (ready, waiting) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|(_, _, msg)| msg.timestamp_ms <= cutoff);
+        self.pending = waiting;
+        Self::sorted(ready)
+    }
+
+    /// Everything still held, in timestamp order.
+    fn drain(&mut self) -> Vec<(usize, LogMessage)> {
+        Self::sorted(std::mem::take(&mut self.pending))
+    }
+
+    fn sorted(mut frames: Vec<(Instant, usize, LogMessage)>) -> Vec<(usize, LogMessage)> {
+        frames.sort_by_key(|(_, _, msg)| msg.timestamp_ms);
+        frames
+            .into_iter()
+            .map(|(_, source, msg)| (source, msg))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::up::plan::test_env;
+    use chrono::NaiveDateTime;
+    use unisrv_api::models::{
+        DeploymentInfo, InstanceListResponse, InstanceState, ServiceDetailResponse,
+        ServiceListItem, ServiceListResponse, ServiceTargetDetail,
+    };
+    use unisrv_api::test_support::MockApiClient;
+
+    fn at(timestamp_ms: u64, text: &str) -> LogMessage {
+        LogMessage {
+            log_type: "stdout".into(),
+            timestamp_ms,
+            state: None,
+            message: Some(text.into()),
+        }
+    }
+
+    fn instance(name: &str, app: Option<&str>, state: &str) -> InstanceListEntry {
+        InstanceListEntry {
+            id: Uuid::new_v4(),
+            name: Some(name.into()),
+            state: InstanceState(state.into()),
+            container_image: "nginx:latest".into(),
+            created_at: NaiveDateTime::default(),
+            deployment: app.map(|name| DeploymentInfo {
+                id: Uuid::nil(),
+                name: name.into(),
+            }),
+            retained_until: None,
+        }
+    }
+
+    fn texts(frames: &[(usize, LogMessage)]) -> Vec<(usize, &str)> {
+        frames
+            .iter()
+            .map(|(source, msg)| (*source, msg.message.as_deref().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn histories_interleave_by_timestamp() {
+        let merged = merge(vec![
+            vec![at(10, "a1"), at(30, "a2")],
+            vec![at(20, "b1"), at(30, "b2")],
+        ]);
+        assert_eq!(
+            texts(&merged),
+            vec![(0, "a1"), (1, "b1"), (0, "a2"), (1, "b2")]
+        );
+    }
+
+    #[test]
+    fn reorder_holds_frames_until_earlier_ones_can_arrive() {
+        let start = Instant::now();
+        let mut reorder = Reorder::new(Duration::from_millis(500));
+        reorder.push(start, 0, at(20, "late-stamped"));
+        assert!(reorder.release(start).is_empty(), "still held");
+
+        // An earlier-stamped frame from another instance arrives while the
+        // first is held; both come out in timestamp order.
+        reorder.push(start + Duration::from_millis(100), 1, at(10, "early"));
+        reorder.push(start + Duration::from_millis(200), 1, at(40, "newer"));
+        let released = reorder.release(start + Duration::from_millis(500));
+        assert_eq!(texts(&released), vec![(1, "early"), (0, "late-stamped")]);
+
+        assert_eq!(texts(&reorder.drain()), vec![(1, "newer")]);
+    }
+
+    #[tokio::test]
+    async fn app_selects_its_running_instances() {
+        let env = test_env();
+        let web = instance("web-0", Some("web"), "running");
+        let mock = MockApiClient::logged_in().with_list_instances(Ok(InstanceListResponse {
+            instances: vec![
+                web.clone(),
+                instance("web-old", Some("web"), "exited"),
+                instance("worker-0", Some("worker"), "running"),
+            ],
+        }));
+
+        let tails = discover(&mock, &env, &Source::App("web".into()))
+            .await
+            .unwrap();
+        assert_eq!(tails.len(), 1);
+        assert_eq!((tails[0].id, tails[0].label.as_str()), (web.id, "web-0"));
+
+        let mock = MockApiClient::logged_in().with_list_instances(Ok(InstanceListResponse {
+            instances: vec![instance("worker-0", Some("worker"), "running")],
+        }));
+        let err = discover(&mock, &env, &Source::App("api".into()))
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("(apps: worker)"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn service_selects_its_target_instances_once_each() {
+        let (web, other) = (
+            instance("web-0", Some("web"), "running"),
+            instance("other", None, "running"),
+        );
+        let service_id = Uuid::new_v4();
+        let target = |group: &str| ServiceTargetDetail {
+            id: Uuid::new_v4(),
+            instance_id: web.id,
+            target_group: group.into(),
+            instance_port: 8080,
+            created_at: NaiveDateTime::default(),
+            health: None,
+        };
+        let mock = MockApiClient::logged_in()
+            .with_list_instances(Ok(InstanceListResponse {
+                instances: vec![web.clone(), other],
+            }))
+            .with_list_services(Ok(ServiceListResponse {
+                services: vec![ServiceListItem {
+                    id: service_id,
+                    name: "site".into(),
+                    base_host: "site-ab12.unisrv.dev".into(),
+                    custom_hosts: vec![],
+                    managed_by: None,
+                }],
+            }))
+            .push_get_service(Ok(ServiceDetailResponse {
+                id: service_id,
+                name: "site".into(),
+                base_host: "site-ab12.unisrv.dev".into(),
+                custom_hosts: vec![],
+                configuration: serde_json::Value::Null,
+                environment_id: Uuid::nil(),
+                created_at: Default::default(),
+                updated_at: Default::default(),
+                providers: vec![],
+                targets: vec![target("web"), target("admin")],
+                statistics: None,
+            }));
+
+        let tails = discover(&mock, &test_env(), &Source::Service("site".into()))
+            .await
+            .unwrap();
+
+        let ids: Vec<Uuid> = tails.iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![web.id]);
+    }
+
+    #[tokio::test]
+    async fn follow_reads_every_instance_and_survives_one_failing() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let tails = [
+            Tail {
+                id: a,
+                label: "a".into(),
+            },
+            Tail {
+                id: b,
+                label: "b".into(),
+            },
+        ];
+        let mock = MockApiClient::logged_in()
+            .push_stream_connect_error(unisrv_api::ApiError::Server {
+                status: 404,
+                reason: "instance not found".into(),
+            })
+            .push_stream_logs(vec![at(1, "hello")]);
+
+        follow_merged(&mock, Uuid::nil(), &tails, &["a |".into(), "b |".into()])
+            .await
+            .unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let streamed: Vec<Uuid> = calls
+            .stream_instance_logs_calls
+            .iter()
+            .map(|(_, id)| *id)
+            .collect();
+        assert_eq!(streamed, vec![a, b]);
+    }
+}
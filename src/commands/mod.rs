@@ -1,8 +1,33 @@
+pub mod account;
 pub mod auth;
+pub mod completions;
+pub mod concurrent;
+pub mod config;
+pub mod confirm;
 pub mod destroy;
+pub mod env;
 pub mod host;
+pub mod import;
 pub mod instance;
+pub mod interrupt;
+pub mod locale;
 pub mod login;
+pub mod logs;
+pub mod networks;
+pub mod output;
+pub mod prompt;
+pub mod regions;
 pub mod registry;
+pub mod report;
+pub mod rollout;
+pub mod service;
+pub mod service_account;
+#[cfg(feature = "interactive")]
+pub mod shell;
+pub mod state;
+pub mod stats;
+pub mod table;
 pub mod ui;
 pub mod up;
+pub mod watch;
+pub mod worker;
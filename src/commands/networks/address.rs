@@ -0,0 +1,514 @@
+//! Addressing within private networks: the `[ip]@network` spec accepted by
+//! `unisrv instance run --network`, and picking or checking an instance's
+//! address against the network's CIDR and the addresses already in use.
+//!
+//! Two commands started at once can pick the same free address; the loser's
+//! request fails with an address-in-use conflict, and [`claim_address`] moves
+//! on to the next candidate.
+//!
+//! Everything here is checked client-side so a typo'd or taken address fails
+//! with a precise message before anything is provisioned. Besides instances'
+//! addresses, the platform's own (gateway, DNS) are never handed out, and
+//! addresses held with `unisrv network reserve` are only given to an instance
+//! that asks for them by address.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::net::Ipv4Addr;
+
+use anyhow::{Context, Result, anyhow, bail};
+use cidr::Ipv4Cidr;
+use unisrv_api::models::{IpReservation, NetworkListItem, NetworkResponse};
+use unisrv_api::{ApiClient, ApiError};
+use uuid::Uuid;
+
+/// Addresses auto-assignment tries before giving up, each lost to a
+/// concurrent claim.
+const MAX_ADDRESS_ATTEMPTS: usize = 5;
+
+/// A parsed `--network` value: `backend`, `@backend` or `10.0.0.5@backend`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkSpec {
+    pub network: String,
+    /// The address asked for, if any; otherwise one is picked.
+    pub ip: Option<Ipv4Addr>,
+}
+
+impl NetworkSpec {
+    pub fn parse(s: &str) -> Result<Self> {
+        let (ip, network) = match s.split_once('@') {
+            Some((ip, network)) => (ip.trim(), network.trim()),
+            None => ("", s.trim()),
+        };
+        if network.is_empty() {
+            bail!("invalid --network {s:?}: expected NETWORK or IP@NETWORK");
+        }
+        let ip =
+            if ip.is_empty() {
+                None
+            } else {
+                Some(ip.parse::<Ipv4Addr>().map_err(|_| {
+                    anyhow!("invalid --network {s:?}: {ip:?} is not an IPv4 address")
+                })?)
+            };
+        Ok(Self {
+            network: network.to_string(),
+            ip,
+        })
+    }
+}
+
+/// Host addresses the platform keeps for itself, as offsets from the network
+/// address: the gateway and the DNS resolver.
+const PLATFORM_RESERVED: &[(u32, &str)] = &[(1, "the gateway"), (2, "DNS")];
+
+/// A network resolved by name, with the addresses its instances already hold.
+#[derive(Debug)]
+pub struct NetworkUsage {
+    pub id: Uuid,
+    pub name: String,
+    pub cidr: Ipv4Cidr,
+    /// Address → instance holding it.
+    pub used: BTreeMap<Ipv4Addr, Uuid>,
+    /// Address → what it's held for. Never auto-assigned and refused when
+    /// asked for explicitly.
+    pub reserved: BTreeMap<Ipv4Addr, String>,
+    /// Address → reservation name. Skipped by auto-assignment, but unlike
+    /// `reserved` an instance may ask for one explicitly — that's what it's
+    /// held for.
+    pub held: BTreeMap<Ipv4Addr, Option<String>>,
+}
+
+impl NetworkUsage {
+    pub fn from_response(network: &NetworkResponse) -> Result<Self> {
+        let cidr = network.ipv4_cidr.parse::<Ipv4Cidr>().with_context(|| {
+            format!(
+                "network {} has an unreadable CIDR {:?}",
+                network.name, network.ipv4_cidr
+            )
+        })?;
+        // An instance whose address doesn't parse can't collide with one that
+        // does, so it's skipped rather than failing the whole lookup.
+        let used = network
+            .instances
+            .iter()
+            .filter_map(|i| Some((i.internal_ip.parse().ok()?, i.id)))
+            .collect();
+        Ok(Self {
+            id: network.id,
+            name: network.name.clone(),
+            cidr,
+            used,
+            reserved: platform_reserved(cidr)
+                .map(|(ip, purpose)| (ip, purpose.to_string()))
+                .collect(),
+            held: BTreeMap::new(),
+        })
+    }
+
+    /// Record the network's reservations. Like instance addresses, one that
+    /// doesn't parse is skipped.
+    pub fn hold(&mut self, reservations: &[IpReservation]) {
+        self.held.extend(
+            reservations
+                .iter()
+                .filter_map(|r| Some((r.ip.parse().ok()?, r.name.clone()))),
+        );
+    }
+
+    /// The address to give a new instance: `requested` if it's usable,
+    /// otherwise the lowest free host address.
+    pub fn assign(&self, requested: Option<Ipv4Addr>) -> Result<Ipv4Addr> {
+        match requested {
+            Some(ip) => {
+                self.check(ip)?;
+                Ok(ip)
+            }
+            None => next_ip(self.cidr, |ip| self.is_taken(ip)).ok_or_else(|| {
+                anyhow!(
+                    "network {} ({}) has no free addresses left",
+                    self.name,
+                    self.cidr
+                )
+            }),
+        }
+    }
+
+    /// Record that `ip` was taken behind our back (another instance won the
+    /// race for it), so the next [`assign`](Self::assign) moves past it.
+    pub fn mark_taken(&mut self, ip: Ipv4Addr) {
+        self.reserved
+            .entry(ip)
+            .or_insert_with(|| "an instance started concurrently".into());
+    }
+
+    /// How many host addresses the network has, taken or not.
+    pub fn capacity(&self) -> u64 {
+        let hosts = host_range(self.cidr);
+        u64::from(hosts.end() - hosts.start()) + 1
+    }
+
+    fn is_taken(&self, ip: &Ipv4Addr) -> bool {
+        self.used.contains_key(ip) || self.reserved.contains_key(ip) || self.held.contains_key(ip)
+    }
+
+    /// Refuse `ip` if it's outside the network, isn't a host address, or is
+    /// reserved or already taken.
+    pub fn check(&self, ip: Ipv4Addr) -> Result<()> {
+        if !self.cidr.contains(&ip) {
+            bail!(
+                "{ip} is outside network {} ({}); pick an address in {}–{}",
+                self.name,
+                self.cidr,
+                self.cidr.first_address(),
+                self.cidr.last_address()
+            );
+        }
+        if has_reserved_ends(self.cidr) {
+            if ip == self.cidr.first_address() {
+                bail!(
+                    "{ip} is the network address of {} ({}) and can't be assigned",
+                    self.name,
+                    self.cidr
+                );
+            }
+            if ip == self.cidr.last_address() {
+                bail!(
+                    "{ip} is the broadcast address of {} ({}) and can't be assigned",
+                    self.name,
+                    self.cidr
+                );
+            }
+        }
+        if let Some(purpose) = self.reserved.get(&ip) {
+            bail!(
+                "{ip} is reserved for {purpose} on network {} and can't be assigned",
+                self.name
+            );
+        }
+        if let Some(holder) = self.used.get(&ip) {
+            bail!(
+                "{ip} is already assigned to instance {holder} on network {}",
+                self.name
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Whether the first and last addresses are the network and broadcast
+/// addresses. /31 and /32 have none (RFC 3021): every address is a host.
+fn has_reserved_ends(cidr: Ipv4Cidr) -> bool {
+    cidr.network_length() < 31
+}
+
+/// The host addresses of `cidr`, as integers so huge networks are walked
+/// without materializing anything.
+fn host_range(cidr: Ipv4Cidr) -> std::ops::RangeInclusive<u32> {
+    let (first, last) = (
+        u32::from(cidr.first_address()),
+        u32::from(cidr.last_address()),
+    );
+    if has_reserved_ends(cidr) {
+        first + 1..=last - 1
+    } else {
+        first..=last
+    }
+}
+
+/// The platform's own addresses within `cidr`. Point-to-point networks (/31,
+/// /32) have no room for them and get none.
+pub fn platform_reserved(cidr: Ipv4Cidr) -> impl Iterator<Item = (Ipv4Addr, &'static str)> {
+    let network = u32::from(cidr.first_address());
+    let hosts = host_range(cidr);
+    PLATFORM_RESERVED
+        .iter()
+        .filter(move |_| has_reserved_ends(cidr))
+        .map(move |&(offset, purpose)| (network + offset, purpose))
+        .filter(move |(ip, _)| hosts.contains(ip))
+        .map(|(ip, purpose)| (Ipv4Addr::from(ip), purpose))
+}
+
+/// Whether two ranges share any address. CIDR blocks either nest or are
+/// disjoint, so it's enough to check each one's first address.
+pub fn overlaps(a: Ipv4Cidr, b: Ipv4Cidr) -> bool {
+    a.contains(&b.first_address()) || b.contains(&a.first_address())
+}
+
+/// The lowest host address in `cidr` for which `taken` is false. Callers pass
+/// the union of used and reserved addresses.
+pub fn next_ip(cidr: Ipv4Cidr, taken: impl Fn(&Ipv4Addr) -> bool) -> Option<Ipv4Addr> {
+    host_range(cidr).map(Ipv4Addr::from).find(|ip| !taken(ip))
+}
+
+/// Call `try_address` with `requested`, or with successive free addresses,
+/// moving past any lost to a concurrent claim
+/// ([`is_address_in_use`](ApiError::is_address_in_use)). An explicitly
+/// requested address is never swapped for another.
+pub async fn claim_address<T, Fut>(
+    usage: &mut NetworkUsage,
+    requested: Option<Ipv4Addr>,
+    mut try_address: impl FnMut(Ipv4Addr) -> Fut,
+) -> Result<(T, Ipv4Addr)>
+where
+    Fut: Future<Output = Result<T>>,
+{
+    for attempt in 1..=MAX_ADDRESS_ATTEMPTS {
+        let ip = usage.assign(requested)?;
+        let err = match try_address(ip).await {
+            Ok(value) => return Ok((value, ip)),
+            Err(err) => err,
+        };
+        let in_use = err
+            .downcast_ref::<ApiError>()
+            .is_some_and(ApiError::is_address_in_use);
+        if !in_use {
+            return Err(err);
+        }
+        if requested.is_some() {
+            bail!(
+                "{ip} was taken on network {} in the meantime; pick another address, \
+                 or leave it out to have one assigned",
+                usage.name
+            );
+        }
+        if attempt < MAX_ADDRESS_ATTEMPTS {
+            tracing::debug!("{ip} was taken concurrently; trying the next address");
+            usage.mark_taken(ip);
+        }
+    }
+    bail!(
+        "gave up after {MAX_ADDRESS_ATTEMPTS} addresses on network {} were taken concurrently; \
+         retry in a moment",
+        usage.name
+    )
+}
+
+/// Look up `name` among the environment's networks.
+pub async fn find(client: &dyn ApiClient, env_id: Uuid, name: &str) -> Result<NetworkListItem> {
+    let networks = client.list_networks(env_id, false).await?.networks;
+    pick(&networks, name).cloned()
+}
+
+/// The network named `name` in an already-fetched list.
+pub fn pick<'a>(networks: &'a [NetworkListItem], name: &str) -> Result<&'a NetworkListItem> {
+    if let Some(found) = networks.iter().find(|n| n.name == name) {
+        return Ok(found);
+    }
+    let known: Vec<&str> = networks.iter().map(|n| n.name.as_str()).collect();
+    if known.is_empty() {
+        bail!("no network named {name:?}: this environment has no networks");
+    }
+    bail!("no network named {name:?} (networks: {})", known.join(", "))
+}
+
+/// Look up `name` among the environment's networks and fetch the addresses
+/// its instances and reservations hold.
+pub async fn resolve(client: &dyn ApiClient, env_id: Uuid, name: &str) -> Result<NetworkUsage> {
+    let found = find(client, env_id, name).await?;
+    let network = client
+        .get_network(env_id, found.id)
+        .await
+        .with_context(|| format!("failed to look up network {name}"))?;
+    let mut usage = NetworkUsage::from_response(&network)?;
+    // A backend without reservations has none to avoid.
+    match client.list_ip_reservations(env_id, found.id).await {
+        Ok(list) => usage.hold(&list.reservations),
+        Err(e) if e.is_unsupported_endpoint() => {}
+        Err(e) => {
+            return Err(e)
+                .with_context(|| format!("failed to list address reservations on {name}"));
+        }
+    }
+    Ok(usage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::collections::BTreeSet;
+
+    fn usage(cidr: &str, used: &[&str]) -> NetworkUsage {
+        NetworkUsage {
+            id: Uuid::nil(),
+            name: "backend".into(),
+            cidr: cidr.parse().unwrap(),
+            used: used
+                .iter()
+                .map(|ip| (ip.parse().unwrap(), Uuid::nil()))
+                .collect(),
+            reserved: BTreeMap::new(),
+            held: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn parses_bare_and_addressed_specs() {
+        assert_eq!(
+            NetworkSpec::parse("backend").unwrap(),
+            NetworkSpec {
+                network: "backend".into(),
+                ip: None
+            }
+        );
+        assert_eq!(NetworkSpec::parse("@backend").unwrap().ip, None);
+        assert_eq!(
+            NetworkSpec::parse("10.0.0.5@backend").unwrap().ip,
+            Some(Ipv4Addr::new(10, 0, 0, 5))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_specs() {
+        let err = NetworkSpec::parse("10.0.0.300@backend").unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("\"10.0.0.300\" is not an IPv4 address")
+        );
+        assert!(NetworkSpec::parse("10.0.0.5@").is_err());
+    }
+
+    #[test]
+    fn explicit_ip_must_be_a_free_host_address_in_the_cidr() {
+        let net = usage("10.0.0.0/24", &["10.0.0.1"]);
+        assert_eq!(
+            net.assign(Some(Ipv4Addr::new(10, 0, 0, 7))).unwrap(),
+            Ipv4Addr::new(10, 0, 0, 7)
+        );
+        let cases = [
+            ("10.0.1.7", "outside network backend (10.0.0.0/24)"),
+            ("10.0.0.0", "network address"),
+            ("10.0.0.255", "broadcast address"),
+            ("10.0.0.1", "already assigned to instance"),
+        ];
+        for (ip, expected) in cases {
+            let err = net.assign(Some(ip.parse().unwrap())).unwrap_err();
+            assert!(err.to_string().contains(expected), "{ip}: {err}");
+        }
+    }
+
+    #[test]
+    fn point_to_point_networks_use_every_address() {
+        let net = usage("10.0.0.0/31", &[]);
+        net.check(Ipv4Addr::new(10, 0, 0, 0)).unwrap();
+        net.check(Ipv4Addr::new(10, 0, 0, 1)).unwrap();
+    }
+
+    #[test]
+    fn auto_assignment_takes_the_lowest_free_host_address() {
+        assert_eq!(
+            usage("10.0.0.0/24", &["10.0.0.1", "10.0.0.3"])
+                .assign(None)
+                .unwrap(),
+            Ipv4Addr::new(10, 0, 0, 2)
+        );
+        let full = usage("10.0.0.0/30", &["10.0.0.1", "10.0.0.2"]);
+        assert!(
+            full.assign(None)
+                .unwrap_err()
+                .to_string()
+                .contains("no free addresses")
+        );
+    }
+
+    fn response(cidr: &str, used: &[&str]) -> NetworkResponse {
+        NetworkResponse {
+            id: Uuid::nil(),
+            environment_id: Uuid::nil(),
+            name: "backend".into(),
+            ipv4_cidr: cidr.into(),
+            created_at: chrono::NaiveDateTime::default(),
+            instances: used
+                .iter()
+                .map(|ip| unisrv_api::models::InstanceInfo {
+                    id: Uuid::nil(),
+                    internal_ip: ip.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn gateway_and_dns_are_never_assigned() {
+        let net = NetworkUsage::from_response(&response("10.0.0.0/24", &[])).unwrap();
+        assert_eq!(net.assign(None).unwrap(), Ipv4Addr::new(10, 0, 0, 3));
+        let err = net.check(Ipv4Addr::new(10, 0, 0, 1)).unwrap_err();
+        assert!(
+            err.to_string().contains("reserved for the gateway"),
+            "{err}"
+        );
+        let err = net.check(Ipv4Addr::new(10, 0, 0, 2)).unwrap_err();
+        assert!(err.to_string().contains("reserved for DNS"), "{err}");
+    }
+
+    #[test]
+    fn small_networks_reserve_what_fits() {
+        // /30 has two hosts, both taken by the platform.
+        let net = NetworkUsage::from_response(&response("10.0.0.0/30", &[])).unwrap();
+        assert!(net.assign(None).is_err());
+        // Point-to-point networks have no platform addresses at all.
+        let p2p = NetworkUsage::from_response(&response("10.0.0.0/31", &[])).unwrap();
+        assert!(p2p.reserved.is_empty());
+        assert_eq!(p2p.assign(None).unwrap(), Ipv4Addr::new(10, 0, 0, 0));
+        let single = NetworkUsage::from_response(&response("10.0.0.7/32", &[])).unwrap();
+        assert_eq!(single.assign(None).unwrap(), Ipv4Addr::new(10, 0, 0, 7));
+    }
+
+    #[test]
+    fn huge_networks_are_walked_lazily() {
+        let net = NetworkUsage::from_response(&response("10.0.0.0/8", &["10.0.0.3"])).unwrap();
+        assert_eq!(net.assign(None).unwrap(), Ipv4Addr::new(10, 0, 0, 4));
+    }
+
+    #[test]
+    fn held_addresses_are_skipped_but_can_be_asked_for() {
+        let mut net = usage("10.0.0.0/24", &["10.0.0.1"]);
+        net.hold(&[IpReservation {
+            id: Uuid::nil(),
+            ip: "10.0.0.2".into(),
+            name: Some("db-vip".into()),
+            created_at: chrono::NaiveDateTime::default(),
+        }]);
+        assert_eq!(net.assign(None).unwrap(), Ipv4Addr::new(10, 0, 0, 3));
+        assert_eq!(
+            net.assign(Some(Ipv4Addr::new(10, 0, 0, 2))).unwrap(),
+            Ipv4Addr::new(10, 0, 0, 2)
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn next_ip_is_the_lowest_free_host_address(
+            base in any::<u32>(),
+            prefix in 8u8..=32,
+            offsets in proptest::collection::btree_set(0u32..64, 0..32),
+        ) {
+            let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+            let cidr = Ipv4Cidr::new(Ipv4Addr::from(base & mask), prefix).unwrap();
+            let network = u32::from(cidr.first_address());
+            let mut taken: BTreeSet<Ipv4Addr> =
+                platform_reserved(cidr).map(|(ip, _)| ip).collect();
+            taken.extend(
+                offsets
+                    .iter()
+                    .filter_map(|o| network.checked_add(*o).map(Ipv4Addr::from))
+                    .filter(|ip| cidr.contains(ip)),
+            );
+            let hosts = host_range(cidr);
+
+            match next_ip(cidr, |ip| taken.contains(ip)) {
+                Some(ip) => {
+                    prop_assert!(hosts.contains(&u32::from(ip)));
+                    prop_assert!(!taken.contains(&ip));
+                    // Nothing free below it.
+                    prop_assert!((*hosts.start()..u32::from(ip))
+                        .all(|a| taken.contains(&Ipv4Addr::from(a))));
+                }
+                None => {
+                    prop_assert!(hosts.into_iter().all(|a| taken.contains(&Ipv4Addr::from(a))));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,300 @@
+//! `unisrv network attach <network> <instance> [--ip x.x.x.x]` and
+//! `unisrv network detach <network> <instance>` — change a running instance's
+//! network membership without restarting it.
+//!
+//! An instance belongs to at most one network, so attaching one that's already
+//! elsewhere is refused rather than silently moving it. Addresses follow the
+//! same rules as `instance run --network`.
+
+use std::net::Ipv4Addr;
+
+use anyhow::{Context, Result, bail};
+use unisrv_api::ApiClient;
+use unisrv_api::models::{InstanceListEntry, InstanceNetworkConfig};
+
+use super::address::{self, claim_address};
+use crate::commands::confirm::Guard;
+use crate::commands::instance::resolve::lookup_instance;
+use crate::commands::up::plan::ResolvedEnvironment;
+
+pub async fn attach(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    network: &str,
+    reference: &str,
+    ip: Option<Ipv4Addr>,
+) -> Result<()> {
+    let instance = lookup_instance(client, env.id, reference).await?;
+    let label = label(&instance);
+    let detail = client
+        .get_instance(env.id, instance.id, false, false)
+        .await?;
+    let mut usage = address::resolve(client, env.id, network).await?;
+
+    match detail.network_id {
+        Some(id) if id == usage.id => {
+            let at = detail.network_ip.as_deref().unwrap_or("an unknown address");
+            println!("{label} is already on {} at {at}.", usage.name);
+            return Ok(());
+        }
+        Some(_) => bail!(
+            "{label} is already on another network; detach it first \
+             (`unisrv network detach <network> {label}`)"
+        ),
+        None => {}
+    }
+
+    let network_id = usage.id;
+    let network_name = usage.name.clone();
+    let ((), ip) = claim_address(&mut usage, ip, |ip| {
+        let (label, network_name) = (&label, &network_name);
+        async move {
+            client
+                .attach_instance_network(
+                    env.id,
+                    instance.id,
+                    InstanceNetworkConfig {
+                        network_id,
+                        instance_ip: Some(ip.to_string()),
+                    },
+                )
+                .await
+                .with_context(|| format!("failed to attach {label} to {network_name}"))
+        }
+    })
+    .await?;
+    println!("\u{2713} Attached {label} to {} at {ip}.", usage.name);
+    Ok(())
+}
+
+pub async fn detach(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    network: &str,
+    reference: &str,
+    guard: &Guard<'_>,
+) -> Result<()> {
+    let instance = lookup_instance(client, env.id, reference).await?;
+    let label = label(&instance);
+    let detail = client
+        .get_instance(env.id, instance.id, false, false)
+        .await?;
+    let usage = address::resolve(client, env.id, network).await?;
+    if detail.network_id != Some(usage.id) {
+        bail!("{label} is not on network {}", usage.name);
+    }
+    if !guard.confirm(&format!("detach {label} from {}", usage.name), &label)? {
+        println!("Aborted.");
+        return Ok(());
+    }
+
+    client
+        .detach_instance_network(env.id, instance.id)
+        .await
+        .with_context(|| format!("failed to detach {label} from {}", usage.name))?;
+    println!("\u{2713} Detached {label} from {}.", usage.name);
+    Ok(())
+}
+
+/// The instance's name, or its id when it has none.
+fn label(instance: &InstanceListEntry) -> String {
+    instance
+        .name
+        .clone()
+        .unwrap_or_else(|| instance.id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::confirm::tests::ScriptedPrompter;
+    use crate::commands::up::plan::test_env;
+    use chrono::NaiveDateTime;
+    use unisrv_api::ApiError;
+    use unisrv_api::models::{
+        InstanceDetailResponse, InstanceInfo, InstanceListResponse, InstanceState, NetworkListItem,
+        NetworkListResponse, NetworkResponse,
+    };
+    use unisrv_api::test_support::MockApiClient;
+    use uuid::Uuid;
+
+    fn entry(id: Uuid) -> InstanceListEntry {
+        InstanceListEntry {
+            id,
+            name: Some("worker".into()),
+            state: InstanceState("running".into()),
+            container_image: "alpine:3".into(),
+            created_at: NaiveDateTime::default(),
+            deployment: None,
+            retained_until: None,
+        }
+    }
+
+    fn detail(id: Uuid, network_id: Option<Uuid>) -> InstanceDetailResponse {
+        InstanceDetailResponse {
+            id,
+            name: Some("worker".into()),
+            node_id: Uuid::nil(),
+            state: InstanceState("running".into()),
+            exit_code: None,
+            exit_reason: None,
+            configuration: serde_json::Value::Null,
+            created_at: NaiveDateTime::default(),
+            updated_at: NaiveDateTime::default(),
+            network_id,
+            network_ip: network_id.map(|_| "10.0.0.3".into()),
+            deployment: None,
+            service_targets: None,
+            proxied_ports: None,
+            resources: None,
+            annotations: None,
+        }
+    }
+
+    /// An environment with one instance (`worker`) and one network
+    /// (`backend`, 10.0.0.0/24) holding `used`.
+    fn mock(instance: Uuid, on: Option<Uuid>, net: Uuid, used: &[&str]) -> MockApiClient {
+        MockApiClient::logged_in()
+            .push_find_instances_by_name(Ok(InstanceListResponse {
+                instances: vec![entry(instance)],
+            }))
+            .push_get_instance(Ok(detail(instance, on)))
+            .with_list_networks(Ok(NetworkListResponse {
+                networks: vec![NetworkListItem {
+                    id: net,
+                    name: "backend".into(),
+                    ipv4_cidr: "10.0.0.0/24".into(),
+                    instance_count: None,
+                    managed_by: None,
+                }],
+            }))
+            .push_get_network(Ok(NetworkResponse {
+                id: net,
+                environment_id: Uuid::nil(),
+                name: "backend".into(),
+                ipv4_cidr: "10.0.0.0/24".into(),
+                created_at: NaiveDateTime::default(),
+                instances: used
+                    .iter()
+                    .map(|ip| InstanceInfo {
+                        id: Uuid::new_v4(),
+                        internal_ip: ip.to_string(),
+                    })
+                    .collect(),
+            }))
+    }
+
+    #[tokio::test]
+    async fn attach_picks_the_next_free_address() {
+        let (instance, net) = (Uuid::new_v4(), Uuid::new_v4());
+        let mock = mock(instance, None, net, &["10.0.0.3"]).push_attach_instance_network(Ok(()));
+
+        attach(&mock, &test_env(), "backend", "worker", None)
+            .await
+            .unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let (_, attached, req) = &calls.attach_instance_network_calls[0];
+        assert_eq!(*attached, instance);
+        assert_eq!(req.network_id, net);
+        assert_eq!(req.instance_ip.as_deref(), Some("10.0.0.4"));
+    }
+
+    #[tokio::test]
+    async fn attach_retries_past_a_concurrently_taken_address() {
+        let mock = mock(Uuid::new_v4(), None, Uuid::new_v4(), &[])
+            .push_attach_instance_network(Err(ApiError::Server {
+                status: 409,
+                reason: "address already in use".into(),
+            }))
+            .push_attach_instance_network(Ok(()));
+
+        attach(&mock, &test_env(), "backend", "worker", None)
+            .await
+            .unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let ips: Vec<_> = calls
+            .attach_instance_network_calls
+            .iter()
+            .map(|(_, _, req)| req.instance_ip.clone().unwrap())
+            .collect();
+        assert_eq!(ips, vec!["10.0.0.3", "10.0.0.4"]);
+    }
+
+    #[tokio::test]
+    async fn attach_refuses_an_instance_on_another_network() {
+        let mock = mock(Uuid::new_v4(), Some(Uuid::new_v4()), Uuid::new_v4(), &[]);
+
+        let err = attach(&mock, &test_env(), "backend", "worker", None)
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains("detach it first"), "{err}");
+        assert!(
+            mock.calls
+                .lock()
+                .unwrap()
+                .attach_instance_network_calls
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn detach_requires_membership_of_the_named_network() {
+        let net = Uuid::new_v4();
+        let other = mock(Uuid::new_v4(), None, net, &[]);
+        let err = detach(
+            &other,
+            &test_env(),
+            "backend",
+            "worker",
+            &Guard::unprotected(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("not on network backend"), "{err}");
+
+        let instance = Uuid::new_v4();
+        let member =
+            mock(instance, Some(net), net, &["10.0.0.3"]).push_detach_instance_network(Ok(()));
+        detach(
+            &member,
+            &test_env(),
+            "backend",
+            "worker",
+            &Guard::unprotected(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            member.calls.lock().unwrap().detach_instance_network_calls[0].1,
+            instance
+        );
+    }
+
+    #[tokio::test]
+    async fn detach_in_a_protected_environment_needs_the_instance_name() {
+        let (net, instance) = (Uuid::new_v4(), Uuid::new_v4());
+        let member = mock(instance, Some(net), net, &["10.0.0.3"]);
+        let prompter = ScriptedPrompter::typing("backend");
+        let guard = Guard::new(Some("prod".into()), &prompter);
+
+        detach(&member, &test_env(), "backend", "worker", &guard)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            prompter.asked.borrow().as_slice(),
+            ["Type worker to confirm"]
+        );
+        assert!(
+            member
+                .calls
+                .lock()
+                .unwrap()
+                .detach_instance_network_calls
+                .is_empty()
+        );
+    }
+}
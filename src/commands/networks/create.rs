@@ -0,0 +1,234 @@
+//! `unisrv network new <name> [--cidr CIDR | --auto-cidr [--range CIDR]]` —
+//! create a private network outside of `unisrv up`.
+//!
+//! `--auto-cidr` picks the first /24 in a private range that no existing
+//! network overlaps, so a new network can later be peered with the others.
+//! The range is `--range`, else `$UNISRV_NETWORK_RANGE`, else
+//! [`DEFAULT_AUTO_CIDR_RANGE`]. An explicit or default CIDR that overlaps an
+//! existing network is allowed, with a warning.
+
+use std::net::Ipv4Addr;
+
+use anyhow::{Context, Result, anyhow, bail};
+use cidr::Ipv4Cidr;
+use unisrv_api::ApiClient;
+use unisrv_api::models::{CreateInternalNetworkRequest, NetworkListItem};
+
+use super::address;
+use crate::commands::up::defaults::{DEFAULT_AUTO_CIDR_RANGE, DEFAULT_NETWORK_CIDR};
+use crate::commands::up::plan::ResolvedEnvironment;
+
+/// Overrides [`DEFAULT_AUTO_CIDR_RANGE`] when `--range` isn't given.
+pub const NETWORK_RANGE_ENV: &str = "UNISRV_NETWORK_RANGE";
+
+/// How the new network's CIDR is chosen.
+#[derive(Debug, PartialEq)]
+pub enum CidrChoice {
+    /// `up`'s default, [`DEFAULT_NETWORK_CIDR`].
+    Default,
+    Explicit(Ipv4Cidr),
+    /// The first free /24 in `range` (`None` for the configured range).
+    Auto {
+        range: Option<Ipv4Cidr>,
+    },
+}
+
+pub async fn create(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    name: &str,
+    choice: CidrChoice,
+) -> Result<()> {
+    let existing = client.list_networks(env.id, false).await?.networks;
+    if existing.iter().any(|n| n.name == name) {
+        bail!("a network named {name:?} already exists");
+    }
+
+    let cidr = match choice {
+        CidrChoice::Auto { range } => {
+            let range = match range {
+                Some(range) => range,
+                None => configured_range()?,
+            };
+            let taken = parse_cidrs(&existing);
+            let cidr = pick_free(range, &taken).ok_or_else(|| {
+                anyhow!("no free /24 left in {range}; pass a wider --range or an explicit --cidr")
+            })?;
+            eprintln!(
+                "{}",
+                console::style(format!("Picked {cidr}, the first free /24 in {range}.")).dim()
+            );
+            cidr
+        }
+        CidrChoice::Explicit(cidr) => cidr,
+        CidrChoice::Default => DEFAULT_NETWORK_CIDR
+            .parse()
+            .expect("the default network CIDR is valid"),
+    };
+    if let Some(warning) = overlap_warning(cidr, &existing) {
+        eprintln!("{}", console::style(warning).yellow());
+    }
+
+    let created = client
+        .create_network(
+            env.id,
+            CreateInternalNetworkRequest {
+                name: name.to_string(),
+                ipv4_cidr: cidr.to_string(),
+                managed_by: None,
+            },
+        )
+        .await
+        .with_context(|| format!("failed to create network {name:?}"))?;
+    println!(
+        "\u{2713} Created network {} ({}).",
+        created.name, created.ipv4_cidr
+    );
+    Ok(())
+}
+
+/// `$UNISRV_NETWORK_RANGE`, or the built-in range.
+fn configured_range() -> Result<Ipv4Cidr> {
+    match std::env::var(NETWORK_RANGE_ENV) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .with_context(|| format!("{NETWORK_RANGE_ENV} {value:?} is not an IPv4 CIDR")),
+        Err(_) => Ok(DEFAULT_AUTO_CIDR_RANGE
+            .parse()
+            .expect("the default auto-CIDR range is valid")),
+    }
+}
+
+/// Existing networks' ranges. One whose CIDR doesn't parse can't be checked
+/// against, so it's skipped.
+fn parse_cidrs(networks: &[NetworkListItem]) -> Vec<Ipv4Cidr> {
+    networks
+        .iter()
+        .filter_map(|n| n.ipv4_cidr.parse().ok())
+        .collect()
+}
+
+/// The lowest /24 in `range` overlapping none of `taken`. A range narrower
+/// than /24 is offered whole.
+fn pick_free(range: Ipv4Cidr, taken: &[Ipv4Cidr]) -> Option<Ipv4Cidr> {
+    let free = |candidate: &Ipv4Cidr| !taken.iter().any(|t| address::overlaps(*candidate, *t));
+    if range.network_length() >= 24 {
+        return Some(range).filter(free);
+    }
+    let start = u32::from(range.first_address());
+    (0..1u32 << (24 - range.network_length()))
+        .map(|i| {
+            Ipv4Cidr::new(Ipv4Addr::from(start + (i << 8)), 24)
+                .expect("a /24 within a wider range is a valid CIDR")
+        })
+        .find(free)
+}
+
+fn overlap_warning(cidr: Ipv4Cidr, existing: &[NetworkListItem]) -> Option<String> {
+    let clashes: Vec<String> = existing
+        .iter()
+        .filter(|n| {
+            n.ipv4_cidr
+                .parse()
+                .is_ok_and(|other| address::overlaps(cidr, other))
+        })
+        .map(|n| format!("{} ({})", n.name, n.ipv4_cidr))
+        .collect();
+    (!clashes.is_empty()).then(|| {
+        format!(
+            "! {cidr} overlaps {}; the networks can't be peered (try --auto-cidr)",
+            clashes.join(", ")
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::up::plan::test_env;
+    use chrono::NaiveDateTime;
+    use unisrv_api::models::{NetworkListResponse, NetworkResponse};
+    use unisrv_api::test_support::MockApiClient;
+    use uuid::Uuid;
+
+    fn cidr(s: &str) -> Ipv4Cidr {
+        s.parse().unwrap()
+    }
+
+    fn network(name: &str, cidr: &str) -> NetworkListItem {
+        NetworkListItem {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            ipv4_cidr: cidr.into(),
+            instance_count: None,
+            managed_by: None,
+        }
+    }
+
+    #[test]
+    fn picks_the_first_free_slash_24() {
+        let range = cidr("172.16.0.0/12");
+        assert_eq!(pick_free(range, &[]), Some(cidr("172.16.0.0/24")));
+        let taken = [cidr("172.16.0.0/23"), cidr("172.16.3.0/24")];
+        assert_eq!(pick_free(range, &taken), Some(cidr("172.16.2.0/24")));
+        // Networks outside the range don't matter.
+        assert_eq!(
+            pick_free(range, &[cidr("10.0.0.0/8")]),
+            Some(cidr("172.16.0.0/24"))
+        );
+    }
+
+    #[test]
+    fn a_full_range_has_nothing_to_pick() {
+        assert_eq!(pick_free(cidr("10.0.0.0/23"), &[cidr("10.0.0.0/16")]), None);
+        assert_eq!(
+            pick_free(cidr("10.9.9.0/28"), &[]),
+            Some(cidr("10.9.9.0/28"))
+        );
+    }
+
+    #[test]
+    fn warns_about_overlapping_networks() {
+        let existing = [network("a", "10.0.0.0/16"), network("b", "10.1.0.0/24")];
+        let warning = overlap_warning(cidr("10.0.5.0/24"), &existing).unwrap();
+        assert!(warning.contains("overlaps a (10.0.0.0/16)"), "{warning}");
+        assert!(!warning.contains("b ("), "{warning}");
+        assert_eq!(overlap_warning(cidr("10.2.0.0/24"), &existing), None);
+    }
+
+    #[tokio::test]
+    async fn auto_cidr_creates_the_network_on_the_free_block() {
+        let mock = MockApiClient::logged_in()
+            .with_list_networks(Ok(NetworkListResponse {
+                networks: vec![network("a", "172.16.0.0/24")],
+            }))
+            .push_create_network(Ok(NetworkResponse {
+                id: Uuid::new_v4(),
+                environment_id: Uuid::nil(),
+                name: "b".into(),
+                ipv4_cidr: "172.16.1.0/24".into(),
+                created_at: NaiveDateTime::default(),
+                instances: vec![],
+            }));
+
+        let range = Some(cidr("172.16.0.0/12"));
+        create(&mock, &test_env(), "b", CidrChoice::Auto { range })
+            .await
+            .unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        assert_eq!(calls.create_network_calls[0].1.ipv4_cidr, "172.16.1.0/24");
+    }
+
+    #[tokio::test]
+    async fn refuses_a_duplicate_name() {
+        let mock = MockApiClient::logged_in().with_list_networks(Ok(NetworkListResponse {
+            networks: vec![network("a", "10.0.0.0/16")],
+        }));
+        let err = create(&mock, &test_env(), "a", CidrChoice::Default)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("already exists"), "{err:#}");
+    }
+}
@@ -0,0 +1,339 @@
+//! `unisrv network ips <network>` — every address on a network that's spoken
+//! for, who holds it, and how much room is left.
+//!
+//! An address counts against capacity whether an instance holds it, it's
+//! reserved with `unisrv network reserve`, or the platform keeps it (gateway,
+//! DNS).
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+use std::net::Ipv4Addr;
+
+use anyhow::Result;
+use comfy_table::{Attribute, Cell, Color};
+use serde::Serialize;
+use unisrv_api::ApiClient;
+use uuid::Uuid;
+
+use super::address::{self, NetworkUsage};
+use super::policy;
+use crate::commands::output::Output;
+use crate::commands::ui::{cell_with_color, colors_enabled, new_table};
+use crate::commands::up::plan::ResolvedEnvironment;
+
+/// Characters in the utilization bar.
+const BAR_WIDTH: usize = 30;
+
+/// The `--json` document.
+#[derive(Debug, Serialize)]
+struct IpReport {
+    network: String,
+    cidr: String,
+    /// Host addresses in the network.
+    capacity: u64,
+    in_use: u64,
+    free: u64,
+    utilization_percent: f64,
+    addresses: Vec<IpEntry>,
+}
+
+#[derive(Debug, Serialize)]
+struct IpEntry {
+    ip: Ipv4Addr,
+    holder: Holder,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instance_id: Option<Uuid>,
+    /// Instance name, reservation name, or what the platform keeps it for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Holder {
+    Instance,
+    Reservation,
+    Platform,
+}
+
+pub async fn ips(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    network: &str,
+    output: &Output,
+) -> Result<()> {
+    let usage = address::resolve(client, env.id, network).await?;
+    let names = policy::names(&client.list_instances(env.id).await?.instances);
+    let report = report(&usage, &names);
+
+    match output {
+        Output::Json | Output::Jq(_) => output.print_json(&report),
+        Output::Template(template) => {
+            print!("{}", template.render_all(&report.addresses)?);
+            Ok(())
+        }
+        Output::Table => {
+            print!("{}", render(&report, colors_enabled()));
+            Ok(())
+        }
+    }
+}
+
+fn report(usage: &NetworkUsage, names: &HashMap<Uuid, String>) -> IpReport {
+    // An instance may sit on an address reserved for it; it's listed once,
+    // as the instance's.
+    let taken: BTreeSet<Ipv4Addr> = usage
+        .used
+        .keys()
+        .chain(usage.reserved.keys())
+        .chain(usage.held.keys())
+        .copied()
+        .collect();
+    let addresses: Vec<IpEntry> = taken
+        .into_iter()
+        .map(|ip| {
+            if let Some(id) = usage.used.get(&ip) {
+                IpEntry {
+                    ip,
+                    holder: Holder::Instance,
+                    instance_id: Some(*id),
+                    name: names.get(id).cloned(),
+                }
+            } else if let Some(purpose) = usage.reserved.get(&ip) {
+                IpEntry {
+                    ip,
+                    holder: Holder::Platform,
+                    instance_id: None,
+                    name: Some(purpose.clone()),
+                }
+            } else {
+                IpEntry {
+                    ip,
+                    holder: Holder::Reservation,
+                    instance_id: None,
+                    name: usage.held.get(&ip).cloned().flatten(),
+                }
+            }
+        })
+        .collect();
+
+    let capacity = usage.capacity();
+    // Addresses outside the host range (a stale instance record) don't eat
+    // into what's left.
+    let in_use = (addresses.len() as u64).min(capacity);
+    IpReport {
+        network: usage.name.clone(),
+        cidr: usage.cidr.to_string(),
+        capacity,
+        in_use,
+        free: capacity - in_use,
+        utilization_percent: (in_use as f64 / capacity as f64 * 1000.0).round() / 10.0,
+        addresses,
+    }
+}
+
+/// Table of addresses, then the utilization bar. Pure so it can be asserted
+/// on without a terminal.
+fn render(report: &IpReport, use_color: bool) -> String {
+    let mut table = new_table();
+    table.set_header(vec![
+        Cell::new("ADDRESS").add_attribute(Attribute::Bold),
+        Cell::new("HELD BY").add_attribute(Attribute::Bold),
+        Cell::new("NAME").add_attribute(Attribute::Bold),
+    ]);
+    for entry in &report.addresses {
+        let (holder, color) = match entry.holder {
+            Holder::Instance => ("instance", None),
+            Holder::Reservation => ("reservation", Some(Color::Cyan)),
+            Holder::Platform => ("platform", Some(Color::DarkGrey)),
+        };
+        let name = match (&entry.name, entry.instance_id) {
+            (Some(name), _) => name.clone(),
+            (None, Some(id)) => id.to_string()[..8].to_string(),
+            (None, None) => "\u{2014}".to_string(),
+        };
+        table.add_row(vec![
+            Cell::new(entry.ip),
+            cell_with_color(holder.to_string(), color, use_color),
+            Cell::new(name),
+        ]);
+    }
+
+    let mut out = format!("{} ({})\n{table}\n", report.network, report.cidr);
+    let _ = writeln!(
+        out,
+        "{} {}% used ({} of {} addresses, {} free)",
+        bar(report.utilization_percent, use_color),
+        report.utilization_percent,
+        report.in_use,
+        report.capacity,
+        report.free
+    );
+    out
+}
+
+/// `[█████░░░░…]`, green below 70%, yellow below 90%, red beyond.
+fn bar(percent: f64, use_color: bool) -> String {
+    let filled = ((percent / 100.0 * BAR_WIDTH as f64).round() as usize).min(BAR_WIDTH);
+    let full = "\u{2588}".repeat(filled);
+    let empty = "\u{2591}".repeat(BAR_WIDTH - filled);
+    if !use_color {
+        return format!("[{full}{empty}]");
+    }
+    let full = match percent {
+        p if p < 70.0 => console::style(full).green(),
+        p if p < 90.0 => console::style(full).yellow(),
+        _ => console::style(full).red(),
+    };
+    format!("[{}{empty}]", full.force_styling(true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+    use unisrv_api::models::{
+        InstanceInfo, InstanceListResponse, IpReservation, IpReservationListResponse,
+        NetworkListItem, NetworkListResponse, NetworkResponse,
+    };
+    use unisrv_api::test_support::MockApiClient;
+
+    fn usage(cidr: &str, used: &[(&str, Uuid)], held: &[(&str, Option<&str>)]) -> NetworkUsage {
+        let mut usage = NetworkUsage::from_response(&NetworkResponse {
+            id: Uuid::nil(),
+            environment_id: Uuid::nil(),
+            name: "backend".into(),
+            ipv4_cidr: cidr.into(),
+            created_at: NaiveDateTime::default(),
+            instances: used
+                .iter()
+                .map(|(ip, id)| InstanceInfo {
+                    id: *id,
+                    internal_ip: (*ip).into(),
+                })
+                .collect(),
+        })
+        .unwrap();
+        let reservations: Vec<IpReservation> = held
+            .iter()
+            .map(|(ip, name)| IpReservation {
+                id: Uuid::new_v4(),
+                ip: (*ip).into(),
+                name: name.map(Into::into),
+                created_at: NaiveDateTime::default(),
+            })
+            .collect();
+        usage.hold(&reservations);
+        usage
+    }
+
+    #[test]
+    fn counts_instances_reservations_and_platform_addresses() {
+        let (api, worker) = (Uuid::new_v4(), Uuid::new_v4());
+        let names = HashMap::from([(api, "api-1".to_string())]);
+        let usage = usage(
+            "10.0.0.0/28",
+            &[("10.0.0.3", api), ("10.0.0.5", worker)],
+            &[("10.0.0.5", None), ("10.0.0.9", Some("db-vip"))],
+        );
+
+        let report = report(&usage, &names);
+
+        // .1 gateway, .2 DNS, .3 api, .5 worker (reserved for it), .9 db-vip.
+        assert_eq!((report.capacity, report.in_use, report.free), (14, 5, 9));
+        assert_eq!(report.utilization_percent, 35.7);
+        let holders: Vec<_> = report
+            .addresses
+            .iter()
+            .map(|e| (e.ip.to_string(), e.holder, e.name.clone()))
+            .collect();
+        assert_eq!(
+            holders,
+            vec![
+                (
+                    "10.0.0.1".into(),
+                    Holder::Platform,
+                    Some("the gateway".into())
+                ),
+                ("10.0.0.2".into(), Holder::Platform, Some("DNS".into())),
+                ("10.0.0.3".into(), Holder::Instance, Some("api-1".into())),
+                ("10.0.0.5".into(), Holder::Instance, None),
+                (
+                    "10.0.0.9".into(),
+                    Holder::Reservation,
+                    Some("db-vip".into())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn renders_the_table_and_utilization_bar() {
+        let worker = Uuid::parse_str("6f1c2a3b-0000-0000-0000-000000000000").unwrap();
+        let report = report(
+            &usage("10.0.0.0/30", &[("10.0.0.2", worker)], &[]),
+            &HashMap::new(),
+        );
+        let out = render(&report, false);
+
+        assert!(out.starts_with("backend (10.0.0.0/30)\n"), "{out}");
+        assert!(out.contains("6f1c2a3b"), "{out}");
+        // A /30 has two hosts: the gateway and the worker.
+        assert!(
+            out.contains(&format!(
+                "[{}] 100% used (2 of 2 addresses, 0 free)",
+                "\u{2588}".repeat(BAR_WIDTH)
+            )),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn bar_fills_in_proportion() {
+        assert_eq!(
+            bar(50.0, false),
+            format!("[{}{}]", "\u{2588}".repeat(15), "\u{2591}".repeat(15))
+        );
+        assert_eq!(
+            bar(0.0, false),
+            format!("[{}]", "\u{2591}".repeat(BAR_WIDTH))
+        );
+    }
+
+    #[tokio::test]
+    async fn json_lists_every_held_address() {
+        let env = ResolvedEnvironment {
+            id: Uuid::new_v4(),
+            name: "prod".into(),
+            project: "demo".into(),
+            slug: "ab12".into(),
+        };
+        let network_id = Uuid::new_v4();
+        let mock = MockApiClient::logged_in()
+            .with_list_networks(Ok(NetworkListResponse {
+                networks: vec![NetworkListItem {
+                    id: network_id,
+                    name: "backend".into(),
+                    ipv4_cidr: "10.0.0.0/24".into(),
+                    instance_count: None,
+                    managed_by: None,
+                }],
+            }))
+            .push_get_network(Ok(NetworkResponse {
+                id: network_id,
+                environment_id: env.id,
+                name: "backend".into(),
+                ipv4_cidr: "10.0.0.0/24".into(),
+                created_at: NaiveDateTime::default(),
+                instances: vec![],
+            }))
+            .push_list_ip_reservations(Ok(IpReservationListResponse {
+                reservations: vec![],
+            }))
+            .with_list_instances(Ok(InstanceListResponse { instances: vec![] }));
+
+        ips(&mock, &env, "backend", &Output::Json).await.unwrap();
+
+        assert_eq!(mock.calls.lock().unwrap().get_network_calls.len(), 1);
+    }
+}
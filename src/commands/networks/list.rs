@@ -0,0 +1,103 @@
+//! `unisrv network list` — the private networks of an environment, with
+//! their address ranges and how many instances are on each.
+
+use anyhow::Result;
+use comfy_table::{Cell, CellAlignment, Color};
+use unisrv_api::ApiClient;
+use unisrv_api::models::NetworkListItem;
+
+use crate::commands::output::Output;
+use crate::commands::table::{Column, Row, TableOptions, draw_table, id_text, sort};
+use crate::commands::ui::{cell_with_color, colors_enabled};
+use crate::commands::up::plan::ResolvedEnvironment;
+
+pub async fn list(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    output: &Output,
+    table: &TableOptions,
+) -> Result<()> {
+    let mut networks = client.list_networks(env.id, true).await?.networks;
+    sort(&mut networks, table.sort, "networks")?;
+
+    match output {
+        Output::Json | Output::Jq(_) => return output.print_json(&networks),
+        Output::Template(template) => {
+            print!("{}", template.render_all(&networks)?);
+            return Ok(());
+        }
+        Output::Table => {}
+    }
+
+    let rendered = render_table(&networks, colors_enabled(), table)?;
+    if networks.is_empty() {
+        println!(
+            "No networks in {}. Run `unisrv network new <name>` to create one.",
+            env.name
+        );
+        return Ok(());
+    }
+    println!("{rendered}");
+    Ok(())
+}
+
+impl Row for NetworkListItem {
+    fn name(&self) -> Option<&str> {
+        Some(&self.name)
+    }
+}
+
+fn render_table(
+    networks: &[NetworkListItem],
+    use_color: bool,
+    options: &TableOptions,
+) -> Result<String> {
+    let columns = [
+        Column::new("id", "ID", |n: &NetworkListItem| {
+            Cell::new(id_text(&n.id, options))
+        }),
+        Column::new("name", "NAME", |n: &NetworkListItem| Cell::new(&n.name)),
+        Column::new("cidr", "CIDR", |n: &NetworkListItem| {
+            Cell::new(&n.ipv4_cidr)
+        }),
+        Column::new("instances", "INSTANCES", |n: &NetworkListItem| {
+            let instances = n
+                .instance_count
+                .map_or_else(|| "—".to_string(), |count| count.to_string());
+            Cell::new(instances).set_alignment(CellAlignment::Right)
+        }),
+        Column::new("managed-by", "MANAGED BY", |n: &NetworkListItem| {
+            match &n.managed_by {
+                Some(project) => cell_with_color(project.clone(), None, use_color),
+                None => cell_with_color("—".to_string(), Some(Color::DarkGrey), use_color),
+            }
+        }),
+    ];
+    draw_table(networks, &columns, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn table_shows_cidr_and_instance_count() {
+        let network = |name: &str, instance_count| NetworkListItem {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            ipv4_cidr: "10.0.0.0/24".into(),
+            instance_count,
+            managed_by: None,
+        };
+        let table = render_table(
+            &[network("backend", Some(3)), network("spare", None)],
+            false,
+            &TableOptions::default(),
+        )
+        .unwrap();
+        assert!(table.contains("10.0.0.0/24"), "{table}");
+        assert!(table.contains(" 3 "), "{table}");
+        assert!(table.contains("—"), "{table}");
+    }
+}
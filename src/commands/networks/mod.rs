@@ -0,0 +1,18 @@
+//! `unisrv network` — create private networks, manage instances' membership
+//! of them, the addresses used and held on them, the policy rules between
+//! them and the peerings between networks, connect this machine to them over
+//! WireGuard, and the addressing rules shared with `instance run --network`.
+
+pub mod address;
+pub mod attach;
+pub mod create;
+pub mod ips;
+pub mod list;
+pub mod peer;
+pub mod policy;
+pub mod reserve;
+pub mod run;
+pub mod show;
+pub mod wireguard;
+
+pub use address::{NetworkSpec, NetworkUsage, claim_address, resolve};
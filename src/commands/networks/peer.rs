@@ -0,0 +1,264 @@
+//! `unisrv network peer <a> <b>` and `unisrv network unpeer <a> <b>` — route
+//! traffic between two networks so their instances can talk without being
+//! moved onto one.
+//!
+//! Routing needs every address to mean one place, so a peering is refused
+//! client-side when the two ranges overlap, or when either overlaps a network
+//! the other side is already peered with. Policy rules still apply on each
+//! side.
+
+use std::iter;
+
+use anyhow::{Context, Result, anyhow, bail};
+use cidr::Ipv4Cidr;
+use unisrv_api::ApiClient;
+use unisrv_api::models::{CreateNetworkPeeringRequest, NetworkListItem, NetworkPeering};
+use uuid::Uuid;
+
+use super::address;
+use crate::commands::confirm::Guard;
+use crate::commands::up::plan::ResolvedEnvironment;
+
+pub async fn peer(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    a: &str,
+    b: &str,
+) -> Result<()> {
+    if a == b {
+        bail!("can't peer {a} with itself");
+    }
+    let networks = client.list_networks(env.id, false).await?.networks;
+    let (a, b) = (address::pick(&networks, a)?, address::pick(&networks, b)?);
+    let a_peers = fetch(client, env.id, a).await?;
+    if a_peers.iter().any(|p| p.peer_network_id == b.id) {
+        bail!("{} and {} are already peered", a.name, b.name);
+    }
+    let b_peers = fetch(client, env.id, b).await?;
+    check_overlap(&networks, a, b, &a_peers, &b_peers)?;
+
+    client
+        .create_network_peering(
+            env.id,
+            a.id,
+            CreateNetworkPeeringRequest {
+                peer_network_id: b.id,
+            },
+        )
+        .await
+        .with_context(|| format!("failed to peer {} with {}", a.name, b.name))?;
+    println!(
+        "\u{2713} Peered {} ({}) with {} ({}).",
+        a.name, a.ipv4_cidr, b.name, b.ipv4_cidr
+    );
+    Ok(())
+}
+
+pub async fn unpeer(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    a: &str,
+    b: &str,
+    guard: &Guard<'_>,
+) -> Result<()> {
+    let networks = client.list_networks(env.id, false).await?.networks;
+    let (a, b) = (address::pick(&networks, a)?, address::pick(&networks, b)?);
+    let peering = fetch(client, env.id, a)
+        .await?
+        .into_iter()
+        .find(|p| p.peer_network_id == b.id)
+        .ok_or_else(|| anyhow!("{} and {} aren't peered", a.name, b.name))?;
+    if !guard.confirm(&format!("unpeer {} and {}", a.name, b.name), &a.name)? {
+        println!("Aborted.");
+        return Ok(());
+    }
+
+    client
+        .delete_network_peering(env.id, a.id, peering.id)
+        .await
+        .with_context(|| format!("failed to unpeer {} and {}", a.name, b.name))?;
+    println!("\u{2713} Unpeered {} and {}.", a.name, b.name);
+    Ok(())
+}
+
+/// The network's peerings, or a clear error from a backend without peering.
+async fn fetch(
+    client: &dyn ApiClient,
+    env_id: Uuid,
+    network: &NetworkListItem,
+) -> Result<Vec<NetworkPeering>> {
+    match client.list_network_peerings(env_id, network.id).await {
+        Ok(list) => Ok(list.peerings),
+        Err(e) if e.is_unsupported_endpoint() => {
+            bail!("this server doesn't support network peering")
+        }
+        Err(e) => Err(e).with_context(|| format!("failed to list peerings of {}", network.name)),
+    }
+}
+
+/// Refuse a peering that would make an address ambiguous: each side against
+/// the other and everything the other is already peered with.
+fn check_overlap(
+    networks: &[NetworkListItem],
+    a: &NetworkListItem,
+    b: &NetworkListItem,
+    a_peers: &[NetworkPeering],
+    b_peers: &[NetworkPeering],
+) -> Result<()> {
+    let cidr = |n: &NetworkListItem| {
+        n.ipv4_cidr.parse::<Ipv4Cidr>().with_context(|| {
+            format!(
+                "network {} has an unreadable CIDR {:?}",
+                n.name, n.ipv4_cidr
+            )
+        })
+    };
+    let by_id = |id: Uuid| networks.iter().find(|n| n.id == id);
+
+    for (side, other, other_peers) in [(a, b, b_peers), (b, a, a_peers)] {
+        let side_cidr = cidr(side)?;
+        let reachable =
+            iter::once(other).chain(other_peers.iter().filter_map(|p| by_id(p.peer_network_id)));
+        for target in reachable {
+            let target_cidr = cidr(target)?;
+            if !address::overlaps(side_cidr, target_cidr) {
+                continue;
+            }
+            if target.id == other.id {
+                bail!(
+                    "can't peer {} ({}) with {} ({}): their address ranges overlap",
+                    a.name,
+                    a.ipv4_cidr,
+                    b.name,
+                    b.ipv4_cidr
+                );
+            }
+            bail!(
+                "can't peer {} with {}: {} ({side_cidr}) overlaps {} ({target_cidr}), \
+                 which {} is already peered with",
+                a.name,
+                b.name,
+                side.name,
+                target.name,
+                other.name
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::up::plan::test_env;
+    use chrono::NaiveDateTime;
+    use unisrv_api::models::{NetworkListResponse, NetworkPeeringListResponse};
+    use unisrv_api::test_support::MockApiClient;
+
+    fn network(name: &str, cidr: &str) -> NetworkListItem {
+        NetworkListItem {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            ipv4_cidr: cidr.into(),
+            instance_count: None,
+            managed_by: None,
+        }
+    }
+
+    fn peering(with: &NetworkListItem) -> NetworkPeering {
+        NetworkPeering {
+            id: Uuid::new_v4(),
+            peer_network_id: with.id,
+            peer_network_name: with.name.clone(),
+            created_at: NaiveDateTime::default(),
+        }
+    }
+
+    fn peerings(
+        list: Vec<NetworkPeering>,
+    ) -> Result<NetworkPeeringListResponse, unisrv_api::ApiError> {
+        Ok(NetworkPeeringListResponse { peerings: list })
+    }
+
+    fn backend(networks: &[&NetworkListItem]) -> MockApiClient {
+        MockApiClient::logged_in().with_list_networks(Ok(NetworkListResponse {
+            networks: networks.iter().map(|n| (*n).clone()).collect(),
+        }))
+    }
+
+    #[tokio::test]
+    async fn peers_disjoint_networks() {
+        let (a, b) = (network("a", "10.0.0.0/24"), network("b", "10.0.1.0/24"));
+        let mock = backend(&[&a, &b])
+            .push_list_network_peerings(peerings(vec![]))
+            .push_list_network_peerings(peerings(vec![]))
+            .push_create_network_peering(Ok(peering(&b)));
+
+        peer(&mock, &test_env(), "a", "b").await.unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let (_, on, req) = &calls.create_network_peering_calls[0];
+        assert_eq!((*on, req.peer_network_id), (a.id, b.id));
+    }
+
+    #[tokio::test]
+    async fn overlapping_ranges_are_refused_before_the_api_call() {
+        let (a, b) = (network("a", "10.0.0.0/16"), network("b", "10.0.5.0/24"));
+        let mock = backend(&[&a, &b])
+            .push_list_network_peerings(peerings(vec![]))
+            .push_list_network_peerings(peerings(vec![]));
+
+        let err = peer(&mock, &test_env(), "a", "b").await.unwrap_err();
+
+        assert!(err.to_string().contains("ranges overlap"), "{err:#}");
+        assert!(
+            mock.calls
+                .lock()
+                .unwrap()
+                .create_network_peering_calls
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn overlap_with_an_existing_peer_is_refused() {
+        let a = network("a", "10.0.0.0/24");
+        let b = network("b", "10.1.0.0/24");
+        let c = network("c", "10.1.0.0/16");
+        let mock = backend(&[&a, &b, &c])
+            .push_list_network_peerings(peerings(vec![peering(&c)]))
+            .push_list_network_peerings(peerings(vec![]));
+
+        let err = peer(&mock, &test_env(), "a", "b").await.unwrap_err();
+
+        let msg = err.to_string();
+        assert!(
+            msg.contains("b (10.1.0.0/24) overlaps c (10.1.0.0/16)"),
+            "{msg}"
+        );
+        assert!(msg.contains("which a is already peered with"), "{msg}");
+    }
+
+    #[tokio::test]
+    async fn unpeer_deletes_the_peering_and_says_when_there_is_none() {
+        let (a, b) = (network("a", "10.0.0.0/24"), network("b", "10.0.1.0/24"));
+        let existing = peering(&b);
+        let mock = backend(&[&a, &b])
+            .push_list_network_peerings(peerings(vec![existing.clone()]))
+            .push_delete_network_peering(Ok(()));
+
+        unpeer(&mock, &test_env(), "a", "b", &Guard::unprotected())
+            .await
+            .unwrap();
+        assert_eq!(
+            mock.calls.lock().unwrap().delete_network_peering_calls[0].2,
+            existing.id
+        );
+
+        let mock = backend(&[&a, &b]).push_list_network_peerings(peerings(vec![]));
+        let err = unpeer(&mock, &test_env(), "a", "b", &Guard::unprotected())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("aren't peered"), "{err:#}");
+    }
+}
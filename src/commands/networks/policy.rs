@@ -0,0 +1,535 @@
+//! `unisrv network policy add|list|delete` — firewall rules for traffic
+//! between the instances on a network, which otherwise reach each other on
+//! every port.
+//!
+//! A rule matches traffic from a CIDR or an instance to an instance, a port,
+//! or a port on one instance (`--to web:5432`), and allows or denies it.
+//! Instances are named the way every other command names them, and must be on
+//! the network the rule is for.
+
+use std::collections::HashMap;
+
+use anyhow::{Context, Result, anyhow, bail};
+use cidr::Ipv4Cidr;
+use comfy_table::{Attribute, Cell, Color};
+use unisrv_api::ApiClient;
+use unisrv_api::models::{
+    CreateNetworkPolicyRequest, InstanceListEntry, NetworkPolicyRule, PolicyAction,
+    PolicyDestination, PolicySource,
+};
+use uuid::Uuid;
+
+use super::address::{self, NetworkUsage};
+use crate::commands::confirm::Guard;
+use crate::commands::instance::resolve::resolve_instance;
+use crate::commands::output::Output;
+use crate::commands::ui::{cell_with_color, colors_enabled, new_table};
+use crate::commands::up::plan::ResolvedEnvironment;
+
+/// A parsed `--from`: a CIDR (a bare address is that one host) or an
+/// instance reference.
+#[derive(Debug, PartialEq)]
+enum FromArg {
+    Cidr(Ipv4Cidr),
+    Instance(String),
+}
+
+impl FromArg {
+    fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+        match s.parse::<Ipv4Cidr>() {
+            Ok(cidr) => Ok(FromArg::Cidr(cidr)),
+            Err(e) if s.contains('/') => Err(anyhow!("invalid --from {s:?}: {e}")),
+            Err(_) if s.is_empty() => bail!("--from can't be empty"),
+            Err(_) => Ok(FromArg::Instance(s.to_string())),
+        }
+    }
+}
+
+/// A parsed `--to`: `5432` (a port on any instance), `web` (every port on an
+/// instance) or `web:5432`.
+#[derive(Debug, PartialEq)]
+struct ToArg {
+    instance: Option<String>,
+    port: Option<u16>,
+}
+
+impl ToArg {
+    fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let port = |p: &str| {
+            p.parse::<u16>()
+                .ok()
+                .filter(|p| *p != 0)
+                .ok_or_else(|| anyhow!("invalid --to {s:?}: {p:?} is not a port"))
+        };
+        if s.is_empty() {
+            bail!("--to can't be empty");
+        }
+        if s.bytes().all(|b| b.is_ascii_digit()) {
+            return Ok(ToArg {
+                instance: None,
+                port: Some(port(s)?),
+            });
+        }
+        match s.rsplit_once(':') {
+            Some((instance, p)) => Ok(ToArg {
+                instance: Some(instance.to_string()),
+                port: Some(port(p)?),
+            }),
+            None => Ok(ToArg {
+                instance: Some(s.to_string()),
+                port: None,
+            }),
+        }
+    }
+}
+
+pub async fn add(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    network: &str,
+    from: &str,
+    to: &str,
+    action: PolicyAction,
+) -> Result<()> {
+    let (from, to) = (FromArg::parse(from)?, ToArg::parse(to)?);
+    let usage = address::resolve(client, env.id, network).await?;
+    let instances = client.list_instances(env.id).await?.instances;
+
+    let source = match from {
+        FromArg::Cidr(cidr) => PolicySource::Cidr {
+            cidr: cidr.to_string(),
+        },
+        FromArg::Instance(reference) => PolicySource::Instance {
+            instance_id: member(&usage, &instances, &reference)?,
+        },
+    };
+    let destination = PolicyDestination {
+        instance_id: to
+            .instance
+            .as_deref()
+            .map(|reference| member(&usage, &instances, reference))
+            .transpose()?,
+        port: to.port,
+    };
+
+    let rule = client
+        .create_network_policy(
+            env.id,
+            usage.id,
+            CreateNetworkPolicyRequest {
+                source,
+                destination,
+                action,
+            },
+        )
+        .await
+        .with_context(|| format!("failed to add a rule to {}", usage.name))?;
+    let names = names(&instances);
+    println!(
+        "\u{2713} Added rule {} to {}: {}.",
+        &rule.id.to_string()[..8],
+        usage.name,
+        describe(&rule, &names)
+    );
+    Ok(())
+}
+
+pub async fn list(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    network: &str,
+    output: &Output,
+) -> Result<()> {
+    let found = address::find(client, env.id, network).await?;
+    let rules = fetch(client, env.id, found.id, &found.name).await?;
+
+    match output {
+        Output::Json | Output::Jq(_) => return output.print_json(&rules),
+        Output::Template(template) => {
+            print!("{}", template.render_all(&rules)?);
+            return Ok(());
+        }
+        Output::Table => {}
+    }
+
+    if rules.is_empty() {
+        println!(
+            "No policy rules on {}: its instances can reach each other on every port.",
+            found.name
+        );
+        return Ok(());
+    }
+    let names = names(&client.list_instances(env.id).await?.instances);
+    println!("{}", render_rules(&rules, &names, colors_enabled()));
+    Ok(())
+}
+
+pub async fn delete(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    network: &str,
+    rule: &str,
+    guard: &Guard<'_>,
+) -> Result<()> {
+    let found = address::find(client, env.id, network).await?;
+    let rules = fetch(client, env.id, found.id, &found.name).await?;
+    let rule = find_rule(&rules, rule, &found.name)?;
+    let short_id = &rule.id.to_string()[..8];
+    if !guard.confirm(
+        &format!("delete rule {short_id} from {}", found.name),
+        short_id,
+    )? {
+        println!("Aborted.");
+        return Ok(());
+    }
+
+    client
+        .delete_network_policy(env.id, found.id, rule.id)
+        .await
+        .with_context(|| format!("failed to delete rule {} from {}", rule.id, found.name))?;
+    let names = names(&client.list_instances(env.id).await?.instances);
+    println!(
+        "\u{2713} Deleted rule {} from {}: {}.",
+        &rule.id.to_string()[..8],
+        found.name,
+        describe(rule, &names)
+    );
+    Ok(())
+}
+
+/// The network's rules, or a clear error from a backend without policies.
+pub async fn fetch(
+    client: &dyn ApiClient,
+    env_id: Uuid,
+    network_id: Uuid,
+    network: &str,
+) -> Result<Vec<NetworkPolicyRule>> {
+    match client.list_network_policies(env_id, network_id).await {
+        Ok(list) => Ok(list.rules),
+        Err(e) if e.is_unsupported_endpoint() => {
+            bail!("this server doesn't support network policies")
+        }
+        Err(e) => Err(e).with_context(|| format!("failed to list policy rules on {network}")),
+    }
+}
+
+/// The rule whose id is, or starts with, `input`.
+fn find_rule<'a>(
+    rules: &'a [NetworkPolicyRule],
+    input: &str,
+    network: &str,
+) -> Result<&'a NetworkPolicyRule> {
+    let input = input.trim().to_ascii_lowercase();
+    if input.is_empty() {
+        bail!("no rule id given");
+    }
+    let matching: Vec<&NetworkPolicyRule> = rules
+        .iter()
+        .filter(|r| r.id.to_string().starts_with(&input))
+        .collect();
+    match matching.as_slice() {
+        [rule] => Ok(rule),
+        [] => bail!(
+            "no rule {input} on network {network} (see `unisrv network policy list {network}`)"
+        ),
+        _ => bail!(
+            "{input} matches {} rules on network {network}; give more of the id",
+            matching.len()
+        ),
+    }
+}
+
+/// Resolve `reference` to an instance on `usage`'s network.
+fn member(usage: &NetworkUsage, instances: &[InstanceListEntry], reference: &str) -> Result<Uuid> {
+    let instance = resolve_instance(reference, instances)?;
+    if !usage.used.values().any(|id| *id == instance.id) {
+        bail!(
+            "{reference} is not on network {}; attach it first (`unisrv network attach {} {reference}`)",
+            usage.name,
+            usage.name
+        );
+    }
+    Ok(instance.id)
+}
+
+/// Instance id → name (or short id), for describing rules.
+pub fn names(instances: &[InstanceListEntry]) -> HashMap<Uuid, String> {
+    instances
+        .iter()
+        .filter_map(|i| Some((i.id, i.name.clone()?)))
+        .collect()
+}
+
+fn instance_label(id: Uuid, names: &HashMap<Uuid, String>) -> String {
+    names
+        .get(&id)
+        .cloned()
+        .unwrap_or_else(|| id.to_string()[..8].to_string())
+}
+
+fn source_label(source: &PolicySource, names: &HashMap<Uuid, String>) -> String {
+    match source {
+        PolicySource::Cidr { cidr } => cidr.clone(),
+        PolicySource::Instance { instance_id } => instance_label(*instance_id, names),
+        PolicySource::Unknown => "?".to_string(),
+    }
+}
+
+/// `web:5432`, `web`, `*:5432`, or `*` for a destination with neither.
+fn destination_label(destination: &PolicyDestination, names: &HashMap<Uuid, String>) -> String {
+    let instance = destination
+        .instance_id
+        .map_or_else(|| "*".to_string(), |id| instance_label(id, names));
+    match destination.port {
+        Some(port) => format!("{instance}:{port}"),
+        None => instance,
+    }
+}
+
+/// One-line rule, e.g. `deny 10.0.0.0/24 → db:5432`.
+fn describe(rule: &NetworkPolicyRule, names: &HashMap<Uuid, String>) -> String {
+    format!(
+        "{} {} \u{2192} {}",
+        rule.action,
+        source_label(&rule.source, names),
+        destination_label(&rule.destination, names)
+    )
+}
+
+/// Pure so it can be asserted on without a terminal.
+pub fn render_rules(
+    rules: &[NetworkPolicyRule],
+    names: &HashMap<Uuid, String>,
+    use_color: bool,
+) -> String {
+    let mut table = new_table();
+    table.set_header(vec![
+        Cell::new("ID").add_attribute(Attribute::Bold),
+        Cell::new("FROM").add_attribute(Attribute::Bold),
+        Cell::new("TO").add_attribute(Attribute::Bold),
+        Cell::new("ACTION").add_attribute(Attribute::Bold),
+    ]);
+    for rule in rules {
+        let color = match rule.action {
+            PolicyAction::Allow => Color::Green,
+            PolicyAction::Deny => Color::Red,
+            PolicyAction::Unknown => Color::DarkGrey,
+        };
+        table.add_row(vec![
+            Cell::new(&rule.id.to_string()[..8]),
+            Cell::new(source_label(&rule.source, names)),
+            Cell::new(destination_label(&rule.destination, names)),
+            cell_with_color(rule.action.to_string(), Some(color), use_color),
+        ]);
+    }
+    table.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::up::plan::test_env;
+    use chrono::NaiveDateTime;
+    use unisrv_api::models::{
+        InstanceInfo, InstanceListResponse, InstanceState, NetworkListItem, NetworkListResponse,
+        NetworkPolicyListResponse, NetworkResponse,
+    };
+    use unisrv_api::test_support::MockApiClient;
+
+    fn instance(id: Uuid, name: &str) -> InstanceListEntry {
+        InstanceListEntry {
+            id,
+            name: Some(name.into()),
+            state: InstanceState("running".into()),
+            container_image: "postgres:16".into(),
+            created_at: NaiveDateTime::default(),
+            deployment: None,
+            retained_until: None,
+        }
+    }
+
+    fn rule(
+        source: PolicySource,
+        instance_id: Option<Uuid>,
+        port: Option<u16>,
+    ) -> NetworkPolicyRule {
+        NetworkPolicyRule {
+            id: Uuid::new_v4(),
+            source,
+            destination: PolicyDestination { instance_id, port },
+            action: PolicyAction::Deny,
+            created_at: NaiveDateTime::default(),
+        }
+    }
+
+    /// `backend` holding `db` (10.0.0.3); `web` exists but isn't on it.
+    fn backend(net: Uuid, db: Uuid) -> MockApiClient {
+        MockApiClient::logged_in()
+            .with_list_networks(Ok(NetworkListResponse {
+                networks: vec![NetworkListItem {
+                    id: net,
+                    name: "backend".into(),
+                    ipv4_cidr: "10.0.0.0/24".into(),
+                    instance_count: None,
+                    managed_by: None,
+                }],
+            }))
+            .push_get_network(Ok(NetworkResponse {
+                id: net,
+                environment_id: Uuid::nil(),
+                name: "backend".into(),
+                ipv4_cidr: "10.0.0.0/24".into(),
+                created_at: NaiveDateTime::default(),
+                instances: vec![InstanceInfo {
+                    id: db,
+                    internal_ip: "10.0.0.3".into(),
+                }],
+            }))
+            .with_list_instances(Ok(InstanceListResponse {
+                instances: vec![instance(db, "db"), instance(Uuid::new_v4(), "web")],
+            }))
+    }
+
+    #[test]
+    fn parses_sources_and_destinations() {
+        assert_eq!(
+            FromArg::parse("10.0.0.0/24").unwrap(),
+            FromArg::Cidr("10.0.0.0/24".parse().unwrap())
+        );
+        assert_eq!(
+            FromArg::parse("10.0.0.7").unwrap(),
+            FromArg::Cidr("10.0.0.7/32".parse().unwrap())
+        );
+        assert_eq!(
+            FromArg::parse("web").unwrap(),
+            FromArg::Instance("web".into())
+        );
+        assert!(FromArg::parse("10.0.0.7/24").is_err(), "host bits set");
+
+        let to = |instance: Option<&str>, port| ToArg {
+            instance: instance.map(String::from),
+            port,
+        };
+        assert_eq!(ToArg::parse("5432").unwrap(), to(None, Some(5432)));
+        assert_eq!(ToArg::parse("db").unwrap(), to(Some("db"), None));
+        assert_eq!(ToArg::parse("db:5432").unwrap(), to(Some("db"), Some(5432)));
+        assert!(ToArg::parse("db:http").is_err());
+        assert!(ToArg::parse("0").is_err());
+    }
+
+    #[tokio::test]
+    async fn add_resolves_instances_on_the_network() {
+        let (net, db) = (Uuid::new_v4(), Uuid::new_v4());
+        let created = rule(
+            PolicySource::Cidr {
+                cidr: "10.0.0.0/24".into(),
+            },
+            Some(db),
+            Some(5432),
+        );
+        let mock = backend(net, db).push_create_network_policy(Ok(created));
+
+        add(
+            &mock,
+            &test_env(),
+            "backend",
+            "10.0.0.0/24",
+            "db:5432",
+            PolicyAction::Deny,
+        )
+        .await
+        .unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let (_, network_id, req) = &calls.create_network_policy_calls[0];
+        assert_eq!(*network_id, net);
+        assert_eq!(
+            req.destination,
+            PolicyDestination {
+                instance_id: Some(db),
+                port: Some(5432)
+            }
+        );
+        assert_eq!(req.action, PolicyAction::Deny);
+    }
+
+    #[tokio::test]
+    async fn add_refuses_instances_off_the_network() {
+        let mock = backend(Uuid::new_v4(), Uuid::new_v4());
+
+        let err = add(
+            &mock,
+            &test_env(),
+            "backend",
+            "web",
+            "db",
+            PolicyAction::Allow,
+        )
+        .await
+        .unwrap_err();
+
+        assert!(
+            err.to_string().contains("web is not on network backend"),
+            "{err}"
+        );
+        assert!(
+            mock.calls
+                .lock()
+                .unwrap()
+                .create_network_policy_calls
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn delete_accepts_an_id_prefix() {
+        let (net, db) = (Uuid::new_v4(), Uuid::new_v4());
+        let target = rule(PolicySource::Instance { instance_id: db }, None, Some(22));
+        let mock = backend(net, db)
+            .with_list_network_policies(Ok(NetworkPolicyListResponse {
+                rules: vec![target.clone()],
+            }))
+            .push_delete_network_policy(Ok(()));
+
+        let env = test_env();
+        delete(
+            &mock,
+            &env,
+            "backend",
+            &target.id.to_string()[..6],
+            &Guard::unprotected(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            mock.calls.lock().unwrap().delete_network_policy_calls,
+            vec![(env.id, net, target.id)]
+        );
+    }
+
+    #[test]
+    fn rules_table_names_instances_and_ports() {
+        let db = Uuid::new_v4();
+        let names = HashMap::from([(db, "db".to_string())]);
+        let out = render_rules(
+            &[
+                rule(
+                    PolicySource::Cidr {
+                        cidr: "10.0.0.0/24".into(),
+                    },
+                    Some(db),
+                    Some(5432),
+                ),
+                rule(PolicySource::Instance { instance_id: db }, None, Some(22)),
+            ],
+            &names,
+            false,
+        );
+        assert!(out.contains("db:5432"), "{out}");
+        assert!(out.contains("*:22"), "{out}");
+        assert!(out.contains("deny"), "{out}");
+    }
+}
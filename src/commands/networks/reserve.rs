@@ -0,0 +1,238 @@
+//! `unisrv network reserve <network> <ip> [--name db-vip]` and
+//! `unisrv network reservations <network>` — hold addresses for instances
+//! that will need a known address later, e.g. a database's virtual IP.
+//!
+//! A held address is never auto-assigned; an instance gets it only by asking
+//! (`instance run --network 10.0.0.5@backend`, `network attach --ip`).
+
+use std::net::Ipv4Addr;
+
+use anyhow::{Context, Result, bail};
+use chrono::NaiveDateTime;
+use comfy_table::{Attribute, Cell};
+use unisrv_api::ApiClient;
+use unisrv_api::models::{CreateIpReservationRequest, IpReservation};
+
+use super::address;
+use crate::commands::output::Output;
+use crate::commands::ui::{format_relative, new_table};
+use crate::commands::up::plan::ResolvedEnvironment;
+
+pub async fn reserve(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    network: &str,
+    ip: Ipv4Addr,
+    name: Option<String>,
+) -> Result<()> {
+    let usage = address::resolve(client, env.id, network).await?;
+    if let Some(held) = usage.held.get(&ip) {
+        match held {
+            Some(as_name) => bail!("{ip} is already reserved on {} as {as_name}", usage.name),
+            None => bail!("{ip} is already reserved on {}", usage.name),
+        }
+    }
+    usage.check(ip)?;
+
+    client
+        .create_ip_reservation(
+            env.id,
+            usage.id,
+            CreateIpReservationRequest {
+                ip: ip.to_string(),
+                name: name.clone(),
+            },
+        )
+        .await
+        .with_context(|| format!("failed to reserve {ip} on {}", usage.name))?;
+    match &name {
+        Some(name) => println!("\u{2713} Reserved {ip} on {} as {name}.", usage.name),
+        None => println!("\u{2713} Reserved {ip} on {}.", usage.name),
+    }
+    eprintln!(
+        "{}",
+        console::style(format!(
+            "Start an instance on it with `unisrv instance run --network {ip}@{} <image>`.",
+            usage.name
+        ))
+        .dim()
+    );
+    Ok(())
+}
+
+pub async fn reservations(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    network: &str,
+    output: &Output,
+) -> Result<()> {
+    let found = address::find(client, env.id, network).await?;
+    let mut list = match client.list_ip_reservations(env.id, found.id).await {
+        Ok(list) => list.reservations,
+        Err(e) if e.is_unsupported_endpoint() => {
+            bail!("this server doesn't support address reservations")
+        }
+        Err(e) => {
+            return Err(e)
+                .with_context(|| format!("failed to list address reservations on {network}"));
+        }
+    };
+    list.sort_by_key(|r| r.ip.parse::<Ipv4Addr>().map_or(u32::MAX, u32::from));
+
+    match output {
+        Output::Json | Output::Jq(_) => return output.print_json(&list),
+        Output::Template(template) => {
+            print!("{}", template.render_all(&list)?);
+            return Ok(());
+        }
+        Output::Table => {}
+    }
+
+    if list.is_empty() {
+        println!("No addresses reserved on {}.", found.name);
+        return Ok(());
+    }
+    println!("{}", render_table(&list, chrono::Utc::now().naive_utc()));
+    Ok(())
+}
+
+/// Pure so it can be asserted on without a terminal.
+fn render_table(reservations: &[IpReservation], now: NaiveDateTime) -> String {
+    let mut table = new_table();
+    table.set_header(vec![
+        Cell::new("ADDRESS").add_attribute(Attribute::Bold),
+        Cell::new("NAME").add_attribute(Attribute::Bold),
+        Cell::new("RESERVED").add_attribute(Attribute::Bold),
+    ]);
+    for reservation in reservations {
+        table.add_row(vec![
+            Cell::new(&reservation.ip),
+            Cell::new(reservation.name.as_deref().unwrap_or("\u{2014}")),
+            Cell::new(format_relative(reservation.created_at, now)),
+        ]);
+    }
+    table.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::up::plan::test_env;
+    use unisrv_api::models::{
+        InstanceInfo, IpReservationListResponse, NetworkListItem, NetworkListResponse,
+        NetworkResponse,
+    };
+    use unisrv_api::test_support::MockApiClient;
+    use uuid::Uuid;
+
+    fn held(ip: &str, name: Option<&str>) -> IpReservation {
+        IpReservation {
+            id: Uuid::new_v4(),
+            ip: ip.into(),
+            name: name.map(String::from),
+            created_at: NaiveDateTime::default(),
+        }
+    }
+
+    /// `backend` (10.0.0.0/24) with an instance at 10.0.0.3 and the given
+    /// reservations.
+    fn backend(net: Uuid, reservations: Vec<IpReservation>) -> MockApiClient {
+        MockApiClient::logged_in()
+            .with_list_networks(Ok(NetworkListResponse {
+                networks: vec![NetworkListItem {
+                    id: net,
+                    name: "backend".into(),
+                    ipv4_cidr: "10.0.0.0/24".into(),
+                    instance_count: None,
+                    managed_by: None,
+                }],
+            }))
+            .push_get_network(Ok(NetworkResponse {
+                id: net,
+                environment_id: Uuid::nil(),
+                name: "backend".into(),
+                ipv4_cidr: "10.0.0.0/24".into(),
+                created_at: NaiveDateTime::default(),
+                instances: vec![InstanceInfo {
+                    id: Uuid::new_v4(),
+                    internal_ip: "10.0.0.3".into(),
+                }],
+            }))
+            .push_list_ip_reservations(Ok(IpReservationListResponse { reservations }))
+    }
+
+    #[tokio::test]
+    async fn reserves_a_free_address() {
+        let net = Uuid::new_v4();
+        let mock = backend(net, vec![]).push_create_ip_reservation(Ok(held("10.0.0.9", None)));
+
+        reserve(
+            &mock,
+            &test_env(),
+            "backend",
+            Ipv4Addr::new(10, 0, 0, 9),
+            Some("db-vip".into()),
+        )
+        .await
+        .unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let (_, network_id, req) = &calls.create_ip_reservation_calls[0];
+        assert_eq!(*network_id, net);
+        assert_eq!(req.ip, "10.0.0.9");
+        assert_eq!(req.name.as_deref(), Some("db-vip"));
+    }
+
+    #[tokio::test]
+    async fn refuses_addresses_already_spoken_for() {
+        let cases = [
+            ("10.0.0.3", "already assigned to instance"),
+            ("10.0.0.1", "reserved for the gateway"),
+            ("10.0.0.7", "already reserved on backend as db-vip"),
+        ];
+        for (ip, expected) in cases {
+            let mock = backend(Uuid::new_v4(), vec![held("10.0.0.7", Some("db-vip"))]);
+            let err = reserve(&mock, &test_env(), "backend", ip.parse().unwrap(), None)
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains(expected), "{ip}: {err}");
+            assert!(
+                mock.calls
+                    .lock()
+                    .unwrap()
+                    .create_ip_reservation_calls
+                    .is_empty()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn listing_says_when_the_server_lacks_reservations() {
+        let mock = MockApiClient::logged_in().with_list_networks(Ok(NetworkListResponse {
+            networks: vec![NetworkListItem {
+                id: Uuid::new_v4(),
+                name: "backend".into(),
+                ipv4_cidr: "10.0.0.0/24".into(),
+                instance_count: None,
+                managed_by: None,
+            }],
+        }));
+
+        let err = reservations(&mock, &test_env(), "backend", &Output::Table)
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains("doesn't support"), "{err}");
+    }
+
+    #[test]
+    fn table_shows_address_and_name() {
+        let out = render_table(
+            &[held("10.0.0.5", Some("db-vip")), held("10.0.0.6", None)],
+            NaiveDateTime::default(),
+        );
+        assert!(out.contains("10.0.0.5"), "{out}");
+        assert!(out.contains("db-vip"), "{out}");
+        assert!(out.contains("\u{2014}"), "{out}");
+    }
+}
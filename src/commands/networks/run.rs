@@ -0,0 +1,171 @@
+//! Entry point for the `network` command group: resolve the environment the
+//! same way the instance commands do, announce it, then dispatch.
+
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use unisrv_api::ApiClient;
+use unisrv_api::models::PolicyAction;
+
+use super::create::{self, CidrChoice};
+use super::wireguard::{self, WgQuick};
+use super::{attach, ips, list, peer, policy, reserve, show};
+use crate::commands::confirm::Guard;
+use crate::commands::instance::run::{announce_environment, current_environment};
+use crate::commands::output::Output;
+use crate::commands::table::TableOptions;
+
+/// What the user asked the network group to do.
+pub enum NetworkAction {
+    List {
+        output: Output,
+        table: TableOptions,
+    },
+    New {
+        name: String,
+        cidr: CidrChoice,
+    },
+    Show {
+        network: String,
+        output: Output,
+        /// Redraw this often until interrupted.
+        watch: Option<Duration>,
+    },
+    Ips {
+        network: String,
+        output: Output,
+    },
+    Attach {
+        network: String,
+        instance: String,
+        ip: Option<Ipv4Addr>,
+    },
+    Detach {
+        network: String,
+        instance: String,
+    },
+    Reserve {
+        network: String,
+        ip: Ipv4Addr,
+        name: Option<String>,
+    },
+    Reservations {
+        network: String,
+        output: Output,
+    },
+    Peer {
+        network: String,
+        peer: String,
+    },
+    Unpeer {
+        network: String,
+        peer: String,
+    },
+    PolicyAdd {
+        network: String,
+        from: String,
+        to: String,
+        action: PolicyAction,
+    },
+    PolicyList {
+        network: String,
+        output: Output,
+    },
+    PolicyDelete {
+        network: String,
+        rule: String,
+    },
+    Connect {
+        network: String,
+        name: Option<String>,
+        apply: bool,
+    },
+    Disconnect {
+        network: String,
+    },
+    Status {
+        output: Output,
+    },
+}
+
+pub async fn run(
+    client: &dyn ApiClient,
+    env_flag: Option<&str>,
+    action: NetworkAction,
+) -> Result<()> {
+    let env = current_environment(client, env_flag).await?;
+    let machine = match &action {
+        NetworkAction::List { output, .. }
+        | NetworkAction::Show { output, .. }
+        | NetworkAction::Ips { output, .. }
+        | NetworkAction::Reservations { output, .. }
+        | NetworkAction::PolicyList { output, .. }
+        | NetworkAction::Status { output } => output.is_machine(),
+        _ => false,
+    };
+    if !machine {
+        announce_environment(&env);
+    }
+    let guard = Guard::for_env(&env)?;
+
+    match action {
+        NetworkAction::List { output, table } => list::list(client, &env, &output, &table).await,
+        NetworkAction::New { name, cidr } => create::create(client, &env, &name, cidr).await,
+        NetworkAction::Show {
+            network,
+            output,
+            watch,
+        } => show::show(client, &env, &network, &output, watch).await,
+        NetworkAction::Ips { network, output } => ips::ips(client, &env, &network, &output).await,
+        NetworkAction::Attach {
+            network,
+            instance,
+            ip,
+        } => attach::attach(client, &env, &network, &instance, ip).await,
+        NetworkAction::Detach { network, instance } => {
+            attach::detach(client, &env, &network, &instance, &guard).await
+        }
+        NetworkAction::Reserve { network, ip, name } => {
+            reserve::reserve(client, &env, &network, ip, name).await
+        }
+        NetworkAction::Reservations { network, output } => {
+            reserve::reservations(client, &env, &network, &output).await
+        }
+        NetworkAction::Peer { network, peer } => peer::peer(client, &env, &network, &peer).await,
+        NetworkAction::Unpeer { network, peer } => {
+            peer::unpeer(client, &env, &network, &peer, &guard).await
+        }
+        NetworkAction::PolicyAdd {
+            network,
+            from,
+            to,
+            action,
+        } => policy::add(client, &env, &network, &from, &to, action).await,
+        NetworkAction::PolicyList { network, output } => {
+            policy::list(client, &env, &network, &output).await
+        }
+        NetworkAction::PolicyDelete { network, rule } => {
+            policy::delete(client, &env, &network, &rule, &guard).await
+        }
+        NetworkAction::Connect {
+            network,
+            name,
+            apply,
+        } => {
+            let dir = wireguard_dir()?;
+            wireguard::connect(client, &env, &network, name, apply, &WgQuick, &dir).await
+        }
+        NetworkAction::Disconnect { network } => {
+            wireguard::disconnect(client, &network, &WgQuick, &wireguard_dir()?, &guard).await
+        }
+        NetworkAction::Status { output } => {
+            wireguard::status(client, &env, &output, &WgQuick, &wireguard_dir()?).await
+        }
+    }
+}
+
+fn wireguard_dir() -> Result<std::path::PathBuf> {
+    wireguard::default_dir()
+        .ok_or_else(|| anyhow!("can't find the home directory to keep WireGuard configs in"))
+}
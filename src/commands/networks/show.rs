@@ -0,0 +1,244 @@
+//! `unisrv network show <network>` — a network's addressing, members,
+//! reservations, policy rules and peerings at a glance.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::Serialize;
+use unisrv_api::ApiClient;
+use unisrv_api::models::{IpReservation, NetworkPeering, NetworkPolicyRule, NetworkResponse};
+use uuid::Uuid;
+
+use super::{address, policy};
+use crate::commands::output::Output;
+use crate::commands::ui::colors_enabled;
+use crate::commands::up::plan::ResolvedEnvironment;
+use crate::commands::watch::watch;
+
+/// Everything `show` prints, and the `--json` document.
+#[derive(Serialize)]
+struct NetworkDetail {
+    #[serde(flatten)]
+    network: NetworkResponse,
+    reservations: Vec<IpReservation>,
+    /// `None` when the backend doesn't support policies.
+    policies: Option<Vec<NetworkPolicyRule>>,
+    /// `None` when the backend doesn't support peering.
+    peerings: Option<Vec<NetworkPeering>>,
+}
+
+/// Print the network `network`, redrawing it every `watch_every` when given.
+pub async fn show(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    network: &str,
+    output: &Output,
+    watch_every: Option<Duration>,
+) -> Result<()> {
+    let found = address::find(client, env.id, network).await?;
+    if let Some(interval) = watch_every {
+        return watch(interval, async || view(client, env, found.id).await).await;
+    }
+
+    match output {
+        Output::Json | Output::Jq(_) => output.print_json(&fetch(client, env, found.id).await?),
+        Output::Template(template) => {
+            println!("{}", template.render(&fetch(client, env, found.id).await?)?);
+            Ok(())
+        }
+        Output::Table => {
+            print!("{}", view(client, env, found.id).await?);
+            Ok(())
+        }
+    }
+}
+
+/// The summary, with members and rules named after their instances.
+async fn view(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    network_id: Uuid,
+) -> Result<String> {
+    let (detail, instances) = tokio::try_join!(
+        fetch(client, env, network_id),
+        client.list_instances(env.id)
+    )?;
+    let names = policy::names(&instances.instances);
+    Ok(render(&detail, &names, colors_enabled()))
+}
+
+/// The network and everything `show` prints alongside it, requested
+/// together.
+async fn fetch(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    network_id: Uuid,
+) -> unisrv_api::Result<NetworkDetail> {
+    let (network, reservations, policies, peerings) = tokio::try_join!(
+        client.get_network(env.id, network_id),
+        async {
+            match client.list_ip_reservations(env.id, network_id).await {
+                Ok(list) => Ok(list.reservations),
+                Err(e) if e.is_unsupported_endpoint() => Ok(Vec::new()),
+                Err(e) => Err(e),
+            }
+        },
+        async {
+            match client.list_network_policies(env.id, network_id).await {
+                Ok(list) => Ok(Some(list.rules)),
+                Err(e) if e.is_unsupported_endpoint() => Ok(None),
+                Err(e) => Err(e),
+            }
+        },
+        async {
+            match client.list_network_peerings(env.id, network_id).await {
+                Ok(list) => Ok(Some(list.peerings)),
+                Err(e) if e.is_unsupported_endpoint() => Ok(None),
+                Err(e) => Err(e),
+            }
+        },
+    )?;
+    Ok(NetworkDetail {
+        network,
+        reservations,
+        policies,
+        peerings,
+    })
+}
+
+/// Plain-text summary. Pure so it can be asserted on without a terminal.
+fn render(detail: &NetworkDetail, names: &HashMap<Uuid, String>, use_color: bool) -> String {
+    let network = &detail.network;
+    let mut out = String::new();
+    let _ = writeln!(out, "{} ({})", network.name, network.id);
+    let _ = writeln!(out, "  cidr:         {}", network.ipv4_cidr);
+    if network.instances.is_empty() {
+        let _ = writeln!(out, "  instances:    none");
+    } else {
+        let _ = writeln!(out, "  instances:");
+        let mut members: Vec<_> = network.instances.iter().collect();
+        members.sort_by_key(|i| i.internal_ip.parse::<std::net::Ipv4Addr>().ok());
+        for member in members {
+            let name = names
+                .get(&member.id)
+                .cloned()
+                .unwrap_or_else(|| member.id.to_string()[..8].to_string());
+            let _ = writeln!(out, "    {:<15}  {name}", member.internal_ip);
+        }
+    }
+    if detail.reservations.is_empty() {
+        let _ = writeln!(out, "  reservations: none");
+    } else {
+        let _ = writeln!(out, "  reservations:");
+        for reservation in &detail.reservations {
+            let _ = writeln!(
+                out,
+                "    {:<15}  {}",
+                reservation.ip,
+                reservation.name.as_deref().unwrap_or("\u{2014}")
+            );
+        }
+    }
+    match &detail.policies {
+        None => {
+            let _ = writeln!(out, "  policies:     not supported by this server");
+        }
+        Some(rules) if rules.is_empty() => {
+            let _ = writeln!(out, "  policies:     none (all traffic allowed)");
+        }
+        Some(rules) => {
+            let _ = writeln!(out, "  policies:");
+            let _ = writeln!(out, "{}", policy::render_rules(rules, names, use_color));
+        }
+    }
+    match &detail.peerings {
+        // Peering is newer than the rest; an older server just omits it.
+        None => {}
+        Some(peerings) if peerings.is_empty() => {
+            let _ = writeln!(out, "  peerings:     none");
+        }
+        Some(peerings) => {
+            let _ = writeln!(out, "  peerings:");
+            for peering in peerings {
+                let _ = writeln!(out, "    {}", peering.peer_network_name);
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+    use unisrv_api::models::{InstanceInfo, PolicyAction, PolicyDestination, PolicySource};
+
+    fn detail(policies: Option<Vec<NetworkPolicyRule>>) -> NetworkDetail {
+        NetworkDetail {
+            network: NetworkResponse {
+                id: Uuid::nil(),
+                environment_id: Uuid::nil(),
+                name: "backend".into(),
+                ipv4_cidr: "10.0.0.0/24".into(),
+                created_at: NaiveDateTime::default(),
+                instances: vec![InstanceInfo {
+                    id: Uuid::nil(),
+                    internal_ip: "10.0.0.3".into(),
+                }],
+            },
+            reservations: vec![],
+            policies,
+            peerings: None,
+        }
+    }
+
+    #[test]
+    fn renders_members_and_a_rules_table() {
+        let names = HashMap::from([(Uuid::nil(), "db".to_string())]);
+        let rule = NetworkPolicyRule {
+            id: Uuid::new_v4(),
+            source: PolicySource::Cidr {
+                cidr: "10.0.0.0/24".into(),
+            },
+            destination: PolicyDestination {
+                instance_id: Some(Uuid::nil()),
+                port: Some(5432),
+            },
+            action: PolicyAction::Allow,
+            created_at: NaiveDateTime::default(),
+        };
+
+        let out = render(&detail(Some(vec![rule])), &names, false);
+
+        assert!(out.contains("cidr:         10.0.0.0/24"), "{out}");
+        assert!(out.contains("10.0.0.3         db"), "{out}");
+        assert!(out.contains("reservations: none"), "{out}");
+        assert!(out.contains("db:5432"), "{out}");
+        assert!(out.contains("allow"), "{out}");
+    }
+
+    #[test]
+    fn says_when_policies_are_unsupported_or_absent() {
+        let names = HashMap::new();
+        let out = render(&detail(None), &names, false);
+        assert!(out.contains("not supported by this server"), "{out}");
+        let out = render(&detail(Some(vec![])), &names, false);
+        assert!(out.contains("none (all traffic allowed)"), "{out}");
+        assert!(!out.contains("peerings"), "{out}");
+    }
+
+    #[test]
+    fn lists_peered_networks() {
+        let mut detail = detail(Some(vec![]));
+        detail.peerings = Some(vec![NetworkPeering {
+            id: Uuid::new_v4(),
+            peer_network_id: Uuid::new_v4(),
+            peer_network_name: "frontend".into(),
+            created_at: NaiveDateTime::default(),
+        }]);
+        let out = render(&detail, &HashMap::new(), false);
+        assert!(out.contains("  peerings:\n    frontend\n"), "{out}");
+    }
+}
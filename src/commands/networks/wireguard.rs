@@ -0,0 +1,877 @@
+//! `unisrv network connect|disconnect|status` — reach a network's instances
+//! at their internal addresses from this machine, over WireGuard.
+//!
+//! `connect` registers this machine as a peer of the network, writes a
+//! wg-quick config to `~/.unisrv/wireguard/<interface>.conf` and brings the
+//! tunnel up with `wg-quick` when it's installed (otherwise it says how to).
+//! Windows has no wg-quick; there the WireGuard app installs the same config
+//! as a tunnel service.
+//! The key pair is generated locally with `wg genkey` when `wg` is installed,
+//! so the private key never leaves the machine; without it the server
+//! generates the pair and returns the private half once.
+//!
+//! Next to each config a small JSON record names the peer, so `disconnect`
+//! can take it off the network and `status` can report on it.
+
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::NaiveDateTime;
+use comfy_table::{Attribute, Cell};
+use serde::{Deserialize, Serialize};
+use unisrv_api::ApiClient;
+use unisrv_api::models::{CreateWireGuardPeerRequest, WireGuardPeerConfig};
+use uuid::Uuid;
+
+use super::address;
+use crate::commands::confirm::Guard;
+use crate::commands::output::Output;
+use crate::commands::ui::{format_relative, new_table};
+use crate::commands::up::plan::ResolvedEnvironment;
+
+/// Where configs and records live, under the config directory.
+pub const WIREGUARD_DIR: &str = "wireguard";
+
+/// Linux caps interface names (and so wg-quick config names) at 15 bytes.
+const MAX_INTERFACE_LEN: usize = 15;
+
+/// Keeps the tunnel open through NAT when nothing is being sent.
+const KEEPALIVE_SECS: u32 = 25;
+
+/// The local WireGuard tools, behind a trait so tests needn't have them.
+pub trait Tunnel {
+    /// A fresh `(private, public)` key pair, or `None` without `wg`.
+    fn generate_keypair(&self) -> Result<Option<(String, String)>>;
+    /// Bring up the tunnel `config` describes. `Ok(false)` without
+    /// `wg-quick`.
+    fn up(&self, config: &Path) -> Result<bool>;
+    /// Take the tunnel down. `Ok(false)` without `wg-quick`.
+    fn down(&self, config: &Path) -> Result<bool>;
+    /// Whether `interface` is currently up.
+    fn is_up(&self, interface: &str) -> bool;
+}
+
+/// The real tools, found on `$PATH`.
+pub struct WgQuick;
+
+impl Tunnel for WgQuick {
+    fn generate_keypair(&self) -> Result<Option<(String, String)>> {
+        let Some(wg) = find_program("wg") else {
+            return Ok(None);
+        };
+        let private = Command::new(&wg)
+            .arg("genkey")
+            .output()
+            .context("failed to run `wg genkey`")?;
+        if !private.status.success() {
+            bail!("`wg genkey` exited with {}", private.status);
+        }
+        let private = String::from_utf8_lossy(&private.stdout).trim().to_string();
+
+        let mut child = Command::new(&wg)
+            .arg("pubkey")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .context("failed to run `wg pubkey`")?;
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(private.as_bytes())?;
+        let public = child.wait_with_output()?;
+        if !public.status.success() {
+            bail!("`wg pubkey` exited with {}", public.status);
+        }
+        let public = String::from_utf8_lossy(&public.stdout).trim().to_string();
+        Ok(Some((private, public)))
+    }
+
+    fn up(&self, config: &Path) -> Result<bool> {
+        run_tunnel_command(TunnelCommand::new(true, config, cfg!(windows)))
+    }
+
+    fn down(&self, config: &Path) -> Result<bool> {
+        run_tunnel_command(TunnelCommand::new(false, config, cfg!(windows)))
+    }
+
+    fn is_up(&self, interface: &str) -> bool {
+        find_program("wg").is_some_and(|wg| {
+            Command::new(wg)
+                .args(["show", interface])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|s| s.success())
+        })
+    }
+}
+
+/// What brings a tunnel up or down: `wg-quick up|down <config>`, or on
+/// Windows `wireguard /installtunnelservice <config>` and
+/// `/uninstalltunnelservice <name>`, the service being named after the
+/// config file as wg-quick's interface is.
+#[derive(Debug, PartialEq)]
+struct TunnelCommand {
+    program: &'static str,
+    args: Vec<String>,
+    /// Whether it needs an elevated prompt (Windows) rather than sudo.
+    windows: bool,
+}
+
+impl TunnelCommand {
+    fn new(up: bool, config: &Path, windows: bool) -> Self {
+        let config_arg = config.display().to_string();
+        let args = match (windows, up) {
+            (false, true) => vec!["up".to_string(), config_arg],
+            (false, false) => vec!["down".to_string(), config_arg],
+            (true, true) => vec!["/installtunnelservice".to_string(), config_arg],
+            (true, false) => vec![
+                "/uninstalltunnelservice".to_string(),
+                config
+                    .file_stem()
+                    .map_or(config_arg, |stem| stem.to_string_lossy().into_owned()),
+            ],
+        };
+        TunnelCommand {
+            program: if windows { "wireguard" } else { "wg-quick" },
+            args,
+            windows,
+        }
+    }
+
+    /// The command as the user would type it to run it themselves.
+    fn manual(&self) -> String {
+        let command = format!("{} {}", self.program, self.args.join(" "));
+        if self.windows {
+            format!("`{command}` from an administrator prompt")
+        } else {
+            format!("`sudo {command}`")
+        }
+    }
+}
+
+fn run_tunnel_command(command: TunnelCommand) -> Result<bool> {
+    let Some(program) = find_program(command.program) else {
+        return Ok(false);
+    };
+    let shown = format!("{} {}", command.program, command.args[0]);
+    let status = Command::new(program)
+        .args(&command.args)
+        .status()
+        .with_context(|| format!("failed to run `{shown}`"))?;
+    if !status.success() {
+        bail!("`{shown}` exited with {status}");
+    }
+    Ok(true)
+}
+
+fn find_program(name: &str) -> Option<PathBuf> {
+    find_program_in(
+        &std::env::var_os("PATH")?,
+        name,
+        std::env::consts::EXE_SUFFIX,
+    )
+}
+
+/// `name` in one of the `path` directories, with the platform's executable
+/// suffix (`wg.exe` on Windows).
+fn find_program_in(path: &std::ffi::OsStr, name: &str, suffix: &str) -> Option<PathBuf> {
+    std::env::split_paths(path)
+        .map(|dir| dir.join(format!("{name}{suffix}")))
+        .find(|path| path.is_file())
+}
+
+/// `~/.unisrv/wireguard`, or `None` without a home directory.
+pub fn default_dir() -> Option<PathBuf> {
+    Some(unisrv_api::config_dir()?.join(WIREGUARD_DIR))
+}
+
+/// What `connect` registered, kept next to the config.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Connection {
+    env_id: Uuid,
+    network_id: Uuid,
+    network: String,
+    peer_id: Uuid,
+    peer_name: String,
+    address: String,
+}
+
+/// A connection as `status` reports it, and its `--json` form.
+#[derive(Debug, Serialize)]
+struct ConnectionStatus {
+    network: String,
+    interface: String,
+    address: String,
+    up: bool,
+    /// `false` once the peer was removed on the server side.
+    registered: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_handshake_at: Option<NaiveDateTime>,
+    config: PathBuf,
+}
+
+pub async fn connect(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    network: &str,
+    name: Option<String>,
+    apply: bool,
+    tunnel: &dyn Tunnel,
+    dir: &Path,
+) -> Result<()> {
+    let found = address::find(client, env.id, network).await?;
+    let interface = interface_name(&found.name);
+    if let Some(existing) = load(dir, &interface) {
+        bail!(
+            "already connected to {} as {} ({interface}); run `unisrv network disconnect {}` first",
+            existing.network,
+            existing.address,
+            existing.network
+        );
+    }
+
+    let keys = tunnel.generate_keypair()?;
+    let peer_name = name.unwrap_or_else(default_peer_name);
+    let config = match client
+        .create_wireguard_peer(
+            env.id,
+            found.id,
+            CreateWireGuardPeerRequest {
+                name: peer_name.clone(),
+                public_key: keys.as_ref().map(|(_, public)| public.clone()),
+            },
+        )
+        .await
+    {
+        Ok(config) => config,
+        Err(e) if e.is_unsupported_route() => {
+            bail!("this server doesn't support WireGuard access to networks")
+        }
+        Err(e) => return Err(e).with_context(|| format!("failed to connect to {}", found.name)),
+    };
+    let private_key = match keys
+        .map(|(private, _)| private)
+        .or(config.private_key.clone())
+    {
+        Some(key) => key,
+        None => {
+            // Don't leave a peer behind that nothing can use.
+            let _ = client
+                .delete_wireguard_peer(env.id, found.id, config.peer.id)
+                .await;
+            bail!(
+                "the server expects this machine's own key; install WireGuard's `wg` tool and retry"
+            );
+        }
+    };
+
+    let connection = Connection {
+        env_id: env.id,
+        network_id: found.id,
+        network: found.name.clone(),
+        peer_id: config.peer.id,
+        peer_name,
+        address: config.peer.address.clone(),
+    };
+    let path = save(
+        dir,
+        &interface,
+        &connection,
+        &render_config(&config, &private_key),
+    )?;
+    println!(
+        "\u{2713} Registered {} on {} as {}.",
+        connection.peer_name, connection.network, connection.address
+    );
+
+    if !apply {
+        println!("Config written to {}.", path.display());
+        return Ok(());
+    }
+    match tunnel.up(&path) {
+        Ok(true) => println!(
+            "\u{2713} Tunnel {interface} is up: instances on {} are reachable at their internal addresses.",
+            connection.network
+        ),
+        Ok(false) => eprintln!(
+            "{}",
+            console::style(if cfg!(windows) {
+                format!(
+                    "The WireGuard app isn't installed. Install it, then import {} into it.",
+                    path.display()
+                )
+            } else {
+                format!(
+                    "wg-quick isn't installed. Bring the tunnel up with `wg-quick up {}`, or import it into the WireGuard app.",
+                    path.display()
+                )
+            })
+            .dim()
+        ),
+        Err(e) => eprintln!(
+            "{}",
+            console::style(format!(
+                "! Couldn't bring the tunnel up ({e:#}); try {}.",
+                TunnelCommand::new(true, &path, cfg!(windows)).manual()
+            ))
+            .yellow()
+        ),
+    }
+    Ok(())
+}
+
+pub async fn disconnect(
+    client: &dyn ApiClient,
+    network: &str,
+    tunnel: &dyn Tunnel,
+    dir: &Path,
+    guard: &Guard<'_>,
+) -> Result<()> {
+    let interface = interface_name(network);
+    let connection = load(dir, &interface).ok_or_else(|| anyhow!("not connected to {network}"))?;
+    let config = dir.join(format!("{interface}.conf"));
+    if !guard.confirm(
+        &format!("remove this machine from {}", connection.network),
+        &connection.network,
+    )? {
+        println!("Aborted.");
+        return Ok(());
+    }
+
+    if tunnel.is_up(&interface)
+        && let Err(e) = tunnel.down(&config)
+    {
+        eprintln!(
+            "{}",
+            console::style(format!(
+                "! Couldn't take the tunnel down ({e:#}); try {}.",
+                TunnelCommand::new(false, &config, cfg!(windows)).manual()
+            ))
+            .yellow()
+        );
+    }
+    match client
+        .delete_wireguard_peer(connection.env_id, connection.network_id, connection.peer_id)
+        .await
+    {
+        Ok(()) => {}
+        // Already removed on the server side; just forget it here.
+        Err(unisrv_api::ApiError::Server { status: 404, .. }) => {}
+        Err(e) => {
+            return Err(e).with_context(|| format!("failed to remove this machine from {network}"));
+        }
+    }
+    for file in [config, dir.join(format!("{interface}.json"))] {
+        match std::fs::remove_file(&file) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed to remove {}", file.display()));
+            }
+        }
+    }
+    println!("\u{2713} Disconnected from {}.", connection.network);
+    Ok(())
+}
+
+pub async fn status(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    output: &Output,
+    tunnel: &dyn Tunnel,
+    dir: &Path,
+) -> Result<()> {
+    let mut statuses = Vec::new();
+    for (interface, connection) in load_all(dir) {
+        if connection.env_id != env.id {
+            continue;
+        }
+        let peer = match client
+            .list_wireguard_peers(env.id, connection.network_id)
+            .await
+        {
+            Ok(list) => list.peers.into_iter().find(|p| p.id == connection.peer_id),
+            Err(e) if e.is_unsupported_endpoint() => None,
+            Err(e) => return Err(e.into()),
+        };
+        statuses.push(ConnectionStatus {
+            network: connection.network,
+            up: tunnel.is_up(&interface),
+            config: dir.join(format!("{interface}.conf")),
+            interface,
+            address: connection.address,
+            registered: peer.is_some(),
+            last_handshake_at: peer.and_then(|p| p.last_handshake_at),
+        });
+    }
+
+    match output {
+        Output::Json | Output::Jq(_) => return output.print_json(&statuses),
+        Output::Template(template) => {
+            print!("{}", template.render_all(&statuses)?);
+            return Ok(());
+        }
+        Output::Table => {}
+    }
+    if statuses.is_empty() {
+        println!(
+            "No WireGuard connections to networks in {}. Connect with `unisrv network connect <network>`.",
+            env.name
+        );
+        return Ok(());
+    }
+    println!(
+        "{}",
+        render_table(&statuses, chrono::Utc::now().naive_utc())
+    );
+    Ok(())
+}
+
+/// `us-<network>`, in the characters wg-quick accepts and within the
+/// interface name limit.
+fn interface_name(network: &str) -> String {
+    let sanitized: String = network
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "_=+.-".contains(c) {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let mut name = format!("us-{sanitized}");
+    name.truncate(MAX_INTERFACE_LEN);
+    name
+}
+
+/// This machine's hostname, so the peer list says whose laptop it is.
+fn default_peer_name() -> String {
+    std::fs::read_to_string("/etc/hostname")
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(|| "unisrv-cli".to_string())
+}
+
+/// The wg-quick config for a created peer.
+fn render_config(config: &WireGuardPeerConfig, private_key: &str) -> String {
+    let mut out = String::from("# Written by `unisrv network connect`.\n[Interface]\n");
+    out.push_str(&format!("PrivateKey = {private_key}\n"));
+    out.push_str(&format!("Address = {}\n", config.peer.address));
+    if let Some(dns) = &config.dns {
+        out.push_str(&format!("DNS = {dns}\n"));
+    }
+    out.push_str("\n[Peer]\n");
+    out.push_str(&format!("PublicKey = {}\n", config.server_public_key));
+    out.push_str(&format!("Endpoint = {}\n", config.endpoint));
+    out.push_str(&format!("AllowedIPs = {}\n", config.allowed_ips.join(", ")));
+    out.push_str(&format!("PersistentKeepalive = {KEEPALIVE_SECS}\n"));
+    out
+}
+
+/// Write the config (readable only by the owner: it holds the private key)
+/// and the record. Returns the config's path.
+fn save(dir: &Path, interface: &str, connection: &Connection, config: &str) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    let path = dir.join(format!("{interface}.conf"));
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+        .open(&path)
+        .and_then(|mut file| file.write_all(config.as_bytes()))
+        .with_context(|| format!("failed to write {}", path.display()))?;
+
+    let record = dir.join(format!("{interface}.json"));
+    std::fs::write(&record, serde_json::to_string_pretty(connection)?)
+        .with_context(|| format!("failed to write {}", record.display()))?;
+    Ok(path)
+}
+
+fn load(dir: &Path, interface: &str) -> Option<Connection> {
+    let json = std::fs::read_to_string(dir.join(format!("{interface}.json"))).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+/// Every saved connection, by interface name. Unreadable records are skipped.
+fn load_all(dir: &Path) -> Vec<(String, Connection)> {
+    let mut all: Vec<(String, Connection)> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "json" {
+                return None;
+            }
+            let interface = path.file_stem()?.to_str()?.to_string();
+            let connection = load(dir, &interface)?;
+            Some((interface, connection))
+        })
+        .collect();
+    all.sort_by(|a, b| a.0.cmp(&b.0));
+    all
+}
+
+/// Pure so it can be asserted on without a terminal.
+fn render_table(statuses: &[ConnectionStatus], now: NaiveDateTime) -> String {
+    let mut table = new_table();
+    table.set_header(vec![
+        Cell::new("NETWORK").add_attribute(Attribute::Bold),
+        Cell::new("INTERFACE").add_attribute(Attribute::Bold),
+        Cell::new("ADDRESS").add_attribute(Attribute::Bold),
+        Cell::new("TUNNEL").add_attribute(Attribute::Bold),
+        Cell::new("LAST HANDSHAKE").add_attribute(Attribute::Bold),
+    ]);
+    for status in statuses {
+        let handshake = match (status.registered, status.last_handshake_at) {
+            (false, _) => "peer removed; disconnect to clean up".to_string(),
+            (true, Some(at)) => format_relative(at, now),
+            (true, None) => "never".to_string(),
+        };
+        table.add_row(vec![
+            Cell::new(&status.network),
+            Cell::new(&status.interface),
+            Cell::new(&status.address),
+            Cell::new(if status.up { "up" } else { "down" }),
+            Cell::new(handshake),
+        ]);
+    }
+    table.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::up::plan::test_env;
+    use std::cell::RefCell;
+    use unisrv_api::ApiError;
+    use unisrv_api::models::{
+        NetworkListItem, NetworkListResponse, WireGuardPeer, WireGuardPeerListResponse,
+    };
+    use unisrv_api::test_support::MockApiClient;
+
+    #[derive(Default)]
+    struct FakeTunnel {
+        keys: Option<(String, String)>,
+        installed: bool,
+        up: bool,
+        calls: RefCell<Vec<String>>,
+    }
+
+    impl Tunnel for FakeTunnel {
+        fn generate_keypair(&self) -> Result<Option<(String, String)>> {
+            Ok(self.keys.clone())
+        }
+        fn up(&self, config: &Path) -> Result<bool> {
+            self.calls
+                .borrow_mut()
+                .push(format!("up {}", config.display()));
+            Ok(self.installed)
+        }
+        fn down(&self, config: &Path) -> Result<bool> {
+            self.calls
+                .borrow_mut()
+                .push(format!("down {}", config.display()));
+            Ok(self.installed)
+        }
+        fn is_up(&self, _interface: &str) -> bool {
+            self.up
+        }
+    }
+
+    fn network() -> NetworkListItem {
+        NetworkListItem {
+            id: Uuid::new_v4(),
+            name: "backend".into(),
+            ipv4_cidr: "10.0.0.0/16".into(),
+            instance_count: None,
+            managed_by: None,
+        }
+    }
+
+    fn peer_config(private_key: Option<&str>) -> WireGuardPeerConfig {
+        WireGuardPeerConfig {
+            peer: WireGuardPeer {
+                id: Uuid::new_v4(),
+                name: "laptop".into(),
+                address: "10.0.255.2/32".into(),
+                public_key: "client-pub".into(),
+                created_at: NaiveDateTime::default(),
+                last_handshake_at: None,
+            },
+            endpoint: "wg.unisrv.dev:51820".into(),
+            server_public_key: "server-pub".into(),
+            allowed_ips: vec!["10.0.0.0/16".into(), "10.1.0.0/24".into()],
+            dns: Some("10.0.0.2".into()),
+            private_key: private_key.map(Into::into),
+        }
+    }
+
+    fn backend(net: &NetworkListItem) -> MockApiClient {
+        MockApiClient::logged_in().with_list_networks(Ok(NetworkListResponse {
+            networks: vec![net.clone()],
+        }))
+    }
+
+    #[test]
+    fn interface_names_fit_wg_quick() {
+        assert_eq!(interface_name("backend"), "us-backend");
+        assert_eq!(interface_name("my net/α"), "us-my-net--");
+        assert_eq!(interface_name("a-very-long-network-name").len(), 15);
+    }
+
+    #[test]
+    fn tunnels_use_the_wireguard_service_on_windows() {
+        let config = Path::new("/home/me/.unisrv/wireguard/us-backend.conf");
+        let up = TunnelCommand::new(true, config, false);
+        assert_eq!(up.program, "wg-quick");
+        assert_eq!(
+            up.manual(),
+            "`sudo wg-quick up /home/me/.unisrv/wireguard/us-backend.conf`"
+        );
+
+        let config = Path::new("us-backend.conf");
+        let up = TunnelCommand::new(true, config, true);
+        assert_eq!(
+            (up.program, up.args.as_slice()),
+            (
+                "wireguard",
+                ["/installtunnelservice", "us-backend.conf"]
+                    .map(String::from)
+                    .as_slice()
+            )
+        );
+        let down = TunnelCommand::new(false, config, true);
+        assert_eq!(down.args, ["/uninstalltunnelservice", "us-backend"]);
+        assert_eq!(
+            down.manual(),
+            "`wireguard /uninstalltunnelservice us-backend` from an administrator prompt"
+        );
+    }
+
+    #[test]
+    fn programs_are_found_with_the_executable_suffix() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("wg.exe"), "").unwrap();
+        let path = std::env::join_paths([Path::new("/nonexistent"), tmp.path()]).unwrap();
+        assert_eq!(
+            find_program_in(&path, "wg", ".exe"),
+            Some(tmp.path().join("wg.exe"))
+        );
+        assert_eq!(find_program_in(&path, "wg", ""), None);
+    }
+
+    #[test]
+    fn config_routes_the_network_through_the_tunnel() {
+        let text = render_config(&peer_config(None), "client-priv");
+        assert!(
+            text.contains("PrivateKey = client-priv\nAddress = 10.0.255.2/32\nDNS = 10.0.0.2\n")
+        );
+        assert!(text.contains("PublicKey = server-pub\nEndpoint = wg.unisrv.dev:51820\n"));
+        assert!(text.contains("AllowedIPs = 10.0.0.0/16, 10.1.0.0/24\n"));
+    }
+
+    #[tokio::test]
+    async fn connect_sends_the_local_public_key_and_brings_the_tunnel_up() {
+        let tmp = tempfile::tempdir().unwrap();
+        let net = network();
+        let mock = backend(&net).push_create_wireguard_peer(Ok(peer_config(None)));
+        let tunnel = FakeTunnel {
+            keys: Some(("client-priv".into(), "client-pub".into())),
+            installed: true,
+            ..FakeTunnel::default()
+        };
+
+        connect(
+            &mock,
+            &test_env(),
+            "backend",
+            Some("laptop".into()),
+            true,
+            &tunnel,
+            tmp.path(),
+        )
+        .await
+        .unwrap();
+
+        let req = mock.calls.lock().unwrap().create_wireguard_peer_calls[0]
+            .2
+            .clone();
+        assert_eq!(req.public_key.as_deref(), Some("client-pub"));
+        let conf = tmp.path().join("us-backend.conf");
+        let text = std::fs::read_to_string(&conf).unwrap();
+        assert!(text.contains("PrivateKey = client-priv"), "{text}");
+        assert_eq!(
+            tunnel.calls.borrow().as_slice(),
+            [format!("up {}", conf.display())]
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&conf).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // A second connect to the same network is refused.
+        let mock = backend(&net);
+        let err = connect(
+            &mock,
+            &test_env(),
+            "backend",
+            None,
+            false,
+            &tunnel,
+            tmp.path(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("already connected"), "{err:#}");
+    }
+
+    #[tokio::test]
+    async fn a_rejected_peer_shows_the_servers_reason() {
+        let tmp = tempfile::tempdir().unwrap();
+        let net = network();
+        let mock = backend(&net).push_create_wireguard_peer(Err(ApiError::Server {
+            status: 400,
+            reason: "peer limit reached".into(),
+        }));
+        let tunnel = FakeTunnel {
+            keys: Some(("client-priv".into(), "client-pub".into())),
+            installed: true,
+            ..FakeTunnel::default()
+        };
+
+        let err = connect(
+            &mock,
+            &test_env(),
+            "backend",
+            None,
+            false,
+            &tunnel,
+            tmp.path(),
+        )
+        .await
+        .unwrap_err();
+        let message = format!("{err:#}");
+        assert!(
+            message.contains("failed to connect to backend"),
+            "{message}"
+        );
+        assert!(message.contains("peer limit reached"), "{message}");
+        assert!(!message.contains("doesn't support"), "{message}");
+        assert!(tunnel.calls.borrow().is_empty());
+    }
+
+    #[tokio::test]
+    async fn without_wg_the_server_generates_the_key() {
+        let tmp = tempfile::tempdir().unwrap();
+        let net = network();
+        let mock = backend(&net).push_create_wireguard_peer(Ok(peer_config(Some("server-priv"))));
+
+        connect(
+            &mock,
+            &test_env(),
+            "backend",
+            None,
+            false,
+            &FakeTunnel::default(),
+            tmp.path(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            mock.calls.lock().unwrap().create_wireguard_peer_calls[0]
+                .2
+                .public_key,
+            None
+        );
+        let text = std::fs::read_to_string(tmp.path().join("us-backend.conf")).unwrap();
+        assert!(text.contains("PrivateKey = server-priv"), "{text}");
+    }
+
+    #[tokio::test]
+    async fn disconnect_removes_the_peer_and_the_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let env = test_env();
+        let net = network();
+        let created = peer_config(None);
+        let mock = backend(&net).push_create_wireguard_peer(Ok(created.clone()));
+        let tunnel = FakeTunnel {
+            keys: Some(("k".into(), "p".into())),
+            installed: true,
+            up: true,
+            ..FakeTunnel::default()
+        };
+        connect(&mock, &env, "backend", None, false, &tunnel, tmp.path())
+            .await
+            .unwrap();
+
+        let mock = MockApiClient::logged_in().push_delete_wireguard_peer(Err(ApiError::Server {
+            status: 404,
+            reason: "gone".into(),
+        }));
+        disconnect(&mock, "backend", &tunnel, tmp.path(), &Guard::unprotected())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            mock.calls.lock().unwrap().delete_wireguard_peer_calls[0],
+            (env.id, net.id, created.peer.id)
+        );
+        assert!(tunnel.calls.borrow()[0].starts_with("down "));
+        assert!(load_all(tmp.path()).is_empty());
+        assert!(!tmp.path().join("us-backend.conf").exists());
+    }
+
+    #[tokio::test]
+    async fn status_reports_tunnels_and_handshakes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let env = test_env();
+        let net = network();
+        let created = peer_config(None);
+        let mock = backend(&net).push_create_wireguard_peer(Ok(created.clone()));
+        let tunnel = FakeTunnel {
+            keys: Some(("k".into(), "p".into())),
+            ..FakeTunnel::default()
+        };
+        connect(&mock, &env, "backend", None, false, &tunnel, tmp.path())
+            .await
+            .unwrap();
+
+        let mut seen = created.peer.clone();
+        seen.last_handshake_at = Some(NaiveDateTime::default());
+        let mock = MockApiClient::logged_in()
+            .push_list_wireguard_peers(Ok(WireGuardPeerListResponse { peers: vec![seen] }));
+        status(&mock, &env, &Output::Json, &tunnel, tmp.path())
+            .await
+            .unwrap();
+        assert_eq!(
+            mock.calls.lock().unwrap().list_wireguard_peers_calls.len(),
+            1
+        );
+
+        let out = render_table(
+            &[ConnectionStatus {
+                network: "backend".into(),
+                interface: "us-backend".into(),
+                address: "10.0.255.2/32".into(),
+                up: false,
+                registered: false,
+                last_handshake_at: None,
+                config: PathBuf::from("us-backend.conf"),
+            }],
+            NaiveDateTime::default(),
+        );
+        assert!(out.contains("peer removed"), "{out}");
+    }
+}
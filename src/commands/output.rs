@@ -0,0 +1,442 @@
+//! How list and show commands print their results: a human table, JSON
+//! (optionally passed through a `--jq` expression), or one line per item from
+//! a `--format` template.
+//!
+//! Templates use Go-template field syntax, as docker/kubectl users expect:
+//! `{{.id}} {{.name}}`, with dotted paths into nested objects
+//! (`{{.deployment.name}}`), numeric segments into arrays, and `{{.}}` for the
+//! whole item. A template that's just a path, like `.deployment.name`, is
+//! read as that one field, the way a jq or JSONPath user would write it. The
+//! item is matched against its JSON form, so field names are exactly those of
+//! `--json`. `\t` and `\n` in the template are unescaped so a single-quoted
+//! shell argument can still produce tab-separated columns.
+//!
+//! `--jq` runs the `--json` document through an embedded jq (jaq) filter, so
+//! scripts don't need jq installed. Like `gh --jq`, string results print raw
+//! and anything else as JSON, one result per line.
+
+use std::fmt;
+
+use anyhow::{Result, anyhow, bail};
+use jaq_core::load::{Arena, File, Loader};
+use jaq_core::{Compiler, Ctx, Native, RcIter, load};
+use jaq_json::Val;
+use serde::Serialize;
+use serde_json::Value;
+
+/// How a list command prints its result.
+#[derive(Debug)]
+pub enum Output {
+    Table,
+    Json,
+    Jq(Jq),
+    Template(Template),
+}
+
+impl Output {
+    /// Build from the `--json` / `--format` / `--jq` flags. clap keeps
+    /// `--format` exclusive with the other two; `--jq` implies `--json`.
+    pub fn from_flags(json: bool, format: Option<&str>, jq: Option<&str>) -> Result<Self> {
+        Ok(match (json, format, jq) {
+            (_, Some(template), _) => Output::Template(Template::parse(template)?),
+            (_, None, Some(expr)) => Output::Jq(Jq::compile(expr)?),
+            (true, None, None) => Output::Json,
+            (false, None, None) => Output::Table,
+        })
+    }
+
+    /// `-q/--quiet`: each item's full id on a line of its own, for piping
+    /// into another command. The same as `--format '{{.id}}'`.
+    pub fn ids() -> Self {
+        Output::Template(Template::parse("{{.id}}").expect("a valid template"))
+    }
+
+    /// Print `payload` as the command's JSON document, through the `--jq`
+    /// filter if one was given. Only meaningful for [`Output::Json`] and
+    /// [`Output::Jq`].
+    pub fn print_json<T: Serialize>(&self, payload: &T) -> Result<()> {
+        match self {
+            Output::Jq(jq) => print!("{}", jq.apply(payload)?),
+            _ => println!("{}", serde_json::to_string_pretty(payload)?),
+        }
+        Ok(())
+    }
+
+    /// Whether stdout carries machine-readable output that banners and hints
+    /// must stay out of.
+    pub fn is_machine(&self) -> bool {
+        !matches!(self, Output::Table)
+    }
+}
+
+/// A compiled `--jq` expression. Compiled up front so a typo fails before any
+/// API call is made.
+pub struct Jq {
+    source: String,
+    filter: jaq_core::Filter<Native<Val>>,
+}
+
+impl fmt::Debug for Jq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Jq").field(&self.source).finish()
+    }
+}
+
+impl Jq {
+    pub fn compile(source: &str) -> Result<Self> {
+        let invalid = |why: String| anyhow!("invalid --jq expression {source:?}: {why}");
+        let loader = Loader::new(jaq_std::defs().chain(jaq_json::defs()));
+        let arena = Arena::default();
+        let modules = loader
+            .load(
+                &arena,
+                File {
+                    code: source,
+                    path: (),
+                },
+            )
+            .map_err(|errs| invalid(describe_load_errors(errs)))?;
+        let filter = Compiler::default()
+            .with_funs(jaq_std::funs().chain(jaq_json::funs()))
+            .compile(modules)
+            .map_err(|errs| {
+                let undefined = errs
+                    .into_iter()
+                    .flat_map(|(_, errs)| errs)
+                    .map(|(name, kind)| format!("undefined {} `{name}`", kind.as_str()))
+                    .collect::<Vec<_>>();
+                invalid(undefined.join(", "))
+            })?;
+        Ok(Self {
+            source: source.to_string(),
+            filter,
+        })
+    }
+
+    /// Run the filter over `payload`'s JSON form, one output line per result.
+    pub fn apply<T: Serialize>(&self, payload: &T) -> Result<String> {
+        let input = Val::from(serde_json::to_value(payload)?);
+        let inputs = RcIter::new(core::iter::empty());
+        let mut out = String::new();
+        for result in self.filter.run((Ctx::new([], &inputs), input)) {
+            let value = result.map_err(|e| anyhow!("--jq {:?} failed: {e}", self.source))?;
+            match value {
+                Val::Str(s) => out.push_str(&s),
+                other => {
+                    let json = Value::from(other);
+                    out.push_str(&serde_json::to_string_pretty(&json)?);
+                }
+            }
+            out.push('\n');
+        }
+        Ok(out)
+    }
+}
+
+/// Lex and parse errors name what was expected and where parsing stopped.
+fn describe_load_errors(errs: load::Errors<&str, ()>) -> String {
+    let mut reasons = Vec::new();
+    for (_, err) in errs {
+        match err {
+            load::Error::Io(_) => reasons.push("modules cannot be imported".to_string()),
+            load::Error::Lex(errs) => reasons.extend(
+                errs.iter()
+                    .map(|(expect, at)| expected(expect.as_str(), at)),
+            ),
+            load::Error::Parse(errs) => reasons.extend(
+                errs.iter()
+                    .map(|(expect, at)| expected(expect.as_str(), at)),
+            ),
+        }
+    }
+    reasons.join(", ")
+}
+
+fn expected(what: &str, at: &str) -> String {
+    if at.is_empty() {
+        format!("expected {what} at end of input")
+    } else {
+        let near: String = at.chars().take(16).collect();
+        format!("expected {what} near `{near}`")
+    }
+}
+
+/// A parsed `--format` template.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    /// A field path; empty for `{{.}}`.
+    Field(Vec<String>),
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self> {
+        if let Some(path) = bare_path(source) {
+            return Self::parse(&format!("{{{{{path}}}}}"));
+        }
+        let mut segments = Vec::new();
+        let mut rest = source;
+        while let Some(open) = rest.find("{{") {
+            if open > 0 {
+                segments.push(Segment::Literal(unescape(&rest[..open])));
+            }
+            let after = &rest[open + 2..];
+            let close = after
+                .find("}}")
+                .ok_or_else(|| anyhow!("unterminated `{{{{` in --format template"))?;
+            let action = after[..close].trim();
+            let Some(path) = action.strip_prefix('.') else {
+                bail!(
+                    "unsupported --format action {{{{{action}}}}}: expected a field like {{{{.name}}}}"
+                );
+            };
+            let fields = if path.is_empty() {
+                Vec::new()
+            } else {
+                path.split('.').map(str::to_string).collect::<Vec<_>>()
+            };
+            if fields.iter().any(String::is_empty) {
+                bail!("invalid field path {{{{{action}}}}} in --format template");
+            }
+            segments.push(Segment::Field(fields));
+            rest = &after[close + 2..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(unescape(rest)));
+        }
+        Ok(Self { segments })
+    }
+
+    /// Render one item.
+    pub fn render<T: Serialize>(&self, item: &T) -> Result<String> {
+        let value = serde_json::to_value(item)?;
+        let mut out = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => out.push_str(text),
+                Segment::Field(path) => out.push_str(&display(lookup(&value, path)?)),
+            }
+        }
+        Ok(out)
+    }
+
+    /// Render every item, one per line.
+    pub fn render_all<T: Serialize>(&self, items: &[T]) -> Result<String> {
+        let mut out = String::new();
+        for item in items {
+            out.push_str(&self.render(item)?);
+            out.push('\n');
+        }
+        Ok(out)
+    }
+}
+
+/// `source` when it's a lone field path like `.certificate.not_after`,
+/// without the braces: nothing but dotted name segments.
+fn bare_path(source: &str) -> Option<&str> {
+    let path = source.trim();
+    let fields = path.strip_prefix('.')?;
+    let plain = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.');
+    (!fields.is_empty() && fields.chars().all(plain)).then_some(path)
+}
+
+/// Follow `path` into `value`. A field that's present but null renders empty;
+/// one that doesn't exist at all is a typo worth reporting, with the options.
+fn lookup<'a>(value: &'a Value, path: &[String]) -> Result<&'a Value> {
+    let mut current = value;
+    for (depth, key) in path.iter().enumerate() {
+        let next = match current {
+            Value::Object(map) => map.get(key),
+            Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+            Value::Null => return Ok(&Value::Null),
+            _ => None,
+        };
+        current = next.ok_or_else(|| {
+            let at = format!(".{}", path[..=depth].join("."));
+            match current {
+                Value::Object(map) => {
+                    let known = map.keys().map(String::as_str).collect::<Vec<_>>();
+                    anyhow!("no field {at} (available: {})", known.join(", "))
+                }
+                _ => anyhow!("no field {at}"),
+            }
+        })?;
+    }
+    Ok(current)
+}
+
+/// Strings print bare, null prints empty, anything else as compact JSON.
+fn display(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("\\t", "\t").replace("\\n", "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(template: &str, item: Value) -> Result<String> {
+        Template::parse(template)?.render(&item)
+    }
+
+    #[test]
+    fn substitutes_top_level_and_nested_fields() {
+        let item = json!({"id": "abc", "state": "running", "deployment": {"name": "web"}});
+        assert_eq!(
+            render("{{.id}} {{ .deployment.name }}: {{.state}}", item).unwrap(),
+            "abc web: running"
+        );
+    }
+
+    #[test]
+    fn quiet_prints_one_id_per_line() {
+        let Output::Template(template) = Output::ids() else {
+            panic!("--quiet is a template");
+        };
+        let items = [json!({"id": "a1", "name": "web"}), json!({"id": "b2"})];
+        assert_eq!(template.render_all(&items).unwrap(), "a1\nb2\n");
+    }
+
+    #[test]
+    fn non_strings_render_as_json_and_null_as_empty() {
+        let item = json!({"n": 3, "ok": true, "tags": ["a", "b"], "gone": null});
+        assert_eq!(
+            render(
+                "{{.n}}|{{.ok}}|{{.tags}}|{{.tags.1}}|{{.gone}}|{{.gone.name}}",
+                item
+            )
+            .unwrap(),
+            "3|true|[\"a\",\"b\"]|b||"
+        );
+    }
+
+    #[test]
+    fn unescapes_tabs_and_newlines() {
+        assert_eq!(
+            render("{{.a}}\\t{{.b}}", json!({"a": 1, "b": 2})).unwrap(),
+            "1\t2"
+        );
+    }
+
+    #[test]
+    fn whole_item_renders_with_a_bare_dot() {
+        assert_eq!(render("{{.}}", json!({"a": 1})).unwrap(), "{\"a\":1}");
+    }
+
+    #[test]
+    fn unknown_field_lists_the_available_ones() {
+        let err = render("{{.nmae}}", json!({"id": 1, "name": "x"})).unwrap_err();
+        let msg = format!("{err:#}");
+        assert!(
+            msg.contains(".nmae") && msg.contains("available: id, name"),
+            "{msg}"
+        );
+    }
+
+    #[test]
+    fn a_bare_path_is_read_as_one_field() {
+        let item = json!({"id": "abc", "certificate": {"not_after": "2026-12-01"}});
+        assert_eq!(
+            render(".certificate.not_after", item.clone()).unwrap(),
+            "2026-12-01"
+        );
+        assert_eq!(render(" .id ", item.clone()).unwrap(), "abc");
+        // Anything else is still a template with literal text.
+        assert_eq!(render(".id: {{.id}}", item).unwrap(), ".id: abc");
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        for bad in ["{{.id", "{{id}}", "{{.a..b}}", "{{ range .x }}"] {
+            assert!(Template::parse(bad).is_err(), "{bad:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn render_all_emits_one_line_per_item() {
+        let template = Template::parse("{{.name}}").unwrap();
+        let items = vec![json!({"name": "a"}), json!({"name": "b"})];
+        assert_eq!(template.render_all(&items).unwrap(), "a\nb\n");
+    }
+
+    #[test]
+    fn format_takes_precedence_and_json_alone_is_json() {
+        assert!(matches!(
+            Output::from_flags(false, None, None).unwrap(),
+            Output::Table
+        ));
+        assert!(matches!(
+            Output::from_flags(true, None, None).unwrap(),
+            Output::Json
+        ));
+        assert!(matches!(
+            Output::from_flags(false, Some("{{.id}}"), None).unwrap(),
+            Output::Template(_)
+        ));
+        assert!(
+            Output::from_flags(false, Some("{{.id}}"), None)
+                .unwrap()
+                .is_machine()
+        );
+    }
+
+    #[test]
+    fn jq_implies_json_and_is_compiled_up_front() {
+        assert!(matches!(
+            Output::from_flags(false, None, Some(".[].id")).unwrap(),
+            Output::Jq(_)
+        ));
+        let err = Output::from_flags(true, None, Some(".[] |")).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("invalid --jq expression"),
+            "{err:#}"
+        );
+        let err = Jq::compile("nosuchfn(1)").unwrap_err();
+        assert!(
+            format!("{err:#}").contains("undefined filter `nosuchfn`"),
+            "{err:#}"
+        );
+    }
+
+    #[test]
+    fn jq_prints_strings_raw_and_other_values_as_json() {
+        let hosts = json!([
+            {"host": "a.example.com", "service_id": null},
+            {"host": "b.example.com", "service_id": "s1"},
+        ]);
+        let jq = Jq::compile(".[].host").unwrap();
+        assert_eq!(jq.apply(&hosts).unwrap(), "a.example.com\nb.example.com\n");
+
+        let jq = Jq::compile("map(select(.service_id != null)) | length").unwrap();
+        assert_eq!(jq.apply(&hosts).unwrap(), "1\n");
+
+        let jq = Jq::compile(".[0] | {host}").unwrap();
+        assert_eq!(
+            jq.apply(&hosts).unwrap(),
+            "{\n  \"host\": \"a.example.com\"\n}\n"
+        );
+    }
+
+    #[test]
+    fn jq_runtime_errors_are_reported() {
+        let jq = Jq::compile(".[0].name").unwrap();
+        let err = jq.apply(&json!({"name": "x"})).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("--jq \".[0].name\" failed"),
+            "{err:#}"
+        );
+    }
+}
@@ -0,0 +1,167 @@
+//! Questions asked at the terminal.
+//!
+//! Every prompt goes through here, so a build without the `interactive`
+//! feature (`--no-default-features`, for CI images) can leave dialoguer out.
+//! In such a build a question with a default takes it, and any other fails
+//! with `hint`: the flag or variable that answers it instead.
+//!
+//! The global `--yes` (or `UNISRV_NONINTERACTIVE`) makes any build behave
+//! that way, except that a yes/no question is answered yes: nothing waits on
+//! a terminal that a CI job doesn't have.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::Result;
+
+/// Set to anything but empty, `0` or `false` for the global `--yes`.
+pub const NONINTERACTIVE_ENV: &str = "UNISRV_NONINTERACTIVE";
+
+static NON_INTERACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Called once from `main` with the resolved `--yes`.
+pub fn set_non_interactive(on: bool) {
+    NON_INTERACTIVE.store(on, Ordering::Relaxed);
+}
+
+/// Whether questions are answered without asking.
+pub fn non_interactive() -> bool {
+    NON_INTERACTIVE.load(Ordering::Relaxed)
+}
+
+/// A yes/no question, no unless answered yes. The answer isn't echoed once
+/// given; what follows shows it.
+pub fn confirm(prompt: &str, hint: &str) -> Result<bool> {
+    if non_interactive() {
+        return Ok(true);
+    }
+    imp::confirm(prompt, hint)
+}
+
+/// A line of text, `default` when nothing is typed (empty without one).
+pub fn text(prompt: &str, default: Option<&str>, hint: &str) -> Result<String> {
+    if non_interactive() {
+        return unattended(prompt, default, "--yes was given", hint);
+    }
+    imp::text(prompt, default, hint)
+}
+
+/// A line of text that can't be left empty.
+pub fn required_text(prompt: &str, hint: &str) -> Result<String> {
+    if non_interactive() {
+        return unattended(prompt, None, "--yes was given", hint);
+    }
+    imp::required_text(prompt, hint)
+}
+
+/// One of `items`, by index; the first is highlighted to start.
+pub fn select(prompt: &str, items: &[String], hint: &str) -> Result<usize> {
+    if non_interactive() {
+        return Err(unavailable(prompt, "--yes was given", hint));
+    }
+    imp::select(prompt, items, hint)
+}
+
+/// The answer to a question nobody is there to type: its default, echoed to
+/// stderr, or an error naming what answers it instead.
+fn unattended(prompt: &str, default: Option<&str>, why: &str, hint: &str) -> Result<String> {
+    match default {
+        Some(default) => {
+            eprintln!("{prompt}: {default}");
+            Ok(default.to_string())
+        }
+        None => Err(unavailable(prompt, why, hint)),
+    }
+}
+
+fn unavailable(prompt: &str, why: &str, hint: &str) -> anyhow::Error {
+    anyhow::anyhow!("can't ask {prompt:?}: {why}; {hint}")
+}
+
+#[cfg(feature = "interactive")]
+mod imp {
+    use anyhow::{Context, Result};
+    use dialoguer::{Confirm, Input, Select};
+
+    pub fn confirm(prompt: &str, _hint: &str) -> Result<bool> {
+        Confirm::new()
+            .with_prompt(prompt)
+            .default(false)
+            .report(false)
+            .interact()
+            .context("failed to read confirmation")
+    }
+
+    pub fn text(prompt: &str, default: Option<&str>, _hint: &str) -> Result<String> {
+        let mut input = Input::<String>::new().with_prompt(prompt).allow_empty(true);
+        if let Some(default) = default {
+            input = input.default(default.to_string());
+        }
+        Ok(input.interact_text()?)
+    }
+
+    pub fn required_text(prompt: &str, _hint: &str) -> Result<String> {
+        Ok(Input::new().with_prompt(prompt).interact_text()?)
+    }
+
+    pub fn select(prompt: &str, items: &[String], _hint: &str) -> Result<usize> {
+        Select::new()
+            .with_prompt(prompt)
+            .items(items)
+            .default(0)
+            .interact()
+            .context("failed to read selection")
+    }
+}
+
+#[cfg(not(feature = "interactive"))]
+mod imp {
+    use anyhow::Result;
+
+    const WHY: &str = "this build has no interactive prompts";
+
+    pub fn confirm(prompt: &str, hint: &str) -> Result<bool> {
+        Err(super::unavailable(prompt, WHY, hint))
+    }
+
+    pub fn text(prompt: &str, default: Option<&str>, hint: &str) -> Result<String> {
+        super::unattended(prompt, default, WHY, hint)
+    }
+
+    pub fn required_text(prompt: &str, hint: &str) -> Result<String> {
+        Err(super::unavailable(prompt, WHY, hint))
+    }
+
+    pub fn select(prompt: &str, _items: &[String], hint: &str) -> Result<usize> {
+        Err(super::unavailable(prompt, WHY, hint))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unattended_takes_the_default_or_names_what_answers_instead() {
+        assert_eq!(
+            unattended("Display name", Some("Demo"), "--yes was given", "").unwrap(),
+            "Demo"
+        );
+        let err = unattended("Username", None, "--yes was given", "pass --username").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "can't ask \"Username\": --yes was given; pass --username"
+        );
+    }
+
+    #[cfg(not(feature = "interactive"))]
+    #[test]
+    fn without_prompts_defaults_are_taken_and_the_rest_names_a_flag() {
+        assert_eq!(text("Display name", Some("Demo"), "").unwrap(), "Demo");
+        let err = confirm("Apply these changes?", "pass --yes").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "can't ask \"Apply these changes?\": this build has no interactive prompts; pass --yes"
+        );
+        assert!(required_text("Username", "pass --username").is_err());
+    }
+}
@@ -0,0 +1,100 @@
+//! `unisrv region` — where instances, workers and services can run, how far
+//! away each is ([`ping`]), and the region they're provisioned in when no
+//! `--region` says.
+//!
+//! That default is `region` in `~/.unisrv/config.json`, else
+//! [`DEFAULT_REGION`]. `main` sets it once with [`set_default`]; `instance
+//! run`, `worker run` and `service clone` take `--region` over it, while `up`
+//! provisions everything in it.
+
+pub mod ping;
+
+use std::sync::OnceLock;
+
+use anyhow::Result;
+use comfy_table::{Attribute, Cell, Color};
+use unisrv_api::ApiClient;
+use unisrv_api::models::Region;
+
+use crate::commands::output::Output;
+use crate::commands::ui::{cell_with_color, colors_enabled, new_table};
+use crate::commands::up::defaults::DEFAULT_REGION;
+
+static DEFAULT: OnceLock<String> = OnceLock::new();
+
+/// Provision in `region` when not told otherwise, for the rest of the
+/// process. Only the first call counts.
+pub fn set_default(region: String) {
+    let _ = DEFAULT.set(region);
+}
+
+/// The region chosen with [`set_default`], or [`DEFAULT_REGION`] before then.
+pub fn default_region() -> &'static str {
+    DEFAULT.get().map_or(DEFAULT_REGION, String::as_str)
+}
+
+/// `unisrv region list`, or bare `unisrv regions`.
+pub async fn list(client: &dyn ApiClient, output: &Output) -> Result<()> {
+    let regions = client.list_regions().await?.regions;
+    match output {
+        Output::Json | Output::Jq(_) => return output.print_json(&regions),
+        Output::Template(template) => {
+            print!("{}", template.render_all(&regions)?);
+            return Ok(());
+        }
+        Output::Table => {}
+    }
+    println!(
+        "{}",
+        render_table(&regions, default_region(), colors_enabled())
+    );
+    Ok(())
+}
+
+fn render_table(regions: &[Region], default: &str, use_color: bool) -> String {
+    let mut table = new_table();
+    table.set_header(vec![
+        Cell::new("NAME").add_attribute(Attribute::Bold),
+        Cell::new("LOCATION").add_attribute(Attribute::Bold),
+        Cell::new("STATUS").add_attribute(Attribute::Bold),
+    ]);
+    for region in regions {
+        let name = if region.name == default {
+            format!("{} (default)", region.name)
+        } else {
+            region.name.clone()
+        };
+        let status = if region.available {
+            cell_with_color("available".into(), Some(Color::Green), use_color)
+        } else {
+            cell_with_color("unavailable".into(), Some(Color::Yellow), use_color)
+        };
+        table.add_row(vec![
+            Cell::new(name),
+            Cell::new(region.location.as_deref().unwrap_or("")),
+            status,
+        ]);
+    }
+    table.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_table_marks_the_default_and_unavailable_regions() {
+        let regions: Vec<Region> = serde_json::from_value(serde_json::json!([
+            { "name": "dev" },
+            { "name": "eu-central", "location": "Frankfurt, DE" },
+            { "name": "us-east", "location": "Virginia, US", "available": false },
+        ]))
+        .unwrap();
+        let table = render_table(&regions, "eu-central", false);
+        assert!(table.contains("eu-central (default)"), "{table}");
+        assert!(table.contains("Frankfurt, DE"), "{table}");
+        let us_east = table.lines().find(|l| l.contains("us-east")).unwrap();
+        assert!(us_east.contains("unavailable"), "{us_east}");
+        assert!(!table.contains("dev (default)"), "{table}");
+    }
+}
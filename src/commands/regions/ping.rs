@@ -0,0 +1,286 @@
+//! `unisrv region ping` — the round trip from this machine to each region,
+//! for choosing where a latency-sensitive service should run.
+//!
+//! Each sample is one TCP handshake with the region's edge endpoint, timed
+//! after its name is resolved: one network round trip, without TLS or the
+//! server's own work in the figure. Regions are probed one after another so
+//! they don't share the link, and the median of `--count` samples is
+//! reported beside the fastest. A region the API lists without an endpoint
+//! can't be measured and is listed last, as is one that doesn't answer.
+
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, anyhow, bail};
+use async_trait::async_trait;
+use comfy_table::{Attribute, Cell, CellAlignment, Color};
+use serde::Serialize;
+use unisrv_api::ApiClient;
+use unisrv_api::models::Region;
+
+use crate::commands::output::Output;
+use crate::commands::ui::{cell_with_color, colors_enabled, new_table};
+
+pub const DEFAULT_SAMPLES: u32 = 5;
+const MAX_SAMPLES: u32 = 50;
+/// How long one handshake may take before the region counts as unreachable.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Round-trip timing, behind a trait so tests can answer without a network.
+#[async_trait]
+pub trait Prober: Sync {
+    /// Resolve `host` and time `samples` handshakes with it on `port`.
+    async fn round_trips(&self, host: &str, port: u16, samples: u32) -> Result<Vec<Duration>>;
+}
+
+/// TCP handshakes over the system's network.
+pub struct TcpProber;
+
+#[async_trait]
+impl Prober for TcpProber {
+    async fn round_trips(&self, host: &str, port: u16, samples: u32) -> Result<Vec<Duration>> {
+        let addr = tokio::net::lookup_host((host, port))
+            .await
+            .with_context(|| format!("can't resolve {host}"))?
+            .next()
+            .ok_or_else(|| anyhow!("{host} has no addresses"))?;
+        let mut times = Vec::with_capacity(samples as usize);
+        for _ in 0..samples {
+            let start = Instant::now();
+            tokio::time::timeout(PROBE_TIMEOUT, tokio::net::TcpStream::connect(addr))
+                .await
+                .map_err(|_| anyhow!("no answer within {}s", PROBE_TIMEOUT.as_secs()))?
+                .with_context(|| format!("can't connect to {addr}"))?;
+            times.push(start.elapsed());
+        }
+        Ok(times)
+    }
+}
+
+/// One region's result, and the `--json` item.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Latency {
+    pub region: String,
+    pub location: Option<String>,
+    pub endpoint: Option<String>,
+    pub median_ms: Option<f64>,
+    pub min_ms: Option<f64>,
+    /// Why it wasn't measured.
+    pub error: Option<String>,
+}
+
+/// `unisrv region ping`.
+pub async fn ping(
+    client: &dyn ApiClient,
+    samples: u32,
+    prober: &dyn Prober,
+    output: &Output,
+) -> Result<()> {
+    if !(1..=MAX_SAMPLES).contains(&samples) {
+        bail!("--count must be between 1 and {MAX_SAMPLES}, got {samples}");
+    }
+    let regions = client.list_regions().await?.regions;
+    if regions.is_empty() && !output.is_machine() {
+        println!("No regions to ping.");
+        return Ok(());
+    }
+    let mut results = Vec::with_capacity(regions.len());
+    for region in &regions {
+        results.push(measure(region, samples, prober).await);
+    }
+    sort(&mut results);
+
+    match output {
+        Output::Json | Output::Jq(_) => output.print_json(&results),
+        Output::Template(template) => {
+            print!("{}", template.render_all(&results)?);
+            Ok(())
+        }
+        Output::Table => {
+            println!("{}", render_table(&results, colors_enabled()));
+            Ok(())
+        }
+    }
+}
+
+async fn measure(region: &Region, samples: u32, prober: &dyn Prober) -> Latency {
+    let mut latency = Latency {
+        region: region.name.clone(),
+        location: region.location.clone(),
+        endpoint: region.endpoint.clone(),
+        median_ms: None,
+        min_ms: None,
+        error: None,
+    };
+    let Some(endpoint) = &region.endpoint else {
+        latency.error = Some("no endpoint to measure".into());
+        return latency;
+    };
+    let timed = match host_and_port(endpoint) {
+        Ok((host, port)) => prober.round_trips(&host, port, samples).await,
+        Err(e) => Err(e),
+    };
+    match timed {
+        Ok(mut times) if !times.is_empty() => {
+            times.sort();
+            latency.median_ms = Some(millis(times[times.len() / 2]));
+            latency.min_ms = Some(millis(times[0]));
+        }
+        Ok(_) => latency.error = Some("no samples".into()),
+        Err(e) => latency.error = Some(format!("{e:#}")),
+    }
+    latency
+}
+
+/// Fastest first; the unmeasured after, by name.
+fn sort(results: &mut [Latency]) {
+    results.sort_by(|a, b| match (a.median_ms, b.median_ms) {
+        (Some(x), Some(y)) => x.total_cmp(&y),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => a.region.cmp(&b.region),
+    });
+}
+
+/// The host and port an endpoint URL (or bare `host[:port]`) connects to.
+fn host_and_port(endpoint: &str) -> Result<(String, u16)> {
+    let (default_port, rest) = match endpoint.split_once("://") {
+        Some(("https", rest)) => (443, rest),
+        Some(("http", rest)) => (80, rest),
+        Some((scheme, _)) => bail!("can't measure a {scheme}:// endpoint"),
+        None => (443, endpoint),
+    };
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let (host, port) = match authority.rsplit_once(':') {
+        // A bracketed IPv6 address has colons of its own.
+        Some((host, port)) if !port.contains(']') => {
+            let port = port
+                .parse()
+                .map_err(|_| anyhow!("invalid port in endpoint {endpoint:?}"))?;
+            (host, port)
+        }
+        _ => (authority, default_port),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() {
+        bail!("endpoint {endpoint:?} has no host");
+    }
+    Ok((host.to_string(), port))
+}
+
+fn millis(duration: Duration) -> f64 {
+    (duration.as_secs_f64() * 10_000.0).round() / 10.0
+}
+
+fn render_table(results: &[Latency], use_color: bool) -> String {
+    let mut table = new_table();
+    table.set_header(vec![
+        Cell::new("REGION").add_attribute(Attribute::Bold),
+        Cell::new("LOCATION").add_attribute(Attribute::Bold),
+        Cell::new("MEDIAN").add_attribute(Attribute::Bold),
+        Cell::new("MIN").add_attribute(Attribute::Bold),
+    ]);
+    let ms = |value: Option<f64>| {
+        Cell::new(value.map_or_else(String::new, |v| format!("{v:.1} ms")))
+            .set_alignment(CellAlignment::Right)
+    };
+    for result in results {
+        let median = match (&result.error, result.median_ms) {
+            (Some(error), _) => cell_with_color(error.clone(), Some(Color::Yellow), use_color),
+            (None, median) => ms(median),
+        };
+        table.add_row(vec![
+            Cell::new(&result.region),
+            Cell::new(result.location.as_deref().unwrap_or("")),
+            median,
+            ms(result.min_ms),
+        ]);
+    }
+    table.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use unisrv_api::models::RegionListResponse;
+    use unisrv_api::test_support::MockApiClient;
+
+    /// Answers with fixed timings per host; anything else is unreachable.
+    struct FixedProber(BTreeMap<&'static str, Vec<u64>>);
+
+    #[async_trait]
+    impl Prober for FixedProber {
+        async fn round_trips(&self, host: &str, port: u16, _: u32) -> Result<Vec<Duration>> {
+            assert_eq!(port, 443);
+            match self.0.get(host) {
+                Some(ms) => Ok(ms.iter().map(|&ms| Duration::from_millis(ms)).collect()),
+                None => bail!("no answer within 3s"),
+            }
+        }
+    }
+
+    fn region(name: &str, endpoint: Option<&str>) -> Region {
+        Region {
+            name: name.into(),
+            location: None,
+            available: true,
+            endpoint: endpoint.map(String::from),
+        }
+    }
+
+    #[test]
+    fn endpoints_resolve_to_a_host_and_port() {
+        assert_eq!(
+            host_and_port("https://edge.eu.unisrv.io/ping").unwrap(),
+            ("edge.eu.unisrv.io".to_string(), 443)
+        );
+        assert_eq!(
+            host_and_port("http://10.0.0.1:8080").unwrap(),
+            ("10.0.0.1".to_string(), 8080)
+        );
+        assert_eq!(
+            host_and_port("[2001:db8::1]").unwrap(),
+            ("2001:db8::1".to_string(), 443)
+        );
+        assert!(host_and_port("wss://edge").is_err());
+    }
+
+    #[tokio::test]
+    async fn fastest_region_comes_first_and_unreachable_last() {
+        let mut results = Vec::new();
+        let prober = FixedProber(BTreeMap::from([
+            ("edge.us", vec![90, 80, 120]),
+            ("edge.eu", vec![30, 12, 25]),
+        ]));
+        for r in [
+            region("us-east", Some("https://edge.us")),
+            region("dev", None),
+            region("ap-south", Some("https://edge.ap")),
+            region("eu-central", Some("https://edge.eu")),
+        ] {
+            results.push(measure(&r, 3, &prober).await);
+        }
+        sort(&mut results);
+
+        let order: Vec<_> = results.iter().map(|r| r.region.as_str()).collect();
+        assert_eq!(order, ["eu-central", "us-east", "ap-south", "dev"]);
+        assert_eq!(
+            (results[0].median_ms, results[0].min_ms),
+            (Some(25.0), Some(12.0))
+        );
+        assert_eq!(results[2].error.as_deref(), Some("no answer within 3s"));
+        let table = render_table(&results, false);
+        assert!(table.contains("25.0 ms"), "{table}");
+        assert!(table.contains("no endpoint to measure"), "{table}");
+    }
+
+    #[tokio::test]
+    async fn count_is_bounded_before_anything_is_asked() {
+        let mock = MockApiClient::logged_in()
+            .with_list_regions(Ok(RegionListResponse { regions: vec![] }));
+        let prober = FixedProber(BTreeMap::new());
+        let err = ping(&mock, 0, &prober, &Output::Table).await.unwrap_err();
+        assert!(err.to_string().contains("between 1 and 50"), "{err:#}");
+        assert_eq!(mock.calls.lock().unwrap().list_regions_calls, 0);
+    }
+}
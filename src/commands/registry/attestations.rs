@@ -0,0 +1,301 @@
+//! `unisrv registry sbom|provenance <image>` — the SBOMs and build provenance
+//! attached to an image, so what's deployed can be reviewed from here.
+//!
+//! The image's tag is resolved to a digest and the artifacts attached to that
+//! digest are listed through the registry's referrers API (or the fallback
+//! tag registries without it keep them under). Their layers are recognised by
+//! content: SPDX and CycloneDX documents, and in-toto statements — bare or in
+//! a DSSE envelope, as `cosign attest` pushes them — whose predicate is an
+//! SBOM or SLSA provenance. Anything else attached, like signatures, is passed
+//! over.
+//!
+//! The summary names each document's format and what it covers; `--json`
+//! prints the documents themselves (a statement's predicate), as an array.
+
+use anyhow::{Result, bail};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde_json::Value;
+use unisrv_api::ApiClient;
+use unisrv_api::distribution::{ArtifactSource, ManifestSource};
+
+use super::tags::Repository;
+use crate::commands::output::Output;
+
+/// Which attestations to show.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Sbom,
+    Provenance,
+}
+
+impl Kind {
+    fn noun(self) -> &'static str {
+        match self {
+            Kind::Sbom => "SBOM",
+            Kind::Provenance => "provenance",
+        }
+    }
+}
+
+/// One recognised document.
+#[derive(Debug, PartialEq)]
+struct Document {
+    kind: Kind,
+    /// The artifact manifest it was attached in.
+    artifact: String,
+    body: Value,
+    /// The predicate type, for an in-toto statement.
+    predicate_type: Option<String>,
+}
+
+/// `unisrv registry sbom|provenance <image>`.
+pub async fn show(client: &dyn ApiClient, image: &str, kind: Kind, output: &Output) -> Result<()> {
+    let (repo, reference) = Repository::parse_manifest_reference(image)?;
+    let source = super::distribution_client(client, &repo.host, &repo.name, false).await?;
+    let digest = source.digest(&reference).await?;
+    let documents = attached(&source, &digest, kind).await?;
+    if documents.is_empty() {
+        bail!("no {} is attached to {image} ({digest})", kind.noun());
+    }
+    if output.is_machine() {
+        let bodies: Vec<&Value> = documents.iter().map(|d| &d.body).collect();
+        return output.print_json(&bodies);
+    }
+    println!("{image} ({digest})");
+    for document in &documents {
+        print!("{}", summarize(document));
+    }
+    Ok(())
+}
+
+/// Every `kind` document attached to the manifest `digest`.
+async fn attached(source: &dyn ArtifactSource, digest: &str, kind: Kind) -> Result<Vec<Document>> {
+    let mut documents = Vec::new();
+    for artifact in source.referrers(digest).await? {
+        for layer in source.layers(&artifact.digest).await? {
+            if !layer.media_type.ends_with("json") {
+                continue;
+            }
+            let blob = source.blob(&layer.digest).await?;
+            if let Some(document) = recognise(&blob, &artifact.digest)
+                && document.kind == kind
+            {
+                documents.push(document);
+            }
+        }
+    }
+    Ok(documents)
+}
+
+/// What `blob` is, if it's an SBOM or provenance.
+fn recognise(blob: &[u8], artifact: &str) -> Option<Document> {
+    let mut value: Value = serde_json::from_slice(blob).ok()?;
+    // A DSSE envelope carries the statement base64-encoded.
+    if let Some(payload) = value.get("payload").and_then(Value::as_str)
+        && value.get("payloadType").is_some()
+    {
+        value = serde_json::from_slice(&STANDARD.decode(payload).ok()?).ok()?;
+    }
+    let document = |kind, body, predicate_type| {
+        Some(Document {
+            kind,
+            artifact: artifact.to_string(),
+            body,
+            predicate_type,
+        })
+    };
+    if let Some(predicate_type) = value.get("predicateType").and_then(Value::as_str) {
+        let kind = if predicate_type.contains("slsa.dev/provenance") {
+            Kind::Provenance
+        } else if predicate_type.contains("spdx") || predicate_type.contains("cyclonedx") {
+            Kind::Sbom
+        } else {
+            return None;
+        };
+        let predicate_type = predicate_type.to_string();
+        let body = value.get_mut("predicate")?.take();
+        return document(kind, body, Some(predicate_type));
+    }
+    if value.get("spdxVersion").is_some()
+        || value.get("bomFormat").and_then(Value::as_str) == Some("CycloneDX")
+    {
+        return document(Kind::Sbom, value, None);
+    }
+    None
+}
+
+/// A few lines on `document`: its format and what it covers.
+fn summarize(document: &Document) -> String {
+    let body = &document.body;
+    let text = |pointer: &str| body.pointer(pointer).and_then(Value::as_str);
+    let count = |key: &str| body.get(key).and_then(Value::as_array).map_or(0, Vec::len);
+    let mut out = String::new();
+    match document.kind {
+        Kind::Sbom => {
+            let counted = |n: usize, noun: &str| match n {
+                1 => format!("1 {noun}"),
+                n => format!("{n} {noun}s"),
+            };
+            let line = match (text("/spdxVersion"), text("/specVersion")) {
+                (Some(version), _) => {
+                    format!("{version}, {}", counted(count("packages"), "package"))
+                }
+                (None, Some(version)) => format!(
+                    "CycloneDX {version}, {}",
+                    counted(count("components"), "component")
+                ),
+                (None, None) => "SBOM in an unrecognised format".to_string(),
+            };
+            out.push_str(&format!("  {line}\n"));
+        }
+        Kind::Provenance => {
+            let version = document
+                .predicate_type
+                .as_deref()
+                .and_then(|t| t.rsplit_once("provenance/"))
+                .map_or("", |(_, version)| version);
+            out.push_str(&format!("  SLSA provenance {version}\n"));
+            // v1 first, then v0.2's layout.
+            let fields = [
+                (
+                    "builder",
+                    text("/runDetails/builder/id").or(text("/builder/id")),
+                ),
+                (
+                    "build type",
+                    text("/buildDefinition/buildType").or(text("/buildType")),
+                ),
+                (
+                    "source",
+                    text("/buildDefinition/resolvedDependencies/0/uri")
+                        .or(text("/invocation/configSource/uri"))
+                        .or(text("/materials/0/uri")),
+                ),
+            ];
+            for (label, value) in fields {
+                if let Some(value) = value {
+                    out.push_str(&format!("    {:<11} {value}\n", format!("{label}:")));
+                }
+            }
+        }
+    }
+    out.push_str(&format!("    attached in {}\n", document.artifact));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use serde_json::json;
+    use std::collections::BTreeMap;
+    use unisrv_api::distribution::Descriptor;
+
+    fn descriptor(media_type: &str, digest: &str) -> Descriptor {
+        Descriptor {
+            media_type: media_type.into(),
+            digest: digest.into(),
+            size: 0,
+            artifact_type: None,
+            annotations: BTreeMap::new(),
+        }
+    }
+
+    /// An image with an SPDX SBOM, SLSA provenance in a DSSE envelope, and a
+    /// signature.
+    struct Attached;
+
+    #[async_trait]
+    impl ArtifactSource for Attached {
+        async fn referrers(&self, _digest: &str) -> unisrv_api::Result<Vec<Descriptor>> {
+            let manifest = "application/vnd.oci.image.manifest.v1+json";
+            Ok(vec![
+                descriptor(manifest, "sha256:sbom"),
+                descriptor(manifest, "sha256:provenance"),
+                descriptor(manifest, "sha256:signature"),
+            ])
+        }
+
+        async fn layers(&self, digest: &str) -> unisrv_api::Result<Vec<Descriptor>> {
+            Ok(vec![match digest {
+                "sha256:sbom" => descriptor("application/spdx+json", "sha256:spdx"),
+                "sha256:provenance" => {
+                    descriptor("application/vnd.dsse.envelope.v1+json", "sha256:dsse")
+                }
+                _ => descriptor(
+                    "application/vnd.dev.cosign.simplesigning.v1+json",
+                    "sha256:sig",
+                ),
+            }])
+        }
+
+        async fn blob(&self, digest: &str) -> unisrv_api::Result<Vec<u8>> {
+            let body = match digest {
+                "sha256:spdx" => json!({
+                    "spdxVersion": "SPDX-2.3",
+                    "packages": [{"name": "openssl"}, {"name": "zlib"}],
+                }),
+                "sha256:dsse" => {
+                    let statement = json!({
+                        "_type": "https://in-toto.io/Statement/v1",
+                        "predicateType": "https://slsa.dev/provenance/v1",
+                        "predicate": {
+                            "buildDefinition": {
+                                "buildType": "https://actions.github.io/buildtypes/workflow/v1",
+                                "resolvedDependencies": [{"uri": "git+https://github.com/acme/app@refs/heads/main"}],
+                            },
+                            "runDetails": {"builder": {"id": "https://github.com/actions/runner"}},
+                        },
+                    });
+                    json!({
+                        "payloadType": "application/vnd.in-toto+json",
+                        "payload": STANDARD.encode(statement.to_string()),
+                        "signatures": [],
+                    })
+                }
+                _ => json!({"critical": {"type": "cosign container image signature"}}),
+            };
+            Ok(body.to_string().into_bytes())
+        }
+    }
+
+    #[tokio::test]
+    async fn finds_each_kind_among_the_attached_artifacts() {
+        let sboms = attached(&Attached, "sha256:image", Kind::Sbom)
+            .await
+            .unwrap();
+        assert_eq!(sboms.len(), 1);
+        assert_eq!(
+            summarize(&sboms[0]),
+            "  SPDX-2.3, 2 packages\n    attached in sha256:sbom\n"
+        );
+
+        let provenance = attached(&Attached, "sha256:image", Kind::Provenance)
+            .await
+            .unwrap();
+        assert_eq!(provenance.len(), 1);
+        assert_eq!(
+            summarize(&provenance[0]),
+            "  SLSA provenance v1\n    \
+             builder:    https://github.com/actions/runner\n    \
+             build type: https://actions.github.io/buildtypes/workflow/v1\n    \
+             source:     git+https://github.com/acme/app@refs/heads/main\n    \
+             attached in sha256:provenance\n"
+        );
+    }
+
+    #[test]
+    fn recognises_cyclonedx_and_ignores_other_statements() {
+        let bom = json!({"bomFormat": "CycloneDX", "specVersion": "1.5", "components": [{}]});
+        let document = recognise(bom.to_string().as_bytes(), "sha256:a").unwrap();
+        assert_eq!(
+            summarize(&document),
+            "  CycloneDX 1.5, 1 component\n    attached in sha256:a\n"
+        );
+
+        let vuln = json!({"predicateType": "https://cosign.sigstore.dev/attestation/vuln/v1", "predicate": {}});
+        assert_eq!(recognise(vuln.to_string().as_bytes(), "sha256:a"), None);
+        assert_eq!(recognise(b"not json", "sha256:a"), None);
+    }
+}
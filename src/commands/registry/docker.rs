@@ -0,0 +1,409 @@
+//! `unisrv registry import-docker` — copy the credentials `docker login`
+//! already saved into unisrv, instead of logging in to every registry again.
+//!
+//! Docker keeps them in `~/.docker/config.json` (or `$DOCKER_CONFIG`), either
+//! inline under `auths` or in a credential helper named by `credsStore` (all
+//! registries) or `credHelpers` (per registry). Helpers are asked through the
+//! same `docker-credential-<name> get` protocol Docker uses. Registries that
+//! already exist in unisrv are updated; the rest are added.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, anyhow, bail};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::Deserialize;
+use unisrv_api::ApiClient;
+use unisrv_api::models::{
+    CreateRegistryRequest, RegistryKind, UpdateRegistryRequest, UserpassConfig, UserpassSecret,
+};
+
+use super::map_registry_write_error;
+
+/// `unisrv registry import-docker`.
+pub async fn import(client: &dyn ApiClient, path: Option<&Path>, validate: bool) -> Result<()> {
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => default_path().ok_or_else(|| anyhow!("can't find Docker's config; pass --path"))?,
+    };
+    let config = DockerConfig::load(&path)?;
+    import_config(client, &config, &SystemHelpers, validate).await
+}
+
+/// Where Docker keeps its config: `$DOCKER_CONFIG/config.json`, else
+/// `~/.docker/config.json`.
+fn default_path() -> Option<PathBuf> {
+    match std::env::var_os("DOCKER_CONFIG").filter(|v| !v.is_empty()) {
+        Some(dir) => Some(PathBuf::from(dir).join("config.json")),
+        None => Some(dirs::home_dir()?.join(".docker").join("config.json")),
+    }
+}
+
+/// The parts of Docker's `config.json` that hold credentials.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DockerConfig {
+    #[serde(default)]
+    auths: BTreeMap<String, DockerAuth>,
+    #[serde(default)]
+    creds_store: Option<String>,
+    #[serde(default)]
+    cred_helpers: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DockerAuth {
+    /// Base64 of `username:password`.
+    #[serde(default)]
+    auth: Option<String>,
+    #[serde(default)]
+    username: Option<String>,
+    #[serde(default)]
+    password: Option<String>,
+    #[serde(default)]
+    identitytoken: Option<String>,
+}
+
+impl DockerConfig {
+    fn load(path: &Path) -> Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        serde_json::from_str(&json).with_context(|| format!("invalid {}", path.display()))
+    }
+}
+
+/// Runs Docker credential helpers. A trait so tests don't need real ones.
+pub(super) trait CredentialHelpers {
+    /// `docker-credential-<helper> get` for `server`: its username and
+    /// secret, or `None` when it has nothing stored.
+    fn get(&self, helper: &str, server: &str) -> Result<Option<(String, String)>>;
+}
+
+pub(super) struct SystemHelpers;
+
+impl CredentialHelpers for SystemHelpers {
+    fn get(&self, helper: &str, server: &str) -> Result<Option<(String, String)>> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "PascalCase")]
+        struct Reply {
+            username: String,
+            secret: String,
+        }
+
+        let program = format!("docker-credential-{helper}");
+        let mut child = Command::new(&program)
+            .arg("get")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("failed to run {program}"))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(server.as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+        // Helpers exit non-zero with "credentials not found" for an unknown
+        // server; anything else would have failed to spawn above.
+        if !output.status.success() {
+            return Ok(None);
+        }
+        let reply: Reply = serde_json::from_slice(&output.stdout)
+            .with_context(|| format!("unexpected reply from {program}"))?;
+        Ok(Some((reply.username, reply.secret)))
+    }
+}
+
+/// One registry's outcome from reading Docker's config.
+#[derive(Debug, PartialEq)]
+enum Found {
+    Credentials { username: String, password: String },
+    Skipped(String),
+}
+
+/// Every registry in `config`, keyed by the hostname unisrv knows it by.
+fn credentials(config: &DockerConfig, helpers: &dyn CredentialHelpers) -> BTreeMap<String, Found> {
+    let servers = config.auths.keys().chain(config.cred_helpers.keys());
+    let mut found = BTreeMap::new();
+    for server in servers {
+        let hostname = normalize_hostname(server);
+        if found.contains_key(&hostname) {
+            continue;
+        }
+        let helper = config
+            .cred_helpers
+            .get(server)
+            .or(config.creds_store.as_ref());
+        let outcome = match helper {
+            Some(helper) => match helpers.get(helper, server) {
+                Ok(Some((username, _))) if username == "<token>" => {
+                    Found::Skipped("uses an identity token, which unisrv can't store".into())
+                }
+                Ok(Some((username, password))) => Found::Credentials { username, password },
+                Ok(None) => match config.auths.get(server) {
+                    // A helper with nothing stored falls back to inline auth.
+                    Some(auth) if !config.cred_helpers.contains_key(server) => inline(auth),
+                    _ => Found::Skipped(format!("docker-credential-{helper} has no credentials")),
+                },
+                Err(e) => Found::Skipped(format!("{e:#}")),
+            },
+            None => match config.auths.get(server) {
+                Some(auth) => inline(auth),
+                None => Found::Skipped("no credentials".into()),
+            },
+        };
+        found.insert(hostname, outcome);
+    }
+    found
+}
+
+/// Credentials written into `config.json` itself.
+fn inline(auth: &DockerAuth) -> Found {
+    if let (Some(username), Some(password)) = (&auth.username, &auth.password) {
+        return Found::Credentials {
+            username: username.clone(),
+            password: password.clone(),
+        };
+    }
+    if let Some(encoded) = auth.auth.as_deref().filter(|a| !a.is_empty()) {
+        let decoded = STANDARD
+            .decode(encoded.trim())
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok());
+        return match decoded.as_deref().and_then(|d| d.split_once(':')) {
+            Some((username, password)) => Found::Credentials {
+                username: username.to_string(),
+                password: password.to_string(),
+            },
+            None => Found::Skipped("its `auth` isn't base64 of username:password".into()),
+        };
+    }
+    if auth.identitytoken.is_some() {
+        return Found::Skipped("uses an identity token, which unisrv can't store".into());
+    }
+    Found::Skipped("no credentials".into())
+}
+
+/// `https://index.docker.io/v1/` → `docker.io`, `https://ghcr.io` → `ghcr.io`.
+fn normalize_hostname(server: &str) -> String {
+    let server = server
+        .strip_prefix("https://")
+        .or_else(|| server.strip_prefix("http://"))
+        .unwrap_or(server);
+    let host = server
+        .split('/')
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match host.as_str() {
+        "index.docker.io" | "registry-1.docker.io" | "registry.hub.docker.com" => {
+            "docker.io".into()
+        }
+        _ => host,
+    }
+}
+
+async fn import_config(
+    client: &dyn ApiClient,
+    config: &DockerConfig,
+    helpers: &dyn CredentialHelpers,
+    validate: bool,
+) -> Result<()> {
+    let found = credentials(config, helpers);
+    if found.is_empty() {
+        println!("No registries in Docker's config.");
+        return Ok(());
+    }
+    let existing: BTreeMap<String, uuid::Uuid> = client
+        .list_registries()
+        .await?
+        .registries
+        .into_iter()
+        .map(|r| (r.hostname.to_ascii_lowercase(), r.id))
+        .collect();
+
+    let mut failed = 0;
+    for (hostname, outcome) in found {
+        let (username, password) = match outcome {
+            Found::Credentials { username, password } => (username, password),
+            Found::Skipped(reason) => {
+                println!("- Skipped {hostname}: {reason}.");
+                continue;
+            }
+        };
+        let config = serde_json::to_value(UserpassConfig { username })?;
+        let secret = serde_json::to_value(UserpassSecret { password })?;
+        let result = match existing.get(&hostname) {
+            Some(id) => client
+                .update_registry(
+                    *id,
+                    UpdateRegistryRequest {
+                        config: Some(config),
+                        secret: Some(secret),
+                    },
+                    validate,
+                )
+                .await
+                .map(|_| "Updated"),
+            None => client
+                .create_registry(
+                    CreateRegistryRequest {
+                        hostname: hostname.clone(),
+                        kind: RegistryKind::Userpass,
+                        config,
+                        secret,
+                    },
+                    validate,
+                )
+                .await
+                .map(|_| "Added"),
+        };
+        match result {
+            Ok(done) => println!("\u{2713} {done} {hostname}."),
+            Err(err) => {
+                failed += 1;
+                eprintln!(
+                    "\u{2717} {hostname}: {}",
+                    map_registry_write_error(err, &hostname)
+                );
+            }
+        }
+    }
+    if failed > 0 {
+        bail!(
+            "{failed} registr{} couldn't be imported",
+            if failed == 1 { "y" } else { "ies" }
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use unisrv_api::models::{RegistryListResponse, RegistryResponse};
+    use unisrv_api::test_support::MockApiClient;
+    use uuid::Uuid;
+
+    /// Helpers answering from a fixed table of `(helper, server)`.
+    struct Helpers(Vec<(&'static str, &'static str, &'static str, &'static str)>);
+
+    impl CredentialHelpers for Helpers {
+        fn get(&self, helper: &str, server: &str) -> Result<Option<(String, String)>> {
+            Ok(self
+                .0
+                .iter()
+                .find(|(h, s, _, _)| *h == helper && *s == server)
+                .map(|(_, _, user, secret)| (user.to_string(), secret.to_string())))
+        }
+    }
+
+    fn config(json: serde_json::Value) -> DockerConfig {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn creds(username: &str, password: &str) -> Found {
+        Found::Credentials {
+            username: username.into(),
+            password: password.into(),
+        }
+    }
+
+    #[test]
+    fn normalizes_docker_server_keys() {
+        assert_eq!(
+            normalize_hostname("https://index.docker.io/v1/"),
+            "docker.io"
+        );
+        assert_eq!(normalize_hostname("https://GHCR.io"), "ghcr.io");
+        assert_eq!(
+            normalize_hostname("registry.example.com:5000"),
+            "registry.example.com:5000"
+        );
+    }
+
+    #[test]
+    fn reads_inline_auths() {
+        let config = config(serde_json::json!({
+            "auths": {
+                "https://index.docker.io/v1/": { "auth": STANDARD.encode("alice:s3cret:x") },
+                "quay.io": { "username": "bob", "password": "pw" },
+                "gcr.io": { "identitytoken": "tok" },
+                "empty.example.com": {}
+            }
+        }));
+        let found = credentials(&config, &Helpers(vec![]));
+        assert_eq!(found["docker.io"], creds("alice", "s3cret:x"));
+        assert_eq!(found["quay.io"], creds("bob", "pw"));
+        assert!(matches!(found["gcr.io"], Found::Skipped(_)));
+        assert!(matches!(found["empty.example.com"], Found::Skipped(_)));
+    }
+
+    #[test]
+    fn asks_credential_helpers() {
+        let config = config(serde_json::json!({
+            "auths": { "ghcr.io": {}, "quay.io": { "auth": STANDARD.encode("carol:pw") } },
+            "credsStore": "desktop",
+            "credHelpers": { "123.dkr.ecr.us-east-1.amazonaws.com": "ecr-login" }
+        }));
+        let helpers = Helpers(vec![
+            ("desktop", "ghcr.io", "alice", "ghp_x"),
+            (
+                "ecr-login",
+                "123.dkr.ecr.us-east-1.amazonaws.com",
+                "AWS",
+                "ecr-token",
+            ),
+        ]);
+        let found = credentials(&config, &helpers);
+        assert_eq!(found["ghcr.io"], creds("alice", "ghp_x"));
+        assert_eq!(
+            found["123.dkr.ecr.us-east-1.amazonaws.com"],
+            creds("AWS", "ecr-token")
+        );
+        // Nothing in the store, so the inline entry is used.
+        assert_eq!(found["quay.io"], creds("carol", "pw"));
+    }
+
+    #[tokio::test]
+    async fn updates_known_registries_and_adds_the_rest() {
+        let now = Utc::now().naive_utc();
+        let existing = RegistryResponse {
+            id: Uuid::new_v4(),
+            hostname: "ghcr.io".into(),
+            kind: RegistryKind::Userpass,
+            config: serde_json::json!({ "username": "old" }),
+            created_at: now,
+            updated_at: now,
+        };
+        let existing_id = existing.id;
+        let mock = MockApiClient::logged_in()
+            .with_list_registries(Ok(RegistryListResponse {
+                registries: vec![existing.clone()],
+            }))
+            .push_create_registry(Ok(existing.clone()))
+            .push_update_registry(Ok(existing));
+        let config = config(serde_json::json!({
+            "auths": {
+                "ghcr.io": { "auth": STANDARD.encode("alice:new") },
+                "https://index.docker.io/v1/": { "auth": STANDARD.encode("bob:pw") }
+            }
+        }));
+
+        import_config(&mock, &config, &Helpers(vec![]), false)
+            .await
+            .unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        assert_eq!(calls.create_registry_calls.len(), 1);
+        assert_eq!(calls.create_registry_calls[0].0.hostname, "docker.io");
+        assert_eq!(calls.update_registry_calls.len(), 1);
+        let (id, req, validate) = &calls.update_registry_calls[0];
+        assert_eq!(*id, existing_id);
+        assert_eq!(req.config, Some(serde_json::json!({ "username": "alice" })));
+        assert!(!validate);
+    }
+}
@@ -0,0 +1,214 @@
+//! The platform's own registry: `unisrv registry repos` lists its
+//! repositories with the storage each takes, `registry images <repo>` the
+//! tags in one, and `registry delete <repo>:<tag>` removes a tag, to clear
+//! out old ones.
+//!
+//! Storage is counted per repository with each blob once, so deleting a tag
+//! frees only the layers no other tag shares.
+
+use anyhow::{Result, bail};
+use chrono::NaiveDateTime;
+use comfy_table::{Attribute, Cell, CellAlignment};
+use unisrv_api::ApiClient;
+use unisrv_api::models::{HostedImage, HostedRepository};
+
+use crate::commands::confirm::Guard;
+use crate::commands::output::Output;
+use crate::commands::ui::{format_relative, human_bytes, new_table};
+
+/// Whether `registry delete`'s argument is a `repo:tag` in the platform
+/// registry rather than a registry hostname. A hostname may carry a port
+/// (`registry.local:5000`), but then it's a domain or `localhost` with a
+/// numeric port; anything else with a colon names a tag.
+pub fn is_tag_reference(target: &str) -> bool {
+    match target.rsplit_once(':') {
+        None => false,
+        Some((host, port)) => {
+            let is_port = !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit());
+            let is_host = !host.contains('/') && (host.contains('.') || host == "localhost");
+            !(is_port && is_host)
+        }
+    }
+}
+
+pub async fn repos(client: &dyn ApiClient, output: &Output) -> Result<()> {
+    let mut repositories = client.list_hosted_repositories().await?.repositories;
+    repositories.sort_by(|a, b| b.size_bytes.cmp(&a.size_bytes).then(a.name.cmp(&b.name)));
+
+    match output {
+        Output::Json | Output::Jq(_) => return output.print_json(&repositories),
+        Output::Template(template) => {
+            print!("{}", template.render_all(&repositories)?);
+            return Ok(());
+        }
+        Output::Table => {}
+    }
+    if repositories.is_empty() {
+        println!("No repositories yet. Push one with `unisrv registry push`.");
+        return Ok(());
+    }
+    let now = chrono::Utc::now().naive_utc();
+    println!("{}", render_repos(&repositories, now));
+    let total: u64 = repositories.iter().map(|r| r.size_bytes).sum();
+    println!("Total: {}", human_bytes(total));
+    Ok(())
+}
+
+pub async fn images(client: &dyn ApiClient, repository: &str, output: &Output) -> Result<()> {
+    let mut images = client.list_hosted_images(repository).await?.images;
+    images.sort_by(|a, b| b.pushed_at.cmp(&a.pushed_at).then(a.tag.cmp(&b.tag)));
+
+    match output {
+        Output::Json | Output::Jq(_) => return output.print_json(&images),
+        Output::Template(template) => {
+            print!("{}", template.render_all(&images)?);
+            return Ok(());
+        }
+        Output::Table => {}
+    }
+    if images.is_empty() {
+        println!("{repository} has no tags.");
+        return Ok(());
+    }
+    let now = chrono::Utc::now().naive_utc();
+    println!("{}", render_images(&images, now));
+    Ok(())
+}
+
+pub async fn delete_tag(client: &dyn ApiClient, reference: &str, yes: bool) -> Result<()> {
+    delete_tag_with_confirm(client, reference, yes, |reference| {
+        // Like registry credentials, images belong to the account rather than
+        // an environment.
+        Guard::unprotected().confirm_or_ask(
+            &format!("delete {reference}"),
+            reference,
+            &format!("Delete {reference} from the registry?"),
+            false,
+        )
+    })
+    .await
+}
+
+async fn delete_tag_with_confirm<F>(
+    client: &dyn ApiClient,
+    reference: &str,
+    yes: bool,
+    confirm: F,
+) -> Result<()>
+where
+    F: FnOnce(&str) -> Result<bool>,
+{
+    let Some((repository, tag)) = reference
+        .rsplit_once(':')
+        .filter(|(repo, tag)| !repo.is_empty() && !tag.is_empty() && !tag.contains('/'))
+    else {
+        bail!("{reference:?} isn't a REPO:TAG, e.g. app:1.2");
+    };
+
+    if !yes && !confirm(reference)? {
+        println!("Aborted.");
+        return Ok(());
+    }
+    client.delete_hosted_image(repository, tag).await?;
+    println!("\u{2713} Deleted {reference}.");
+    Ok(())
+}
+
+fn header(names: &[&str]) -> Vec<Cell> {
+    names
+        .iter()
+        .map(|name| Cell::new(name).add_attribute(Attribute::Bold))
+        .collect()
+}
+
+fn render_repos(repositories: &[HostedRepository], now: NaiveDateTime) -> String {
+    let mut table = new_table();
+    table.set_header(header(&["REPOSITORY", "TAGS", "SIZE", "LAST PUSHED"]));
+    for repo in repositories {
+        table.add_row(vec![
+            Cell::new(&repo.name),
+            Cell::new(repo.tag_count).set_alignment(CellAlignment::Right),
+            Cell::new(human_bytes(repo.size_bytes)).set_alignment(CellAlignment::Right),
+            Cell::new(
+                repo.last_pushed_at
+                    .map_or_else(|| "\u{2014}".to_string(), |at| format_relative(at, now)),
+            ),
+        ]);
+    }
+    table.to_string()
+}
+
+fn render_images(images: &[HostedImage], now: NaiveDateTime) -> String {
+    let mut table = new_table();
+    table.set_header(header(&["TAG", "DIGEST", "SIZE", "PUSHED"]));
+    for image in images {
+        // `sha256:` and twelve hex digits, as `docker images` shortens ids.
+        let digest: String = image.digest.chars().take(19).collect();
+        table.add_row(vec![
+            Cell::new(&image.tag),
+            Cell::new(digest),
+            Cell::new(human_bytes(image.size_bytes)).set_alignment(CellAlignment::Right),
+            Cell::new(format_relative(image.pushed_at, now)),
+        ]);
+    }
+    table.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use unisrv_api::test_support::MockApiClient;
+
+    #[test]
+    fn tells_tags_from_hostnames() {
+        for hostname in ["ghcr.io", "registry.local:5000", "localhost:5000"] {
+            assert!(!is_tag_reference(hostname), "{hostname}");
+        }
+        for tag in ["app:1", "app:latest", "team/app:2.0", "ghcr.io/app:1"] {
+            assert!(is_tag_reference(tag), "{tag}");
+        }
+    }
+
+    #[test]
+    fn renders_repositories_with_sizes() {
+        let now = chrono::Utc::now().naive_utc();
+        let rendered = render_repos(
+            &[HostedRepository {
+                name: "team/app".into(),
+                tag_count: 12,
+                size_bytes: 3 * 1024 * 1024 * 1024,
+                last_pushed_at: Some(now - Duration::days(2)),
+            }],
+            now,
+        );
+        assert!(rendered.contains("team/app"), "{rendered}");
+        assert!(rendered.contains("3.00 GiB"), "{rendered}");
+        assert!(rendered.contains("2 days ago"), "{rendered}");
+    }
+
+    #[tokio::test]
+    async fn deletes_a_tag_once_confirmed() {
+        let mock = MockApiClient::logged_in().push_delete_hosted_image(Ok(()));
+        delete_tag_with_confirm(&mock, "team/app:1.2", false, |_| Ok(true))
+            .await
+            .unwrap();
+        assert_eq!(
+            mock.calls.lock().unwrap().delete_hosted_image_calls,
+            [("team/app".to_string(), "1.2".to_string())]
+        );
+
+        let declined = MockApiClient::logged_in();
+        delete_tag_with_confirm(&declined, "app:1", false, |_| Ok(false))
+            .await
+            .unwrap();
+        assert!(
+            declined
+                .calls
+                .lock()
+                .unwrap()
+                .delete_hosted_image_calls
+                .is_empty()
+        );
+    }
+}
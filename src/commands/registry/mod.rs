@@ -1,11 +1,20 @@
+pub mod attestations;
+pub mod docker;
+pub mod hosted;
+pub mod policy;
+pub mod push;
+pub mod tags;
+
+use std::sync::OnceLock;
+
 use anyhow::{Result, anyhow, bail};
 use chrono::NaiveDateTime;
 use chrono_humanize::{Accuracy, HumanTime, Tense};
-use comfy_table::{Attribute, Cell, ContentArrangement, Table, presets::UTF8_FULL};
-use dialoguer::Confirm;
+use comfy_table::{Attribute, Cell};
 use std::io::Read;
 use unisrv_api::ApiClient;
 use unisrv_api::ApiError;
+use unisrv_api::distribution::DistributionClient;
 use unisrv_api::models::{
     CreateRegistryRequest, RegistryKind, RegistryResponse, UpdateRegistryRequest, UserpassConfig,
     UserpassSecret,
@@ -13,6 +22,22 @@ use unisrv_api::models::{
 use uuid::Uuid;
 use yapp::PasswordReader;
 
+use self::docker::{CredentialHelpers, SystemHelpers};
+use super::confirm::Guard;
+use super::output::Output;
+use super::prompt;
+use crate::commands::ui::new_table;
+use crate::state::StateDir;
+
+static CREDENTIAL_HELPER: OnceLock<Option<String>> = OnceLock::new();
+
+/// Authenticate the CLI's own registry requests with
+/// `docker-credential-<helper>` instead of credentials stored in unisrv, for
+/// the rest of the process. Only the first call counts.
+pub fn set_credential_helper(helper: Option<String>) {
+    let _ = CREDENTIAL_HELPER.set(helper);
+}
+
 pub async fn add(
     client: &dyn ApiClient,
     hostname: &str,
@@ -54,12 +79,32 @@ pub async fn update(
     username: Option<&str>,
     password_stdin: bool,
     validate: bool,
+) -> Result<()> {
+    let state = StateDir::locate();
+    update_in(
+        client,
+        state.as_ref(),
+        hostname,
+        username,
+        password_stdin,
+        validate,
+    )
+    .await
+}
+
+async fn update_in(
+    client: &dyn ApiClient,
+    state: Option<&StateDir>,
+    hostname: &str,
+    username: Option<&str>,
+    password_stdin: bool,
+    validate: bool,
 ) -> Result<()> {
     if username.is_none() && !password_stdin {
         bail!("Specify --username and/or --password-stdin to indicate what to update.");
     }
 
-    let id = resolve_registry_id(client, hostname).await?;
+    let id = resolve_registry_id(client, state, hostname).await?;
 
     let config = match username {
         Some(u) => Some(serde_json::to_value(UserpassConfig {
@@ -76,7 +121,11 @@ pub async fn update(
 
     let req = UpdateRegistryRequest { config, secret };
 
-    match client.update_registry(id, req, validate).await {
+    let updated = with_registry_id(client, state, hostname, id, async |id| {
+        client.update_registry(id, req.clone(), validate).await
+    })
+    .await?;
+    match updated {
         Ok(reg) => {
             if validate {
                 println!("\u{2713} Updated {}.", reg.hostname);
@@ -93,18 +142,31 @@ pub async fn update(
 }
 
 pub async fn delete(client: &dyn ApiClient, hostname: &str, yes: bool) -> Result<()> {
-    delete_with_confirm(client, hostname, yes, prompt_delete_confirmation).await
+    let state = StateDir::locate();
+    delete_with_confirm(
+        client,
+        state.as_ref(),
+        hostname,
+        yes,
+        prompt_delete_confirmation,
+    )
+    .await
 }
 
 fn prompt_delete_confirmation(hostname: &str) -> Result<bool> {
-    Ok(Confirm::new()
-        .with_prompt(format!("Delete registry credentials for {hostname}?"))
-        .default(false)
-        .interact()?)
+    // Registry credentials belong to the account, not an environment, so
+    // they're never protected.
+    Guard::unprotected().confirm_or_ask(
+        &format!("delete the registry credentials for {hostname}"),
+        hostname,
+        &format!("Delete registry credentials for {hostname}?"),
+        false,
+    )
 }
 
 async fn delete_with_confirm<F>(
     client: &dyn ApiClient,
+    state: Option<&StateDir>,
     hostname: &str,
     yes: bool,
     confirm: F,
@@ -112,24 +174,31 @@ async fn delete_with_confirm<F>(
 where
     F: FnOnce(&str) -> Result<bool>,
 {
-    let id = resolve_registry_id(client, hostname).await?;
+    let id = resolve_registry_id(client, state, hostname).await?;
 
     if !yes && !confirm(hostname)? {
         println!("Aborted.");
         return Ok(());
     }
 
-    client.delete_registry(id).await?;
+    with_registry_id(client, state, hostname, id, async |id| {
+        client.delete_registry(id).await
+    })
+    .await??;
     println!("\u{2713} Deleted {hostname}.");
     Ok(())
 }
 
-pub async fn list(client: &dyn ApiClient, json: bool) -> Result<()> {
+pub async fn list(client: &dyn ApiClient, output: &Output) -> Result<()> {
     let resp = client.list_registries().await?;
 
-    if json {
-        println!("{}", serde_json::to_string_pretty(&resp.registries)?);
-        return Ok(());
+    match output {
+        Output::Json | Output::Jq(_) => return output.print_json(&resp.registries),
+        Output::Template(template) => {
+            print!("{}", template.render_all(&resp.registries)?);
+            return Ok(());
+        }
+        Output::Table => {}
     }
 
     if resp.registries.is_empty() {
@@ -143,8 +212,15 @@ pub async fn list(client: &dyn ApiClient, json: bool) -> Result<()> {
 }
 
 pub async fn test(client: &dyn ApiClient, hostname: &str) -> Result<()> {
-    let id = resolve_registry_id(client, hostname).await?;
-    let resp = client.test_registry(id).await?;
+    test_in(client, StateDir::locate().as_ref(), hostname).await
+}
+
+async fn test_in(client: &dyn ApiClient, state: Option<&StateDir>, hostname: &str) -> Result<()> {
+    let id = resolve_registry_id(client, state, hostname).await?;
+    let resp = with_registry_id(client, state, hostname, id, async |id| {
+        client.test_registry(id).await
+    })
+    .await??;
 
     if resp.ok {
         let validity = match resp.expires_in_seconds {
@@ -167,9 +243,7 @@ pub async fn test(client: &dyn ApiClient, hostname: &str) -> Result<()> {
 fn resolve_username(username: Option<&str>) -> Result<String> {
     match username {
         Some(u) => Ok(u.to_string()),
-        None => Ok(dialoguer::Input::new()
-            .with_prompt("Username")
-            .interact_text()?),
+        None => prompt::required_text("Username", "pass --username"),
     }
 }
 
@@ -188,18 +262,155 @@ fn read_password(from_stdin: bool) -> Result<String> {
     }
 }
 
-async fn resolve_registry_id(client: &dyn ApiClient, hostname: &str) -> Result<Uuid> {
-    let resp = client.list_registries().await?;
+/// The id of the registry for `hostname`, from `state`'s resolution cache
+/// when an earlier command looked it up. Registries can't be renamed, so a
+/// cached id is either still `hostname`'s or gone; [`with_registry_id`]
+/// revalidates it by using it.
+async fn resolve_registry_id(
+    client: &dyn ApiClient,
+    state: Option<&StateDir>,
+    hostname: &str,
+) -> Result<Uuid> {
+    let key = resolution_key(hostname);
+    if let Some(id) = state.and_then(|state| state.cached_resolution(&key)) {
+        return Ok(id);
+    }
+    lookup_registry_id(client, state, hostname).await
+}
+
+/// Run `op` on the registry `id`. A 404 means a cached id went stale (the
+/// registry was deleted and maybe added again), so `hostname` is looked up
+/// afresh and `op` runs once more with what it resolves to now.
+async fn with_registry_id<T>(
+    client: &dyn ApiClient,
+    state: Option<&StateDir>,
+    hostname: &str,
+    id: Uuid,
+    op: impl AsyncFn(Uuid) -> Result<T, ApiError>,
+) -> Result<Result<T, ApiError>> {
+    match op(id).await {
+        Err(ApiError::Server { status: 404, .. }) => {
+            let current = lookup_registry_id(client, state, hostname).await?;
+            Ok(op(current).await)
+        }
+        result => Ok(result),
+    }
+}
+
+/// The id the server has for `hostname`, cached for the next command.
+async fn lookup_registry_id(
+    client: &dyn ApiClient,
+    state: Option<&StateDir>,
+    hostname: &str,
+) -> Result<Uuid> {
+    let id = find_registry_id(client, hostname).await?.ok_or_else(|| {
+        anyhow!(
+            "No registry found for {hostname}. Run `unisrv registry list` to see configured registries."
+        )
+    })?;
+    // Only a shortcut: failing to cache costs the next command a lookup.
+    if let Some(state) = state {
+        let _ = state.cache_resolution(&resolution_key(hostname), id);
+    }
+    Ok(id)
+}
+
+/// Hostnames are matched case-insensitively, so they're cached that way.
+fn resolution_key(hostname: &str) -> String {
+    format!("registry/{}", hostname.to_ascii_lowercase())
+}
+
+/// Find the registry for `hostname`, asking the server to filter first and
+/// falling back to the full list when the filter is unsupported or misses.
+async fn find_registry_id(client: &dyn ApiClient, hostname: &str) -> Result<Option<Uuid>> {
     let needle = hostname.to_ascii_lowercase();
-    resp.registries
-        .into_iter()
-        .find(|r| r.hostname.to_ascii_lowercase() == needle)
-        .map(|r| r.id)
-        .ok_or_else(|| {
-            anyhow!(
-                "No registry found for {hostname}. Run `unisrv registry list` to see configured registries."
-            )
-        })
+    let find = |registries: Vec<RegistryResponse>| {
+        registries
+            .into_iter()
+            .find(|r| r.hostname.to_ascii_lowercase() == needle)
+            .map(|r| r.id)
+    };
+    match client.find_registries_by_hostname(hostname).await {
+        Ok(resp) => {
+            if let Some(id) = find(resp.registries) {
+                return Ok(Some(id));
+            }
+        }
+        Err(e) if e.is_unsupported_endpoint() => {}
+        Err(e) => return Err(e.into()),
+    }
+    let resp = client.list_registries().await?;
+    Ok(find(resp.registries))
+}
+
+/// A client for `repository` on `hostname`, for reading it, or also pushing
+/// to it if `push`. Its credentials come from the configured credential
+/// helper; without one, from a token minted from those stored in unisrv.
+/// Reads fall back to anonymous access when there are none.
+pub(crate) async fn distribution_client(
+    client: &dyn ApiClient,
+    hostname: &str,
+    repository: &str,
+    push: bool,
+) -> Result<DistributionClient> {
+    if let Some(helper) = CREDENTIAL_HELPER.get().and_then(Option::as_deref) {
+        return helper_client(&SystemHelpers, helper, hostname, repository, push);
+    }
+    let token = scoped_token(client, hostname, repository, push).await?;
+    if push && token.is_none() {
+        bail!(
+            "No registry found for {hostname}. Add its credentials with `unisrv registry add {hostname}` first."
+        );
+    }
+    Ok(DistributionClient::for_host(hostname, repository, token))
+}
+
+/// A client authenticating with what `docker-credential-<helper>` holds for
+/// `hostname`.
+fn helper_client(
+    helpers: &dyn CredentialHelpers,
+    helper: &str,
+    hostname: &str,
+    repository: &str,
+    push: bool,
+) -> Result<DistributionClient> {
+    // Docker stores Docker Hub's credentials under its v1 index URL.
+    let server = match hostname {
+        "docker.io" => "https://index.docker.io/v1/",
+        other => other,
+    };
+    let client = DistributionClient::for_host(hostname, repository, None);
+    match helpers.get(helper, server)? {
+        Some((username, _)) if username == "<token>" => bail!(
+            "docker-credential-{helper} holds an identity token for {hostname}, which unisrv can't use"
+        ),
+        Some((username, secret)) => Ok(client.with_credentials(username, secret)),
+        None if push => bail!(
+            "docker-credential-{helper} has no credentials for {hostname}. Run `docker login {hostname}` first."
+        ),
+        None => Ok(client),
+    }
+}
+
+/// A token for `repository` on `hostname` minted from its stored credentials,
+/// or `None` when unisrv has none for it.
+async fn scoped_token(
+    client: &dyn ApiClient,
+    hostname: &str,
+    repository: &str,
+    push: bool,
+) -> Result<Option<String>> {
+    let Some(id) = find_registry_id(client, hostname).await? else {
+        return Ok(None);
+    };
+    match client.get_scoped_token(id, repository, push).await {
+        Ok(scoped) => Ok(Some(scoped.token)),
+        Err(e) if e.is_unsupported_endpoint() => bail!(
+            "this server doesn't support registry tokens, so {hostname}'s stored \
+             credentials can't be used from the CLI"
+        ),
+        Err(e) => Err(e.into()),
+    }
 }
 
 fn map_registry_write_error(err: ApiError, hostname: &str) -> anyhow::Error {
@@ -224,9 +435,7 @@ fn map_registry_write_error(err: ApiError, hostname: &str) -> anyhow::Error {
 }
 
 fn render_table(registries: &[RegistryResponse], now: NaiveDateTime) -> String {
-    let mut table = Table::new();
-    table.load_preset(UTF8_FULL);
-    table.set_content_arrangement(ContentArrangement::Dynamic);
+    let mut table = new_table();
     table.set_header(vec![
         Cell::new("HOSTNAME").add_attribute(Attribute::Bold),
         Cell::new("KIND").add_attribute(Attribute::Bold),
@@ -255,6 +464,7 @@ fn render_table(registries: &[RegistryResponse], now: NaiveDateTime) -> String {
 fn format_kind(kind: RegistryKind) -> String {
     match kind {
         RegistryKind::Userpass => "userpass".into(),
+        RegistryKind::Unknown => "?".into(),
     }
 }
 
@@ -263,6 +473,7 @@ fn extract_username(kind: RegistryKind, config: &serde_json::Value) -> String {
         RegistryKind::Userpass => serde_json::from_value::<UserpassConfig>(config.clone())
             .map(|c| c.username)
             .unwrap_or_else(|_| "\u{2014}".into()),
+        RegistryKind::Unknown => "\u{2014}".into(),
     }
 }
 
@@ -341,7 +552,7 @@ mod tests {
             registries: vec![registry("ghcr.io", "alice"), registry("docker.io", "bob")],
         }));
 
-        let result = list(&mock, false).await;
+        let result = list(&mock, &Output::Table).await;
         assert!(result.is_ok());
         assert_eq!(mock.calls.lock().unwrap().list_registries_calls, 1);
     }
@@ -350,7 +561,7 @@ mod tests {
     async fn list_json_outputs_array() {
         let mock = MockApiClient::logged_in()
             .with_list_registries(Ok(RegistryListResponse { registries: vec![] }));
-        let result = list(&mock, true).await;
+        let result = list(&mock, &Output::Json).await;
         assert!(result.is_ok());
     }
 
@@ -358,7 +569,7 @@ mod tests {
     async fn list_empty_prints_friendly_hint() {
         let mock = MockApiClient::logged_in()
             .with_list_registries(Ok(RegistryListResponse { registries: vec![] }));
-        let result = list(&mock, false).await;
+        let result = list(&mock, &Output::Table).await;
         assert!(result.is_ok());
     }
 
@@ -372,7 +583,7 @@ mod tests {
             }))
             .push_delete_registry(Ok(()));
 
-        let result = delete_with_confirm(&mock, "GHCR.IO", true, |_| {
+        let result = delete_with_confirm(&mock, None, "GHCR.IO", true, |_| {
             panic!("--yes should skip confirmation");
         })
         .await;
@@ -388,7 +599,7 @@ mod tests {
             registries: vec![registry("ghcr.io", "alice")],
         }));
 
-        let result = delete_with_confirm(&mock, "docker.io", true, |_| Ok(true)).await;
+        let result = delete_with_confirm(&mock, None, "docker.io", true, |_| Ok(true)).await;
         let err = result.unwrap_err();
         assert!(err.to_string().contains("No registry found for docker.io"));
     }
@@ -400,7 +611,7 @@ mod tests {
             registries: vec![reg],
         }));
 
-        let result = delete_with_confirm(&mock, "ghcr.io", false, |_| Ok(false)).await;
+        let result = delete_with_confirm(&mock, None, "ghcr.io", false, |_| Ok(false)).await;
         assert!(result.is_ok());
         assert!(mock.calls.lock().unwrap().delete_registry_calls.is_empty());
     }
@@ -419,7 +630,7 @@ mod tests {
                 error: None,
             }));
 
-        let result = test(&mock, "ghcr.io").await;
+        let result = test_in(&mock, None, "ghcr.io").await;
         assert!(result.is_ok());
         assert_eq!(
             mock.calls.lock().unwrap().test_registry_calls,
@@ -427,6 +638,73 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_resolves_through_the_hostname_filter_when_supported() {
+        let reg = registry("ghcr.io", "alice");
+        let expected_id = reg.id;
+        let mock = MockApiClient::logged_in()
+            .push_find_registries_by_hostname(Ok(RegistryListResponse {
+                registries: vec![reg],
+            }))
+            .push_test_registry(Ok(TestRegistryResponse {
+                ok: true,
+                expires_in_seconds: None,
+                error: None,
+            }));
+
+        test_in(&mock, None, "GHCR.io").await.unwrap();
+        let calls = mock.calls.lock().unwrap();
+        assert_eq!(calls.test_registry_calls, vec![expected_id]);
+        assert_eq!(calls.list_registries_calls, 0, "no full list needed");
+    }
+
+    #[tokio::test]
+    async fn a_resolved_hostname_is_reused_until_it_goes_stale() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = StateDir::new(tmp.path().to_path_buf());
+        let ok = || {
+            Ok(TestRegistryResponse {
+                ok: true,
+                expires_in_seconds: None,
+                error: None,
+            })
+        };
+        let first = registry("ghcr.io", "alice");
+        let mock = MockApiClient::logged_in()
+            .push_find_registries_by_hostname(Ok(RegistryListResponse {
+                registries: vec![first.clone()],
+            }))
+            .push_test_registry(ok())
+            .push_test_registry(ok());
+
+        test_in(&mock, Some(&state), "ghcr.io").await.unwrap();
+        test_in(&mock, Some(&state), "GHCR.io").await.unwrap();
+        {
+            let calls = mock.calls.lock().unwrap();
+            assert_eq!(calls.find_registries_by_hostname_calls.len(), 1);
+            assert_eq!(calls.test_registry_calls, vec![first.id, first.id]);
+        }
+
+        // Deleted and added again: the cached id is gone, so it's looked up.
+        let again = registry("ghcr.io", "alice");
+        let mock = MockApiClient::logged_in()
+            .push_test_registry(Err(ApiError::Server {
+                status: 404,
+                reason: "not found".into(),
+            }))
+            .push_find_registries_by_hostname(Ok(RegistryListResponse {
+                registries: vec![again.clone()],
+            }))
+            .push_test_registry(ok());
+
+        test_in(&mock, Some(&state), "ghcr.io").await.unwrap();
+        assert_eq!(
+            mock.calls.lock().unwrap().test_registry_calls,
+            vec![first.id, again.id]
+        );
+        assert_eq!(state.cached_resolution("registry/ghcr.io"), Some(again.id));
+    }
+
     #[tokio::test]
     async fn test_failure_returns_error() {
         let reg = registry("ghcr.io", "alice");
@@ -440,7 +718,7 @@ mod tests {
                 error: Some("registry rejected credentials".into()),
             }));
 
-        let result = test(&mock, "ghcr.io").await;
+        let result = test_in(&mock, None, "ghcr.io").await;
         let err = result.unwrap_err();
         assert!(err.to_string().contains("Registry test failed"));
     }
@@ -448,7 +726,7 @@ mod tests {
     #[tokio::test]
     async fn update_requires_at_least_one_field() {
         let mock = MockApiClient::logged_in();
-        let result = update(&mock, "ghcr.io", None, false, true).await;
+        let result = update_in(&mock, None, "ghcr.io", None, false, true).await;
         let err = result.unwrap_err();
         assert!(err.to_string().contains("--username"));
     }
@@ -463,7 +741,7 @@ mod tests {
             }))
             .push_update_registry(Ok(registry("ghcr.io", "carol")));
 
-        let result = update(&mock, "ghcr.io", Some("carol"), false, true).await;
+        let result = update_in(&mock, None, "ghcr.io", Some("carol"), false, true).await;
         assert!(result.is_ok(), "expected ok, got {result:?}");
 
         let calls = mock.calls.lock().unwrap();
@@ -505,4 +783,28 @@ mod tests {
         let val = extract_username(RegistryKind::Userpass, &serde_json::json!({}));
         assert_eq!(val, "\u{2014}");
     }
+
+    /// A helper holding credentials for Docker Hub only, as Docker files them.
+    struct HubOnly;
+
+    impl CredentialHelpers for HubOnly {
+        fn get(&self, _helper: &str, server: &str) -> Result<Option<(String, String)>> {
+            Ok((server == "https://index.docker.io/v1/")
+                .then(|| ("ci".to_string(), "s3cret".to_string())))
+        }
+    }
+
+    #[test]
+    fn credential_helpers_are_asked_by_docker_server_name() {
+        assert!(helper_client(&HubOnly, "pass", "docker.io", "acme/app", true).is_ok());
+        // Reads go anonymous without credentials; pushes can't.
+        assert!(helper_client(&HubOnly, "pass", "ghcr.io", "acme/app", false).is_ok());
+        let Err(err) = helper_client(&HubOnly, "pass", "ghcr.io", "acme/app", true) else {
+            panic!("pushed without credentials");
+        };
+        assert_eq!(
+            err.to_string(),
+            "docker-credential-pass has no credentials for ghcr.io. Run `docker login ghcr.io` first."
+        );
+    }
 }
@@ -0,0 +1,108 @@
+//! `allowed_registries` in `~/.unisrv/config.json`: the registries images may
+//! come from, so a platform team can keep images from anywhere else out of
+//! `instance run` and `up`.
+//!
+//! Each entry is a registry host, optionally followed by a path, and allows
+//! every repository under it: `ghcr.io/acme` allows `ghcr.io/acme/app` but
+//! not `ghcr.io/acme-forks/app`. Docker Hub images are matched by their full
+//! name, so `nginx` is `docker.io/library/nginx`. With no entries, anything
+//! goes. This is a guard against mistakes, enforced by this CLI before it
+//! sends anything; the server doesn't know about it.
+
+use std::sync::OnceLock;
+
+use anyhow::{Result, bail};
+
+use super::tags::Repository;
+
+static ALLOWED_REGISTRIES: OnceLock<Vec<String>> = OnceLock::new();
+
+/// Enforce `allowed` for the rest of the process. Only the first call counts.
+pub fn set_allowed_registries(allowed: Vec<String>) {
+    let _ = ALLOWED_REGISTRIES.set(allowed);
+}
+
+/// Refuse `images` unless each is from a registry the settings allow.
+pub fn enforce<'a>(images: impl IntoIterator<Item = &'a str>) -> Result<()> {
+    check(ALLOWED_REGISTRIES.get().map_or(&[], Vec::as_slice), images)
+}
+
+fn check<'a>(allowed: &[String], images: impl IntoIterator<Item = &'a str>) -> Result<()> {
+    if allowed.is_empty() {
+        return Ok(());
+    }
+    let mut refused = Vec::new();
+    for image in images {
+        let (repo, _) = Repository::parse_reference(image)?;
+        let full = format!("{}/{}", repo.host, repo.name);
+        if !allowed.iter().any(|entry| allows(entry, &full)) && !refused.contains(&image) {
+            refused.push(image);
+        }
+    }
+    if refused.is_empty() {
+        return Ok(());
+    }
+    bail!(
+        "policy violation: {} not from an allowed registry; allowed_registries in \
+         ~/.unisrv/config.json permits {}",
+        refused.join(", "),
+        allowed.join(", ")
+    )
+}
+
+/// Whether `entry` covers the repository `full` (`host/name`).
+fn allows(entry: &str, full: &str) -> bool {
+    let entry = entry.trim_end_matches('/');
+    let (host, path) = entry.split_once('/').unwrap_or((entry, ""));
+    let entry = match path {
+        "" => host.to_ascii_lowercase(),
+        path => format!("{}/{path}", host.to_ascii_lowercase()),
+    };
+    full.strip_prefix(&entry)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowed(entries: &[&str]) -> Vec<String> {
+        entries.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn allows_repositories_under_an_entry() {
+        let allowed = allowed(&["GHCR.io/acme/", "docker.io/library"]);
+        check(
+            &allowed,
+            [
+                "ghcr.io/acme/app:1",
+                "ghcr.io/acme/tools/migrate@sha256:abc",
+                "nginx",
+            ],
+        )
+        .unwrap();
+
+        let err = check(
+            &allowed,
+            [
+                "ghcr.io/acme-forks/app:1",
+                "ghcr.io/acme/app:1",
+                "bitnami/redis",
+            ],
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "policy violation: ghcr.io/acme-forks/app:1, bitnami/redis not from an allowed \
+             registry; allowed_registries in ~/.unisrv/config.json permits GHCR.io/acme/, \
+             docker.io/library"
+        );
+    }
+
+    #[test]
+    fn no_entries_allow_anything() {
+        check(&[], ["evil.example.com/app:1"]).unwrap();
+        check(&allowed(&["ghcr.io"]), ["ghcr.io/anyone/app"]).unwrap();
+    }
+}
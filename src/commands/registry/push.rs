@@ -0,0 +1,631 @@
+//! `unisrv registry push <local-image> <remote-ref>`: copy an image from the
+//! local Docker daemon, or from an OCI image layout directory, to a registry.
+//!
+//! Both are read as an OCI image layout — Docker hands one over through
+//! `docker save` (Docker 25 and newer) — so every blob is already named by its
+//! digest and goes up as it is, with the registry checking it on arrival.
+//! Blobs the repository already has are skipped, and the manifest is pushed
+//! last so the tag never points at an image that's only half there.
+//!
+//! It pushes with a token minted from the registry's credentials in unisrv,
+//! or with a Docker credential helper's when one is configured.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, anyhow, bail};
+#[cfg(feature = "progress")]
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use serde::Deserialize;
+use unisrv_api::distribution::ImageSink;
+use unisrv_api::{ApiClient, ApiError};
+use uuid::Uuid;
+
+use super::tags::Repository;
+use crate::commands::ui::human_bytes;
+
+/// Bytes sent per upload request.
+const CHUNK_SIZE: usize = 8 * 1024 * 1024;
+
+/// The annotation naming an image within a layout, e.g. its tag.
+const REF_NAME: &str = "org.opencontainers.image.ref.name";
+
+/// A layout's `index.json`.
+#[derive(Debug, Deserialize)]
+struct Index {
+    manifests: Vec<Descriptor>,
+}
+
+/// A reference to a blob by digest.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Descriptor {
+    media_type: String,
+    digest: String,
+    size: u64,
+    #[serde(default)]
+    annotations: BTreeMap<String, String>,
+}
+
+/// An image manifest or an index of them; only what they reference matters.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    media_type: Option<String>,
+    config: Option<Descriptor>,
+    #[serde(default)]
+    layers: Vec<Descriptor>,
+    #[serde(default)]
+    manifests: Vec<Descriptor>,
+}
+
+/// Where an OCI image layout's files are.
+enum Layout {
+    Dir(PathBuf),
+    /// A `docker save` archive, with where each file starts in it and how
+    /// long it is.
+    Tar {
+        path: PathBuf,
+        entries: HashMap<String, (u64, u64)>,
+    },
+}
+
+impl Layout {
+    fn open(&self, name: &str) -> Result<Box<dyn Read>> {
+        match self {
+            Layout::Dir(dir) => {
+                let path = dir.join(name);
+                let file = File::open(&path)
+                    .with_context(|| format!("failed to open {}", path.display()))?;
+                Ok(Box::new(file))
+            }
+            Layout::Tar { path, entries } => {
+                let &(offset, size) = entries
+                    .get(name)
+                    .ok_or_else(|| anyhow!("the saved image has no {name}"))?;
+                let mut file = File::open(path)?;
+                file.seek(SeekFrom::Start(offset))?;
+                Ok(Box::new(file.take(size)))
+            }
+        }
+    }
+
+    fn read(&self, name: &str) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.open(name)?.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn blob_name(digest: &str) -> Result<String> {
+        let (algorithm, hex) = digest
+            .split_once(':')
+            .ok_or_else(|| anyhow!("{digest:?} isn't a digest"))?;
+        Ok(format!("blobs/{algorithm}/{hex}"))
+    }
+
+    fn index(&self) -> Result<Index> {
+        if let Layout::Tar { entries, .. } = self
+            && !entries.contains_key("index.json")
+        {
+            bail!(
+                "docker saved the image in its older format, without an OCI index; \
+                 push needs Docker 25 or newer, or an OCI layout directory"
+            );
+        }
+        serde_json::from_slice(&self.read("index.json")?)
+            .context("the image layout's index.json is malformed")
+    }
+}
+
+/// Where each regular file in a tar archive starts, and how long it is.
+fn tar_entries(archive: &mut (impl Read + Seek)) -> Result<HashMap<String, (u64, u64)>> {
+    let mut entries = HashMap::new();
+    let mut header = [0u8; 512];
+    let mut offset = 0;
+    loop {
+        archive
+            .read_exact(&mut header)
+            .context("the saved image archive ends early")?;
+        // The archive ends with zeroed blocks.
+        if header.iter().all(|&b| b == 0) {
+            return Ok(entries);
+        }
+        let field = |bytes: &[u8]| {
+            let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+            String::from_utf8_lossy(&bytes[..end]).trim().to_string()
+        };
+        let size = u64::from_str_radix(&field(&header[124..136]), 8)
+            .context("the saved image isn't a tar archive")?;
+        let mut name = field(&header[..100]);
+        let prefix = field(&header[345..500]);
+        if header[257..262] == *b"ustar" && !prefix.is_empty() {
+            name = format!("{prefix}/{name}");
+        }
+        offset += 512;
+        if matches!(header[156], b'0' | 0) {
+            let name = name.trim_start_matches("./").to_string();
+            entries.insert(name, (offset, size));
+        }
+        offset += size.div_ceil(512) * 512;
+        archive.seek(SeekFrom::Start(offset))?;
+    }
+}
+
+/// An archive `docker save` wrote, removed when dropped.
+struct Saved(PathBuf);
+
+impl Drop for Saved {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn docker_save(image: &str) -> Result<Saved> {
+    let saved = Saved(std::env::temp_dir().join(format!("unisrv-push-{}.tar", Uuid::new_v4())));
+    let output = Command::new("docker")
+        .args(["save", "-o"])
+        .arg(&saved.0)
+        .arg(image)
+        .stdin(Stdio::null())
+        .output()
+        .context("failed to run docker; is it installed?")?;
+    if !output.status.success() {
+        bail!(
+            "docker save {image} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(saved)
+}
+
+/// `local` as an OCI layout directory, with the image asked for by a
+/// `<dir>:<ref>` suffix; `None` when it isn't a directory.
+fn layout_dir(local: &str) -> Option<(PathBuf, Option<String>)> {
+    if Path::new(local).is_dir() {
+        return Some((local.into(), None));
+    }
+    let (dir, wanted) = local.rsplit_once(':')?;
+    Path::new(dir)
+        .is_dir()
+        .then(|| (dir.into(), Some(wanted.to_string())))
+}
+
+/// The image in `index` to push: the one named `wanted`, or the only one.
+fn pick(index: Index, wanted: Option<&str>) -> Result<Descriptor> {
+    let mut manifests = index.manifests;
+    if let Some(wanted) = wanted {
+        return manifests
+            .into_iter()
+            .find(|d| d.annotations.get(REF_NAME).is_some_and(|r| r == wanted))
+            .ok_or_else(|| anyhow!("the image layout has no image named {wanted:?}"));
+    }
+    match manifests.len() {
+        0 => bail!("the image layout has no images"),
+        1 => Ok(manifests.remove(0)),
+        n => {
+            let names: Vec<_> = manifests
+                .iter()
+                .filter_map(|d| d.annotations.get(REF_NAME).map(String::as_str))
+                .collect();
+            bail!(
+                "the image layout holds {n} images; pick one with <dir>:<ref> (named: {})",
+                names.join(", ")
+            )
+        }
+    }
+}
+
+/// Per-blob transfer feedback.
+trait Bars {
+    fn blob(&self, digest: &str, size: u64) -> Box<dyn Bar>;
+}
+
+trait Bar {
+    fn advance(&self, bytes: u64);
+    fn finish(&self, outcome: &str);
+}
+
+/// A progress bar per blob on a terminal; a line per finished blob otherwise,
+/// with `--no-spinner` or `--no-color`, and always without the `progress`
+/// feature.
+struct TerminalBars {
+    #[cfg(feature = "progress")]
+    multi: Option<MultiProgress>,
+}
+
+impl TerminalBars {
+    fn new() -> Self {
+        Self {
+            #[cfg(feature = "progress")]
+            multi: (console::user_attended_stderr() && !crate::progress::narrated())
+                .then(MultiProgress::new),
+        }
+    }
+}
+
+impl Bars for TerminalBars {
+    fn blob(&self, digest: &str, size: u64) -> Box<dyn Bar> {
+        let label = short(digest).to_string();
+        #[cfg(feature = "progress")]
+        if let Some(multi) = &self.multi {
+            let bar = multi.add(ProgressBar::new(size));
+            bar.set_style(
+                ProgressStyle::with_template("{prefix} [{bar:30}] {bytes}/{total_bytes} {msg}")
+                    .expect("progress template is valid")
+                    .progress_chars("=> "),
+            );
+            bar.set_prefix(label);
+            return Box::new(LiveBar(bar));
+        }
+        Box::new(LineBar { label, size })
+    }
+}
+
+#[cfg(feature = "progress")]
+struct LiveBar(ProgressBar);
+
+#[cfg(feature = "progress")]
+impl Bar for LiveBar {
+    fn advance(&self, bytes: u64) {
+        self.0.inc(bytes);
+    }
+
+    fn finish(&self, outcome: &str) {
+        self.0.set_position(self.0.length().unwrap_or_default());
+        self.0.finish_with_message(outcome.to_string());
+    }
+}
+
+struct LineBar {
+    label: String,
+    size: u64,
+}
+
+impl Bar for LineBar {
+    fn advance(&self, _bytes: u64) {}
+
+    fn finish(&self, outcome: &str) {
+        eprintln!("{} {outcome} ({})", self.label, human_bytes(self.size));
+    }
+}
+
+/// `sha256:0123456789ab…` → `0123456789ab`, as Docker shows layers.
+fn short(digest: &str) -> &str {
+    let hex = digest.split_once(':').map_or(digest, |(_, hex)| hex);
+    &hex[..hex.len().min(12)]
+}
+
+/// Fill `buf` from `reader` as far as it goes; the count read, 0 at the end.
+fn read_full(reader: &mut dyn Read, buf: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+struct Pusher<'a> {
+    layout: &'a Layout,
+    sink: &'a dyn ImageSink,
+    bars: &'a dyn Bars,
+    /// Blobs already sent, so layers shared between an index's images go up
+    /// once.
+    sent: HashSet<String>,
+}
+
+impl Pusher<'_> {
+    /// Push everything `manifest` references, then the manifest itself as
+    /// `reference`.
+    async fn push_manifest(&mut self, manifest: &Descriptor, reference: &str) -> Result<()> {
+        let body = self.layout.read(&Layout::blob_name(&manifest.digest)?)?;
+        let parsed: Manifest = serde_json::from_slice(&body)
+            .with_context(|| format!("manifest {} is malformed", manifest.digest))?;
+        for child in &parsed.manifests {
+            Box::pin(self.push_manifest(child, &child.digest)).await?;
+        }
+        for blob in parsed.config.iter().chain(&parsed.layers) {
+            self.push_blob(blob)
+                .await
+                .with_context(|| format!("failed to push blob {}", blob.digest))?;
+        }
+        let media_type = parsed.media_type.as_deref().unwrap_or(&manifest.media_type);
+        self.sink
+            .put_manifest(reference, media_type, body)
+            .await
+            .with_context(|| format!("failed to push manifest {}", manifest.digest))?;
+        Ok(())
+    }
+
+    async fn push_blob(&mut self, blob: &Descriptor) -> Result<()> {
+        if !self.sent.insert(blob.digest.clone()) {
+            return Ok(());
+        }
+        let bar = self.bars.blob(&blob.digest, blob.size);
+        if self.sink.blob_exists(&blob.digest).await? {
+            bar.finish("already exists");
+            return Ok(());
+        }
+        let mut reader = self.layout.open(&Layout::blob_name(&blob.digest)?)?;
+        let mut location = self.sink.start_upload().await?;
+        let mut chunk = vec![0; CHUNK_SIZE];
+        let mut offset = 0;
+        loop {
+            let n = read_full(&mut reader, &mut chunk)?;
+            if n == 0 {
+                break;
+            }
+            location = self
+                .sink
+                .upload_chunk(&location, offset, chunk[..n].to_vec())
+                .await?;
+            offset += n as u64;
+            bar.advance(n as u64);
+        }
+        if offset != blob.size {
+            bail!(
+                "the layout has {offset} bytes of it but its manifest says {}",
+                blob.size
+            );
+        }
+        self.sink.finish_upload(&location, &blob.digest).await?;
+        bar.finish("pushed");
+        Ok(())
+    }
+}
+
+pub async fn push(client: &dyn ApiClient, local: &str, remote: &str) -> Result<()> {
+    if remote.contains('@') {
+        bail!("push to a tag, not a digest: {remote}");
+    }
+    let (repo, tag) = Repository::parse_reference(remote)?;
+    let tag = tag.unwrap_or_else(|| "latest".to_string());
+    // The registry is written to directly, not through `client`, so its
+    // read-only guard would never see the upload.
+    if client.is_read_only() {
+        return Err(ApiError::ReadOnly {
+            method: "PUT".into(),
+            path: format!("/v2/{}/manifests/{tag}", repo.name),
+        }
+        .into());
+    }
+    let sink = super::distribution_client(client, &repo.host, &repo.name, true).await?;
+    sink.authorize(true)
+        .await
+        .with_context(|| format!("failed to authenticate to {}", repo.host))?;
+
+    let (layout, wanted, _saved) = match layout_dir(local) {
+        Some((dir, wanted)) => (Layout::Dir(dir), wanted, None),
+        None => {
+            let saved = docker_save(local)?;
+            let entries = tar_entries(&mut File::open(&saved.0)?)?;
+            let layout = Layout::Tar {
+                path: saved.0.clone(),
+                entries,
+            };
+            (layout, None, Some(saved))
+        }
+    };
+    let image = pick(layout.index()?, wanted.as_deref())?;
+
+    let mut pusher = Pusher {
+        layout: &layout,
+        sink: &sink,
+        bars: &TerminalBars::new(),
+        sent: HashSet::new(),
+    };
+    pusher.push_manifest(&image, &tag).await?;
+    println!(
+        "\u{2713} Pushed {}/{}:{tag} ({})",
+        repo.host, repo.name, image.digest
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use serde_json::json;
+    use std::io::Cursor;
+    use std::sync::Mutex;
+
+    struct NoBars;
+
+    impl Bars for NoBars {
+        fn blob(&self, _digest: &str, _size: u64) -> Box<dyn Bar> {
+            Box::new(NoBars)
+        }
+    }
+
+    impl Bar for NoBars {
+        fn advance(&self, _bytes: u64) {}
+        fn finish(&self, _outcome: &str) {}
+    }
+
+    /// Records what was sent; `existing` blobs are already there.
+    #[derive(Default)]
+    struct Recorder {
+        existing: Vec<String>,
+        sent: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl ImageSink for Recorder {
+        async fn blob_exists(&self, digest: &str) -> unisrv_api::Result<bool> {
+            Ok(self.existing.iter().any(|d| d == digest))
+        }
+
+        async fn start_upload(&self) -> unisrv_api::Result<String> {
+            Ok("/upload".into())
+        }
+
+        async fn upload_chunk(
+            &self,
+            location: &str,
+            offset: u64,
+            chunk: Vec<u8>,
+        ) -> unisrv_api::Result<String> {
+            let chunk = String::from_utf8(chunk).unwrap();
+            self.sent
+                .lock()
+                .unwrap()
+                .push(format!("chunk {location} {offset} {chunk}"));
+            Ok(format!("{location}+"))
+        }
+
+        async fn finish_upload(&self, location: &str, digest: &str) -> unisrv_api::Result<()> {
+            self.sent
+                .lock()
+                .unwrap()
+                .push(format!("finish {location} {digest}"));
+            Ok(())
+        }
+
+        async fn put_manifest(
+            &self,
+            reference: &str,
+            media_type: &str,
+            _body: Vec<u8>,
+        ) -> unisrv_api::Result<()> {
+            self.sent
+                .lock()
+                .unwrap()
+                .push(format!("manifest {reference} {media_type}"));
+            Ok(())
+        }
+    }
+
+    fn write_blob(dir: &Path, digest: &str, body: &[u8]) {
+        let path = dir.join(Layout::blob_name(digest).unwrap());
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, body).unwrap();
+    }
+
+    fn descriptor(media_type: &str, digest: &str, size: usize) -> serde_json::Value {
+        json!({"mediaType": media_type, "digest": digest, "size": size})
+    }
+
+    #[tokio::test]
+    async fn read_only_refuses_before_asking_for_a_push_token() {
+        let client = unisrv_api::test_support::MockApiClient::logged_in().read_only();
+        let err = push(&client, "missing.tar", "registry.example.com/shop/web:v1")
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                err.downcast_ref::<ApiError>(),
+                Some(ApiError::ReadOnly { method, path })
+                    if method == "PUT" && path == "/v2/shop/web/manifests/v1"
+            ),
+            "{err:#}"
+        );
+        assert!(client.calls.lock().unwrap().call_order.is_empty());
+    }
+
+    #[tokio::test]
+    async fn pushes_missing_blobs_then_the_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        write_blob(dir.path(), "sha256:c0", b"{}");
+        write_blob(dir.path(), "sha256:l1", b"base");
+        write_blob(dir.path(), "sha256:l2", b"app");
+        let manifest = json!({
+            "schemaVersion": 2,
+            "mediaType": "application/vnd.oci.image.manifest.v1+json",
+            "config": descriptor("application/vnd.oci.image.config.v1+json", "sha256:c0", 2),
+            "layers": [
+                descriptor("application/vnd.oci.image.layer.v1.tar", "sha256:l1", 4),
+                descriptor("application/vnd.oci.image.layer.v1.tar", "sha256:l2", 3),
+            ],
+        });
+        write_blob(dir.path(), "sha256:m0", manifest.to_string().as_bytes());
+        std::fs::write(
+            dir.path().join("index.json"),
+            json!({"manifests": [descriptor("application/vnd.oci.image.manifest.v1+json", "sha256:m0", 0)]})
+                .to_string(),
+        )
+        .unwrap();
+
+        let layout = Layout::Dir(dir.path().into());
+        let image = pick(layout.index().unwrap(), None).unwrap();
+        let sink = Recorder {
+            existing: vec!["sha256:l1".into()],
+            ..Default::default()
+        };
+        let mut pusher = Pusher {
+            layout: &layout,
+            sink: &sink,
+            bars: &NoBars,
+            sent: HashSet::new(),
+        };
+        pusher.push_manifest(&image, "1.0").await.unwrap();
+
+        assert_eq!(
+            *sink.sent.lock().unwrap(),
+            [
+                "chunk /upload 0 {}",
+                "finish /upload+ sha256:c0",
+                "chunk /upload 0 app",
+                "finish /upload+ sha256:l2",
+                "manifest 1.0 application/vnd.oci.image.manifest.v1+json",
+            ]
+        );
+    }
+
+    #[test]
+    fn picks_an_image_by_ref_name_when_the_layout_has_several() {
+        let index = |names: &[&str]| Index {
+            manifests: names
+                .iter()
+                .map(|name| Descriptor {
+                    media_type: "application/vnd.oci.image.manifest.v1+json".into(),
+                    digest: format!("sha256:{name}"),
+                    size: 0,
+                    annotations: BTreeMap::from([(REF_NAME.to_string(), name.to_string())]),
+                })
+                .collect(),
+        };
+        assert_eq!(
+            pick(index(&["a", "b"]), Some("b")).unwrap().digest,
+            "sha256:b"
+        );
+        let err = pick(index(&["a", "b"]), None).unwrap_err().to_string();
+        assert!(err.contains("<dir>:<ref> (named: a, b)"), "{err}");
+        assert_eq!(pick(index(&["a"]), None).unwrap().digest, "sha256:a");
+    }
+
+    fn tar_header(name: &str, size: usize, kind: u8) -> Vec<u8> {
+        let mut header = vec![0u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        let size = format!("{size:011o}");
+        header[124..135].copy_from_slice(size.as_bytes());
+        header[156] = kind;
+        header[257..262].copy_from_slice(b"ustar");
+        header
+    }
+
+    #[test]
+    fn indexes_the_files_in_a_tar_archive() {
+        let mut archive = tar_header("blobs/sha256/", 0, b'5');
+        archive.extend(tar_header("index.json", 2, b'0'));
+        archive.extend(b"{}");
+        archive.resize(archive.len() + 510, 0);
+        archive.extend(tar_header("blobs/sha256/ab", 600, b'0'));
+        archive.extend(vec![b'x'; 1024]);
+        archive.extend(vec![0u8; 1024]);
+
+        let entries = tar_entries(&mut Cursor::new(archive)).unwrap();
+        assert_eq!(
+            entries,
+            HashMap::from([
+                ("index.json".to_string(), (1024, 2)),
+                ("blobs/sha256/ab".to_string(), (2048, 600)),
+            ])
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! `unisrv registry tags <image>`: the tags a repository has, newest version
+//! first, read straight from the registry.
+//!
+//! A registry with stored credentials is read with a pull token the server
+//! mints from them, or with a Docker credential helper's when one is
+//! configured; any other is read anonymously, which works for public
+//! repositories.
+
+use std::cmp::Ordering;
+
+use anyhow::{Context, Result, bail};
+use unisrv_api::ApiClient;
+use unisrv_api::distribution::TagSource;
+
+/// Stop following `next` links past this many pages, in case a registry
+/// keeps handing out the same one.
+const MAX_PAGES: usize = 100;
+
+/// Where an image reference's repository lives.
+#[derive(Debug, PartialEq)]
+pub struct Repository {
+    pub host: String,
+    pub name: String,
+}
+
+impl Repository {
+    /// `ghcr.io/acme/app:1.2` → `ghcr.io` + `acme/app`; a reference with no
+    /// registry host is on Docker Hub, where bare names are under `library/`.
+    pub fn parse(image: &str) -> Result<Self> {
+        Self::parse_reference(image).map(|(repo, _tag)| repo)
+    }
+
+    /// [`Repository::parse`], also returning the reference's tag if it has one.
+    pub fn parse_reference(image: &str) -> Result<(Self, Option<String>)> {
+        let image = image.split('@').next().unwrap_or_default();
+        let (host, path) = match image.split_once('/') {
+            Some((first, rest))
+                if first.contains('.') || first.contains(':') || first == "localhost" =>
+            {
+                (first.to_ascii_lowercase(), rest)
+            }
+            _ => ("docker.io".to_string(), image),
+        };
+        // A tag is a `:` in the last path segment; one earlier is a port.
+        let (path, tag) = match path.rsplit_once(':') {
+            Some((name, tag)) if !tag.contains('/') => (name, Some(tag.to_string())),
+            _ => (path, None),
+        };
+        if path.is_empty() || path.split('/').any(str::is_empty) {
+            bail!("{image:?} isn't an image reference like ghcr.io/acme/app or nginx");
+        }
+        let name = if host == "docker.io" && !path.contains('/') {
+            format!("library/{path}")
+        } else {
+            path.to_string()
+        };
+        Ok((Repository { host, name }, tag))
+    }
+
+    /// [`Repository::parse`], also returning what to ask the registry's
+    /// manifest endpoint for: the reference's digest if it has one, else its
+    /// tag, else `latest`.
+    pub fn parse_manifest_reference(image: &str) -> Result<(Self, String)> {
+        let (repo, tag) = Self::parse_reference(image)?;
+        let reference = match image.split_once('@') {
+            Some((_, digest)) => digest.to_string(),
+            None => tag.unwrap_or_else(|| "latest".into()),
+        };
+        Ok((repo, reference))
+    }
+}
+
+/// A tag's version, when it reads as one: `v1.2.3`, `1.2`, `2.0.0-rc.1`.
+#[derive(Debug, PartialEq, Eq)]
+struct Version<'a> {
+    numbers: Vec<u64>,
+    pre: Option<&'a str>,
+}
+
+impl<'a> Version<'a> {
+    fn parse(tag: &'a str) -> Option<Self> {
+        let tag = tag.strip_prefix('v').unwrap_or(tag);
+        let (core, pre) = match tag.split_once('-') {
+            Some((core, pre)) => (core, Some(pre)),
+            None => (tag, None),
+        };
+        let numbers = core
+            .split('.')
+            .map(|n| n.parse().ok())
+            .collect::<Option<Vec<u64>>>()?;
+        Some(Version { numbers, pre })
+    }
+}
+
+impl Ord for Version<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let len = self.numbers.len().max(other.numbers.len());
+        let number = |v: &Version, i: usize| v.numbers.get(i).copied().unwrap_or(0);
+        (0..len)
+            .map(|i| number(self, i).cmp(&number(other, i)))
+            .find(|o| o.is_ne())
+            .unwrap_or(Ordering::Equal)
+            // A release sorts after its pre-releases.
+            .then_with(|| match (self.pre, other.pre) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => a.cmp(b),
+            })
+    }
+}
+
+impl PartialOrd for Version<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Versions newest first, then every other tag (`latest`, `main`, …) by name.
+fn sort_tags(tags: &mut [String]) {
+    tags.sort_by(|a, b| match (Version::parse(a), Version::parse(b)) {
+        (Some(va), Some(vb)) => vb.cmp(&va).then_with(|| a.cmp(b)),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.cmp(b),
+    });
+}
+
+/// Every tag `source` has, across pages.
+async fn collect_tags(source: &dyn TagSource) -> Result<Vec<String>> {
+    let mut tags = Vec::new();
+    let mut next = None;
+    for _ in 0..MAX_PAGES {
+        let page = source.tags_page(next.as_deref()).await?;
+        tags.extend(page.tags);
+        match page.next {
+            Some(link) => next = Some(link),
+            None => return Ok(tags),
+        }
+    }
+    bail!("the registry was still paging after {MAX_PAGES} pages of tags")
+}
+
+pub async fn tags(client: &dyn ApiClient, image: &str, json: bool) -> Result<()> {
+    let repo = Repository::parse(image)?;
+    let source = super::distribution_client(client, &repo.host, &repo.name, false).await?;
+    let mut tags = collect_tags(&source)
+        .await
+        .with_context(|| format!("failed to list tags for {}/{}", repo.host, repo.name))?;
+    sort_tags(&mut tags);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&tags)?);
+    } else if tags.is_empty() {
+        eprintln!("{}/{} has no tags.", repo.host, repo.name);
+    } else {
+        for tag in tags {
+            println!("{tag}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+    use unisrv_api::distribution::TagPage;
+
+    #[test]
+    fn parses_image_references() {
+        let parse = |image| {
+            let repo = Repository::parse(image).unwrap();
+            (repo.host, repo.name)
+        };
+        assert_eq!(parse("nginx"), ("docker.io".into(), "library/nginx".into()));
+        assert_eq!(
+            parse("grafana/grafana:11.0"),
+            ("docker.io".into(), "grafana/grafana".into())
+        );
+        assert_eq!(
+            parse("ghcr.io/acme/app:1.2@sha256:abc"),
+            ("ghcr.io".into(), "acme/app".into())
+        );
+        assert_eq!(
+            parse("localhost:5000/app"),
+            ("localhost:5000".into(), "app".into())
+        );
+        assert!(Repository::parse("ghcr.io/").is_err());
+    }
+
+    #[test]
+    fn sorts_versions_newest_first_then_other_tags() {
+        let mut tags: Vec<String> = [
+            "latest",
+            "1.9.0",
+            "v1.10.0",
+            "1.10.0-rc.1",
+            "main",
+            "1.2",
+            "1.10.0",
+        ]
+        .map(String::from)
+        .to_vec();
+        sort_tags(&mut tags);
+        assert_eq!(
+            tags,
+            [
+                "1.10.0",
+                "v1.10.0",
+                "1.10.0-rc.1",
+                "1.9.0",
+                "1.2",
+                "latest",
+                "main"
+            ]
+        );
+    }
+
+    struct Pages(Mutex<Vec<TagPage>>);
+
+    #[async_trait]
+    impl TagSource for Pages {
+        async fn tags_page(&self, _next: Option<&str>) -> unisrv_api::Result<TagPage> {
+            Ok(self.0.lock().unwrap().remove(0))
+        }
+    }
+
+    #[tokio::test]
+    async fn collects_every_page() {
+        let pages = Pages(Mutex::new(vec![
+            TagPage {
+                tags: vec!["a".into()],
+                next: Some("/v2/app/tags/list?last=a".into()),
+            },
+            TagPage {
+                tags: vec!["b".into()],
+                next: None,
+            },
+        ]));
+        assert_eq!(collect_tags(&pages).await.unwrap(), ["a", "b"]);
+    }
+}
@@ -0,0 +1,243 @@
+//! `unisrv report --since 30d` — the account's activity per day: instances
+//! created and stopped, deployment rollouts and certificates issued.
+//!
+//! Counted from the audit log (`GET /events`), so it covers everything done
+//! in the account, whether with this CLI, `unisrv up` in CI or the dashboard.
+//! Days are UTC and run from the one `--since` falls on to today, including
+//! days with nothing on them, so the rows line up in a spreadsheet. `-o csv`
+//! is for exactly that.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use comfy_table::{Attribute, Cell, CellAlignment};
+use serde::Serialize;
+use unisrv_api::ApiClient;
+use unisrv_api::models::ActivityEvent;
+
+use crate::commands::locale::{self, Locale};
+use crate::commands::ui::new_table;
+
+/// `-o`: how the report is printed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Table,
+    Csv,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "table" => Ok(Format::Table),
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("unknown format {s:?}: expected table, csv or json")),
+        }
+    }
+}
+
+/// One day's activity.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct Day {
+    pub date: NaiveDate,
+    pub instances_created: u64,
+    pub instances_stopped: u64,
+    pub rollouts: u64,
+    pub certificates_issued: u64,
+}
+
+impl Day {
+    /// Count `event` if it's one of the kinds reported on.
+    fn count(&mut self, event: &ActivityEvent) {
+        match event.kind.as_str() {
+            "instance.created" => self.instances_created += 1,
+            "instance.stopped" => self.instances_stopped += 1,
+            "deployment.rolled_out" => self.rollouts += 1,
+            "certificate.issued" => self.certificates_issued += 1,
+            _ => {}
+        }
+    }
+}
+
+/// `unisrv report`.
+pub async fn report(client: &dyn ApiClient, since: Duration, format: Format) -> Result<()> {
+    let now = Utc::now();
+    let start = now - chrono::Duration::from_std(since).context("--since is too long")?;
+    let events = fetch(client, start).await?;
+    let days = tally(&events, start.date_naive(), now.date_naive());
+    match format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(&days)?),
+        Format::Csv => print!("{}", to_csv(&days)),
+        Format::Table => println!("{}", render_table(&days, locale::current())),
+    }
+    Ok(())
+}
+
+/// Every event since `since`, following the pages.
+async fn fetch(client: &dyn ApiClient, since: DateTime<Utc>) -> Result<Vec<ActivityEvent>> {
+    let mut events = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = client
+            .list_events(since, cursor.as_deref())
+            .await
+            .context("failed to read the activity log")?;
+        events.extend(page.events);
+        match page.next_cursor {
+            Some(next) if cursor.as_ref() != Some(&next) => cursor = Some(next),
+            _ => return Ok(events),
+        }
+    }
+}
+
+/// One row per day from `first` to `last`, with `events` counted on the day
+/// they happened.
+fn tally(events: &[ActivityEvent], first: NaiveDate, last: NaiveDate) -> Vec<Day> {
+    let mut days: BTreeMap<NaiveDate, Day> = first
+        .iter_days()
+        .take_while(|date| *date <= last)
+        .map(|date| {
+            (
+                date,
+                Day {
+                    date,
+                    ..Day::default()
+                },
+            )
+        })
+        .collect();
+    for event in events {
+        if let Some(day) = days.get_mut(&event.at.date_naive()) {
+            day.count(event);
+        }
+    }
+    days.into_values().collect()
+}
+
+fn to_csv(days: &[Day]) -> String {
+    let mut out =
+        String::from("date,instances_created,instances_stopped,rollouts,certificates_issued\n");
+    for day in days {
+        out.push_str(&format!(
+            "{},{},{},{},{}\n",
+            day.date,
+            day.instances_created,
+            day.instances_stopped,
+            day.rollouts,
+            day.certificates_issued
+        ));
+    }
+    out
+}
+
+fn render_table(days: &[Day], locale: &Locale) -> String {
+    let mut table = new_table();
+    table.set_header(vec![
+        Cell::new("DATE").add_attribute(Attribute::Bold),
+        Cell::new("CREATED").add_attribute(Attribute::Bold),
+        Cell::new("STOPPED").add_attribute(Attribute::Bold),
+        Cell::new("ROLLOUTS").add_attribute(Attribute::Bold),
+        Cell::new("CERTS ISSUED").add_attribute(Attribute::Bold),
+    ]);
+    let number = |n: u64| Cell::new(locale.format_int(n)).set_alignment(CellAlignment::Right);
+    let mut total = Day::default();
+    for day in days {
+        table.add_row(vec![
+            Cell::new(day.date),
+            number(day.instances_created),
+            number(day.instances_stopped),
+            number(day.rollouts),
+            number(day.certificates_issued),
+        ]);
+        total.instances_created += day.instances_created;
+        total.instances_stopped += day.instances_stopped;
+        total.rollouts += day.rollouts;
+        total.certificates_issued += day.certificates_issued;
+    }
+    table.add_row(vec![
+        Cell::new("total").add_attribute(Attribute::Bold),
+        number(total.instances_created),
+        number(total.instances_stopped),
+        number(total.rollouts),
+        number(total.certificates_issued),
+    ]);
+    table.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use unisrv_api::models::EventListResponse;
+    use unisrv_api::test_support::MockApiClient;
+    use uuid::Uuid;
+
+    fn event(kind: &str, at: &str) -> ActivityEvent {
+        ActivityEvent {
+            id: Uuid::new_v4(),
+            kind: kind.into(),
+            at: at.parse().unwrap(),
+            environment_id: None,
+            resource_id: None,
+        }
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn every_day_gets_a_row_and_each_event_its_own_day() {
+        let events = [
+            event("instance.created", "2026-03-01T09:00:00Z"),
+            event("instance.created", "2026-03-01T23:59:59Z"),
+            event("deployment.rolled_out", "2026-03-03T00:00:00Z"),
+            event("certificate.issued", "2026-03-03T12:00:00Z"),
+            event("environment.created", "2026-03-03T12:00:00Z"),
+        ];
+        let days = tally(&events, date("2026-03-01"), date("2026-03-03"));
+        assert_eq!(days.len(), 3);
+        assert_eq!(days[0].instances_created, 2);
+        assert_eq!(
+            days[1],
+            Day {
+                date: date("2026-03-02"),
+                ..Day::default()
+            }
+        );
+        assert_eq!((days[2].rollouts, days[2].certificates_issued), (1, 1));
+
+        assert_eq!(
+            to_csv(&days),
+            "date,instances_created,instances_stopped,rollouts,certificates_issued\n\
+             2026-03-01,2,0,0,0\n\
+             2026-03-02,0,0,0,0\n\
+             2026-03-03,0,0,1,1\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_follows_the_pages() {
+        let since: DateTime<Utc> = "2026-03-01T00:00:00Z".parse().unwrap();
+        let mock = MockApiClient::logged_in()
+            .push_list_events(Ok(EventListResponse {
+                events: vec![event("instance.created", "2026-03-01T09:00:00Z")],
+                next_cursor: Some("p2".into()),
+            }))
+            .push_list_events(Ok(EventListResponse {
+                events: vec![event("instance.stopped", "2026-03-02T09:00:00Z")],
+                next_cursor: None,
+            }));
+        let events = fetch(&mock, since).await.unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            mock.calls.lock().unwrap().list_events_calls,
+            [(since, None), (since, Some("p2".to_string()))]
+        );
+    }
+}
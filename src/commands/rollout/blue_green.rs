@@ -0,0 +1,897 @@
+//! `rollout deploy --strategy blue-green`, `rollout promote <service>` and
+//! `rollout abort <service>`: stand a deployment's new generation up next to
+//! the live one, check it through a preview URL, then move the service over
+//! to it in one go.
+//!
+//! Deploying starts as many instances on the new image as the deployment has
+//! replicas, named `<deployment>-green-N`, and registers them with the
+//! deployment's service in a target group of their own, `<group>-green`. No
+//! live traffic reaches them: the service gains one location,
+//! `/_preview/<deployment>`, routed to that group with its prefix stripped, so
+//! `https://<service host>/_preview/<deployment>/` is the new generation. The
+//! rollout is then pending, noted in the state directory (see
+//! [`crate::state`]) under the service's name.
+//!
+//! Promoting points every location served by the deployment's group at the
+//! green group in a single service update, which is the cutover, and drops the
+//! preview location. The deployment itself is then updated to the new image,
+//! configured as the previewed instances were, account defaults included —
+//! the green instances carry the traffic while its replicas are replaced — and
+//! once they're running the locations are pointed back at its group and the
+//! green instances are retired. If the deployment's new replicas don't come
+//! up, it's rolled back to its previous image before traffic moves back.
+//! Aborting routes everything to the deployment's group, removes the preview
+//! location and retires the green instances, at any point.
+//!
+//! Pending state is only on this machine. If it's lost, the green instances
+//! and the preview location are left for `instance stop` and `service
+//! location` by hand.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::Utc;
+use console::style;
+use unisrv_api::ApiClient;
+use unisrv_api::models::{
+    DeploymentDetailResponse, HTTPLocation, HTTPLocationTarget, HTTPServiceConfig,
+    ServiceInstanceTarget,
+};
+use uuid::Uuid;
+
+use super::canary::instance_request;
+use super::{record, redeploy};
+use crate::commands::confirm::Guard;
+use crate::commands::instance::list::is_active;
+use crate::commands::instance::run::{announce_environment, current_environment};
+use crate::commands::up::apply::{Poll, PollOutcome, RealWaiter, Waiter, poll_until};
+use crate::commands::up::plan::ResolvedEnvironment;
+use crate::commands::up::ready::format_duration;
+use crate::progress::{Icon, Progress, SpinnerProgress, Tone};
+use crate::state::{PendingRollout, StateDir};
+
+/// Where a pending rollout's preview location lives, under the service.
+const PREVIEW_PREFIX: &str = "/_preview";
+/// How often the new generation is checked while it starts.
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Stage `deployment`'s new generation on `image` and note it as pending,
+/// printing where to preview it.
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn stage_in(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    state: &StateDir,
+    deployment: DeploymentDetailResponse,
+    image: &str,
+    timeout: Duration,
+    waiter: &dyn Waiter,
+    progress: &dyn Progress,
+) -> Result<()> {
+    let name = deployment.name.clone();
+    let service_id = deployment.service_id.ok_or_else(|| {
+        anyhow!(
+            "{name} isn't behind a service, so there's no traffic to switch; \
+             roll it out without --strategy blue-green"
+        )
+    })?;
+    let port = deployment
+        .configuration
+        .instance_port
+        .ok_or_else(|| anyhow!("{name} has no instance port for the new generation to serve on"))?;
+    let service = client
+        .get_service(env.id, service_id)
+        .await
+        .context("failed to fetch the deployment's service")?;
+    if let Some(pending) = state.pending_rollout(&env.project, &env.name, &service.name) {
+        bail!(
+            "a blue/green rollout of {} to {} is already pending on {}; \
+             `unisrv rollout promote {}` or `unisrv rollout abort {}` it first",
+            pending.deployment,
+            pending.image,
+            service.name,
+            service.name,
+            service.name
+        );
+    }
+    let group = deployment
+        .service_target_group
+        .clone()
+        .unwrap_or_else(|| "default".to_string());
+    let mut pending = PendingRollout {
+        at: Utc::now(),
+        deployment: name.clone(),
+        deployment_id: deployment.id,
+        service_id,
+        image: image.to_string(),
+        preview_group: format!("{group}-green"),
+        group,
+        preview_path: format!("{PREVIEW_PREFIX}/{name}"),
+        instances: Vec::new(),
+        targets: Vec::new(),
+        configuration: Some(deployment.configuration.clone()),
+    };
+
+    if let Err(e) = bring_up(
+        client,
+        env,
+        &deployment,
+        port,
+        &mut pending,
+        timeout,
+        waiter,
+        progress,
+    )
+    .await
+    {
+        retire(client, env.id, &pending, progress).await;
+        return Err(e.context(format!("blue/green rollout of {name} aborted")));
+    }
+    state.save_pending_rollout(&env.project, &env.name, &service.name, &pending)?;
+
+    println!(
+        "{} Staged {image} next to {name}: {} instance(s) in target group {}, serving no live traffic.",
+        style("✓").green().bold(),
+        pending.instances.len(),
+        pending.preview_group
+    );
+    println!(
+        "Preview it at https://{}{}/",
+        service.base_host, pending.preview_path
+    );
+    println!(
+        "{}",
+        style(format!(
+            "Switch {0}'s traffic over with `unisrv rollout promote {0}`, or undo this with `unisrv rollout abort {0}`.",
+            service.name
+        ))
+        .dim()
+    );
+    Ok(())
+}
+
+/// Start the new generation, wait for it, register it with the service in
+/// its own group and open the preview location. What's been started is noted
+/// in `pending` as it goes, so a failure can be cleaned up.
+#[allow(clippy::too_many_arguments)]
+async fn bring_up(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    deployment: &DeploymentDetailResponse,
+    port: u16,
+    pending: &mut PendingRollout,
+    timeout: Duration,
+    waiter: &dyn Waiter,
+    progress: &dyn Progress,
+) -> Result<()> {
+    let name = &deployment.name;
+    let replicas = deployment.configuration.replicas.max(1);
+    let step = progress.step(
+        Icon::Deployment,
+        &format!(
+            "Starting {replicas} {name} instance(s) on {}",
+            pending.image
+        ),
+    );
+    for n in 1..=replicas {
+        let request = instance_request(deployment, &pending.image, format!("{name}-green-{n}"));
+        let created = client
+            .provision_instance(env.id, request)
+            .await
+            .context("failed to start the new generation")?;
+        pending.instances.push(created.id);
+    }
+
+    let max_rounds = timeout.as_secs().div_ceil(CHECK_INTERVAL.as_secs()) as usize + 1;
+    let started = poll_until(waiter, CHECK_INTERVAL, max_rounds, &step, async || {
+        let instances = client.list_instances(env.id).await?.instances;
+        let mut running = 0;
+        for id in &pending.instances {
+            let state = instances
+                .iter()
+                .find(|i| i.id == *id)
+                .map_or("gone", |i| i.state.0.as_str());
+            if !is_active(state) {
+                bail!("a new instance is {state} instead of starting");
+            }
+            running += usize::from(state == "running");
+        }
+        if running == pending.instances.len() {
+            return Ok(Poll::Done);
+        }
+        Ok(Poll::Pending(format!(
+            "Starting the new generation: {running}/{} running",
+            pending.instances.len()
+        )))
+    })
+    .await?;
+    if started == PollOutcome::TimedOut {
+        step.finish(Tone::Warn, "new generation not running yet");
+        bail!(
+            "the new generation wasn't running after {}",
+            format_duration(timeout)
+        );
+    }
+
+    for &instance_id in &pending.instances {
+        let target = ServiceInstanceTarget {
+            instance_id,
+            instance_port: port,
+            group: pending.preview_group.clone(),
+        };
+        let created = client
+            .create_service_target(env.id, pending.service_id, target)
+            .await
+            .context("failed to register the new generation with the service")?;
+        pending.targets.push(created.target_id);
+    }
+
+    let (mut config, service) = service_config(client, env.id, pending.service_id).await?;
+    config.locations.retain(|l| l.path != pending.preview_path);
+    config.locations.insert(
+        0,
+        HTTPLocation {
+            path: pending.preview_path.clone(),
+            override_404: None,
+            headers: Default::default(),
+            websocket: false,
+            grpc: false,
+            cache: None,
+            target: HTTPLocationTarget::Instance {
+                group: pending.preview_group.clone(),
+                rewrite: Some("/".into()),
+            },
+        },
+    );
+    client
+        .update_service(env.id, pending.service_id, config)
+        .await
+        .with_context(|| format!("failed to add a preview location to {service}"))?;
+    step.finish(
+        Tone::Add,
+        &format!(
+            "{} {name} instance(s) running {} in {}",
+            pending.instances.len(),
+            pending.image,
+            pending.preview_group
+        ),
+    );
+    Ok(())
+}
+
+/// `unisrv rollout promote <service>`.
+pub async fn promote(
+    client: &dyn ApiClient,
+    env_flag: Option<&str>,
+    service: &str,
+    timeout: Duration,
+    yes: bool,
+) -> Result<()> {
+    let env = current_environment(client, env_flag).await?;
+    announce_environment(&env);
+    let state = StateDir::locate().ok_or_else(|| anyhow!("no state directory is available"))?;
+    let pending = pending(&state, &env, service)?;
+    if !Guard::for_env(&env)?.confirm_or_ask(
+        &format!("switch {service} over to {}", pending.image),
+        service,
+        &format!(
+            "Switch {service}'s traffic over to {} and roll {} out to it?",
+            pending.image, pending.deployment
+        ),
+        yes,
+    )? {
+        println!("Aborted.");
+        return Ok(());
+    }
+    let progress = SpinnerProgress::new();
+    promote_in(
+        client,
+        &env,
+        &state,
+        service,
+        timeout,
+        &RealWaiter,
+        &progress,
+    )
+    .await?;
+    println!(
+        "{} Promoted {} on {service}: {} runs {}.",
+        style("✓").green().bold(),
+        pending.image,
+        pending.deployment,
+        pending.image
+    );
+    Ok(())
+}
+
+pub(super) async fn promote_in(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    state: &StateDir,
+    service: &str,
+    timeout: Duration,
+    waiter: &dyn Waiter,
+    progress: &dyn Progress,
+) -> Result<()> {
+    let pending = pending(state, env, service)?;
+    let name = &pending.deployment;
+    let (deployment, (config, service_name)) = tokio::try_join!(
+        async {
+            client
+                .get_deployment(env.id, pending.deployment_id)
+                .await
+                .with_context(|| format!("failed to fetch deployment {name:?}"))
+        },
+        service_config(client, env.id, pending.service_id),
+    )?;
+    let previous = deployment.configuration.container_image.clone();
+    // Promoted as previewed: with the account defaults the new generation
+    // was started with, which the deployment as fetched doesn't have.
+    let mut staged = deployment.clone();
+    if let Some(configuration) = &pending.configuration {
+        staged.configuration = configuration.clone();
+    }
+
+    let step = progress.step(
+        Icon::Service,
+        &format!("Switching {service}'s traffic to {}", pending.image),
+    );
+    switch_traffic(
+        client,
+        env.id,
+        &pending,
+        config,
+        &service_name,
+        &pending.group,
+        &pending.preview_group,
+    )
+    .await?;
+    step.finish(
+        Tone::Change,
+        &format!(
+            "{service} serving {} from {}",
+            pending.image, pending.preview_group
+        ),
+    );
+
+    let result = redeploy(
+        client,
+        env,
+        staged,
+        &pending.image,
+        timeout,
+        waiter,
+        progress,
+    )
+    .await;
+    record(state, env, name, &pending.image, &result);
+    if let Err(e) = result {
+        let step = progress.step(
+            Icon::Deployment,
+            &format!("Rolling {name} back to {previous}"),
+        );
+        if let Err(rollback) = redeploy(
+            client, env, deployment, &previous, timeout, waiter, progress,
+        )
+        .await
+        {
+            step.finish(
+                Tone::Warn,
+                &format!("couldn't roll {name} back: {rollback:#}"),
+            );
+            return Err(e.context(format!(
+                "promoting {} failed, and {service}'s traffic stays on {}; \
+                 `unisrv rollout abort {service}` moves it back",
+                pending.image, pending.preview_group
+            )));
+        }
+        step.finish(Tone::Change, &format!("{name} rolled back"));
+        reroute(
+            client,
+            env.id,
+            &pending,
+            &pending.preview_group,
+            &pending.group,
+        )
+        .await?;
+        retire(client, env.id, &pending, progress).await;
+        state.clear_pending_rollout(&env.project, &env.name, service)?;
+        return Err(e.context(format!(
+            "promoting {} failed; {name} is back on {previous}",
+            pending.image
+        )));
+    }
+
+    reroute(
+        client,
+        env.id,
+        &pending,
+        &pending.preview_group,
+        &pending.group,
+    )
+    .await?;
+    retire(client, env.id, &pending, progress).await;
+    state.clear_pending_rollout(&env.project, &env.name, service)?;
+    Ok(())
+}
+
+/// `unisrv rollout abort <service>`.
+pub async fn abort(
+    client: &dyn ApiClient,
+    env_flag: Option<&str>,
+    service: &str,
+    yes: bool,
+) -> Result<()> {
+    let env = current_environment(client, env_flag).await?;
+    announce_environment(&env);
+    let state = StateDir::locate().ok_or_else(|| anyhow!("no state directory is available"))?;
+    let pending = pending(&state, &env, service)?;
+    if !Guard::for_env(&env)?.confirm_or_ask(
+        &format!("abort the rollout of {} on {service}", pending.image),
+        service,
+        &format!(
+            "Abort the rollout of {} to {} and stop its new generation?",
+            pending.deployment, pending.image
+        ),
+        yes,
+    )? {
+        println!("Aborted.");
+        return Ok(());
+    }
+    abort_in(client, &env, &state, service, &SpinnerProgress::new()).await?;
+    println!(
+        "{} Aborted the rollout of {} on {service}; it's served from {} as before.",
+        style("✓").green().bold(),
+        pending.image,
+        pending.group
+    );
+    Ok(())
+}
+
+pub(super) async fn abort_in(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    state: &StateDir,
+    service: &str,
+    progress: &dyn Progress,
+) -> Result<()> {
+    let pending = pending(state, env, service)?;
+    let step = progress.step(
+        Icon::Service,
+        &format!("Routing {service} back to {}", pending.group),
+    );
+    reroute(
+        client,
+        env.id,
+        &pending,
+        &pending.preview_group,
+        &pending.group,
+    )
+    .await?;
+    step.finish(
+        Tone::Change,
+        &format!("{service} serving from {}", pending.group),
+    );
+    retire(client, env.id, &pending, progress).await;
+    state.clear_pending_rollout(&env.project, &env.name, service)
+}
+
+fn pending(state: &StateDir, env: &ResolvedEnvironment, service: &str) -> Result<PendingRollout> {
+    state
+        .pending_rollout(&env.project, &env.name, service)
+        .ok_or_else(|| {
+            anyhow!(
+                "no blue/green rollout is pending on {service} from this machine; \
+                 start one with `unisrv rollout deploy <deployment> <image> --strategy blue-green`"
+            )
+        })
+}
+
+/// The service's configuration, and its name for messages.
+async fn service_config(
+    client: &dyn ApiClient,
+    env_id: Uuid,
+    service_id: Uuid,
+) -> Result<(HTTPServiceConfig, String)> {
+    let detail = client
+        .get_service(env_id, service_id)
+        .await
+        .context("failed to fetch the deployment's service")?;
+    let config = serde_json::from_value(detail.configuration).with_context(|| {
+        format!(
+            "cannot switch {}'s traffic: its configuration isn't one this CLI understands",
+            detail.name
+        )
+    })?;
+    Ok((config, detail.name))
+}
+
+/// Point every location served by group `from` at group `to`, and drop the
+/// preview location, in one service update.
+async fn reroute(
+    client: &dyn ApiClient,
+    env_id: Uuid,
+    pending: &PendingRollout,
+    from: &str,
+    to: &str,
+) -> Result<()> {
+    let (config, service) = service_config(client, env_id, pending.service_id).await?;
+    switch_traffic(client, env_id, pending, config, &service, from, to).await
+}
+
+/// [`reroute`] with the service's configuration already fetched.
+async fn switch_traffic(
+    client: &dyn ApiClient,
+    env_id: Uuid,
+    pending: &PendingRollout,
+    mut config: HTTPServiceConfig,
+    service: &str,
+    from: &str,
+    to: &str,
+) -> Result<()> {
+    switch_group(&mut config, &pending.preview_path, from, to);
+    client
+        .update_service(env_id, pending.service_id, config)
+        .await
+        .with_context(|| format!("failed to switch {service}'s traffic to {to}"))
+}
+
+fn switch_group(config: &mut HTTPServiceConfig, preview_path: &str, from: &str, to: &str) {
+    config.locations.retain(|l| l.path != preview_path);
+    for location in &mut config.locations {
+        if let HTTPLocationTarget::Instance { group, .. } = &mut location.target
+            && group == from
+        {
+            *group = to.to_string();
+        }
+    }
+}
+
+/// Take the new generation out of the service and stop it. Best-effort: what
+/// can't be stopped is reported by id so it can be cleaned up by hand.
+async fn retire(
+    client: &dyn ApiClient,
+    env_id: Uuid,
+    pending: &PendingRollout,
+    progress: &dyn Progress,
+) {
+    if pending.instances.is_empty() {
+        return;
+    }
+    let step = progress.step(Icon::Instance, "Retiring the staged generation");
+    for &target in &pending.targets {
+        if let Err(e) = client
+            .delete_service_target(env_id, pending.service_id, target)
+            .await
+        {
+            tracing::warn!("failed to remove target {target}: {e}");
+        }
+    }
+    let mut left = Vec::new();
+    for &id in &pending.instances {
+        if let Err(e) = client.deprovision_instance(env_id, id, None).await {
+            tracing::warn!("failed to stop {id}: {e}");
+            left.push(id.to_string());
+        }
+    }
+    if left.is_empty() {
+        step.finish(
+            Tone::Remove,
+            &format!("{} instance(s) retired", pending.instances.len()),
+        );
+    } else {
+        step.finish(
+            Tone::Warn,
+            &format!("couldn't stop instance(s) {}", left.join(", ")),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::rollout::tests::detail;
+    use crate::commands::up::apply::NoSleep;
+    use crate::commands::up::plan::test_env;
+    use crate::progress::SilentProgress;
+    use chrono::NaiveDateTime;
+    use serde_json::json;
+    use unisrv_api::models::{
+        CreateTargetResponse, DeploymentConfiguration, InstanceListEntry, InstanceListResponse,
+        InstanceProvisionResponse, InstanceState, ServiceDetailResponse,
+    };
+    use unisrv_api::test_support::MockApiClient;
+
+    fn fronted(id: Uuid, image: &str) -> DeploymentDetailResponse {
+        let mut deployment = detail(id, image, &["running"]);
+        deployment.service_id = Some(Uuid::nil());
+        deployment.service_target_group = Some("web".into());
+        deployment.configuration.instance_port = Some(8080);
+        deployment
+    }
+
+    fn instances(id: Uuid, state: &str) -> InstanceListResponse {
+        InstanceListResponse {
+            instances: vec![InstanceListEntry {
+                id,
+                name: Some("api-green-1".into()),
+                state: InstanceState(state.into()),
+                container_image: "api:2".into(),
+                created_at: NaiveDateTime::default(),
+                deployment: None,
+                retained_until: None,
+            }],
+        }
+    }
+
+    fn location(path: &str, group: &str) -> serde_json::Value {
+        json!({ "path": path, "target": { "type": "instance", "group": group } })
+    }
+
+    fn service(locations: Vec<serde_json::Value>) -> ServiceDetailResponse {
+        ServiceDetailResponse {
+            id: Uuid::nil(),
+            name: "web".into(),
+            base_host: "web-ab12.unisrv.dev".into(),
+            custom_hosts: vec![],
+            configuration: json!({ "locations": locations, "allow_http": false }),
+            environment_id: Uuid::nil(),
+            created_at: Default::default(),
+            updated_at: Default::default(),
+            providers: vec![],
+            targets: vec![],
+            statistics: None,
+        }
+    }
+
+    fn groups(config: &HTTPServiceConfig) -> Vec<(&str, &str)> {
+        config
+            .locations
+            .iter()
+            .map(|l| match &l.target {
+                HTTPLocationTarget::Instance { group, .. } => (l.path.as_str(), group.as_str()),
+                _ => (l.path.as_str(), ""),
+            })
+            .collect()
+    }
+
+    fn staged(
+        state: &StateDir,
+        instance: Uuid,
+        target: Uuid,
+        configuration: Option<DeploymentConfiguration>,
+    ) {
+        let env = test_env();
+        let pending = PendingRollout {
+            at: Utc::now(),
+            deployment: "api".into(),
+            deployment_id: Uuid::nil(),
+            service_id: Uuid::nil(),
+            image: "api:2".into(),
+            group: "web".into(),
+            preview_group: "web-green".into(),
+            preview_path: "/_preview/api".into(),
+            instances: vec![instance],
+            targets: vec![target],
+            configuration,
+        };
+        state
+            .save_pending_rollout(&env.project, &env.name, "web", &pending)
+            .unwrap();
+    }
+
+    #[test]
+    fn switching_groups_moves_every_location_and_drops_the_preview() {
+        let mut config: HTTPServiceConfig = serde_json::from_value(
+            service(vec![
+                location("/_preview/api", "web-green"),
+                location("/", "web"),
+                location("/admin", "admin"),
+            ])
+            .configuration,
+        )
+        .unwrap();
+        switch_group(&mut config, "/_preview/api", "web", "web-green");
+        assert_eq!(groups(&config), [("/", "web-green"), ("/admin", "admin")]);
+    }
+
+    #[tokio::test]
+    async fn staging_serves_the_new_generation_only_at_the_preview_location() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = StateDir::new(tmp.path().to_path_buf());
+        let (instance, target) = (Uuid::new_v4(), Uuid::new_v4());
+        let mock = MockApiClient::logged_in()
+            .push_get_service(Ok(service(vec![location("/", "web")])))
+            .push_provision_instance(Ok(InstanceProvisionResponse { id: instance }))
+            .with_list_instances(Ok(instances(instance, "running")))
+            .push_create_service_target(Ok(CreateTargetResponse { target_id: target }))
+            .push_get_service(Ok(service(vec![location("/", "web")])))
+            .push_update_service(Ok(()));
+
+        stage_in(
+            &mock,
+            &test_env(),
+            &state,
+            fronted(Uuid::new_v4(), "api:1"),
+            "api:2",
+            Duration::from_secs(60),
+            &NoSleep,
+            &SilentProgress,
+        )
+        .await
+        .unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let (_, started) = &calls.provision_instance_calls[0];
+        assert_eq!(started.name.as_deref(), Some("api-green-1"));
+        let (_, _, registered) = &calls.create_service_target_calls[0];
+        assert_eq!(registered.group, "web-green");
+        let (_, _, config) = &calls.update_service_calls[0];
+        assert_eq!(
+            groups(config),
+            [("/_preview/api", "web-green"), ("/", "web")]
+        );
+        let pending = state.pending_rollout("demo", "prod", "web").unwrap();
+        assert_eq!(
+            (pending.instances, pending.targets),
+            (vec![instance], vec![target])
+        );
+    }
+
+    #[tokio::test]
+    async fn a_new_generation_that_stops_is_retired_and_nothing_is_pending() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = StateDir::new(tmp.path().to_path_buf());
+        let instance = Uuid::new_v4();
+        let mock = MockApiClient::logged_in()
+            .push_get_service(Ok(service(vec![location("/", "web")])))
+            .push_provision_instance(Ok(InstanceProvisionResponse { id: instance }))
+            .with_list_instances(Ok(instances(instance, "stopped")))
+            .push_deprovision_instance(Ok(()));
+
+        let err = stage_in(
+            &mock,
+            &test_env(),
+            &state,
+            fronted(Uuid::new_v4(), "api:1"),
+            "api:2",
+            Duration::from_secs(60),
+            &NoSleep,
+            &SilentProgress,
+        )
+        .await
+        .unwrap_err();
+
+        assert!(
+            format!("{err:#}").contains("is stopped instead of starting"),
+            "{err:#}"
+        );
+        assert!(mock.calls.lock().unwrap().update_service_calls.is_empty());
+        assert_eq!(state.pending_rollout("demo", "prod", "web"), None);
+    }
+
+    #[tokio::test]
+    async fn promoting_switches_traffic_over_and_back_around_the_update() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = StateDir::new(tmp.path().to_path_buf());
+        let (instance, target) = (Uuid::new_v4(), Uuid::new_v4());
+        staged(&state, instance, target, None);
+        let mock = MockApiClient::logged_in()
+            .push_get_deployment(Ok(fronted(Uuid::nil(), "api:1")))
+            .push_get_service(Ok(service(vec![
+                location("/_preview/api", "web-green"),
+                location("/", "web"),
+            ])))
+            .push_update_service(Ok(()))
+            .push_update_deployment(Ok(()))
+            .push_get_deployment(Ok(fronted(Uuid::nil(), "api:2")))
+            .push_get_deployment(Ok(fronted(Uuid::nil(), "api:2")))
+            .push_instance_logs(Ok(Vec::new()))
+            .push_get_service(Ok(service(vec![location("/", "web-green")])))
+            .push_update_service(Ok(()))
+            .push_delete_service_target(Ok(()))
+            .push_deprovision_instance(Ok(()));
+
+        promote_in(
+            &mock,
+            &test_env(),
+            &state,
+            "web",
+            Duration::from_secs(60),
+            &NoSleep,
+            &SilentProgress,
+        )
+        .await
+        .unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let switched: Vec<_> = calls
+            .update_service_calls
+            .iter()
+            .map(|(_, _, config)| groups(config))
+            .collect();
+        assert_eq!(switched, [[("/", "web-green")], [("/", "web")]]);
+        let (_, _, updated) = &calls.update_deployment_calls[0];
+        assert_eq!(updated.configuration.container_image, "api:2");
+        assert_eq!(calls.deprovision_instance_calls[0].1, instance);
+        assert_eq!(state.pending_rollout("demo", "prod", "web"), None);
+    }
+
+    #[tokio::test]
+    async fn promoting_keeps_the_default_env_the_preview_ran_with() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = StateDir::new(tmp.path().to_path_buf());
+        let (instance, target) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut previewed = fronted(Uuid::nil(), "api:1").configuration;
+        previewed.env = Some([("LOG_LEVEL".into(), "info".into())].into());
+        staged(&state, instance, target, Some(previewed));
+        let mock = MockApiClient::logged_in()
+            .push_get_deployment(Ok(fronted(Uuid::nil(), "api:1")))
+            .push_get_service(Ok(service(vec![
+                location("/_preview/api", "web-green"),
+                location("/", "web"),
+            ])))
+            .push_update_service(Ok(()))
+            .push_update_deployment(Ok(()))
+            .push_get_deployment(Ok(fronted(Uuid::nil(), "api:2")))
+            .push_get_deployment(Ok(fronted(Uuid::nil(), "api:2")))
+            .push_instance_logs(Ok(Vec::new()))
+            .push_get_service(Ok(service(vec![location("/", "web-green")])))
+            .push_update_service(Ok(()))
+            .push_delete_service_target(Ok(()))
+            .push_deprovision_instance(Ok(()));
+
+        promote_in(
+            &mock,
+            &test_env(),
+            &state,
+            "web",
+            Duration::from_secs(60),
+            &NoSleep,
+            &SilentProgress,
+        )
+        .await
+        .unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let (_, _, updated) = &calls.update_deployment_calls[0];
+        assert_eq!(updated.configuration.container_image, "api:2");
+        let env = updated.configuration.env.as_ref().unwrap();
+        assert_eq!(env["LOG_LEVEL"], "info");
+    }
+
+    #[tokio::test]
+    async fn aborting_routes_back_and_retires_the_staged_generation() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = StateDir::new(tmp.path().to_path_buf());
+        let (instance, target) = (Uuid::new_v4(), Uuid::new_v4());
+        staged(&state, instance, target, None);
+        let mock = MockApiClient::logged_in()
+            .push_get_service(Ok(service(vec![
+                location("/_preview/api", "web-green"),
+                location("/", "web"),
+            ])))
+            .push_update_service(Ok(()))
+            .push_delete_service_target(Ok(()))
+            .push_deprovision_instance(Ok(()));
+
+        abort_in(&mock, &test_env(), &state, "web", &SilentProgress)
+            .await
+            .unwrap();
+
+        {
+            let calls = mock.calls.lock().unwrap();
+            assert_eq!(groups(&calls.update_service_calls[0].2), [("/", "web")]);
+            assert_eq!(calls.delete_service_target_calls[0].2, target);
+            assert_eq!(calls.deprovision_instance_calls[0].1, instance);
+        }
+        assert_eq!(state.pending_rollout("demo", "prod", "web"), None);
+        let err = abort_in(&mock, &test_env(), &state, "web", &SilentProgress)
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("no blue/green rollout is pending"),
+            "{err}"
+        );
+    }
+}
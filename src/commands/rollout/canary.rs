@@ -0,0 +1,637 @@
+//! `rollout deploy --strategy canary`: move a deployment behind a service to a
+//! new image a share of its traffic at a time.
+//!
+//! Service targets carry no weights, so a step's share is made by instance
+//! count: for a step of P% next to R replicas, enough canary instances on the
+//! new image join the deployment's target group to make up at least P% of it
+//! (10% next to 3 replicas is one canary, serving one request in four). Each
+//! step is held for the interval while the canaries' target health and state
+//! are checked; more unhealthy canaries than allowed, or one that stops,
+//! aborts the rollout and removes the canaries, leaving the deployment as it
+//! was. The final 100% step updates the deployment itself — rolled back to
+//! its old image if its replicas aren't running in time — and then retires the
+//! canaries. Interrupted, the CLI can't clean up after itself: the canaries,
+//! named `<deployment>-canary-N`, are left for `instance stop`.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+use unisrv_api::ApiClient;
+use unisrv_api::models::{
+    DeploymentConfiguration, DeploymentDetailResponse, InstanceConfiguration,
+    InstanceNetworkConfig, InstanceProvisionRequest, ServiceInstanceTarget,
+    UpdateDeploymentRequest,
+};
+use uuid::Uuid;
+
+use super::redeploy;
+use crate::commands::instance::list::is_active;
+use crate::commands::up::apply::{Poll, PollOutcome, Waiter, poll_until};
+use crate::commands::up::plan::ResolvedEnvironment;
+use crate::commands::up::ready::format_duration;
+use crate::progress::{Icon, Progress, Tone};
+
+/// `--steps` when not given: the traffic share after each step, in percent.
+pub const DEFAULT_STEPS: &str = "10,50,100";
+/// `--interval` when not given: how long each step is held.
+pub const DEFAULT_INTERVAL: &str = "5m";
+
+/// How often the canaries are checked while a step is held, and while they
+/// start.
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Traffic shares in percent, rising, ending at 100.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Steps(pub Vec<u8>);
+
+/// How a canary rollout proceeds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Canary {
+    pub steps: Steps,
+    /// How long each step short of 100% is held.
+    pub interval: Duration,
+    /// How many canaries may be unhealthy at once before the rollout aborts.
+    pub max_unhealthy: usize,
+}
+
+/// Parse `--steps`, e.g. "10,50,100".
+pub fn parse_steps(spec: &str) -> Result<Steps, String> {
+    let steps = spec
+        .split(',')
+        .map(|step| {
+            let step = step.trim().trim_end_matches('%');
+            match step.parse::<u8>() {
+                Ok(percent @ 1..=100) => Ok(percent),
+                _ => Err(format!("{step:?} is not a percentage from 1 to 100")),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if steps.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err("steps must rise, e.g. 10,50,100".into());
+    }
+    if steps.last() != Some(&100) {
+        return Err("the last step must be 100, when the deployment itself is updated".into());
+    }
+    Ok(Steps(steps))
+}
+
+/// How many canaries make up at least `percent` of the targets next to
+/// `replicas` of the old image. Always at least one.
+fn canaries_for(percent: u8, replicas: u32) -> u32 {
+    let percent = u32::from(percent.min(99));
+    (percent * replicas.max(1)).div_ceil(100 - percent).max(1)
+}
+
+/// A canary instance, and its target once it's registered.
+struct Launched {
+    id: Uuid,
+    target: Option<Uuid>,
+}
+
+/// Roll `deployment` out to `image` through `plan`'s steps. On success the
+/// deployment runs `image` and the canaries are gone; on failure they're gone
+/// too, and the deployment runs what it did before. `previous` is what it's
+/// rolled back to: its configuration as fetched, before any account defaults
+/// were merged into `deployment`'s.
+#[allow(clippy::too_many_arguments)]
+pub async fn shift(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    deployment: DeploymentDetailResponse,
+    previous: DeploymentConfiguration,
+    image: &str,
+    plan: &Canary,
+    timeout: Duration,
+    waiter: &dyn Waiter,
+    progress: &dyn Progress,
+) -> Result<()> {
+    let name = deployment.name.clone();
+    let service_id = deployment.service_id.ok_or_else(|| {
+        anyhow!(
+            "{name} isn't behind a service, so there's no traffic to shift; \
+             roll it out without --strategy canary"
+        )
+    })?;
+    let port = deployment
+        .configuration
+        .instance_port
+        .ok_or_else(|| anyhow!("{name} has no instance port for canary traffic to reach"))?;
+    let group = deployment
+        .service_target_group
+        .clone()
+        .unwrap_or_else(|| "default".to_string());
+
+    let shift = Shift {
+        service_id,
+        port,
+        group,
+        image,
+    };
+    let mut canaries = Vec::new();
+    let mut result = Ok(());
+    for &percent in plan.steps.0.iter().filter(|&&p| p < 100) {
+        result = hold(
+            client,
+            env,
+            &deployment,
+            &shift,
+            percent,
+            plan,
+            timeout,
+            &mut canaries,
+            waiter,
+            progress,
+        )
+        .await
+        .with_context(|| format!("canary rollout of {name} aborted at {percent}%"));
+        if result.is_err() {
+            break;
+        }
+    }
+    if result.is_ok() {
+        let (id, previous) = (
+            deployment.id,
+            UpdateDeploymentRequest {
+                network_id: deployment.network_id,
+                configuration: previous,
+            },
+        );
+        result = redeploy(client, env, deployment, image, timeout, waiter, progress).await;
+        if result.is_err() {
+            let step = progress.step(Icon::Deployment, &format!("Rolling {name} back"));
+            match client.update_deployment(env.id, id, previous).await {
+                Ok(()) => step.finish(Tone::Change, &format!("{name} rolled back")),
+                Err(e) => step.finish(Tone::Warn, &format!("couldn't roll {name} back: {e}")),
+            }
+        }
+    }
+    retire(client, env.id, service_id, &canaries, progress).await;
+    result
+}
+
+/// What every step shares.
+struct Shift<'a> {
+    service_id: Uuid,
+    port: u16,
+    group: String,
+    image: &'a str,
+}
+
+/// Bring the canaries up to `percent` of the traffic and hold there for the
+/// interval, failing if they don't start or too many turn unhealthy.
+#[allow(clippy::too_many_arguments)]
+async fn hold(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    deployment: &DeploymentDetailResponse,
+    shift: &Shift<'_>,
+    percent: u8,
+    plan: &Canary,
+    timeout: Duration,
+    canaries: &mut Vec<Launched>,
+    waiter: &dyn Waiter,
+    progress: &dyn Progress,
+) -> Result<()> {
+    let name = &deployment.name;
+    let replicas = deployment.configuration.replicas;
+    let wanted = canaries_for(percent, replicas) as usize;
+    let step = progress.step(
+        Icon::Deployment,
+        &format!("Shifting {percent}% of {name}'s traffic to {}", shift.image),
+    );
+
+    while canaries.len() < wanted {
+        let request = instance_request(
+            deployment,
+            shift.image,
+            format!("{name}-canary-{}", canaries.len() + 1),
+        );
+        let created = client
+            .provision_instance(env.id, request)
+            .await
+            .context("failed to start a canary")?;
+        canaries.push(Launched {
+            id: created.id,
+            target: None,
+        });
+    }
+
+    let max_rounds = timeout.as_secs().div_ceil(CHECK_INTERVAL.as_secs()) as usize + 1;
+    let started = poll_until(waiter, CHECK_INTERVAL, max_rounds, &step, async || {
+        let states = canary_states(client, env.id, canaries).await?;
+        if let Some(state) = states.iter().find(|s| !is_active(s)) {
+            bail!("a canary is {state} instead of starting");
+        }
+        let running = states.iter().filter(|s| *s == "running").count();
+        if running == canaries.len() {
+            return Ok(Poll::Done);
+        }
+        Ok(Poll::Pending(format!(
+            "Starting canaries: {running}/{} running",
+            canaries.len()
+        )))
+    })
+    .await?;
+    if started == PollOutcome::TimedOut {
+        bail!(
+            "the canaries weren't running after {}",
+            format_duration(timeout)
+        );
+    }
+
+    for canary in canaries.iter_mut().filter(|c| c.target.is_none()) {
+        let target = ServiceInstanceTarget {
+            instance_id: canary.id,
+            instance_port: shift.port,
+            group: shift.group.clone(),
+        };
+        let created = client
+            .create_service_target(env.id, shift.service_id, target)
+            .await
+            .context("failed to route traffic to a canary")?;
+        canary.target = Some(created.target_id);
+    }
+
+    let rounds = (plan.interval.as_secs() / CHECK_INTERVAL.as_secs()).max(1) as usize;
+    let mut round = 0;
+    poll_until(waiter, CHECK_INTERVAL, rounds, &step, async || {
+        round += 1;
+        check(
+            client,
+            env.id,
+            shift.service_id,
+            canaries,
+            plan.max_unhealthy,
+        )
+        .await?;
+        let left = CHECK_INTERVAL * (rounds - round) as u32;
+        Ok(Poll::Pending(format!(
+            "{percent}% on {}, {} canary(s) healthy; next step in {}",
+            shift.image,
+            canaries.len(),
+            format_duration(left.max(Duration::from_secs(1)))
+        )))
+    })
+    .await?;
+    step.finish(
+        Tone::Change,
+        &format!(
+            "{percent}% of {name}'s traffic on {} ({} canary(s) next to {replicas} replica(s))",
+            shift.image,
+            canaries.len()
+        ),
+    );
+    Ok(())
+}
+
+/// Fail if a canary has stopped, or more than `max_unhealthy` of their
+/// targets are unhealthy.
+async fn check(
+    client: &dyn ApiClient,
+    env_id: Uuid,
+    service_id: Uuid,
+    canaries: &[Launched],
+    max_unhealthy: usize,
+) -> Result<()> {
+    let (states, service) = tokio::try_join!(canary_states(client, env_id, canaries), async {
+        client
+            .get_service(env_id, service_id)
+            .await
+            .context("failed to check the canaries' health")
+    },)?;
+    if let Some(state) = states.iter().find(|s| !is_active(s)) {
+        bail!("a canary is {state}");
+    }
+    let unhealthy = service
+        .targets
+        .iter()
+        .filter(|t| canaries.iter().any(|c| c.target == Some(t.id)))
+        .filter(|t| t.health.as_ref().is_some_and(|h| h.status == "unhealthy"))
+        .count();
+    if unhealthy > max_unhealthy {
+        bail!(
+            "{unhealthy} of {} canary(s) unhealthy (at most {max_unhealthy} allowed)",
+            canaries.len()
+        );
+    }
+    Ok(())
+}
+
+/// Each canary's state, "gone" for one no longer listed.
+async fn canary_states(
+    client: &dyn ApiClient,
+    env_id: Uuid,
+    canaries: &[Launched],
+) -> Result<Vec<String>> {
+    let instances = client.list_instances(env_id).await?.instances;
+    Ok(canaries
+        .iter()
+        .map(|c| {
+            instances
+                .iter()
+                .find(|i| i.id == c.id)
+                .map_or_else(|| "gone".to_string(), |i| i.state.0.clone())
+        })
+        .collect())
+}
+
+/// An instance named `name` with the deployment's configuration on `image`,
+/// outside the deployment.
+pub(super) fn instance_request(
+    deployment: &DeploymentDetailResponse,
+    image: &str,
+    name: String,
+) -> InstanceProvisionRequest {
+    let config = &deployment.configuration;
+    InstanceProvisionRequest {
+        name: Some(name),
+        region: config.region.clone(),
+        vcpu_ratio: config.vcpu_ratio,
+        vcpu_count: config.vcpu_count,
+        memory_mb: config.memory_mb,
+        memory_request_mb: None,
+        configuration: InstanceConfiguration {
+            container_image: image.to_string(),
+            args: config.args.clone(),
+            env: config.env.clone(),
+            hostname: None,
+            metadata: None,
+            init_containers: config.init_containers.clone(),
+            sidecars: config.sidecars.clone(),
+            platform: None,
+        },
+        container_registry_token: None,
+        network: deployment
+            .network_id
+            .map(|network_id| InstanceNetworkConfig {
+                network_id,
+                instance_ip: None,
+            }),
+        annotations: None,
+    }
+}
+
+/// Take the canaries out of the service and stop them. Best-effort: what
+/// can't be removed is reported by id so it can be cleaned up by hand.
+async fn retire(
+    client: &dyn ApiClient,
+    env_id: Uuid,
+    service_id: Uuid,
+    canaries: &[Launched],
+    progress: &dyn Progress,
+) {
+    if canaries.is_empty() {
+        return;
+    }
+    let step = progress.step(Icon::Instance, "Retiring canaries");
+    let mut left = Vec::new();
+    for canary in canaries {
+        if let Some(target) = canary.target
+            && let Err(e) = client
+                .delete_service_target(env_id, service_id, target)
+                .await
+        {
+            tracing::warn!("failed to remove canary target {target}: {e}");
+        }
+        if let Err(e) = client.deprovision_instance(env_id, canary.id, None).await {
+            tracing::warn!("failed to stop canary {}: {e}", canary.id);
+            left.push(canary.id.to_string());
+        }
+    }
+    if left.is_empty() {
+        step.finish(
+            Tone::Remove,
+            &format!("{} canary(s) retired", canaries.len()),
+        );
+    } else {
+        step.finish(
+            Tone::Warn,
+            &format!("couldn't stop canary(s) {}", left.join(", ")),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::rollout::tests::detail;
+    use crate::commands::up::apply::NoSleep;
+    use crate::commands::up::plan::test_env;
+    use crate::progress::SilentProgress;
+    use chrono::NaiveDateTime;
+    use unisrv_api::ApiError;
+    use unisrv_api::models::{
+        CreateTargetResponse, InstanceListEntry, InstanceListResponse, InstanceProvisionResponse,
+        InstanceState, ServiceDetailResponse, ServiceTargetDetail, TargetHealth,
+    };
+    use unisrv_api::test_support::MockApiClient;
+
+    fn fronted(id: Uuid, image: &str) -> DeploymentDetailResponse {
+        let mut deployment = detail(id, image, &["running"]);
+        deployment.service_id = Some(Uuid::nil());
+        deployment.service_target_group = Some("web".into());
+        deployment.configuration.instance_port = Some(8080);
+        deployment
+    }
+
+    fn instances(id: Uuid, state: &str) -> InstanceListResponse {
+        InstanceListResponse {
+            instances: vec![InstanceListEntry {
+                id,
+                name: Some("api-canary-1".into()),
+                state: InstanceState(state.into()),
+                container_image: "api:2".into(),
+                created_at: NaiveDateTime::default(),
+                deployment: None,
+                retained_until: None,
+            }],
+        }
+    }
+
+    fn service(target: Uuid, health: &str) -> ServiceDetailResponse {
+        ServiceDetailResponse {
+            id: Uuid::nil(),
+            name: "web".into(),
+            base_host: "web-ab12.unisrv.dev".into(),
+            custom_hosts: vec![],
+            configuration: serde_json::Value::Null,
+            environment_id: Uuid::nil(),
+            created_at: Default::default(),
+            updated_at: Default::default(),
+            providers: vec![],
+            targets: vec![ServiceTargetDetail {
+                id: target,
+                instance_id: Uuid::new_v4(),
+                target_group: "web".into(),
+                instance_port: 8080,
+                created_at: NaiveDateTime::default(),
+                health: Some(TargetHealth {
+                    status: health.into(),
+                    last_checked_at: None,
+                }),
+            }],
+            statistics: None,
+        }
+    }
+
+    fn plan() -> Canary {
+        Canary {
+            steps: parse_steps("50,100").unwrap(),
+            interval: Duration::from_secs(10),
+            max_unhealthy: 0,
+        }
+    }
+
+    #[test]
+    fn steps_must_rise_to_100() {
+        assert_eq!(parse_steps("10, 50%,100"), Ok(Steps(vec![10, 50, 100])));
+        assert!(parse_steps("50,10,100").unwrap_err().contains("rise"));
+        assert!(parse_steps("10,50").unwrap_err().contains("last step"));
+        assert!(parse_steps("0,100").unwrap_err().contains("percentage"));
+    }
+
+    #[test]
+    fn enough_canaries_make_up_the_step_share() {
+        assert_eq!(canaries_for(10, 3), 1);
+        assert_eq!(canaries_for(50, 3), 3);
+        assert_eq!(canaries_for(25, 3), 1);
+        assert_eq!(canaries_for(90, 1), 9);
+    }
+
+    #[tokio::test]
+    async fn a_healthy_canary_is_promoted_and_then_retired() {
+        let (id, canary, target) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let mock = MockApiClient::logged_in()
+            .push_provision_instance(Ok(InstanceProvisionResponse { id: canary }))
+            .with_list_instances(Ok(instances(canary, "running")))
+            .push_create_service_target(Ok(CreateTargetResponse { target_id: target }))
+            .with_list_instances(Ok(instances(canary, "running")))
+            .push_get_service(Ok(service(target, "healthy")))
+            .push_update_deployment(Ok(()))
+            .push_get_deployment(Ok(fronted(id, "api:2")))
+            .push_get_deployment(Ok(fronted(id, "api:2")))
+            .push_instance_logs(Ok(Vec::new()))
+            .push_delete_service_target(Ok(()))
+            .push_deprovision_instance(Ok(()));
+
+        shift(
+            &mock,
+            &test_env(),
+            fronted(id, "api:1"),
+            fronted(id, "api:1").configuration,
+            "api:2",
+            &plan(),
+            Duration::from_secs(60),
+            &NoSleep,
+            &SilentProgress,
+        )
+        .await
+        .unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let (_, started) = &calls.provision_instance_calls[0];
+        assert_eq!(started.configuration.container_image, "api:2");
+        assert_eq!(started.name.as_deref(), Some("api-canary-1"));
+        let (_, _, routed) = &calls.create_service_target_calls[0];
+        assert_eq!((routed.instance_id, routed.group.as_str()), (canary, "web"));
+        let (_, _, promoted) = &calls.update_deployment_calls[0];
+        assert_eq!(promoted.configuration.container_image, "api:2");
+        assert_eq!(calls.delete_service_target_calls[0].2, target);
+        assert_eq!(calls.deprovision_instance_calls[0].1, canary);
+    }
+
+    #[tokio::test]
+    async fn an_unhealthy_canary_aborts_and_leaves_the_deployment_alone() {
+        let (id, canary, target) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let mock = MockApiClient::logged_in()
+            .push_provision_instance(Ok(InstanceProvisionResponse { id: canary }))
+            .with_list_instances(Ok(instances(canary, "running")))
+            .push_create_service_target(Ok(CreateTargetResponse { target_id: target }))
+            .with_list_instances(Ok(instances(canary, "running")))
+            .push_get_service(Ok(service(target, "unhealthy")))
+            .push_delete_service_target(Ok(()))
+            .push_deprovision_instance(Ok(()));
+
+        let err = shift(
+            &mock,
+            &test_env(),
+            fronted(id, "api:1"),
+            fronted(id, "api:1").configuration,
+            "api:2",
+            &plan(),
+            Duration::from_secs(60),
+            &NoSleep,
+            &SilentProgress,
+        )
+        .await
+        .unwrap_err();
+
+        let msg = format!("{err:#}");
+        assert!(msg.contains("aborted at 50%"), "{msg}");
+        assert!(msg.contains("1 of 1 canary(s) unhealthy"), "{msg}");
+        let calls = mock.calls.lock().unwrap();
+        assert!(calls.update_deployment_calls.is_empty());
+        assert_eq!(calls.deprovision_instance_calls[0].1, canary);
+    }
+
+    #[tokio::test]
+    async fn a_failed_final_step_rolls_back_to_the_config_without_defaults() {
+        let (id, canary, target) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let mock = MockApiClient::logged_in()
+            .push_provision_instance(Ok(InstanceProvisionResponse { id: canary }))
+            .with_list_instances(Ok(instances(canary, "running")))
+            .push_create_service_target(Ok(CreateTargetResponse { target_id: target }))
+            .with_list_instances(Ok(instances(canary, "running")))
+            .push_get_service(Ok(service(target, "healthy")))
+            .push_update_deployment(Err(ApiError::Server {
+                status: 500,
+                reason: "boom".into(),
+            }))
+            .push_update_deployment(Ok(()))
+            .push_delete_service_target(Ok(()))
+            .push_deprovision_instance(Ok(()));
+        let own = fronted(id, "api:1");
+        let mut merged = own.clone();
+        merged.configuration.env = Some([("LOG_LEVEL".into(), "info".into())].into());
+
+        shift(
+            &mock,
+            &test_env(),
+            merged,
+            own.configuration,
+            "api:2",
+            &plan(),
+            Duration::from_secs(60),
+            &NoSleep,
+            &SilentProgress,
+        )
+        .await
+        .unwrap_err();
+
+        let calls = mock.calls.lock().unwrap();
+        let (_, _, promoted) = &calls.update_deployment_calls[0];
+        assert!(promoted.configuration.env.is_some());
+        let (_, _, restored) = &calls.update_deployment_calls[1];
+        assert_eq!(restored.configuration.container_image, "api:1");
+        assert_eq!(restored.configuration.env, None);
+    }
+
+    #[tokio::test]
+    async fn a_deployment_without_a_service_has_no_traffic_to_shift() {
+        let mock = MockApiClient::logged_in();
+        let err = shift(
+            &mock,
+            &test_env(),
+            detail(Uuid::new_v4(), "api:1", &["running"]),
+            detail(Uuid::new_v4(), "api:1", &["running"]).configuration,
+            "api:2",
+            &plan(),
+            Duration::from_secs(60),
+            &NoSleep,
+            &SilentProgress,
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("isn't behind a service"), "{err}");
+    }
+}
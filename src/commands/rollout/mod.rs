@@ -0,0 +1,688 @@
+//! `unisrv rollout deploy|history|rollback <deployment>` — moving a
+//! deployment to a new image, the images it has been rolled out with, and
+//! going back to an earlier one.
+//!
+//! `deploy` replaces the deployment's instances in one go by default; with
+//! `--strategy canary` it shifts traffic over in steps (see [`canary`]), and
+//! with `--strategy blue-green` it stages the new generation for `rollout
+//! promote` or `rollout abort` (see [`blue_green`]).
+//! With any strategy, the account's default environment variables are
+//! merged in under the deployment's own, unless `--no-default-env` leaves them
+//! out (see [`default_env`](crate::default_env)). Whatever updates the
+//! deployment itself — a deploy, a promote or a rollback — waits for its new
+//! replicas to run, then shows how the newest one booted (see [`boot`]).
+//!
+//! The API only keeps a deployment's current configuration, so the record is
+//! local: every `up` notes the image it applied to each deployment in the
+//! state directory (see [`crate::state`]). Each such entry is a revision,
+//! named by a short hex digest of when it was applied and what it deployed, so
+//! its name doesn't shift as older entries are dropped. Rolling back updates
+//! the deployment to an earlier revision's image — the operator replaces the
+//! current instances with new ones — and waits for its replicas to be running.
+//!
+//! History is only as complete as this machine's: an `up` run in CI or from
+//! another checkout isn't in it.
+
+pub mod blue_green;
+pub mod canary;
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{DateTime, Utc};
+use comfy_table::{Attribute, Cell};
+use console::style;
+use unisrv_api::ApiClient;
+use unisrv_api::models::{DeploymentDetailResponse, DeploymentListEntry, UpdateDeploymentRequest};
+use uuid::Uuid;
+
+use super::confirm::Guard;
+use super::instance::boot;
+use super::instance::run::{announce_environment, current_environment};
+use super::ui::{format_relative, new_table};
+use super::up::apply::{RealWaiter, Waiter};
+use super::up::plan::ResolvedEnvironment;
+use super::up::ready::{Rollout, render_not_ready, wait_ready};
+use crate::default_env::AccountDefaults;
+use crate::progress::{Progress, SpinnerProgress};
+use crate::state::{HistoryEntry, Outcome, StateDir};
+
+/// How long `deploy` and `rollback` wait for the new replicas by default.
+pub const DEFAULT_TIMEOUT: &str = "5m";
+
+/// `--strategy`: how `deploy` moves a deployment to its new image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    /// Update the deployment, which replaces all its instances.
+    Replace,
+    /// Shift its service's traffic over in steps first.
+    Canary,
+    /// Stage the new generation next to the old, for `rollout promote`.
+    BlueGreen,
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "replace" => Ok(Strategy::Replace),
+            "canary" => Ok(Strategy::Canary),
+            "blue-green" => Ok(Strategy::BlueGreen),
+            other => Err(format!(
+                "unknown strategy {other:?} (expected replace, canary or blue-green)"
+            )),
+        }
+    }
+}
+
+/// One recorded rollout of a deployment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Revision {
+    pub id: String,
+    pub at: DateTime<Utc>,
+    pub image: String,
+    pub outcome: Outcome,
+}
+
+/// `unisrv rollout history <deployment>`: its recorded revisions, newest
+/// first, with the one it's running now marked.
+pub async fn history(client: &dyn ApiClient, env_flag: Option<&str>, name: &str) -> Result<()> {
+    let env = current_environment(client, env_flag).await?;
+    announce_environment(&env);
+    let deployment = find_deployment(client, &env, name).await?;
+    let state = StateDir::locate().ok_or_else(|| anyhow!("no state directory is available"))?;
+
+    let revisions = revisions(&state.history(), &env, name);
+    if revisions.is_empty() {
+        println!("No rollouts of {name} recorded on this machine.");
+        return Ok(());
+    }
+    let current = current_revision(&revisions, &deployment.container_image);
+    let now = Utc::now();
+    let mut table = new_table();
+    table.set_header(
+        ["REVISION", "DEPLOYED", "IMAGE", "OUTCOME"]
+            .map(|h| Cell::new(h).add_attribute(Attribute::Bold)),
+    );
+    for (i, revision) in revisions.iter().enumerate() {
+        let outcome = match revision.outcome {
+            Outcome::Applied if current == Some(i) => "current",
+            Outcome::Applied => "applied",
+            Outcome::Failed => "failed",
+        };
+        table.add_row(vec![
+            Cell::new(&revision.id),
+            Cell::new(format_relative(revision.at, now)),
+            Cell::new(&revision.image),
+            Cell::new(outcome),
+        ]);
+    }
+    println!("{table}");
+    if current.is_none() {
+        println!(
+            "{}",
+            style(format!(
+                "Running {}, which no recorded rollout deployed.",
+                deployment.container_image
+            ))
+            .dim()
+        );
+    }
+    Ok(())
+}
+
+/// `unisrv rollout rollback <deployment> [--to <revision>]`.
+pub async fn rollback(
+    client: &dyn ApiClient,
+    env_flag: Option<&str>,
+    name: &str,
+    to: Option<&str>,
+    timeout: Duration,
+    yes: bool,
+) -> Result<()> {
+    let env = current_environment(client, env_flag).await?;
+    announce_environment(&env);
+    let state = StateDir::locate().ok_or_else(|| anyhow!("no state directory is available"))?;
+    let guard = Guard::for_env(&env)?;
+    let progress = SpinnerProgress::new();
+    let request = Rollback {
+        name,
+        to,
+        timeout,
+        yes,
+    };
+    rollback_in(
+        client,
+        &env,
+        &state,
+        &request,
+        &guard,
+        &RealWaiter,
+        &progress,
+    )
+    .await
+}
+
+/// `unisrv rollout deploy <deployment> <image>`.
+#[allow(clippy::too_many_arguments)]
+pub async fn deploy(
+    client: &dyn ApiClient,
+    env_flag: Option<&str>,
+    name: &str,
+    image: &str,
+    strategy: Strategy,
+    canary: &canary::Canary,
+    default_env: Option<&AccountDefaults>,
+    timeout: Duration,
+    yes: bool,
+) -> Result<()> {
+    let env = current_environment(client, env_flag).await?;
+    announce_environment(&env);
+    let state = StateDir::locate().ok_or_else(|| anyhow!("no state directory is available"))?;
+    let found = find_deployment(client, &env, name).await?;
+    let mut detail = client
+        .get_deployment(env.id, found.id)
+        .await
+        .with_context(|| format!("failed to fetch deployment {name:?}"))?;
+    // What a failed canary rolls back to: the deployment's own configuration,
+    // without the defaults merged in below.
+    let original = detail.configuration.clone();
+    if let Some(defaults) = default_env {
+        let own = detail.configuration.env.take();
+        detail.configuration.env = defaults.under(&env.project, own.as_ref());
+    }
+    let current = &detail.configuration.container_image;
+    if current == image {
+        println!("{name} is already running {image}.");
+        return Ok(());
+    }
+    let guard = Guard::for_env(&env)?;
+    let how = match strategy {
+        Strategy::Replace => "",
+        Strategy::Canary => " by canary",
+        Strategy::BlueGreen => " by blue/green",
+    };
+    if !guard.confirm_or_ask(
+        &format!("roll {name} out to {image}"),
+        name,
+        &format!("Roll {name} out from {current} to {image}{how}?"),
+        yes,
+    )? {
+        println!("Aborted.");
+        return Ok(());
+    }
+
+    let progress = SpinnerProgress::new();
+    let result = match strategy {
+        Strategy::Replace => {
+            redeploy(client, &env, detail, image, timeout, &RealWaiter, &progress).await
+        }
+        Strategy::Canary => {
+            canary::shift(
+                client,
+                &env,
+                detail,
+                original,
+                image,
+                canary,
+                timeout,
+                &RealWaiter,
+                &progress,
+            )
+            .await
+        }
+        // Nothing has rolled out until it's promoted.
+        Strategy::BlueGreen => {
+            return blue_green::stage_in(
+                client,
+                &env,
+                &state,
+                detail,
+                image,
+                timeout,
+                &RealWaiter,
+                &progress,
+            )
+            .await;
+        }
+    };
+    record(&state, &env, name, image, &result);
+    result?;
+    println!(
+        "{} Rolled {name} out to {image}.",
+        style("✓").green().bold()
+    );
+    if found.managed_by.is_some() {
+        println!(
+            "{}",
+            style("The next `unisrv up` rolls it to the image unisrv.hcl declares, unless that's updated too.").dim()
+        );
+    }
+    Ok(())
+}
+
+/// What `rollback` was asked to do.
+struct Rollback<'a> {
+    name: &'a str,
+    to: Option<&'a str>,
+    timeout: Duration,
+    yes: bool,
+}
+
+async fn rollback_in(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    state: &StateDir,
+    request: &Rollback<'_>,
+    guard: &Guard<'_>,
+    waiter: &dyn Waiter,
+    progress: &dyn Progress,
+) -> Result<()> {
+    let name = request.name;
+    let found = find_deployment(client, env, name).await?;
+    let detail = client
+        .get_deployment(env.id, found.id)
+        .await
+        .with_context(|| format!("failed to fetch deployment {name:?}"))?;
+    let current = detail.configuration.container_image.clone();
+    let revisions = revisions(&state.history(), env, name);
+    let target = pick(&revisions, &current, request.to, name)?.clone();
+
+    let go = guard.confirm_or_ask(
+        &format!("roll {name} back to {}", target.image),
+        name,
+        &format!(
+            "Roll {name} back from {current} to {} (revision {})?",
+            target.image, target.id
+        ),
+        request.yes,
+    )?;
+    if !go {
+        println!("Aborted.");
+        return Ok(());
+    }
+
+    let result = redeploy(
+        client,
+        env,
+        detail,
+        &target.image,
+        request.timeout,
+        waiter,
+        progress,
+    )
+    .await;
+    // Recorded like an `up` of just this deployment, so the rollback is itself
+    // a revision to come back from.
+    record(state, env, name, &target.image, &result);
+    result?;
+
+    println!(
+        "{} Rolled {name} back to {} (revision {}).",
+        style("✓").green().bold(),
+        target.image,
+        target.id
+    );
+    println!(
+        "{}",
+        style("The next `unisrv up` rolls it forward to the image unisrv.hcl declares.").dim()
+    );
+    Ok(())
+}
+
+/// Update `deployment` to `image` in place and wait up to `timeout` for its
+/// replicas to be running.
+async fn redeploy(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    deployment: DeploymentDetailResponse,
+    image: &str,
+    timeout: Duration,
+    waiter: &dyn Waiter,
+    progress: &dyn Progress,
+) -> Result<()> {
+    let name = deployment.name;
+    let mut configuration = deployment.configuration;
+    configuration.container_image = image.to_string();
+    let replicas = configuration.replicas;
+    let update = UpdateDeploymentRequest {
+        network_id: deployment.network_id,
+        configuration,
+    };
+    client
+        .update_deployment(env.id, deployment.id, update)
+        .await
+        .with_context(|| format!("failed to update deployment {name:?}"))?;
+    let rollout = Rollout {
+        id: deployment.id,
+        name,
+        replicas,
+        timeout,
+    };
+    let not_ready = wait_ready(client, env.id, vec![rollout], waiter, progress).await?;
+    if !not_ready.is_empty() {
+        return Err(anyhow!(render_not_ready(&not_ready)));
+    }
+    report_boot(client, env.id, deployment.id).await;
+    Ok(())
+}
+
+/// Show how the deployment's newest running instance booted. Best-effort.
+async fn report_boot(client: &dyn ApiClient, env_id: Uuid, deployment_id: Uuid) {
+    let detail = match client.get_deployment(env_id, deployment_id).await {
+        Ok(detail) => detail,
+        Err(e) => {
+            tracing::debug!("no boot breakdown for deployment {deployment_id}: {e}");
+            return;
+        }
+    };
+    if let Some(newest) = detail
+        .instances
+        .iter()
+        .filter(|i| i.state.0 == "running")
+        .max_by_key(|i| i.created_at)
+    {
+        boot::report(client, env_id, newest.id).await;
+    }
+}
+
+/// Note a rollout of `name` to `image` in the local history. Best-effort, as
+/// for `up`.
+fn record(
+    state: &StateDir,
+    env: &ResolvedEnvironment,
+    name: &str,
+    image: &str,
+    result: &Result<()>,
+) {
+    let entry = HistoryEntry {
+        at: Utc::now(),
+        project: env.project.clone(),
+        environment: env.name.clone(),
+        deployments: BTreeMap::from([(name.to_string(), image.to_string())]),
+        outcome: if result.is_ok() {
+            Outcome::Applied
+        } else {
+            Outcome::Failed
+        },
+    };
+    if let Err(e) = state.record_history(&entry) {
+        tracing::warn!("failed to record local state: {e:#}");
+    }
+}
+
+async fn find_deployment(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    name: &str,
+) -> Result<DeploymentListEntry> {
+    let deployments = client
+        .list_deployments(env.id)
+        .await
+        .context("failed to list deployments")?
+        .deployments;
+    let names: Vec<String> = deployments.iter().map(|d| d.name.clone()).collect();
+    deployments
+        .into_iter()
+        .find(|d| d.name == name)
+        .ok_or_else(|| {
+            if names.is_empty() {
+                anyhow!(
+                    "no deployment named {name:?} in {}: nothing is deployed",
+                    env.name
+                )
+            } else {
+                anyhow!(
+                    "no deployment named {name:?} in {} (deployments: {})",
+                    env.name,
+                    names.join(", ")
+                )
+            }
+        })
+}
+
+/// The rollouts of deployment `name` in `env` that `history` records, newest
+/// first.
+pub fn revisions(history: &[HistoryEntry], env: &ResolvedEnvironment, name: &str) -> Vec<Revision> {
+    history
+        .iter()
+        .rev()
+        .filter(|entry| entry.project == env.project && entry.environment == env.name)
+        .filter_map(|entry| {
+            let image = entry.deployments.get(name)?;
+            Some(Revision {
+                id: revision_id(entry.at, image),
+                at: entry.at,
+                image: image.clone(),
+                outcome: entry.outcome,
+            })
+        })
+        .collect()
+}
+
+/// A revision's name: the first 8 hex digits of an FNV-1a hash of when it was
+/// applied and its image. Stable across builds, unlike the std hasher.
+fn revision_id(at: DateTime<Utc>, image: &str) -> String {
+    let mut hash: u32 = 0x811c_9dc5;
+    for byte in format!("{}\n{image}", at.to_rfc3339()).bytes() {
+        hash ^= u32::from(byte);
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    format!("{hash:08x}")
+}
+
+/// The index of the newest applied revision running `image`, if any.
+fn current_revision(revisions: &[Revision], image: &str) -> Option<usize> {
+    revisions
+        .iter()
+        .position(|r| r.outcome == Outcome::Applied && r.image == image)
+}
+
+/// The revision to roll back to: the one `to` names (by a unique prefix), or
+/// else the newest applied one with an image other than `current`.
+fn pick<'a>(
+    revisions: &'a [Revision],
+    current: &str,
+    to: Option<&str>,
+    name: &str,
+) -> Result<&'a Revision> {
+    let Some(to) = to else {
+        return revisions
+            .iter()
+            .find(|r| r.outcome == Outcome::Applied && r.image != current)
+            .ok_or_else(|| {
+                anyhow!(
+                    "no earlier image of {name} is recorded on this machine \
+                     (see `unisrv rollout history {name}`)"
+                )
+            });
+    };
+    let matches: Vec<&Revision> = revisions.iter().filter(|r| r.id.starts_with(to)).collect();
+    let revision = match matches.as_slice() {
+        [] => bail!("no revision {to:?} of {name} (see `unisrv rollout history {name}`)"),
+        [one] => *one,
+        _ => bail!("revision {to:?} is ambiguous: give more of it"),
+    };
+    if revision.outcome == Outcome::Failed {
+        bail!(
+            "revision {} of {name} failed to apply; pick one that applied",
+            revision.id
+        );
+    }
+    if revision.image == current {
+        bail!("{name} is already running {}", revision.image);
+    }
+    Ok(revision)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::confirm::tests::ScriptedPrompter;
+    use crate::commands::up::apply::NoSleep;
+    use crate::commands::up::plan::test_env;
+    use crate::progress::SilentProgress;
+    use chrono::{NaiveDateTime, TimeZone};
+    use unisrv_api::models::{
+        DeploymentConfiguration, DeploymentDetailResponse, DeploymentInstanceEntry,
+        DeploymentListResponse, DeploymentState, InstanceState,
+    };
+    use unisrv_api::test_support::MockApiClient;
+    use uuid::Uuid;
+
+    fn entry(minute: u32, environment: &str, image: &str, outcome: Outcome) -> HistoryEntry {
+        HistoryEntry {
+            at: Utc.with_ymd_and_hms(2026, 10, 1, 12, minute, 0).unwrap(),
+            project: "demo".into(),
+            environment: environment.into(),
+            deployments: BTreeMap::from([("api".into(), image.into())]),
+            outcome,
+        }
+    }
+
+    fn history() -> Vec<HistoryEntry> {
+        vec![
+            entry(1, "prod", "api:1", Outcome::Applied),
+            entry(2, "staging", "api:9", Outcome::Applied),
+            entry(3, "prod", "api:2", Outcome::Applied),
+            entry(4, "prod", "api:3", Outcome::Failed),
+        ]
+    }
+
+    pub(super) fn detail(id: Uuid, image: &str, states: &[&str]) -> DeploymentDetailResponse {
+        DeploymentDetailResponse {
+            id,
+            name: "api".into(),
+            state: DeploymentState("running".into()),
+            configuration: DeploymentConfiguration {
+                replicas: 1,
+                region: "dev".into(),
+                container_image: image.into(),
+                args: None,
+                env: None,
+                vcpu_ratio: 0.25,
+                vcpu_count: 1,
+                memory_mb: 512,
+                instance_port: None,
+                init_containers: None,
+                sidecars: None,
+            },
+            metadata: serde_json::Value::Null,
+            service_id: None,
+            service_target_group: None,
+            network_id: Some(Uuid::nil()),
+            instances: states
+                .iter()
+                .map(|state| DeploymentInstanceEntry {
+                    id: Uuid::new_v4(),
+                    name: None,
+                    state: InstanceState(state.to_string()),
+                    node_id: Uuid::nil(),
+                    created_at: NaiveDateTime::default(),
+                })
+                .collect(),
+            backoff: None,
+            created_at: NaiveDateTime::default(),
+            updated_at: NaiveDateTime::default(),
+        }
+    }
+
+    fn listed(id: Uuid, image: &str) -> DeploymentListResponse {
+        DeploymentListResponse {
+            deployments: vec![DeploymentListEntry {
+                id,
+                name: "api".into(),
+                state: DeploymentState("running".into()),
+                replicas: 1,
+                container_image: image.into(),
+                created_at: NaiveDateTime::default(),
+                managed_by: Some("demo".into()),
+            }],
+        }
+    }
+
+    #[test]
+    fn revisions_are_this_environments_newest_first_with_stable_ids() {
+        let revisions = revisions(&history(), &test_env(), "api");
+        let images: Vec<&str> = revisions.iter().map(|r| r.image.as_str()).collect();
+        assert_eq!(images, ["api:3", "api:2", "api:1"]);
+        assert_eq!(revisions[0].id.len(), 8);
+        assert_eq!(revisions[0].id, revision_id(revisions[0].at, "api:3"));
+        assert_ne!(revisions[0].id, revisions[1].id);
+        assert_eq!(current_revision(&revisions, "api:2"), Some(1));
+        assert!(super::revisions(&history(), &test_env(), "worker").is_empty());
+    }
+
+    #[test]
+    fn picks_the_newest_other_applied_image_or_the_named_revision() {
+        let revisions = revisions(&history(), &test_env(), "api");
+        assert_eq!(
+            pick(&revisions, "api:2", None, "api").unwrap().image,
+            "api:1"
+        );
+
+        let first = &revisions[2].id;
+        assert_eq!(
+            pick(&revisions, "api:2", Some(&first[..5]), "api")
+                .unwrap()
+                .image,
+            "api:1"
+        );
+        let failed = pick(&revisions, "api:2", Some(&revisions[0].id), "api").unwrap_err();
+        assert!(failed.to_string().contains("failed to apply"), "{failed}");
+        let same = pick(&revisions, "api:2", Some(&revisions[1].id), "api").unwrap_err();
+        assert!(same.to_string().contains("already running"), "{same}");
+        let none = pick(&revisions[1..2], "api:2", None, "api").unwrap_err();
+        assert!(none.to_string().contains("no earlier image"), "{none}");
+    }
+
+    #[tokio::test]
+    async fn rollback_redeploys_the_previous_image_and_records_it() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = StateDir::new(tmp.path().join("state"));
+        for entry in history() {
+            state.record_history(&entry).unwrap();
+        }
+        let env = test_env();
+        let id = Uuid::new_v4();
+        let mock = MockApiClient::logged_in()
+            .with_list_deployments(Ok(listed(id, "api:2")))
+            .push_get_deployment(Ok(detail(id, "api:2", &["running"])))
+            .push_update_deployment(Ok(()))
+            .push_get_deployment(Ok(detail(id, "api:1", &["running"])))
+            .push_get_deployment(Ok(detail(id, "api:1", &["running"])))
+            .push_instance_logs(Ok(Vec::new()));
+        let prompter = ScriptedPrompter::default();
+        let request = Rollback {
+            name: "api",
+            to: None,
+            timeout: Duration::from_secs(60),
+            yes: true,
+        };
+
+        rollback_in(
+            &mock,
+            &env,
+            &state,
+            &request,
+            &Guard::new(None, &prompter),
+            &NoSleep,
+            &SilentProgress,
+        )
+        .await
+        .unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let (_, updated, request) = &calls.update_deployment_calls[0];
+        assert_eq!(*updated, id);
+        assert_eq!(request.configuration.container_image, "api:1");
+        assert_eq!(request.network_id, Some(Uuid::nil()));
+        assert!(prompter.asked.borrow().is_empty());
+        let recorded = state.history().pop().unwrap();
+        assert_eq!(recorded.deployments["api"], "api:1");
+        assert_eq!(recorded.outcome, Outcome::Applied);
+    }
+}
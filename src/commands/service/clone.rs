@@ -0,0 +1,310 @@
+//! `unisrv service clone <ref> [--name N] [--host H]... [--with-targets]` —
+//! provision a new service with the same locations and configuration as an
+//! existing one, e.g. a staging copy of a production routing table.
+//!
+//! Hosts are checked before anything is created, so a typo or a host bound
+//! elsewhere fails without leaving a half-made service behind. Target groups
+//! are only copied on request: sharing the source's instances is sometimes the
+//! point of a copy, but usually the clone gets its own deployments.
+
+use anyhow::{Context, Result, bail};
+use unisrv_api::ApiClient;
+use unisrv_api::models::{HTTPServiceConfig, ServiceInstanceTarget, ServiceProvisionRequest};
+
+use super::hosts::{find_claimed, manifest_hint};
+use super::resolve::pick_service;
+use crate::commands::up::plan::ResolvedEnvironment;
+
+/// Options for [`clone`] beyond the source reference.
+pub struct CloneOptions {
+    /// Name for the new service; defaults to `<source>-copy`.
+    pub name: Option<String>,
+    /// Claimed hosts to attach to the new service.
+    pub hosts: Vec<String>,
+    /// Also copy the source's instance targets.
+    pub with_targets: bool,
+    /// From `--region`, or the configured default.
+    pub region: String,
+}
+
+pub async fn clone(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    reference: &str,
+    opts: CloneOptions,
+) -> Result<()> {
+    let services = client.list_services(env.id).await?.services;
+    let source = pick_service(&services, reference)?;
+    let name = opts.name.unwrap_or_else(|| format!("{}-copy", source.name));
+    if services.iter().any(|s| s.name == name) {
+        bail!("a service named {name:?} already exists in {}", env.name);
+    }
+
+    let detail = client.get_service(env.id, source.id).await?;
+    let configuration: HTTPServiceConfig = serde_json::from_value(detail.configuration)
+        .with_context(|| {
+            format!(
+                "cannot clone {}: its configuration isn't one this CLI understands",
+                source.name
+            )
+        })?;
+
+    let mut hosts = Vec::with_capacity(opts.hosts.len());
+    for hostname in &opts.hosts {
+        let host = find_claimed(client, hostname).await?;
+        if host.service_id.is_some() {
+            bail!(
+                "{} is attached to another service; detach it there first \
+                 (`unisrv service host remove <service> {}`)",
+                host.host,
+                host.host
+            );
+        }
+        hosts.push(host);
+    }
+
+    let instance_targets = if opts.with_targets {
+        detail
+            .targets
+            .iter()
+            .map(|t| ServiceInstanceTarget {
+                instance_id: t.instance_id,
+                instance_port: t.instance_port,
+                group: t.target_group.clone(),
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let target_count = instance_targets.len();
+
+    let created = client
+        .provision_service(
+            env.id,
+            ServiceProvisionRequest {
+                region: opts.region,
+                name: name.clone(),
+                configuration,
+                instance_targets,
+                managed_by: None,
+            },
+        )
+        .await
+        .with_context(|| format!("failed to create {name}"))?;
+    println!(
+        "\u{2713} Cloned {} to {name} ({}).",
+        source.name, created.service_id
+    );
+    if target_count > 0 {
+        println!("  copied {target_count} instance target(s)");
+    }
+
+    for host in &hosts {
+        client
+            .link_host_to_service(host.id, created.service_id)
+            .await
+            .with_context(|| format!("{name} was created, but attaching {} failed", host.host))?;
+        println!("  attached {}", host.host);
+    }
+
+    eprintln!(
+        "{}",
+        console::style(format!(
+            "{name} isn't declared in unisrv.hcl; `unisrv up` deletes services it doesn't \
+             declare, so add a `service \"{name}\"` block to keep it."
+        ))
+        .yellow()
+    );
+    if !hosts.is_empty() {
+        manifest_hint();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::up::plan::test_env;
+    use chrono::NaiveDateTime;
+    use serde_json::json;
+    use unisrv_api::models::{
+        HostResponse, ServiceDetailResponse, ServiceListItem, ServiceListResponse,
+        ServiceProvisionResponse, ServiceTargetDetail,
+    };
+    use unisrv_api::test_support::MockApiClient;
+    use uuid::Uuid;
+
+    fn item(id: Uuid, name: &str) -> ServiceListItem {
+        ServiceListItem {
+            id,
+            name: name.into(),
+            base_host: format!("{name}-ab12.unisrv.dev"),
+            custom_hosts: vec![],
+            managed_by: None,
+        }
+    }
+
+    fn detail(id: Uuid, targets: Vec<ServiceTargetDetail>) -> ServiceDetailResponse {
+        ServiceDetailResponse {
+            id,
+            name: "web".into(),
+            base_host: "web-ab12.unisrv.dev".into(),
+            custom_hosts: vec!["shop.acme.com".into()],
+            configuration: json!({
+                "allow_http": true,
+                "locations": [
+                    {"path": "/", "target": {"type": "instance", "group": "web"}},
+                    {"path": "/docs", "target": {"type": "url", "url": "https://docs.acme.com"}},
+                ],
+            }),
+            environment_id: Uuid::nil(),
+            created_at: Default::default(),
+            updated_at: Default::default(),
+            providers: vec![],
+            targets,
+            statistics: None,
+        }
+    }
+
+    fn target(group: &str) -> ServiceTargetDetail {
+        ServiceTargetDetail {
+            id: Uuid::new_v4(),
+            instance_id: Uuid::new_v4(),
+            target_group: group.into(),
+            instance_port: 8080,
+            created_at: NaiveDateTime::default(),
+            health: None,
+        }
+    }
+
+    fn host(id: Uuid, name: &str, service_id: Option<Uuid>) -> HostResponse {
+        HostResponse {
+            id,
+            host: name.into(),
+            user_id: Uuid::nil(),
+            service_id,
+            certificate_type: None,
+            certificate_valid_until: None,
+            created_at: Default::default(),
+            updated_at: Default::default(),
+        }
+    }
+
+    fn opts(hosts: &[&str], with_targets: bool) -> CloneOptions {
+        CloneOptions {
+            name: Some("web-staging".into()),
+            hosts: hosts.iter().map(|h| h.to_string()).collect(),
+            with_targets,
+            region: "eu-central".into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn copies_configuration_and_attaches_the_new_host() {
+        let (src, new, h) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let mock = MockApiClient::logged_in()
+            .with_list_services(Ok(ServiceListResponse {
+                services: vec![item(src, "web")],
+            }))
+            .push_get_service(Ok(detail(src, vec![target("web")])))
+            .with_list_hosts(Ok(vec![host(h, "staging.acme.com", None)]))
+            .push_provision_service(Ok(ServiceProvisionResponse { service_id: new }))
+            .push_link_host(Ok(host(h, "staging.acme.com", Some(new))));
+
+        clone(
+            &mock,
+            &test_env(),
+            "web",
+            opts(&["staging.acme.com"], false),
+        )
+        .await
+        .unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let (_, req) = &calls.provision_service_calls[0];
+        assert_eq!(req.name, "web-staging");
+        assert_eq!(req.region, "eu-central");
+        assert!(req.configuration.allow_http);
+        assert_eq!(req.configuration.locations.len(), 2);
+        assert_eq!(req.configuration.locations[1].path, "/docs");
+        assert!(req.instance_targets.is_empty(), "targets are opt-in");
+        assert_eq!(calls.link_host_calls, vec![(h, new)]);
+    }
+
+    #[tokio::test]
+    async fn with_targets_copies_instance_targets() {
+        let src = Uuid::new_v4();
+        let source_target = target("api");
+        let mock = MockApiClient::logged_in()
+            .with_list_services(Ok(ServiceListResponse {
+                services: vec![item(src, "web")],
+            }))
+            .push_get_service(Ok(detail(src, vec![source_target.clone()])))
+            .push_provision_service(Ok(ServiceProvisionResponse {
+                service_id: Uuid::new_v4(),
+            }));
+
+        clone(&mock, &test_env(), "web", opts(&[], true))
+            .await
+            .unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        assert_eq!(
+            calls.provision_service_calls[0].1.instance_targets,
+            vec![ServiceInstanceTarget {
+                instance_id: source_target.instance_id,
+                instance_port: 8080,
+                group: "api".into(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn default_name_is_derived_and_must_be_free() {
+        let src = Uuid::new_v4();
+        let mock = MockApiClient::logged_in().with_list_services(Ok(ServiceListResponse {
+            services: vec![item(src, "web"), item(Uuid::new_v4(), "web-copy")],
+        }));
+        let options = CloneOptions {
+            name: None,
+            hosts: vec![],
+            with_targets: false,
+            region: "dev".into(),
+        };
+
+        let err = clone(&mock, &test_env(), "web", options).await.unwrap_err();
+
+        assert!(format!("{err:#}").contains("\"web-copy\" already exists"));
+        assert!(
+            mock.calls
+                .lock()
+                .unwrap()
+                .provision_service_calls
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn bound_host_is_refused_before_anything_is_created() {
+        let src = Uuid::new_v4();
+        let mock = MockApiClient::logged_in()
+            .with_list_services(Ok(ServiceListResponse {
+                services: vec![item(src, "web")],
+            }))
+            .push_get_service(Ok(detail(src, vec![])))
+            .with_list_hosts(Ok(vec![host(Uuid::new_v4(), "shop.acme.com", Some(src))]));
+
+        let err = clone(&mock, &test_env(), "web", opts(&["shop.acme.com"], false))
+            .await
+            .unwrap_err();
+
+        assert!(format!("{err:#}").contains("another service"), "{err:#}");
+        assert!(
+            mock.calls
+                .lock()
+                .unwrap()
+                .provision_service_calls
+                .is_empty()
+        );
+    }
+}
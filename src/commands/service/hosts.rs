@@ -0,0 +1,218 @@
+//! `unisrv service host add|remove <service> <host>` — bind a claimed host to a
+//! service, or release it.
+//!
+//! These act on the live service directly. For a service declared in
+//! `unisrv.hcl`, `up` reconciles hosts back to the manifest's `hosts` list, so
+//! the change only sticks if it's mirrored there — we say so after each change.
+
+use anyhow::{Result, bail};
+use unisrv_api::ApiClient;
+use unisrv_api::models::HostResponse;
+
+use super::resolve::resolve_service;
+use crate::commands::confirm::Guard;
+use crate::commands::host::normalize_host;
+use crate::commands::up::plan::ResolvedEnvironment;
+use crate::commands::up::preflight::has_valid_cert;
+
+/// Attach the claimed `hostname` to the service `reference`.
+pub async fn add(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    reference: &str,
+    hostname: &str,
+) -> Result<()> {
+    let service = resolve_service(client, env.id, reference).await?;
+    let host = find_claimed(client, hostname).await?;
+
+    match host.service_id {
+        Some(id) if id == service.id => {
+            println!("{} is already attached to {}.", host.host, service.name);
+            return Ok(());
+        }
+        Some(_) => bail!(
+            "{} is attached to another service; detach it there first \
+             (`unisrv service host remove <service> {}`)",
+            host.host,
+            host.host
+        ),
+        None => {}
+    }
+
+    client.link_host_to_service(host.id, service.id).await?;
+    println!("\u{2713} Attached {} to {}.", host.host, service.name);
+    if !has_valid_cert(&host, chrono::Utc::now()) {
+        eprintln!(
+            "{}",
+            console::style(format!(
+                "! {} has no valid certificate yet; HTTPS requests will fail until one is issued \
+                 (re-run `unisrv host claim {}`)",
+                host.host, host.host
+            ))
+            .yellow()
+        );
+    }
+    manifest_hint();
+    Ok(())
+}
+
+/// Detach `hostname` from the service `reference`.
+pub async fn remove(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    reference: &str,
+    hostname: &str,
+    guard: &Guard<'_>,
+) -> Result<()> {
+    let service = resolve_service(client, env.id, reference).await?;
+    let host = find_claimed(client, hostname).await?;
+    if host.service_id != Some(service.id) {
+        bail!("{} is not attached to {}", host.host, service.name);
+    }
+    if !guard.confirm(
+        &format!("detach {} from {}", host.host, service.name),
+        &host.host,
+    )? {
+        println!("Aborted.");
+        return Ok(());
+    }
+
+    client.unlink_host_from_service(host.id, service.id).await?;
+    println!("\u{2713} Detached {} from {}.", host.host, service.name);
+    manifest_hint();
+    Ok(())
+}
+
+/// The user's claimed host matching `hostname` (case- and trailing-dot-
+/// insensitively).
+pub(super) async fn find_claimed(client: &dyn ApiClient, hostname: &str) -> Result<HostResponse> {
+    let wanted = normalize_host(hostname);
+    let hosts = client.list_hosts().await?;
+    match hosts
+        .into_iter()
+        .find(|h| normalize_host(&h.host) == wanted)
+    {
+        Some(host) => Ok(host),
+        None => bail!("{wanted} is not claimed; claim it first with `unisrv host claim {wanted}`"),
+    }
+}
+
+pub(super) fn manifest_hint() {
+    eprintln!(
+        "{}",
+        console::style(
+            "If this service is managed by unisrv.hcl, update its `hosts` too — \
+             `unisrv up` reconciles hosts to the manifest."
+        )
+        .dim()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::up::plan::test_env;
+    use unisrv_api::models::{ServiceListItem, ServiceListResponse};
+    use unisrv_api::test_support::MockApiClient;
+    use uuid::Uuid;
+
+    fn host(id: Uuid, name: &str, service_id: Option<Uuid>) -> HostResponse {
+        HostResponse {
+            id,
+            host: name.into(),
+            user_id: Uuid::nil(),
+            service_id,
+            certificate_type: None,
+            certificate_valid_until: None,
+            created_at: Default::default(),
+            updated_at: Default::default(),
+        }
+    }
+
+    fn with_service(svc: Uuid, hosts: Vec<HostResponse>) -> MockApiClient {
+        MockApiClient::logged_in()
+            .with_list_services(Ok(ServiceListResponse {
+                services: vec![ServiceListItem {
+                    id: svc,
+                    name: "web".into(),
+                    base_host: "web-ab12.unisrv.dev".into(),
+                    custom_hosts: vec![],
+                    managed_by: None,
+                }],
+            }))
+            .with_list_hosts(Ok(hosts))
+    }
+
+    #[tokio::test]
+    async fn add_links_an_unattached_host_matching_case_insensitively() {
+        let (svc, h) = (Uuid::new_v4(), Uuid::new_v4());
+        let mock = with_service(svc, vec![host(h, "shop.acme.com", None)])
+            .push_link_host(Ok(host(h, "shop.acme.com", Some(svc))));
+
+        add(&mock, &test_env(), "web", "Shop.Acme.com.")
+            .await
+            .unwrap();
+
+        assert_eq!(mock.calls.lock().unwrap().link_host_calls, vec![(h, svc)]);
+    }
+
+    #[tokio::test]
+    async fn add_is_a_no_op_when_already_attached() {
+        let (svc, h) = (Uuid::new_v4(), Uuid::new_v4());
+        let mock = with_service(svc, vec![host(h, "shop.acme.com", Some(svc))]);
+
+        add(&mock, &test_env(), "web", "shop.acme.com")
+            .await
+            .unwrap();
+
+        assert!(mock.calls.lock().unwrap().link_host_calls.is_empty());
+    }
+
+    #[tokio::test]
+    async fn add_refuses_a_host_bound_elsewhere_or_unclaimed() {
+        let (svc, h) = (Uuid::new_v4(), Uuid::new_v4());
+        let mock = with_service(svc, vec![host(h, "shop.acme.com", Some(Uuid::new_v4()))]);
+        let err = add(&mock, &test_env(), "web", "shop.acme.com")
+            .await
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("another service"), "{err:#}");
+
+        let mock = with_service(svc, vec![]);
+        let err = add(&mock, &test_env(), "web", "new.acme.com")
+            .await
+            .unwrap_err();
+        assert!(
+            format!("{err:#}").contains("unisrv host claim new.acme.com"),
+            "{err:#}"
+        );
+    }
+
+    #[tokio::test]
+    async fn remove_unlinks_only_a_host_attached_to_this_service() {
+        let (svc, h) = (Uuid::new_v4(), Uuid::new_v4());
+        let mock = with_service(svc, vec![host(h, "shop.acme.com", Some(svc))])
+            .push_unlink_host(Ok(host(h, "shop.acme.com", None)));
+        remove(
+            &mock,
+            &test_env(),
+            "web",
+            "shop.acme.com",
+            &Guard::unprotected(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(mock.calls.lock().unwrap().unlink_host_calls, vec![(h, svc)]);
+
+        let mock = with_service(svc, vec![host(h, "shop.acme.com", None)]);
+        let err = remove(
+            &mock,
+            &test_env(),
+            "web",
+            "shop.acme.com",
+            &Guard::unprotected(),
+        )
+        .await
+        .unwrap_err();
+        assert!(format!("{err:#}").contains("not attached"), "{err:#}");
+    }
+}
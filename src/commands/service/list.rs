@@ -0,0 +1,98 @@
+//! `unisrv service list` — the services of an environment with the hosts
+//! they answer on.
+
+use anyhow::Result;
+use comfy_table::{Cell, Color};
+use unisrv_api::ApiClient;
+use unisrv_api::models::ServiceListItem;
+
+use crate::commands::output::Output;
+use crate::commands::table::{Column, Row, TableOptions, draw_table, id_text, sort};
+use crate::commands::ui::{cell_with_color, colors_enabled};
+use crate::commands::up::plan::ResolvedEnvironment;
+
+pub async fn list(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    output: &Output,
+    table: &TableOptions,
+) -> Result<()> {
+    let mut services = client.list_services(env.id).await?.services;
+    sort(&mut services, table.sort, "services")?;
+
+    match output {
+        Output::Json | Output::Jq(_) => return output.print_json(&services),
+        Output::Template(template) => {
+            print!("{}", template.render_all(&services)?);
+            return Ok(());
+        }
+        Output::Table => {}
+    }
+
+    let rendered = render_table(&services, colors_enabled(), table)?;
+    if services.is_empty() {
+        println!(
+            "No services in {}. `unisrv up` creates them from unisrv.toml.",
+            env.name
+        );
+        return Ok(());
+    }
+    println!("{rendered}");
+    Ok(())
+}
+
+impl Row for ServiceListItem {
+    fn name(&self) -> Option<&str> {
+        Some(&self.name)
+    }
+}
+
+fn render_table(
+    services: &[ServiceListItem],
+    use_color: bool,
+    options: &TableOptions,
+) -> Result<String> {
+    let columns = [
+        Column::new("id", "ID", |s: &ServiceListItem| {
+            Cell::new(id_text(&s.id, options))
+        }),
+        Column::new("name", "NAME", |s: &ServiceListItem| Cell::new(&s.name)),
+        Column::new("hosts", "HOSTS", |s: &ServiceListItem| {
+            let hosts = std::iter::once(&s.base_host)
+                .chain(&s.custom_hosts)
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join("\n");
+            Cell::new(hosts)
+        }),
+        Column::new("managed-by", "MANAGED BY", |s: &ServiceListItem| {
+            match &s.managed_by {
+                Some(project) => cell_with_color(project.clone(), None, use_color),
+                None => cell_with_color("—".to_string(), Some(Color::DarkGrey), use_color),
+            }
+        }),
+    ];
+    draw_table(services, &columns, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn table_lists_the_base_host_before_custom_ones() {
+        let services = [ServiceListItem {
+            id: Uuid::new_v4(),
+            name: "web".into(),
+            base_host: "web-ab12.unisrv.dev".into(),
+            custom_hosts: vec!["example.com".into()],
+            managed_by: Some("shop".into()),
+        }];
+        let table = render_table(&services, false, &TableOptions::default()).unwrap();
+        let base = table.find("web-ab12.unisrv.dev").unwrap();
+        let custom = table.find("example.com").unwrap();
+        assert!(base < custom, "{table}");
+        assert!(table.contains("shop"), "{table}");
+    }
+}
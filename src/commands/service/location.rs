@@ -0,0 +1,471 @@
+//! `unisrv service location add <service> <path> [--group g | --url u |
+//! --redirect-to u] [--rewrite p | --strip-prefix] [--websocket] [--grpc]
+//! [--set-header name=value]...` — route another path prefix on a live
+//! service, e.g. a `/old` path redirected at the edge.
+//!
+//! `unisrv service location cache <service> <path> --ttl 1h
+//! [--stale-while-revalidate 10m] | --off` — have the edge cache one
+//! location's responses, e.g. a `/static` path of fingerprinted assets,
+//! without putting a CDN in front of the service.
+//!
+//! The edge keeps a response for `--ttl`, then for `--stale-while-revalidate`
+//! longer serves it while a fresh copy is fetched, so a slow origin isn't
+//! felt on expiry. Redirect locations are answered at the edge already and
+//! have nothing to cache.
+//!
+//! Like `service tls`, this edits the live service: `up` reconciles it back to
+//! the location's `cache` block in `unisrv.hcl`.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use unisrv_api::ApiClient;
+use unisrv_api::models::{CachePolicy, HTTPLocation, HTTPLocationTarget, HTTPServiceConfig};
+
+use super::resolve::resolve_service;
+use crate::commands::up::config::{invalid_header, invalid_location_path, invalid_url_target};
+use crate::commands::up::diff::service::{cache_label, target_label};
+use crate::commands::up::plan::ResolvedEnvironment;
+
+/// Parse `--set-header`, e.g. "X-Frame-Options=DENY". The value may contain
+/// '='.
+pub fn parse_header(s: &str) -> Result<(String, String), String> {
+    let (name, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("{s:?} is not NAME=VALUE"))?;
+    match invalid_header(name, value) {
+        Some(reason) => Err(reason),
+        None => Ok((name.to_string(), value.to_string())),
+    }
+}
+
+/// Append `location` to the service's routes. Its path must be new; to change
+/// an existing location, edit `unisrv.hcl` and run `up`.
+pub async fn add(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    reference: &str,
+    location: HTTPLocation,
+) -> Result<()> {
+    let path = location.path.clone();
+    if let Some(reason) = invalid_location_path(&path) {
+        bail!("cannot add {path}: {reason}");
+    }
+    match &location.target {
+        HTTPLocationTarget::Url { url } | HTTPLocationTarget::Redirect { url } => {
+            if let Some(reason) = invalid_url_target(url) {
+                bail!("cannot add {path}: {reason}");
+            }
+        }
+        HTTPLocationTarget::Instance {
+            rewrite: Some(rewrite),
+            ..
+        } => {
+            if let Some(reason) = invalid_location_path(rewrite) {
+                bail!("cannot add {path}: --rewrite {rewrite:?}: {reason}");
+            }
+        }
+        HTTPLocationTarget::Instance { rewrite: None, .. } => {}
+    }
+    if matches!(location.target, HTTPLocationTarget::Redirect { .. })
+        && (location.websocket || location.grpc)
+    {
+        bail!(
+            "cannot add {path}: a redirect is answered at the edge, so there's no \
+             WebSocket or gRPC connection to proxy"
+        );
+    }
+    let service = resolve_service(client, env.id, reference).await?;
+    let detail = client.get_service(env.id, service.id).await?;
+    let mut config: HTTPServiceConfig =
+        serde_json::from_value(detail.configuration).with_context(|| {
+            format!(
+                "cannot add a location to {}: its configuration isn't one this CLI understands",
+                service.name
+            )
+        })?;
+    if config.locations.iter().any(|l| l.path == path) {
+        bail!(
+            "{} already has a location {path:?}; edit it in unisrv.hcl and run `unisrv up`",
+            service.name
+        );
+    }
+
+    let target = target_label(&location.target);
+    config.locations.push(location);
+    client
+        .update_service(env.id, service.id, config)
+        .await
+        .with_context(|| format!("failed to update {}", service.name))?;
+    println!(
+        "\u{2713} Added {path} \u{2192} {target} to {}.",
+        service.name
+    );
+    eprintln!(
+        "{}",
+        console::style(
+            "If this service is managed by unisrv.hcl, add the location there too — \
+             `unisrv up` reconciles configuration to the manifest."
+        )
+        .dim()
+    );
+    Ok(())
+}
+
+/// Route `path` to `target`, setting `headers` on its responses; the other
+/// options, e.g. `websocket`, are left at their defaults.
+pub fn new_location(
+    path: String,
+    target: HTTPLocationTarget,
+    headers: BTreeMap<String, String>,
+) -> HTTPLocation {
+    HTTPLocation {
+        path,
+        override_404: None,
+        headers,
+        websocket: false,
+        grpc: false,
+        cache: None,
+        target,
+    }
+}
+
+/// What to do with a location's cache policy.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CacheChange {
+    Set {
+        ttl: Duration,
+        stale_while_revalidate: Option<Duration>,
+    },
+    Off,
+}
+
+impl CacheChange {
+    fn policy(self) -> Option<CachePolicy> {
+        match self {
+            CacheChange::Set {
+                ttl,
+                stale_while_revalidate,
+            } => Some(CachePolicy {
+                ttl_secs: ttl.as_secs(),
+                stale_while_revalidate_secs: stale_while_revalidate.map(|d| d.as_secs()),
+            }),
+            CacheChange::Off => None,
+        }
+    }
+}
+
+pub async fn cache(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    reference: &str,
+    path: &str,
+    change: CacheChange,
+) -> Result<()> {
+    if let CacheChange::Set { ttl, .. } = change
+        && ttl.as_secs() == 0
+    {
+        bail!("--ttl must be at least 1s; pass --off to stop caching");
+    }
+    let service = resolve_service(client, env.id, reference).await?;
+    let detail = client.get_service(env.id, service.id).await?;
+    let mut config: HTTPServiceConfig =
+        serde_json::from_value(detail.configuration).with_context(|| {
+            format!(
+                "cannot change caching for {}: its configuration isn't one this CLI understands",
+                service.name
+            )
+        })?;
+
+    let policy = change.policy();
+    let location = find_location(&mut config, path, policy.is_some())
+        .with_context(|| format!("cannot cache {path} on {}", service.name))?;
+    let before = location.cache;
+    if before == policy {
+        println!(
+            "{path} on {} already has cache {}.",
+            service.name,
+            cache_label(policy.as_ref())
+        );
+        return Ok(());
+    }
+    location.cache = policy;
+
+    client
+        .update_service(env.id, service.id, config)
+        .await
+        .with_context(|| format!("failed to update {}", service.name))?;
+    println!(
+        "\u{2713} Cache for {path} on {}: {} -> {}.",
+        service.name,
+        cache_label(before.as_ref()),
+        cache_label(policy.as_ref())
+    );
+    eprintln!(
+        "{}",
+        console::style(
+            "If this service is managed by unisrv.hcl, set the location's `cache` block too — \
+             `unisrv up` reconciles configuration to the manifest."
+        )
+        .dim()
+    );
+    Ok(())
+}
+
+/// The location at exactly `path`, which mustn't be a redirect if it's to
+/// be `cached`.
+fn find_location<'a>(
+    config: &'a mut HTTPServiceConfig,
+    path: &str,
+    cached: bool,
+) -> Result<&'a mut HTTPLocation> {
+    let paths: Vec<_> = config.locations.iter().map(|l| l.path.clone()).collect();
+    let Some(location) = config.locations.iter_mut().find(|l| l.path == path) else {
+        bail!("no location {path:?}; it has {}", paths.join(", "));
+    };
+    if cached && matches!(location.target, HTTPLocationTarget::Redirect { .. }) {
+        bail!("{path} redirects at the edge, so there's nothing to cache");
+    }
+    Ok(location)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::up::plan::test_env;
+    use serde_json::json;
+    use unisrv_api::models::{ServiceDetailResponse, ServiceListItem, ServiceListResponse};
+    use unisrv_api::test_support::MockApiClient;
+    use uuid::Uuid;
+
+    fn with_locations(id: Uuid) -> MockApiClient {
+        MockApiClient::logged_in()
+            .with_list_services(Ok(ServiceListResponse {
+                services: vec![ServiceListItem {
+                    id,
+                    name: "web".into(),
+                    base_host: "web-ab12.unisrv.dev".into(),
+                    custom_hosts: vec![],
+                    managed_by: None,
+                }],
+            }))
+            .push_get_service(Ok(ServiceDetailResponse {
+                id,
+                name: "web".into(),
+                base_host: "web-ab12.unisrv.dev".into(),
+                custom_hosts: vec![],
+                configuration: json!({
+                    "allow_http": false,
+                    "locations": [
+                        {"path": "/", "target": {"type": "instance", "group": "web"}},
+                        {"path": "/static", "target": {"type": "instance", "group": "web"}},
+                        {"path": "/old", "target": {"type": "redirect", "url": "https://x.io"}},
+                    ],
+                }),
+                environment_id: Uuid::nil(),
+                created_at: Default::default(),
+                updated_at: Default::default(),
+                providers: vec![],
+                targets: vec![],
+                statistics: None,
+            }))
+    }
+
+    #[tokio::test]
+    async fn adds_a_redirect_with_headers() {
+        let mock = with_locations(Uuid::new_v4()).push_update_service(Ok(()));
+        let headers = BTreeMap::from([parse_header("Cache-Control=max-age=60").unwrap()]);
+        let target = HTTPLocationTarget::Redirect {
+            url: "https://example.com/new".into(),
+        };
+        add(
+            &mock,
+            &test_env(),
+            "web",
+            new_location("/moved".into(), target, headers),
+        )
+        .await
+        .unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let (_, _, config) = &calls.update_service_calls[0];
+        assert_eq!(config.locations.len(), 4);
+        let added = &config.locations[3];
+        assert_eq!(added.path, "/moved");
+        assert_eq!(added.headers["Cache-Control"], "max-age=60");
+        assert!(matches!(
+            &added.target,
+            HTTPLocationTarget::Redirect { url } if url == "https://example.com/new"
+        ));
+    }
+
+    #[tokio::test]
+    async fn add_refuses_an_existing_path_or_a_relative_redirect() {
+        let group = HTTPLocationTarget::Instance {
+            group: "web".into(),
+            rewrite: None,
+        };
+        let mock = with_locations(Uuid::new_v4());
+        let err = add(
+            &mock,
+            &test_env(),
+            "web",
+            new_location("/static".into(), group, BTreeMap::new()),
+        )
+        .await
+        .unwrap_err();
+        assert!(format!("{err:#}").contains("already has"), "{err:#}");
+        assert!(mock.calls.lock().unwrap().update_service_calls.is_empty());
+
+        let redirect = HTTPLocationTarget::Redirect { url: "/new".into() };
+        let err = add(
+            &mock,
+            &test_env(),
+            "web",
+            new_location("/x".into(), redirect, BTreeMap::new()),
+        )
+        .await
+        .unwrap_err();
+        assert!(format!("{err:#}").contains("absolute URL"), "{err:#}");
+    }
+
+    #[tokio::test]
+    async fn sends_the_rewrite_with_the_instance_target() {
+        let mock = with_locations(Uuid::new_v4()).push_update_service(Ok(()));
+        let target = HTTPLocationTarget::Instance {
+            group: "api".into(),
+            rewrite: Some("/v2".into()),
+        };
+        add(
+            &mock,
+            &test_env(),
+            "web",
+            new_location("/api".into(), target, BTreeMap::new()),
+        )
+        .await
+        .unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let (_, _, config) = &calls.update_service_calls[0];
+        assert!(matches!(
+            &config.locations[3].target,
+            HTTPLocationTarget::Instance { group, rewrite: Some(rewrite) }
+                if group == "api" && rewrite == "/v2"
+        ));
+    }
+
+    #[tokio::test]
+    async fn a_rewrite_must_be_a_path() {
+        let target = HTTPLocationTarget::Instance {
+            group: "api".into(),
+            rewrite: Some("v2".into()),
+        };
+        let mock = with_locations(Uuid::new_v4());
+        let err = add(
+            &mock,
+            &test_env(),
+            "web",
+            new_location("/api".into(), target, BTreeMap::new()),
+        )
+        .await
+        .unwrap_err();
+        assert!(format!("{err:#}").contains("--rewrite"), "{err:#}");
+    }
+
+    #[tokio::test]
+    async fn adds_a_websocket_and_grpc_location_but_not_on_a_redirect() {
+        let mock = with_locations(Uuid::new_v4()).push_update_service(Ok(()));
+        let target = HTTPLocationTarget::Instance {
+            group: "web".into(),
+            rewrite: None,
+        };
+        let mut location = new_location("/live".into(), target, BTreeMap::new());
+        location.websocket = true;
+        location.grpc = true;
+        add(&mock, &test_env(), "web", location).await.unwrap();
+        {
+            let calls = mock.calls.lock().unwrap();
+            let added = &calls.update_service_calls[0].2.locations[3];
+            assert!(added.websocket && added.grpc);
+        }
+
+        let target = HTTPLocationTarget::Redirect {
+            url: "https://example.com".into(),
+        };
+        let mut location = new_location("/ws".into(), target, BTreeMap::new());
+        location.websocket = true;
+        let err = add(&mock, &test_env(), "web", location).await.unwrap_err();
+        assert!(format!("{err:#}").contains("redirect"), "{err:#}");
+    }
+
+    #[test]
+    fn header_flags_are_validated() {
+        assert!(
+            parse_header("X-Frame-Options")
+                .unwrap_err()
+                .contains("NAME=VALUE")
+        );
+        assert!(
+            parse_header("Bad Header=x")
+                .unwrap_err()
+                .contains("not a valid")
+        );
+        assert!(
+            parse_header("Connection=close")
+                .unwrap_err()
+                .contains("managed by the proxy")
+        );
+    }
+
+    #[tokio::test]
+    async fn writes_the_policy_onto_that_location_only() {
+        let id = Uuid::new_v4();
+        let mock = with_locations(id).push_update_service(Ok(()));
+        let change = CacheChange::Set {
+            ttl: Duration::from_secs(3600),
+            stale_while_revalidate: Some(Duration::from_secs(600)),
+        };
+        cache(&mock, &test_env(), "web", "/static", change)
+            .await
+            .unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let (_, service_id, config) = &calls.update_service_calls[0];
+        assert_eq!(*service_id, id);
+        assert_eq!(config.locations[0].cache, None);
+        assert_eq!(
+            config.locations[1].cache,
+            Some(CachePolicy {
+                ttl_secs: 3600,
+                stale_while_revalidate_secs: Some(600),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_paths_and_redirects_are_refused() {
+        let change = CacheChange::Set {
+            ttl: Duration::from_secs(60),
+            stale_while_revalidate: None,
+        };
+        let mock = with_locations(Uuid::new_v4());
+        let err = cache(&mock, &test_env(), "web", "/assets", change)
+            .await
+            .unwrap_err();
+        assert!(
+            format!("{err:#}").contains("it has /, /static, /old"),
+            "{err:#}"
+        );
+
+        let mock = with_locations(Uuid::new_v4());
+        let err = cache(&mock, &test_env(), "web", "/old", change)
+            .await
+            .unwrap_err();
+        assert!(
+            format!("{err:#}").contains("redirects at the edge"),
+            "{err:#}"
+        );
+        assert!(mock.calls.lock().unwrap().update_service_calls.is_empty());
+    }
+}
@@ -0,0 +1,17 @@
+//! `unisrv service` — inspect and adjust the services of an environment.
+
+pub mod clone;
+pub mod hosts;
+pub mod list;
+pub mod location;
+pub mod new;
+pub mod protect;
+pub mod reach;
+pub mod resolve;
+pub mod run;
+pub mod show;
+pub mod stats;
+pub mod target;
+pub mod targets;
+pub mod tls;
+pub mod update;
@@ -0,0 +1,214 @@
+//! `unisrv service new http [name] --from-cert-host <host>` — an HTTP service
+//! for a host that's already claimed and has its certificate, in one step.
+//!
+//! Done by hand this is `host claim`, waiting out the certificate, creating
+//! the service and `service host add`, in that order. Here the host is looked
+//! up first and has to be free and hold a valid certificate, so nothing is
+//! created for a host that couldn't be served yet. It also isn't held to the
+//! single-label rule `unisrv.hcl` applies to `*.unisrv.dev` hosts: that rule
+//! is about claiming, and this host is already claimed, so a
+//! `api.eu.example.com` works as well as an apex.
+//!
+//! The service routes everything to the `default` target group; point it at
+//! instances with `unisrv service target add`.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result, bail};
+use chrono::Utc;
+use unisrv_api::ApiClient;
+use unisrv_api::models::{
+    HTTPLocation, HTTPLocationTarget, HTTPServiceConfig, ServiceProvisionRequest, TlsPolicy,
+};
+
+use super::hosts::{find_claimed, manifest_hint};
+use crate::commands::up::defaults::{
+    DEFAULT_ALLOW_HTTP, DEFAULT_LOCATION_PATH, DEFAULT_TARGET_GROUP,
+};
+use crate::commands::up::plan::ResolvedEnvironment;
+use crate::commands::up::preflight::has_valid_cert;
+
+/// Options for [`http`].
+pub struct NewHttpOptions {
+    /// Name for the service; defaults to the host's first label.
+    pub name: Option<String>,
+    /// The claimed host to create the service for.
+    pub from_cert_host: String,
+    /// From `--region`, or the configured default.
+    pub region: String,
+    /// `--compression on|off`; the platform's default without it.
+    pub compression: Option<bool>,
+}
+
+/// `unisrv service new http`.
+pub async fn http(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    opts: NewHttpOptions,
+) -> Result<()> {
+    let host = find_claimed(client, &opts.from_cert_host).await?;
+    if host.service_id.is_some() {
+        bail!(
+            "{} is attached to another service; detach it there first \
+             (`unisrv service host remove <service> {}`)",
+            host.host,
+            host.host
+        );
+    }
+    if !has_valid_cert(&host, Utc::now()) {
+        bail!(
+            "{} has no valid certificate yet; issue one with `unisrv host cert {}` and try again",
+            host.host,
+            host.host
+        );
+    }
+
+    let name = opts.name.unwrap_or_else(|| default_name(&host.host));
+    let services = client.list_services(env.id).await?.services;
+    if services.iter().any(|s| s.name == name) {
+        bail!(
+            "a service named {name:?} already exists in {}; pick another with --name",
+            env.name
+        );
+    }
+
+    let created = client
+        .provision_service(
+            env.id,
+            ServiceProvisionRequest {
+                region: opts.region,
+                name: name.clone(),
+                configuration: HTTPServiceConfig {
+                    compression: opts.compression,
+                    ..catch_all()
+                },
+                instance_targets: Vec::new(),
+                managed_by: None,
+            },
+        )
+        .await
+        .with_context(|| format!("failed to create {name}"))?;
+    client
+        .link_host_to_service(host.id, created.service_id)
+        .await
+        .with_context(|| format!("{name} was created, but attaching {} failed", host.host))?;
+    println!(
+        "\u{2713} Created {name} ({}) serving https://{}.",
+        created.service_id, host.host
+    );
+    println!(
+        "  Route it to an instance with `unisrv service target add {name} <instance> --port <port>`."
+    );
+    manifest_hint();
+    Ok(())
+}
+
+/// `shop` for `shop.example.com`.
+fn default_name(host: &str) -> String {
+    host.split('.').next().unwrap_or(host).to_string()
+}
+
+/// Every path to the default target group, as `unisrv.hcl` does for a
+/// service that declares no locations.
+fn catch_all() -> HTTPServiceConfig {
+    HTTPServiceConfig {
+        locations: vec![HTTPLocation {
+            path: DEFAULT_LOCATION_PATH.to_string(),
+            override_404: None,
+            headers: BTreeMap::new(),
+            websocket: false,
+            grpc: false,
+            cache: None,
+            target: HTTPLocationTarget::Instance {
+                group: DEFAULT_TARGET_GROUP.to_string(),
+                rewrite: None,
+            },
+        }],
+        allow_http: DEFAULT_ALLOW_HTTP,
+        basic_auth: Vec::new(),
+        allow_ips: Vec::new(),
+        tls: TlsPolicy::default(),
+        compression: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::up::plan::test_env;
+    use unisrv_api::models::{
+        CertificateType, HostResponse, ServiceListResponse, ServiceProvisionResponse,
+    };
+    use unisrv_api::test_support::MockApiClient;
+    use uuid::Uuid;
+
+    fn host(id: Uuid, name: &str, certificate_type: Option<CertificateType>) -> HostResponse {
+        HostResponse {
+            id,
+            host: name.into(),
+            user_id: Uuid::nil(),
+            service_id: None,
+            certificate_type,
+            certificate_valid_until: Some(Utc::now() + chrono::Duration::days(60)),
+            created_at: Default::default(),
+            updated_at: Default::default(),
+        }
+    }
+
+    fn opts(name: Option<&str>) -> NewHttpOptions {
+        NewHttpOptions {
+            name: name.map(String::from),
+            from_cert_host: "API.eu.acme.com.".into(),
+            region: "eu-central".into(),
+            compression: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn creates_the_service_and_attaches_the_host() {
+        let (h, new) = (Uuid::new_v4(), Uuid::new_v4());
+        let mock = MockApiClient::logged_in()
+            .with_list_hosts(Ok(vec![host(
+                h,
+                "api.eu.acme.com",
+                Some(CertificateType::LetsEncrypt),
+            )]))
+            .with_list_services(Ok(ServiceListResponse { services: vec![] }))
+            .push_provision_service(Ok(ServiceProvisionResponse { service_id: new }))
+            .push_link_host(Ok(host(h, "api.eu.acme.com", None)));
+
+        let opts = NewHttpOptions {
+            compression: Some(true),
+            ..opts(None)
+        };
+        http(&mock, &test_env(), opts).await.unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let (_, req) = &calls.provision_service_calls[0];
+        assert_eq!(req.name, "api");
+        assert_eq!(req.region, "eu-central");
+        assert_eq!(req.configuration.locations, catch_all().locations);
+        assert_eq!(req.configuration.compression, Some(true));
+        assert_eq!(calls.link_host_calls, vec![(h, new)]);
+    }
+
+    #[tokio::test]
+    async fn nothing_is_created_for_a_host_without_a_certificate() {
+        let mock = MockApiClient::logged_in().with_list_hosts(Ok(vec![host(
+            Uuid::new_v4(),
+            "api.eu.acme.com",
+            None,
+        )]));
+        let err = http(&mock, &test_env(), opts(Some("api")))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("unisrv host cert"), "{err:#}");
+        assert!(
+            mock.calls
+                .lock()
+                .unwrap()
+                .provision_service_calls
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,236 @@
+//! `unisrv service protect <ref> [--basic-auth user:pass]... [--allow-ip
+//! cidr]... [--no-basic-auth] [--no-allow-ip]` — put a service behind HTTP
+//! basic auth and/or an IP allowlist at the edge. With no flags, prints what
+//! currently protects it.
+//!
+//! The given credentials or addresses replace the service's current ones;
+//! `--no-basic-auth` and `--no-allow-ip` clear them. Like `service tls`, this
+//! edits the live service: `up` reconciles the configuration back to
+//! `unisrv.hcl`, so the change only sticks once the service's `basic_auth`
+//! and `allow_ips` say the same.
+
+use anyhow::{Context, Result};
+use unisrv_api::ApiClient;
+use unisrv_api::models::{BasicAuthCredential, HTTPServiceConfig};
+
+use super::resolve::resolve_service;
+use crate::commands::up::plan::ResolvedEnvironment;
+
+/// Requested changes; `None` leaves a setting as it is, an empty list clears
+/// it.
+#[derive(Debug, Default)]
+pub struct ProtectChange {
+    pub basic_auth: Option<Vec<BasicAuthCredential>>,
+    pub allow_ips: Option<Vec<String>>,
+}
+
+impl ProtectChange {
+    fn is_empty(&self) -> bool {
+        self.basic_auth.is_none() && self.allow_ips.is_none()
+    }
+
+    fn apply(&self, config: &mut HTTPServiceConfig) {
+        if let Some(credentials) = &self.basic_auth {
+            config.basic_auth = credentials.clone();
+        }
+        if let Some(ips) = &self.allow_ips {
+            config.allow_ips = ips.clone();
+        }
+    }
+}
+
+/// Parse `--basic-auth`, e.g. "alice:s3cret". The password may contain ':'.
+pub fn parse_credential(s: &str) -> Result<BasicAuthCredential, String> {
+    let (username, password) = s
+        .split_once(':')
+        .ok_or_else(|| format!("{s:?} is not USER:PASSWORD"))?;
+    if username.is_empty() {
+        return Err("the username must not be empty".into());
+    }
+    if password.is_empty() {
+        return Err(format!("the password for {username:?} must not be empty"));
+    }
+    Ok(BasicAuthCredential {
+        username: username.to_string(),
+        password: password.to_string(),
+    })
+}
+
+/// Parse `--allow-ip`, an address or CIDR block.
+pub fn parse_allow_ip(s: &str) -> Result<String, String> {
+    s.parse::<cidr::IpCidr>()
+        .map(|_| s.to_string())
+        .map_err(|_| {
+            format!(
+                "{s:?} is not an IP address or CIDR block (e.g. 203.0.113.0/24 or 2001:db8::/32)"
+            )
+        })
+}
+
+pub async fn protect(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    reference: &str,
+    change: ProtectChange,
+) -> Result<()> {
+    let service = resolve_service(client, env.id, reference).await?;
+    let detail = client.get_service(env.id, service.id).await?;
+    let mut config: HTTPServiceConfig =
+        serde_json::from_value(detail.configuration).with_context(|| {
+            format!(
+                "cannot change protection for {}: its configuration isn't one this CLI understands",
+                service.name
+            )
+        })?;
+
+    if change.is_empty() {
+        println!("{}: {}", service.name, summary(&config));
+        return Ok(());
+    }
+
+    let before = config.clone();
+    change.apply(&mut config);
+    if config == before {
+        println!("{} already has {}.", service.name, summary(&config));
+        return Ok(());
+    }
+
+    client
+        .update_service(env.id, service.id, config.clone())
+        .await
+        .with_context(|| format!("failed to update {}", service.name))?;
+    println!(
+        "\u{2713} Updated protection for {}: {}.",
+        service.name,
+        summary(&config)
+    );
+    eprintln!(
+        "{}",
+        console::style(
+            "If this service is managed by unisrv.hcl, update its `basic_auth` and `allow_ips` \
+             too — `unisrv up` reconciles configuration to the manifest."
+        )
+        .dim()
+    );
+    Ok(())
+}
+
+/// One-line protection, e.g. `basic auth for alice, allowed from
+/// 203.0.113.0/24`. Never the passwords.
+pub fn summary(config: &HTTPServiceConfig) -> String {
+    let mut parts = Vec::new();
+    if !config.basic_auth.is_empty() {
+        let users: Vec<&str> = config
+            .basic_auth
+            .iter()
+            .map(|c| c.username.as_str())
+            .collect();
+        parts.push(format!("basic auth for {}", users.join(", ")));
+    }
+    if !config.allow_ips.is_empty() {
+        parts.push(format!("allowed from {}", config.allow_ips.join(", ")));
+    }
+    if parts.is_empty() {
+        return "open to everyone".into();
+    }
+    parts.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::up::plan::test_env;
+    use serde_json::json;
+    use unisrv_api::models::{ServiceDetailResponse, ServiceListItem, ServiceListResponse};
+    use unisrv_api::test_support::MockApiClient;
+    use uuid::Uuid;
+
+    fn mock(id: Uuid, configuration: serde_json::Value) -> MockApiClient {
+        MockApiClient::logged_in()
+            .with_list_services(Ok(ServiceListResponse {
+                services: vec![ServiceListItem {
+                    id,
+                    name: "web".into(),
+                    base_host: "web-ab12.unisrv.dev".into(),
+                    custom_hosts: vec![],
+                    managed_by: None,
+                }],
+            }))
+            .push_get_service(Ok(ServiceDetailResponse {
+                id,
+                name: "web".into(),
+                base_host: "web-ab12.unisrv.dev".into(),
+                custom_hosts: vec![],
+                configuration,
+                environment_id: Uuid::nil(),
+                created_at: Default::default(),
+                updated_at: Default::default(),
+                providers: vec![],
+                targets: vec![],
+                statistics: None,
+            }))
+    }
+
+    fn config() -> serde_json::Value {
+        json!({
+            "allow_http": false,
+            "allow_ips": ["10.0.0.0/8"],
+            "locations": [{"path": "/", "target": {"type": "instance", "group": "web"}}],
+        })
+    }
+
+    #[tokio::test]
+    async fn sets_basic_auth_and_clears_the_allowlist() {
+        let id = Uuid::new_v4();
+        let mock = mock(id, config()).push_update_service(Ok(()));
+
+        let change = ProtectChange {
+            basic_auth: Some(vec![parse_credential("alice:s3:cret").unwrap()]),
+            allow_ips: Some(vec![]),
+        };
+        protect(&mock, &test_env(), "web", change).await.unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let (_, service_id, updated) = &calls.update_service_calls[0];
+        assert_eq!(*service_id, id);
+        assert_eq!(updated.basic_auth[0].username, "alice");
+        assert_eq!(updated.basic_auth[0].password, "s3:cret");
+        assert!(updated.allow_ips.is_empty());
+        assert_eq!(updated.locations.len(), 1, "routing is left alone");
+    }
+
+    #[tokio::test]
+    async fn leaves_unmentioned_settings_and_skips_a_no_op() {
+        let mock = mock(Uuid::new_v4(), config());
+        let change = ProtectChange {
+            allow_ips: Some(vec![parse_allow_ip("10.0.0.0/8").unwrap()]),
+            ..Default::default()
+        };
+        protect(&mock, &test_env(), "web", change).await.unwrap();
+        assert!(mock.calls.lock().unwrap().update_service_calls.is_empty());
+    }
+
+    #[test]
+    fn flags_are_validated() {
+        assert!(
+            parse_credential("alice")
+                .unwrap_err()
+                .contains("USER:PASSWORD")
+        );
+        assert!(parse_credential(":pw").unwrap_err().contains("username"));
+        assert!(parse_credential("alice:").unwrap_err().contains("password"));
+        assert!(parse_allow_ip("2001:db8::/32").is_ok());
+        assert!(parse_allow_ip("office").unwrap_err().contains("not an IP"));
+    }
+
+    #[test]
+    fn summary_names_users_but_not_passwords() {
+        let mut cfg: HTTPServiceConfig = serde_json::from_value(config()).unwrap();
+        cfg.basic_auth = vec![parse_credential("alice:s3cret").unwrap()];
+        let summary = summary(&cfg);
+        assert_eq!(summary, "basic auth for alice, allowed from 10.0.0.0/8");
+        cfg.basic_auth.clear();
+        cfg.allow_ips.clear();
+        assert_eq!(super::summary(&cfg), "open to everyone");
+    }
+}
@@ -0,0 +1,195 @@
+//! Whether a service's edge can reach an instance it routes to.
+//!
+//! A service's providers — the edge proxies — forward to a target directly
+//! when they run on the target's node, and over the instance's private network
+//! address otherwise. An instance that shares no node with a provider and is
+//! on no network has no route from the edge: the target registers fine and
+//! every request through it fails with a 502. `service target add` refuses
+//! that up front, and `up` warns about it after a rollout.
+
+use anyhow::Result;
+use unisrv_api::models::{InstanceDetailResponse, ServiceDetailResponse};
+use unisrv_api::{ApiClient, ApiError};
+use uuid::Uuid;
+
+use crate::commands::instance::list::is_active;
+
+/// What [`assess`] found.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reach {
+    Reachable,
+    /// Routing to it can only fail; the reason says why and how to fix it.
+    Unreachable(String),
+    /// Probably fine, but nothing can confirm it yet.
+    Doubtful(String),
+}
+
+/// Can `service`'s edge reach `instance` (called `label` in messages)?
+pub fn assess(
+    service: &ServiceDetailResponse,
+    instance: &InstanceDetailResponse,
+    label: &str,
+) -> Reach {
+    if !is_active(&instance.state.0) {
+        return Reach::Doubtful(format!(
+            "{label} is {}, so {} will have nothing to route to until it runs again",
+            instance.state.0, service.name
+        ));
+    }
+    if service
+        .providers
+        .iter()
+        .any(|p| p.node_id == instance.node_id)
+    {
+        return Reach::Reachable;
+    }
+    match (&instance.network_id, &instance.network_ip) {
+        (Some(_), Some(_)) => Reach::Reachable,
+        (Some(_), None) => Reach::Doubtful(format!(
+            "{label} hasn't been given an address on its network yet, so {} can't route to it until it has one",
+            service.name
+        )),
+        (None, _) if service.providers.is_empty() => Reach::Doubtful(format!(
+            "{} has no providers yet and {label} is on no network; unless a provider is placed on \
+             {label}'s node, the edge won't be able to reach it",
+            service.name
+        )),
+        (None, _) => Reach::Unreachable(format!(
+            "{label} runs on a node without any of {}'s providers and is on no network, so the edge \
+             has no route to it and requests would fail with 502; attach it to a network first \
+             (`unisrv network attach <network> {label}`)",
+            service.name
+        )),
+    }
+}
+
+/// Every target of `service` the edge can't reach, or may not, as warning
+/// lines. Targets whose instance is gone are skipped — the platform removes
+/// those itself.
+pub async fn unreachable_targets(
+    client: &dyn ApiClient,
+    env_id: Uuid,
+    service: &ServiceDetailResponse,
+) -> Result<Vec<String>> {
+    let mut seen: Vec<Uuid> = Vec::new();
+    let mut warnings = Vec::new();
+    for target in &service.targets {
+        if seen.contains(&target.instance_id) {
+            continue;
+        }
+        seen.push(target.instance_id);
+        let instance = match client
+            .get_instance(env_id, target.instance_id, false, false)
+            .await
+        {
+            Ok(instance) => instance,
+            Err(ApiError::Server { status: 404, .. }) => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let label = instance
+            .name
+            .clone()
+            .unwrap_or_else(|| instance.id.to_string());
+        match assess(service, &instance, &label) {
+            Reach::Reachable => {}
+            Reach::Unreachable(reason) | Reach::Doubtful(reason) => warnings.push(reason),
+        }
+    }
+    Ok(warnings)
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+    use unisrv_api::models::{InstanceState, ServiceProviderDetail};
+
+    pub(in crate::commands) fn service(provider_nodes: &[Uuid]) -> ServiceDetailResponse {
+        ServiceDetailResponse {
+            id: Uuid::new_v4(),
+            name: "web".into(),
+            base_host: "web-prod.unisrv.dev".into(),
+            custom_hosts: vec![],
+            configuration: serde_json::json!({}),
+            environment_id: Uuid::nil(),
+            created_at: Default::default(),
+            updated_at: Default::default(),
+            providers: provider_nodes
+                .iter()
+                .map(|&node_id| ServiceProviderDetail {
+                    id: Uuid::new_v4(),
+                    node_id,
+                    created_at: Default::default(),
+                })
+                .collect(),
+            targets: vec![],
+            statistics: None,
+        }
+    }
+
+    pub(in crate::commands) fn instance(
+        node_id: Uuid,
+        network_ip: Option<&str>,
+    ) -> InstanceDetailResponse {
+        InstanceDetailResponse {
+            id: Uuid::new_v4(),
+            name: Some("api-1".into()),
+            node_id,
+            state: InstanceState("running".into()),
+            exit_code: None,
+            exit_reason: None,
+            configuration: serde_json::json!({}),
+            created_at: NaiveDateTime::default(),
+            updated_at: NaiveDateTime::default(),
+            network_id: network_ip.map(|_| Uuid::new_v4()),
+            network_ip: network_ip.map(Into::into),
+            deployment: None,
+            service_targets: None,
+            proxied_ports: None,
+            resources: None,
+            annotations: None,
+        }
+    }
+
+    #[test]
+    fn co_located_or_networked_instances_are_reachable() {
+        let node = Uuid::new_v4();
+        let svc = service(&[node]);
+        assert_eq!(
+            assess(&svc, &instance(node, None), "api-1"),
+            Reach::Reachable
+        );
+        assert_eq!(
+            assess(&svc, &instance(Uuid::new_v4(), Some("10.0.0.4")), "api-1"),
+            Reach::Reachable
+        );
+    }
+
+    #[test]
+    fn an_instance_with_no_route_is_unreachable() {
+        let svc = service(&[Uuid::new_v4()]);
+        let Reach::Unreachable(reason) = assess(&svc, &instance(Uuid::new_v4(), None), "api-1")
+        else {
+            panic!("expected unreachable");
+        };
+        assert!(reason.contains("502"), "{reason}");
+        assert!(
+            reason.contains("unisrv network attach <network> api-1"),
+            "{reason}"
+        );
+    }
+
+    #[test]
+    fn stopped_instances_and_provider_less_services_are_only_doubtful() {
+        let mut stopped = instance(Uuid::new_v4(), None);
+        stopped.state = InstanceState("exited".into());
+        assert!(matches!(
+            assess(&service(&[Uuid::new_v4()]), &stopped, "api-1"),
+            Reach::Doubtful(_)
+        ));
+        assert!(matches!(
+            assess(&service(&[]), &instance(Uuid::new_v4(), None), "api-1"),
+            Reach::Doubtful(_)
+        ));
+    }
+}
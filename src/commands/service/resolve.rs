@@ -0,0 +1,95 @@
+//! Resolve a user-supplied service reference (UUID or name) within an
+//! environment. Service names are unique per environment, so unlike instances
+//! there's no ambiguity to report — just a miss, which lists what does exist.
+
+use anyhow::{Result, bail};
+use unisrv_api::ApiClient;
+use unisrv_api::models::ServiceListItem;
+use uuid::Uuid;
+
+pub async fn resolve_service(
+    client: &dyn ApiClient,
+    env_id: Uuid,
+    input: &str,
+) -> Result<ServiceListItem> {
+    if input.trim().is_empty() {
+        bail!("no service reference given");
+    }
+    let services = client.list_services(env_id).await?.services;
+    pick_service(&services, input).cloned()
+}
+
+/// Find `input` among an already-fetched listing, for callers that need the
+/// rest of the listing too.
+pub fn pick_service<'a>(
+    services: &'a [ServiceListItem],
+    input: &str,
+) -> Result<&'a ServiceListItem> {
+    let input = input.trim();
+    let id = Uuid::parse_str(input).ok();
+    if let Some(found) = services
+        .iter()
+        .find(|s| Some(s.id) == id || s.name == input)
+    {
+        return Ok(found);
+    }
+    if services.is_empty() {
+        bail!("no service {input:?}: this environment has no services");
+    }
+    let names = services
+        .iter()
+        .map(|s| s.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    bail!("no service {input:?} in this environment (available: {names})")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use unisrv_api::models::ServiceListResponse;
+    use unisrv_api::test_support::MockApiClient;
+
+    fn item(id: Uuid, name: &str) -> ServiceListItem {
+        ServiceListItem {
+            id,
+            name: name.into(),
+            base_host: format!("{name}-env.unisrv.dev"),
+            custom_hosts: vec![],
+            managed_by: None,
+        }
+    }
+
+    fn mock_with(services: Vec<ServiceListItem>) -> MockApiClient {
+        MockApiClient::logged_in().with_list_services(Ok(ServiceListResponse { services }))
+    }
+
+    #[tokio::test]
+    async fn resolves_by_name_and_by_uuid() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let services = vec![item(a, "web"), item(b, "api")];
+
+        let by_name = resolve_service(&mock_with(services.clone()), Uuid::nil(), "api")
+            .await
+            .unwrap();
+        assert_eq!(by_name.id, b);
+
+        let by_id = resolve_service(&mock_with(services), Uuid::nil(), &a.to_string())
+            .await
+            .unwrap();
+        assert_eq!(by_id.name, "web");
+    }
+
+    #[tokio::test]
+    async fn a_miss_lists_the_available_services() {
+        let mock = mock_with(vec![item(Uuid::new_v4(), "web")]);
+        let err = resolve_service(&mock, Uuid::nil(), "wbe")
+            .await
+            .unwrap_err();
+        let msg = format!("{err:#}");
+        assert!(
+            msg.contains("wbe") && msg.contains("available: web"),
+            "{msg}"
+        );
+    }
+}
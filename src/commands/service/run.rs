@@ -0,0 +1,159 @@
+//! Entry point for the `service` command group: resolve the environment the
+//! same way the instance commands do, announce it, then dispatch.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use unisrv_api::ApiClient;
+use unisrv_api::models::HTTPLocation;
+
+use super::clone::CloneOptions;
+use super::location::CacheChange;
+use super::new::NewHttpOptions;
+use super::protect::ProtectChange;
+use super::tls::TlsChange;
+use super::update::UpdateChange;
+use super::{
+    clone, hosts, list, location, new, protect, show, stats, target, targets, tls, update,
+};
+use crate::commands::confirm::Guard;
+use crate::commands::instance::run::{announce_environment, current_environment};
+use crate::commands::output::Output;
+use crate::commands::table::TableOptions;
+
+/// What the user asked the service group to do.
+pub enum ServiceAction {
+    List {
+        output: Output,
+        table: TableOptions,
+    },
+    Show {
+        reference: String,
+        output: Output,
+        /// Redraw this often until interrupted.
+        watch: Option<Duration>,
+    },
+    Targets {
+        reference: String,
+        unhealthy: bool,
+        output: Output,
+    },
+    Stats {
+        reference: String,
+        window: String,
+        watch: bool,
+    },
+    Clone {
+        reference: String,
+        options: CloneOptions,
+    },
+    NewHttp {
+        options: NewHttpOptions,
+    },
+    Update {
+        reference: String,
+        change: UpdateChange,
+    },
+    Tls {
+        reference: String,
+        change: TlsChange,
+    },
+    Protect {
+        reference: String,
+        change: ProtectChange,
+    },
+    HostAdd {
+        reference: String,
+        host: String,
+    },
+    HostRemove {
+        reference: String,
+        host: String,
+    },
+    LocationAdd {
+        reference: String,
+        location: HTTPLocation,
+    },
+    LocationCache {
+        reference: String,
+        path: String,
+        change: CacheChange,
+    },
+    TargetAdd {
+        reference: String,
+        instance: String,
+        port: u16,
+        group: String,
+    },
+}
+
+pub async fn run(
+    client: &dyn ApiClient,
+    env_flag: Option<&str>,
+    action: ServiceAction,
+) -> Result<()> {
+    let env = current_environment(client, env_flag).await?;
+    let machine = match &action {
+        ServiceAction::List { output, .. }
+        | ServiceAction::Show { output, .. }
+        | ServiceAction::Targets { output, .. } => output.is_machine(),
+        _ => false,
+    };
+    if !machine {
+        announce_environment(&env);
+    }
+
+    match action {
+        ServiceAction::List { output, table } => list::list(client, &env, &output, &table).await,
+        ServiceAction::Show {
+            reference,
+            output,
+            watch,
+        } => show::show(client, &env, &reference, &output, watch).await,
+        ServiceAction::Targets {
+            reference,
+            unhealthy,
+            output,
+        } => targets::targets(client, &env, &reference, unhealthy, &output).await,
+        ServiceAction::Stats {
+            reference,
+            window,
+            watch,
+        } => stats::stats(client, &env, &reference, &window, watch).await,
+        ServiceAction::Clone { reference, options } => {
+            clone::clone(client, &env, &reference, options).await
+        }
+        ServiceAction::NewHttp { options } => new::http(client, &env, options).await,
+        ServiceAction::Update { reference, change } => {
+            update::update(client, &env, &reference, change).await
+        }
+        ServiceAction::LocationCache {
+            reference,
+            path,
+            change,
+        } => location::cache(client, &env, &reference, &path, change).await,
+        ServiceAction::Tls { reference, change } => {
+            tls::tls(client, &env, &reference, change).await
+        }
+        ServiceAction::Protect { reference, change } => {
+            protect::protect(client, &env, &reference, change).await
+        }
+        ServiceAction::HostAdd { reference, host } => {
+            hosts::add(client, &env, &reference, &host).await
+        }
+        ServiceAction::HostRemove { reference, host } => {
+            let guard = Guard::for_env(&env)?;
+            hosts::remove(client, &env, &reference, &host, &guard).await
+        }
+        ServiceAction::LocationAdd {
+            reference,
+            location,
+        } => location::add(client, &env, &reference, location).await,
+        ServiceAction::TargetAdd {
+            reference,
+            instance,
+            port,
+            group,
+        } => target::add(client, &env, &reference, &instance, port, &group).await,
+    }
+}
@@ -0,0 +1,252 @@
+//! `unisrv service show <ref>` — a service's hosts and routing at a glance.
+
+use std::fmt::Write;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use unisrv_api::ApiClient;
+use unisrv_api::models::{HTTPServiceConfig, ServiceDetailResponse};
+use unisrv_api::session::redact;
+
+use super::resolve::resolve_service;
+use super::{protect, targets, tls};
+use crate::commands::output::Output;
+use crate::commands::ui::format_relative;
+use crate::commands::up::diff::service::{cache_label, compression_label, target_label};
+use crate::commands::up::plan::ResolvedEnvironment;
+use crate::commands::watch::watch;
+
+/// Print the service `reference`, redrawing it every `watch_every` when
+/// given.
+pub async fn show(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    reference: &str,
+    output: &Output,
+    watch_every: Option<Duration>,
+) -> Result<()> {
+    let service = resolve_service(client, env.id, reference).await?;
+    if let Some(interval) = watch_every {
+        return watch(interval, async || {
+            let detail = client.get_service(env.id, service.id).await?;
+            Ok(render(&detail, chrono::Utc::now().naive_utc()))
+        })
+        .await;
+    }
+    let detail = masked(client.get_service(env.id, service.id).await?);
+    match output {
+        Output::Json | Output::Jq(_) => output.print_json(&detail),
+        Output::Template(template) => {
+            println!("{}", template.render(&detail)?);
+            Ok(())
+        }
+        Output::Table => {
+            print!("{}", render(&detail, chrono::Utc::now().naive_utc()));
+            Ok(())
+        }
+    }
+}
+
+/// `detail` with its basic-auth passwords blanked, as recorded sessions
+/// blank them, so printing it doesn't leak them.
+fn masked(mut detail: ServiceDetailResponse) -> ServiceDetailResponse {
+    detail.configuration = redact(detail.configuration);
+    detail
+}
+
+/// Plain-text summary. Pure so it can be asserted on without a terminal.
+fn render(detail: &ServiceDetailResponse, now: NaiveDateTime) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "{} ({})", detail.name, detail.id);
+    let _ = writeln!(out, "  base host: {}", detail.base_host);
+    if detail.custom_hosts.is_empty() {
+        let _ = writeln!(out, "  hosts:     none");
+    } else {
+        let _ = writeln!(out, "  hosts:");
+        for host in &detail.custom_hosts {
+            let _ = writeln!(out, "    {host}");
+        }
+    }
+    // An unparseable configuration (a newer backend shape) still shows the
+    // rest rather than failing the whole command.
+    match serde_json::from_value::<HTTPServiceConfig>(detail.configuration.clone()) {
+        Ok(config) => {
+            let _ = writeln!(out, "  tls:       {}", tls::summary(&config));
+            let _ = writeln!(out, "  access:    {}", protect::summary(&config));
+            let _ = writeln!(
+                out,
+                "  compression: {}",
+                compression_label(config.compression)
+            );
+            let _ = writeln!(out, "  locations:");
+            let width = config.locations.iter().map(|l| l.path.len()).max();
+            for loc in &config.locations {
+                let protocols = [(loc.websocket, " [websocket]"), (loc.grpc, " [grpc]")]
+                    .into_iter()
+                    .filter_map(|(on, tag)| on.then_some(tag))
+                    .collect::<String>();
+                let cache = match &loc.cache {
+                    Some(cache) => format!(" [cache {}]", cache_label(Some(cache))),
+                    None => String::new(),
+                };
+                let headers = loc
+                    .headers
+                    .iter()
+                    .map(|(name, value)| format!(" [{name}: {value}]"))
+                    .collect::<String>();
+                let _ = writeln!(
+                    out,
+                    "    {:<width$}  \u{2192} {}{protocols}{cache}{headers}",
+                    loc.path,
+                    target_label(&loc.target),
+                    width = width.unwrap_or(0)
+                );
+            }
+        }
+        Err(_) => {
+            let _ = writeln!(out, "  locations: (unrecognised configuration)");
+        }
+    }
+    if detail.targets.is_empty() {
+        let _ = writeln!(out, "  targets:   none");
+    } else {
+        let _ = writeln!(out, "  targets:");
+        let width = detail.targets.iter().map(|t| t.target_group.len()).max();
+        for target in &detail.targets {
+            let health = target.health.as_ref();
+            let (status, _) = targets::format_health(health);
+            let checked = match health.and_then(|h| h.last_checked_at) {
+                Some(_) => format!(" (checked {})", targets::last_checked(health, now)),
+                None => String::new(),
+            };
+            let _ = writeln!(
+                out,
+                "    {:<width$}  {}:{}  {status}{checked}",
+                target.target_group,
+                &target.instance_id.to_string()[..8],
+                target.instance_port,
+                width = width.unwrap_or(0)
+            );
+        }
+    }
+    let _ = writeln!(
+        out,
+        "  created:   {}",
+        format_relative(detail.created_at, now.and_utc())
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    fn detail(custom_hosts: Vec<&str>, configuration: serde_json::Value) -> ServiceDetailResponse {
+        ServiceDetailResponse {
+            id: Uuid::nil(),
+            name: "web".into(),
+            base_host: "web-ab12.unisrv.dev".into(),
+            custom_hosts: custom_hosts.into_iter().map(String::from).collect(),
+            configuration,
+            environment_id: Uuid::nil(),
+            created_at: Default::default(),
+            updated_at: Default::default(),
+            providers: vec![],
+            targets: vec![],
+            statistics: None,
+        }
+    }
+
+    #[test]
+    fn lists_bound_hosts_and_locations() {
+        let config = json!({
+            "allow_http": false,
+            "tls": {"min_version": "1.3", "hsts": true},
+            "compression": false,
+            "locations": [
+                {"path": "/", "target": {"type": "instance", "group": "web"}, "websocket": true},
+                {"path": "/docs", "target": {"type": "url", "url": "https://docs.example.com"},
+                 "headers": {"X-Frame-Options": "DENY"}},
+                {"path": "/static", "target": {"type": "instance", "group": "web"},
+                 "cache": {"ttl_secs": 3600, "stale_while_revalidate_secs": 600}},
+            ],
+        });
+        let out = render(
+            &detail(vec!["shop.acme.com", "www.acme.com"], config),
+            NaiveDateTime::default(),
+        );
+        assert!(out.contains("base host: web-ab12.unisrv.dev"), "{out}");
+        assert!(
+            out.contains("tls:       min TLS 1.3, HSTS, HTTP redirected to HTTPS"),
+            "{out}"
+        );
+        assert!(out.contains("  compression: off\n"), "{out}");
+        assert!(out.contains("  access:    open to everyone\n"), "{out}");
+        assert!(
+            out.contains("    shop.acme.com\n    www.acme.com\n"),
+            "{out}"
+        );
+        assert!(
+            out.contains("/        \u{2192} instance(web) [websocket]\n"),
+            "{out}"
+        );
+        assert!(
+            out.contains(
+                "/docs    \u{2192} url(https://docs.example.com) [X-Frame-Options: DENY]\n"
+            ),
+            "{out}"
+        );
+        assert!(
+            out.contains("/static  \u{2192} instance(web) [cache 1h, stale 10m]\n"),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn printed_configuration_leaves_out_passwords() {
+        let config = json!({
+            "allow_http": false,
+            "basic_auth": [{"username": "alice", "password": "s3cret"}],
+            "locations": [],
+        });
+        let json = serde_json::to_string(&masked(detail(vec![], config))).unwrap();
+        assert!(!json.contains("s3cret"), "{json}");
+        assert!(json.contains("alice"), "{json}");
+    }
+
+    #[test]
+    fn says_none_without_custom_hosts_and_survives_odd_config() {
+        let out = render(
+            &detail(vec![], json!({"new": "shape"})),
+            NaiveDateTime::default(),
+        );
+        assert!(out.contains("hosts:     none"), "{out}");
+        assert!(out.contains("unrecognised configuration"), "{out}");
+    }
+
+    #[test]
+    fn lists_targets_with_health() {
+        use unisrv_api::models::{ServiceTargetDetail, TargetHealth};
+        let now = NaiveDateTime::default() + chrono::Duration::minutes(5);
+        let mut d = detail(vec![], json!({"allow_http": false, "locations": []}));
+        d.targets = vec![ServiceTargetDetail {
+            id: Uuid::nil(),
+            instance_id: Uuid::nil(),
+            target_group: "web".into(),
+            instance_port: 8080,
+            created_at: NaiveDateTime::default(),
+            health: Some(TargetHealth {
+                status: "draining".into(),
+                last_checked_at: Some(now - chrono::Duration::minutes(1)),
+            }),
+        }];
+        let out = render(&d, now);
+        assert!(
+            out.contains("    web  00000000:8080  draining (checked a minute ago)"),
+            "{out}"
+        );
+    }
+}
@@ -0,0 +1,332 @@
+//! `unisrv service stats <ref>` — request rate, error rate and latency
+//! percentiles for a service, per location and per target.
+//!
+//! With `--watch` the view is redrawn every [`WATCH_INTERVAL`] until
+//! interrupted (see [`crate::commands::watch`]).
+
+use std::time::Duration;
+
+use anyhow::{Result, bail};
+use comfy_table::{Attribute, Cell, Color, Table};
+use unisrv_api::ApiClient;
+use unisrv_api::models::{RequestMetrics, ServiceMetricsResponse};
+
+use super::resolve::resolve_service;
+use crate::commands::locale::{self, Locale};
+use crate::commands::ui::{cell_with_color, colors_enabled, new_table};
+use crate::commands::up::plan::ResolvedEnvironment;
+use crate::commands::up::ready::parse_duration;
+use crate::commands::watch;
+
+/// How often `--watch` refreshes.
+pub const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The shortest window the API aggregates over.
+const MIN_WINDOW_SECS: u64 = 60;
+/// The longest window the API keeps metrics for.
+const MAX_WINDOW_SECS: u64 = 30 * 24 * 3600;
+
+/// Show metrics for the service referenced by `reference` over the trailing
+/// `window` (e.g. `15m`, `1h`, `7d`).
+pub async fn stats(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    reference: &str,
+    window: &str,
+    watch: bool,
+) -> Result<()> {
+    parse_window(window)?;
+    let service = resolve_service(client, env.id, reference).await?;
+    let use_color = colors_enabled();
+
+    if !watch {
+        let metrics = client
+            .get_service_metrics(env.id, service.id, window)
+            .await?;
+        print!(
+            "{}",
+            render(
+                &service.name,
+                window,
+                &metrics,
+                locale::current(),
+                use_color
+            )
+        );
+        return Ok(());
+    }
+
+    watch::watch(WATCH_INTERVAL, async || {
+        let metrics = client
+            .get_service_metrics(env.id, service.id, window)
+            .await?;
+        Ok(render(
+            &service.name,
+            window,
+            &metrics,
+            locale::current(),
+            use_color,
+        ))
+    })
+    .await
+}
+
+/// Validate a window like `30m`, `1h` or `7d` and return it in seconds.
+fn parse_window(window: &str) -> Result<u64> {
+    let secs = parse_duration(window)
+        .map_err(|e| anyhow::anyhow!("invalid --window: {e}"))?
+        .as_secs();
+    if !(MIN_WINDOW_SECS..=MAX_WINDOW_SECS).contains(&secs) {
+        bail!("--window must be between 1m and 30d, got {window:?}");
+    }
+    Ok(secs)
+}
+
+/// Render the heading plus location and target tables. Pure so it can be
+/// asserted on without a terminal; colour and locale are the caller's.
+fn render(
+    service: &str,
+    window: &str,
+    metrics: &ServiceMetricsResponse,
+    locale: &Locale,
+    use_color: bool,
+) -> String {
+    let total: u64 = metrics.locations.iter().map(|l| l.metrics.requests).sum();
+    let mut out = format!(
+        "{service} — last {window}, {} requests\n",
+        locale.format_int(total)
+    );
+    if total == 0 {
+        out.push_str(&format!("No traffic in the last {window}.\n"));
+        return out;
+    }
+
+    let rows = metrics
+        .locations
+        .iter()
+        .map(|l| (l.path.clone(), &l.metrics));
+    out.push_str(&format!(
+        "{}\n",
+        table("LOCATION", rows, metrics.window_seconds, locale, use_color)
+    ));
+
+    if !metrics.targets.is_empty() {
+        let rows = metrics.targets.iter().map(|t| {
+            let label = match t.instance_id {
+                Some(id) => format!("{} ({})", t.group, &id.to_string()[..8]),
+                None => t.group.clone(),
+            };
+            (label, &t.metrics)
+        });
+        out.push_str(&format!(
+            "{}\n",
+            table("TARGET", rows, metrics.window_seconds, locale, use_color)
+        ));
+    }
+    out
+}
+
+fn table<'a>(
+    first: &str,
+    rows: impl Iterator<Item = (String, &'a RequestMetrics)>,
+    window_seconds: u64,
+    locale: &Locale,
+    use_color: bool,
+) -> Table {
+    let mut table = new_table();
+    table.set_header(
+        [first, "REQ/S", "ERRORS", "P50", "P95", "P99"]
+            .map(|h| Cell::new(h).add_attribute(Attribute::Bold)),
+    );
+    for (label, m) in rows {
+        let rate = m.requests as f64 / window_seconds.max(1) as f64;
+        let (errors, error_color) = format_error_rate(m, locale);
+        table.add_row(vec![
+            Cell::new(label),
+            Cell::new(locale.format_decimal(rate, 2)),
+            cell_with_color(errors, error_color, use_color),
+            Cell::new(format_latency(m.p50_ms, locale)),
+            Cell::new(format_latency(m.p95_ms, locale)),
+            Cell::new(format_latency(m.p99_ms, locale)),
+        ]);
+    }
+    table
+}
+
+/// Error percentage, red from 5% and yellow from 1%.
+fn format_error_rate(m: &RequestMetrics, locale: &Locale) -> (String, Option<Color>) {
+    if m.requests == 0 {
+        return ("\u{2014}".to_string(), Some(Color::DarkGrey));
+    }
+    let pct = m.errors as f64 * 100.0 / m.requests as f64;
+    let color = match pct {
+        p if p >= 5.0 => Some(Color::Red),
+        p if p >= 1.0 => Some(Color::Yellow),
+        _ => None,
+    };
+    (format!("{}%", locale.format_decimal(pct, 1)), color)
+}
+
+fn format_latency(ms: Option<f64>, locale: &Locale) -> String {
+    match ms {
+        None => "\u{2014}".to_string(),
+        Some(ms) if ms >= 1000.0 => format!("{} s", locale.format_decimal(ms / 1000.0, 2)),
+        Some(ms) => format!("{} ms", locale.format_decimal(ms, 0)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use unisrv_api::models::{
+        LocationMetrics, ServiceListItem, ServiceListResponse, TargetMetrics,
+    };
+    use unisrv_api::test_support::MockApiClient;
+    use uuid::Uuid;
+
+    fn metrics(requests: u64, errors: u64, p50: f64) -> RequestMetrics {
+        RequestMetrics {
+            requests,
+            errors,
+            p50_ms: Some(p50),
+            p95_ms: Some(p50 * 4.0),
+            p99_ms: Some(p50 * 40.0),
+        }
+    }
+
+    fn response() -> ServiceMetricsResponse {
+        ServiceMetricsResponse {
+            window_seconds: 3600,
+            locations: vec![
+                LocationMetrics {
+                    path: "/".into(),
+                    metrics: metrics(7200, 0, 12.0),
+                },
+                LocationMetrics {
+                    path: "/api".into(),
+                    metrics: metrics(360, 36, 80.0),
+                },
+            ],
+            targets: vec![TargetMetrics {
+                group: "api".into(),
+                instance_id: Some(Uuid::parse_str("abcdef01-0000-0000-0000-000000000000").unwrap()),
+                metrics: metrics(360, 36, 80.0),
+            }],
+        }
+    }
+
+    #[test]
+    fn parses_windows() {
+        assert_eq!(parse_window("15m").unwrap(), 900);
+        assert_eq!(parse_window("1h").unwrap(), 3600);
+        assert_eq!(parse_window("7d").unwrap(), 7 * 24 * 3600);
+        assert_eq!(parse_window("1h30m").unwrap(), 5400);
+        for bad in [
+            "",
+            "h",
+            "1",
+            "1w",
+            "0m",
+            "30s",
+            "31d",
+            "-1h",
+            "1é",
+            "213503982334602d",
+        ] {
+            assert!(parse_window(bad).is_err(), "{bad:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn renders_rates_errors_and_percentiles_per_location_and_target() {
+        let out = render("web", "1h", &response(), &Locale::default(), false);
+        assert!(out.contains("web — last 1h, 7560 requests"), "{out}");
+        assert!(
+            out.contains("2.00"),
+            "7200 req over an hour is 2 req/s: {out}"
+        );
+        assert!(out.contains("10.0%"), "36 of 360 failed: {out}");
+        assert!(
+            out.contains("3.20 s"),
+            "p99 above a second reads in seconds: {out}"
+        );
+        assert!(
+            out.contains("api (abcdef01)"),
+            "target with its instance: {out}"
+        );
+    }
+
+    #[test]
+    fn idle_service_says_so_instead_of_empty_tables() {
+        let idle = ServiceMetricsResponse {
+            window_seconds: 900,
+            locations: vec![LocationMetrics {
+                path: "/".into(),
+                metrics: RequestMetrics::default(),
+            }],
+            targets: vec![],
+        };
+        let out = render("web", "15m", &idle, &Locale::default(), false);
+        assert!(out.contains("No traffic in the last 15m."), "{out}");
+        assert!(!out.contains("REQ/S"), "{out}");
+    }
+
+    #[test]
+    fn error_rate_colours_by_severity() {
+        assert_eq!(
+            format_error_rate(&metrics(100, 0, 1.0), &Locale::default()).1,
+            None
+        );
+        assert_eq!(
+            format_error_rate(&metrics(100, 2, 1.0), &Locale::default()).1,
+            Some(Color::Yellow)
+        );
+        assert_eq!(
+            format_error_rate(&metrics(100, 5, 1.0), &Locale::default()).1,
+            Some(Color::Red)
+        );
+    }
+
+    #[tokio::test]
+    async fn fetches_metrics_for_the_resolved_service_and_window() {
+        let env = ResolvedEnvironment {
+            id: Uuid::new_v4(),
+            name: "prod".into(),
+            project: "demo".into(),
+            slug: "ab12".into(),
+        };
+        let svc = Uuid::new_v4();
+        let mock = MockApiClient::logged_in()
+            .with_list_services(Ok(ServiceListResponse {
+                services: vec![ServiceListItem {
+                    id: svc,
+                    name: "web".into(),
+                    base_host: "web-ab12.unisrv.dev".into(),
+                    custom_hosts: vec![],
+                    managed_by: None,
+                }],
+            }))
+            .push_get_service_metrics(Ok(response()));
+
+        stats(&mock, &env, "web", "1h", false).await.unwrap();
+
+        assert_eq!(
+            mock.calls.lock().unwrap().get_service_metrics_calls,
+            vec![(env.id, svc, "1h".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_a_bad_window_before_calling_the_api() {
+        let mock = MockApiClient::logged_in();
+        let env = ResolvedEnvironment {
+            id: Uuid::new_v4(),
+            name: "prod".into(),
+            project: "demo".into(),
+            slug: "ab12".into(),
+        };
+        let err = stats(&mock, &env, "web", "soon", false).await.unwrap_err();
+        assert!(format!("{err:#}").contains("--window"));
+        assert!(mock.calls.lock().unwrap().call_order.is_empty());
+    }
+}
@@ -0,0 +1,160 @@
+//! `unisrv service target add <service> <instance> --port N --group G` —
+//! route one of a service's instance groups to a running instance.
+//!
+//! The edge has to be able to reach the instance, so that's checked before
+//! the target is registered (see [`super::reach`]): a target with no route
+//! would only show up later as 502s.
+
+use anyhow::{Result, bail};
+use unisrv_api::ApiClient;
+use unisrv_api::models::ServiceInstanceTarget;
+
+use super::reach::{Reach, assess};
+use super::resolve::resolve_service;
+use crate::commands::instance::resolve::lookup_instance;
+use crate::commands::up::plan::ResolvedEnvironment;
+
+pub async fn add(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    reference: &str,
+    instance: &str,
+    port: u16,
+    group: &str,
+) -> Result<()> {
+    let service = resolve_service(client, env.id, reference).await?;
+    let instance = lookup_instance(client, env.id, instance).await?;
+    let label = instance
+        .name
+        .clone()
+        .unwrap_or_else(|| instance.id.to_string());
+
+    let detail = client.get_service(env.id, service.id).await?;
+    if detail
+        .targets
+        .iter()
+        .any(|t| t.instance_id == instance.id && t.instance_port == port && t.target_group == group)
+    {
+        println!(
+            "{label}:{port} is already a target of {} in group {group}.",
+            service.name
+        );
+        return Ok(());
+    }
+
+    let instance_detail = client
+        .get_instance(env.id, instance.id, false, false)
+        .await?;
+    match assess(&detail, &instance_detail, &label) {
+        Reach::Reachable => {}
+        Reach::Unreachable(reason) => bail!("{reason}"),
+        Reach::Doubtful(reason) => {
+            eprintln!("{}", console::style(format!("! {reason}")).yellow());
+        }
+    }
+
+    client
+        .create_service_target(
+            env.id,
+            service.id,
+            ServiceInstanceTarget {
+                instance_id: instance.id,
+                instance_port: port,
+                group: group.to_string(),
+            },
+        )
+        .await?;
+    println!(
+        "\u{2713} {} now routes group {group} to {label}:{port}.",
+        service.name
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::service::reach::tests::{instance, service};
+    use crate::commands::up::plan::test_env;
+    use chrono::NaiveDateTime;
+    use unisrv_api::models::{
+        CreateTargetResponse, InstanceListEntry, InstanceListResponse, InstanceState,
+        ServiceListItem, ServiceListResponse,
+    };
+    use unisrv_api::test_support::MockApiClient;
+    use uuid::Uuid;
+
+    /// A mock that resolves `web` and `api-1` to `svc` and `inst`.
+    fn mock(
+        svc: &unisrv_api::models::ServiceDetailResponse,
+        inst: &unisrv_api::models::InstanceDetailResponse,
+    ) -> MockApiClient {
+        MockApiClient::logged_in()
+            .with_list_services(Ok(ServiceListResponse {
+                services: vec![ServiceListItem {
+                    id: svc.id,
+                    name: svc.name.clone(),
+                    base_host: svc.base_host.clone(),
+                    custom_hosts: vec![],
+                    managed_by: None,
+                }],
+            }))
+            .with_list_instances(Ok(InstanceListResponse {
+                instances: vec![InstanceListEntry {
+                    id: inst.id,
+                    name: inst.name.clone(),
+                    state: InstanceState("running".into()),
+                    container_image: "api:1".into(),
+                    created_at: NaiveDateTime::default(),
+                    deployment: None,
+                    retained_until: None,
+                }],
+            }))
+            .push_get_service(Ok(svc.clone()))
+            .push_get_instance(Ok(inst.clone()))
+    }
+
+    #[tokio::test]
+    async fn registers_a_reachable_instance() {
+        let svc = service(&[Uuid::new_v4()]);
+        let inst = instance(Uuid::new_v4(), Some("10.0.0.4"));
+        let env = test_env();
+        let mock = mock(&svc, &inst).push_create_service_target(Ok(CreateTargetResponse {
+            target_id: Uuid::new_v4(),
+        }));
+
+        add(&mock, &env, "web", "api-1", 8080, "api").await.unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let (env_id, service_id, target) = &calls.create_service_target_calls[0];
+        assert_eq!((*env_id, *service_id), (env.id, svc.id));
+        assert_eq!(
+            *target,
+            ServiceInstanceTarget {
+                instance_id: inst.id,
+                instance_port: 8080,
+                group: "api".into(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_an_instance_the_edge_cant_reach() {
+        let svc = service(&[Uuid::new_v4()]);
+        let inst = instance(Uuid::new_v4(), None);
+        let mock = mock(&svc, &inst);
+
+        let err = add(&mock, &test_env(), "web", "api-1", 8080, "api")
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains("no route"), "{err:#}");
+        assert!(
+            mock.calls
+                .lock()
+                .unwrap()
+                .create_service_target_calls
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,156 @@
+//! `unisrv service targets <ref> [--unhealthy]` — a service's instance
+//! targets with the health the edge last observed for each.
+//!
+//! Health comes from the edge's own checks, so it answers "is the proxy
+//! sending traffic there?" rather than "is the instance running?". Backends
+//! that don't report it show `unknown`.
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use comfy_table::{Attribute, Cell, Color};
+use unisrv_api::ApiClient;
+use unisrv_api::models::{ServiceTargetDetail, TargetHealth};
+
+use super::resolve::resolve_service;
+use crate::commands::output::Output;
+use crate::commands::ui::{cell_with_color, colors_enabled, format_relative, new_table};
+use crate::commands::up::plan::ResolvedEnvironment;
+
+pub async fn targets(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    reference: &str,
+    unhealthy: bool,
+    output: &Output,
+) -> Result<()> {
+    let service = resolve_service(client, env.id, reference).await?;
+    let detail = client.get_service(env.id, service.id).await?;
+    let shown: Vec<ServiceTargetDetail> = detail
+        .targets
+        .into_iter()
+        .filter(|t| !unhealthy || !is_serving(t.health.as_ref()))
+        .collect();
+
+    match output {
+        Output::Json | Output::Jq(_) => return output.print_json(&shown),
+        Output::Template(template) => {
+            print!("{}", template.render_all(&shown)?);
+            return Ok(());
+        }
+        Output::Table => {}
+    }
+
+    if shown.is_empty() {
+        if unhealthy {
+            println!("No unhealthy targets on {}.", service.name);
+        } else {
+            println!("{} has no instance targets.", service.name);
+        }
+        return Ok(());
+    }
+    let now = chrono::Utc::now().naive_utc();
+    println!("{}", render_table(&shown, now, colors_enabled()));
+    Ok(())
+}
+
+/// Whether the edge is routing to the target. Unknown health counts as
+/// serving: a backend that doesn't report health shouldn't make every target
+/// look broken.
+fn is_serving(health: Option<&TargetHealth>) -> bool {
+    health.is_none_or(|h| h.status == "healthy")
+}
+
+/// Status → (display, colour). Unknown statuses render plainly.
+pub fn format_health(health: Option<&TargetHealth>) -> (String, Option<Color>) {
+    let Some(health) = health else {
+        return ("unknown".to_string(), Some(Color::DarkGrey));
+    };
+    let color = match health.status.as_str() {
+        "healthy" => Some(Color::Green),
+        "unhealthy" => Some(Color::Red),
+        "draining" => Some(Color::Yellow),
+        _ => None,
+    };
+    (health.status.clone(), color)
+}
+
+/// When the edge last checked, e.g. "2 minutes ago", or "—".
+pub fn last_checked(health: Option<&TargetHealth>, now: NaiveDateTime) -> String {
+    health
+        .and_then(|h| h.last_checked_at)
+        .map_or_else(|| "\u{2014}".to_string(), |at| format_relative(at, now))
+}
+
+/// Pure so it can be asserted on without a terminal.
+fn render_table(targets: &[ServiceTargetDetail], now: NaiveDateTime, use_color: bool) -> String {
+    let mut table = new_table();
+    table.set_header(vec![
+        Cell::new("GROUP").add_attribute(Attribute::Bold),
+        Cell::new("INSTANCE").add_attribute(Attribute::Bold),
+        Cell::new("PORT").add_attribute(Attribute::Bold),
+        Cell::new("HEALTH").add_attribute(Attribute::Bold),
+        Cell::new("LAST CHECK").add_attribute(Attribute::Bold),
+    ]);
+    for target in targets {
+        let (health, color) = format_health(target.health.as_ref());
+        table.add_row(vec![
+            Cell::new(&target.target_group),
+            Cell::new(&target.instance_id.to_string()[..8]),
+            Cell::new(target.instance_port),
+            cell_with_color(health, color, use_color),
+            Cell::new(last_checked(target.health.as_ref(), now)),
+        ]);
+    }
+    table.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use uuid::Uuid;
+
+    fn target(group: &str, health: Option<(&str, Option<NaiveDateTime>)>) -> ServiceTargetDetail {
+        ServiceTargetDetail {
+            id: Uuid::new_v4(),
+            instance_id: Uuid::new_v4(),
+            target_group: group.into(),
+            instance_port: 8080,
+            created_at: NaiveDateTime::default(),
+            health: health.map(|(status, last_checked_at)| TargetHealth {
+                status: status.into(),
+                last_checked_at,
+            }),
+        }
+    }
+
+    #[test]
+    fn only_known_healthy_or_unreported_targets_count_as_serving() {
+        let health = |status: &str| TargetHealth {
+            status: status.into(),
+            last_checked_at: None,
+        };
+        assert!(is_serving(None));
+        assert!(is_serving(Some(&health("healthy"))));
+        assert!(!is_serving(Some(&health("unhealthy"))));
+        assert!(!is_serving(Some(&health("draining"))));
+    }
+
+    #[test]
+    fn table_shows_health_and_last_check() {
+        let now = NaiveDateTime::default() + Duration::minutes(10);
+        let checked = Some(now - Duration::minutes(2));
+        let out = render_table(
+            &[
+                target("web", Some(("unhealthy", checked))),
+                target("api", None),
+            ],
+            now,
+            false,
+        );
+        assert!(out.contains("HEALTH"), "{out}");
+        assert!(out.contains("unhealthy"), "{out}");
+        assert!(out.contains("2 minutes ago"), "{out}");
+        assert!(out.contains("unknown"), "{out}");
+    }
+}
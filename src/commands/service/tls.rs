@@ -0,0 +1,193 @@
+//! `unisrv service tls <ref> [--min-version 1.2|1.3] [--hsts|--no-hsts]
+//! [--redirect-http|--allow-http]` — adjust how the edge terminates TLS for a
+//! service. With no flags, prints the current policy.
+//!
+//! Like `service host`, this edits the live service. `up` reconciles the
+//! configuration back to `unisrv.hcl`, so the change only sticks once the
+//! service's `tls` block and `allow_http` say the same.
+
+use anyhow::{Context, Result};
+use unisrv_api::ApiClient;
+use unisrv_api::models::{HTTPServiceConfig, TlsVersion};
+
+use super::resolve::resolve_service;
+use crate::commands::up::diff::service::min_version_label;
+use crate::commands::up::plan::ResolvedEnvironment;
+
+/// Requested changes; `None` leaves a setting as it is.
+#[derive(Debug, Default)]
+pub struct TlsChange {
+    pub min_version: Option<TlsVersion>,
+    pub hsts: Option<bool>,
+    pub redirect_http: Option<bool>,
+}
+
+impl TlsChange {
+    fn is_empty(&self) -> bool {
+        self.min_version.is_none() && self.hsts.is_none() && self.redirect_http.is_none()
+    }
+
+    fn apply(&self, config: &mut HTTPServiceConfig) {
+        if let Some(version) = self.min_version {
+            config.tls.min_version = Some(version);
+        }
+        if let Some(hsts) = self.hsts {
+            config.tls.hsts = hsts;
+        }
+        if let Some(redirect) = self.redirect_http {
+            config.allow_http = !redirect;
+        }
+    }
+}
+
+pub async fn tls(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    reference: &str,
+    change: TlsChange,
+) -> Result<()> {
+    let service = resolve_service(client, env.id, reference).await?;
+    let detail = client.get_service(env.id, service.id).await?;
+    let mut config: HTTPServiceConfig =
+        serde_json::from_value(detail.configuration).with_context(|| {
+            format!(
+                "cannot change TLS for {}: its configuration isn't one this CLI understands",
+                service.name
+            )
+        })?;
+
+    if change.is_empty() {
+        println!("{}: {}", service.name, summary(&config));
+        return Ok(());
+    }
+
+    let before = config.clone();
+    change.apply(&mut config);
+    if config == before {
+        println!("{} already has {}.", service.name, summary(&config));
+        return Ok(());
+    }
+
+    client
+        .update_service(env.id, service.id, config.clone())
+        .await
+        .with_context(|| format!("failed to update {}", service.name))?;
+    println!(
+        "\u{2713} Updated TLS for {}: {}.",
+        service.name,
+        summary(&config)
+    );
+    eprintln!(
+        "{}",
+        console::style(
+            "If this service is managed by unisrv.hcl, update its `tls` block and `allow_http` \
+             too — `unisrv up` reconciles configuration to the manifest."
+        )
+        .dim()
+    );
+    Ok(())
+}
+
+/// One-line policy, e.g. `min TLS 1.3, HSTS, HTTP redirected to HTTPS`.
+pub fn summary(config: &HTTPServiceConfig) -> String {
+    let mut parts = vec![format!(
+        "min TLS {}",
+        min_version_label(config.tls.min_version)
+    )];
+    if config.tls.hsts {
+        parts.push("HSTS".into());
+    }
+    parts.push(if config.allow_http {
+        "plain HTTP allowed".into()
+    } else {
+        "HTTP redirected to HTTPS".into()
+    });
+    parts.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::up::plan::test_env;
+    use serde_json::json;
+    use unisrv_api::models::{ServiceDetailResponse, ServiceListItem, ServiceListResponse};
+    use unisrv_api::test_support::MockApiClient;
+    use uuid::Uuid;
+
+    fn mock(id: Uuid, configuration: serde_json::Value) -> MockApiClient {
+        MockApiClient::logged_in()
+            .with_list_services(Ok(ServiceListResponse {
+                services: vec![ServiceListItem {
+                    id,
+                    name: "web".into(),
+                    base_host: "web-ab12.unisrv.dev".into(),
+                    custom_hosts: vec![],
+                    managed_by: None,
+                }],
+            }))
+            .push_get_service(Ok(ServiceDetailResponse {
+                id,
+                name: "web".into(),
+                base_host: "web-ab12.unisrv.dev".into(),
+                custom_hosts: vec![],
+                configuration,
+                environment_id: Uuid::nil(),
+                created_at: Default::default(),
+                updated_at: Default::default(),
+                providers: vec![],
+                targets: vec![],
+                statistics: None,
+            }))
+    }
+
+    fn config(allow_http: bool) -> serde_json::Value {
+        json!({
+            "allow_http": allow_http,
+            "locations": [{"path": "/", "target": {"type": "instance", "group": "web"}}],
+        })
+    }
+
+    #[tokio::test]
+    async fn updates_only_the_requested_settings() {
+        let id = Uuid::new_v4();
+        let mock = mock(id, config(true)).push_update_service(Ok(()));
+
+        let change = TlsChange {
+            min_version: Some(TlsVersion::Tls13),
+            hsts: Some(true),
+            redirect_http: Some(true),
+        };
+        tls(&mock, &test_env(), "web", change).await.unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let (_, service_id, updated) = &calls.update_service_calls[0];
+        assert_eq!(*service_id, id);
+        assert_eq!(updated.tls.min_version, Some(TlsVersion::Tls13));
+        assert!(updated.tls.hsts);
+        assert!(!updated.allow_http);
+        assert_eq!(updated.locations.len(), 1, "routing is left alone");
+    }
+
+    #[tokio::test]
+    async fn no_op_change_skips_the_update() {
+        let mock = mock(Uuid::new_v4(), config(false));
+
+        let change = TlsChange {
+            redirect_http: Some(true),
+            ..Default::default()
+        };
+        tls(&mock, &test_env(), "web", change).await.unwrap();
+
+        assert!(mock.calls.lock().unwrap().update_service_calls.is_empty());
+    }
+
+    #[test]
+    fn summary_names_every_setting() {
+        let mut cfg: HTTPServiceConfig = serde_json::from_value(config(false)).unwrap();
+        assert_eq!(summary(&cfg), "min TLS <default>, HTTP redirected to HTTPS");
+        cfg.tls.min_version = Some(TlsVersion::Tls12);
+        cfg.tls.hsts = true;
+        cfg.allow_http = true;
+        assert_eq!(summary(&cfg), "min TLS 1.2, HSTS, plain HTTP allowed");
+    }
+}
@@ -0,0 +1,137 @@
+//! `unisrv service update <ref> [--compression on|off]` — change a live
+//! service's settings that aren't routing or TLS.
+//!
+//! Compression is gzip or brotli at the edge, whichever the client accepts,
+//! on text responses. Turn it off for an API whose backend already
+//! compresses, so nothing is compressed twice; on for text-heavy pages the
+//! platform default leaves alone.
+//!
+//! For a service declared in `unisrv.hcl`, `up` reconciles these back to the
+//! service block, so mirror the change there (`compression = true`).
+
+use anyhow::{Context, Result, bail};
+use unisrv_api::ApiClient;
+use unisrv_api::models::HTTPServiceConfig;
+
+use super::resolve::resolve_service;
+use crate::commands::up::diff::service::compression_label;
+use crate::commands::up::plan::ResolvedEnvironment;
+
+/// Requested changes; `None` leaves a setting as it is.
+#[derive(Debug, Default)]
+pub struct UpdateChange {
+    pub compression: Option<bool>,
+}
+
+/// `--compression on|off`.
+pub fn parse_on_off(s: &str) -> Result<bool, String> {
+    match s {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(format!("{s:?} is neither on nor off")),
+    }
+}
+
+pub async fn update(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    reference: &str,
+    change: UpdateChange,
+) -> Result<()> {
+    let Some(compression) = change.compression else {
+        bail!("nothing to update; pass --compression on|off");
+    };
+    let service = resolve_service(client, env.id, reference).await?;
+    let detail = client.get_service(env.id, service.id).await?;
+    let mut config: HTTPServiceConfig =
+        serde_json::from_value(detail.configuration).with_context(|| {
+            format!(
+                "cannot update {}: its configuration isn't one this CLI understands",
+                service.name
+            )
+        })?;
+
+    if config.compression == Some(compression) {
+        println!(
+            "{} already has compression {}.",
+            service.name,
+            compression_label(Some(compression))
+        );
+        return Ok(());
+    }
+    let before = config.compression.replace(compression);
+    client
+        .update_service(env.id, service.id, config)
+        .await
+        .with_context(|| format!("failed to update {}", service.name))?;
+    println!(
+        "\u{2713} Updated {}: compression {} -> {}.",
+        service.name,
+        compression_label(before),
+        compression_label(Some(compression))
+    );
+    eprintln!(
+        "{}",
+        console::style(
+            "If this service is managed by unisrv.hcl, set `compression` in its service block \
+             too — `unisrv up` reconciles configuration to the manifest."
+        )
+        .dim()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::up::plan::test_env;
+    use serde_json::json;
+    use unisrv_api::models::{ServiceDetailResponse, ServiceListItem, ServiceListResponse};
+    use unisrv_api::test_support::MockApiClient;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn compression_is_written_and_the_rest_kept() {
+        let id = Uuid::new_v4();
+        let mock = MockApiClient::logged_in()
+            .with_list_services(Ok(ServiceListResponse {
+                services: vec![ServiceListItem {
+                    id,
+                    name: "api".into(),
+                    base_host: "api-ab12.unisrv.dev".into(),
+                    custom_hosts: vec![],
+                    managed_by: None,
+                }],
+            }))
+            .push_get_service(Ok(ServiceDetailResponse {
+                id,
+                name: "api".into(),
+                base_host: "api-ab12.unisrv.dev".into(),
+                custom_hosts: vec![],
+                configuration: json!({
+                    "allow_http": true,
+                    "locations": [{"path": "/", "target": {"type": "instance", "group": "api"}}],
+                }),
+                environment_id: Uuid::nil(),
+                created_at: Default::default(),
+                updated_at: Default::default(),
+                providers: vec![],
+                targets: vec![],
+                statistics: None,
+            }))
+            .push_update_service(Ok(()));
+
+        let change = UpdateChange {
+            compression: Some(false),
+        };
+        update(&mock, &test_env(), "api", change).await.unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let (_, _, config) = &calls.update_service_calls[0];
+        assert_eq!(config.compression, Some(false));
+        assert!(config.allow_http);
+        assert_eq!(config.locations.len(), 1);
+        assert_eq!(parse_on_off("on"), Ok(true));
+        assert!(parse_on_off("yes").is_err());
+    }
+}
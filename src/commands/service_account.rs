@@ -0,0 +1,299 @@
+//! `unisrv auth service-account` — identities for deployment pipelines, so
+//! they don't run with someone's personal credentials.
+//!
+//! A service account holds only the permission scopes it was created with,
+//! each `<resource>:<read|write>` (`write` implies `read`). Its token is
+//! printed once, by `create`; the pipeline sets `UNISRV_TOKEN` to it, as with
+//! an API token. Deleting the account revokes the token.
+
+use anyhow::{Result, bail};
+use chrono::{DateTime, Utc};
+use comfy_table::{Attribute, Cell};
+use unisrv_api::models::{CreateServiceAccountRequest, ServiceAccountListItem};
+use unisrv_api::{ApiClient, TOKEN_ENV};
+use uuid::Uuid;
+
+use crate::commands::auth::Expiry;
+use crate::commands::confirm::Guard;
+use crate::commands::output::Output;
+use crate::commands::ui::{format_relative, new_table};
+
+/// What a scope can grant access to.
+const RESOURCES: &[&str] = &[
+    "environments",
+    "instances",
+    "services",
+    "networks",
+    "hosts",
+    "registries",
+    "images",
+];
+
+/// `--scope`: a permission like `instances:write` or `services:read`.
+pub fn parse_scope(s: &str) -> Result<String, String> {
+    let Some((resource, access)) = s.split_once(':') else {
+        return Err(format!(
+            "invalid scope {s:?}: expected <resource>:<read|write>, e.g. instances:write"
+        ));
+    };
+    if !RESOURCES.contains(&resource) {
+        return Err(format!(
+            "unknown resource {resource:?} in scope {s:?}: expected one of {}",
+            RESOURCES.join(", ")
+        ));
+    }
+    if !matches!(access, "read" | "write") {
+        return Err(format!(
+            "invalid access {access:?} in scope {s:?}: expected read or write"
+        ));
+    }
+    Ok(s.to_string())
+}
+
+/// The scopes to ask for: sorted, without duplicates or reads a write on the
+/// same resource already covers.
+fn normalize_scopes(scopes: &[String]) -> Vec<String> {
+    let mut kept: Vec<String> = scopes
+        .iter()
+        .filter(|scope| {
+            let Some(resource) = scope.strip_suffix(":read") else {
+                return true;
+            };
+            !scopes.contains(&format!("{resource}:write"))
+        })
+        .cloned()
+        .collect();
+    kept.sort();
+    kept.dedup();
+    kept
+}
+
+/// `unisrv auth service-account create`.
+pub async fn create(
+    client: &dyn ApiClient,
+    name: &str,
+    scopes: &[String],
+    expires: Expiry,
+    output: &Output,
+) -> Result<()> {
+    let created = client
+        .create_service_account(CreateServiceAccountRequest {
+            name: name.to_string(),
+            scopes: normalize_scopes(scopes),
+            expires_at: expires.at(Utc::now())?,
+        })
+        .await?;
+    if output.is_machine() {
+        return output.print_json(&created);
+    }
+    let expiry = match created.expires_at {
+        Some(at) => format!("expires {}", at.format("%Y-%m-%d")),
+        None => "never expires".to_string(),
+    };
+    println!(
+        "\u{2713} Created service account {} with {} ({expiry}).",
+        created.name,
+        created.scopes.join(", ")
+    );
+    println!("  Set {TOKEN_ENV} to its token; it won't be shown again:");
+    println!("{}", created.token);
+    Ok(())
+}
+
+/// `unisrv auth service-account list`.
+pub async fn list(client: &dyn ApiClient, output: &Output) -> Result<()> {
+    let accounts = client.list_service_accounts().await?.service_accounts;
+    match output {
+        Output::Json | Output::Jq(_) => return output.print_json(&accounts),
+        Output::Template(template) => {
+            print!("{}", template.render_all(&accounts)?);
+            return Ok(());
+        }
+        Output::Table => {}
+    }
+    if accounts.is_empty() {
+        println!(
+            "No service accounts. Create one with `unisrv auth service-account create --name <name> --scope <scope>`."
+        );
+        return Ok(());
+    }
+    println!("{}", render_table(&accounts, Utc::now()));
+    Ok(())
+}
+
+fn render_table(accounts: &[ServiceAccountListItem], now: DateTime<Utc>) -> String {
+    let mut table = new_table();
+    table.set_header(vec![
+        Cell::new("NAME").add_attribute(Attribute::Bold),
+        Cell::new("SCOPES").add_attribute(Attribute::Bold),
+        Cell::new("CREATED").add_attribute(Attribute::Bold),
+        Cell::new("EXPIRES").add_attribute(Attribute::Bold),
+        Cell::new("LAST USED").add_attribute(Attribute::Bold),
+    ]);
+    for account in accounts {
+        let expires = match account.expires_at {
+            Some(at) if at <= now => "expired".to_string(),
+            Some(at) => format_relative(at, now),
+            None => "never".to_string(),
+        };
+        let last_used = account
+            .last_used_at
+            .map_or_else(|| "never".to_string(), |at| format_relative(at, now));
+        table.add_row(vec![
+            Cell::new(&account.name),
+            Cell::new(account.scopes.join("\n")),
+            Cell::new(format_relative(account.created_at, now)),
+            Cell::new(expires),
+            Cell::new(last_used),
+        ]);
+    }
+    table.to_string()
+}
+
+/// `unisrv auth service-account delete <name-or-id>`.
+pub async fn delete(client: &dyn ApiClient, reference: &str, yes: bool) -> Result<()> {
+    let accounts = client.list_service_accounts().await?.service_accounts;
+    let account = resolve_account(&accounts, reference)?;
+    // Like API tokens, service accounts belong to the account rather than an
+    // environment, so they're never protected.
+    let confirmed = Guard::unprotected().confirm_or_ask(
+        &format!("delete the service account {}", account.name),
+        &account.name,
+        &format!(
+            "Delete service account {}? Pipelines using its token stop working.",
+            account.name
+        ),
+        yes,
+    )?;
+    if !confirmed {
+        println!("Aborted.");
+        return Ok(());
+    }
+    client.delete_service_account(account.id).await?;
+    println!("\u{2713} Deleted {}.", account.name);
+    Ok(())
+}
+
+/// The service account `reference` names, by id or by name.
+fn resolve_account<'a>(
+    accounts: &'a [ServiceAccountListItem],
+    reference: &str,
+) -> Result<&'a ServiceAccountListItem> {
+    if let Ok(id) = reference.parse::<Uuid>()
+        && let Some(account) = accounts.iter().find(|a| a.id == id)
+    {
+        return Ok(account);
+    }
+    let named: Vec<&ServiceAccountListItem> =
+        accounts.iter().filter(|a| a.name == reference).collect();
+    match named.as_slice() {
+        [account] => Ok(account),
+        [] => {
+            bail!("no service account named {reference:?}; see `unisrv auth service-account list`")
+        }
+        _ => bail!(
+            "{} service accounts are named {reference:?}; delete one by its id",
+            named.len()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use unisrv_api::models::{CreatedServiceAccount, ServiceAccountListResponse};
+    use unisrv_api::test_support::MockApiClient;
+
+    fn listed(name: &str) -> ServiceAccountListItem {
+        ServiceAccountListItem {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            scopes: vec!["instances:write".into()],
+            created_at: Utc::now(),
+            expires_at: None,
+            last_used_at: None,
+        }
+    }
+
+    #[test]
+    fn scopes_name_a_resource_and_read_or_write() {
+        assert_eq!(
+            parse_scope("instances:write").as_deref(),
+            Ok("instances:write")
+        );
+        assert!(
+            parse_scope("instances")
+                .unwrap_err()
+                .contains("<resource>:<read|write>")
+        );
+        assert!(
+            parse_scope("secrets:read")
+                .unwrap_err()
+                .contains("unknown resource")
+        );
+        assert!(
+            parse_scope("services:admin")
+                .unwrap_err()
+                .contains("read or write")
+        );
+    }
+
+    #[tokio::test]
+    async fn create_asks_for_the_scopes_a_write_does_not_already_cover() {
+        let mock =
+            MockApiClient::logged_in().push_create_service_account(Ok(CreatedServiceAccount {
+                id: Uuid::new_v4(),
+                name: "deploy".into(),
+                scopes: vec!["instances:write".into(), "services:read".into()],
+                token: "usa_secret".into(),
+                expires_at: None,
+            }));
+        let scopes = [
+            "services:read",
+            "instances:read",
+            "instances:write",
+            "services:read",
+        ]
+        .map(String::from);
+        create(
+            &mock,
+            "deploy",
+            &scopes,
+            "never".parse().unwrap(),
+            &Output::Json,
+        )
+        .await
+        .unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        let req = &calls.create_service_account_calls[0];
+        assert_eq!(req.scopes, ["instances:write", "services:read"]);
+        assert_eq!(req.expires_at, None);
+    }
+
+    #[tokio::test]
+    async fn delete_finds_an_account_by_name_or_id() {
+        let deploy = listed("deploy");
+        let id = deploy.id;
+        let mock = MockApiClient::logged_in()
+            .with_list_service_accounts(Ok(ServiceAccountListResponse {
+                service_accounts: vec![listed("nightly"), deploy],
+            }))
+            .push_delete_service_account(Ok(()));
+        delete(&mock, "deploy", true).await.unwrap();
+        assert_eq!(
+            mock.calls.lock().unwrap().delete_service_account_calls,
+            [id]
+        );
+
+        let accounts = [listed("ci"), listed("ci")];
+        assert_eq!(
+            resolve_account(&accounts, &accounts[1].id.to_string())
+                .unwrap()
+                .id,
+            accounts[1].id
+        );
+        let err = resolve_account(&accounts, "ci").unwrap_err();
+        assert!(err.to_string().contains("delete one by its id"), "{err:#}");
+    }
+}
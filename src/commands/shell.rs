@@ -0,0 +1,382 @@
+//! `unisrv shell` — an interactive session for running many commands in a
+//! row.
+//!
+//! Each line is parsed exactly like the command-line arguments it spells out
+//! (`instance ls --all`, `network ips backend`), so everything that works as
+//! `unisrv …` works here. What the session adds over separate invocations:
+//!
+//! - one API client for the whole session, so the login is refreshed once
+//!   rather than per command;
+//! - a [`CachingClient`] in front of it, so the environment and name lookups
+//!   every command starts with are answered from memory (see its docs for
+//!   when entries are dropped);
+//! - history, kept across sessions in the state directory;
+//! - tab completion of subcommands, flags, and the names of the current
+//!   environment's instances, networks, services and deployments.
+//!
+//! `refresh` drops the cache and re-resolves the environment; `exit` or
+//! `quit` leaves.
+
+use std::fmt;
+use std::io::IsTerminal;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use clap::Command;
+use dialoguer::theme::Theme;
+use dialoguer::{Completion, History, Input};
+use unisrv_api::CachingClient;
+
+use crate::commands::completions::Resources;
+use crate::commands::instance::run::current_environment;
+use crate::commands::up::plan::ResolvedEnvironment;
+use crate::state::{MAX_SHELL_HISTORY, StateDir};
+
+/// How long list responses are reused before being fetched again.
+pub const CACHE_MAX_AGE: Duration = Duration::from_secs(30);
+
+/// The interactive session: prompt, history and completion.
+pub struct Shell {
+    env: Option<ResolvedEnvironment>,
+    history: ShellHistory,
+    completer: Completer,
+}
+
+impl Shell {
+    /// `grammar` is the command tree lines are parsed with, walked for
+    /// completion.
+    pub async fn start(client: &CachingClient<'_>, grammar: Command) -> Result<Self> {
+        if !std::io::stdin().is_terminal() {
+            bail!(
+                "`unisrv shell` needs a terminal; pipe commands to `unisrv` one at a time instead"
+            );
+        }
+        let mut shell = Self {
+            env: None,
+            history: ShellHistory::load(StateDir::locate()),
+            completer: Completer::new(grammar),
+        };
+        shell.resolve(client).await;
+        eprintln!(
+            "{}",
+            console::style("Type a command without the leading `unisrv`, `help` for a list, or `exit` to leave.")
+                .dim()
+        );
+        Ok(shell)
+    }
+
+    /// The next command's arguments, handling blank lines and the shell's own
+    /// commands in between. `None` when the session is over.
+    pub async fn next_command(&mut self, client: &CachingClient<'_>) -> Option<Vec<String>> {
+        loop {
+            self.completer.resources = Resources::load(client, self.env.as_ref()).await;
+            let prompt = match &self.env {
+                Some(env) => format!("unisrv ({})>", env.name),
+                None => "unisrv>".to_string(),
+            };
+            let line = Input::<String>::with_theme(&ShellTheme)
+                .with_prompt(prompt)
+                .allow_empty(true)
+                .history_with(&mut self.history)
+                .completion_with(&self.completer)
+                .interact_text();
+            // Ctrl-C at the prompt, or stdin went away.
+            let Ok(line) = line else {
+                return None;
+            };
+            match line.trim() {
+                "" => continue,
+                "exit" | "quit" => return None,
+                "refresh" => {
+                    client.invalidate();
+                    self.resolve(client).await;
+                    continue;
+                }
+                _ => {}
+            }
+            match split(&line) {
+                Ok(words) => return Some(words),
+                Err(e) => eprintln!("Error: {e:#}"),
+            }
+        }
+    }
+
+    /// The environment shown in the prompt and completed against. Not being
+    /// able to pick one only costs completion; each command still resolves
+    /// its own.
+    async fn resolve(&mut self, client: &CachingClient<'_>) {
+        self.env = match current_environment(client, None).await {
+            Ok(env) => Some(env),
+            Err(e) => {
+                eprintln!(
+                    "{}",
+                    console::style(format!("note: no default environment ({e:#})")).dim()
+                );
+                None
+            }
+        };
+    }
+}
+
+/// Split a line into arguments the way a POSIX shell would, quotes and
+/// escapes included.
+fn split(line: &str) -> Result<Vec<String>> {
+    shell_words::split(line).context("unbalanced quotes")
+}
+
+/// Prompt as `unisrv (prod)> `, without the default theme's colon.
+struct ShellTheme;
+
+impl Theme for ShellTheme {
+    fn format_input_prompt(
+        &self,
+        f: &mut dyn fmt::Write,
+        prompt: &str,
+        _default: Option<&str>,
+    ) -> fmt::Result {
+        write!(f, "{prompt} ")
+    }
+
+    fn format_input_prompt_selection(
+        &self,
+        f: &mut dyn fmt::Write,
+        prompt: &str,
+        sel: &str,
+    ) -> fmt::Result {
+        write!(f, "{prompt} {sel}")
+    }
+}
+
+/// Command lines, oldest first, persisted as they're entered.
+struct ShellHistory {
+    lines: Vec<String>,
+    state: Option<StateDir>,
+}
+
+impl ShellHistory {
+    fn load(state: Option<StateDir>) -> Self {
+        let lines = state
+            .as_ref()
+            .map(StateDir::shell_history)
+            .unwrap_or_default();
+        Self { lines, state }
+    }
+}
+
+impl History<String> for ShellHistory {
+    /// `pos` counts up-arrow presses, so 0 is the newest line.
+    fn read(&self, pos: usize) -> Option<String> {
+        self.lines.iter().rev().nth(pos).cloned()
+    }
+
+    fn write(&mut self, line: &String) {
+        let line = line.trim();
+        if line.is_empty() || self.lines.last().is_some_and(|last| last == line) {
+            return;
+        }
+        self.lines.push(line.to_string());
+        let excess = self.lines.len().saturating_sub(MAX_SHELL_HISTORY);
+        self.lines.drain(..excess);
+        // History is a convenience; failing to save it mustn't interrupt.
+        if let Some(state) = &self.state {
+            let _ = state.record_shell_command(line);
+        }
+    }
+}
+
+/// Tab completion: subcommands and flags from the command tree, names from
+/// the environment.
+struct Completer {
+    grammar: Command,
+    resources: Resources,
+}
+
+impl Completer {
+    fn new(grammar: Command) -> Self {
+        Self {
+            grammar,
+            resources: Resources::default(),
+        }
+    }
+
+    /// Candidates for the word being typed, given the words before it.
+    fn candidates(&self, before: &[&str], partial: &str) -> Vec<String> {
+        // Descend through the subcommands named so far; flags and their
+        // values don't change which command we're in.
+        let mut command = &self.grammar;
+        let mut path = Vec::new();
+        for word in before {
+            if let Some(sub) = command.find_subcommand(word) {
+                command = sub;
+                path.push(sub.get_name());
+            }
+        }
+
+        if before.last() == Some(&"--env") {
+            return matching(
+                self.resources.environments.iter().map(String::as_str),
+                partial,
+            );
+        }
+        if partial.starts_with('-') {
+            let flags = command
+                .get_arguments()
+                .filter(|a| !a.is_hide_set())
+                .filter_map(|a| a.get_long())
+                .map(|long| format!("--{long}"));
+            return matching(
+                flags.collect::<Vec<_>>().iter().map(String::as_str),
+                partial,
+            );
+        }
+        if command.has_subcommands() {
+            let subcommands = command
+                .get_subcommands()
+                .filter(|c| !c.is_hide_set())
+                .map(Command::get_name);
+            return matching(subcommands, partial);
+        }
+        matching(self.resources.for_command(&path).into_iter(), partial)
+    }
+}
+
+impl Completion for Completer {
+    /// The whole line with the last word completed as far as it's
+    /// unambiguous: the full word and a space for a single match, the common
+    /// prefix for several.
+    fn get(&self, input: &str) -> Option<String> {
+        let (head, partial) = match input.rfind(' ') {
+            Some(i) => input.split_at(i + 1),
+            None => ("", input),
+        };
+        let before: Vec<&str> = head.split_whitespace().collect();
+        let candidates = self.candidates(&before, partial);
+        let completed = match candidates.as_slice() {
+            [] => return None,
+            [only] => format!("{only} "),
+            many => common_prefix(many),
+        };
+        (completed.len() > partial.len()).then(|| format!("{head}{completed}"))
+    }
+}
+
+/// `options` starting with `partial`, deduplicated, in order.
+fn matching<'a>(options: impl Iterator<Item = &'a str>, partial: &str) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for option in options.filter(|o| o.starts_with(partial)) {
+        if !out.iter().any(|o| o == option) {
+            out.push(option.to_string());
+        }
+    }
+    out
+}
+
+fn common_prefix(words: &[String]) -> String {
+    let mut prefix = words[0].clone();
+    for word in &words[1..] {
+        while !word.starts_with(&prefix) {
+            prefix.pop();
+        }
+    }
+    prefix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, ArgAction};
+
+    fn grammar() -> Command {
+        let env = || Arg::new("env").long("env");
+        Command::new("unisrv")
+            .no_binary_name(true)
+            .subcommand(
+                Command::new("instance")
+                    .subcommand(
+                        Command::new("list")
+                            .arg(Arg::new("all").long("all").action(ArgAction::SetTrue))
+                            .arg(env()),
+                    )
+                    .subcommand(Command::new("logs").arg(Arg::new("reference")).arg(env())),
+            )
+            .subcommand(
+                Command::new("network").subcommand(Command::new("ips").arg(Arg::new("network"))),
+            )
+    }
+
+    fn completer() -> Completer {
+        let mut completer = Completer::new(grammar());
+        completer.resources = Resources {
+            environments: vec!["prod".into(), "preview".into()],
+            instances: vec!["api-1".into(), "api-2".into(), "worker".into()],
+            networks: vec!["backend".into()],
+            ..Resources::default()
+        };
+        completer
+    }
+
+    #[test]
+    fn completes_subcommands_and_flags() {
+        let c = completer();
+        assert_eq!(c.get("ins").as_deref(), Some("instance "));
+        assert_eq!(c.get("instance l").as_deref(), None, "list and logs");
+        assert_eq!(c.get("instance li").as_deref(), Some("instance list "));
+        assert_eq!(
+            c.get("instance list --a").as_deref(),
+            Some("instance list --all ")
+        );
+        assert_eq!(c.get("zzz"), None);
+    }
+
+    #[test]
+    fn completes_names_for_the_command_at_hand() {
+        let c = completer();
+        assert_eq!(
+            c.get("instance logs w").as_deref(),
+            Some("instance logs worker ")
+        );
+        // Two instances share a prefix: complete what they have in common.
+        assert_eq!(
+            c.get("instance logs a").as_deref(),
+            Some("instance logs api-")
+        );
+        assert_eq!(
+            c.get("network ips b").as_deref(),
+            Some("network ips backend ")
+        );
+        assert_eq!(
+            c.get("instance list --env pro").as_deref(),
+            Some("instance list --env prod ")
+        );
+    }
+
+    #[test]
+    fn history_reads_newest_first_and_skips_repeats() {
+        let mut history = ShellHistory {
+            lines: vec![],
+            state: None,
+        };
+        history.write(&"instance ls".to_string());
+        history.write(&"instance ls".to_string());
+        history.write(&"  ".to_string());
+        history.write(&"network ips backend".to_string());
+        assert_eq!(history.read(0).as_deref(), Some("network ips backend"));
+        assert_eq!(history.read(1).as_deref(), Some("instance ls"));
+        assert_eq!(history.read(2), None);
+    }
+
+    #[test]
+    fn lines_split_like_a_shell() {
+        assert_eq!(
+            split(r#"instance run alpine:3 --env-var 'GREETING=hello world'"#).unwrap(),
+            vec![
+                "instance",
+                "run",
+                "alpine:3",
+                "--env-var",
+                "GREETING=hello world"
+            ]
+        );
+        assert!(split("instance show 'unterminated").is_err());
+    }
+}
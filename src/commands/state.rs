@@ -0,0 +1,118 @@
+//! `unisrv state` — inspect and reset the local state directory.
+
+use anyhow::{Result, anyhow};
+
+use crate::commands::confirm::{Prompter, TerminalPrompter};
+use crate::state::StateDir;
+
+/// `unisrv state clear`: delete all local state (history, caches, reports),
+/// asking first if that would forget a pending blue/green rollout.
+pub fn clear() -> Result<()> {
+    let state = StateDir::locate().ok_or_else(|| anyhow!("no state directory is available"))?;
+    clear_at(&state, &TerminalPrompter)
+}
+
+/// `unisrv state path`: print where local state is kept.
+pub fn path() -> Result<()> {
+    let state = StateDir::locate().ok_or_else(|| anyhow!("no state directory is available"))?;
+    println!("{}", state.root().display());
+    Ok(())
+}
+
+fn clear_at(state: &StateDir, prompter: &dyn Prompter) -> Result<()> {
+    let pending = state.pending_rollouts();
+    if !pending.is_empty() {
+        println!(
+            "{} Blue/green rollouts are pending for {}. Without local state, \
+             `unisrv rollout promote` and `abort` can't finish them.",
+            console::style("!").yellow().bold(),
+            pending.join(", ")
+        );
+        if !prompter.confirm("Clear local state anyway?")? {
+            println!("Nothing cleared.");
+            return Ok(());
+        }
+    }
+    let freed = state.clear()?;
+    if freed == 0 {
+        println!("No local state to clear ({}).", state.root().display());
+    } else {
+        println!(
+            "Cleared {} of local state ({}).",
+            human_bytes(freed),
+            state.root().display()
+        );
+    }
+    Ok(())
+}
+
+fn human_bytes(bytes: u64) -> String {
+    match bytes {
+        b if b < 1024 => format!("{b} B"),
+        b if b < 1024 * 1024 => format!("{:.1} KiB", b as f64 / 1024.0),
+        b => format!("{:.1} MiB", b as f64 / (1024.0 * 1024.0)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::confirm::tests::ScriptedPrompter;
+    use crate::state::PendingRollout;
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    #[test]
+    fn clear_at_wipes_the_directory() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = StateDir::new(tmp.path().join("state"));
+        state.remember_images(["nginx:1"]).unwrap();
+
+        let prompter = ScriptedPrompter::default();
+        clear_at(&state, &prompter).unwrap();
+
+        assert!(!state.root().exists());
+        assert!(prompter.asked.borrow().is_empty());
+    }
+
+    #[test]
+    fn clear_at_asks_before_forgetting_a_pending_rollout() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = StateDir::new(tmp.path().join("state"));
+        let pending = PendingRollout {
+            at: Utc::now(),
+            deployment: "web".into(),
+            deployment_id: Uuid::new_v4(),
+            service_id: Uuid::new_v4(),
+            image: "nginx:2".into(),
+            group: "web".into(),
+            preview_group: "web-preview".into(),
+            preview_path: "/_preview".into(),
+            instances: vec![],
+            targets: vec![],
+            configuration: None,
+        };
+        state
+            .save_pending_rollout("shop", "prod", "web", &pending)
+            .unwrap();
+
+        let declined = ScriptedPrompter::default();
+        clear_at(&state, &declined).unwrap();
+        assert_eq!(state.pending_rollout("shop", "prod", "web"), Some(pending));
+        assert_eq!(declined.asked.borrow().len(), 1);
+
+        let accepted = ScriptedPrompter {
+            confirm: true,
+            ..ScriptedPrompter::default()
+        };
+        clear_at(&state, &accepted).unwrap();
+        assert!(!state.root().exists());
+    }
+
+    #[test]
+    fn human_bytes_picks_a_readable_unit() {
+        assert_eq!(human_bytes(512), "512 B");
+        assert_eq!(human_bytes(1536), "1.5 KiB");
+        assert_eq!(human_bytes(3 * 1024 * 1024), "3.0 MiB");
+    }
+}
@@ -0,0 +1,165 @@
+//! `unisrv stats self` — the user's own use of the CLI: how often each
+//! command ran, how often it failed, and how long it took.
+//!
+//! Read from the command runs [`crate::telemetry`] keeps in the local state
+//! directory, whether or not remote telemetry is on; nothing here leaves the
+//! machine.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Result, anyhow};
+use comfy_table::{Attribute, Cell, CellAlignment, Table};
+use serde::Serialize;
+
+use crate::commands::locale::{self, Locale};
+use crate::commands::output::Output;
+use crate::commands::ui::new_table;
+use crate::state::{CommandRun, StateDir};
+
+/// One command's runs.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct CommandStats {
+    pub command: String,
+    pub runs: usize,
+    pub failures: usize,
+    pub median_ms: u64,
+    pub p95_ms: u64,
+}
+
+/// `unisrv stats self`.
+pub fn show_self(output: &Output) -> Result<()> {
+    let state = StateDir::locate().ok_or_else(|| anyhow!("no state directory is available"))?;
+    let runs = state.command_runs();
+    let stats = summarize(&runs);
+    match output {
+        Output::Json | Output::Jq(_) => return output.print_json(&stats),
+        Output::Template(template) => {
+            print!("{}", template.render_all(&stats)?);
+            return Ok(());
+        }
+        Output::Table => {}
+    }
+    let Some(first) = runs.first() else {
+        println!("No commands recorded yet ({}).", state.root().display());
+        return Ok(());
+    };
+    let locale = locale::current();
+    println!(
+        "{} commands since {}",
+        locale.format_int(runs.len() as u64),
+        locale.format_datetime(first.at)
+    );
+    println!("{}", render_table(&stats, locale));
+    Ok(())
+}
+
+/// Per-command totals and latencies, most used first.
+fn summarize(runs: &[CommandRun]) -> Vec<CommandStats> {
+    let mut by_command: BTreeMap<&str, Vec<&CommandRun>> = BTreeMap::new();
+    for run in runs {
+        by_command.entry(&run.command).or_default().push(run);
+    }
+    let mut stats: Vec<CommandStats> = by_command
+        .into_iter()
+        .map(|(command, runs)| {
+            let mut durations: Vec<u64> = runs.iter().map(|r| r.duration_ms).collect();
+            durations.sort_unstable();
+            CommandStats {
+                command: command.to_string(),
+                runs: runs.len(),
+                failures: runs.iter().filter(|r| !r.success).count(),
+                median_ms: percentile(&durations, 50),
+                p95_ms: percentile(&durations, 95),
+            }
+        })
+        .collect();
+    // Stable, so equally used commands stay in name order.
+    stats.sort_by_key(|s| std::cmp::Reverse(s.runs));
+    stats
+}
+
+/// The nearest-rank `p`th percentile of `sorted`, which isn't empty.
+fn percentile(sorted: &[u64], p: usize) -> u64 {
+    let rank = (sorted.len() * p).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+fn render_table(stats: &[CommandStats], locale: &Locale) -> Table {
+    let mut table = new_table();
+    table.set_header(
+        ["COMMAND", "RUNS", "FAILED", "P50", "P95"]
+            .map(|h| Cell::new(h).add_attribute(Attribute::Bold)),
+    );
+    for s in stats {
+        let failed = s.failures as f64 * 100.0 / s.runs as f64;
+        table.add_row(vec![
+            Cell::new(&s.command),
+            Cell::new(locale.format_int(s.runs as u64)).set_alignment(CellAlignment::Right),
+            Cell::new(format!("{}%", locale.format_decimal(failed, 1)))
+                .set_alignment(CellAlignment::Right),
+            Cell::new(format_ms(s.median_ms, locale)).set_alignment(CellAlignment::Right),
+            Cell::new(format_ms(s.p95_ms, locale)).set_alignment(CellAlignment::Right),
+        ]);
+    }
+    table
+}
+
+fn format_ms(ms: u64, locale: &Locale) -> String {
+    if ms >= 1000 {
+        format!("{} s", locale.format_decimal(ms as f64 / 1000.0, 2))
+    } else {
+        format!("{ms} ms")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn run(command: &str, duration_ms: u64, success: bool) -> CommandRun {
+        CommandRun {
+            at: Utc::now(),
+            command: command.into(),
+            duration_ms,
+            success,
+        }
+    }
+
+    #[test]
+    fn summarizes_each_command_most_used_first() {
+        let mut runs = vec![run("up", 4000, true), run("up", 9000, false)];
+        runs.extend((1..=20).map(|i| run("instance ls", i * 10, i != 7)));
+        runs.push(run("auth token", 5, true));
+        let stats = summarize(&runs);
+        assert_eq!(
+            stats,
+            [
+                CommandStats {
+                    command: "instance ls".into(),
+                    runs: 20,
+                    failures: 1,
+                    median_ms: 100,
+                    p95_ms: 190,
+                },
+                CommandStats {
+                    command: "up".into(),
+                    runs: 2,
+                    failures: 1,
+                    median_ms: 4000,
+                    p95_ms: 9000,
+                },
+                CommandStats {
+                    command: "auth token".into(),
+                    runs: 1,
+                    failures: 0,
+                    median_ms: 5,
+                    p95_ms: 5,
+                },
+            ]
+        );
+        let table = render_table(&stats[1..2], &Locale::default()).to_string();
+        assert!(table.contains("50.0%"), "{table}");
+        assert!(table.contains("9.00 s"), "{table}");
+    }
+}
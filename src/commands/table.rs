@@ -0,0 +1,255 @@
+//! The tables `list` commands print, and their `--columns`, `--sort` and
+//! `--no-trunc` options.
+//!
+//! A list declares its columns once — the key `--columns` picks it by, its
+//! header, and how an item renders in it — and hands the items themselves to
+//! [`draw_table`], so choosing and ordering columns works the same on every
+//! list. `--sort` orders the items before any output, so `--json` and `-q`
+//! follow it too.
+//!
+//! By default ids are cut to their first 8 characters and cells wrap to fit
+//! the terminal; `--no-trunc` prints full ids and lets lines run long.
+
+use std::str::FromStr;
+
+use anyhow::{Result, bail};
+use chrono::{DateTime, Utc};
+use comfy_table::{Attribute, Cell, ContentArrangement};
+use uuid::Uuid;
+
+use crate::commands::ui::new_table;
+
+/// How a list's table is laid out.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TableOptions {
+    /// `--columns`, in the order given; the list's defaults without it.
+    pub columns: Option<Vec<String>>,
+    /// `--sort`; the API's order without it.
+    pub sort: Option<SortKey>,
+    pub no_trunc: bool,
+}
+
+/// `--sort`: what items are ordered by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey {
+    /// Newest first.
+    Created,
+    /// Alphabetical, unnamed last.
+    Name,
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "created" => Ok(SortKey::Created),
+            "name" => Ok(SortKey::Name),
+            _ => Err(format!("unknown sort key {s:?}: expected created or name")),
+        }
+    }
+}
+
+/// An item a list prints, as `--sort` sees it.
+pub trait Row {
+    fn name(&self) -> Option<&str>;
+
+    /// `None` for things the API doesn't date.
+    fn created(&self) -> Option<DateTime<Utc>> {
+        None
+    }
+}
+
+/// Order `rows` by `key`. `what` names them in the error for sorting by
+/// creation time things that don't have one.
+pub fn sort<R: Row>(rows: &mut [R], key: Option<SortKey>, what: &str) -> Result<()> {
+    match key {
+        None => {}
+        Some(SortKey::Name) => {
+            rows.sort_by(|a, b| (a.name().is_none(), a.name()).cmp(&(b.name().is_none(), b.name())))
+        }
+        Some(SortKey::Created) => {
+            if rows.iter().any(|r| r.created().is_none()) {
+                bail!("{what} have no creation time to sort by; use --sort name");
+            }
+            rows.sort_by_key(|r| std::cmp::Reverse(r.created()));
+        }
+    }
+    Ok(())
+}
+
+/// One column of a list's table.
+pub struct Column<'a, R> {
+    /// What `--columns` calls it, e.g. `managed-by`.
+    key: &'static str,
+    header: &'static str,
+    shown: bool,
+    cell: Box<dyn Fn(&R) -> Cell + 'a>,
+}
+
+impl<'a, R> Column<'a, R> {
+    /// A column shown unless `--columns` leaves it out.
+    pub fn new(key: &'static str, header: &'static str, cell: impl Fn(&R) -> Cell + 'a) -> Self {
+        Self {
+            key,
+            header,
+            shown: true,
+            cell: Box::new(cell),
+        }
+    }
+
+    /// Shown by default only when `shown`; `--columns` can always ask for it.
+    pub fn shown_if(mut self, shown: bool) -> Self {
+        self.shown = shown;
+        self
+    }
+}
+
+/// `id` as a table shows it: the first 8 characters, or all with
+/// `--no-trunc`.
+pub fn id_text(id: &Uuid, options: &TableOptions) -> String {
+    let id = id.to_string();
+    if options.no_trunc {
+        id
+    } else {
+        id[..8].to_string()
+    }
+}
+
+/// `rows` under the columns `options` selects. Fails on a `--columns` key
+/// the list doesn't have, naming the ones it does.
+pub fn draw_table<R>(
+    rows: &[R],
+    columns: &[Column<'_, R>],
+    options: &TableOptions,
+) -> Result<String> {
+    let selected = select(columns, options.columns.as_deref())?;
+    let mut table = new_table();
+    if options.no_trunc {
+        table.set_content_arrangement(ContentArrangement::Disabled);
+    }
+    table.set_header(
+        selected
+            .iter()
+            .map(|c| Cell::new(c.header).add_attribute(Attribute::Bold))
+            .collect::<Vec<_>>(),
+    );
+    for row in rows {
+        table.add_row(selected.iter().map(|c| (c.cell)(row)).collect::<Vec<_>>());
+    }
+    Ok(table.to_string())
+}
+
+fn select<'c, 'a, R>(
+    columns: &'c [Column<'a, R>],
+    keys: Option<&[String]>,
+) -> Result<Vec<&'c Column<'a, R>>> {
+    let Some(keys) = keys else {
+        return Ok(columns.iter().filter(|c| c.shown).collect());
+    };
+    keys.iter()
+        .map(|key| {
+            let key = key.trim().to_ascii_lowercase();
+            match columns.iter().find(|c| c.key == key) {
+                Some(column) => Ok(column),
+                None => bail!(
+                    "no column {key:?}; choose from {}",
+                    columns.iter().map(|c| c.key).collect::<Vec<_>>().join(", ")
+                ),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Item {
+        name: Option<&'static str>,
+        age: i64,
+    }
+
+    impl Row for Item {
+        fn name(&self) -> Option<&str> {
+            self.name
+        }
+
+        fn created(&self) -> Option<DateTime<Utc>> {
+            Some(DateTime::<Utc>::default() - chrono::Duration::days(self.age))
+        }
+    }
+
+    fn columns<'a>() -> Vec<Column<'a, Item>> {
+        vec![
+            Column::new("name", "NAME", |i: &Item| Cell::new(i.name.unwrap_or("-"))),
+            Column::new("age", "AGE", |i: &Item| Cell::new(i.age)),
+            Column::new("secret", "SECRET", |_: &Item| Cell::new("hidden")).shown_if(false),
+        ]
+    }
+
+    #[test]
+    fn columns_are_picked_and_ordered_by_key() {
+        let rows = [Item {
+            name: Some("web"),
+            age: 3,
+        }];
+        let default = draw_table(&rows, &columns(), &TableOptions::default()).unwrap();
+        assert!(
+            default.contains("NAME") && default.contains("AGE"),
+            "{default}"
+        );
+        assert!(!default.contains("SECRET"), "{default}");
+
+        let options = TableOptions {
+            columns: Some(vec!["secret".into(), " Name".into()]),
+            ..TableOptions::default()
+        };
+        let picked = draw_table(&rows, &columns(), &options).unwrap();
+        assert!(!picked.contains("AGE"), "{picked}");
+        assert!(picked.find("SECRET") < picked.find("NAME"), "{picked}");
+
+        let options = TableOptions {
+            columns: Some(vec!["image".into()]),
+            ..TableOptions::default()
+        };
+        let err = draw_table(&rows, &columns(), &options).unwrap_err();
+        assert!(
+            err.to_string().contains("choose from name, age, secret"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn sorts_by_name_unnamed_last_or_newest_first() {
+        let mut rows = vec![
+            Item { name: None, age: 1 },
+            Item {
+                name: Some("web"),
+                age: 5,
+            },
+            Item {
+                name: Some("api"),
+                age: 2,
+            },
+        ];
+        sort(&mut rows, Some(SortKey::Name), "items").unwrap();
+        let names: Vec<_> = rows.iter().map(|r| r.name).collect();
+        assert_eq!(names, [Some("api"), Some("web"), None]);
+
+        sort(&mut rows, Some(SortKey::Created), "items").unwrap();
+        let ages: Vec<_> = rows.iter().map(|r| r.age).collect();
+        assert_eq!(ages, [1, 2, 5]);
+    }
+
+    #[test]
+    fn ids_are_cut_unless_no_trunc() {
+        let id = Uuid::parse_str("0a1b2c3d-0000-0000-0000-000000000000").unwrap();
+        assert_eq!(id_text(&id, &TableOptions::default()), "0a1b2c3d");
+        let options = TableOptions {
+            no_trunc: true,
+            ..TableOptions::default()
+        };
+        assert_eq!(id_text(&id, &options), id.to_string());
+    }
+}
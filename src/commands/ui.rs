@@ -1,15 +1,62 @@
 //! Shared rendering helpers for the table-style command output (`host`,
 //! `instance ls`, …) so colour handling and relative-time formatting live in one
 //! place rather than being copy-pasted per command.
+//!
+//! Colour follows each stream: on for a colour terminal, off for a pipe or a
+//! file, and off everywhere with `--no-color` or `NO_COLOR` ([`disable_colors`]).
+//! Decorative emoji go wherever colour goes.
 
-use chrono::NaiveDateTime;
+use std::ops::Sub;
+
+use chrono::TimeDelta;
 use chrono_humanize::HumanTime;
-use comfy_table::{Cell, Color};
+use comfy_table::{Cell, Color, ContentArrangement, Table, presets::UTF8_FULL};
+
+/// Turns colour off when set to anything, as <https://no-color.org> asks.
+pub const NO_COLOR_ENV: &str = "NO_COLOR";
+
+/// Turn colour off on stdout and stderr for the rest of the process. Set from
+/// `--no-color` or `NO_COLOR`.
+pub fn disable_colors() {
+    console::set_colors_enabled(false);
+    console::set_colors_enabled_stderr(false);
+}
 
-/// Whether stdout currently supports ANSI colour. Centralised so every table
-/// decides colour the same way.
+/// Whether stdout gets ANSI colour. Centralised so every table decides colour
+/// the same way.
 pub fn colors_enabled() -> bool {
-    console::Term::stdout().features().colors_supported()
+    console::colors_enabled()
+}
+
+/// Whether stderr gets ANSI colour, for what's printed there.
+pub fn stderr_colors_enabled() -> bool {
+    console::colors_enabled_stderr()
+}
+
+/// `symbol` and a space to lead a stdout line with, or nothing where colour
+/// is off: an emoji is as out of place in a log file as an escape code.
+pub fn emoji(symbol: &str) -> String {
+    if colors_enabled() {
+        format!("{symbol} ")
+    } else {
+        String::new()
+    }
+}
+
+/// An empty table in the house style. comfy-table styles headers whenever
+/// stdout is a terminal, so with colour off it's told there isn't one, and
+/// given the terminal's width it would otherwise stop measuring.
+pub fn new_table() -> Table {
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL);
+    table.set_content_arrangement(ContentArrangement::Dynamic);
+    if !colors_enabled() {
+        if let Some((_, width)) = console::Term::stdout().size_checked() {
+            table.set_width(width);
+        }
+        table.force_no_tty();
+    }
+    table
 }
 
 /// Build a table cell, applying `color` only when colour is enabled.
@@ -21,7 +68,24 @@ pub fn cell_with_color(text: String, color: Option<Color>, use_color: bool) -> C
     }
 }
 
-/// Render `when` relative to `now`, e.g. "5 minutes ago".
-pub fn format_relative(when: NaiveDateTime, now: NaiveDateTime) -> String {
+/// Render `when` relative to `now`, e.g. "5 minutes ago". Works for naive and
+/// UTC times alike, as long as both are the same kind.
+pub fn format_relative<T: Sub<Output = TimeDelta>>(when: T, now: T) -> String {
     HumanTime::from(when - now).to_string()
 }
+
+/// A byte count in binary units, e.g. "1.46 KiB" (as indicatif shows them,
+/// so tables and progress bars agree).
+pub fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.2} {}", UNITS[unit])
+}
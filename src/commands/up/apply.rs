@@ -30,7 +30,16 @@
 //! delete while any non-stopped instance is attached, so deletes are gated on
 //! a bounded drain wait. Do not reorder.
 //!
-//! No rollback. On error, return immediately. Reconcile re-run will pick up.
+//! Within the create phases (#2, #3, #10) and the link pass (#12) nothing
+//! depends on anything else in the same phase, so those run concurrently,
+//! bounded by [`MAX_IN_FLIGHT`](crate::commands::concurrent::MAX_IN_FLIGHT):
+//! a thirty-deployment stack takes about as long to create as the slowest few.
+//! Every request a phase starts is seen through before its first error is
+//! returned, so each one that landed is journaled.
+//!
+//! On error, return immediately: a reconcile re-run will pick up. Each step
+//! that lands is journaled (see [`super::rollback`]) so `up` can report what
+//! was left in place, or undo it with `--rollback-on-failure`.
 
 use anyhow::{Context, Result};
 use async_trait::async_trait;
@@ -50,6 +59,8 @@ use super::plan::{
     Plan, ResolvedServiceBinding, ResourceRef, ServiceAction,
 };
 use super::render::{Reachability, render_reachability};
+use super::rollback::{Change, Journal};
+use crate::commands::concurrent::apply_each;
 use crate::commands::host::normalize_host;
 use crate::progress::{Icon, Progress, Tone};
 
@@ -70,6 +81,16 @@ impl Waiter for RealWaiter {
     }
 }
 
+/// Test waiter that never sleeps, so poll loops run instantly.
+#[cfg(test)]
+pub(crate) struct NoSleep;
+
+#[cfg(test)]
+#[async_trait]
+impl Waiter for NoSleep {
+    async fn sleep(&self, _dur: Duration) {}
+}
+
 /// Poll cadence and ceiling while waiting for a network's instances to stop
 /// before deleting it. Bounded so a stuck teardown can't hang the CLI — on
 /// timeout we error with a rerun hint (apply is reconcile-on-rerun).
@@ -122,6 +143,26 @@ pub async fn apply(
     hosts: &[HostResponse],
     waiter: &dyn Waiter,
     progress: &dyn Progress,
+) -> Result<()> {
+    apply_recorded(
+        plan,
+        client,
+        hosts,
+        waiter,
+        progress,
+        &mut Journal::default(),
+    )
+    .await
+}
+
+/// [`apply`], recording each change that lands in `journal`.
+pub async fn apply_recorded(
+    plan: Plan,
+    client: &dyn ApiClient,
+    hosts: &[HostResponse],
+    waiter: &dyn Waiter,
+    progress: &dyn Progress,
+    journal: &mut Journal,
 ) -> Result<()> {
     // Host string → claimed-host id, for resolving link/unlink targets. Hosts
     // are user-global (not env-scoped), so this is just an id dictionary.
@@ -192,6 +233,8 @@ pub async fn apply(
     // Standalone instances to tear down (destroy only; empty for up). Captured
     // before the plan's other fields are consumed by partitioning below.
     let instance_stops = plan.instance_stops;
+    // Everything this apply creates is stamped as the project's own.
+    let project = plan.project;
 
     // ── Phase 1: env ──
     let (env_id, env_slug) = match plan.env_action {
@@ -206,9 +249,14 @@ pub async fn apply(
                 .await
                 .with_context(|| format!("failed to create environment {:?}", req.name))?;
             step.finish(Tone::Add, &format!("environment {} created", env.name));
+            journal.record(Change::EnvironmentCreated {
+                id: env.id,
+                name: env.name,
+            });
             (env.id, env.slug)
         }
     };
+    journal.env_id = Some(env_id);
 
     // Minted ids: name → uuid for resources created or recreated DURING this
     // apply. References to anything else were resolved at plan time
@@ -222,20 +270,38 @@ pub async fn apply(
     let networks = PartitionedNetworks::from_actions(plan.network_actions);
 
     // ── Phase 2: create new networks (before anything references them) ──
-    for desired in networks.creates {
+    let project = project.as_str();
+    let created = apply_each(networks.creates, |desired| async move {
         let step = progress.step(Icon::Network, &format!("Creating network {}", desired.name));
-        let id = create_network(client, env_id, &desired).await?;
-        minted_network_ids.insert(desired.name.clone(), id);
+        let id = create_network(client, env_id, project, &desired).await?;
         step.finish(Tone::Add, &format!("network {} created", desired.name));
-    }
+        Ok((id, desired))
+    })
+    .await;
+    record_all(created, |(id, desired)| {
+        minted_network_ids.insert(desired.name.clone(), id);
+        journal.record(Change::NetworkCreated {
+            id,
+            name: desired.name,
+            ipv4_cidr: desired.ipv4_cidr,
+        });
+    })?;
 
     // ── Phase 3: create new services ──
-    for desired in services.creates {
+    let created = apply_each(services.creates, |desired| async move {
         let step = progress.step(Icon::Service, &format!("Creating service {}", desired.name));
-        let id = create_service(client, env_id, &desired).await?;
-        minted_service_ids.insert(desired.name.clone(), id);
+        let id = create_service(client, env_id, project, &desired).await?;
         step.finish(Tone::Add, &format!("service {} created", desired.name));
-    }
+        Ok((id, desired))
+    })
+    .await;
+    record_all(created, |(id, desired)| {
+        minted_service_ids.insert(desired.name.clone(), id);
+        journal.record(Change::ServiceCreated {
+            id,
+            name: desired.name,
+        });
+    })?;
 
     // ── Phase 4: update services (config only; skip when config is unchanged
     //    and only the host set differs — hosts are reconciled by link/unlink) ──
@@ -247,6 +313,11 @@ pub async fn apply(
                 .await
                 .with_context(|| format!("failed to update service {:?}", desired.name))?;
             step.finish(Tone::Change, &format!("service {} updated", desired.name));
+            journal.record(Change::ServiceUpdated {
+                id,
+                name: desired.name,
+                previous: current.configuration,
+            });
         }
     }
 
@@ -270,6 +341,11 @@ pub async fn apply(
                 .await
                 .with_context(|| format!("failed to unlink host {host:?}"))?;
             step.finish(Tone::Remove, &format!("host {host} unlinked"));
+            journal.record(Change::HostUnlinked {
+                host_id,
+                service_id: *service_id,
+                host: host.clone(),
+            });
         }
     }
 
@@ -281,6 +357,7 @@ pub async fn apply(
             .await
             .with_context(|| format!("failed to delete deployment {name:?}"))?;
         step.finish(Tone::Remove, &format!("deployment {name} deleted"));
+        journal.record(Change::Irreversible(format!("deployment {name} deleted")));
     }
 
     // ── Phase 7: update deployments ──
@@ -294,7 +371,7 @@ pub async fn apply(
     //
     // The backend treats an absent `network_id` as a detach, so the resolved
     // desired binding is sent on EVERY update — even config-only ones.
-    for (id, desired, network) in deployments.updates.drain(..) {
+    for (id, desired, current, network) in deployments.updates.drain(..) {
         let step = progress.step(
             Icon::Deployment,
             &format!("Updating deployment {}", desired.name),
@@ -318,6 +395,15 @@ pub async fn apply(
             Tone::Change,
             &format!("deployment {} updated", desired.name),
         );
+        journal.record(Change::DeploymentUpdated {
+            id,
+            name: desired.name,
+            replicas: desired.configuration.replicas,
+            previous: UpdateDeploymentRequest {
+                network_id: current.network_binding.map(|b| b.network_id),
+                configuration: current.configuration,
+            },
+        });
     }
 
     // ── Phase 8: recreate services (delete then create) ──
@@ -330,12 +416,20 @@ pub async fn apply(
             .delete_service(env_id, current.id)
             .await
             .with_context(|| format!("failed to delete service {:?}", current.name))?;
-        let new_id = create_service(client, env_id, &desired).await?;
+        journal.record(Change::Irreversible(format!(
+            "service {} deleted",
+            current.name
+        )));
+        let new_id = create_service(client, env_id, project, &desired).await?;
         minted_service_ids.insert(desired.name.clone(), new_id);
         step.finish(
             Tone::Recreate,
             &format!("service {} recreated", desired.name),
         );
+        journal.record(Change::ServiceCreated {
+            id: new_id,
+            name: desired.name,
+        });
     }
 
     // ── Phase 9: recreate networks (drain → delete old → create new) ──
@@ -354,32 +448,55 @@ pub async fn apply(
             .delete_network(env_id, current.id)
             .await
             .with_context(|| format!("failed to delete network {:?}", current.name))?;
-        let new_id = create_network(client, env_id, &desired).await?;
+        journal.record(Change::Irreversible(format!(
+            "network {} deleted",
+            current.name
+        )));
+        let new_id = create_network(client, env_id, project, &desired).await?;
         minted_network_ids.insert(desired.name.clone(), new_id);
         step.finish(
             Tone::Recreate,
             &format!("network {} recreated", desired.name),
         );
+        journal.record(Change::NetworkCreated {
+            id: new_id,
+            name: desired.name,
+            ipv4_cidr: desired.ipv4_cidr,
+        });
     }
 
     // ── Phase 10: create deployments (new + recreated) ──
-    for (desired, service, network) in deployments.drain_for_create() {
-        let step = progress.step(
-            Icon::Deployment,
-            &format!("Creating deployment {}", desired.name),
-        );
-        create_deployment(
-            client,
-            env_id,
-            &desired,
-            service.as_ref(),
-            network.as_ref(),
-            &minted_service_ids,
-            &minted_network_ids,
-        )
-        .await?;
-        step.finish(Tone::Add, &format!("deployment {} created", desired.name));
-    }
+    let (minted_services, minted_networks) = (&minted_service_ids, &minted_network_ids);
+    let created = apply_each(
+        deployments.drain_for_create(),
+        |(desired, service, network)| async move {
+            let step = progress.step(
+                Icon::Deployment,
+                &format!("Creating deployment {}", desired.name),
+            );
+            let id = create_deployment(
+                client,
+                env_id,
+                project,
+                &desired,
+                service.as_ref(),
+                network.as_ref(),
+                minted_services,
+                minted_networks,
+            )
+            .await?;
+            step.finish(Tone::Add, &format!("deployment {} created", desired.name));
+            Ok((id, desired))
+        },
+    )
+    .await;
+    record_all(created, |(id, desired)| {
+        journal.record(Change::DeploymentCreated {
+            id,
+            name: desired.name,
+            replicas: desired.configuration.replicas,
+        });
+    })?;
 
     // ── Phase 11: delete services being removed ──
     //
@@ -397,21 +514,37 @@ pub async fn apply(
             .await
             .with_context(|| format!("failed to delete service {:?}", current.name))?;
         step.finish(Tone::Remove, &format!("service {} deleted", current.name));
+        journal.record(Change::Irreversible(format!(
+            "service {} deleted",
+            current.name
+        )));
     }
 
     // ── Phase 12: link pass — bind desired hosts to their (now final-id) services ──
+    let mut links = Vec::new();
     for (service_ref, to_link) in &host_links {
         let service_id = resolve_ref(service_ref, &minted_service_ids)?;
         for host in to_link {
-            let host_id = resolve_host_id(&host_ids, host)?;
-            let step = progress.step(Icon::Host, &format!("Linking host {host}"));
-            client
-                .link_host_to_service(host_id, service_id)
-                .await
-                .with_context(|| format!("failed to link host {host:?}"))?;
-            step.finish(Tone::Add, &format!("host {host} linked"));
+            links.push((resolve_host_id(&host_ids, host)?, service_id, host));
         }
     }
+    let linked = apply_each(links, |(host_id, service_id, host)| async move {
+        let step = progress.step(Icon::Host, &format!("Linking host {host}"));
+        client
+            .link_host_to_service(host_id, service_id)
+            .await
+            .with_context(|| format!("failed to link host {host:?}"))?;
+        step.finish(Tone::Add, &format!("host {host} linked"));
+        Ok((host_id, service_id, host))
+    })
+    .await;
+    record_all(linked, |(host_id, service_id, host)| {
+        journal.record(Change::HostLinked {
+            host_id,
+            service_id,
+            host: host.clone(),
+        });
+    })?;
 
     // ── Phase 13: stop pass — deprovision standalone instances (destroy only) ──
     //
@@ -427,6 +560,7 @@ pub async fn apply(
             .await
             .with_context(|| format!("failed to stop instance {name}"))?;
         step.finish(Tone::Remove, &format!("instance {name} stopped"));
+        journal.record(Change::Irreversible(format!("instance {name} stopped")));
     }
 
     // ── Phase 14: delete removed networks ──
@@ -444,6 +578,10 @@ pub async fn apply(
             .await
             .with_context(|| format!("failed to delete network {:?}", current.name))?;
         step.finish(Tone::Remove, &format!("network {} deleted", current.name));
+        journal.record(Change::Irreversible(format!(
+            "network {} deleted",
+            current.name
+        )));
     }
 
     // Reachability summary: every acted-on service's live base host + customs.
@@ -460,6 +598,21 @@ pub async fn apply(
     Ok(())
 }
 
+/// Hand every change a concurrent phase made to `record`, in plan order, then
+/// fail with the phase's first error, if any.
+fn record_all<T>(outcomes: Vec<Result<T>>, mut record: impl FnMut(T)) -> Result<()> {
+    let mut first_error = None;
+    for outcome in outcomes {
+        match outcome {
+            Ok(change) => record(change),
+            Err(err) => {
+                first_error.get_or_insert(err);
+            }
+        }
+    }
+    first_error.map_or(Ok(()), Err)
+}
+
 /// Resolve a host string to its claimed-host id. Preflight guarantees every
 /// referenced host is claimed, so a miss is an internal inconsistency.
 fn resolve_host_id(host_ids: &BTreeMap<String, Uuid>, host: &str) -> Result<Uuid> {
@@ -495,7 +648,7 @@ fn resolve_ref(r: &ResourceRef, minted: &BTreeMap<String, Uuid>) -> Result<Uuid>
 /// owned by a deployment are still converging (operator mid-roll / teardown in
 /// flight) and a rerun finishes the job; standalone instances will never be
 /// stopped by this command and need explicit action.
-async fn wait_for_network_drain(
+pub(super) async fn wait_for_network_drain(
     client: &dyn ApiClient,
     env_id: Uuid,
     network: &CurrentNetwork,
@@ -616,7 +769,12 @@ type DeploymentCreate = (
 #[derive(Default)]
 struct PartitionedDeployments {
     creates: Vec<DeploymentCreate>,
-    updates: Vec<(Uuid, DesiredDeployment, Option<ResourceRef>)>,
+    updates: Vec<(
+        Uuid,
+        DesiredDeployment,
+        CurrentDeployment,
+        Option<ResourceRef>,
+    )>,
     recreates: Vec<(CurrentDeployment, DeploymentCreate)>,
     deletes: Vec<CurrentDeployment>,
 }
@@ -634,9 +792,9 @@ impl PartitionedDeployments {
                 DeploymentAction::Update {
                     id,
                     desired,
+                    current,
                     network,
-                    ..
-                } => p.updates.push((id, desired, network)),
+                } => p.updates.push((id, desired, current, network)),
                 DeploymentAction::Recreate {
                     current,
                     desired,
@@ -702,11 +860,13 @@ impl PartitionedNetworks {
 async fn create_network(
     client: &dyn ApiClient,
     env_id: Uuid,
+    project: &str,
     desired: &DesiredNetwork,
 ) -> Result<Uuid> {
     let req = CreateInternalNetworkRequest {
         name: desired.name.clone(),
         ipv4_cidr: desired.ipv4_cidr.clone(),
+        managed_by: Some(project.to_string()),
     };
     let resp = client
         .create_network(env_id, req)
@@ -718,6 +878,7 @@ async fn create_network(
 async fn create_service(
     client: &dyn ApiClient,
     env_id: Uuid,
+    project: &str,
     desired: &DesiredService,
 ) -> Result<Uuid> {
     let req = ServiceProvisionRequest {
@@ -725,6 +886,7 @@ async fn create_service(
         name: desired.name.clone(),
         configuration: desired.configuration.clone(),
         instance_targets: vec![],
+        managed_by: Some(project.to_string()),
     };
     let resp = client
         .provision_service(env_id, req)
@@ -737,12 +899,13 @@ async fn create_service(
 async fn create_deployment(
     client: &dyn ApiClient,
     env_id: Uuid,
+    project: &str,
     desired: &DesiredDeployment,
     service: Option<&ResolvedServiceBinding>,
     network: Option<&ResourceRef>,
     minted_service_ids: &BTreeMap<String, Uuid>,
     minted_network_ids: &BTreeMap<String, Uuid>,
-) -> Result<()> {
+) -> Result<Uuid> {
     let service = service
         .map(|b| {
             Ok::<_, anyhow::Error>(DeploymentServiceBinding {
@@ -758,12 +921,13 @@ async fn create_deployment(
             .map(|r| resolve_ref(r, minted_network_ids))
             .transpose()?,
         configuration: desired.configuration.clone(),
+        managed_by: Some(project.to_string()),
     };
-    client
+    let resp = client
         .create_deployment(env_id, req)
         .await
         .with_context(|| format!("failed to create deployment {:?}", desired.name))?;
-    Ok(())
+    Ok(resp.id)
 }
 
 #[cfg(test)]
@@ -784,14 +948,6 @@ mod tests {
 
     use crate::commands::up::plan::ResolvedEnvironment;
 
-    /// Test waiter that never sleeps, so drain polls run instantly.
-    struct NoSleep;
-
-    #[async_trait::async_trait]
-    impl Waiter for NoSleep {
-        async fn sleep(&self, _dur: std::time::Duration) {}
-    }
-
     /// Network fixture as the backend returns it from create/get.
     fn network_response(
         id: Uuid,
@@ -1139,6 +1295,7 @@ mod tests {
                     id: Uuid::new_v4(),
                     name: "api".into(),
                 }),
+                retained_until: None,
             }],
         }));
 
@@ -1337,6 +1494,7 @@ mod tests {
                 container_image: "redis:7".into(),
                 created_at: NaiveDateTime::default(),
                 deployment: None, // standalone
+                retained_until: None,
             }],
         }));
 
@@ -1425,8 +1583,8 @@ mod tests {
             service_id: None,
             certificate_type: None,
             certificate_valid_until: None,
-            created_at: NaiveDateTime::default(),
-            updated_at: NaiveDateTime::default(),
+            created_at: Default::default(),
+            updated_at: Default::default(),
         }
     }
 
@@ -1693,10 +1851,19 @@ mod tests {
             locations: vec![HTTPLocation {
                 path: "/".into(),
                 override_404: None,
+                headers: Default::default(),
+                websocket: false,
+                grpc: false,
+                cache: None,
                 target: HTTPLocationTarget::Instance {
                     group: "default".into(),
+                    rewrite: None,
                 },
             }],
+            basic_auth: vec![],
+            allow_ips: vec![],
+            tls: Default::default(),
+            compression: None,
         }
     }
 
@@ -1711,6 +1878,8 @@ mod tests {
             vcpu_count: 1,
             memory_mb: 256,
             instance_port: Some(80),
+            init_containers: None,
+            sidecars: None,
         }
     }
 
@@ -2117,6 +2286,47 @@ mod tests {
         let calls = client.calls.lock().unwrap();
         let (_env, req) = &calls.create_deployment_calls[0];
         assert!(req.service.is_none());
+        assert_eq!(req.managed_by.as_deref(), Some("demo"));
+    }
+
+    #[tokio::test]
+    async fn a_failed_create_still_journals_the_phase_creates_that_landed() {
+        let created = Uuid::new_v4();
+        let client = MockApiClient::logged_in()
+            .push_create_deployment(Err(unisrv_api::ApiError::Server {
+                status: 500,
+                reason: "boom".into(),
+            }))
+            .push_create_deployment(Ok(CreateDeploymentResponse { id: created }));
+        let deployment = |name: &str| DeploymentAction::Create {
+            service: None,
+            network: None,
+            desired: DesiredDeployment {
+                network: None,
+                name: name.into(),
+                configuration: dep_config("w:1"),
+                service_binding: None,
+            },
+        };
+        let plan = Plan {
+            network_actions: vec![],
+            project: "demo".into(),
+            env_action: use_env(),
+            service_actions: vec![],
+            deployment_actions: vec![deployment("a"), deployment("b")],
+            instance_stops: vec![],
+        };
+
+        let mut journal = Journal::default();
+        let err = apply_recorded(plan, &client, &[], &NoSleep, &SilentProgress, &mut journal)
+            .await
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("deployment \"a\""), "{err:#}");
+        assert_eq!(
+            client.calls.lock().unwrap().create_deployment_calls.len(),
+            2
+        );
+        assert_eq!(journal.rollouts(), [(created, "b".to_string(), 1)]);
     }
 
     /// Drives every variant of `ServiceAction` and `DeploymentAction` through
@@ -2408,4 +2618,73 @@ mod tests {
         // 10 → 11: deployment creates before the final service delete (delete-svc).
         assert!(last("create_deployment") < last("delete_service"));
     }
+
+    #[tokio::test]
+    async fn applies_over_http_with_minted_ids_in_request_bodies() {
+        // The same network-then-deployment rollout as above, but through the
+        // real HTTP client, so paths and request bodies are what's asserted.
+        use crate::commands::up::desired::DesiredNetwork;
+        use crate::commands::up::plan::NetworkAction;
+        use unisrv_api::test_support::server::{MockServer, Reply};
+        use unisrv_api::{AuthSession, HttpApiClient};
+
+        let env_id = Uuid::new_v4();
+        let net_id = Uuid::new_v4();
+        let server = MockServer::start().await;
+        server
+            .on(
+                "POST",
+                &format!("/environment/{env_id}/network"),
+                Reply::json(
+                    200,
+                    &network_response(net_id, "internal", "10.0.0.0/16", vec![]),
+                ),
+            )
+            .on(
+                "POST",
+                &format!("/environment/{env_id}/deployment"),
+                Reply::json(200, &CreateDeploymentResponse { id: Uuid::new_v4() }),
+            );
+        let session = AuthSession::test_session("t", chrono::Duration::hours(1));
+        let client = HttpApiClient::with_session(server.url(), Some(session));
+
+        let plan = Plan {
+            project: "demo".into(),
+            env_action: EnvAction::Use(ResolvedEnvironment {
+                id: env_id,
+                name: "prod".into(),
+                project: "demo".into(),
+                slug: "ab12".into(),
+            }),
+            service_actions: vec![],
+            deployment_actions: vec![DeploymentAction::Create {
+                service: None,
+                network: Some(ResourceRef::Pending {
+                    name: "internal".into(),
+                }),
+                desired: DesiredDeployment {
+                    name: "api".into(),
+                    configuration: dep_config("i:1"),
+                    service_binding: None,
+                    network: Some("internal".into()),
+                },
+            }],
+            network_actions: vec![NetworkAction::Create(DesiredNetwork {
+                name: "internal".into(),
+                ipv4_cidr: "10.0.0.0/16".into(),
+            })],
+            instance_stops: vec![],
+        };
+
+        apply(plan, &client, &[], &NoSleep, &SilentProgress)
+            .await
+            .unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2, "{requests:?}");
+        assert!(requests[0].path.ends_with("/network"));
+        assert_eq!(requests[0].json()["name"], "internal");
+        assert!(requests[1].path.ends_with("/deployment"));
+        assert_eq!(requests[1].json()["network_id"], net_id.to_string());
+    }
 }
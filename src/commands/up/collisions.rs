@@ -0,0 +1,391 @@
+//! Declared names already taken by something `up` doesn't own.
+//!
+//! `up` matches resources by name, so a network, service or deployment made
+//! some other way (`network create`, the dashboard, another project's `up`)
+//! under a declared name would be quietly taken over, and two resources
+//! sharing a name make that name ambiguous for every command that resolves
+//! one. Both are refused unless the run says how to settle them: `--adopt`
+//! takes the existing resource over and reconciles it to the config;
+//! `--replace` deletes it (and any namesakes) and creates the declared one.
+//!
+//! Ownership is the `managed_by` project the server records for what `up`
+//! creates. A server that doesn't record it reports none for anything, so
+//! then only duplicate names are collisions.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result, bail};
+use unisrv_api::ApiClient;
+use unisrv_api::models::{ManagedKind, SetManagedByRequest};
+use uuid::Uuid;
+
+use super::desired::DesiredState;
+use super::plan::CurrentState;
+use crate::progress::{Icon, Progress, Tone};
+
+/// How `--adopt` / `--replace` settle collisions; neither refuses them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Resolution {
+    #[default]
+    Refuse,
+    Adopt,
+    Replace,
+}
+
+impl Resolution {
+    pub fn from_flags(adopt: bool, replace: bool) -> Self {
+        match (adopt, replace) {
+            (true, _) => Resolution::Adopt,
+            (_, true) => Resolution::Replace,
+            _ => Resolution::Refuse,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Conflict {
+    /// One resource under the name, made outside this project's `up`.
+    Unowned { id: Uuid, owner: Option<String> },
+    /// Several resources share the name.
+    Duplicate { ids: Vec<Uuid> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Collision {
+    pub kind: ManagedKind,
+    pub name: String,
+    pub conflict: Conflict,
+}
+
+impl Collision {
+    fn describe(&self) -> String {
+        let kind = self.kind.as_str();
+        let name = &self.name;
+        match &self.conflict {
+            Conflict::Unowned { owner: None, .. } => {
+                format!("{kind} {name} exists but wasn't created by unisrv up")
+            }
+            Conflict::Unowned {
+                owner: Some(owner), ..
+            } => format!("{kind} {name} belongs to project {owner:?}"),
+            Conflict::Duplicate { ids } => format!("{} {kind}s are named {name}", ids.len()),
+        }
+    }
+}
+
+/// Every declared name that collides with what's in `current`.
+pub fn find(desired: &DesiredState, current: &CurrentState) -> Vec<Collision> {
+    let tracked = current.listed.iter().any(|l| l.managed_by.is_some());
+    let mut by_name: BTreeMap<(ManagedKind, &str), Vec<_>> = BTreeMap::new();
+    for listed in &current.listed {
+        by_name
+            .entry((listed.kind, listed.name.as_str()))
+            .or_default()
+            .push(listed);
+    }
+
+    let declared = desired
+        .networks
+        .keys()
+        .map(|name| (ManagedKind::Network, name))
+        .chain(
+            desired
+                .services
+                .keys()
+                .map(|name| (ManagedKind::Service, name)),
+        )
+        .chain(
+            desired
+                .deployments
+                .keys()
+                .map(|name| (ManagedKind::Deployment, name)),
+        );
+    let mut collisions = Vec::new();
+    for (kind, name) in declared {
+        let conflict = match by_name.get(&(kind, name.as_str())).map(Vec::as_slice) {
+            None | Some([]) => continue,
+            Some([only]) => {
+                if !tracked || only.managed_by.as_deref() == Some(desired.project.as_str()) {
+                    continue;
+                }
+                Conflict::Unowned {
+                    id: only.id,
+                    owner: only.managed_by.clone(),
+                }
+            }
+            Some(many) => Conflict::Duplicate {
+                ids: many.iter().map(|l| l.id).collect(),
+            },
+        };
+        collisions.push(Collision {
+            kind,
+            name: name.clone(),
+            conflict,
+        });
+    }
+    collisions
+}
+
+/// What settling the collisions leaves for after confirmation.
+#[derive(Debug, Default, PartialEq)]
+pub struct Settlement {
+    /// Resources to stamp as the project's own.
+    pub adopt: Vec<(ManagedKind, Uuid, String)>,
+    /// Namesakes to delete outright: the one `current` kept is recreated by
+    /// the plan instead.
+    pub remove: Vec<(ManagedKind, Uuid, String)>,
+}
+
+/// Settle `collisions` per `resolution`, marking what `--replace` recreates
+/// in `current` so the diff plans it. Refusing lists every collision.
+pub fn settle(
+    collisions: &[Collision],
+    resolution: Resolution,
+    current: &mut CurrentState,
+) -> Result<Settlement> {
+    let mut settlement = Settlement::default();
+    if collisions.is_empty() {
+        return Ok(settlement);
+    }
+    match resolution {
+        Resolution::Refuse => {
+            let lines: Vec<String> = collisions
+                .iter()
+                .map(|c| format!("  - {}", c.describe()))
+                .collect();
+            bail!(
+                "declared names are already taken:\n{}\n\
+                 Pass --adopt to take them over and reconcile them, or --replace to delete and recreate them.",
+                lines.join("\n")
+            );
+        }
+        Resolution::Adopt => {
+            for collision in collisions {
+                match &collision.conflict {
+                    Conflict::Unowned { id, .. } => {
+                        settlement
+                            .adopt
+                            .push((collision.kind, *id, collision.name.clone()));
+                    }
+                    Conflict::Duplicate { .. } => bail!(
+                        "can't adopt: {}. Delete the extra ones or pass --replace.",
+                        collision.describe()
+                    ),
+                }
+            }
+        }
+        Resolution::Replace => {
+            for collision in collisions {
+                let kept = kept_id(current, collision.kind, &collision.name);
+                if let Conflict::Duplicate { ids } = &collision.conflict {
+                    settlement.remove.extend(
+                        ids.iter()
+                            .filter(|id| Some(**id) != kept)
+                            .map(|id| (collision.kind, *id, collision.name.clone())),
+                    );
+                }
+                current
+                    .replacing
+                    .insert((collision.kind, collision.name.clone()));
+            }
+        }
+    }
+    Ok(settlement)
+}
+
+/// The id `current`'s by-name map holds for `name`.
+fn kept_id(current: &CurrentState, kind: ManagedKind, name: &str) -> Option<Uuid> {
+    match kind {
+        ManagedKind::Network => current.networks.get(name).map(|n| n.id),
+        ManagedKind::Service => current.services.get(name).map(|s| s.id),
+        ManagedKind::Deployment => current.deployments.get(name).map(|d| d.id),
+    }
+}
+
+impl Settlement {
+    /// Lines to show above the plan for what it doesn't cover itself.
+    pub fn notes(&self) -> Vec<String> {
+        let adopted = self
+            .adopt
+            .iter()
+            .map(|(kind, _, name)| format!("adopt {} {name}", kind.as_str()));
+        let removed = self
+            .remove
+            .iter()
+            .map(|(kind, id, name)| format!("delete {} {name} ({id})", kind.as_str()));
+        adopted.chain(removed).collect()
+    }
+
+    /// Stamp adopted resources and delete extra namesakes, deployments before
+    /// the services and networks they may use.
+    pub async fn carry_out(
+        &self,
+        client: &dyn ApiClient,
+        env_id: Uuid,
+        project: &str,
+        progress: &dyn Progress,
+    ) -> Result<()> {
+        for (kind, id, name) in &self.adopt {
+            let kind_name = kind.as_str();
+            let step = progress.step(icon(*kind), &format!("Adopting {kind_name} {name}"));
+            client
+                .set_managed_by(
+                    env_id,
+                    *kind,
+                    *id,
+                    SetManagedByRequest {
+                        managed_by: Some(project.to_string()),
+                    },
+                )
+                .await
+                .with_context(|| format!("failed to adopt {kind_name} {name:?}"))?;
+            step.finish(Tone::Change, &format!("{kind_name} {name} adopted"));
+        }
+        let mut remove: Vec<_> = self.remove.iter().collect();
+        remove.sort_by_key(|(kind, ..)| std::cmp::Reverse(*kind));
+        for (kind, id, name) in remove {
+            let kind_name = kind.as_str();
+            let step = progress.step(icon(*kind), &format!("Deleting {kind_name} {name} ({id})"));
+            let deleted = match kind {
+                ManagedKind::Network => client.delete_network(env_id, *id).await,
+                ManagedKind::Service => client.delete_service(env_id, *id).await,
+                ManagedKind::Deployment => client.delete_deployment(env_id, *id).await,
+            };
+            deleted.with_context(|| format!("failed to delete {kind_name} {name:?} ({id})"))?;
+            step.finish(Tone::Remove, &format!("{kind_name} {name} ({id}) deleted"));
+        }
+        Ok(())
+    }
+}
+
+fn icon(kind: ManagedKind) -> Icon {
+    match kind {
+        ManagedKind::Network => Icon::Network,
+        ManagedKind::Service => Icon::Service,
+        ManagedKind::Deployment => Icon::Deployment,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::up::config::UpConfig;
+    use crate::commands::up::plan::{CurrentNetwork, Listed};
+    use unisrv_api::test_support::MockApiClient;
+
+    fn declared() -> DesiredState {
+        DesiredState::from_config(
+            UpConfig::parse(
+                r#"
+project = "demo"
+network "backend" { iprange = "10.0.0.0/24" }
+deployment "api" {
+  container { image = "api:1" }
+}
+"#,
+            )
+            .unwrap(),
+        )
+    }
+
+    fn listed(kind: ManagedKind, name: &str, managed_by: Option<&str>) -> Listed {
+        Listed {
+            kind,
+            id: Uuid::new_v4(),
+            name: name.into(),
+            managed_by: managed_by.map(String::from),
+        }
+    }
+
+    #[test]
+    fn finds_unowned_and_duplicate_names() {
+        let mut current = CurrentState::empty();
+        current.listed = vec![
+            listed(ManagedKind::Network, "backend", None),
+            listed(ManagedKind::Deployment, "api", Some("demo")),
+            listed(ManagedKind::Deployment, "api", Some("demo")),
+            listed(ManagedKind::Deployment, "cron", Some("other")),
+        ];
+        let collisions = find(&declared(), &current);
+        assert_eq!(collisions.len(), 2, "{collisions:?}");
+        assert_eq!(
+            collisions[0].describe(),
+            "network backend exists but wasn't created by unisrv up"
+        );
+        assert_eq!(collisions[1].describe(), "2 deployments are named api");
+
+        // A server that doesn't record owners only flags duplicates.
+        current.listed[1].managed_by = None;
+        current.listed[2].managed_by = None;
+        current.listed[3].managed_by = None;
+        let collisions = find(&declared(), &current);
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(collisions[0].name, "api");
+    }
+
+    #[test]
+    fn refuses_unless_told_how_to_settle() {
+        let mut current = CurrentState::empty();
+        let network = listed(ManagedKind::Network, "backend", Some("other"));
+        let id = network.id;
+        current.listed = vec![network];
+        let collisions = find(&declared(), &current);
+
+        let err = settle(&collisions, Resolution::Refuse, &mut current).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("network backend belongs to project \"other\""),
+            "{err:#}"
+        );
+
+        let settlement = settle(&collisions, Resolution::Adopt, &mut current).unwrap();
+        assert_eq!(
+            settlement.adopt,
+            [(ManagedKind::Network, id, "backend".to_string())]
+        );
+        assert!(current.replacing.is_empty());
+
+        let settlement = settle(&collisions, Resolution::Replace, &mut current).unwrap();
+        assert_eq!(settlement, Settlement::default());
+        assert!(
+            current
+                .replacing
+                .contains(&(ManagedKind::Network, "backend".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn replace_removes_extra_namesakes_and_recreates_the_kept_one() {
+        let mut current = CurrentState::empty();
+        let kept = listed(ManagedKind::Network, "backend", None);
+        let extra = listed(ManagedKind::Network, "backend", None);
+        current.networks.insert(
+            "backend".into(),
+            CurrentNetwork {
+                id: kept.id,
+                name: "backend".into(),
+                ipv4_cidr: "10.0.0.0/24".into(),
+            },
+        );
+        current.listed = vec![kept, extra.clone()];
+        let collisions = find(&declared(), &current);
+
+        assert!(settle(&collisions, Resolution::Adopt, &mut current.clone()).is_err());
+        let settlement = settle(&collisions, Resolution::Replace, &mut current).unwrap();
+        assert_eq!(
+            settlement.remove,
+            [(ManagedKind::Network, extra.id, "backend".to_string())]
+        );
+
+        let env = Uuid::new_v4();
+        let client = MockApiClient::logged_in().push_delete_network(Ok(()));
+        settlement
+            .carry_out(&client, env, "demo", &crate::progress::SilentProgress)
+            .await
+            .unwrap();
+        assert_eq!(
+            client.calls.lock().unwrap().delete_network_calls,
+            [(env, extra.id)]
+        );
+    }
+}
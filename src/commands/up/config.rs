@@ -11,9 +11,13 @@ use indexmap::IndexMap;
 use serde::Deserialize;
 use std::collections::{BTreeMap, BTreeSet};
 use std::path::Path;
+use std::time::Duration;
+use unisrv_api::models::{CachePolicy, TlsVersion};
 
 use super::defaults::DEFAULT_LOCATION_PATH;
+use super::outputs::Outputs;
 use super::parse_error::{ConfigParseError, Locator};
+use super::ready::parse_duration;
 
 /// Outcome of resolving a config against a set of interpolation variables.
 ///
@@ -41,6 +45,10 @@ pub struct UpConfig {
     pub deployment: BTreeMap<String, DeploymentBlock>,
     #[serde(default)]
     pub network: BTreeMap<String, NetworkBlock>,
+    /// `output "NAME"` blocks, split off before evaluation; see
+    /// [`super::outputs`].
+    #[serde(skip)]
+    pub outputs: Box<Outputs>,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
@@ -64,6 +72,19 @@ pub struct ServiceBlock {
     /// permanently redirected to HTTPS instead.
     #[serde(default)]
     pub allow_http: Option<bool>,
+    /// Require HTTP basic auth on every request: a map of username to
+    /// password. Pass passwords in via `${var.…}` rather than committing them.
+    #[serde(default)]
+    pub basic_auth: Option<BTreeMap<String, String>>,
+    /// Only serve requests from these source IPs / CIDR blocks (IPv4 or IPv6).
+    #[serde(default)]
+    pub allow_ips: Option<Vec<String>>,
+    /// `tls { min_version = "1.3"  hsts = true }`: TLS behaviour at the edge.
+    #[serde(default)]
+    pub tls: Option<TlsBlock>,
+    /// Compress text responses at the edge; the platform decides when unset.
+    #[serde(default)]
+    pub compression: Option<bool>,
     /// Shorthand for `location "/" { deployment = "…" }`. Desugars to a
     /// catch-all appended *after* every explicit location, so it never shadows
     /// them under the proxy's first-match-wins order.
@@ -77,7 +98,7 @@ pub struct ServiceBlock {
 
 /// A `location "PATH" { … }` block inside a service: routes requests whose path
 /// starts with PATH to exactly one target — a deployment reference, a raw
-/// instance group, or an external URL.
+/// instance group, an external URL, or a redirect answered at the edge.
 #[derive(Debug, Deserialize, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct LocationBlock {
@@ -93,10 +114,57 @@ pub struct LocationBlock {
     /// prefix is stripped before forwarding.
     #[serde(default)]
     pub url: Option<String>,
+    /// Absolute URL to redirect matching requests to, answered by the edge
+    /// without contacting any upstream — e.g. a www→apex redirect.
+    #[serde(default)]
+    pub redirect: Option<String>,
     /// Path (plus optional query) to re-route to within the same target when
     /// the upstream responds 404 — e.g. "/index.html" for SPA fallback.
     #[serde(default)]
     pub override_404: Option<String>,
+    /// Response headers the edge sets on every response from this location,
+    /// e.g. `Strict-Transport-Security` or CORS headers.
+    #[serde(default)]
+    pub headers: Option<BTreeMap<String, String>>,
+    /// Replacement for the matched path prefix on instance targets, e.g.
+    /// `location "/api/" { rewrite = "/v1/" }` forwards `/api/x` as `/v1/x`.
+    #[serde(default)]
+    pub rewrite: Option<String>,
+    /// Shorthand for `rewrite = "/"`: forward `/api/x` as `/x`.
+    #[serde(default)]
+    pub strip_prefix: Option<bool>,
+    /// Pass WebSocket upgrades through to the target.
+    #[serde(default)]
+    pub websocket: Option<bool>,
+    /// Proxy to the target over HTTP/2, for gRPC backends.
+    #[serde(default)]
+    pub grpc: Option<bool>,
+    /// `cache { ttl = "1h"  stale_while_revalidate = "10m" }`: have the edge
+    /// cache this location's responses.
+    #[serde(default)]
+    pub cache: Option<CacheBlock>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct CacheBlock {
+    /// How long a response is served from the cache, e.g. "1h".
+    pub ttl: String,
+    /// How much longer an expired response is served while it's fetched
+    /// again, e.g. "10m".
+    #[serde(default)]
+    pub stale_while_revalidate: Option<String>,
+}
+
+impl CacheBlock {
+    /// The policy as the API takes it. Only call after `validate`.
+    pub fn policy(&self) -> CachePolicy {
+        let secs = |spec: &str| parse_duration(spec).expect("validated cache").as_secs();
+        CachePolicy {
+            ttl_secs: secs(&self.ttl),
+            stale_while_revalidate_secs: self.stale_while_revalidate.as_deref().map(secs),
+        }
+    }
 }
 
 /// The single resolved target of a location. A [`LocationBlock`] is parsed with
@@ -110,6 +178,8 @@ pub enum LocationTarget {
     InstanceGroup(String),
     /// Proxy to an external URL.
     Url(String),
+    /// Redirect to an absolute URL at the edge.
+    Redirect(String),
 }
 
 /// A location after desugaring: a service's explicit `location` blocks in
@@ -120,6 +190,14 @@ pub enum LocationTarget {
 pub struct ResolvedLocation<'a> {
     pub path: &'a str,
     pub override_404: Option<&'a str>,
+    /// `None` for the `deployment` shorthand, which has nowhere to set headers.
+    pub headers: Option<&'a BTreeMap<String, String>>,
+    /// The prefix replacement, with `strip_prefix = true` desugared to "/".
+    pub rewrite: Option<&'a str>,
+    pub websocket: bool,
+    pub grpc: bool,
+    /// `None` for the `deployment` shorthand, which has nowhere to set it.
+    pub cache: Option<&'a CacheBlock>,
     /// `None` only for a malformed location that does not set exactly one
     /// target — a state `validate` rejects, so post-validation consumers
     /// (`from_config`) may `expect` it.
@@ -128,15 +206,31 @@ pub struct ResolvedLocation<'a> {
 
 impl LocationBlock {
     /// The single resolved target, or `None` when not exactly one of
-    /// `deployment`/`instance_group`/`url` is set.
+    /// `deployment`/`instance_group`/`url`/`redirect` is set.
     fn target(&self) -> Option<LocationTarget> {
-        match (&self.deployment, &self.instance_group, &self.url) {
-            (Some(d), None, None) => Some(LocationTarget::Deployment(d.clone())),
-            (None, Some(g), None) => Some(LocationTarget::InstanceGroup(g.clone())),
-            (None, None, Some(u)) => Some(LocationTarget::Url(u.clone())),
+        match (
+            &self.deployment,
+            &self.instance_group,
+            &self.url,
+            &self.redirect,
+        ) {
+            (Some(d), None, None, None) => Some(LocationTarget::Deployment(d.clone())),
+            (None, Some(g), None, None) => Some(LocationTarget::InstanceGroup(g.clone())),
+            (None, None, Some(u), None) => Some(LocationTarget::Url(u.clone())),
+            (None, None, None, Some(r)) => Some(LocationTarget::Redirect(r.clone())),
             _ => None,
         }
     }
+
+    /// The effective prefix rewrite. When both `rewrite` and `strip_prefix`
+    /// are set, `rewrite` wins here; `validate` rejects that combination.
+    fn rewrite(&self) -> Option<&str> {
+        match (&self.rewrite, self.strip_prefix) {
+            (Some(r), _) => Some(r),
+            (None, Some(true)) => Some(DEFAULT_LOCATION_PATH),
+            (None, _) => None,
+        }
+    }
 }
 
 impl ServiceBlock {
@@ -150,6 +244,11 @@ impl ServiceBlock {
             .map(|(path, loc)| ResolvedLocation {
                 path,
                 override_404: loc.override_404.as_deref(),
+                headers: loc.headers.as_ref(),
+                rewrite: loc.rewrite(),
+                websocket: loc.websocket.unwrap_or(false),
+                grpc: loc.grpc.unwrap_or(false),
+                cache: loc.cache.as_ref(),
                 target: loc.target(),
             })
             .collect();
@@ -157,6 +256,11 @@ impl ServiceBlock {
             out.push(ResolvedLocation {
                 path: DEFAULT_LOCATION_PATH,
                 override_404: None,
+                headers: None,
+                rewrite: None,
+                websocket: false,
+                grpc: false,
+                cache: None,
                 target: Some(LocationTarget::Deployment(dep.clone())),
             });
         }
@@ -179,7 +283,7 @@ impl ServiceBlock {
 pub struct DeploymentBlock {
     /// Port that the container listens on. Required when a service location
     /// references this deployment.
-    #[serde(default)]
+    #[serde(default, deserialize_with = "number_or_string")]
     pub port: Option<u16>,
     /// Name of a `network` block whose network all instances join (optional).
     /// The referenced network must be defined in this file.
@@ -188,24 +292,65 @@ pub struct DeploymentBlock {
     /// Number of vCPUs per instance (1–32). Optional — defaults to
     /// [`super::defaults::DEFAULT_VCPU_COUNT`]. Parsed wide; `validate`
     /// enforces the range, so post-validation consumers may narrow.
-    #[serde(default)]
+    #[serde(default, deserialize_with = "number_or_string")]
     pub vcpus: Option<u64>,
     /// Guaranteed share of a physical core per vCPU. Optional — defaults to
     /// [`super::defaults::DEFAULT_VCPU_RATIO`]; `validate` restricts it to the
     /// scheduler's discrete tiers.
-    #[serde(default)]
+    #[serde(default, deserialize_with = "number_or_string")]
     pub vcpu_ratio: Option<f64>,
     /// Number of instances to run (0–10; 0 keeps the deployment defined but
     /// runs nothing). Optional — defaults to
     /// [`super::defaults::DEFAULT_REPLICAS`].
-    #[serde(default)]
+    #[serde(default, deserialize_with = "number_or_string")]
     pub replicas: Option<u64>,
     /// Memory per instance (128MB–32GB). A bare number is megabytes; a string
     /// takes an MB/M/GB/G suffix ("512MB", "2G"). Optional — defaults to
     /// [`super::defaults::DEFAULT_MEMORY_MB`].
     #[serde(default)]
     pub memory: Option<MemoryAttr>,
+    /// How long `up` waits for every replica to be running after rolling this
+    /// deployment out, e.g. "90s" or "5m". Optional — without it (or
+    /// `--timeout`) `up` doesn't wait at all.
+    #[serde(default)]
+    pub ready_timeout: Option<String>,
     pub container: ContainerBlock,
+    /// `init_container { … }` blocks, run to completion in order before
+    /// `container` starts, e.g. a schema migration.
+    #[serde(
+        default,
+        rename = "init_container",
+        deserialize_with = "repeated_block"
+    )]
+    pub init_containers: Vec<AuxContainerBlock>,
+    /// `sidecar { … }` blocks, run alongside `container`, e.g. a log shipper.
+    #[serde(default, rename = "sidecar", deserialize_with = "repeated_block")]
+    pub sidecars: Vec<AuxContainerBlock>,
+}
+
+/// A number attribute, also taken as a numeric string — what a `var.X`
+/// reference evaluates to, since variables are strings — so `replicas =
+/// var.replicas` works.
+fn number_or_string<'de, D, T>(de: D) -> std::result::Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de> + std::str::FromStr,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw<T> {
+        Number(T),
+        Text(String),
+    }
+    match Option::<Raw<T>>::deserialize(de)? {
+        None => Ok(None),
+        Some(Raw::Number(n)) => Ok(Some(n)),
+        Some(Raw::Text(s)) => s
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| serde::de::Error::custom(format!("expected a number, got {s:?}"))),
+    }
 }
 
 /// The `memory` attribute as written: HCL allows a bare number (megabytes) or
@@ -261,6 +406,18 @@ impl MemoryAttr {
     }
 }
 
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TlsBlock {
+    /// Lowest TLS version accepted, "1.2" or "1.3". Unset leaves the
+    /// platform default.
+    #[serde(default)]
+    pub min_version: Option<String>,
+    /// Send `Strict-Transport-Security` on HTTPS responses.
+    #[serde(default)]
+    pub hsts: Option<bool>,
+}
+
 #[derive(Debug, Deserialize, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct ContainerBlock {
@@ -271,6 +428,47 @@ pub struct ContainerBlock {
     pub env: Option<BTreeMap<String, String>>,
 }
 
+/// An `init_container` or `sidecar` block. It shares the main container's
+/// environment, so it takes no `env` of its own.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AuxContainerBlock {
+    pub image: String,
+    #[serde(default)]
+    pub args: Option<Vec<String>>,
+}
+
+/// A repeatable unlabeled block. `hcl-rs` hands over a lone block as a map
+/// and several as a sequence; either becomes a `Vec`, keeping the block's
+/// own errors (an unknown attribute, say) rather than an untagged-enum miss.
+fn repeated_block<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    use serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer};
+
+    struct Blocks<T>(std::marker::PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>> serde::de::Visitor<'de> for Blocks<T> {
+        type Value = Vec<T>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("one or more blocks")
+        }
+
+        fn visit_map<A: serde::de::MapAccess<'de>>(self, map: A) -> Result<Vec<T>, A::Error> {
+            T::deserialize(MapAccessDeserializer::new(map)).map(|block| vec![block])
+        }
+
+        fn visit_seq<A: serde::de::SeqAccess<'de>>(self, seq: A) -> Result<Vec<T>, A::Error> {
+            Vec::deserialize(SeqAccessDeserializer::new(seq))
+        }
+    }
+
+    deserializer.deserialize_any(Blocks(std::marker::PhantomData))
+}
+
 impl UpConfig {
     /// Parse with no interpolation variables, expecting none to be referenced.
     /// Test convenience over [`resolve`](Self::resolve).
@@ -296,6 +494,7 @@ impl UpConfig {
         vars: &BTreeMap<String, String>,
     ) -> Result<VarResolution> {
         let body = parse_body(path, source)?;
+        let (body, outputs) = Outputs::split(path, source, body, var_object(vars))?;
 
         let mut ctx = hcl::eval::Context::new();
         ctx.declare_var("var", var_object(vars));
@@ -338,9 +537,11 @@ impl UpConfig {
             return Ok(VarResolution::Missing(missing));
         }
 
-        let cfg: Self =
+        let mut cfg: Self =
             hcl::from_body(evaluated).map_err(|e| ConfigParseError::from_hcl(path, source, e))?;
         cfg.validate(path, source)?;
+        outputs.check(&cfg, path, source)?;
+        cfg.outputs = Box::new(outputs);
         Ok(VarResolution::Resolved(cfg))
     }
 
@@ -371,6 +572,19 @@ impl UpConfig {
         Err(ConfigParseError::validation(path, source, "missing field `project`", None).into())
     }
 
+    /// Labels of the top-level `kind` blocks, e.g. every `deployment "…"`,
+    /// read off the raw body like [`parse_project_at`](Self::parse_project_at)
+    /// so unset `${var.…}` references don't get in the way.
+    pub fn block_labels_at(path: &Path, source: &str, kind: &str) -> Result<Vec<String>> {
+        let body = parse_body(path, source)?;
+        Ok(body
+            .blocks()
+            .filter(|block| block.identifier() == kind)
+            .filter_map(|block| block.labels().first())
+            .map(|label| label.as_str().to_string())
+            .collect())
+    }
+
     /// Read just the `project` name from the config file at `path`.
     pub fn load_project(path: &Path) -> Result<String> {
         let source = std::fs::read_to_string(path)
@@ -378,6 +592,19 @@ impl UpConfig {
         Self::parse_project_at(path, &source)
     }
 
+    /// Every container image the deployments run, init containers and
+    /// sidecars included.
+    pub fn images(&self) -> Vec<&str> {
+        self.deployment
+            .values()
+            .flat_map(|d| {
+                std::iter::once(d.container.image.as_str())
+                    .chain(d.init_containers.iter().map(|c| c.image.as_str()))
+                    .chain(d.sidecars.iter().map(|c| c.image.as_str()))
+            })
+            .collect()
+    }
+
     /// Non-fatal warnings about a *valid* config that is probably not what the
     /// user meant. Printed by `up` before planning; never blocks an apply.
     pub fn lints(&self) -> Vec<String> {
@@ -428,6 +655,44 @@ impl UpConfig {
                 }
             }
         }
+        for (svc_name, svc) in &self.service {
+            for (username, password) in svc.basic_auth.iter().flatten() {
+                let reason = if username.is_empty() || username.contains(':') {
+                    Some(format!(
+                        "username {username:?} must be non-empty and must not contain \":\""
+                    ))
+                } else if password.is_empty() {
+                    Some(format!("password for {username:?} must not be empty"))
+                } else {
+                    None
+                };
+                if let Some(reason) = reason {
+                    return Err(err(
+                        format!("`basic_auth` in service \"{svc_name}\": {reason}"),
+                        Some(Locator::field("basic_auth")),
+                    ));
+                }
+            }
+            for ip in svc.allow_ips.iter().flatten() {
+                if ip.parse::<cidr::IpCidr>().is_err() {
+                    return Err(err(
+                        format!(
+                            "`allow_ips` in service \"{svc_name}\": {ip:?} is not an IP address \
+                             or CIDR block (e.g. \"203.0.113.0/24\" or \"2001:db8::/32\")"
+                        ),
+                        Some(Locator::substring(&format!("\"{ip}\""))),
+                    ));
+                }
+            }
+            if let Some(version) = svc.tls.as_ref().and_then(|t| t.min_version.as_deref())
+                && let Err(reason) = version.parse::<TlsVersion>()
+            {
+                return Err(err(
+                    format!("`tls.min_version` in service \"{svc_name}\": {reason}"),
+                    Some(Locator::field("min_version")),
+                ));
+            }
+        }
         for (svc_name, svc) in &self.service {
             // The shorthand `deployment` is desugared into this list (a "/"
             // catch-all appended last), so every check below sees the same
@@ -445,7 +710,7 @@ impl UpConfig {
                     return Err(err(
                         format!(
                             "location \"{path}\" in service \"{svc_name}\" must have exactly one \
-                             of `deployment`, `instance_group` or `url`"
+                             of `deployment`, `instance_group`, `url` or `redirect`"
                         ),
                         Some(Locator::substring(&format!("location \"{path}\""))),
                     ));
@@ -468,6 +733,113 @@ impl UpConfig {
                         Some(Locator::substring(&format!("\"{url}\""))),
                     ));
                 }
+                if let LocationTarget::Redirect(url) = target
+                    && let Some(reason) = invalid_url_target(url)
+                {
+                    return Err(err(
+                        format!(
+                            "`redirect` in location \"{path}\" of service \"{svc_name}\": {reason}"
+                        ),
+                        Some(Locator::substring(&format!("\"{url}\""))),
+                    ));
+                }
+                if let LocationTarget::Redirect(_) = target
+                    && loc.override_404.is_some()
+                {
+                    return Err(err(
+                        format!(
+                            "location \"{path}\" in service \"{svc_name}\" sets `override_404` on a \
+                             `redirect`, which never reaches an upstream that could 404"
+                        ),
+                        Some(Locator::substring("override_404")),
+                    ));
+                }
+                if let LocationTarget::Redirect(_) = target
+                    && (loc.websocket || loc.grpc)
+                {
+                    let field = if loc.websocket { "websocket" } else { "grpc" };
+                    return Err(err(
+                        format!(
+                            "location \"{path}\" in service \"{svc_name}\" sets `{field}` on a \
+                             `redirect`, which the edge answers itself without proxying anything"
+                        ),
+                        Some(Locator::substring(field)),
+                    ));
+                }
+                if let Some(cache) = loc.cache {
+                    if let LocationTarget::Redirect(_) = target {
+                        return Err(err(
+                            format!(
+                                "location \"{path}\" in service \"{svc_name}\" sets `cache` on a \
+                                 `redirect`, which the edge answers itself with nothing to cache"
+                            ),
+                            Some(Locator::substring("cache")),
+                        ));
+                    }
+                    let ttl = match parse_duration(&cache.ttl) {
+                        Ok(ttl) if ttl.as_secs() == 0 => Err("must be at least 1s".to_string()),
+                        other => other,
+                    };
+                    let stale = cache
+                        .stale_while_revalidate
+                        .as_deref()
+                        .map(parse_duration)
+                        .transpose();
+                    for (field, result) in [
+                        ("ttl", ttl.map(drop)),
+                        ("stale_while_revalidate", stale.map(drop)),
+                    ] {
+                        if let Err(reason) = result {
+                            return Err(err(
+                                format!(
+                                    "`cache.{field}` in location \"{path}\" of service \"{svc_name}\": {reason}"
+                                ),
+                                Some(Locator::field(field)),
+                            ));
+                        }
+                    }
+                }
+                if let Some(rewrite) = loc.rewrite {
+                    let explicit = &svc.locations[path];
+                    if explicit.rewrite.is_some() && explicit.strip_prefix.is_some() {
+                        return Err(err(
+                            format!(
+                                "location \"{path}\" in service \"{svc_name}\" sets both `rewrite` \
+                                 and `strip_prefix`; `strip_prefix = true` is shorthand for \
+                                 `rewrite = \"/\"`, so set only one"
+                            ),
+                            Some(Locator::substring(&format!("location \"{path}\""))),
+                        ));
+                    }
+                    if matches!(target, LocationTarget::Url(_) | LocationTarget::Redirect(_)) {
+                        return Err(err(
+                            format!(
+                                "location \"{path}\" in service \"{svc_name}\" rewrites the path of a \
+                                 `url` or `redirect` target; only `deployment` and `instance_group` \
+                                 targets can be rewritten (`url` targets already strip the prefix)"
+                            ),
+                            Some(Locator::substring(&format!("location \"{path}\""))),
+                        ));
+                    }
+                    if let Some(reason) = invalid_location_path(rewrite) {
+                        return Err(err(
+                            format!(
+                                "`rewrite` in location \"{path}\" of service \"{svc_name}\": {reason}"
+                            ),
+                            Some(Locator::substring(&format!("\"{rewrite}\""))),
+                        ));
+                    }
+                }
+                for (name, value) in loc.headers.into_iter().flatten() {
+                    if let Some(reason) = invalid_header(name, value) {
+                        return Err(err(
+                            format!(
+                                "`headers` in location \"{path}\" of service \"{svc_name}\": {reason}"
+                            ),
+                            Some(Locator::substring(name)),
+                        ));
+                    }
+                }
             }
             // The same path twice — including the shorthand "/" colliding with
             // an explicit one — can never both be reached.
@@ -609,9 +981,29 @@ impl UpConfig {
                     Ok(_) => {}
                 }
             }
+            if let Some(timeout) = &dep.ready_timeout
+                && let Err(reason) = parse_duration(timeout)
+            {
+                return Err(err(
+                    format!("`ready_timeout` in deployment \"{name}\": {reason}"),
+                    Some(Locator::substring("ready_timeout")),
+                ));
+            }
         }
         Ok(())
     }
+
+    /// Each deployment's `ready_timeout`. Only call after `validate`.
+    pub fn ready_timeouts(&self) -> BTreeMap<String, Duration> {
+        self.deployment
+            .iter()
+            .filter_map(|(name, dep)| {
+                let timeout = dep.ready_timeout.as_deref()?;
+                let timeout = parse_duration(timeout).expect("validated ready_timeout");
+                Some((name.clone(), timeout))
+            })
+            .collect()
+    }
 }
 
 /// Parse `source` to a structural body and run the variable-independent
@@ -668,7 +1060,7 @@ fn var_object(vars: &BTreeMap<String, String>) -> hcl::Value {
 /// a request path: leading `/`, no query/fragment, no whitespace, no empty
 /// segments. A trailing slash is allowed — `/api/` (subtree only) and `/api`
 /// (subtree plus the bare path) are distinct, intentional routes.
-fn invalid_location_path(path: &str) -> Option<String> {
+pub(crate) fn invalid_location_path(path: &str) -> Option<String> {
     if !path.starts_with('/') {
         return Some("path must start with \"/\"".into());
     }
@@ -714,7 +1106,7 @@ fn invalid_override_404(value: &str) -> Option<String> {
 /// `None`. The proxy resolves the target host from the URL's authority, so a
 /// relative value has nowhere to go. Parsed with the same `http` crate as the
 /// proxy.
-fn invalid_url_target(url: &str) -> Option<String> {
+pub(crate) fn invalid_url_target(url: &str) -> Option<String> {
     let parsed: http::Uri = match url.parse() {
         Ok(uri) => uri,
         Err(e) => return Some(format!("{url:?} is not a valid URL: {e}")),
@@ -732,6 +1124,34 @@ fn invalid_url_target(url: &str) -> Option<String> {
     }
 }
 
+/// Returns an error message if `name: value` is not a header the edge can set,
+/// else `None`. Checked with the same `http` crate as the proxy. Hop-by-hop
+/// and framing headers are the proxy's to manage, so they're refused outright.
+pub(crate) fn invalid_header(name: &str, value: &str) -> Option<String> {
+    let parsed = match name.parse::<http::HeaderName>() {
+        Ok(n) => n,
+        Err(_) => return Some(format!("{name:?} is not a valid header name")),
+    };
+    if matches!(
+        parsed.as_str(),
+        "connection"
+            | "content-length"
+            | "transfer-encoding"
+            | "keep-alive"
+            | "upgrade"
+            | "te"
+            | "trailer"
+    ) {
+        return Some(format!(
+            "{name:?} is managed by the proxy and cannot be overridden"
+        ));
+    }
+    match http::HeaderValue::from_str(value) {
+        Ok(_) => None,
+        Err(_) => Some(format!("value for {name:?} is not a valid header value")),
+    }
+}
+
 /// Returns an error message if `iprange` is not a valid IPv4 CIDR block, else
 /// `None`. Parses with the same `cidr` crate as the backend, so the CLI and
 /// server agree exactly on what's accepted — notably, host bits must be zero
@@ -758,13 +1178,13 @@ fn invalid_ipv4_cidr(iprange: &str) -> Option<String> {
 
 /// Per-instance resource bounds, mirroring the scheduler's limits so the CLI
 /// fails fast with a source span instead of waiting for an API 400.
-const MIN_MEMORY_MB: u64 = 128;
-const MAX_MEMORY_MB: u64 = 32 * 1024;
-const MIN_VCPUS: u64 = 1;
-const MAX_VCPUS: u64 = 32;
+pub(crate) const MIN_MEMORY_MB: u64 = 128;
+pub(crate) const MAX_MEMORY_MB: u64 = 32 * 1024;
+pub(crate) const MIN_VCPUS: u64 = 1;
+pub(crate) const MAX_VCPUS: u64 = 32;
 /// Discrete core-share tiers the scheduler supports. All powers of two, so
 /// exact f64 comparison is sound.
-const VCPU_RATIO_TIERS: [f64; 4] = [0.125, 0.25, 0.5, 1.0];
+pub(crate) const VCPU_RATIO_TIERS: [f64; 4] = [0.125, 0.25, 0.5, 1.0];
 /// 0 is allowed: the deployment stays defined but runs no instances.
 const MAX_REPLICAS: u64 = 10;
 
@@ -860,6 +1280,60 @@ service "${var.env}-web" {}
         assert!(UpConfig::resolve(Path::new("unisrv.hcl"), src, &BTreeMap::new()).is_err());
     }
 
+    #[test]
+    fn deployment_takes_init_and_sidecar_blocks() {
+        let cfg = UpConfig::parse(
+            r#"
+project = "demo"
+deployment "app" {
+  container {
+    image = "myapp:1"
+  }
+  init_container {
+    image = "myapp:1"
+    args  = ["migrate"]
+  }
+  sidecar {
+    image = "fluent/fluent-bit:2.2"
+  }
+  sidecar {
+    image = "otel/collector:0.98"
+  }
+}
+"#,
+        )
+        .unwrap();
+        let dep = &cfg.deployment["app"];
+        assert_eq!(
+            dep.init_containers,
+            vec![AuxContainerBlock {
+                image: "myapp:1".into(),
+                args: Some(vec!["migrate".into()]),
+            }]
+        );
+        let sidecars: Vec<&str> = dep.sidecars.iter().map(|s| s.image.as_str()).collect();
+        assert_eq!(sidecars, ["fluent/fluent-bit:2.2", "otel/collector:0.98"]);
+    }
+
+    #[test]
+    fn sidecar_block_rejects_unknown_attributes() {
+        let err = UpConfig::parse(
+            r#"
+project = "demo"
+deployment "app" {
+  container { image = "myapp:1" }
+  sidecar {
+    image = "fluent-bit:2"
+    env   = { A = "1" }
+  }
+}
+"#,
+        )
+        .unwrap_err();
+        let msg = format!("{err:#}");
+        assert!(msg.contains("unknown field `env`"), "{msg}");
+    }
+
     #[test]
     fn resolve_substitutes_provided_var_into_template() {
         let src = r#"
@@ -1015,6 +1489,33 @@ deployment "app" {
         assert_eq!(cfg.deployment["app"].container.image, "myapp:v1");
     }
 
+    #[test]
+    fn resolve_takes_numbers_from_vars() {
+        let src = r#"
+project = "demo"
+deployment "api" {
+  replicas = var.replicas
+  port     = "${var.port}"
+  container { image = "i" }
+}
+"#;
+        let cfg = match resolve_with(src, &[("replicas", "3"), ("port", "8080")]) {
+            VarResolution::Resolved(cfg) => cfg,
+            VarResolution::Missing(m) => panic!("unexpected missing vars: {m:?}"),
+        };
+        assert_eq!(cfg.deployment["api"].replicas, Some(3));
+        assert_eq!(cfg.deployment["api"].port, Some(8080));
+
+        let map = BTreeMap::from([("replicas".to_string(), "three".to_string())]);
+        let err = UpConfig::resolve(
+            Path::new("unisrv.hcl"),
+            src.replace("${var.port}", "80").as_str(),
+            &map,
+        )
+        .unwrap_err();
+        assert!(format!("{err:#}").contains("expected a number"), "{err:#}");
+    }
+
     #[test]
     fn resolve_validates_substituted_values() {
         // The interpolated host is only invalid *after* substitution, so this
@@ -1035,6 +1536,35 @@ service "web" {
         );
     }
 
+    #[test]
+    fn ready_timeout_is_a_duration() {
+        let src = |timeout: &str| {
+            format!(
+                r#"
+project = "demo"
+deployment "api" {{
+  ready_timeout = "{timeout}"
+  container {{
+    image = "api:1"
+  }}
+}}
+"#
+            )
+        };
+        let cfg = UpConfig::parse(&src("1m30s")).unwrap();
+        assert_eq!(
+            cfg.ready_timeouts(),
+            BTreeMap::from([("api".to_string(), Duration::from_secs(90))])
+        );
+
+        let err = UpConfig::parse(&src("90")).unwrap_err();
+        let msg = format!("{err:#}");
+        assert!(
+            msg.contains("`ready_timeout` in deployment \"api\""),
+            "{msg}"
+        );
+    }
+
     #[test]
     fn parse_project_reads_project_ignoring_var_references() {
         // `destroy` reads only the project name and supplies no vars, so a
@@ -1517,6 +2047,297 @@ service "web" {
         );
     }
 
+    #[test]
+    fn parses_location_headers_and_redirect() {
+        let src = r#"
+project = "demo"
+service "web" {
+  location "/old" { redirect = "https://example.com/new" }
+  location "/" {
+    instance_group = "g"
+    headers = {
+      "Strict-Transport-Security" = "max-age=63072000"
+    }
+  }
+}
+"#;
+        let cfg = UpConfig::parse(src).unwrap();
+        let resolved = cfg.service["web"].resolved_locations();
+        assert_eq!(
+            resolved[0].target,
+            Some(LocationTarget::Redirect("https://example.com/new".into()))
+        );
+        assert_eq!(
+            resolved[1].headers.unwrap()["Strict-Transport-Security"],
+            "max-age=63072000"
+        );
+    }
+
+    #[test]
+    fn parses_websocket_and_grpc_per_location() {
+        let src = r#"
+project = "demo"
+service "web" {
+  location "/ws" {
+    instance_group = "chat"
+    websocket      = true
+  }
+  location "/rpc" {
+    url  = "http://backend.internal:50051"
+    grpc = true
+  }
+  deployment = "site"
+}
+deployment "site" {
+  port = 80
+  container {
+    image = "nginx:1"
+  }
+}
+"#;
+        let cfg = UpConfig::parse(src).unwrap();
+        let resolved = cfg.service["web"].resolved_locations();
+        assert!(resolved[0].websocket && !resolved[0].grpc);
+        assert!(resolved[1].grpc && !resolved[1].websocket);
+        assert!(
+            !resolved[2].websocket && !resolved[2].grpc,
+            "the shorthand catch-all enables neither"
+        );
+    }
+
+    #[test]
+    fn parses_a_location_cache_policy() {
+        let src = r#"
+project = "demo"
+service "web" {
+  location "/static" {
+    instance_group = "web"
+    cache {
+      ttl                    = "1h"
+      stale_while_revalidate = "10m"
+    }
+  }
+}
+"#;
+        let cfg = UpConfig::parse(src).unwrap();
+        let resolved = cfg.service["web"].resolved_locations();
+        assert_eq!(
+            resolved[0].cache.unwrap().policy(),
+            CachePolicy {
+                ttl_secs: 3600,
+                stale_while_revalidate_secs: Some(600),
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_a_cache_on_a_redirect_or_without_a_ttl() {
+        for (location, needle) in [
+            (
+                r#"redirect = "https://example.com"
+    cache { ttl = "1h" }"#,
+                "nothing to cache",
+            ),
+            (
+                r#"instance_group = "web"
+    cache { ttl = "0s" }"#,
+                "`cache.ttl`",
+            ),
+            (
+                r#"instance_group = "web"
+    cache {
+      ttl                    = "1h"
+      stale_while_revalidate = "soon"
+    }"#,
+                "`cache.stale_while_revalidate`",
+            ),
+        ] {
+            let src = format!(
+                r#"
+project = "demo"
+service "web" {{
+  location "/static" {{
+    {location}
+  }}
+}}
+"#
+            );
+            let msg = format!("{:#}", UpConfig::parse(&src).unwrap_err());
+            assert!(msg.contains(needle), "{needle}: {msg}");
+        }
+    }
+
+    #[test]
+    fn rejects_protocol_options_on_a_redirect() {
+        let src = r#"
+project = "demo"
+service "web" {
+  location "/" {
+    redirect  = "https://example.com"
+    websocket = true
+  }
+}
+"#;
+        let msg = format!("{:#}", UpConfig::parse(src).unwrap_err());
+        assert!(
+            msg.contains("`websocket`") && msg.contains("redirect"),
+            "{msg}"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_and_proxy_managed_headers() {
+        for (name, needle) in [
+            ("Bad Header", "not a valid header name"),
+            ("Connection", "managed by the proxy"),
+        ] {
+            let src = format!(
+                r#"
+project = "demo"
+service "web" {{
+  location "/" {{
+    instance_group = "g"
+    headers = {{ "{name}" = "x" }}
+  }}
+}}
+"#
+            );
+            let err = UpConfig::parse(&src).unwrap_err();
+            let msg = format!("{err:#}");
+            assert!(msg.contains("headers"), "names the field: {msg}");
+            assert!(msg.contains(needle), "explains the problem: {msg}");
+        }
+    }
+
+    #[test]
+    fn rejects_relative_redirect_and_override_404_on_redirect() {
+        let src = r#"
+project = "demo"
+service "web" {
+  location "/" { redirect = "/elsewhere" }
+}
+"#;
+        let msg = format!("{:#}", UpConfig::parse(src).unwrap_err());
+        assert!(msg.contains("redirect"), "names the field: {msg}");
+
+        let src = r#"
+project = "demo"
+service "web" {
+  location "/" {
+    redirect     = "https://example.com"
+    override_404 = "/index.html"
+  }
+}
+"#;
+        let msg = format!("{:#}", UpConfig::parse(src).unwrap_err());
+        assert!(msg.contains("override_404"), "names the field: {msg}");
+    }
+
+    #[test]
+    fn strip_prefix_desugars_to_root_rewrite() {
+        let src = r#"
+project = "demo"
+service "web" {
+  location "/api/" {
+    instance_group = "api"
+    strip_prefix   = true
+  }
+  location "/" {
+    instance_group = "g"
+    rewrite        = "/app/"
+  }
+}
+"#;
+        let cfg = UpConfig::parse(src).unwrap();
+        let resolved = cfg.service["web"].resolved_locations();
+        assert_eq!(resolved[0].rewrite, Some("/"));
+        assert_eq!(resolved[1].rewrite, Some("/app/"));
+    }
+
+    #[test]
+    fn rejects_rewrite_misuse() {
+        let cases = [
+            (
+                r#"instance_group = "g"
+    rewrite = "/x"
+    strip_prefix = true"#,
+                "set only one",
+            ),
+            (
+                r#"url = "https://example.com"
+    strip_prefix = true"#,
+                "already strip the prefix",
+            ),
+            (
+                r#"instance_group = "g"
+    rewrite = "v1/""#,
+                "must start with",
+            ),
+        ];
+        for (body, needle) in cases {
+            let src = format!(
+                "project = \"demo\"\nservice \"web\" {{\n  location \"/api\" {{\n    {body}\n  }}\n}}\n"
+            );
+            let msg = format!("{:#}", UpConfig::parse(&src).unwrap_err());
+            assert!(msg.contains(needle), "expected {needle:?} in: {msg}");
+        }
+    }
+
+    #[test]
+    fn parses_basic_auth_and_allow_ips() {
+        let src = r#"
+project = "demo"
+service "web" {
+  basic_auth = { alice = "s3cret" }
+  allow_ips  = ["203.0.113.0/24", "2001:db8::1"]
+  location "/" { url = "https://example.com" }
+}
+"#;
+        let cfg = UpConfig::parse(src).unwrap();
+        let svc = &cfg.service["web"];
+        assert_eq!(svc.basic_auth.as_ref().unwrap()["alice"], "s3cret");
+        assert_eq!(svc.allow_ips.as_ref().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn parses_and_validates_tls_block() {
+        let src = r#"
+project = "demo"
+service "web" {
+  tls {
+    min_version = "1.3"
+    hsts        = true
+  }
+  location "/" { url = "https://example.com" }
+}
+"#;
+        let cfg = UpConfig::parse(src).unwrap();
+        let tls = cfg.service["web"].tls.as_ref().unwrap();
+        assert_eq!(tls.min_version.as_deref(), Some("1.3"));
+        assert_eq!(tls.hsts, Some(true));
+
+        let bad = src.replace("\"1.3\"", "\"1.1\"");
+        let msg = format!("{:#}", UpConfig::parse(&bad).unwrap_err());
+        assert!(msg.contains("unsupported TLS version \"1.1\""), "{msg}");
+    }
+
+    #[test]
+    fn rejects_invalid_access_rules() {
+        let cases = [
+            (r#"basic_auth = { "a:b" = "pw" }"#, "must not contain"),
+            (r#"basic_auth = { alice = "" }"#, "must not be empty"),
+            (r#"allow_ips = ["10.0.0.0/33"]"#, "not an IP address"),
+            (r#"allow_ips = ["office"]"#, "not an IP address"),
+        ];
+        for (body, needle) in cases {
+            let src = format!(
+                "project = \"demo\"\nservice \"web\" {{\n  {body}\n  location \"/\" {{ url = \"https://example.com\" }}\n}}\n"
+            );
+            let msg = format!("{:#}", UpConfig::parse(&src).unwrap_err());
+            assert!(msg.contains(needle), "expected {needle:?} in: {msg}");
+        }
+    }
+
     #[test]
     fn lints_instance_group_matching_a_deployment_name() {
         // `instance_group` routes to a raw group WITHOUT binding the
@@ -1713,7 +2534,7 @@ deployment "app" {
         let cfg = UpConfig::parse(src).unwrap();
         let dep = &cfg.deployment["app"];
         assert_eq!(
-            dep.container.args.as_ref().map(|v| v.as_slice()),
+            dep.container.args.as_deref(),
             Some([String::from("--config"), String::from("/etc/app.conf")].as_slice(),),
         );
         let env = dep.container.env.as_ref().unwrap();
@@ -10,6 +10,9 @@ pub const DEFAULT_VCPU_COUNT: u8 = 1;
 pub const DEFAULT_MEMORY_MB: u32 = 512;
 
 pub const DEFAULT_NETWORK_CIDR: &str = "10.0.0.0/16";
+/// Where `network new --auto-cidr` carves its /24s from, away from the
+/// 10.0.0.0/8 space corporate VPNs tend to claim.
+pub const DEFAULT_AUTO_CIDR_RANGE: &str = "172.16.0.0/12";
 
 pub const DEFAULT_TARGET_GROUP: &str = "default";
 pub const DEFAULT_LOCATION_PATH: &str = "/";
@@ -8,12 +8,14 @@
 use std::collections::BTreeMap;
 
 use unisrv_api::models::{
-    DeploymentConfiguration, HTTPLocation, HTTPLocationTarget, HTTPServiceConfig,
+    AuxContainer, BasicAuthCredential, DeploymentConfiguration, HTTPLocation, HTTPLocationTarget,
+    HTTPServiceConfig, TlsPolicy,
 };
 
 use crate::commands::host::normalize_host;
+use crate::commands::regions::default_region;
 
-use super::config::{LocationTarget, UpConfig};
+use super::config::{AuxContainerBlock, CacheBlock, LocationTarget, UpConfig};
 use super::defaults::*;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -94,14 +96,22 @@ impl DesiredState {
                             .expect("validation guarantees exactly one location target")
                         {
                             LocationTarget::Url(url) => HTTPLocationTarget::Url { url },
+                            LocationTarget::Redirect(url) => HTTPLocationTarget::Redirect { url },
                             LocationTarget::Deployment(group)
                             | LocationTarget::InstanceGroup(group) => {
-                                HTTPLocationTarget::Instance { group }
+                                HTTPLocationTarget::Instance {
+                                    group,
+                                    rewrite: loc.rewrite.map(str::to_string),
+                                }
                             }
                         };
                         HTTPLocation {
                             path: loc.path.to_string(),
                             override_404: loc.override_404.map(str::to_string),
+                            headers: loc.headers.cloned().unwrap_or_default(),
+                            websocket: loc.websocket,
+                            grpc: loc.grpc,
+                            cache: loc.cache.map(CacheBlock::policy),
                             target,
                         }
                     })
@@ -112,14 +122,35 @@ impl DesiredState {
                     locations.push(HTTPLocation {
                         path: DEFAULT_LOCATION_PATH.to_string(),
                         override_404: None,
+                        headers: BTreeMap::new(),
+                        websocket: false,
+                        grpc: false,
+                        cache: None,
                         target: HTTPLocationTarget::Instance {
                             group: DEFAULT_TARGET_GROUP.to_string(),
+                            rewrite: None,
                         },
                     });
                 }
                 let configuration = HTTPServiceConfig {
                     locations,
                     allow_http: block.allow_http.unwrap_or(DEFAULT_ALLOW_HTTP),
+                    basic_auth: block
+                        .basic_auth
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|(username, password)| BasicAuthCredential { username, password })
+                        .collect(),
+                    allow_ips: block.allow_ips.unwrap_or_default(),
+                    // `min_version` was checked during validation.
+                    tls: block
+                        .tls
+                        .map(|tls| TlsPolicy {
+                            min_version: tls.min_version.and_then(|v| v.parse().ok()),
+                            hsts: tls.hsts.unwrap_or(false),
+                        })
+                        .unwrap_or_default(),
+                    compression: block.compression,
                 };
                 let svc = DesiredService {
                     name: name.clone(),
@@ -131,7 +162,7 @@ impl DesiredState {
                         .iter()
                         .map(|h| normalize_host(h))
                         .collect(),
-                    region: DEFAULT_REGION.to_string(),
+                    region: default_region().to_string(),
                     configuration,
                 };
                 (name, svc)
@@ -144,7 +175,7 @@ impl DesiredState {
             .map(|(name, block)| {
                 let configuration = DeploymentConfiguration {
                     replicas: block.replicas.map(|r| r as u32).unwrap_or(DEFAULT_REPLICAS),
-                    region: DEFAULT_REGION.to_string(),
+                    region: default_region().to_string(),
                     container_image: block.container.image,
                     args: block.container.args,
                     env: block.container.env,
@@ -157,6 +188,8 @@ impl DesiredState {
                         })
                         .unwrap_or(DEFAULT_MEMORY_MB),
                     instance_port: block.port,
+                    init_containers: aux_containers(block.init_containers),
+                    sidecars: aux_containers(block.sidecars),
                 };
                 let service_binding = bindings.remove(&name);
                 let dep = DesiredDeployment {
@@ -192,9 +225,24 @@ impl DesiredState {
     }
 }
 
+/// `None` for no blocks, matching what the server reports for a deployment
+/// without any, so an untouched deployment doesn't diff.
+fn aux_containers(blocks: Vec<AuxContainerBlock>) -> Option<Vec<AuxContainer>> {
+    (!blocks.is_empty()).then(|| {
+        blocks
+            .into_iter()
+            .map(|b| AuxContainer {
+                image: b.image,
+                args: b.args,
+            })
+            .collect()
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use unisrv_api::models::CachePolicy;
 
     fn parse(src: &str) -> DesiredState {
         let cfg = UpConfig::parse(src).unwrap();
@@ -233,16 +281,39 @@ service "web" { hosts = ["web.example.com"] }
         assert_eq!(svc.name, "web");
         assert_eq!(svc.hosts, vec!["web.example.com".to_string()]);
         assert_eq!(svc.region, DEFAULT_REGION);
-        assert_eq!(svc.configuration.allow_http, false);
+        assert!(!svc.configuration.allow_http);
         assert_eq!(svc.configuration.locations.len(), 1);
         let loc = &svc.configuration.locations[0];
         assert_eq!(loc.path, "/");
         match &loc.target {
-            HTTPLocationTarget::Instance { group } => assert_eq!(group, DEFAULT_TARGET_GROUP),
+            HTTPLocationTarget::Instance { group, .. } => assert_eq!(group, DEFAULT_TARGET_GROUP),
             _ => panic!("unexpected target"),
         }
     }
 
+    #[test]
+    fn carries_a_location_cache_policy() {
+        let state = parse(
+            r#"
+project = "demo"
+service "web" {
+  location "/static" {
+    instance_group = "web"
+    cache { ttl = "1h" }
+  }
+}
+"#,
+        );
+        let loc = &state.services["web"].configuration.locations[0];
+        assert_eq!(
+            loc.cache,
+            Some(CachePolicy {
+                ttl_secs: 3600,
+                stale_while_revalidate_secs: None,
+            })
+        );
+    }
+
     #[test]
     fn fills_in_deployment_defaults() {
         let state = parse(
@@ -267,6 +338,8 @@ deployment "web" {
         assert_eq!(dep.configuration.memory_mb, DEFAULT_MEMORY_MB);
         assert_eq!(dep.configuration.instance_port, Some(8080));
         assert!(dep.configuration.args.is_none());
+        assert!(dep.configuration.init_containers.is_none());
+        assert!(dep.configuration.sidecars.is_none());
 
         let binding = dep.service_binding.as_ref().unwrap();
         assert_eq!(binding.service_name, "web");
@@ -296,7 +369,8 @@ deployment "api" {
         assert_eq!(
             locations[0].target,
             HTTPLocationTarget::Instance {
-                group: "api".into()
+                group: "api".into(),
+                rewrite: None,
             }
         );
 
@@ -334,7 +408,8 @@ deployment "api" {
         assert_eq!(
             locations[1].target,
             HTTPLocationTarget::Instance {
-                group: "frontend".into()
+                group: "frontend".into(),
+                rewrite: None,
             }
         );
 
@@ -370,7 +445,8 @@ service "web" {
         assert_eq!(
             locations[1].target,
             HTTPLocationTarget::Instance {
-                group: "canary".into()
+                group: "canary".into(),
+                rewrite: None,
             }
         );
         assert!(state.deployments.is_empty());
@@ -7,7 +7,7 @@
 use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::Write;
 
-use unisrv_api::models::DeploymentConfiguration;
+use unisrv_api::models::{AuxContainer, DeploymentConfiguration};
 
 pub fn render_config_diff(
     out: &mut String,
@@ -24,6 +24,8 @@ pub fn render_config_diff(
         vcpu_count: c_vcpu_count,
         memory_mb: c_memory_mb,
         instance_port: c_instance_port,
+        init_containers: c_init_containers,
+        sidecars: c_sidecars,
     } = current;
     let DeploymentConfiguration {
         replicas: d_replicas,
@@ -35,6 +37,8 @@ pub fn render_config_diff(
         vcpu_count: d_vcpu_count,
         memory_mb: d_memory_mb,
         instance_port: d_instance_port,
+        init_containers: d_init_containers,
+        sidecars: d_sidecars,
     } = desired;
 
     if c_container_image != d_container_image {
@@ -68,6 +72,22 @@ pub fn render_config_diff(
     if c_env != d_env {
         render_env_diff(out, c_env.as_ref(), d_env.as_ref());
     }
+    if c_init_containers != d_init_containers {
+        let _ = writeln!(
+            out,
+            "      init:     {} -> {}",
+            containers_display(c_init_containers.as_deref()),
+            containers_display(d_init_containers.as_deref()),
+        );
+    }
+    if c_sidecars != d_sidecars {
+        let _ = writeln!(
+            out,
+            "      sidecars: {} -> {}",
+            containers_display(c_sidecars.as_deref()),
+            containers_display(d_sidecars.as_deref()),
+        );
+    }
     if (c_vcpu_count, c_vcpu_ratio, c_memory_mb) != (d_vcpu_count, d_vcpu_ratio, d_memory_mb) {
         let _ = writeln!(
             out,
@@ -101,6 +121,22 @@ fn args_display(v: Option<&[String]>) -> String {
     }
 }
 
+/// Init or sidecar containers on one line: `myapp:1 ["migrate"], otel:1`.
+/// Shared with the create rendering.
+pub fn containers_display(v: Option<&[AuxContainer]>) -> String {
+    match v {
+        Some(containers) if !containers.is_empty() => containers
+            .iter()
+            .map(|c| match &c.args {
+                Some(args) => format!("{} {args:?}", c.image),
+                None => c.image.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(", "),
+        _ => "<none>".into(),
+    }
+}
+
 fn render_env_diff(
     out: &mut String,
     current: Option<&BTreeMap<String, String>>,
@@ -140,6 +176,8 @@ mod tests {
             vcpu_count: 1,
             memory_mb: 256,
             instance_port: Some(80),
+            init_containers: None,
+            sidecars: None,
         }
     }
 
@@ -186,6 +224,23 @@ mod tests {
         assert!(out.contains("--debug"), "got: {out}");
     }
 
+    #[test]
+    fn renders_sidecar_change() {
+        let mut out = String::new();
+        let c = base();
+        let mut d = base();
+        d.sidecars = Some(vec![AuxContainer {
+            image: "fluent-bit:2".into(),
+            args: Some(vec!["-q".into()]),
+        }]);
+        render_config_diff(&mut out, &c, &d);
+        assert!(
+            out.contains(r#"sidecars: <none> -> fluent-bit:2 ["-q"]"#),
+            "got: {out}"
+        );
+        assert!(!out.contains("init:"), "got: {out}");
+    }
+
     #[test]
     fn renders_env_diff_with_add_remove_modify() {
         let mut out = String::new();
@@ -13,11 +13,16 @@
 
 use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::Write;
+use std::time::Duration;
 
-use unisrv_api::models::{HTTPLocation, HTTPLocationTarget, HTTPServiceConfig};
+use unisrv_api::models::{
+    BasicAuthCredential, CachePolicy, HTTPLocation, HTTPLocationTarget, HTTPServiceConfig,
+    TlsVersion,
+};
 
 use crate::commands::up::desired::DesiredService;
 use crate::commands::up::plan::{CurrentService, RecreateReason};
+use crate::commands::up::ready::format_duration;
 
 /// Returns one `RecreateReason::ImmutableField` per immutable field that
 /// differs between desired and current. Mutability is hard-coded here per
@@ -78,20 +83,111 @@ pub fn render_config_diff(
     let HTTPServiceConfig {
         locations: c_locations,
         allow_http: c_allow_http,
+        basic_auth: c_basic_auth,
+        allow_ips: c_allow_ips,
+        tls: c_tls,
+        compression: c_compression,
     } = current;
     let HTTPServiceConfig {
         locations: d_locations,
         allow_http: d_allow_http,
+        basic_auth: d_basic_auth,
+        allow_ips: d_allow_ips,
+        tls: d_tls,
+        compression: d_compression,
     } = desired;
 
     if c_allow_http != d_allow_http {
         let _ = writeln!(out, "      allow_http: {c_allow_http} -> {d_allow_http}");
     }
+    if c_allow_ips != d_allow_ips {
+        let list = |ips: &[String]| {
+            if ips.is_empty() {
+                "<any>".to_string()
+            } else {
+                ips.join(", ")
+            }
+        };
+        let _ = writeln!(
+            out,
+            "      allow_ips: {} -> {}",
+            list(c_allow_ips),
+            list(d_allow_ips)
+        );
+    }
+    if c_tls.min_version != d_tls.min_version {
+        let _ = writeln!(
+            out,
+            "      tls.min_version: {} -> {}",
+            min_version_label(c_tls.min_version),
+            min_version_label(d_tls.min_version)
+        );
+    }
+    if c_tls.hsts != d_tls.hsts {
+        let _ = writeln!(out, "      tls.hsts: {} -> {}", c_tls.hsts, d_tls.hsts);
+    }
+    if c_compression != d_compression {
+        let _ = writeln!(
+            out,
+            "      compression: {} -> {}",
+            compression_label(*c_compression),
+            compression_label(*d_compression)
+        );
+    }
+    if c_basic_auth != d_basic_auth {
+        render_basic_auth_diff(out, c_basic_auth, d_basic_auth);
+    }
     if c_locations != d_locations {
         render_locations_diff(out, c_locations, d_locations);
     }
 }
 
+/// `1.3`, or `<default>` when the platform decides.
+pub fn min_version_label(version: Option<TlsVersion>) -> String {
+    version.map_or_else(|| "<default>".to_string(), |v| v.to_string())
+}
+
+/// `on`, `off`, or `<default>` when the platform decides.
+pub fn compression_label(compression: Option<bool>) -> &'static str {
+    match compression {
+        Some(true) => "on",
+        Some(false) => "off",
+        None => "<default>",
+    }
+}
+
+/// Per-user `+`/`-`/`~` lines. Passwords are never printed — a changed one
+/// shows only that it changed, since plan output lands in terminals and CI logs.
+fn render_basic_auth_diff(
+    out: &mut String,
+    current: &[BasicAuthCredential],
+    desired: &[BasicAuthCredential],
+) {
+    let by_user = |creds: &[BasicAuthCredential]| -> BTreeMap<String, String> {
+        creds
+            .iter()
+            .map(|c| (c.username.clone(), c.password.clone()))
+            .collect()
+    };
+    let (c, d) = (by_user(current), by_user(desired));
+    let _ = writeln!(out, "      basic_auth:");
+    let users: BTreeSet<&String> = c.keys().chain(d.keys()).collect();
+    for user in users {
+        match (c.get(user), d.get(user)) {
+            (None, Some(_)) => {
+                let _ = writeln!(out, "        + {user}");
+            }
+            (Some(_), None) => {
+                let _ = writeln!(out, "        - {user}");
+            }
+            (Some(cp), Some(dp)) if cp != dp => {
+                let _ = writeln!(out, "        ~ {user} (password changed)");
+            }
+            _ => {}
+        }
+    }
+}
+
 fn render_locations_diff(out: &mut String, current: &[HTTPLocation], desired: &[HTTPLocation]) {
     let c_by_path: BTreeMap<&str, &HTTPLocation> =
         current.iter().map(|l| (l.path.as_str(), l)).collect();
@@ -138,11 +234,19 @@ fn render_location_diff(
     let HTTPLocation {
         path: c_path,
         override_404: c_override_404,
+        headers: c_headers,
+        websocket: c_websocket,
+        grpc: c_grpc,
+        cache: c_cache,
         target: c_target,
     } = current;
     let HTTPLocation {
         path: d_path,
         override_404: d_override_404,
+        headers: d_headers,
+        websocket: d_websocket,
+        grpc: d_grpc,
+        cache: d_cache,
         target: d_target,
     } = desired;
 
@@ -154,6 +258,19 @@ fn render_location_diff(
         let ds = d_override_404.as_deref().unwrap_or("<unset>");
         let _ = writeln!(out, "{indent}override_404: {cs} -> {ds}");
     }
+    if c_headers != d_headers {
+        render_headers_diff(out, indent, c_headers, d_headers);
+    }
+    if c_websocket != d_websocket {
+        let _ = writeln!(out, "{indent}websocket: {c_websocket} -> {d_websocket}");
+    }
+    if c_grpc != d_grpc {
+        let _ = writeln!(out, "{indent}grpc: {c_grpc} -> {d_grpc}");
+    }
+    if c_cache != d_cache {
+        let (c, d) = (cache_label(c_cache.as_ref()), cache_label(d_cache.as_ref()));
+        let _ = writeln!(out, "{indent}cache: {c} -> {d}");
+    }
     if c_target != d_target {
         render_target_diff(out, indent, c_target, d_target);
     }
@@ -165,20 +282,64 @@ fn render_target_diff(
     current: &HTTPLocationTarget,
     desired: &HTTPLocationTarget,
 ) {
-    // Pair-destructuring forces exhaustive coverage of every variant cross.
-    // Adding a new `HTTPLocationTarget` variant breaks the build here.
-    match (current, desired) {
-        (HTTPLocationTarget::Instance { group: c }, HTTPLocationTarget::Instance { group: d }) => {
-            let _ = writeln!(out, "{indent}target: instance({c}) -> instance({d})");
-        }
-        (HTTPLocationTarget::Url { url: c }, HTTPLocationTarget::Url { url: d }) => {
-            let _ = writeln!(out, "{indent}target: url({c}) -> url({d})");
-        }
-        (HTTPLocationTarget::Instance { group: c }, HTTPLocationTarget::Url { url: d }) => {
-            let _ = writeln!(out, "{indent}target: instance({c}) -> url({d})");
-        }
-        (HTTPLocationTarget::Url { url: c }, HTTPLocationTarget::Instance { group: d }) => {
-            let _ = writeln!(out, "{indent}target: url({c}) -> instance({d})");
+    let (c, d) = (target_label(current), target_label(desired));
+    let _ = writeln!(out, "{indent}target: {c} -> {d}");
+}
+
+/// One-line rendering of a target, e.g. `instance(web)`. The exhaustive match
+/// means adding an `HTTPLocationTarget` variant breaks the build here.
+pub(crate) fn target_label(target: &HTTPLocationTarget) -> String {
+    match target {
+        HTTPLocationTarget::Instance {
+            group,
+            rewrite: None,
+        } => format!("instance({group})"),
+        HTTPLocationTarget::Instance {
+            group,
+            rewrite: Some(rewrite),
+        } => format!("instance({group}, rewrite {rewrite})"),
+        HTTPLocationTarget::Url { url } => format!("url({url})"),
+        HTTPLocationTarget::Redirect { url } => format!("redirect({url})"),
+    }
+}
+
+/// A location's cache policy, e.g. `1h, stale 10m`, or `off`.
+pub(crate) fn cache_label(cache: Option<&CachePolicy>) -> String {
+    let Some(cache) = cache else {
+        return "off".to_string();
+    };
+    let ttl = format_duration(Duration::from_secs(cache.ttl_secs));
+    match cache.stale_while_revalidate_secs {
+        Some(stale) => format!(
+            "{ttl}, stale {}",
+            format_duration(Duration::from_secs(stale))
+        ),
+        None => ttl,
+    }
+}
+
+/// Per-header `+`/`-`/`~` lines. Header names are compared as written; the
+/// edge treats them case-insensitively, but the stored config keeps spelling.
+fn render_headers_diff(
+    out: &mut String,
+    indent: &str,
+    current: &BTreeMap<String, String>,
+    desired: &BTreeMap<String, String>,
+) {
+    let _ = writeln!(out, "{indent}headers:");
+    let names: BTreeSet<&String> = current.keys().chain(desired.keys()).collect();
+    for name in names {
+        match (current.get(name), desired.get(name)) {
+            (None, Some(d)) => {
+                let _ = writeln!(out, "{indent}  + {name}: {d}");
+            }
+            (Some(c), None) => {
+                let _ = writeln!(out, "{indent}  - {name}: {c}");
+            }
+            (Some(c), Some(d)) if c != d => {
+                let _ = writeln!(out, "{indent}  ~ {name}: {c} -> {d}");
+            }
+            _ => {}
         }
     }
 }
@@ -187,19 +348,28 @@ fn render_location_full(out: &mut String, indent: &str, loc: &HTTPLocation) {
     let HTTPLocation {
         path: _,
         override_404,
+        headers,
+        websocket,
+        grpc,
+        cache,
         target,
     } = loc;
     if let Some(v) = override_404 {
         let _ = writeln!(out, "{indent}override_404: {v}");
     }
-    match target {
-        HTTPLocationTarget::Instance { group } => {
-            let _ = writeln!(out, "{indent}target: instance({group})");
-        }
-        HTTPLocationTarget::Url { url } => {
-            let _ = writeln!(out, "{indent}target: url({url})");
-        }
+    for (name, value) in headers {
+        let _ = writeln!(out, "{indent}header: {name}: {value}");
+    }
+    if *websocket {
+        let _ = writeln!(out, "{indent}websocket: true");
+    }
+    if *grpc {
+        let _ = writeln!(out, "{indent}grpc: true");
+    }
+    if cache.is_some() {
+        let _ = writeln!(out, "{indent}cache: {}", cache_label(cache.as_ref()));
     }
+    let _ = writeln!(out, "{indent}target: {}", target_label(target));
 }
 
 #[cfg(test)]
@@ -211,6 +381,10 @@ mod tests {
         HTTPServiceConfig {
             allow_http,
             locations,
+            basic_auth: vec![],
+            allow_ips: vec![],
+            tls: Default::default(),
+            compression: None,
         }
     }
 
@@ -218,6 +392,10 @@ mod tests {
         HTTPLocation {
             path: path.into(),
             override_404: None,
+            headers: BTreeMap::new(),
+            websocket: false,
+            grpc: false,
+            cache: None,
             target,
         }
     }
@@ -225,6 +403,7 @@ mod tests {
     fn instance(group: &str) -> HTTPLocationTarget {
         HTTPLocationTarget::Instance {
             group: group.into(),
+            rewrite: None,
         }
     }
 
@@ -276,6 +455,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn renders_a_cache_policy_the_manifest_would_remove() {
+        let mut out = String::new();
+        let mut cached = loc("/static", instance("default"));
+        cached.cache = Some(CachePolicy {
+            ttl_secs: 3600,
+            stale_while_revalidate_secs: Some(600),
+        });
+        let c = cfg(false, vec![cached]);
+        let d = cfg(false, vec![loc("/static", instance("default"))]);
+        render_config_diff(&mut out, &c, &d);
+        assert!(out.contains("cache: 1h, stale 10m -> off"), "got: {out}");
+    }
+
     #[test]
     fn renders_modified_location_override_404() {
         let mut out = String::new();
@@ -292,6 +485,127 @@ mod tests {
         );
     }
 
+    #[test]
+    fn renders_protocol_option_changes() {
+        let mut out = String::new();
+        let a = loc("/", instance("default"));
+        let mut b = a.clone();
+        b.websocket = true;
+        render_config_diff(&mut out, &cfg(false, vec![a]), &cfg(false, vec![b]));
+        assert!(out.contains("websocket: false -> true"), "got: {out}");
+        assert!(!out.contains("grpc"), "unchanged options stay quiet: {out}");
+    }
+
+    #[test]
+    fn renders_header_changes_and_redirect_target() {
+        let mut out = String::new();
+        let mut a = loc("/", instance("default"));
+        let mut b = loc(
+            "/",
+            HTTPLocationTarget::Redirect {
+                url: "https://example.com".into(),
+            },
+        );
+        a.headers.insert("X-Old".into(), "1".into());
+        a.headers
+            .insert("Access-Control-Allow-Origin".into(), "*".into());
+        b.headers
+            .insert("Access-Control-Allow-Origin".into(), "https://a.com".into());
+        b.headers.insert(
+            "Strict-Transport-Security".into(),
+            "max-age=63072000".into(),
+        );
+        render_config_diff(&mut out, &cfg(false, vec![a]), &cfg(false, vec![b]));
+        assert!(
+            out.contains("~ Access-Control-Allow-Origin: * -> https://a.com"),
+            "got: {out}"
+        );
+        assert!(
+            out.contains("+ Strict-Transport-Security: max-age=63072000"),
+            "got: {out}"
+        );
+        assert!(out.contains("- X-Old: 1"), "got: {out}");
+        assert!(
+            out.contains("target: instance(default) -> redirect(https://example.com)"),
+            "got: {out}"
+        );
+    }
+
+    #[test]
+    fn renders_rewrite_change_on_instance_target() {
+        let mut out = String::new();
+        let c = cfg(false, vec![loc("/api", instance("api"))]);
+        let d = cfg(
+            false,
+            vec![loc(
+                "/api",
+                HTTPLocationTarget::Instance {
+                    group: "api".into(),
+                    rewrite: Some("/".into()),
+                },
+            )],
+        );
+        render_config_diff(&mut out, &c, &d);
+        assert!(
+            out.contains("target: instance(api) -> instance(api, rewrite /)"),
+            "got: {out}"
+        );
+    }
+
+    #[test]
+    fn renders_tls_changes() {
+        let c = cfg(false, vec![]);
+        let mut d = cfg(false, vec![]);
+        d.tls.min_version = Some(TlsVersion::Tls13);
+        d.tls.hsts = true;
+
+        let mut out = String::new();
+        render_config_diff(&mut out, &c, &d);
+
+        assert!(
+            out.contains("tls.min_version: <default> -> 1.3"),
+            "got: {out}"
+        );
+        assert!(out.contains("tls.hsts: false -> true"), "got: {out}");
+    }
+
+    #[test]
+    fn renders_compression_changes() {
+        let mut c = cfg(false, vec![]);
+        c.compression = Some(false);
+        let d = cfg(false, vec![]);
+
+        let mut out = String::new();
+        render_config_diff(&mut out, &c, &d);
+
+        assert!(out.contains("compression: off -> <default>"), "got: {out}");
+    }
+
+    #[test]
+    fn renders_access_changes_without_leaking_passwords() {
+        let cred = |u: &str, p: &str| BasicAuthCredential {
+            username: u.into(),
+            password: p.into(),
+        };
+        let mut c = cfg(false, vec![]);
+        c.basic_auth = vec![cred("alice", "old-secret"), cred("bob", "b")];
+        let mut d = cfg(false, vec![]);
+        d.basic_auth = vec![cred("alice", "new-secret"), cred("carol", "c")];
+        d.allow_ips = vec!["203.0.113.0/24".into()];
+
+        let mut out = String::new();
+        render_config_diff(&mut out, &c, &d);
+
+        assert!(
+            out.contains("allow_ips: <any> -> 203.0.113.0/24"),
+            "got: {out}"
+        );
+        assert!(out.contains("~ alice (password changed)"), "got: {out}");
+        assert!(out.contains("- bob"), "got: {out}");
+        assert!(out.contains("+ carol"), "got: {out}");
+        assert!(!out.contains("secret"), "must not print passwords: {out}");
+    }
+
     #[test]
     fn no_output_when_unchanged() {
         let mut out = String::new();
@@ -21,7 +21,7 @@ use super::plan::EnvAction;
 use crate::progress::{Icon, Progress};
 
 /// Abstraction over user prompting for env metadata. Production uses a
-/// dialoguer-backed impl; tests inject scripted answers.
+/// terminal-backed impl; tests inject scripted answers.
 pub trait Prompter {
     /// Prompt for a string with an optional default. Empty answer should yield default.
     fn prompt_string(&self, prompt: &str, default: Option<&str>) -> Result<String>;
@@ -2,30 +2,66 @@
 //!
 //! Custom hosts come straight from each `ServiceDetailResponse.custom_hosts`
 //! (the authoritative per-service set). `region` is not exposed by the backend
-//! yet, so it's treated as the configured default — switch to the response
-//! value when the backend exposes it.
+//! yet, so it's treated as the configured default ([`default_region`]) —
+//! switch to the response value when the backend exposes it.
+//!
+//! The three listings go out together, then every service and deployment
+//! detail GET in one bounded fan-out — nothing here depends on an earlier
+//! response until the results are stitched together.
 
 use anyhow::{Context, Result};
 use std::collections::BTreeMap;
 use unisrv_api::ApiClient;
-use unisrv_api::models::HTTPServiceConfig;
+use unisrv_api::models::{HTTPServiceConfig, ManagedKind};
 use uuid::Uuid;
 
-use super::defaults::DEFAULT_REGION;
+use crate::commands::regions::default_region;
+
 use super::plan::{
     CurrentDeployment, CurrentNetwork, CurrentNetworkBinding, CurrentService,
-    CurrentServiceBinding, CurrentState,
+    CurrentServiceBinding, CurrentState, Listed,
 };
+use crate::commands::concurrent::{authenticate, fetch_each};
 
 pub async fn fetch_current_state(client: &dyn ApiClient, env_id: Uuid) -> Result<CurrentState> {
-    let networks_list = client
-        .list_networks(env_id, false)
-        .await
-        .context("failed to list networks")?;
+    authenticate(client).await?;
+    let (networks_list, services_list, deployments_list) = tokio::try_join!(
+        async {
+            client
+                .list_networks(env_id, false)
+                .await
+                .context("failed to list networks")
+        },
+        async {
+            client
+                .list_services(env_id)
+                .await
+                .context("failed to list services")
+        },
+        async {
+            client
+                .list_deployments(env_id)
+                .await
+                .context("failed to list deployments")
+        },
+    )?;
+    let (service_details, deployment_details) = tokio::try_join!(
+        fetch_each(&services_list.services, |entry| client
+            .get_service(env_id, entry.id)),
+        fetch_each(&deployments_list.deployments, |entry| client
+            .get_deployment(env_id, entry.id)),
+    )?;
 
+    let mut listed = Vec::new();
     let mut networks_by_id: BTreeMap<Uuid, CurrentNetwork> = BTreeMap::new();
     let mut networks: BTreeMap<String, CurrentNetwork> = BTreeMap::new();
     for entry in networks_list.networks {
+        listed.push(Listed {
+            kind: ManagedKind::Network,
+            id: entry.id,
+            name: entry.name.clone(),
+            managed_by: entry.managed_by,
+        });
         let net = CurrentNetwork {
             id: entry.id,
             name: entry.name.clone(),
@@ -35,30 +71,40 @@ pub async fn fetch_current_state(client: &dyn ApiClient, env_id: Uuid) -> Result
         networks.insert(entry.name, net);
     }
 
-    let services_list = client.list_services(env_id).await?;
-
     let mut services_by_id: BTreeMap<Uuid, CurrentService> = BTreeMap::new();
     let mut services: BTreeMap<String, CurrentService> = BTreeMap::new();
-    for entry in services_list.services {
-        let detail = client.get_service(env_id, entry.id).await?;
+    for (entry, detail) in services_list.services.into_iter().zip(service_details) {
+        listed.push(Listed {
+            kind: ManagedKind::Service,
+            id: entry.id,
+            name: entry.name.clone(),
+            managed_by: entry.managed_by.clone(),
+        });
         let configuration: HTTPServiceConfig = serde_json::from_value(detail.configuration.clone())
             .with_context(|| format!("failed to parse configuration for service {}", entry.name))?;
         let svc = CurrentService {
             id: entry.id,
             name: detail.name.clone(),
             hosts: detail.custom_hosts.clone(),
-            region: DEFAULT_REGION.to_string(),
+            region: default_region().to_string(),
             configuration,
         };
         services_by_id.insert(entry.id, svc.clone());
         services.insert(detail.name, svc);
     }
 
-    let deployments_list = client.list_deployments(env_id).await?;
-
     let mut deployments: BTreeMap<String, CurrentDeployment> = BTreeMap::new();
-    for entry in deployments_list.deployments {
-        let detail = client.get_deployment(env_id, entry.id).await?;
+    for (entry, detail) in deployments_list
+        .deployments
+        .into_iter()
+        .zip(deployment_details)
+    {
+        listed.push(Listed {
+            kind: ManagedKind::Deployment,
+            id: entry.id,
+            name: entry.name.clone(),
+            managed_by: entry.managed_by,
+        });
         let service_binding = match (detail.service_id, detail.service_target_group.as_ref()) {
             (Some(sid), Some(tg)) => services_by_id.get(&sid).map(|svc| CurrentServiceBinding {
                 service_id: sid,
@@ -91,6 +137,8 @@ pub async fn fetch_current_state(client: &dyn ApiClient, env_id: Uuid) -> Result
         services,
         deployments,
         networks,
+        listed,
+        replacing: Default::default(),
     })
 }
 
@@ -120,8 +168,8 @@ mod tests {
                 "allow_http": false,
             }),
             environment_id: env_id,
-            created_at: NaiveDateTime::default(),
-            updated_at: NaiveDateTime::default(),
+            created_at: Default::default(),
+            updated_at: Default::default(),
             providers: vec![],
             targets: vec![],
             statistics: None,
@@ -139,6 +187,8 @@ mod tests {
             vcpu_count: 1,
             memory_mb: 256,
             instance_port: Some(80),
+            init_containers: None,
+            sidecars: None,
         }
     }
 
@@ -195,6 +245,7 @@ mod tests {
                     name: "web".into(),
                     base_host: "web-env.unisrv.dev".into(),
                     custom_hosts: vec!["shop.acme.com".into()],
+                    managed_by: None,
                 }],
             }))
             .push_get_service(Ok(detail))
@@ -205,10 +256,10 @@ mod tests {
         let svc = &state.services["web"];
         assert_eq!(svc.hosts, vec!["shop.acme.com".to_string()]);
         assert_eq!(svc.region, "dev");
-        assert_eq!(svc.configuration.allow_http, false);
+        assert!(!svc.configuration.allow_http);
         assert_eq!(svc.configuration.locations.len(), 1);
         match &svc.configuration.locations[0].target {
-            HTTPLocationTarget::Instance { group } => assert_eq!(group, "default"),
+            HTTPLocationTarget::Instance { group, .. } => assert_eq!(group, "default"),
             _ => panic!("unexpected"),
         }
     }
@@ -226,6 +277,7 @@ mod tests {
                     name: "web".into(),
                     base_host: "web-env.unisrv.dev".into(),
                     custom_hosts: vec![],
+                    managed_by: None,
                 }],
             }))
             .push_get_service(Ok(service_detail(svc_id, env, "web")))
@@ -237,6 +289,7 @@ mod tests {
                     replicas: 1,
                     container_image: "nginx:1".into(),
                     created_at: NaiveDateTime::default(),
+                    managed_by: None,
                 }],
             }))
             .push_get_deployment(Ok(deployment_detail(
@@ -267,6 +320,7 @@ mod tests {
                     name: "internal".into(),
                     ipv4_cidr: "10.0.0.0/16".into(),
                     instance_count: None,
+                    managed_by: None,
                 }],
             }))
             .with_list_deployments(Ok(DeploymentListResponse {
@@ -277,6 +331,7 @@ mod tests {
                     replicas: 1,
                     container_image: "i:1".into(),
                     created_at: NaiveDateTime::default(),
+                    managed_by: None,
                 }],
             }))
             .push_get_deployment(Ok(detail));
@@ -292,15 +347,61 @@ mod tests {
         assert_eq!(binding.network_name, "internal");
     }
 
+    #[tokio::test]
+    async fn pairs_each_listing_entry_with_its_detail() {
+        // Details are fetched concurrently; each must still land on the entry
+        // it was requested for.
+        let env = Uuid::new_v4();
+        let ids = [Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+        let names = ["api", "web", "admin"];
+        let mut client = MockApiClient::logged_in()
+            .with_list_networks(Ok(NetworkListResponse { networks: vec![] }))
+            .with_list_services(Ok(ServiceListResponse {
+                services: ids
+                    .iter()
+                    .zip(names)
+                    .map(|(&id, name)| ServiceListItem {
+                        id,
+                        name: name.into(),
+                        base_host: format!("{name}-env.unisrv.dev"),
+                        custom_hosts: vec![],
+                        managed_by: None,
+                    })
+                    .collect(),
+            }))
+            .with_list_deployments(Ok(DeploymentListResponse {
+                deployments: vec![],
+            }));
+        for (&id, name) in ids.iter().zip(names) {
+            client = client.push_get_service(Ok(service_detail(id, env, name)));
+        }
+
+        let state = fetch_current_state(&client, env).await.unwrap();
+
+        for (&id, name) in ids.iter().zip(names) {
+            assert_eq!(state.services[name].id, id);
+        }
+        let calls = client.calls.lock().unwrap();
+        assert_eq!(calls.access_token_calls, 1, "token resolved once up front");
+        assert_eq!(
+            calls.get_service_calls,
+            ids.iter().map(|&id| (env, id)).collect::<Vec<_>>()
+        );
+    }
+
     #[tokio::test]
     async fn list_networks_failure_is_contextualized() {
-        // The networks listing is the first call of every fetch; a bare API
-        // error with no framing would be the whole story the user sees.
+        // The listings run concurrently; a bare API error with no framing
+        // wouldn't say which of them failed.
         let env = Uuid::new_v4();
-        let client =
-            MockApiClient::logged_in().with_list_networks(Err(unisrv_api::ApiError::Server {
+        let client = MockApiClient::logged_in()
+            .with_list_networks(Err(unisrv_api::ApiError::Server {
                 status: 500,
                 reason: "boom".into(),
+            }))
+            .with_list_services(Ok(ServiceListResponse { services: vec![] }))
+            .with_list_deployments(Ok(DeploymentListResponse {
+                deployments: vec![],
             }));
         let err = fetch_current_state(&client, env).await.unwrap_err();
         let msg = format!("{err:#}");
@@ -330,6 +431,7 @@ mod tests {
                     replicas: 1,
                     container_image: "i:1".into(),
                     created_at: NaiveDateTime::default(),
+                    managed_by: None,
                 }],
             }))
             .push_get_deployment(Ok(detail));
@@ -353,10 +455,46 @@ mod tests {
                     replicas: 1,
                     container_image: "w:1".into(),
                     created_at: NaiveDateTime::default(),
+                    managed_by: None,
                 }],
             }))
             .push_get_deployment(Ok(deployment_detail(dep_id, "worker", None, None)));
         let state = fetch_current_state(&client, env).await.unwrap();
         assert!(state.deployments["worker"].service_binding.is_none());
     }
+
+    #[tokio::test]
+    async fn server_error_over_http_names_the_failing_listing() {
+        use unisrv_api::test_support::server::{MockServer, Reply};
+        use unisrv_api::{AuthSession, HttpApiClient};
+
+        let env = Uuid::new_v4();
+        let server = MockServer::start().await;
+        server
+            .on(
+                "GET",
+                &format!("/environment/{env}/networks"),
+                Reply::json(200, &NetworkListResponse { networks: vec![] }),
+            )
+            .on(
+                "GET",
+                &format!("/environment/{env}/services"),
+                Reply::json(200, &ServiceListResponse { services: vec![] }),
+            )
+            .on(
+                "GET",
+                &format!("/environment/{env}/deployments"),
+                Reply::json(502, &json!({ "reason": "upstream unavailable" })),
+            );
+        let session = AuthSession::test_session("t", chrono::Duration::hours(1));
+        let client = HttpApiClient::with_session(server.url(), Some(session));
+
+        let err = fetch_current_state(&client, env).await.unwrap_err();
+
+        let msg = format!("{err:#}");
+        assert!(
+            msg.contains("failed to list deployments") && msg.contains("upstream unavailable"),
+            "{msg}"
+        );
+    }
 }
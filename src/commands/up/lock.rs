@@ -0,0 +1,297 @@
+//! `unisrv.lock`: the digest every image tag in `unisrv.hcl` resolved to, so
+//! later runs deploy exactly those images even after a tag moves.
+//!
+//! `up` and `plan` resolve each tag the lockfile doesn't have yet, record it,
+//! and deploy `image:tag@digest` in its place. A tag that's already locked
+//! keeps its digest until `--update-images` resolves everything afresh.
+//! Images given by digest are pinned already and left alone; tags that are
+//! no longer in the config are dropped from the lockfile.
+//!
+//! Commit the lockfile next to `unisrv.hcl`, as with any other lockfile.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use unisrv_api::ApiClient;
+use unisrv_api::distribution::ManifestSource;
+
+use super::config::UpConfig;
+use crate::commands::registry::{self, tags::Repository};
+use crate::progress::{Icon, Progress};
+
+/// The lockfile's name, next to `unisrv.hcl`.
+pub const LOCK_FILE: &str = "unisrv.lock";
+
+/// The only lockfile format so far.
+const VERSION: u32 = 1;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Lockfile {
+    pub version: u32,
+    /// Image reference as written (after variables) → its digest.
+    pub images: BTreeMap<String, String>,
+}
+
+impl Default for Lockfile {
+    fn default() -> Self {
+        Lockfile {
+            version: VERSION,
+            images: BTreeMap::new(),
+        }
+    }
+}
+
+impl Lockfile {
+    /// `unisrv.lock` in the current directory, where `unisrv.hcl` is.
+    pub fn path() -> Result<PathBuf> {
+        let cwd = std::env::current_dir().context("failed to determine the current directory")?;
+        Ok(cwd.join(LOCK_FILE))
+    }
+
+    /// The lockfile at `path`; an empty one if there's none yet.
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = match std::fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+        };
+        let lock: Self = serde_json::from_str(&json)
+            .with_context(|| format!("invalid lockfile {}", path.display()))?;
+        if lock.version != VERSION {
+            bail!(
+                "{} is lockfile version {}; this unisrv reads version {VERSION}",
+                path.display(),
+                lock.version
+            );
+        }
+        Ok(lock)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json + "\n")
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+}
+
+/// Looks up what an image tag points at now. A trait so tests don't need a
+/// registry.
+#[async_trait]
+pub trait DigestResolver: Send + Sync {
+    async fn digest(&self, image: &str) -> Result<String>;
+}
+
+/// Resolves digests from each image's registry, with the same credentials as
+/// `registry tags`.
+pub struct Registries<'a> {
+    pub client: &'a dyn ApiClient,
+}
+
+#[async_trait]
+impl DigestResolver for Registries<'_> {
+    async fn digest(&self, image: &str) -> Result<String> {
+        // A digest reference resolves to itself, if the registry has it.
+        let (repo, reference) = Repository::parse_manifest_reference(image)?;
+        let source =
+            registry::distribution_client(self.client, &repo.host, &repo.name, false).await?;
+        Ok(source.digest(&reference).await?)
+    }
+}
+
+/// What [`pin_images`] did.
+#[derive(Debug, Default, PartialEq)]
+pub struct Pinned {
+    /// Tags resolved this run, rather than taken from the lockfile.
+    pub resolved: Vec<String>,
+    /// Tags that couldn't be resolved, with why. They keep the digest they
+    /// had locked, if any, and otherwise deploy unpinned.
+    pub unresolved: Vec<(String, String)>,
+}
+
+/// Swap every tagged image in `config` for `image@digest`, taking digests
+/// from `lock` unless `update` and resolving (and recording) the rest.
+pub async fn pin_images(
+    config: &mut UpConfig,
+    lock: &mut Lockfile,
+    resolver: &dyn DigestResolver,
+    update: bool,
+) -> Pinned {
+    let mut images: Vec<&mut String> = Vec::new();
+    for deployment in config.deployment.values_mut() {
+        images.push(&mut deployment.container.image);
+        for aux in deployment
+            .init_containers
+            .iter_mut()
+            .chain(deployment.sidecars.iter_mut())
+        {
+            images.push(&mut aux.image);
+        }
+    }
+    images.retain(|image| !image.contains('@'));
+
+    let mut pinned = Pinned::default();
+    let mut locked: BTreeMap<String, String> = BTreeMap::new();
+    for image in images {
+        let previous = lock.images.get(image.as_str());
+        let digest = match (locked.get(image.as_str()), previous) {
+            (Some(digest), _) => Some(digest.clone()),
+            (None, Some(digest)) if !update => Some(digest.clone()),
+            (None, previous) => match resolver.digest(image).await {
+                Ok(digest) => {
+                    pinned.resolved.push(image.clone());
+                    Some(digest)
+                }
+                Err(err) => {
+                    pinned.unresolved.push((image.clone(), format!("{err:#}")));
+                    previous.cloned()
+                }
+            },
+        };
+        if let Some(digest) = digest {
+            locked.insert(image.clone(), digest.clone());
+            image.push('@');
+            image.push_str(&digest);
+        }
+    }
+    lock.images = locked;
+    pinned
+}
+
+/// Pin `config`'s images through `unisrv.lock`, updating the lockfile when
+/// anything was resolved or dropped. Tags that can't be resolved are warned
+/// about, not fatal: they deploy as written.
+pub async fn lock_images(
+    client: &dyn ApiClient,
+    config: &mut UpConfig,
+    update: bool,
+    progress: &dyn Progress,
+) -> Result<()> {
+    let path = Lockfile::path()?;
+    let mut lock = Lockfile::load(&path)?;
+    let before = lock.images.clone();
+
+    let step = progress.step(Icon::Lookup, "Resolving image digests");
+    let pinned = pin_images(config, &mut lock, &Registries { client }, update).await;
+    step.clear();
+
+    for (image, reason) in &pinned.unresolved {
+        eprintln!(
+            "  {} couldn't resolve {image}'s digest: {reason}",
+            console::style("!").yellow()
+        );
+    }
+    if lock.images != before {
+        lock.save(&path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Answers `sha256:<n>` for the nth tag it's asked about.
+    #[derive(Default)]
+    struct Counter(Mutex<Vec<String>>);
+
+    #[async_trait]
+    impl DigestResolver for Counter {
+        async fn digest(&self, image: &str) -> Result<String> {
+            if image.starts_with("private/") {
+                bail!("unauthorized");
+            }
+            let mut asked = self.0.lock().unwrap();
+            asked.push(image.to_string());
+            Ok(format!("sha256:{}", asked.len()))
+        }
+    }
+
+    fn declared() -> UpConfig {
+        UpConfig::parse(
+            r#"
+project = "demo"
+deployment "api" {
+  container { image = "api:1" }
+  sidecar { image = "fluent-bit:2" }
+}
+deployment "worker" {
+  container { image = "api:1" }
+  init_container { image = "migrate@sha256:fixed" }
+}
+deployment "secret" {
+  container { image = "private/app:1" }
+}
+"#,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn resolves_new_tags_once_and_records_them() {
+        let mut config = declared();
+        let mut lock = Lockfile::default();
+        lock.images.insert("gone:1".into(), "sha256:old".into());
+        let resolver = Counter::default();
+
+        let pinned = pin_images(&mut config, &mut lock, &resolver, false).await;
+
+        assert_eq!(pinned.resolved, ["api:1", "fluent-bit:2"]);
+        assert_eq!(pinned.unresolved[0].0, "private/app:1");
+        assert_eq!(
+            config.images(),
+            [
+                "api:1@sha256:1",
+                "fluent-bit:2@sha256:2",
+                "private/app:1",
+                "api:1@sha256:1",
+                "migrate@sha256:fixed"
+            ]
+        );
+        assert_eq!(
+            lock.images,
+            BTreeMap::from([
+                ("api:1".to_string(), "sha256:1".to_string()),
+                ("fluent-bit:2".to_string(), "sha256:2".to_string()),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn keeps_locked_digests_unless_updating() {
+        let mut lock = Lockfile::default();
+        lock.images.insert("api:1".into(), "sha256:locked".into());
+        lock.images
+            .insert("fluent-bit:2".into(), "sha256:locked".into());
+
+        let mut config = declared();
+        let pinned = pin_images(&mut config, &mut lock, &Counter::default(), false).await;
+        assert!(pinned.resolved.is_empty());
+        assert_eq!(config.images()[0], "api:1@sha256:locked");
+
+        let mut config = declared();
+        let pinned = pin_images(&mut config, &mut lock, &Counter::default(), true).await;
+        assert_eq!(pinned.resolved, ["api:1", "fluent-bit:2"]);
+        assert_eq!(lock.images["api:1"], "sha256:1");
+    }
+
+    #[test]
+    fn a_missing_lockfile_is_empty_and_saves_round_trip() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join(LOCK_FILE);
+        assert_eq!(Lockfile::load(&path).unwrap(), Lockfile::default());
+
+        let mut lock = Lockfile::default();
+        lock.images.insert("api:1".into(), "sha256:abc".into());
+        lock.save(&path).unwrap();
+        assert_eq!(Lockfile::load(&path).unwrap(), lock);
+
+        std::fs::write(&path, r#"{"version": 2, "images": {}}"#).unwrap();
+        let err = Lockfile::load(&path).unwrap_err();
+        assert!(err.to_string().contains("version 2"), "{err:#}");
+    }
+}
@@ -1,15 +1,22 @@
 pub mod apply;
+pub mod collisions;
 pub mod config;
 pub mod defaults;
 pub mod desired;
 pub mod diff;
 pub mod env_resolve;
 pub mod fetch;
+pub mod lock;
+pub mod outputs;
 pub mod parse_error;
 pub mod plan;
 pub mod preflight;
+pub mod preview;
+pub mod ready;
 pub mod render;
+pub mod rollback;
 pub mod run;
+pub mod targeting;
 pub mod vars;
 
 pub use run::run;
@@ -0,0 +1,485 @@
+//! `output "NAME" { value = … }` blocks: values worked out from what `up`
+//! just applied — a service's URL, a deployment's addresses, a connection
+//! string built from them — printed after the apply and kept in local state
+//! for `unisrv output` to hand to scripts later.
+//!
+//! A `value` may refer to:
+//!
+//! * `service.NAME.url`, `.host`, `.hosts` and `.id`
+//! * `deployment.NAME.ips` (each running instance's private address),
+//!   `.instances` and `.id`
+//! * `network.NAME.iprange` and `.id`
+//! * `environment.name` and `environment.id`
+//! * `var.NAME`, as anywhere else in the file
+//!
+//! None of that exists until the apply has run, so output blocks are taken
+//! out of the body before the rest is evaluated. [`Outputs::check`] evaluates
+//! them against placeholders for every declared resource, so a typo fails
+//! before anything changes rather than after.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{Context as _, Result, anyhow, bail};
+use hcl::eval::{Context, Evaluate};
+use indexmap::IndexMap;
+use unisrv_api::ApiClient;
+use uuid::Uuid;
+
+use super::config::UpConfig;
+use super::parse_error::{ConfigParseError, Locator};
+use crate::commands::concurrent::fetch_each;
+use crate::commands::instance::list::is_active;
+use crate::config_locate::{CONFIG_FILE, find_config};
+use crate::state::StateDir;
+
+/// The file's `output` blocks, unevaluated, in declaration order.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Outputs {
+    values: IndexMap<String, hcl::Expression>,
+    /// The `var` object the rest of the file was evaluated with.
+    vars: hcl::Value,
+}
+
+impl Outputs {
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Take the `output` blocks out of `body`, leaving the rest to be
+    /// evaluated and deserialized as usual.
+    pub(super) fn split(
+        path: &Path,
+        source: &str,
+        body: hcl::Body,
+        vars: hcl::Value,
+    ) -> Result<(hcl::Body, Outputs), ConfigParseError> {
+        let mut rest = Vec::new();
+        let mut values = IndexMap::new();
+        for structure in body.into_inner() {
+            let hcl::Structure::Block(block) = structure else {
+                rest.push(structure);
+                continue;
+            };
+            if block.identifier() != "output" {
+                rest.push(hcl::Structure::Block(block));
+                continue;
+            }
+            let [label] = block.labels() else {
+                return Err(ConfigParseError::validation(
+                    path,
+                    source,
+                    "an `output` block takes exactly one label, its name",
+                    Some(Locator::substring("output")),
+                ));
+            };
+            let name = label.as_str().to_string();
+            let needle = format!("output \"{name}\"");
+            let mut value = None;
+            for inner in block.body().iter() {
+                match inner {
+                    hcl::Structure::Attribute(attr) if attr.key() == "value" => {
+                        value = Some(attr.expr().clone());
+                    }
+                    _ => {
+                        return Err(ConfigParseError::validation(
+                            path,
+                            source,
+                            format!("`output \"{name}\"` takes only a `value`"),
+                            Some(Locator::substring(&needle)),
+                        ));
+                    }
+                }
+            }
+            let Some(value) = value else {
+                return Err(ConfigParseError::validation(
+                    path,
+                    source,
+                    format!("`output \"{name}\"` is missing its `value`"),
+                    Some(Locator::substring(&needle)),
+                ));
+            };
+            values.insert(name, value);
+        }
+        Ok((hcl::Body(rest), Outputs { values, vars }))
+    }
+
+    /// Evaluate every output against placeholders for the resources `cfg`
+    /// declares, so references to anything else are caught up front.
+    pub(super) fn check(
+        &self,
+        cfg: &UpConfig,
+        path: &Path,
+        source: &str,
+    ) -> Result<(), ConfigParseError> {
+        let placeholders = Live::placeholders(cfg);
+        for (name, result) in self.resolve(&placeholders) {
+            if let Err(reason) = result {
+                let needle = format!("output \"{name}\"");
+                return Err(ConfigParseError::validation(
+                    path,
+                    source,
+                    format!("`output \"{name}\"`: {reason}"),
+                    Some(Locator::substring(&needle)),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Each output's value against `live`, or why it couldn't be worked out.
+    pub fn resolve(&self, live: &Live) -> Vec<(String, Result<serde_json::Value, String>)> {
+        let mut ctx = Context::new();
+        ctx.declare_var("var", self.vars.clone());
+        ctx.declare_var("environment", live.environment.clone());
+        ctx.declare_var("service", hcl::Value::Object(live.services.clone()));
+        ctx.declare_var("deployment", hcl::Value::Object(live.deployments.clone()));
+        ctx.declare_var("network", hcl::Value::Object(live.networks.clone()));
+
+        self.values
+            .iter()
+            .map(|(name, expr)| {
+                let value = expr
+                    .evaluate(&ctx)
+                    .map_err(|e| e.to_string())
+                    .and_then(|v| serde_json::to_value(v).map_err(|e| e.to_string()));
+                (name.clone(), value)
+            })
+            .collect()
+    }
+}
+
+/// What outputs can refer to, as HCL objects keyed by resource name.
+#[derive(Debug, Default)]
+pub struct Live {
+    environment: hcl::Value,
+    services: hcl::Map<String, hcl::Value>,
+    deployments: hcl::Map<String, hcl::Value>,
+    networks: hcl::Map<String, hcl::Value>,
+}
+
+impl Live {
+    /// Read back environment `env_id` (named `env_name`) after an apply.
+    pub async fn fetch(client: &dyn ApiClient, env_id: Uuid, env_name: &str) -> Result<Live> {
+        let (services, deployments, networks, instances) = tokio::try_join!(
+            client.list_services(env_id),
+            client.list_deployments(env_id),
+            client.list_networks(env_id, false),
+            client.list_instances(env_id),
+        )?;
+        let deployed: Vec<_> = instances
+            .instances
+            .into_iter()
+            .filter(|i| i.deployment.is_some() && is_active(&i.state.0))
+            .collect();
+        let (service_details, instance_details) = tokio::try_join!(
+            fetch_each(&services.services, |s| client.get_service(env_id, s.id)),
+            fetch_each(&deployed, |i| client
+                .get_instance(env_id, i.id, false, false)),
+        )?;
+
+        let mut live = Live {
+            environment: object([("id", env_id.to_string().into()), ("name", env_name.into())]),
+            ..Live::default()
+        };
+        for service in service_details {
+            let value = service_value(
+                service.id.to_string(),
+                &service.base_host,
+                &service.custom_hosts,
+            );
+            live.services.insert(service.name, value);
+        }
+        for deployment in deployments.deployments {
+            let (ids, ips): (Vec<_>, Vec<_>) = instance_details
+                .iter()
+                .filter(|i| i.deployment.as_ref().is_some_and(|d| d.id == deployment.id))
+                .map(|i| (i.id.to_string(), i.network_ip.clone()))
+                .unzip();
+            live.deployments.insert(
+                deployment.name,
+                deployment_value(
+                    deployment.id.to_string(),
+                    ids,
+                    ips.into_iter().flatten().collect(),
+                ),
+            );
+        }
+        for network in networks.networks {
+            live.networks.insert(
+                network.name,
+                object([
+                    ("id", network.id.to_string().into()),
+                    ("iprange", network.ipv4_cidr.into()),
+                ]),
+            );
+        }
+        Ok(live)
+    }
+
+    /// Stand-ins for everything `cfg` declares, shaped like [`Live::fetch`]'s.
+    fn placeholders(cfg: &UpConfig) -> Live {
+        const PENDING: &str = "(known after apply)";
+        let mut live = Live {
+            environment: object([("id", PENDING.into()), ("name", PENDING.into())]),
+            ..Live::default()
+        };
+        for name in cfg.service.keys() {
+            live.services.insert(
+                name.clone(),
+                service_value(PENDING.into(), PENDING, &[PENDING.into()]),
+            );
+        }
+        for name in cfg.deployment.keys() {
+            live.deployments.insert(
+                name.clone(),
+                deployment_value(PENDING.into(), vec![PENDING.into()], vec![PENDING.into()]),
+            );
+        }
+        for name in cfg.network.keys() {
+            live.networks.insert(
+                name.clone(),
+                object([("id", PENDING.into()), ("iprange", PENDING.into())]),
+            );
+        }
+        live
+    }
+}
+
+fn service_value(id: String, base_host: &str, custom_hosts: &[String]) -> hcl::Value {
+    let hosts: Vec<hcl::Value> = std::iter::once(base_host)
+        .chain(custom_hosts.iter().map(String::as_str))
+        .map(hcl::Value::from)
+        .collect();
+    object([
+        ("id", id.into()),
+        ("url", format!("https://{base_host}").into()),
+        ("host", base_host.into()),
+        ("hosts", hcl::Value::Array(hosts)),
+    ])
+}
+
+fn deployment_value(id: String, instances: Vec<String>, ips: Vec<String>) -> hcl::Value {
+    object([
+        ("id", id.into()),
+        ("instances", instances.into()),
+        ("ips", ips.into()),
+    ])
+}
+
+fn object<const N: usize>(fields: [(&str, hcl::Value); N]) -> hcl::Value {
+    hcl::Value::Object(
+        fields
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect(),
+    )
+}
+
+/// An output as printed: strings bare, anything else as JSON.
+pub fn display_value(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// The `Outputs:` section printed after an apply.
+pub fn render(values: &BTreeMap<String, serde_json::Value>) -> String {
+    use std::fmt::Write;
+    let mut out = String::from("\n  Outputs:\n");
+    let width = values.keys().map(String::len).max().unwrap_or(0);
+    for (name, value) in values {
+        let _ = writeln!(out, "    {name:width$} = {}", display_value(value));
+    }
+    out
+}
+
+/// `unisrv output [NAME]`: the outputs saved by the last `up` of this
+/// directory's project.
+pub fn show(name: Option<&str>, env: Option<&str>, json: bool) -> Result<()> {
+    let cwd = std::env::current_dir().context("failed to determine the current directory")?;
+    let manifest = find_config(&cwd, CONFIG_FILE)
+        .ok_or_else(|| anyhow!("no {CONFIG_FILE} found in the current directory"))?;
+    let project = UpConfig::load_project(&manifest.path)?;
+    let state = StateDir::locate().ok_or_else(|| anyhow!("no local state directory"))?;
+    print!("{}", saved(&state, &project, env, name, json)?);
+    Ok(())
+}
+
+/// What [`show`] prints. Without `env`, the project's only environment with
+/// saved outputs is used.
+fn saved(
+    state: &StateDir,
+    project: &str,
+    env: Option<&str>,
+    name: Option<&str>,
+    json: bool,
+) -> Result<String> {
+    let env = match env {
+        Some(env) => env.to_string(),
+        None => {
+            let mut envs = state.output_environments(project);
+            match envs.len() {
+                0 => bail!("no outputs saved for project \"{project}\"; run `unisrv up` first"),
+                1 => envs.remove(0),
+                _ => bail!(
+                    "outputs are saved for several environments of \"{project}\" ({}); pick one with --env",
+                    envs.join(", ")
+                ),
+            }
+        }
+    };
+    let stored = state
+        .outputs(project, &env)
+        .ok_or_else(|| anyhow!("no outputs saved for {project}/{env}; run `unisrv up` first"))?;
+
+    let Some(name) = name else {
+        if json {
+            return Ok(format!(
+                "{}\n",
+                serde_json::to_string_pretty(&stored.values)?
+            ));
+        }
+        return Ok(stored
+            .values
+            .iter()
+            .map(|(name, value)| format!("{name} = {}\n", display_value(value)))
+            .collect());
+    };
+    let Some(value) = stored.values.get(name) else {
+        let defined: Vec<_> = stored.values.keys().map(String::as_str).collect();
+        bail!(
+            "no output \"{name}\" for {project}/{env} (defined: {})",
+            if defined.is_empty() {
+                "none".to_string()
+            } else {
+                defined.join(", ")
+            }
+        );
+    };
+    Ok(if json {
+        format!("{value}\n")
+    } else {
+        format!("{}\n", display_value(value))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::up::config::VarResolution;
+    use serde_json::json;
+
+    const SOURCE: &str = r#"
+project = "shop"
+
+network "backend" {}
+
+service "web" {
+  deployment = "api"
+}
+
+deployment "api" {
+  port    = 8080
+  network = "backend"
+  container {
+    image = "nginx"
+  }
+}
+
+output "url" {
+  value = service.web.url
+}
+
+output "db" {
+  value = "postgres://${deployment.api.ips[0]}:5432/${var.db}"
+}
+"#;
+
+    fn resolve(source: &str) -> Result<UpConfig> {
+        let vars = BTreeMap::from([("db".to_string(), "shop".to_string())]);
+        match UpConfig::resolve(Path::new("unisrv.hcl"), source, &vars)? {
+            VarResolution::Resolved(cfg) => Ok(cfg),
+            VarResolution::Missing(missing) => anyhow::bail!("missing {missing:?}"),
+        }
+    }
+
+    #[test]
+    fn outputs_resolve_against_what_was_applied() {
+        let cfg = resolve(SOURCE).unwrap();
+        let mut live = Live::default();
+        live.services.insert(
+            "web".into(),
+            service_value("s1".into(), "web-ab12.unisrv.dev", &[]),
+        );
+        live.deployments.insert(
+            "api".into(),
+            deployment_value("d1".into(), vec!["i1".into()], vec!["10.0.0.7".into()]),
+        );
+
+        let values: Vec<_> = cfg
+            .outputs
+            .resolve(&live)
+            .into_iter()
+            .map(|(name, value)| (name, value.unwrap()))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                ("url".to_string(), json!("https://web-ab12.unisrv.dev")),
+                ("db".to_string(), json!("postgres://10.0.0.7:5432/shop")),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_reference_to_an_undeclared_resource_fails_up_front() {
+        let source = SOURCE.replace("service.web.url", "service.www.url");
+        let err = resolve(&source).unwrap_err();
+        assert!(err.to_string().contains("output \"url\""), "{err}");
+
+        let source = SOURCE.replace("value = service.web.url", "valeu = service.web.url");
+        let err = resolve(&source).unwrap_err();
+        assert!(err.to_string().contains("takes only a `value`"), "{err}");
+    }
+
+    #[test]
+    fn saved_outputs_are_read_back_by_name() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = StateDir::new(tmp.path().to_path_buf());
+        let values = BTreeMap::from([
+            ("port".to_string(), json!(8080)),
+            ("url".to_string(), json!("https://web-ab12.unisrv.dev")),
+        ]);
+        state.save_outputs("shop", "prod", values).unwrap();
+
+        let url = saved(&state, "shop", None, Some("url"), false).unwrap();
+        assert_eq!(url, "https://web-ab12.unisrv.dev\n");
+        let url = saved(&state, "shop", None, Some("url"), true).unwrap();
+        assert_eq!(url, "\"https://web-ab12.unisrv.dev\"\n");
+        let all = saved(&state, "shop", Some("prod"), None, false).unwrap();
+        assert_eq!(all, "port = 8080\nurl = https://web-ab12.unisrv.dev\n");
+
+        let err = saved(&state, "shop", None, Some("db"), false).unwrap_err();
+        assert!(err.to_string().contains("defined: port, url"), "{err}");
+
+        state
+            .save_outputs("shop", "staging", BTreeMap::new())
+            .unwrap();
+        let err = saved(&state, "shop", None, Some("url"), false).unwrap_err();
+        assert!(err.to_string().contains("prod, staging"), "{err}");
+    }
+
+    #[test]
+    fn renders_strings_bare_and_the_rest_as_json() {
+        let values = BTreeMap::from([
+            ("ips".to_string(), json!(["10.0.0.7", "10.0.0.8"])),
+            ("url".to_string(), json!("https://web-ab12.unisrv.dev")),
+        ]);
+        assert_eq!(
+            render(&values),
+            "\n  Outputs:\n    ips = [\"10.0.0.7\",\"10.0.0.8\"]\n    url = https://web-ab12.unisrv.dev\n"
+        );
+    }
+}
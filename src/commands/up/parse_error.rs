@@ -10,11 +10,14 @@ use std::path::{Path, PathBuf};
 
 use console::Style;
 
+use crate::commands::ui::stderr_colors_enabled;
+
 #[derive(Debug)]
 pub struct ConfigParseError {
     path: PathBuf,
     source: String,
-    kind: ParseErrorKind,
+    /// Boxed to keep `Result<_, ConfigParseError>` small on the happy path.
+    kind: Box<ParseErrorKind>,
 }
 
 #[derive(Debug)]
@@ -95,11 +98,11 @@ impl ConfigParseError {
             return Self {
                 path: path.to_path_buf(),
                 source: source.to_string(),
-                kind: ParseErrorKind::Syntax {
+                kind: Box::new(ParseErrorKind::Syntax {
                     message,
                     line: loc.line(),
                     column: loc.column(),
-                },
+                }),
             };
         }
 
@@ -113,11 +116,11 @@ impl ConfigParseError {
         Self {
             path: path.to_path_buf(),
             source: source.to_string(),
-            kind: ParseErrorKind::Located {
+            kind: Box::new(ParseErrorKind::Located {
                 message,
                 location,
                 notes,
-            },
+            }),
         }
     }
 
@@ -131,11 +134,11 @@ impl ConfigParseError {
         Self {
             path: path.to_path_buf(),
             source: source.to_string(),
-            kind: ParseErrorKind::Located {
+            kind: Box::new(ParseErrorKind::Located {
                 message: message.into(),
                 location,
                 notes: Vec::new(),
-            },
+            }),
         }
     }
 
@@ -147,7 +150,7 @@ impl ConfigParseError {
 
     fn render(&self, styles: &ParseErrorStyles) -> String {
         let mut out = String::new();
-        match &self.kind {
+        match &*self.kind {
             ParseErrorKind::Syntax {
                 message,
                 line,
@@ -206,7 +209,7 @@ impl ConfigParseError {
 
 impl fmt::Display for ConfigParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let styles = if console::Term::stderr().features().colors_supported() {
+        let styles = if stderr_colors_enabled() {
             ParseErrorStyles::colored()
         } else {
             ParseErrorStyles::plain()
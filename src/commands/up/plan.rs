@@ -17,7 +17,9 @@
 
 use std::collections::{BTreeMap, BTreeSet};
 
-use unisrv_api::models::{CreateEnvironmentRequest, DeploymentConfiguration, HTTPServiceConfig};
+use unisrv_api::models::{
+    CreateEnvironmentRequest, DeploymentConfiguration, HTTPServiceConfig, ManagedKind,
+};
 use uuid::Uuid;
 
 use super::desired::{DesiredDeployment, DesiredNetwork, DesiredService, DesiredState};
@@ -27,6 +29,12 @@ pub struct CurrentState {
     pub services: BTreeMap<String, CurrentService>,
     pub deployments: BTreeMap<String, CurrentDeployment>,
     pub networks: BTreeMap<String, CurrentNetwork>,
+    /// Every listed resource with its owner, duplicate names included — the
+    /// maps above keep one per name.
+    pub listed: Vec<Listed>,
+    /// Declared resources `--replace` deletes and creates afresh rather than
+    /// reconciling, because `up` doesn't own them.
+    pub replacing: BTreeSet<(ManagedKind, String)>,
 }
 
 impl CurrentState {
@@ -35,8 +43,24 @@ impl CurrentState {
             services: BTreeMap::new(),
             deployments: BTreeMap::new(),
             networks: BTreeMap::new(),
+            listed: Vec::new(),
+            replacing: BTreeSet::new(),
         }
     }
+
+    fn replaces(&self, kind: ManagedKind, name: &str) -> bool {
+        self.replacing.contains(&(kind, name.to_string()))
+    }
+}
+
+/// A network, service or deployment as listed, with the project whose `up`
+/// owns it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Listed {
+    pub kind: ManagedKind,
+    pub id: Uuid,
+    pub name: String,
+    pub managed_by: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -136,6 +160,18 @@ impl From<&unisrv_api::models::EnvironmentListEntry> for ResolvedEnvironment {
     }
 }
 
+/// The environment command tests run against: `prod` of project `demo`,
+/// with a fresh id.
+#[cfg(test)]
+pub(crate) fn test_env() -> ResolvedEnvironment {
+    ResolvedEnvironment {
+        id: Uuid::new_v4(),
+        name: "prod".into(),
+        project: "demo".into(),
+        slug: "ab12".into(),
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum ServiceAction {
     Create(DesiredService),
@@ -153,7 +189,6 @@ pub enum ServiceAction {
 }
 
 impl ServiceAction {
-    #[allow(dead_code)]
     pub fn name(&self) -> &str {
         match self {
             ServiceAction::Create(d) => &d.name,
@@ -213,7 +248,6 @@ pub enum DeploymentAction {
 }
 
 impl DeploymentAction {
-    #[allow(dead_code)]
     pub fn name(&self) -> &str {
         match self {
             DeploymentAction::Create { desired, .. } => &desired.name,
@@ -238,6 +272,16 @@ pub enum NetworkAction {
     Delete(CurrentNetwork),
 }
 
+impl NetworkAction {
+    pub fn name(&self) -> &str {
+        match self {
+            NetworkAction::Create(d) => &d.name,
+            NetworkAction::Recreate { desired, .. } => &desired.name,
+            NetworkAction::Delete(c) => &c.name,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum RecreateReason {
     /// e.g. "host" — an immutable field changed
@@ -254,6 +298,9 @@ pub enum RecreateReason {
     /// The network this deployment joins is being recreated under a new id, so
     /// the deployment must be recreated after the network to pick it up.
     DependentNetworkRecreated { network_name: String },
+    /// `--replace`: an existing resource `up` doesn't own takes the declared
+    /// name, so it's deleted and the declared one created in its place.
+    Replaced,
 }
 
 /// The shared name-keyed reconciliation walk: a desired entry with no current
@@ -293,7 +340,10 @@ pub fn diff(desired: &DesiredState, current: &CurrentState, env_action: EnvActio
         &current.services,
         |d| ServiceAction::Create(d.clone()),
         |d, c| {
-            let immutable_diffs = super::diff::service::immutable_diffs(d, c);
+            let mut immutable_diffs = super::diff::service::immutable_diffs(d, c);
+            if current.replaces(ManagedKind::Service, &d.name) {
+                immutable_diffs.push(RecreateReason::Replaced);
+            }
             if !immutable_diffs.is_empty() {
                 recreated_services.insert(d.name.clone());
                 Some(ServiceAction::Recreate {
@@ -325,20 +375,26 @@ pub fn diff(desired: &DesiredState, current: &CurrentState, env_action: EnvActio
         &current.networks,
         |d| NetworkAction::Create(d.clone()),
         |d, c| {
+            let mut reasons = Vec::new();
             if d.ipv4_cidr != c.ipv4_cidr {
-                recreated_networks.insert(d.name.clone());
-                Some(NetworkAction::Recreate {
-                    current: c.clone(),
-                    desired: d.clone(),
-                    reasons: vec![RecreateReason::ImmutableField {
-                        field: "iprange",
-                        old: c.ipv4_cidr.clone(),
-                        new: d.ipv4_cidr.clone(),
-                    }],
-                })
-            } else {
-                None
+                reasons.push(RecreateReason::ImmutableField {
+                    field: "iprange",
+                    old: c.ipv4_cidr.clone(),
+                    new: d.ipv4_cidr.clone(),
+                });
+            }
+            if current.replaces(ManagedKind::Network, &d.name) {
+                reasons.push(RecreateReason::Replaced);
             }
+            if reasons.is_empty() {
+                return None;
+            }
+            recreated_networks.insert(d.name.clone());
+            Some(NetworkAction::Recreate {
+                current: c.clone(),
+                desired: d.clone(),
+                reasons,
+            })
         },
         |c| NetworkAction::Delete(c.clone()),
     );
@@ -408,6 +464,10 @@ pub fn diff(desired: &DesiredState, current: &CurrentState, env_action: EnvActio
                 reasons.push(RecreateReason::ServiceBindingChanged);
             }
 
+            if current.replaces(ManagedKind::Deployment, &d.name) {
+                reasons.push(RecreateReason::Replaced);
+            }
+
             if !reasons.is_empty() {
                 Some(DeploymentAction::Recreate {
                     service: d.service_binding.as_ref().map(&service_ref),
@@ -503,10 +563,19 @@ mod tests {
             locations: vec![HTTPLocation {
                 path: "/".into(),
                 override_404: None,
+                headers: Default::default(),
+                websocket: false,
+                grpc: false,
+                cache: None,
                 target: HTTPLocationTarget::Instance {
                     group: "default".into(),
+                    rewrite: None,
                 },
             }],
+            basic_auth: vec![],
+            allow_ips: vec![],
+            tls: Default::default(),
+            compression: None,
         }
     }
 
@@ -521,6 +590,8 @@ mod tests {
             vcpu_count: 1,
             memory_mb: 256,
             instance_port: Some(80),
+            init_containers: None,
+            sidecars: None,
         }
     }
 
@@ -1233,6 +1304,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn replacing_an_unchanged_network_recreates_it() {
+        let mut desired = empty_desired();
+        desired.networks.insert(
+            "internal".into(),
+            desired_network("internal", "10.0.0.0/16"),
+        );
+        let mut current = CurrentState::empty();
+        current.networks.insert(
+            "internal".into(),
+            current_network(Uuid::new_v4(), "internal", "10.0.0.0/16"),
+        );
+        assert!(diff(&desired, &current, use_env()).is_empty());
+
+        current
+            .replacing
+            .insert((ManagedKind::Network, "internal".into()));
+        let plan = diff(&desired, &current, use_env());
+        assert!(matches!(
+            plan.network_actions.as_slice(),
+            [NetworkAction::Recreate { reasons, .. }] if reasons == &[RecreateReason::Replaced]
+        ));
+    }
+
     #[test]
     fn extra_network_is_delete() {
         let mut current = CurrentState::empty();
@@ -4,7 +4,7 @@
 //! already be ready and otherwise surface actionable error messages.
 
 use anyhow::{Context, Result, bail};
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use std::collections::BTreeSet;
 use unisrv_api::ApiClient;
 use unisrv_api::models::HostResponse;
@@ -29,7 +29,7 @@ pub async fn ensure_hosts_ready(
     let step = progress.step(Icon::Host, "Checking hosts");
     let mut hosts = client.list_hosts().await?;
     step.clear();
-    let now = Utc::now().naive_utc();
+    let now = Utc::now();
 
     for host in &referenced {
         let ready = hosts
@@ -61,7 +61,7 @@ pub async fn ensure_hosts_ready(
 ///    which has no per-host expiry. Ready as soon as it's claimed.
 ///  * `lets_encrypt` / `custom` — ready only while their per-host cert is valid.
 ///  * no cert type — not ready.
-fn has_valid_cert(host: &HostResponse, now: chrono::NaiveDateTime) -> bool {
+pub(crate) fn has_valid_cert(host: &HostResponse, now: DateTime<Utc>) -> bool {
     use unisrv_api::models::CertificateType;
     match host.certificate_type {
         Some(CertificateType::CommonWildcard) => true,
@@ -80,7 +80,7 @@ fn has_valid_cert(host: &HostResponse, now: chrono::NaiveDateTime) -> bool {
 pub fn validate_hosts_against(
     referenced: &BTreeSet<&str>,
     claimed: &[HostResponse],
-    now: chrono::NaiveDateTime,
+    now: DateTime<Utc>,
 ) -> Result<()> {
     if referenced.is_empty() {
         return Ok(());
@@ -302,6 +302,7 @@ mod tests {
                 container_image: "i:1".into(),
                 created_at: NaiveDateTime::default(),
                 deployment,
+                retained_until: None,
             }
         }
 
@@ -317,6 +318,7 @@ mod tests {
                         name: "internal".into(),
                         ipv4_cidr: "10.0.0.0/16".into(),
                         instance_count: Some(1),
+                        managed_by: None,
                     }],
                 }))
                 .with_list_instances(Ok(InstanceListResponse {
@@ -351,6 +353,7 @@ mod tests {
                         name: "internal".into(),
                         ipv4_cidr: "10.0.0.0/16".into(),
                         instance_count: Some(1),
+                        managed_by: None,
                     }],
                 }))
                 .with_list_instances(Ok(InstanceListResponse {
@@ -378,6 +381,7 @@ mod tests {
                         name: "internal".into(),
                         ipv4_cidr: "10.0.0.0/16".into(),
                         instance_count: Some(1),
+                        managed_by: None,
                     }],
                 }))
                 .with_list_instances(Ok(InstanceListResponse { instances: vec![] }))
@@ -404,6 +408,7 @@ mod tests {
                         name: "internal".into(),
                         ipv4_cidr: "10.0.0.0/16".into(),
                         instance_count: Some(1),
+                        managed_by: None,
                     }],
                 }))
                 .with_list_instances(Ok(InstanceListResponse {
@@ -440,6 +445,7 @@ mod tests {
                     name: "internal".into(),
                     ipv4_cidr: "10.0.0.0/16".into(),
                     instance_count: Some(0),
+                    managed_by: None,
                 }],
             }));
 
@@ -501,6 +507,10 @@ mod tests {
                     configuration: HTTPServiceConfig {
                         allow_http: false,
                         locations: vec![],
+                        basic_auth: vec![],
+                        allow_ips: vec![],
+                        tls: Default::default(),
+                        compression: None,
                     },
                 },
             );
@@ -510,7 +520,7 @@ mod tests {
 
     fn host_with_cert(host: &str, valid: bool) -> HostResponse {
         let valid_until = if valid {
-            Some(Utc::now().naive_utc() + Duration::days(30))
+            Some(Utc::now() + Duration::days(30))
         } else {
             None
         };
@@ -525,8 +535,8 @@ mod tests {
                 None
             },
             certificate_valid_until: valid_until,
-            created_at: NaiveDateTime::default(),
-            updated_at: NaiveDateTime::default(),
+            created_at: Default::default(),
+            updated_at: Default::default(),
         }
     }
 
@@ -722,30 +732,29 @@ mod tests {
         // count as ready even though it has no expiry.
         let h = wildcard_host("app.unisrv.dev");
         let referenced: BTreeSet<&str> = ["app.unisrv.dev"].into_iter().collect();
-        assert!(validate_hosts_against(&referenced, &[h], Utc::now().naive_utc()).is_ok());
+        assert!(validate_hosts_against(&referenced, &[h], Utc::now()).is_ok());
     }
 
     #[test]
     fn validate_flags_claimed_host_without_cert() {
         let claimed = vec![host_with_cert("h.example", false)];
         let referenced: BTreeSet<&str> = ["h.example"].into_iter().collect();
-        let err =
-            validate_hosts_against(&referenced, &claimed, Utc::now().naive_utc()).unwrap_err();
+        let err = validate_hosts_against(&referenced, &claimed, Utc::now()).unwrap_err();
         assert!(format!("{err:#}").contains("certificate"));
     }
 
     #[test]
     fn validate_flags_claimed_host_with_expired_cert() {
         let mut h = host_with_cert("h.example", true);
-        h.certificate_valid_until = Some(Utc::now().naive_utc() - Duration::days(1));
+        h.certificate_valid_until = Some(Utc::now() - Duration::days(1));
         let referenced: BTreeSet<&str> = ["h.example"].into_iter().collect();
-        let err = validate_hosts_against(&referenced, &[h], Utc::now().naive_utc()).unwrap_err();
+        let err = validate_hosts_against(&referenced, &[h], Utc::now()).unwrap_err();
         assert!(format!("{err:#}").contains("certificate"));
     }
 
     #[test]
     fn unknown_cert_type_is_ready_only_with_unexpired_validity() {
-        let now = Utc::now().naive_utc();
+        let now = Utc::now();
         let mut h = host_with_cert("x.example.com", true);
         h.certificate_type = Some(CertificateType::Unknown);
         h.certificate_valid_until = Some(now + Duration::days(10));
@@ -0,0 +1,554 @@
+//! `unisrv plan`: what `up` would do, without doing any of it.
+//!
+//! Computes the same plan as `up` but never claims hosts, never prompts to
+//! create an environment (a missing one is planned with its defaults) and
+//! never applies. With `--json` the plan is a document for policy checks in
+//! CI, e.g. "no service in prod may allow plain HTTP":
+//!
+//! ```json
+//! {
+//!   "project": "shop",
+//!   "environment": "prod",
+//!   "changes": [
+//!     {
+//!       "type": "service",
+//!       "name": "web",
+//!       "action": "update",
+//!       "changed_fields": ["configuration.allow_http"],
+//!       "reasons": [],
+//!       "before": { "hosts": [], "region": "eu", "configuration": { … } },
+//!       "after": { … }
+//!     }
+//!   ],
+//!   "summary": { "create": 0, "update": 1, "recreate": 0, "delete": 0 }
+//! }
+//! ```
+//!
+//! `before` is `null` for a create and `after` for a delete. Basic-auth
+//! passwords are masked in both; a changed one still shows in
+//! `changed_fields`.
+//!
+//! Images are pinned through `unisrv.lock` just as `up` pins them, so the
+//! plan shows the digests `up` will deploy, and a tag planned for the first
+//! time is locked then.
+//!
+//! `--target` and `--exclude` narrow the plan as they narrow `up`.
+//!
+//! Declared names taken by resources `up` doesn't own fail the plan as they
+//! would fail `up`, unless `--adopt` or `--replace` says how to settle them.
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{Value, json};
+use unisrv_api::ApiClient;
+
+use super::collisions::Resolution;
+use super::desired::{DesiredDeployment, DesiredNetwork, DesiredService, DesiredState};
+use super::env_resolve::{Prompter, resolve as resolve_env};
+use super::fetch::fetch_current_state;
+use super::lock::lock_images;
+use super::plan::{
+    CurrentDeployment, CurrentNetwork, CurrentService, CurrentState, DeploymentAction, EnvAction,
+    NetworkAction, Plan, RecreateReason, ServiceAction, diff,
+};
+use super::preflight::{validate_host_ownership, validate_network_instances};
+use super::render::{PlanStyles, describe_reason, render};
+use super::run::load_config;
+use super::targeting::{Targeting, restrict};
+use crate::commands::output::Output;
+use crate::commands::registry::policy;
+use crate::commands::ui::colors_enabled;
+use crate::progress::{Icon, Progress, SpinnerProgress};
+
+/// The plan as a machine-readable document.
+#[derive(Debug, Serialize)]
+pub struct PlanDocument {
+    pub project: String,
+    pub environment: String,
+    pub changes: Vec<ResourceChange>,
+    pub summary: Summary,
+}
+
+/// One resource the plan touches.
+#[derive(Debug, Serialize)]
+pub struct ResourceChange {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub name: String,
+    /// `create`, `update`, `recreate` or `delete`.
+    pub action: &'static str,
+    /// Dotted paths of the fields that differ, e.g. `configuration.replicas`.
+    /// Empty for a create or delete.
+    pub changed_fields: Vec<String>,
+    /// Why a recreate can't be an update.
+    pub reasons: Vec<String>,
+    pub before: Value,
+    pub after: Value,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Summary {
+    pub create: usize,
+    pub update: usize,
+    pub recreate: usize,
+    pub delete: usize,
+}
+
+impl ResourceChange {
+    fn new(
+        kind: &'static str,
+        name: &str,
+        action: &'static str,
+        before: Value,
+        after: Value,
+    ) -> Self {
+        let changed_fields = if before.is_null() || after.is_null() {
+            Vec::new()
+        } else {
+            changed_fields(&before, &after)
+        };
+        ResourceChange {
+            kind,
+            name: name.to_string(),
+            action,
+            changed_fields,
+            reasons: Vec::new(),
+            before: masked(before),
+            after: masked(after),
+        }
+    }
+
+    fn because(mut self, reasons: &[RecreateReason]) -> Self {
+        self.reasons = reasons.iter().map(describe_reason).collect();
+        self
+    }
+}
+
+impl PlanDocument {
+    pub fn from_plan(plan: &Plan) -> Self {
+        let mut changes = Vec::new();
+        let environment = match &plan.env_action {
+            EnvAction::Use(env) => env.name.clone(),
+            EnvAction::Create(req) => {
+                let after = serde_json::to_value(req).unwrap_or_default();
+                changes.push(ResourceChange::new(
+                    "environment",
+                    &req.name,
+                    "create",
+                    Value::Null,
+                    after,
+                ));
+                req.name.clone()
+            }
+        };
+        for action in &plan.network_actions {
+            changes.push(match action {
+                NetworkAction::Create(d) => {
+                    ResourceChange::new("network", &d.name, "create", Value::Null, network(d))
+                }
+                NetworkAction::Recreate {
+                    current,
+                    desired,
+                    reasons,
+                } => ResourceChange::new(
+                    "network",
+                    &desired.name,
+                    "recreate",
+                    current_network(current),
+                    network(desired),
+                )
+                .because(reasons),
+                NetworkAction::Delete(c) => ResourceChange::new(
+                    "network",
+                    &c.name,
+                    "delete",
+                    current_network(c),
+                    Value::Null,
+                ),
+            });
+        }
+        for action in &plan.service_actions {
+            changes.push(match action {
+                ServiceAction::Create(d) => {
+                    ResourceChange::new("service", &d.name, "create", Value::Null, service(d))
+                }
+                ServiceAction::Update {
+                    desired, current, ..
+                } => ResourceChange::new(
+                    "service",
+                    &desired.name,
+                    "update",
+                    current_service(current),
+                    service(desired),
+                ),
+                ServiceAction::Recreate {
+                    current,
+                    desired,
+                    reasons,
+                } => ResourceChange::new(
+                    "service",
+                    &desired.name,
+                    "recreate",
+                    current_service(current),
+                    service(desired),
+                )
+                .because(reasons),
+                ServiceAction::Delete(c) => ResourceChange::new(
+                    "service",
+                    &c.name,
+                    "delete",
+                    current_service(c),
+                    Value::Null,
+                ),
+            });
+        }
+        for action in &plan.deployment_actions {
+            changes.push(match action {
+                DeploymentAction::Create { desired, .. } => ResourceChange::new(
+                    "deployment",
+                    &desired.name,
+                    "create",
+                    Value::Null,
+                    deployment(desired),
+                ),
+                DeploymentAction::Update {
+                    desired, current, ..
+                } => ResourceChange::new(
+                    "deployment",
+                    &desired.name,
+                    "update",
+                    current_deployment(current),
+                    deployment(desired),
+                ),
+                DeploymentAction::Recreate {
+                    current,
+                    desired,
+                    reasons,
+                    ..
+                } => ResourceChange::new(
+                    "deployment",
+                    &desired.name,
+                    "recreate",
+                    current_deployment(current),
+                    deployment(desired),
+                )
+                .because(reasons),
+                DeploymentAction::Delete(c) => ResourceChange::new(
+                    "deployment",
+                    &c.name,
+                    "delete",
+                    current_deployment(c),
+                    Value::Null,
+                ),
+            });
+        }
+
+        let mut summary = Summary::default();
+        for change in &changes {
+            match change.action {
+                "create" => summary.create += 1,
+                "update" => summary.update += 1,
+                "recreate" => summary.recreate += 1,
+                _ => summary.delete += 1,
+            }
+        }
+        PlanDocument {
+            project: plan.project.clone(),
+            environment,
+            changes,
+            summary,
+        }
+    }
+}
+
+fn network(n: &DesiredNetwork) -> Value {
+    json!({ "ipv4_cidr": n.ipv4_cidr })
+}
+
+fn current_network(n: &CurrentNetwork) -> Value {
+    json!({ "ipv4_cidr": n.ipv4_cidr })
+}
+
+fn service(s: &DesiredService) -> Value {
+    json!({ "hosts": s.hosts, "region": s.region, "configuration": s.configuration })
+}
+
+fn current_service(s: &CurrentService) -> Value {
+    json!({ "hosts": s.hosts, "region": s.region, "configuration": s.configuration })
+}
+
+fn deployment(d: &DesiredDeployment) -> Value {
+    let service = d
+        .service_binding
+        .as_ref()
+        .map(|b| json!({ "name": b.service_name, "target_group": b.target_group }));
+    json!({ "configuration": d.configuration, "service": service, "network": d.network })
+}
+
+fn current_deployment(d: &CurrentDeployment) -> Value {
+    let service = d
+        .service_binding
+        .as_ref()
+        .map(|b| json!({ "name": b.service_name, "target_group": b.target_group }));
+    let network = d.network_binding.as_ref().map(|b| &b.network_name);
+    json!({ "configuration": d.configuration, "service": service, "network": network })
+}
+
+/// Dotted paths where `before` and `after` differ, descending into objects;
+/// arrays and scalars compare whole.
+fn changed_fields(before: &Value, after: &Value) -> Vec<String> {
+    fn walk(prefix: &str, before: &Value, after: &Value, out: &mut Vec<String>) {
+        match (before, after) {
+            (Value::Object(b), Value::Object(a)) => {
+                let keys: BTreeSet<&String> = b.keys().chain(a.keys()).collect();
+                for key in keys {
+                    let path = if prefix.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{prefix}.{key}")
+                    };
+                    let null = Value::Null;
+                    walk(
+                        &path,
+                        b.get(key).unwrap_or(&null),
+                        a.get(key).unwrap_or(&null),
+                        out,
+                    );
+                }
+            }
+            _ if before != after => out.push(prefix.to_string()),
+            _ => {}
+        }
+    }
+    let mut out = Vec::new();
+    walk("", before, after, &mut out);
+    out
+}
+
+/// `value` with any service basic-auth passwords replaced.
+fn masked(mut value: Value) -> Value {
+    if let Some(creds) = value
+        .pointer_mut("/configuration/basic_auth")
+        .and_then(Value::as_array_mut)
+    {
+        for cred in creds {
+            if let Some(password) = cred.get_mut("password") {
+                *password = Value::from("(sensitive)");
+            }
+        }
+    }
+    value
+}
+
+/// Plans against an environment that doesn't exist yet with the defaults `up`
+/// would offer, instead of asking.
+struct Defaults;
+
+impl Prompter for Defaults {
+    fn prompt_string(&self, _prompt: &str, default: Option<&str>) -> Result<String> {
+        Ok(default.unwrap_or_default().to_string())
+    }
+
+    fn prompt_optional(&self, _prompt: &str) -> Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn plan(
+    client: &dyn ApiClient,
+    env_flag: Option<&str>,
+    var_flags: &[String],
+    var_files: &[PathBuf],
+    update_images: bool,
+    collisions: Resolution,
+    targeting: &Targeting,
+    output: &Output,
+) -> Result<()> {
+    let mut config = load_config(var_flags, var_files)?;
+    policy::enforce(config.images())?;
+    let progress = SpinnerProgress::new();
+    lock_images(client, &mut config, update_images, &progress).await?;
+    let desired = DesiredState::from_config(config);
+
+    let env_action = resolve_env(client, &desired.project, env_flag, &Defaults, &progress).await?;
+    let mut current = match &env_action {
+        EnvAction::Use(env) => {
+            let step = progress.step(Icon::Lookup, "Fetching current state");
+            let state = fetch_current_state(client, env.id).await?;
+            step.clear();
+            state
+        }
+        EnvAction::Create(_) => CurrentState::empty(),
+    };
+    // Refused as `up` refuses them; what `--adopt` and `--replace` would do
+    // first is noted above the plan.
+    let found = super::collisions::find(&desired, &current);
+    let settlement = super::collisions::settle(&found, collisions, &mut current)?;
+    // The same up-front refusals as `up`, minus claiming hosts: a managed
+    // host that isn't claimed yet would be claimed by the apply.
+    let hosts = client.list_hosts().await?;
+    let managed_service_ids = current.services.values().map(|s| s.id).collect();
+    validate_host_ownership(&desired, &hosts, &managed_service_ids)?;
+    let plan = restrict(diff(&desired, &current, env_action), targeting, &desired)?;
+    if let EnvAction::Use(env) = &plan.env_action {
+        validate_network_instances(client, env.id, &plan).await?;
+    }
+
+    if output.is_machine() {
+        return output.print_json(&PlanDocument::from_plan(&plan));
+    }
+    for note in settlement.notes() {
+        println!("  ! {note}");
+    }
+    if plan.is_empty() {
+        if settlement.notes().is_empty() {
+            println!("Everything's up to date — nothing to apply.");
+        }
+        return Ok(());
+    }
+    let styles = if colors_enabled() {
+        PlanStyles::colored()
+    } else {
+        PlanStyles::plain()
+    };
+    print!("{}", render(&plan, &styles));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::up::plan::{CurrentNetworkBinding, ResolvedEnvironment};
+    use unisrv_api::models::{
+        BasicAuthCredential, CreateEnvironmentRequest, DeploymentConfiguration, HTTPServiceConfig,
+    };
+    use uuid::Uuid;
+
+    fn config(replicas: u32, image: &str) -> DeploymentConfiguration {
+        DeploymentConfiguration {
+            replicas,
+            region: "dev".into(),
+            container_image: image.into(),
+            args: None,
+            env: None,
+            vcpu_ratio: 0.25,
+            vcpu_count: 1,
+            memory_mb: 256,
+            instance_port: Some(80),
+            init_containers: None,
+            sidecars: None,
+        }
+    }
+
+    fn plan(env_action: EnvAction) -> Plan {
+        Plan {
+            project: "shop".into(),
+            env_action,
+            service_actions: vec![],
+            deployment_actions: vec![],
+            network_actions: vec![],
+            instance_stops: vec![],
+        }
+    }
+
+    #[test]
+    fn lists_each_change_with_the_fields_it_touches() {
+        let mut plan = plan(EnvAction::Use(ResolvedEnvironment {
+            id: Uuid::nil(),
+            name: "prod".into(),
+            project: "shop".into(),
+            slug: "ab12".into(),
+        }));
+        plan.network_actions = vec![NetworkAction::Create(DesiredNetwork {
+            name: "backend".into(),
+            ipv4_cidr: "10.0.0.0/24".into(),
+        })];
+        plan.deployment_actions = vec![DeploymentAction::Update {
+            id: Uuid::nil(),
+            desired: DesiredDeployment {
+                name: "api".into(),
+                configuration: config(3, "api:2"),
+                service_binding: None,
+                network: Some("backend".into()),
+            },
+            current: CurrentDeployment {
+                id: Uuid::nil(),
+                name: "api".into(),
+                configuration: config(1, "api:2"),
+                service_binding: None,
+                network_binding: Some(CurrentNetworkBinding {
+                    network_id: Uuid::nil(),
+                    network_name: "backend".into(),
+                }),
+            },
+            network: None,
+        }];
+
+        let doc = serde_json::to_value(PlanDocument::from_plan(&plan)).unwrap();
+        assert_eq!(doc["environment"], "prod");
+        assert_eq!(doc["changes"][0]["type"], "network");
+        assert_eq!(doc["changes"][0]["action"], "create");
+        assert_eq!(doc["changes"][0]["before"], Value::Null);
+        assert_eq!(doc["changes"][0]["after"]["ipv4_cidr"], "10.0.0.0/24");
+        assert_eq!(doc["changes"][1]["action"], "update");
+        assert_eq!(
+            doc["changes"][1]["changed_fields"],
+            json!(["configuration.replicas"])
+        );
+        assert_eq!(
+            doc["summary"],
+            json!({"create": 1, "update": 1, "recreate": 0, "delete": 0})
+        );
+    }
+
+    #[test]
+    fn masks_passwords_but_still_reports_them_changed() {
+        let service = |password: &str| CurrentService {
+            id: Uuid::nil(),
+            name: "web".into(),
+            hosts: vec![],
+            region: "dev".into(),
+            configuration: HTTPServiceConfig {
+                locations: vec![],
+                allow_http: false,
+                basic_auth: vec![BasicAuthCredential {
+                    username: "ops".into(),
+                    password: password.into(),
+                }],
+                allow_ips: vec![],
+                tls: Default::default(),
+                compression: None,
+            },
+        };
+        let change = ResourceChange::new(
+            "service",
+            "web",
+            "update",
+            current_service(&service("old")),
+            current_service(&service("new")),
+        );
+        assert_eq!(change.changed_fields, ["configuration.basic_auth"]);
+        assert_eq!(
+            change.after["configuration"]["basic_auth"][0]["password"],
+            "(sensitive)"
+        );
+    }
+
+    #[test]
+    fn a_new_environment_is_a_change_too() {
+        let plan = plan(EnvAction::Create(CreateEnvironmentRequest {
+            project: "shop".into(),
+            name: "staging".into(),
+            display_name: None,
+            description: None,
+        }));
+        let doc = PlanDocument::from_plan(&plan);
+        assert_eq!(doc.environment, "staging");
+        assert_eq!(doc.changes[0].kind, "environment");
+        assert_eq!(doc.summary.create, 1);
+    }
+}
@@ -0,0 +1,352 @@
+//! The readiness gate after `up` rolls deployments out.
+//!
+//! The apply itself returns as soon as the API has accepted each change; the
+//! operator brings instances up afterwards. When a deployment declares
+//! `ready_timeout` (or `up` is given `--timeout`), `up` then waits for every
+//! replica of each deployment it created or updated to be running, and fails
+//! with a per-deployment summary of those that never got there — so a CI job
+//! fails on a crash-looping image instead of passing and leaving it behind.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use unisrv_api::ApiClient;
+use unisrv_api::models::DeploymentDetailResponse;
+use uuid::Uuid;
+
+use super::apply::{Poll, PollOutcome, Waiter, poll_until};
+use crate::progress::{Icon, Progress, Tone};
+
+/// How often rolled-out deployments are checked.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Parse a duration like "90s", "5m", "1h", "1m30s" or "90d".
+pub fn parse_duration(spec: &str) -> Result<Duration, String> {
+    let invalid = || format!("{spec:?} is not a valid duration (e.g. \"90s\", \"5m\", \"1h30m\")");
+    let mut total = 0u64;
+    let mut digits = String::new();
+    for c in spec.trim().chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let count: u64 = digits.parse().map_err(|_| invalid())?;
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86_400,
+            _ => return Err(invalid()),
+        };
+        total = count
+            .checked_mul(unit)
+            .and_then(|secs| total.checked_add(secs))
+            .ok_or_else(invalid)?;
+        digits.clear();
+    }
+    if !digits.is_empty() {
+        return Err(format!(
+            "{spec:?} has no unit; write e.g. \"{digits}s\" or \"{digits}m\""
+        ));
+    }
+    if total == 0 {
+        return Err(format!("{spec:?} must be a positive duration"));
+    }
+    Ok(Duration::from_secs(total))
+}
+
+/// A duration as [`parse_duration`] reads it, e.g. "1m30s".
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    let mut out = String::new();
+    if h > 0 {
+        out.push_str(&format!("{h}h"));
+    }
+    if m > 0 {
+        out.push_str(&format!("{m}m"));
+    }
+    if s > 0 || out.is_empty() {
+        out.push_str(&format!("{s}s"));
+    }
+    out
+}
+
+/// A deployment `up` created or updated, and how long it gets to be ready.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rollout {
+    pub id: Uuid,
+    pub name: String,
+    pub replicas: u32,
+    pub timeout: Duration,
+}
+
+/// A rollout that ran out of time, with how far it got.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotReady {
+    pub name: String,
+    pub timeout: Duration,
+    pub status: String,
+}
+
+/// `None` once every replica is running and nothing is still starting;
+/// otherwise how far along it is, e.g. "1/3 running, 2 starting".
+fn pending_status(detail: &DeploymentDetailResponse, replicas: u32) -> Option<String> {
+    let count = |state: &str| {
+        detail
+            .instances
+            .iter()
+            .filter(|i| i.state.0 == state)
+            .count()
+    };
+    let (running, starting) = (count("running"), count("provisioning"));
+    if running >= replicas as usize && starting == 0 {
+        return None;
+    }
+    let mut status = format!("{running}/{replicas} running");
+    if starting > 0 {
+        status.push_str(&format!(", {starting} starting"));
+    }
+    if let Some(backoff) = &detail.backoff
+        && backoff.consecutive_instance_failures > 0
+    {
+        status.push_str(&format!(
+            ", {} failed in a row",
+            backoff.consecutive_instance_failures
+        ));
+    }
+    Some(status)
+}
+
+/// Wait for each of `rollouts` to be ready or run out of its timeout,
+/// whichever comes first. Returns the ones that ran out.
+pub async fn wait_ready(
+    client: &dyn ApiClient,
+    env_id: Uuid,
+    rollouts: Vec<Rollout>,
+    waiter: &dyn Waiter,
+    progress: &dyn Progress,
+) -> Result<Vec<NotReady>> {
+    let Some(longest) = rollouts.iter().map(|r| r.timeout).max() else {
+        return Ok(Vec::new());
+    };
+    // One more round than the longest timeout, so its last check lands at or
+    // past the deadline.
+    let max_rounds = longest.as_secs().div_ceil(POLL_INTERVAL.as_secs()) as usize + 1;
+
+    let step = progress.step(Icon::Deployment, "Waiting for deployments to be ready");
+    let mut pending: Vec<(Rollout, String)> = rollouts
+        .into_iter()
+        .map(|r| (r, "not checked yet".to_string()))
+        .collect();
+    let mut not_ready = Vec::new();
+    let mut round = 0u32;
+    let outcome = poll_until(waiter, POLL_INTERVAL, max_rounds, &step, async || {
+        let elapsed = POLL_INTERVAL * round;
+        round += 1;
+        let mut still = Vec::new();
+        for (rollout, _) in pending.drain(..) {
+            let detail = client
+                .get_deployment(env_id, rollout.id)
+                .await
+                .with_context(|| format!("failed to check deployment {:?}", rollout.name))?;
+            match pending_status(&detail, rollout.replicas) {
+                None => {}
+                Some(status) if elapsed >= rollout.timeout => not_ready.push(NotReady {
+                    name: rollout.name,
+                    timeout: rollout.timeout,
+                    status,
+                }),
+                Some(status) => still.push((rollout, status)),
+            }
+        }
+        pending = still;
+        if pending.is_empty() {
+            return Ok(Poll::Done);
+        }
+        let waiting: Vec<_> = pending
+            .iter()
+            .map(|(r, status)| format!("{} {status}", r.name))
+            .collect();
+        Ok(Poll::Pending(format!("Waiting for {}", waiting.join("; "))))
+    })
+    .await?;
+    if outcome == PollOutcome::TimedOut {
+        not_ready.extend(pending.into_iter().map(|(rollout, status)| NotReady {
+            name: rollout.name,
+            timeout: rollout.timeout,
+            status,
+        }));
+    }
+
+    if not_ready.is_empty() {
+        step.finish(Tone::Add, "deployments ready");
+    } else {
+        step.finish(
+            Tone::Warn,
+            &format!("{} deployment(s) not ready", not_ready.len()),
+        );
+    }
+    Ok(not_ready)
+}
+
+/// The error summary for rollouts that never became ready.
+pub fn render_not_ready(not_ready: &[NotReady]) -> String {
+    let lines: Vec<_> = not_ready
+        .iter()
+        .map(|n| {
+            format!(
+                "  deployment {} not ready after {}: {}",
+                n.name,
+                format_duration(n.timeout),
+                n.status
+            )
+        })
+        .collect();
+    format!(
+        "{} deployment(s) never became ready:\n{}",
+        not_ready.len(),
+        lines.join("\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::up::apply::NoSleep;
+    use crate::progress::SilentProgress;
+    use chrono::NaiveDateTime;
+    use unisrv_api::models::{
+        BackoffStatus, DeploymentConfiguration, DeploymentInstanceEntry, DeploymentState,
+        InstanceState,
+    };
+    use unisrv_api::test_support::MockApiClient;
+
+    fn detail(id: Uuid, states: &[&str], failures: usize) -> DeploymentDetailResponse {
+        DeploymentDetailResponse {
+            id,
+            name: "api".into(),
+            state: DeploymentState("running".into()),
+            configuration: DeploymentConfiguration {
+                replicas: 2,
+                region: "dev".into(),
+                container_image: "api:2".into(),
+                args: None,
+                env: None,
+                vcpu_ratio: 0.25,
+                vcpu_count: 1,
+                memory_mb: 512,
+                instance_port: None,
+                init_containers: None,
+                sidecars: None,
+            },
+            metadata: serde_json::Value::Null,
+            service_id: None,
+            service_target_group: None,
+            network_id: None,
+            instances: states
+                .iter()
+                .map(|state| DeploymentInstanceEntry {
+                    id: Uuid::new_v4(),
+                    name: None,
+                    state: InstanceState(state.to_string()),
+                    node_id: Uuid::nil(),
+                    created_at: NaiveDateTime::default(),
+                })
+                .collect(),
+            backoff: Some(BackoffStatus {
+                consecutive_instance_failures: failures,
+                next_retry_at: None,
+            }),
+            created_at: NaiveDateTime::default(),
+            updated_at: NaiveDateTime::default(),
+        }
+    }
+
+    fn rollout(id: Uuid, timeout: u64) -> Rollout {
+        Rollout {
+            id,
+            name: "api".into(),
+            replicas: 2,
+            timeout: Duration::from_secs(timeout),
+        }
+    }
+
+    #[test]
+    fn parses_and_formats_durations() {
+        for (spec, secs) in [
+            ("90s", 90),
+            ("5m", 300),
+            ("1h30m", 5400),
+            ("1m5s", 65),
+            ("90d", 7_776_000),
+        ] {
+            assert_eq!(parse_duration(spec), Ok(Duration::from_secs(secs)));
+        }
+        assert_eq!(format_duration(Duration::from_secs(90)), "1m30s");
+        assert_eq!(format_duration(Duration::from_secs(3600)), "1h");
+        assert!(parse_duration("90").unwrap_err().contains("no unit"));
+        assert!(parse_duration("0s").unwrap_err().contains("positive"));
+        assert!(parse_duration("5 minutes").is_err());
+    }
+
+    #[test]
+    fn an_overflowing_duration_is_invalid_rather_than_a_panic() {
+        for spec in [
+            "99999999999999999999d",
+            "213503982334602d",
+            "18446744073709551615s1s",
+        ] {
+            assert!(
+                parse_duration(spec)
+                    .unwrap_err()
+                    .contains("not a valid duration"),
+                "{spec}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn waits_until_every_replica_is_running() {
+        let id = Uuid::new_v4();
+        let mock = MockApiClient::logged_in()
+            .push_get_deployment(Ok(detail(id, &["running", "provisioning"], 0)))
+            .push_get_deployment(Ok(detail(id, &["running", "running"], 0)));
+
+        let not_ready = wait_ready(
+            &mock,
+            Uuid::nil(),
+            vec![rollout(id, 60)],
+            &NoSleep,
+            &SilentProgress,
+        )
+        .await
+        .unwrap();
+        assert_eq!(not_ready, []);
+    }
+
+    #[tokio::test]
+    async fn reports_what_never_became_ready_once_its_timeout_passes() {
+        let id = Uuid::new_v4();
+        // Checked at 0s, 2s and 4s: the 4s check is past the 3s timeout.
+        let mut mock = MockApiClient::logged_in();
+        for _ in 0..3 {
+            mock = mock.push_get_deployment(Ok(detail(id, &["running", "provisioning"], 3)));
+        }
+
+        let not_ready = wait_ready(
+            &mock,
+            Uuid::nil(),
+            vec![rollout(id, 3)],
+            &NoSleep,
+            &SilentProgress,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            render_not_ready(&not_ready),
+            "1 deployment(s) never became ready:\n  deployment api not ready after 3s: \
+             1/2 running, 1 starting, 3 failed in a row"
+        );
+    }
+}
@@ -75,10 +75,10 @@ pub fn render(plan: &Plan, styles: &PlanStyles) -> String {
             if let Some(d) = &req.display_name {
                 let _ = writeln!(out, "      display_name: {d:?}");
             }
-            if let Some(d) = &req.description {
-                if !d.is_empty() {
-                    let _ = writeln!(out, "      description:  {d:?}");
-                }
+            if let Some(d) = &req.description
+                && !d.is_empty()
+            {
+                let _ = writeln!(out, "      description:  {d:?}");
             }
         }
     }
@@ -234,6 +234,20 @@ pub fn render(plan: &Plan, styles: &PlanStyles) -> String {
                 if let Some(p) = d.configuration.instance_port {
                     let _ = writeln!(out, "      port:     {p}");
                 }
+                if let Some(init) = &d.configuration.init_containers {
+                    let _ = writeln!(
+                        out,
+                        "      init:     {}",
+                        diff::deployment::containers_display(Some(init))
+                    );
+                }
+                if let Some(sidecars) = &d.configuration.sidecars {
+                    let _ = writeln!(
+                        out,
+                        "      sidecars: {}",
+                        diff::deployment::containers_display(Some(sidecars))
+                    );
+                }
                 if let Some(b) = &d.service_binding {
                     let _ = writeln!(
                         out,
@@ -344,20 +358,27 @@ fn render_network_transition(
 fn format_reasons(reasons: &[RecreateReason]) -> String {
     reasons
         .iter()
-        .map(|r| match r {
-            RecreateReason::ImmutableField { field, .. } => format!("{field} changed"),
-            RecreateReason::ServiceBindingChanged => "service binding changed".to_string(),
-            RecreateReason::DependentServiceRecreated { service_name } => {
-                format!("service {service_name} recreated")
-            }
-            RecreateReason::DependentNetworkRecreated { network_name } => {
-                format!("network {network_name} recreated")
-            }
-        })
+        .map(describe_reason)
         .collect::<Vec<_>>()
         .join(", ")
 }
 
+/// Why a resource is recreated, e.g. "network backend recreated". Shared with
+/// the JSON plan so both say the same thing.
+pub fn describe_reason(reason: &RecreateReason) -> String {
+    match reason {
+        RecreateReason::ImmutableField { field, .. } => format!("{field} changed"),
+        RecreateReason::ServiceBindingChanged => "service binding changed".to_string(),
+        RecreateReason::DependentServiceRecreated { service_name } => {
+            format!("service {service_name} recreated")
+        }
+        RecreateReason::DependentNetworkRecreated { network_name } => {
+            format!("network {network_name} recreated")
+        }
+        RecreateReason::Replaced => "replacing one up doesn't own".to_string(),
+    }
+}
+
 /// A service's reachable hosts after `up`: the always-live derived base host
 /// plus any bound custom hosts.
 pub struct Reachability {
@@ -404,10 +425,19 @@ mod tests {
             locations: vec![HTTPLocation {
                 path: "/".into(),
                 override_404: None,
+                headers: Default::default(),
+                websocket: false,
+                grpc: false,
+                cache: None,
                 target: HTTPLocationTarget::Instance {
                     group: "default".into(),
+                    rewrite: None,
                 },
             }],
+            basic_auth: vec![],
+            allow_ips: vec![],
+            tls: Default::default(),
+            compression: None,
         }
     }
 
@@ -422,6 +452,8 @@ mod tests {
             vcpu_count: 1,
             memory_mb: 256,
             instance_port: Some(80),
+            init_containers: None,
+            sidecars: None,
         }
     }
 
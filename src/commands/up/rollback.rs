@@ -0,0 +1,336 @@
+//! What an apply changed, and `--rollback-on-failure`'s undo of it.
+//!
+//! [`apply_recorded`](super::apply::apply_recorded) appends a [`Change`] as
+//! each step lands, so when a later step fails (or a rollout never becomes
+//! ready) `up` can say exactly what it left behind — and, when asked, walk the
+//! journal backwards putting things back. Deletes and recreates can't be put
+//! back (the old resource is gone), so they're journaled as
+//! [`Change::Irreversible`] and reported rather than silently skipped.
+
+use anyhow::{Context, Result};
+use unisrv_api::ApiClient;
+use unisrv_api::models::{HTTPServiceConfig, UpdateDeploymentRequest};
+use uuid::Uuid;
+
+use super::apply::{Waiter, wait_for_network_drain};
+use super::plan::CurrentNetwork;
+use crate::commands::destroy::execute::poll_deployments_drained;
+use crate::progress::{Icon, Progress, Tone};
+
+/// Rounds to wait for a rolled-back environment's deployments to drain before
+/// deleting it; matches `destroy`.
+const ENV_DRAIN_MAX_ATTEMPTS: usize = 60;
+
+/// One change an apply made, with what's needed to undo it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    EnvironmentCreated {
+        id: Uuid,
+        name: String,
+    },
+    NetworkCreated {
+        id: Uuid,
+        name: String,
+        ipv4_cidr: String,
+    },
+    ServiceCreated {
+        id: Uuid,
+        name: String,
+    },
+    ServiceUpdated {
+        id: Uuid,
+        name: String,
+        previous: HTTPServiceConfig,
+    },
+    HostUnlinked {
+        host_id: Uuid,
+        service_id: Uuid,
+        host: String,
+    },
+    HostLinked {
+        host_id: Uuid,
+        service_id: Uuid,
+        host: String,
+    },
+    DeploymentCreated {
+        id: Uuid,
+        name: String,
+        replicas: u32,
+    },
+    DeploymentUpdated {
+        id: Uuid,
+        name: String,
+        replicas: u32,
+        previous: UpdateDeploymentRequest,
+    },
+    /// Done, but nothing to put back: a delete, or the delete half of a
+    /// recreate. Describes what happened, e.g. "deployment api deleted".
+    Irreversible(String),
+}
+
+impl Change {
+    fn describe(&self) -> String {
+        match self {
+            Change::EnvironmentCreated { name, .. } => format!("environment {name} created"),
+            Change::NetworkCreated { name, .. } => format!("network {name} created"),
+            Change::ServiceCreated { name, .. } => format!("service {name} created"),
+            Change::ServiceUpdated { name, .. } => format!("service {name} updated"),
+            Change::HostUnlinked { host, .. } => format!("host {host} unlinked"),
+            Change::HostLinked { host, .. } => format!("host {host} linked"),
+            Change::DeploymentCreated { name, .. } => format!("deployment {name} created"),
+            Change::DeploymentUpdated { name, .. } => format!("deployment {name} updated"),
+            Change::Irreversible(what) => what.clone(),
+        }
+    }
+}
+
+/// Every change an apply made, in the order it made them.
+#[derive(Debug, Default)]
+pub struct Journal {
+    /// The environment applied to, once known (created or existing).
+    pub env_id: Option<Uuid>,
+    pub changes: Vec<Change>,
+}
+
+impl Journal {
+    pub fn record(&mut self, change: Change) {
+        self.changes.push(change);
+    }
+
+    /// Deployments this apply rolled out — created or updated — as
+    /// `(id, name, replicas)`, for the readiness wait.
+    pub fn rollouts(&self) -> Vec<(Uuid, String, u32)> {
+        self.changes
+            .iter()
+            .filter_map(|change| match change {
+                Change::DeploymentCreated { id, name, replicas }
+                | Change::DeploymentUpdated {
+                    id, name, replicas, ..
+                } => Some((*id, name.clone(), *replicas)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The partial state a failed apply left behind, for the error report.
+    pub fn render_left_in_place(&self) -> String {
+        if self.changes.is_empty() {
+            return "  Nothing was changed.\n".to_string();
+        }
+        let mut out = String::from("  Left in place:\n");
+        for change in &self.changes {
+            out.push_str(&format!("    {}\n", change.describe()));
+        }
+        out
+    }
+}
+
+/// Undo `journal` newest first. Keeps going past a failed step so as much as
+/// possible is put back; returns the steps that failed, plus the changes that
+/// couldn't be undone at all.
+pub async fn rollback(
+    client: &dyn ApiClient,
+    journal: &Journal,
+    waiter: &dyn Waiter,
+    progress: &dyn Progress,
+) -> Vec<String> {
+    let Some(env_id) = journal.env_id else {
+        return Vec::new();
+    };
+    let mut problems = Vec::new();
+    for change in journal.changes.iter().rev() {
+        if let Change::Irreversible(what) = change {
+            problems.push(format!("{what}; can't be undone"));
+            continue;
+        }
+        if let Err(e) = undo(client, env_id, change, waiter, progress).await {
+            problems.push(format!("{e:#}"));
+        }
+    }
+    problems
+}
+
+async fn undo(
+    client: &dyn ApiClient,
+    env_id: Uuid,
+    change: &Change,
+    waiter: &dyn Waiter,
+    progress: &dyn Progress,
+) -> Result<()> {
+    match change {
+        Change::DeploymentCreated { id, name, .. } => {
+            let step = progress.step(Icon::Deployment, &format!("Removing deployment {name}"));
+            client
+                .delete_deployment(env_id, *id)
+                .await
+                .with_context(|| format!("failed to remove deployment {name:?}"))?;
+            step.finish(Tone::Remove, &format!("deployment {name} removed"));
+        }
+        Change::DeploymentUpdated {
+            id, name, previous, ..
+        } => {
+            let step = progress.step(Icon::Deployment, &format!("Reverting deployment {name}"));
+            client
+                .update_deployment(env_id, *id, previous.clone())
+                .await
+                .with_context(|| format!("failed to revert deployment {name:?}"))?;
+            step.finish(Tone::Change, &format!("deployment {name} reverted"));
+        }
+        Change::HostLinked {
+            host_id,
+            service_id,
+            host,
+        } => {
+            let step = progress.step(Icon::Host, &format!("Unlinking host {host}"));
+            client
+                .unlink_host_from_service(*host_id, *service_id)
+                .await
+                .with_context(|| format!("failed to unlink host {host:?}"))?;
+            step.finish(Tone::Remove, &format!("host {host} unlinked"));
+        }
+        Change::HostUnlinked {
+            host_id,
+            service_id,
+            host,
+        } => {
+            let step = progress.step(Icon::Host, &format!("Relinking host {host}"));
+            client
+                .link_host_to_service(*host_id, *service_id)
+                .await
+                .with_context(|| format!("failed to relink host {host:?}"))?;
+            step.finish(Tone::Add, &format!("host {host} relinked"));
+        }
+        Change::ServiceCreated { id, name } => {
+            let step = progress.step(Icon::Service, &format!("Removing service {name}"));
+            client
+                .delete_service(env_id, *id)
+                .await
+                .with_context(|| format!("failed to remove service {name:?}"))?;
+            step.finish(Tone::Remove, &format!("service {name} removed"));
+        }
+        Change::ServiceUpdated { id, name, previous } => {
+            let step = progress.step(Icon::Service, &format!("Reverting service {name}"));
+            client
+                .update_service(env_id, *id, previous.clone())
+                .await
+                .with_context(|| format!("failed to revert service {name:?}"))?;
+            step.finish(Tone::Change, &format!("service {name} reverted"));
+        }
+        Change::NetworkCreated {
+            id,
+            name,
+            ipv4_cidr,
+        } => {
+            // The deployments removed above have to drain off it first.
+            let network = CurrentNetwork {
+                id: *id,
+                name: name.clone(),
+                ipv4_cidr: ipv4_cidr.clone(),
+            };
+            wait_for_network_drain(client, env_id, &network, waiter, progress).await?;
+            let step = progress.step(Icon::Network, &format!("Removing network {name}"));
+            client
+                .delete_network(env_id, *id)
+                .await
+                .with_context(|| format!("failed to remove network {name:?}"))?;
+            step.finish(Tone::Remove, &format!("network {name} removed"));
+        }
+        Change::EnvironmentCreated { id, name } => {
+            poll_deployments_drained(client, *id, waiter, ENV_DRAIN_MAX_ATTEMPTS, progress).await?;
+            let step = progress.step(Icon::Environment, &format!("Removing environment {name}"));
+            client
+                .delete_environment(*id)
+                .await
+                .with_context(|| format!("failed to remove environment {name:?}"))?;
+            step.finish(Tone::Remove, &format!("environment {name} removed"));
+        }
+        Change::Irreversible(_) => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::up::apply::NoSleep;
+    use crate::progress::SilentProgress;
+    use unisrv_api::models::DeploymentConfiguration;
+    use unisrv_api::test_support::MockApiClient;
+
+    fn config(image: &str) -> DeploymentConfiguration {
+        DeploymentConfiguration {
+            replicas: 1,
+            region: "dev".into(),
+            container_image: image.into(),
+            args: None,
+            env: None,
+            vcpu_ratio: 0.25,
+            vcpu_count: 1,
+            memory_mb: 512,
+            instance_port: Some(8080),
+            init_containers: None,
+            sidecars: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn undoes_newest_first_and_reports_what_it_cannot() {
+        let env_id = Uuid::new_v4();
+        let created = Uuid::new_v4();
+        let updated = Uuid::new_v4();
+        let previous = UpdateDeploymentRequest {
+            network_id: None,
+            configuration: config("api:1"),
+        };
+        let journal = Journal {
+            env_id: Some(env_id),
+            changes: vec![
+                Change::DeploymentUpdated {
+                    id: updated,
+                    name: "api".into(),
+                    replicas: 1,
+                    previous: previous.clone(),
+                },
+                Change::Irreversible("deployment old deleted".into()),
+                Change::DeploymentCreated {
+                    id: created,
+                    name: "worker".into(),
+                    replicas: 1,
+                },
+            ],
+        };
+        let mock = MockApiClient::logged_in()
+            .push_delete_deployment(Ok(()))
+            .push_update_deployment(Ok(()));
+
+        let problems = rollback(&mock, &journal, &NoSleep, &SilentProgress).await;
+
+        assert_eq!(problems, ["deployment old deleted; can't be undone"]);
+        let calls = mock.calls.lock().unwrap();
+        assert_eq!(calls.delete_deployment_calls, [(env_id, created)]);
+        assert_eq!(calls.update_deployment_calls, [(env_id, updated, previous)]);
+    }
+
+    #[test]
+    fn rollouts_are_the_created_and_updated_deployments() {
+        let journal = Journal {
+            env_id: None,
+            changes: vec![
+                Change::ServiceCreated {
+                    id: Uuid::nil(),
+                    name: "web".into(),
+                },
+                Change::DeploymentCreated {
+                    id: Uuid::nil(),
+                    name: "api".into(),
+                    replicas: 2,
+                },
+            ],
+        };
+        assert_eq!(journal.rollouts(), [(Uuid::nil(), "api".to_string(), 2)]);
+        assert_eq!(
+            journal.render_left_in_place(),
+            "  Left in place:\n    service web created\n    deployment api created\n"
+        );
+    }
+}
@@ -3,50 +3,73 @@
 //! Composition only — each step lives in its own module with focused tests.
 
 use anyhow::{Context, Result, anyhow};
-use dialoguer::{Confirm, Input};
+use chrono::Utc;
+use std::collections::BTreeMap;
 use std::io::IsTerminal;
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
 use unisrv_api::ApiClient;
+use uuid::Uuid;
 
-use super::apply::apply;
+use super::apply::{RealWaiter, Waiter, apply_recorded};
+use super::collisions::{self, Resolution};
+use super::config::UpConfig;
 use super::desired::DesiredState;
 use super::env_resolve::{Prompter, resolve as resolve_env};
 use super::fetch::fetch_current_state;
-use super::plan::{EnvAction, diff};
+use super::lock::lock_images;
+use super::outputs::{Live, Outputs, render as render_outputs};
+use super::plan::{CurrentState, EnvAction, diff};
 use super::preflight::{ensure_hosts_ready, validate_host_ownership, validate_network_instances};
+use super::ready::{Rollout, format_duration, render_not_ready, wait_ready};
 use super::render::{PlanStyles, render};
+use super::rollback::{Journal, rollback};
+use super::targeting::{Targeting, restrict};
 use super::vars;
+use crate::commands::prompt;
+use crate::commands::registry::policy;
+use crate::commands::service::reach::unreachable_targets;
+use crate::commands::ui::{colors_enabled, emoji};
 use crate::config_locate::{CONFIG_FILE, find_config};
 use crate::progress::{Icon, Progress, SpinnerProgress};
+use crate::state::{HistoryEntry, Outcome, StateDir};
+
+/// How `up` treats its rollout: `timeout` bounds the whole apply including
+/// the readiness wait, and `rollback_on_failure` undoes what a failed apply
+/// did instead of leaving it in place. `update_images` re-resolves every
+/// image tag rather than deploying the digests in `unisrv.lock`, and
+/// `collisions` settles declared names taken by resources `up` doesn't own.
+/// `targeting` narrows the apply to some resources, and `yes` applies the
+/// plan without asking.
+#[derive(Debug, Default)]
+pub struct Gates {
+    pub timeout: Option<Duration>,
+    pub rollback_on_failure: bool,
+    pub update_images: bool,
+    pub collisions: Resolution,
+    pub targeting: Targeting,
+    pub yes: bool,
+}
 
 pub async fn run(
     client: &dyn ApiClient,
     env_flag: Option<&str>,
     var_flags: &[String],
     var_files: &[PathBuf],
+    gates: &Gates,
 ) -> Result<()> {
-    let cwd = std::env::current_dir().context("failed to determine the current directory")?;
-    let manifest = find_config(&cwd, CONFIG_FILE)
-        .ok_or_else(|| anyhow!("no {CONFIG_FILE} found in the current directory"))?;
-    let path = manifest.path.as_path();
-    let source = std::fs::read_to_string(path)
-        .with_context(|| format!("failed to read {}", path.display()))?;
-
-    let prompter = DialoguerPrompter;
-
-    // Gather interpolation variables, then resolve the config — prompting for
-    // any referenced-but-unset variable when stdin is a terminal. We gate on
-    // stdin (not stdout) because that's where prompt answers are read from.
-    let files = read_var_files(var_files)?;
-    let base = vars::collect(var_flags, &files)?;
-    let interactive = std::io::stdin().is_terminal();
-    let config = vars::resolve_config(path, &source, base, interactive, &prompter)?;
+    let mut config = load_config(var_flags, var_files)?;
+    policy::enforce(config.images())?;
+    let prompter = TerminalPrompter;
     for lint in config.lints() {
         println!("  {} {lint}", console::style("!").yellow());
     }
-    let desired = DesiredState::from_config(config);
-
     let progress = SpinnerProgress::new();
+    lock_images(client, &mut config, gates.update_images, &progress).await?;
+
+    let outputs = std::mem::take(&mut config.outputs);
+    let ready_timeouts = config.ready_timeouts();
+    let desired = DesiredState::from_config(config);
 
     // Ensures every referenced host is claimed + cert-ready. The returned list
     // is reused by apply for host→id resolution when linking/unlinking.
@@ -55,14 +78,31 @@ pub async fn run(
     let env_action = resolve_env(client, &desired.project, env_flag, &prompter, &progress).await?;
 
     // If we're creating an env, there is no current state to fetch.
-    let current = match &env_action {
+    let mut current = match &env_action {
         EnvAction::Use(env) => {
             let step = progress.step(Icon::Lookup, "Fetching current state");
             let state = fetch_current_state(client, env.id).await?;
             step.clear();
             state
         }
-        EnvAction::Create(_) => super::plan::CurrentState::empty(),
+        EnvAction::Create(_) => CurrentState::empty(),
+    };
+
+    // Declared names taken by what `up` doesn't own are settled (or refused)
+    // before anything is planned against them.
+    let collisions = collisions::find(&desired, &current);
+    let settlement = match collisions::settle(&collisions, gates.collisions, &mut current) {
+        Ok(settlement) => settlement,
+        Err(err) => {
+            if gates.collisions == Resolution::Refuse {
+                let plan = diff(&desired, &current, env_action.clone());
+                if !plan.is_empty() {
+                    println!("\nWith --adopt, up would apply:");
+                    print!("{}", render(&plan, &plan_styles()));
+                }
+            }
+            return Err(err);
+        }
     };
 
     // A referenced host bound to a service outside this env can't be linked here
@@ -70,7 +110,11 @@ pub async fn run(
     let managed_service_ids = current.services.values().map(|s| s.id).collect();
     validate_host_ownership(&desired, &hosts, &managed_service_ids)?;
 
-    let plan = diff(&desired, &current, env_action);
+    let plan = restrict(
+        diff(&desired, &current, env_action),
+        &gates.targeting,
+        &desired,
+    )?;
 
     // A network the plan removes/recreates can never drain if a standalone
     // instance is attached (up won't stop it). Fail before any mutation.
@@ -80,36 +124,321 @@ pub async fn run(
         step.clear();
     }
 
-    if plan.is_empty() {
-        // `console` strips the styling when stdout isn't a terminal, so piped
-        // runs still get a clean plain line. Padded with blank lines for room,
-        // and kept understated — only the sparkle carries colour.
+    if plan.is_empty() && settlement.notes().is_empty() {
+        // `console` strips the styling when stdout isn't a terminal, and the
+        // sparkle goes with it, so piped runs still get a clean plain line.
+        // Padded with blank lines for room, and kept understated — only the
+        // sparkle carries colour.
         println!(
-            "\n  ✨ {}\n",
+            "\n  {}{}\n",
+            emoji("✨"),
             console::style("Everything's up to date — nothing to apply.").dim()
         );
+        if let EnvAction::Use(env) = &plan.env_action {
+            report_outputs(client, &outputs, &desired.project, Some(env.id), &env.name).await;
+        }
+        return Ok(());
+    }
+
+    for note in settlement.notes() {
+        println!("  {} {note}", console::style("!").yellow());
+    }
+    if !gates.targeting.is_empty() {
+        println!(
+            "  {} only part of the plan applies; anything left out stays as it is",
+            console::style("!").yellow()
+        );
+    }
+    print!("{}", render(&plan, &plan_styles()));
+
+    let confirmed =
+        gates.yes || prompt::confirm("Apply these changes?", "pass --yes to apply without asking")?;
+    if !confirmed {
+        println!("Aborted.");
         return Ok(());
     }
 
-    let styles = if console::Term::stdout().features().colors_supported() {
+    let environment = match &plan.env_action {
+        EnvAction::Use(env) => env.name.clone(),
+        EnvAction::Create(req) => req.name.clone(),
+    };
+    let existing_env = match &plan.env_action {
+        EnvAction::Use(env) => Some(env.id),
+        EnvAction::Create(_) => None,
+    };
+    if let Some(env_id) = existing_env {
+        settlement
+            .carry_out(client, env_id, &desired.project, &progress)
+            .await?;
+    }
+
+    let waiter = RealWaiter;
+    let started = Instant::now();
+    let mut journal = Journal::default();
+    let applied = apply_recorded(plan, client, &hosts, &waiter, &progress, &mut journal);
+    let mut result = match gates.timeout {
+        Some(limit) => tokio::time::timeout(limit, applied)
+            .await
+            .unwrap_or_else(|_| {
+                Err(anyhow!(
+                    "timed out after {} applying changes",
+                    format_duration(limit)
+                ))
+            }),
+        None => applied.await,
+    };
+    if result.is_ok()
+        && let Some(env_id) = journal.env_id
+    {
+        let remaining = gates.timeout.map(|t| t.saturating_sub(started.elapsed()));
+        let rollouts = rollouts(&journal, &ready_timeouts, remaining);
+        result = match wait_ready(client, env_id, rollouts, &waiter, &progress).await {
+            Ok(not_ready) if not_ready.is_empty() => Ok(()),
+            Ok(not_ready) => Err(anyhow!(render_not_ready(&not_ready))),
+            Err(e) => Err(e),
+        };
+    }
+    if result.is_err() {
+        settle_failure(client, &journal, gates, &waiter, &progress).await;
+    }
+    if let Some(state) = StateDir::locate() {
+        record_outcome(&state, &desired, environment.clone(), &result);
+    }
+    if let (Ok(()), Some(env_id)) = (&result, existing_env) {
+        warn_unreachable_targets(client, env_id, &desired).await;
+    }
+    if result.is_ok() {
+        report_outputs(
+            client,
+            &outputs,
+            &desired.project,
+            existing_env,
+            &environment,
+        )
+        .await;
+    }
+    result
+}
+
+fn plan_styles() -> PlanStyles {
+    if colors_enabled() {
         PlanStyles::colored()
     } else {
         PlanStyles::plain()
+    }
+}
+
+/// The deployments `journal` rolled out that have a readiness deadline: their
+/// own `ready_timeout`, capped by what's `remaining` of `--timeout`, or just
+/// the latter. Neither set means `up` doesn't wait.
+fn rollouts(
+    journal: &Journal,
+    ready_timeouts: &BTreeMap<String, Duration>,
+    remaining: Option<Duration>,
+) -> Vec<Rollout> {
+    journal
+        .rollouts()
+        .into_iter()
+        .filter_map(|(id, name, replicas)| {
+            let timeout = match (ready_timeouts.get(&name).copied(), remaining) {
+                (Some(own), Some(remaining)) => own.min(remaining),
+                (own, remaining) => own.or(remaining)?,
+            };
+            Some(Rollout {
+                id,
+                name,
+                replicas,
+                timeout,
+            })
+        })
+        .collect()
+}
+
+/// After a failed apply or readiness wait: roll back with
+/// `--rollback-on-failure`, otherwise say exactly what was left in place.
+async fn settle_failure(
+    client: &dyn ApiClient,
+    journal: &Journal,
+    gates: &Gates,
+    waiter: &dyn Waiter,
+    progress: &dyn Progress,
+) {
+    if !gates.rollback_on_failure {
+        print!("\n{}", journal.render_left_in_place());
+        println!(
+            "  Rerun `unisrv up` once fixed to pick up from here, or pass \
+             --rollback-on-failure to undo a failed apply.\n"
+        );
+        return;
+    }
+    println!("\n  Rolling back…");
+    let problems = rollback(client, journal, waiter, progress).await;
+    if problems.is_empty() {
+        println!("  Rolled back every change.\n");
+        return;
+    }
+    println!("  Not rolled back:");
+    for problem in problems {
+        println!("    {} {problem}", console::style("!").yellow());
+    }
+    println!();
+}
+
+/// Resolve the file's `output` blocks against what's now live, print them and
+/// save them for `unisrv output`. `env_id` is `None` for an environment this
+/// run created, which is looked up by name. Best-effort like the reachability
+/// check: the apply already succeeded, so failures are only warned about.
+async fn report_outputs(
+    client: &dyn ApiClient,
+    outputs: &Outputs,
+    project: &str,
+    env_id: Option<Uuid>,
+    environment: &str,
+) {
+    if outputs.is_empty() {
+        return;
+    }
+    let live = async {
+        let env_id = match env_id {
+            Some(id) => id,
+            None => client
+                .list_environments()
+                .await?
+                .environments
+                .into_iter()
+                .find(|e| e.project == project && e.name == environment)
+                .map(|e| e.id)
+                .ok_or_else(|| anyhow!("environment \"{environment}\" not found"))?,
+        };
+        Live::fetch(client, env_id, environment).await
+    };
+    let live = match live.await {
+        Ok(live) => live,
+        Err(e) => {
+            println!(
+                "  {} couldn't resolve outputs: {e:#}",
+                console::style("!").yellow()
+            );
+            return;
+        }
     };
-    print!("{}", render(&plan, &styles));
 
-    let confirmed = Confirm::new()
-        .with_prompt("Apply these changes?")
-        .default(false)
-        .interact()
-        .context("failed to read confirmation")?;
-    if !confirmed {
-        println!("Aborted.");
-        return Ok(());
+    let mut values = BTreeMap::new();
+    for (name, value) in outputs.resolve(&live) {
+        match value {
+            Ok(value) => {
+                values.insert(name, value);
+            }
+            Err(reason) => println!(
+                "  {} output \"{name}\": {reason}",
+                console::style("!").yellow()
+            ),
+        }
+    }
+    if !values.is_empty() {
+        print!("{}", render_outputs(&values));
+    }
+    if let Some(state) = StateDir::locate()
+        && let Err(e) = state.save_outputs(project, environment, values)
+    {
+        tracing::warn!("failed to save outputs: {e:#}");
+    }
+}
+
+/// After a rollout into an existing environment, warn about any target of the
+/// applied services the edge can't reach — instances attached by hand, or left
+/// behind on a node the service's providers moved off. A fresh environment
+/// has only the targets `up` just placed, so it's skipped. Best-effort: the
+/// apply already succeeded, so a failed lookup is logged, never surfaced.
+async fn warn_unreachable_targets(client: &dyn ApiClient, env_id: Uuid, desired: &DesiredState) {
+    let check = async {
+        let mut warnings = Vec::new();
+        for service in client.list_services(env_id).await?.services {
+            if !desired.services.contains_key(&service.name) {
+                continue;
+            }
+            let detail = client.get_service(env_id, service.id).await?;
+            warnings.extend(unreachable_targets(client, env_id, &detail).await?);
+        }
+        anyhow::Ok(warnings)
+    };
+    match check.await {
+        Ok(warnings) => {
+            for warning in warnings {
+                println!("  {} {warning}", console::style("!").yellow());
+            }
+        }
+        Err(e) => tracing::warn!("failed to check service targets: {e:#}"),
+    }
+}
+
+/// Remember an attempted apply in local state: a history entry, the images it
+/// deployed, and on failure a report of the error chain. Best-effort — state is
+/// a convenience, so a write failure is logged, never surfaced over the result.
+fn record_outcome(
+    state: &StateDir,
+    desired: &DesiredState,
+    environment: String,
+    result: &Result<()>,
+) {
+    let deployments: BTreeMap<String, String> = desired
+        .deployments
+        .iter()
+        .map(|(name, d)| (name.clone(), d.configuration.container_image.clone()))
+        .collect();
+    let entry = HistoryEntry {
+        at: Utc::now(),
+        project: desired.project.clone(),
+        environment,
+        deployments,
+        outcome: if result.is_ok() {
+            Outcome::Applied
+        } else {
+            Outcome::Failed
+        },
+    };
+    let mut written = state.record_history(&entry);
+    match result {
+        Ok(()) => {
+            written = written.and_then(|()| {
+                state.remember_images(entry.deployments.values().map(String::as_str))
+            });
+        }
+        Err(err) => {
+            let report = format!(
+                "unisrv up failed at {}\nproject: {}\nenvironment: {}\n\n{err:?}\n",
+                entry.at.to_rfc3339(),
+                entry.project,
+                entry.environment
+            );
+            written = written.and_then(|()| state.save_failure_report("up", &report).map(drop));
+        }
+    }
+    if let Err(e) = written {
+        tracing::warn!("failed to record local state: {e:#}");
     }
+}
 
-    apply(plan, client, &hosts, &super::apply::RealWaiter, &progress).await?;
-    Ok(())
+/// Find and resolve the unisrv.hcl in the current directory with the given
+/// `--var`s and `--var-file`s.
+pub(super) fn load_config(var_flags: &[String], var_files: &[PathBuf]) -> Result<UpConfig> {
+    let cwd = std::env::current_dir().context("failed to determine the current directory")?;
+    let manifest = find_config(&cwd, CONFIG_FILE)
+        .ok_or_else(|| anyhow!("no {CONFIG_FILE} found in the current directory"))?;
+    let path = manifest.path.as_path();
+    let source = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+
+    // Gather interpolation variables, then resolve the config — prompting for
+    // any referenced-but-unset variable when stdin is a terminal and --yes
+    // wasn't given. We gate on stdin (not stdout) because that's where prompt
+    // answers are read from.
+    let files = read_var_files(var_files)?;
+    let base = vars::collect(var_flags, &files)?;
+    let interactive = cfg!(feature = "interactive")
+        && !prompt::non_interactive()
+        && std::io::stdin().is_terminal();
+    vars::resolve_config(path, &source, base, interactive, &TerminalPrompter)
 }
 
 /// Read each `--var-file` into `(label, contents)` for [`vars::collect`]. The
@@ -125,23 +454,15 @@ fn read_var_files(paths: &[PathBuf]) -> Result<Vec<(String, String)>> {
         .collect()
 }
 
-struct DialoguerPrompter;
+/// Without a terminal prompt, defaults are taken and the rest is an error.
+struct TerminalPrompter;
 
-impl Prompter for DialoguerPrompter {
-    fn prompt_string(&self, prompt: &str, default: Option<&str>) -> Result<String> {
-        let mut input = Input::<String>::new().with_prompt(prompt).allow_empty(true);
-        if let Some(d) = default {
-            input = input.default(d.to_string());
-        }
-        let value = input.interact_text()?;
-        Ok(value)
-    }
-    fn prompt_optional(&self, prompt: &str) -> Result<Option<String>> {
-        let value: String = Input::new()
-            .with_prompt(prompt)
-            .allow_empty(true)
-            .default(String::new())
-            .interact_text()?;
+impl Prompter for TerminalPrompter {
+    fn prompt_string(&self, question: &str, default: Option<&str>) -> Result<String> {
+        prompt::text(question, default, "pass it with --var or --var-file")
+    }
+    fn prompt_optional(&self, question: &str) -> Result<Option<String>> {
+        let value = prompt::text(question, Some(""), "")?;
         if value.trim().is_empty() {
             Ok(None)
         } else {
@@ -0,0 +1,355 @@
+//! `--target` / `--exclude`: apply only part of the plan.
+//!
+//! An address is `<kind>.<name>` for a block in `unisrv.hcl`, e.g.
+//! `deployment.api` or `service.web`. With targets, only their changes are
+//! applied, along with what they can't be applied without: the service or
+//! network a targeted deployment binds to when that's created this run, and
+//! the deployments a targeted recreate takes down with it. Excludes are then
+//! dropped, and dropping something a kept change needs is an error rather
+//! than a half-applied stack. Everything left out stays as it is, for the
+//! next full `up` to reconcile.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{Result, bail};
+use unisrv_api::models::ManagedKind;
+
+use super::desired::DesiredState;
+use super::plan::{DeploymentAction, Plan, RecreateReason, ResolvedServiceBinding, ResourceRef};
+
+/// A `<kind>.<name>` resource address.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Address {
+    pub kind: ManagedKind,
+    pub name: String,
+}
+
+impl Address {
+    fn new(kind: ManagedKind, name: &str) -> Self {
+        Address {
+            kind,
+            name: name.to_string(),
+        }
+    }
+}
+
+impl FromStr for Address {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, name) = s.split_once('.').unwrap_or((s, ""));
+        let kind = match kind {
+            "network" => ManagedKind::Network,
+            "service" => ManagedKind::Service,
+            "deployment" => ManagedKind::Deployment,
+            _ => {
+                return Err(format!(
+                    "invalid address {s:?}: expected network.<name>, service.<name> or deployment.<name>"
+                ));
+            }
+        };
+        if name.is_empty() {
+            return Err(format!(
+                "invalid address {s:?}: missing the name after the dot"
+            ));
+        }
+        Ok(Address::new(kind, name))
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.kind.as_str(), self.name)
+    }
+}
+
+/// The `--target` and `--exclude` addresses of one run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Targeting {
+    pub targets: Vec<Address>,
+    pub excludes: Vec<Address>,
+}
+
+impl Targeting {
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty() && self.excludes.is_empty()
+    }
+}
+
+/// Each planned change's address.
+fn planned(plan: &Plan) -> BTreeSet<Address> {
+    let services = plan
+        .service_actions
+        .iter()
+        .map(|a| Address::new(ManagedKind::Service, a.name()));
+    let deployments = plan
+        .deployment_actions
+        .iter()
+        .map(|a| Address::new(ManagedKind::Deployment, a.name()));
+    let networks = plan
+        .network_actions
+        .iter()
+        .map(|a| Address::new(ManagedKind::Network, a.name()));
+    services.chain(deployments).chain(networks).collect()
+}
+
+/// What a deployment change can't be applied without: the service and
+/// network it binds to that this plan creates or recreates.
+fn needs(action: &DeploymentAction) -> Vec<Address> {
+    let (service, network) = match action {
+        DeploymentAction::Create {
+            service, network, ..
+        }
+        | DeploymentAction::Recreate {
+            service, network, ..
+        } => (service.as_ref(), network.as_ref()),
+        DeploymentAction::Update { network, .. } => (None, network.as_ref()),
+        DeploymentAction::Delete(_) => (None, None),
+    };
+    let pending = |r: &ResourceRef| match r {
+        ResourceRef::Pending { name } => Some(name.clone()),
+        ResourceRef::Existing { .. } => None,
+    };
+    let service = service
+        .and_then(|ResolvedServiceBinding { service, .. }| pending(service))
+        .map(|name| Address::new(ManagedKind::Service, &name));
+    let network = network
+        .and_then(pending)
+        .map(|name| Address::new(ManagedKind::Network, &name));
+    service.into_iter().chain(network).collect()
+}
+
+/// The recreates that force a deployment's recreate along with them.
+fn recreated_with(action: &DeploymentAction) -> Vec<Address> {
+    let DeploymentAction::Recreate { reasons, .. } = action else {
+        return Vec::new();
+    };
+    reasons
+        .iter()
+        .filter_map(|reason| match reason {
+            RecreateReason::DependentServiceRecreated { service_name } => {
+                Some(Address::new(ManagedKind::Service, service_name))
+            }
+            RecreateReason::DependentNetworkRecreated { network_name } => {
+                Some(Address::new(ManagedKind::Network, network_name))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Narrow `plan` to `targeting`. Addresses must name a declared block or a
+/// planned change (a removal has no block left).
+pub fn restrict(mut plan: Plan, targeting: &Targeting, desired: &DesiredState) -> Result<Plan> {
+    if targeting.is_empty() {
+        return Ok(plan);
+    }
+    let planned = planned(&plan);
+    for address in targeting.targets.iter().chain(&targeting.excludes) {
+        let declared = match address.kind {
+            ManagedKind::Network => desired.networks.contains_key(&address.name),
+            ManagedKind::Service => desired.services.contains_key(&address.name),
+            ManagedKind::Deployment => desired.deployments.contains_key(&address.name),
+        };
+        if !declared && !planned.contains(address) {
+            bail!("{address} isn't declared in unisrv.hcl or present in the environment");
+        }
+    }
+
+    let mut selected: BTreeSet<Address> = if targeting.targets.is_empty() {
+        planned.clone()
+    } else {
+        targeting.targets.iter().cloned().collect()
+    };
+    // Pull in what the selection needs, and what it takes down with it, until
+    // nothing more is added.
+    loop {
+        let before = selected.len();
+        for action in &plan.deployment_actions {
+            let address = Address::new(ManagedKind::Deployment, action.name());
+            if selected.contains(&address) {
+                selected.extend(needs(action));
+            } else if recreated_with(action).iter().any(|a| selected.contains(a)) {
+                selected.insert(address);
+            }
+        }
+        if selected.len() == before {
+            break;
+        }
+    }
+    for exclude in &targeting.excludes {
+        selected.remove(exclude);
+    }
+
+    for action in &plan.deployment_actions {
+        let address = Address::new(ManagedKind::Deployment, action.name());
+        if selected.contains(&address) {
+            if let Some(missing) = needs(action).into_iter().find(|a| !selected.contains(a)) {
+                bail!("{address} can't be applied without {missing}, which is excluded");
+            }
+        } else if let Some(cause) = recreated_with(action)
+            .into_iter()
+            .find(|a| selected.contains(a))
+        {
+            bail!("recreating {cause} recreates {address} too, which is excluded");
+        }
+    }
+
+    plan.service_actions
+        .retain(|a| selected.contains(&Address::new(ManagedKind::Service, a.name())));
+    plan.deployment_actions
+        .retain(|a| selected.contains(&Address::new(ManagedKind::Deployment, a.name())));
+    plan.network_actions
+        .retain(|a| selected.contains(&Address::new(ManagedKind::Network, a.name())));
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::up::config::UpConfig;
+    use crate::commands::up::plan::{
+        CurrentDeployment, CurrentNetwork, CurrentNetworkBinding, CurrentState, EnvAction,
+        NetworkAction, ResolvedEnvironment, ServiceAction, diff,
+    };
+    use uuid::Uuid;
+
+    fn desired() -> DesiredState {
+        DesiredState::from_config(
+            UpConfig::parse(
+                r#"
+project = "demo"
+network "backend" { iprange = "10.1.0.0/24" }
+service "web" {
+  location "/" {
+    deployment = "api"
+  }
+}
+deployment "api" {
+  port = 8000
+  network = "backend"
+  container { image = "api:1" }
+}
+deployment "worker" {
+  container { image = "worker:1" }
+}
+"#,
+            )
+            .unwrap(),
+        )
+    }
+
+    fn use_env() -> EnvAction {
+        EnvAction::Use(ResolvedEnvironment {
+            id: Uuid::new_v4(),
+            name: "prod".into(),
+            project: "demo".into(),
+            slug: "ab12".into(),
+        })
+    }
+
+    fn targeting(targets: &[&str], excludes: &[&str]) -> Targeting {
+        Targeting {
+            targets: targets.iter().map(|a| a.parse().unwrap()).collect(),
+            excludes: excludes.iter().map(|a| a.parse().unwrap()).collect(),
+        }
+    }
+
+    fn addresses(plan: &Plan) -> Vec<String> {
+        planned(plan).iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn parses_addresses() {
+        let address: Address = "deployment.api".parse().unwrap();
+        assert_eq!(address, Address::new(ManagedKind::Deployment, "api"));
+        assert_eq!(address.to_string(), "deployment.api");
+        for bad in ["api", "instance.api", "service.", "host.example.com"] {
+            assert!(bad.parse::<Address>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn a_target_brings_what_it_binds_to() {
+        let desired = desired();
+        let plan = diff(&desired, &CurrentState::empty(), use_env());
+        let plan = restrict(plan, &targeting(&["deployment.api"], &[]), &desired).unwrap();
+        assert_eq!(
+            addresses(&plan),
+            ["network.backend", "service.web", "deployment.api"]
+        );
+
+        let plan = diff(&desired, &CurrentState::empty(), use_env());
+        let plan = restrict(plan, &targeting(&[], &["deployment.api"]), &desired).unwrap();
+        assert_eq!(
+            addresses(&plan),
+            ["network.backend", "service.web", "deployment.worker"]
+        );
+    }
+
+    #[test]
+    fn excluding_what_a_kept_change_needs_is_refused() {
+        let desired = desired();
+        let plan = diff(&desired, &CurrentState::empty(), use_env());
+        let err = restrict(
+            plan,
+            &targeting(&["deployment.api"], &["service.web"]),
+            &desired,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "deployment.api can't be applied without service.web, which is excluded"
+        );
+
+        let plan = diff(&desired, &CurrentState::empty(), use_env());
+        let err = restrict(plan, &targeting(&["service.nope"], &[]), &desired).unwrap_err();
+        assert!(err.to_string().contains("service.nope"), "{err:#}");
+    }
+
+    #[test]
+    fn a_targeted_recreate_takes_its_dependents_along() {
+        let desired = desired();
+        let network_id = Uuid::new_v4();
+        let mut current = CurrentState::empty();
+        current.networks.insert(
+            "backend".into(),
+            CurrentNetwork {
+                id: network_id,
+                name: "backend".into(),
+                ipv4_cidr: "10.9.0.0/24".into(),
+            },
+        );
+        current.deployments.insert(
+            "api".into(),
+            CurrentDeployment {
+                id: Uuid::new_v4(),
+                name: "api".into(),
+                configuration: desired.deployments["api"].configuration.clone(),
+                service_binding: None,
+                network_binding: Some(CurrentNetworkBinding {
+                    network_id,
+                    network_name: "backend".into(),
+                }),
+            },
+        );
+        let plan = diff(&desired, &current, use_env());
+        let plan = restrict(plan, &targeting(&["network.backend"], &[]), &desired).unwrap();
+        assert!(matches!(
+            plan.network_actions.as_slice(),
+            [NetworkAction::Recreate { .. }]
+        ));
+        // `api` is recreated with the network it joins, and takes along the
+        // service it binds to, which doesn't exist yet.
+        assert_eq!(
+            addresses(&plan),
+            ["network.backend", "service.web", "deployment.api"]
+        );
+        assert!(matches!(
+            plan.service_actions.as_slice(),
+            [ServiceAction::Create(_)]
+        ));
+    }
+}
@@ -1,8 +1,9 @@
 //! Interpolation variables supplied on the command line.
 //!
-//! Values come from `--var KEY=VALUE` flags and `--var-file` dotenv files, are
-//! always strings, and are merged into a single map. Any key set more than once
-//! across all sources is an error (there is no override precedence).
+//! Values come from `--var KEY=VALUE` flags and `--var-file` files — dotenv,
+//! or TOML when the name ends in `.toml` — are always strings, and are merged
+//! into a single map. Any key set more than once across all sources is an error
+//! (there is no override precedence).
 
 use anyhow::{Context, Result, bail};
 use std::collections::{BTreeMap, BTreeSet};
@@ -59,9 +60,10 @@ pub fn resolve_config(
     }
 }
 
-/// Merge `--var` flag assignments and dotenv file contents into a single map.
+/// Merge `--var` flag assignments and var file contents into a single map.
 ///
-/// `files` is `(label, contents)` where `label` is shown in error messages.
+/// `files` is `(label, contents)` where `label` is shown in error messages and
+/// picks the format: TOML for a `.toml` label, dotenv otherwise.
 /// Every key must be set exactly once across all sources combined — there is no
 /// override precedence, so any duplicate (within a file or across sources) is an
 /// error.
@@ -80,7 +82,12 @@ pub fn collect(flags: &[String], files: &[(String, String)]) -> Result<VarMap> {
         Ok(())
     };
     for (label, contents) in files {
-        let pairs = parse_var_file(contents).with_context(|| format!("failed to parse {label}"))?;
+        let pairs = if label.ends_with(".toml") {
+            parse_toml_var_file(contents)
+        } else {
+            parse_var_file(contents)
+        }
+        .with_context(|| format!("failed to parse {label}"))?;
         for (key, value) in pairs {
             insert(key, value, format!("--var-file {label}"))?;
         }
@@ -124,6 +131,30 @@ pub fn parse_var_file(contents: &str) -> Result<Vec<(String, String)>> {
     Ok(pairs)
 }
 
+/// Parse a TOML var file's top-level `key = value` pairs. Variables are
+/// strings, so a number, boolean or date is taken as written (`replicas = 3`
+/// sets `"3"`, which number attributes accept). Tables and arrays have no
+/// variable to land in and are rejected.
+pub fn parse_toml_var_file(contents: &str) -> Result<Vec<(String, String)>> {
+    let doc: toml_edit::DocumentMut = contents.parse()?;
+    let mut pairs = Vec::new();
+    for (key, item) in doc.iter() {
+        validate_key(key)?;
+        let value = match item.as_value() {
+            Some(toml_edit::Value::String(s)) => s.value().clone(),
+            Some(toml_edit::Value::Integer(n)) => n.value().to_string(),
+            Some(toml_edit::Value::Float(n)) => n.value().to_string(),
+            Some(toml_edit::Value::Boolean(b)) => b.value().to_string(),
+            Some(toml_edit::Value::Datetime(d)) => d.value().to_string(),
+            _ => bail!(
+                "{key:?} isn't a plain value; var files hold only strings, numbers and booleans"
+            ),
+        };
+        pairs.push((key.to_string(), value));
+    }
+    Ok(pairs)
+}
+
 /// A variable name is referenced as `var.<key>`, so it must be a valid
 /// identifier: a leading letter or underscore followed by letters, digits, or
 /// underscores.
@@ -372,6 +403,35 @@ api_url = https://api.example.com
         );
     }
 
+    #[test]
+    fn parse_toml_var_file_reads_plain_values_as_strings() {
+        let contents = r#"
+# prod
+image_tag = "v1.2.3"
+replicas = 3
+public = true
+"#;
+        assert_eq!(
+            parse_toml_var_file(contents).unwrap(),
+            vec![
+                ("image_tag".to_string(), "v1.2.3".to_string()),
+                ("replicas".to_string(), "3".to_string()),
+                ("public".to_string(), "true".to_string()),
+            ]
+        );
+        let err = parse_toml_var_file("[hosts]\nweb = \"x\"\n").unwrap_err();
+        assert!(format!("{err:#}").contains("plain value"), "{err:#}");
+    }
+
+    #[test]
+    fn collect_reads_toml_files_by_extension() {
+        let files = [(
+            "prod.toml".to_string(),
+            "host = \"app.example.com\"\n".to_string(),
+        )];
+        assert_eq!(collect(&[], &files).unwrap()["host"], "app.example.com");
+    }
+
     #[test]
     fn parse_assignment_rejects_invalid_key() {
         // Keys must be valid identifiers: they become `var.<key>` references.
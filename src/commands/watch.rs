@@ -0,0 +1,146 @@
+//! `--watch [INTERVAL]` on `instance list`, `service show`, `network show` and
+//! `service stats`: redraw the view every interval until interrupted.
+//!
+//! The API has no change feed for these views, so watching polls. Lines that
+//! weren't in the previous draw are highlighted, so a row that changed stands
+//! out. A transient API failure keeps the last view and retries on the next
+//! tick instead of ending the watch. On a terminal each draw replaces the
+//! last; piped, draws follow one another as a log.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::Result;
+use console::style;
+use unisrv_api::ApiError;
+
+use crate::commands::interrupt::until_interrupted;
+use crate::commands::ui::colors_enabled;
+use crate::commands::up::ready::format_duration;
+
+/// What a bare `--watch` refreshes every.
+pub const DEFAULT_INTERVAL: &str = "2s";
+
+/// Redraw `draw`'s view every `interval` until Ctrl-C. Fails if the first
+/// draw does, or a later one for a reason retrying won't fix.
+pub async fn watch(interval: Duration, draw: impl AsyncFnMut() -> Result<String>) -> Result<()> {
+    until_interrupted(redraw(interval, draw)).await
+}
+
+async fn redraw(interval: Duration, mut draw: impl AsyncFnMut() -> Result<String>) -> Result<()> {
+    let term = console::Term::stdout();
+    let mut last: Option<String> = None;
+    loop {
+        let first = last.is_none();
+        let (view, note) = match draw().await {
+            Ok(view) => {
+                let shown = highlight(last.as_deref(), &view, colors_enabled());
+                last = Some(view);
+                let note = format!(
+                    "refreshing every {} — Ctrl-C to stop",
+                    format_duration(interval)
+                );
+                (shown, note)
+            }
+            Err(e) if is_transient(&e) && last.is_some() => (
+                last.clone().unwrap_or_default(),
+                format!("refresh failed ({e}); retrying"),
+            ),
+            Err(e) => return Err(e),
+        };
+        if term.is_term() {
+            term.clear_screen()?;
+        } else if !first {
+            println!();
+        }
+        print!("{view}");
+        println!("{}", style(note).dim());
+        tokio::time::sleep(interval).await;
+    }
+}
+
+fn is_transient(err: &anyhow::Error) -> bool {
+    err.chain()
+        .find_map(|cause| cause.downcast_ref::<ApiError>())
+        .is_some_and(ApiError::is_transient)
+}
+
+/// `view` with the lines that weren't in `previous` shown in reverse video,
+/// when `color` allows. Nothing is highlighted on the first draw.
+fn highlight(previous: Option<&str>, view: &str, color: bool) -> String {
+    let Some(previous) = previous.filter(|_| color) else {
+        return view.to_string();
+    };
+    changed_lines(previous, view)
+        .into_iter()
+        .map(|(line, changed)| {
+            if changed {
+                format!("{}\n", style(line).reverse().force_styling(true))
+            } else {
+                format!("{line}\n")
+            }
+        })
+        .collect()
+}
+
+/// Each line of `view`, and whether it's new since `previous`. A line counts
+/// as unchanged as often as it appeared before, so a repeated row that's
+/// gained a twin still shows as new.
+fn changed_lines<'a>(previous: &str, view: &'a str) -> Vec<(&'a str, bool)> {
+    let mut before: HashMap<&str, usize> = HashMap::new();
+    for line in previous.lines() {
+        *before.entry(line).or_default() += 1;
+    }
+    view.lines()
+        .map(|line| match before.get_mut(line) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                (line, false)
+            }
+            _ => (line, true),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_lines_new_since_the_last_draw_are_marked() {
+        let before = "web  running\napi  provisioning\n";
+        let after = "web  running\napi  running\napi  running\n";
+        let marked: Vec<bool> = changed_lines(before, after)
+            .into_iter()
+            .map(|(_, changed)| changed)
+            .collect();
+        assert_eq!(marked, [false, true, true]);
+
+        assert_eq!(highlight(None, after, true), after);
+        assert_eq!(highlight(Some(before), after, false), after);
+        let shown = highlight(Some(before), after, true);
+        assert!(shown.starts_with("web  running\n"), "{shown:?}");
+        assert!(shown.contains("\u{1b}[7mapi  running"), "{shown:?}");
+    }
+
+    #[tokio::test]
+    async fn a_transient_failure_keeps_the_watch_going_but_others_end_it() {
+        let mut draws = 0;
+        let err = redraw(Duration::ZERO, async || {
+            draws += 1;
+            match draws {
+                1 => Ok("web  running\n".to_string()),
+                2 => Err(ApiError::Server {
+                    status: 502,
+                    reason: "bad gateway".into(),
+                }
+                .into()),
+                _ => Err(ApiError::AuthRequired("log in".into()).into()),
+            }
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(draws, 3);
+        assert!(err.to_string().contains("log in"), "{err}");
+    }
+}
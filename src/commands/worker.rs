@@ -0,0 +1,287 @@
+//! `unisrv worker run <module.wasm> [-- args...]` — run a WebAssembly module
+//! on the platform's lightweight isolate runtime instead of in a VM.
+//!
+//! A worker has no image, network or vCPUs: it's a core module (not a
+//! component), a memory limit (`--memory`) and a CPU-time budget
+//! (`--cpu-time-ms`). It's still an instance once started, so `instance
+//! list`, `show` and `logs` work on it; `instance run` and its VM flags are
+//! untouched by any of this.
+//!
+//! Isolates start in milliseconds and often finish before a log stream could
+//! connect, so `--follow` doesn't wait and resubscribe as `instance logs
+//! --follow` does for a booting VM: it attaches straight away, and when the
+//! stream ends, breaks or is refused, prints whatever the history has that
+//! the stream didn't. Only a flaky upgrade is retried, briefly.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use base64::Engine;
+use futures_util::StreamExt;
+use unisrv_api::ApiClient;
+use unisrv_api::models::{LogMessage, WorkerProvisionRequest};
+use uuid::Uuid;
+
+use crate::commands::instance::logs::{Resubscribe, connect, emit, route};
+use crate::commands::interrupt::until_interrupted;
+use crate::commands::up::plan::ResolvedEnvironment;
+
+/// The largest module the runtime accepts.
+const MAX_MODULE_BYTES: u64 = 32 * 1024 * 1024;
+pub const DEFAULT_MEMORY_MB: u32 = 128;
+const MIN_MEMORY_MB: u32 = 16;
+const MAX_MEMORY_MB: u32 = 512;
+pub const DEFAULT_CPU_TIME_MS: u32 = 30_000;
+const MAX_CPU_TIME_MS: u32 = 15 * 60 * 1000;
+
+/// `\0asm`, then the binary format version: 1 for a core module, while
+/// components carry their own layer and version.
+const WASM_MAGIC: [u8; 4] = *b"\0asm";
+const CORE_MODULE_VERSION: [u8; 4] = [1, 0, 0, 0];
+
+/// What to run, as given on the command line.
+pub struct WorkerOptions {
+    pub module: PathBuf,
+    pub name: Option<String>,
+    pub env_vars: BTreeMap<String, String>,
+    pub args: Vec<String>,
+    pub memory_mb: u32,
+    pub cpu_time_ms: u32,
+    /// From `--region`, or the configured default.
+    pub region: String,
+    /// Print the worker's logs until it finishes.
+    pub follow: bool,
+}
+
+pub async fn run(
+    client: &dyn ApiClient,
+    env: &ResolvedEnvironment,
+    opts: WorkerOptions,
+) -> Result<()> {
+    let request = build_request(&opts, &read_module(&opts.module)?)?;
+    let module = opts.module.display();
+    let created = client
+        .provision_worker(env.id, request)
+        .await
+        .with_context(|| format!("failed to start {module}"))?;
+    println!("\u{2713} Started worker {module} ({}).", created.id);
+    if !opts.follow {
+        return Ok(());
+    }
+    until_interrupted(follow(client, env.id, created.id, &mut |msg| {
+        emit(route(msg))
+    }))
+    .await
+}
+
+/// The module's bytes, once they're known to be a core WebAssembly module the
+/// runtime will take.
+fn read_module(path: &Path) -> Result<Vec<u8>> {
+    let size = std::fs::metadata(path)
+        .with_context(|| format!("failed to read {}", path.display()))?
+        .len();
+    if size > MAX_MODULE_BYTES {
+        bail!(
+            "{} is {}; workers are limited to {}",
+            path.display(),
+            crate::commands::ui::human_bytes(size),
+            crate::commands::ui::human_bytes(MAX_MODULE_BYTES)
+        );
+    }
+    let bytes =
+        std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    check_module(&bytes).with_context(|| format!("can't run {}", path.display()))?;
+    Ok(bytes)
+}
+
+fn check_module(bytes: &[u8]) -> Result<()> {
+    if bytes.len() < 8 || bytes[..4] != WASM_MAGIC {
+        bail!("not a WebAssembly module");
+    }
+    if bytes[4..8] != CORE_MODULE_VERSION {
+        bail!(
+            "it's a WebAssembly component or an unsupported version; the worker runtime runs core modules"
+        );
+    }
+    Ok(())
+}
+
+fn build_request(opts: &WorkerOptions, module: &[u8]) -> Result<WorkerProvisionRequest> {
+    if !(MIN_MEMORY_MB..=MAX_MEMORY_MB).contains(&opts.memory_mb) {
+        bail!(
+            "--memory must be between {MIN_MEMORY_MB} and {MAX_MEMORY_MB}MB for a worker, got {}MB",
+            opts.memory_mb
+        );
+    }
+    if opts.cpu_time_ms == 0 || opts.cpu_time_ms > MAX_CPU_TIME_MS {
+        bail!(
+            "--cpu-time-ms must be between 1 and {MAX_CPU_TIME_MS}, got {}",
+            opts.cpu_time_ms
+        );
+    }
+    Ok(WorkerProvisionRequest {
+        name: opts.name.clone(),
+        region: opts.region.clone(),
+        module: base64::engine::general_purpose::STANDARD.encode(module),
+        memory_mb: opts.memory_mb,
+        cpu_time_ms: opts.cpu_time_ms,
+        args: (!opts.args.is_empty()).then(|| opts.args.clone()),
+        env: (!opts.env_vars.is_empty()).then(|| opts.env_vars.clone()),
+    })
+}
+
+/// Print the worker's frames as they arrive, then the rest of its history:
+/// what it logged before the stream attached, or after it broke. A stream
+/// replays history from the start, so the frames it printed are the
+/// history's first ones.
+async fn follow(
+    client: &dyn ApiClient,
+    env_id: Uuid,
+    instance_id: Uuid,
+    print: &mut (dyn FnMut(&LogMessage) + Send),
+) -> Result<()> {
+    let mut printed = 0;
+    match connect(client, env_id, instance_id, &Resubscribe::ALONGSIDE).await {
+        Ok(mut stream) => {
+            while let Some(frame) = stream.next().await {
+                match frame {
+                    Ok(frame) => {
+                        print(&frame);
+                        printed += 1;
+                    }
+                    Err(e) => {
+                        tracing::debug!("worker log stream broke ({e}); reading its history");
+                        break;
+                    }
+                }
+            }
+        }
+        Err(e) if e.is_transient() => eprintln!(
+            "{}",
+            console::style(format!(
+                "! live logs unavailable ({e}); reading the history"
+            ))
+            .yellow()
+        ),
+        Err(e) => tracing::debug!("worker log stream refused ({e}); reading its history"),
+    }
+    let history = client
+        .get_instance_logs(env_id, instance_id)
+        .await
+        .context("failed to read the worker's logs")?;
+    for msg in history.iter().skip(printed) {
+        print(msg);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use unisrv_api::ApiError;
+    use unisrv_api::test_support::MockApiClient;
+
+    const MODULE: &[u8] = b"\0asm\x01\0\0\0";
+
+    fn options() -> WorkerOptions {
+        WorkerOptions {
+            module: PathBuf::from("hello.wasm"),
+            name: Some("hello".into()),
+            env_vars: BTreeMap::new(),
+            args: vec!["--greet".into()],
+            memory_mb: DEFAULT_MEMORY_MB,
+            cpu_time_ms: DEFAULT_CPU_TIME_MS,
+            region: "dev".into(),
+            follow: false,
+        }
+    }
+
+    fn frame(timestamp_ms: u64, message: &str) -> LogMessage {
+        LogMessage {
+            timestamp_ms,
+            log_type: "stdout".into(),
+            message: Some(message.into()),
+            state: None,
+        }
+    }
+
+    #[test]
+    fn only_core_modules_are_accepted() {
+        assert!(check_module(MODULE).is_ok());
+        let err = check_module(b"\x7fELF\x02\x01\x01\0").unwrap_err();
+        assert_eq!(err.to_string(), "not a WebAssembly module");
+        let component = b"\0asm\x0d\0\x01\0";
+        assert!(
+            check_module(component)
+                .unwrap_err()
+                .to_string()
+                .contains("component")
+        );
+    }
+
+    #[test]
+    fn requests_carry_the_module_and_its_limits() {
+        let req = build_request(&options(), MODULE).unwrap();
+        assert_eq!(req.module, "AGFzbQEAAAA=");
+        assert_eq!((req.memory_mb, req.cpu_time_ms), (128, 30_000));
+        assert_eq!(req.args, Some(vec!["--greet".to_string()]));
+        assert_eq!(req.env, None);
+
+        let too_big = WorkerOptions {
+            memory_mb: 1024,
+            ..options()
+        };
+        let err = build_request(&too_big, MODULE).unwrap_err();
+        assert!(err.to_string().contains("between 16 and 512MB"), "{err:#}");
+    }
+
+    #[tokio::test]
+    async fn follow_backfills_what_the_stream_missed() {
+        let (env_id, id) = (Uuid::new_v4(), Uuid::new_v4());
+        let history = vec![frame(1, "one"), frame(2, "two"), frame(3, "three")];
+
+        // The stream broke after the first frame.
+        let mock = MockApiClient::logged_in()
+            .push_stream_logs_frames(vec![
+                Ok(frame(1, "one")),
+                Err(ApiError::Other(anyhow::anyhow!("connection reset"))),
+            ])
+            .push_instance_logs(Ok(history.clone()));
+        let mut seen = Vec::new();
+        follow(&mock, env_id, id, &mut |msg| seen.push(msg.message.clone()))
+            .await
+            .unwrap();
+        assert_eq!(seen, ["one", "two", "three"].map(|s| Some(s.to_string())));
+
+        // The worker finished before the stream could attach.
+        let mock = MockApiClient::logged_in()
+            .push_stream_connect_error(ApiError::Server {
+                status: 404,
+                reason: "instance not found".into(),
+            })
+            .push_instance_logs(Ok(history));
+        let mut seen = 0;
+        follow(&mock, env_id, id, &mut |_| seen += 1).await.unwrap();
+        assert_eq!(seen, 3);
+    }
+
+    #[tokio::test]
+    async fn follow_retries_a_flaky_upgrade_before_falling_back() {
+        let (env_id, id) = (Uuid::new_v4(), Uuid::new_v4());
+        let mock = MockApiClient::logged_in()
+            .push_stream_connect_error(ApiError::Server {
+                status: 502,
+                reason: "bad gateway".into(),
+            })
+            .push_stream_logs(vec![frame(1, "one")])
+            .push_instance_logs(Ok(vec![frame(1, "one"), frame(2, "two")]));
+        let mut seen = 0;
+        follow(&mock, env_id, id, &mut |_| seen += 1).await.unwrap();
+        assert_eq!(seen, 2);
+        assert_eq!(
+            mock.calls.lock().unwrap().stream_instance_logs_calls.len(),
+            2
+        );
+    }
+}
@@ -0,0 +1,278 @@
+//! Default environment variables, set with `unisrv env set-default` and kept
+//! in `~/.unisrv/default_env.json`:
+//!
+//! ```json
+//! {
+//!   "accounts": {
+//!     "alice@https://api.unisrv.io": {
+//!       "vars": { "LOG_FORMAT": "json" },
+//!       "projects": { "shop": { "OTEL_EXPORTER_OTLP_ENDPOINT": "https://otel.acme.com" } }
+//!     }
+//!   }
+//! }
+//! ```
+//!
+//! Defaults belong to the account in use (`username@api-host`), across its
+//! projects or in one of them, and are merged into every `instance run` and
+//! `rollout deploy` under it unless `--no-default-env` is passed: the
+//! account's, then the project's over them, then the command's own over both.
+//!
+//! The CLI writes this file, but a default can carry a setting every instance
+//! relies on, so one that doesn't parse is an error rather than read as empty.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use unisrv_api::{Account, AuthStore};
+
+/// One account's defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccountDefaults {
+    /// For every project.
+    pub vars: BTreeMap<String, String>,
+    /// For one project, by name, over `vars`.
+    pub projects: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl AccountDefaults {
+    /// The defaults that apply in `project`.
+    pub fn for_project(&self, project: &str) -> BTreeMap<String, String> {
+        let mut vars = self.vars.clone();
+        if let Some(overrides) = self.projects.get(project) {
+            vars.extend(overrides.clone());
+        }
+        vars
+    }
+
+    /// `own` over the defaults that apply in `project`, or `None` when that
+    /// leaves nothing to set.
+    pub fn under(
+        &self,
+        project: &str,
+        own: Option<&BTreeMap<String, String>>,
+    ) -> Option<BTreeMap<String, String>> {
+        let mut vars = self.for_project(project);
+        vars.extend(
+            own.into_iter()
+                .flatten()
+                .map(|(k, v)| (k.clone(), v.clone())),
+        );
+        (!vars.is_empty()).then_some(vars)
+    }
+
+    /// The variables set at `project`'s scope, or the account's without one.
+    fn scope(&mut self, project: Option<&str>) -> &mut BTreeMap<String, String> {
+        match project {
+            Some(project) => self.projects.entry(project.to_string()).or_default(),
+            None => &mut self.vars,
+        }
+    }
+}
+
+/// How an account's defaults are keyed: `username@api-host`.
+pub fn account_key(account: &Account) -> String {
+    format!("{}@{}", account.username, account.api_host)
+}
+
+/// The account in use on `api_host`, as [`account_key`] keys it.
+pub fn active_account(api_host: &str) -> Result<String> {
+    AuthStore::new(api_host)
+        .accounts()
+        .active(api_host)
+        .map(account_key)
+        .ok_or_else(|| anyhow!("not logged in; default environment variables belong to an account, log in with `unisrv login`"))
+}
+
+/// The defaults of the account in use on `api_host`: none when not logged in
+/// or there's nowhere to keep them.
+pub fn for_active_account(api_host: &str) -> Result<AccountDefaults> {
+    let (Some(path), Ok(account)) = (DefaultEnvStore::default_path(), active_account(api_host))
+    else {
+        return Ok(AccountDefaults::default());
+    };
+    DefaultEnvStore::new(path).account(&account)
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Doc {
+    #[serde(default)]
+    accounts: BTreeMap<String, AccountDefaults>,
+}
+
+/// The defaults file at a fixed path.
+pub struct DefaultEnvStore {
+    path: PathBuf,
+}
+
+impl DefaultEnvStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// The default location, `~/.unisrv/default_env.json`. `None` if the home
+    /// directory can't be determined.
+    pub fn default_path() -> Option<PathBuf> {
+        Some(unisrv_api::config_dir()?.join("default_env.json"))
+    }
+
+    /// `account`'s defaults; none if it has none or there's no file.
+    pub fn account(&self, account: &str) -> Result<AccountDefaults> {
+        Ok(self.load()?.accounts.remove(account).unwrap_or_default())
+    }
+
+    /// Set `vars` for `account`, in `project` or across its projects.
+    pub fn set(
+        &self,
+        account: &str,
+        project: Option<&str>,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<()> {
+        let mut doc = self.load()?;
+        let defaults = doc.accounts.entry(account.to_string()).or_default();
+        defaults.scope(project).extend(vars);
+        self.save(&doc)
+    }
+
+    /// Remove `keys` from `account`'s defaults at that scope, returning those
+    /// that weren't set.
+    pub fn unset(
+        &self,
+        account: &str,
+        project: Option<&str>,
+        keys: &[String],
+    ) -> Result<Vec<String>> {
+        let mut doc = self.load()?;
+        let defaults = doc.accounts.entry(account.to_string()).or_default();
+        let scope = defaults.scope(project);
+        let missing = keys
+            .iter()
+            .filter(|key| scope.remove(key.as_str()).is_none())
+            .cloned()
+            .collect();
+        if let Some(project) = project
+            && defaults
+                .projects
+                .get(project)
+                .is_some_and(BTreeMap::is_empty)
+        {
+            defaults.projects.remove(project);
+        }
+        if *defaults == AccountDefaults::default() {
+            doc.accounts.remove(account);
+        }
+        self.save(&doc)?;
+        Ok(missing)
+    }
+
+    fn load(&self) -> Result<Doc> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(json) => serde_json::from_str(&json)
+                .with_context(|| format!("invalid default environment in {}", self.path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Doc::default()),
+            Err(e) => Err(e).with_context(|| format!("failed to read {}", self.path.display())),
+        }
+    }
+
+    fn save(&self, doc: &Doc) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        let json = serde_json::to_string_pretty(doc)?;
+        std::fs::write(&self.path, json)
+            .with_context(|| format!("failed to write {}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store_at(tmp: &tempfile::TempDir) -> DefaultEnvStore {
+        DefaultEnvStore::new(tmp.path().join("default_env.json"))
+    }
+
+    fn var(key: &str, value: &str) -> (String, String) {
+        (key.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn project_defaults_override_the_accounts() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = store_at(&tmp);
+        store
+            .set(
+                "alice@api",
+                None,
+                [var("LOG_FORMAT", "json"), var("TZ", "UTC")],
+            )
+            .unwrap();
+        store
+            .set("alice@api", Some("shop"), [var("LOG_FORMAT", "logfmt")])
+            .unwrap();
+
+        let defaults = store.account("alice@api").unwrap();
+        assert_eq!(
+            defaults.for_project("shop"),
+            BTreeMap::from([var("LOG_FORMAT", "logfmt"), var("TZ", "UTC")])
+        );
+        assert_eq!(defaults.for_project("blog")["LOG_FORMAT"], "json");
+        assert_eq!(
+            store.account("bob@api").unwrap(),
+            AccountDefaults::default()
+        );
+    }
+
+    #[test]
+    fn unsetting_the_last_default_drops_the_scope() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = store_at(&tmp);
+        store
+            .set("alice@api", Some("shop"), [var("TZ", "UTC")])
+            .unwrap();
+
+        let missing = store
+            .unset("alice@api", Some("shop"), &["TZ".into(), "NOPE".into()])
+            .unwrap();
+
+        assert_eq!(missing, ["NOPE"]);
+        assert_eq!(
+            store.account("alice@api").unwrap(),
+            AccountDefaults::default()
+        );
+        let written = std::fs::read_to_string(&store.path).unwrap();
+        assert!(!written.contains("alice"), "{written}");
+    }
+
+    #[test]
+    fn a_commands_own_variables_win_over_defaults() {
+        let defaults = AccountDefaults {
+            vars: BTreeMap::from([var("LOG_FORMAT", "json"), var("TZ", "UTC")]),
+            projects: BTreeMap::new(),
+        };
+        let own = BTreeMap::from([var("TZ", "Europe/Oslo")]);
+        assert_eq!(
+            defaults.under("shop", Some(&own)),
+            Some(BTreeMap::from([
+                var("LOG_FORMAT", "json"),
+                var("TZ", "Europe/Oslo")
+            ]))
+        );
+        assert_eq!(AccountDefaults::default().under("shop", None), None);
+    }
+
+    #[test]
+    fn a_corrupt_file_is_an_error() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = store_at(&tmp);
+        std::fs::write(&store.path, "{ not json").unwrap();
+        let err = store.account("alice@api").unwrap_err();
+        assert!(
+            format!("{err:#}").contains("invalid default environment"),
+            "{err:#}"
+        );
+    }
+}
@@ -0,0 +1,236 @@
+//! How a failed command ends: what's printed on stderr, and the exit status.
+//!
+//! The status says what kind of failure it was, so a script can tell "log in
+//! again" from "it isn't there" without reading the message:
+//!
+//! | status | failure |
+//! |--------|---------|
+//! | 1 | anything else |
+//! | 2 | not logged in, or not allowed (401, 403) |
+//! | 3 | not found (404) |
+//! | 4 | conflict (409), e.g. a name that's taken |
+//! | 5 | the server failed (5xx) |
+//! | 64 | the command line didn't parse |
+//!
+//! clap exits with 2 for a usage error, which would read as an auth failure,
+//! so `main` exits with 64 (`EX_USAGE`) instead.
+//!
+//! With `--error-format json` the message is one JSON object on stderr —
+//! `code`, `reason`, `operation`, `http_status` and, with `--trace`,
+//! `trace_id` — so a CI step can parse it without scraping text.
+
+use std::str::FromStr;
+
+use serde::Serialize;
+use unisrv_api::ApiError;
+
+use crate::commands::up::parse_error::ConfigParseError;
+
+/// The exit status for a command line that didn't parse.
+pub const USAGE_EXIT_CODE: i32 = 64;
+
+/// `--error-format`: how a failure is printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ErrorFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "text" => Ok(ErrorFormat::Text),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(format!("unknown error format {s:?}: expected text or json")),
+        }
+    }
+}
+
+/// What kind of failure an error is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Class {
+    Other,
+    Auth,
+    NotFound,
+    Conflict,
+    Server,
+}
+
+impl Class {
+    /// Classified by the API error behind `err`, wherever it is in the
+    /// chain; anything that didn't come from the API is [`Class::Other`].
+    pub fn of(err: &anyhow::Error) -> Self {
+        match api_error(err) {
+            Some(ApiError::AuthRequired(_)) => Class::Auth,
+            Some(ApiError::Server { status, .. }) => match status {
+                401 | 403 => Class::Auth,
+                404 => Class::NotFound,
+                409 => Class::Conflict,
+                500.. => Class::Server,
+                _ => Class::Other,
+            },
+            _ => Class::Other,
+        }
+    }
+
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Class::Other => 1,
+            Class::Auth => 2,
+            Class::NotFound => 3,
+            Class::Conflict => 4,
+            Class::Server => 5,
+        }
+    }
+
+    /// The `code` of the JSON report.
+    fn code(self) -> &'static str {
+        match self {
+            Class::Other => "error",
+            Class::Auth => "auth",
+            Class::NotFound => "not_found",
+            Class::Conflict => "conflict",
+            Class::Server => "server",
+        }
+    }
+}
+
+/// The `--error-format json` object.
+#[derive(Debug, PartialEq, Serialize)]
+struct JsonReport {
+    code: &'static str,
+    reason: String,
+    /// The command that failed, e.g. `instance stop`.
+    operation: String,
+    http_status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trace_id: Option<String>,
+}
+
+/// Print `err` from the command `operation` in `format`, and return the
+/// status to exit with.
+pub fn report(
+    err: &anyhow::Error,
+    format: ErrorFormat,
+    operation: &str,
+    trace_id: Option<&str>,
+) -> i32 {
+    let class = Class::of(err);
+    match format {
+        ErrorFormat::Text => {
+            print_text(err);
+            if let Some(trace_id) = trace_id {
+                eprintln!("Trace ID: {trace_id}");
+            }
+        }
+        ErrorFormat::Json => {
+            let report = json_report(err, class, operation, trace_id);
+            match serde_json::to_string(&report) {
+                Ok(json) => eprintln!("{json}"),
+                Err(_) => print_text(err),
+            }
+        }
+    }
+    class.exit_code()
+}
+
+fn json_report(
+    err: &anyhow::Error,
+    class: Class,
+    operation: &str,
+    trace_id: Option<&str>,
+) -> JsonReport {
+    let (reason, http_status) = match api_error(err) {
+        Some(ApiError::Server { status, reason }) => (reason.clone(), Some(*status)),
+        _ => (format!("{err:#}"), None),
+    };
+    JsonReport {
+        code: class.code(),
+        reason,
+        operation: operation.to_string(),
+        http_status,
+        trace_id: trace_id.map(String::from),
+    }
+}
+
+/// The message for `err` as a person reads it.
+pub fn print_text(err: &anyhow::Error) {
+    if let Some(parse_err) = err.downcast_ref::<ConfigParseError>() {
+        eprint!("{parse_err}");
+    } else if let Some(ApiError::AuthRequired(msg)) = err.downcast_ref::<ApiError>() {
+        eprintln!("Error: {msg}");
+    } else if let Some(ApiError::Server { status, reason }) = err.downcast_ref::<ApiError>() {
+        eprintln!("Error ({status}): {reason}");
+    } else if let Some(ApiError::ReadOnly { .. }) = err.downcast_ref::<ApiError>() {
+        eprintln!("Error: {err:#}");
+        eprintln!(
+            "{}",
+            console::style(
+                "Read-only mode is on (--read-only, or read_only in ~/.unisrv/config.json)."
+            )
+            .dim()
+        );
+    } else {
+        eprintln!("Error: {err:#}");
+    }
+}
+
+fn api_error(err: &anyhow::Error) -> Option<&ApiError> {
+    err.chain()
+        .find_map(|cause| cause.downcast_ref::<ApiError>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    fn server(status: u16) -> anyhow::Error {
+        anyhow::Error::new(ApiError::Server {
+            status,
+            reason: "no such instance".into(),
+        })
+    }
+
+    #[test]
+    fn api_failures_map_to_their_exit_codes_through_context() {
+        let wrapped = Err::<(), _>(server(404))
+            .context("failed to stop api-1")
+            .unwrap_err();
+        assert_eq!(Class::of(&wrapped).exit_code(), 3);
+        assert_eq!(Class::of(&server(403)).exit_code(), 2);
+        assert_eq!(
+            Class::of(&anyhow::Error::new(ApiError::not_logged_in())).exit_code(),
+            2
+        );
+        assert_eq!(Class::of(&server(409)).exit_code(), 4);
+        assert_eq!(Class::of(&server(503)).exit_code(), 5);
+        assert_eq!(Class::of(&server(422)).exit_code(), 1);
+        assert_eq!(Class::of(&anyhow::anyhow!("no unisrv.hcl")).exit_code(), 1);
+    }
+
+    #[test]
+    fn json_report_carries_the_status_and_the_servers_reason() {
+        let err = Err::<(), _>(server(404))
+            .context("failed to stop api-1")
+            .unwrap_err();
+        let report = json_report(&err, Class::of(&err), "instance stop", None);
+        assert_eq!(
+            serde_json::to_value(&report).unwrap(),
+            serde_json::json!({
+                "code": "not_found",
+                "reason": "no such instance",
+                "operation": "instance stop",
+                "http_status": 404,
+            })
+        );
+
+        let err = anyhow::anyhow!("no unisrv.hcl found");
+        let report = json_report(&err, Class::of(&err), "up", Some("abc"));
+        assert_eq!((report.code, report.http_status), ("error", None));
+        assert_eq!(report.trace_id.as_deref(), Some("abc"));
+    }
+}
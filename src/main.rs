@@ -1,27 +1,115 @@
 mod commands;
 mod config_locate;
+mod default_env;
+mod failure;
 mod preferences;
 mod progress;
+mod settings;
+mod state;
+mod telemetry;
 
 use std::path::PathBuf;
 
-use clap::{Parser, Subcommand};
-use commands::up::parse_error::ConfigParseError;
-use unisrv_api::{ApiClient, ApiError, HttpApiClient};
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use commands::config::Effective;
+use commands::locale::{self, Locale};
+use commands::output::Output;
+use unisrv_api::models::{LogSearch, PolicyAction, TlsVersion};
+use unisrv_api::session::Tape;
+use unisrv_api::{ApiClient, ApiVersion, HttpApiClient, TraceContext};
 
 #[derive(Parser)]
 #[command(
     name = "unisrv",
-    about = "Declarative infrastructure deployments on Unisrv"
+    about = "Declarative infrastructure deployments on Unisrv",
+    after_help = "Exit status: 0 success, 1 failure, 2 not logged in or not allowed, \
+                  3 not found, 4 conflict, 5 server error, 64 usage error."
 )]
 struct Cli {
+    /// Record every API request and response to FILE (credentials redacted),
+    /// e.g. to attach to a bug report
+    #[arg(long, value_name = "FILE", global = true, conflicts_with = "replay")]
+    record: Option<PathBuf>,
+    /// Answer API calls from a session recorded with --record instead of the
+    /// network
+    #[arg(long, value_name = "FILE", global = true)]
+    replay: Option<PathBuf>,
+    /// Send W3C trace context with every API request and print the trace id
+    /// on errors. Implied when TRACEPARENT is set, whose trace is joined
+    #[arg(long, global = true)]
+    trace: bool,
+    /// Refuse to send any request that would change something. Also set with
+    /// "read_only": true in ~/.unisrv/config.json
+    #[arg(long, global = true)]
+    read_only: bool,
+    /// Ask the server for this API version instead of the newest this CLI
+    /// speaks, e.g. 1.2 against a server that hasn't caught up
+    #[arg(long, value_name = "VERSION", global = true, value_parser = parse_api_version)]
+    api_version: Option<ApiVersion>,
+    /// Print progress as plain "step N: …" status lines instead of animated
+    /// spinners, for screen readers and CI logs. Also set with
+    /// "no_spinner": true in ~/.unisrv/config.json
+    #[arg(long, global = true)]
+    no_spinner: bool,
+    /// Leave output unstyled — no colour, spinners or emoji — even on a
+    /// terminal. Also set with NO_COLOR; piped output is always plain
+    #[arg(long, global = true)]
+    no_color: bool,
+    /// Format dates and numbers in tables for this locale, e.g. en-GB or de.
+    /// Also set with "locale" in ~/.unisrv/config.json; defaults to the
+    /// system locale
+    #[arg(long, value_name = "LOCALE", global = true)]
+    locale: Option<Locale>,
+    /// Never wait for input: confirmations are answered yes, and anything
+    /// that has to be typed fails at once. For CI; also set with
+    /// UNISRV_NONINTERACTIVE=1
+    #[arg(short = 'y', long, visible_alias = "non-interactive", global = true)]
+    yes: bool,
+    /// Print a failure as text, or as one JSON object on stderr with its
+    /// code, reason, operation and HTTP status
+    #[arg(long, value_name = "FORMAT", default_value = "text", global = true)]
+    error_format: failure::ErrorFormat,
     #[command(subcommand)]
     command: Commands,
 }
 
+fn parse_api_version(s: &str) -> Result<ApiVersion, String> {
+    let version: ApiVersion = s.parse()?;
+    if !version.is_supported() {
+        return Err(format!(
+            "this CLI speaks API versions {} to {}",
+            ApiVersion::OLDEST,
+            ApiVersion::CURRENT
+        ));
+    }
+    Ok(version)
+}
+
+// One line typed into `unisrv shell`: any command, without the binary name.
+// Recording, replay and tracing are set for the whole session instead.
+#[cfg(feature = "interactive")]
+#[derive(Parser)]
+#[command(
+    name = "unisrv",
+    about = "Declarative infrastructure deployments on Unisrv",
+    no_binary_name = true
+)]
+struct ShellLine {
+    /// Never wait for input: confirmations are answered yes, and anything
+    /// that has to be typed fails at once. For CI; also set with
+    /// UNISRV_NONINTERACTIVE=1
+    #[arg(short = 'y', long, visible_alias = "non-interactive", global = true)]
+    yes: bool,
+    #[command(subcommand)]
+    command: Commands,
+}
+
+// `instance run`'s flags make `Instance` the largest by far; as with
+// `InstanceCommands`, it's parsed once per invocation.
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
-    /// Login with a user account
+    /// Login with a user account, alongside any already logged into
     Login {
         /// Username
         #[arg(short, long)]
@@ -35,6 +123,11 @@ enum Commands {
         #[command(subcommand)]
         command: AuthCommands,
     },
+    /// List the accounts logged into and switch between them
+    Account {
+        #[command(subcommand)]
+        command: AccountCommands,
+    },
     /// Manage service hosts (domains)
     Host {
         #[command(subcommand)]
@@ -54,9 +147,85 @@ enum Commands {
         /// Set an interpolation variable, e.g. --var image_tag=v1.2.3 (repeatable)
         #[arg(long = "var", value_name = "KEY=VALUE")]
         vars: Vec<String>,
-        /// Load interpolation variables from a dotenv-style file (repeatable)
+        /// Load interpolation variables from a dotenv-style file, or TOML when it
+        /// ends in .toml (repeatable)
+        #[arg(long = "var-file", value_name = "FILE")]
+        var_files: Vec<PathBuf>,
+        /// Fail if applying, including waiting for rolled-out deployments to
+        /// be ready, takes longer than this, e.g. 10m
+        #[arg(long, value_name = "DURATION", value_parser = commands::up::ready::parse_duration)]
+        timeout: Option<std::time::Duration>,
+        /// Undo the changes a failed apply made instead of leaving them in place
+        #[arg(long)]
+        rollback_on_failure: bool,
+        /// Resolve every image tag afresh instead of deploying the digests
+        /// locked in unisrv.lock
+        #[arg(long)]
+        update_images: bool,
+        /// Take over existing resources under declared names that unisrv up
+        /// didn't create, reconciling them to the config
+        #[arg(long, conflicts_with = "replace")]
+        adopt: bool,
+        /// Delete existing resources under declared names that unisrv up
+        /// didn't create, and create the declared ones in their place
+        #[arg(long)]
+        replace: bool,
+        /// Only apply the changes to this resource, e.g. deployment.api, and
+        /// what it needs (repeatable)
+        #[arg(long = "target", value_name = "ADDRESS")]
+        targets: Vec<commands::up::targeting::Address>,
+        /// Leave this resource as it is, e.g. service.web (repeatable)
+        #[arg(long = "exclude", value_name = "ADDRESS")]
+        excludes: Vec<commands::up::targeting::Address>,
+    },
+    /// Show what `up` would change, without changing anything
+    Plan {
+        /// Pin which environment to plan against by name
+        #[arg(long)]
+        env: Option<String>,
+        /// Set an interpolation variable, e.g. --var image_tag=v1.2.3 (repeatable)
+        #[arg(long = "var", value_name = "KEY=VALUE")]
+        vars: Vec<String>,
+        /// Load interpolation variables from a dotenv-style file, or TOML when it
+        /// ends in .toml (repeatable)
         #[arg(long = "var-file", value_name = "FILE")]
         var_files: Vec<PathBuf>,
+        /// Resolve every image tag afresh instead of planning with the
+        /// digests locked in unisrv.lock
+        #[arg(long)]
+        update_images: bool,
+        /// Take over existing resources under declared names that unisrv up
+        /// didn't create, reconciling them to the config
+        #[arg(long, conflicts_with = "replace")]
+        adopt: bool,
+        /// Delete existing resources under declared names that unisrv up
+        /// didn't create, and create the declared ones in their place
+        #[arg(long)]
+        replace: bool,
+        /// Only apply the changes to this resource, e.g. deployment.api, and
+        /// what it needs (repeatable)
+        #[arg(long = "target", value_name = "ADDRESS")]
+        targets: Vec<commands::up::targeting::Address>,
+        /// Leave this resource as it is, e.g. service.web (repeatable)
+        #[arg(long = "exclude", value_name = "ADDRESS")]
+        excludes: Vec<commands::up::targeting::Address>,
+        /// Output the plan as JSON, for policy checks
+        #[arg(long)]
+        json: bool,
+        /// Filter the JSON output through a jq expression, e.g. '.summary'
+        #[arg(long, value_name = "EXPR")]
+        jq: Option<String>,
+    },
+    /// Print the `output` values saved by the last `up`
+    Output {
+        /// A single output to print; strings are printed bare, for scripts
+        name: Option<String>,
+        /// Which environment's outputs (needed when several have been applied)
+        #[arg(long)]
+        env: Option<String>,
+        /// Print as JSON
+        #[arg(long)]
+        json: bool,
     },
     /// Destroy the selected environment: delete all its services, deployments,
     /// standalone instances, and the environment itself
@@ -71,19 +240,228 @@ enum Commands {
         #[command(subcommand)]
         command: Option<InstanceCommands>,
     },
+    /// List the regions instances, workers and services can run in, and
+    /// measure the latency to them
+    #[command(alias = "regions")]
+    Region {
+        #[command(subcommand)]
+        command: Option<RegionCommands>,
+    },
+    /// Run WebAssembly modules on the lightweight isolate runtime
+    Worker {
+        #[command(subcommand)]
+        command: WorkerCommands,
+    },
+    /// Inspect services in an environment
+    #[command(alias = "svc")]
+    Service {
+        #[command(subcommand)]
+        command: ServiceCommands,
+    },
+    /// Manage private networks: creation, membership, reserved addresses, policy rules
+    /// and peering
+    #[command(alias = "net")]
+    Network {
+        #[command(subcommand)]
+        command: NetworkCommands,
+    },
+    /// Print the merged logs of every instance in an app or behind a service
+    Logs {
+        /// Deployment name whose instances to show
+        #[arg(
+            long,
+            value_name = "DEPLOYMENT",
+            conflicts_with = "service",
+            required_unless_present = "service"
+        )]
+        app: Option<String>,
+        /// Service name or UUID whose target instances to show
+        #[arg(long, value_name = "NAME_OR_UUID")]
+        service: Option<String>,
+        /// Stream new log lines as they arrive, in timestamp order
+        #[arg(short = 'f', long)]
+        follow: bool,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Roll deployments out to new images, list their past rollouts and roll
+    /// back to an earlier image
+    Rollout {
+        #[command(subcommand)]
+        command: RolloutCommands,
+    },
+    /// Default environment variables merged into every `instance run` and
+    /// `rollout deploy` under the account in use
+    Env {
+        #[command(subcommand)]
+        command: EnvCommands,
+    },
+    /// Manage local CLI state (deployment history, caches, failure reports)
+    State {
+        #[command(subcommand)]
+        command: StateCommands,
+    },
+    /// Show usage statistics
+    Stats {
+        #[command(subcommand)]
+        command: StatsCommands,
+    },
+    /// Count instances created and stopped, rollouts and certificates issued
+    /// per day, from the account's activity log
+    Report {
+        /// How far back to report, e.g. 7d or 30d
+        #[arg(long, value_name = "DURATION", default_value = "30d", value_parser = commands::up::ready::parse_duration)]
+        since: std::time::Duration,
+        /// Print as a table, csv or json
+        #[arg(short, long = "output", value_name = "FORMAT", default_value = "table")]
+        output: commands::report::Format,
+    },
+    /// Bring resources created outside unisrv.hcl under its management
+    Import {
+        #[command(subcommand)]
+        command: ImportCommands,
+    },
+    /// Inspect the CLI's settings
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+    /// Run commands interactively, with history, tab completion and lookups
+    /// cached between commands
+    Shell,
+    /// Print a tab-completion script for bash, zsh, fish or powershell,
+    /// completing instance, network and service names as well as commands
+    Completions {
+        /// bash, zsh, fish or powershell
+        shell: commands::completions::Shell,
+    },
+    /// The names a completion script offers for the words typed so far
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        #[arg(allow_hyphen_values = true, trailing_var_arg = true)]
+        words: Vec<String>,
+    },
 }
 
+// How a list command lays out its table; shared by the `list` subcommands.
+#[derive(Args, Default)]
+struct TableFlags {
+    /// Show these columns, in this order, e.g. id,name,state
+    #[arg(long, value_name = "COLUMNS", value_delimiter = ',', conflicts_with_all = ["json", "format", "jq", "quiet"])]
+    columns: Option<Vec<String>>,
+    /// Order by created (newest first) or name
+    #[arg(long, value_name = "KEY")]
+    sort: Option<commands::table::SortKey>,
+    /// Print full ids and don't wrap cells to the terminal width
+    #[arg(long, conflicts_with_all = ["json", "format", "jq", "quiet"])]
+    no_trunc: bool,
+}
+
+impl TableFlags {
+    fn options(self) -> commands::table::TableOptions {
+        commands::table::TableOptions {
+            columns: self.columns,
+            sort: self.sort,
+            no_trunc: self.no_trunc,
+        }
+    }
+}
+
+// `run`'s flags dwarf the other subcommands', but it's parsed once per
+// invocation.
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum InstanceCommands {
     /// List instances in the selected environment
     #[command(alias = "ls")]
     List {
-        /// Include stopped instances, not just running/provisioning ones
-        #[arg(short = 'a', long)]
+        /// Include stopped instances, not just running/provisioning ones,
+        /// with when a retained one can be restored until
+        #[arg(short = 'a', long, visible_alias = "include-stopped")]
         all: bool,
         /// Output as JSON
         #[arg(long)]
         json: bool,
+        /// Print each item through a template, e.g. '{{.id}}\t{{.name}}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Filter the JSON output through a jq expression, e.g. '.[].name'
+        #[arg(long, value_name = "EXPR", conflicts_with = "format")]
+        jq: Option<String>,
+        /// Print only each item's full id, one per line
+        #[arg(short, long, conflicts_with_all = ["json", "format", "jq"])]
+        quiet: bool,
+        #[command(flatten)]
+        table: TableFlags,
+        /// Redraw every INTERVAL (2s without one) until Ctrl-C, highlighting
+        /// what changed
+        #[arg(short = 'w', long, value_name = "INTERVAL", num_args = 0..=1,
+              default_missing_value = commands::watch::DEFAULT_INTERVAL,
+              value_parser = commands::up::ready::parse_duration,
+              conflicts_with_all = ["json", "format", "jq", "quiet"])]
+        watch: Option<std::time::Duration>,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Show an instance's state, containers and placement
+    Show {
+        /// Instance UUID, name, or UUID prefix
+        #[arg(value_name = "NAME_OR_UUID")]
+        reference: String,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+        /// Print through a template, e.g. '{{.id}} {{.state}}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Filter the JSON output through a jq expression, e.g. '.configuration'
+        #[arg(long, value_name = "EXPR", conflicts_with = "format")]
+        jq: Option<String>,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Stop an instance, optionally keeping it to restore later
+    Stop {
+        /// Instance UUID, name, or UUID prefix
+        #[arg(value_name = "NAME_OR_UUID")]
+        reference: String,
+        /// Keep its configuration and disk this long, e.g. 24h, so `instance
+        /// restore` can bring it back (at most 30d)
+        #[arg(long, value_name = "DURATION", value_parser = commands::up::ready::parse_duration)]
+        keep: Option<std::time::Duration>,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Print the host:port an instance's TCP proxies are reached on
+    ConnectionString {
+        /// Instance UUID, name, or UUID prefix
+        #[arg(value_name = "NAME_OR_UUID")]
+        reference: String,
+        /// Only the proxy for this port inside the instance
+        #[arg(long)]
+        port: Option<u16>,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+        /// Print each item through a template, e.g. '{{.port}} {{.address}}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Filter the JSON output through a jq expression, e.g. '.[0].address'
+        #[arg(long, value_name = "EXPR", conflicts_with = "format")]
+        jq: Option<String>,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Bring back an instance stopped with --keep, as it was
+    Restore {
+        /// Instance UUID, name, or UUID prefix
+        #[arg(value_name = "NAME_OR_UUID")]
+        reference: String,
         /// Target a specific environment by name
         #[arg(long)]
         env: Option<String>,
@@ -94,22 +472,1112 @@ enum InstanceCommands {
         /// Instance UUID, name, or UUID prefix
         #[arg(value_name = "NAME_OR_UUID")]
         reference: String,
-        /// Stream new log lines as they arrive (until the instance stops)
+        /// Stream new log lines as they arrive (until the instance stops),
+        /// reconnecting and resuming if the connection drops
         #[arg(short = 'f', long)]
         follow: bool,
+        /// Show the logs of the instance this one replaced: the newest older
+        /// instance with the same name, stopped ones included
+        #[arg(short = 'p', long, conflicts_with = "follow")]
+        previous: bool,
+        /// Only lines in this UTC window, e.g. 2024-06-01T10:00/11:00
+        #[arg(long, value_name = "START/END", conflicts_with = "follow")]
+        between: Option<String>,
+        /// Only lines containing TEXT
+        #[arg(long, value_name = "TEXT", conflicts_with = "follow")]
+        grep: Option<String>,
+        /// Print the lines as a table with time and stream columns
+        #[arg(long, conflicts_with = "follow")]
+        table: bool,
         /// Target a specific environment by name
         #[arg(long)]
-        env: Option<String>,
+        env: Option<String>,
+    },
+    /// Start a one-off instance from a container image
+    Run {
+        /// Container image, e.g. alpine:3
+        image: String,
+        /// Instance name
+        #[arg(long)]
+        name: Option<String>,
+        /// Set an environment variable in the container (repeatable)
+        #[arg(short = 'e', long = "env-var", value_name = "KEY=VALUE")]
+        env_vars: Vec<String>,
+        /// Join a private network, optionally at a fixed address
+        /// (e.g. backend or 10.0.0.5@backend)
+        #[arg(long, value_name = "[IP@]NETWORK")]
+        network: Option<String>,
+        /// Let the server allocate the --network address instead of picking
+        /// one locally (falls back when the server can't)
+        #[arg(long, requires = "network")]
+        ip_from_pool: bool,
+        /// Number of vCPUs the instance may burst to
+        #[arg(long, visible_alias = "cpu-limit")]
+        vcpus: Option<u8>,
+        /// Cores guaranteed across those vCPUs, e.g. 0.5 (a 1/8, 1/4, 1/2 or
+        /// full share of the limit) [default: a quarter of the limit]
+        #[arg(long, value_name = "CORES")]
+        cpu_request: Option<f64>,
+        /// Memory limit in MB
+        #[arg(long, value_name = "MB", visible_alias = "memory-limit")]
+        memory: Option<u32>,
+        /// Memory guaranteed in MB, up to the limit [default: the limit]
+        #[arg(long, value_name = "MB")]
+        memory_request: Option<u32>,
+        /// Guest hostname (defaults to the instance name)
+        #[arg(long)]
+        hostname: Option<String>,
+        /// Attach metadata the instance can read from the in-guest metadata
+        /// endpoint, http://169.254.169.254/v1/instance (repeatable)
+        #[arg(long, value_name = "KEY=VALUE")]
+        metadata: Vec<String>,
+        /// Run a container to completion before the main one starts, e.g. a
+        /// migration (repeatable, run in order)
+        #[arg(long = "init-image", value_name = "IMAGE[:ARGS]")]
+        init_images: Vec<String>,
+        /// Run a container alongside the main one in the same VM, e.g. a log
+        /// shipper (repeatable)
+        #[arg(long = "sidecar", value_name = "IMAGE[:ARGS]")]
+        sidecars: Vec<String>,
+        /// Run this platform's build of a multi-platform image, e.g.
+        /// linux/arm64 [default: `platform` in ~/.unisrv/config.json, else
+        /// linux/amd64]
+        #[arg(long, value_name = "OS/ARCH[/VARIANT]")]
+        platform: Option<commands::instance::platform::Platform>,
+        /// Region to run in [default: `region` in ~/.unisrv/config.json,
+        /// else dev]; see `unisrv regions`
+        #[arg(long)]
+        region: Option<String>,
+        /// Start without checking the images exist in their registries, for
+        /// redeploying a known-good image while its registry is down
+        #[arg(long, conflicts_with = "verify_signature")]
+        skip_image_verify: bool,
+        /// Refuse images without a cosign signature from
+        /// --certificate-identity, and run them by the verified digest
+        /// (needs cosign installed)
+        #[arg(long, requires_all = ["certificate_identity", "certificate_oidc_issuer"])]
+        verify_signature: bool,
+        /// The signer --verify-signature expects, e.g. an email address or a
+        /// CI workflow URL
+        #[arg(long, value_name = "IDENTITY", requires = "verify_signature")]
+        certificate_identity: Option<String>,
+        /// The OIDC issuer that vouched for --certificate-identity, e.g.
+        /// https://token.actions.githubusercontent.com
+        #[arg(long, value_name = "URL", requires = "verify_signature")]
+        certificate_oidc_issuer: Option<String>,
+        /// Wait for the instance to be running, then show how long each boot
+        /// phase took
+        #[arg(long)]
+        wait: bool,
+        /// Don't merge in the account's default environment variables (see
+        /// `unisrv env set-default`)
+        #[arg(long)]
+        no_default_env: bool,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+        /// Arguments passed to the container, after `--`
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum WorkerCommands {
+    /// Start a WebAssembly module (a core module, not a component)
+    Run {
+        /// The module, e.g. target/wasm32-wasip1/release/app.wasm
+        module: std::path::PathBuf,
+        /// Worker name
+        #[arg(long)]
+        name: Option<String>,
+        /// Set an environment variable in the worker (repeatable)
+        #[arg(short = 'e', long = "env-var", value_name = "KEY=VALUE")]
+        env_vars: Vec<String>,
+        /// Memory limit in MB, up to 512
+        #[arg(long, value_name = "MB", default_value_t = commands::worker::DEFAULT_MEMORY_MB)]
+        memory: u32,
+        /// CPU time the worker may use before it's stopped, in milliseconds
+        #[arg(long, value_name = "MS", default_value_t = commands::worker::DEFAULT_CPU_TIME_MS)]
+        cpu_time_ms: u32,
+        /// Region to run in [default: `region` in ~/.unisrv/config.json,
+        /// else dev]; see `unisrv regions`
+        #[arg(long)]
+        region: Option<String>,
+        /// Print the worker's logs until it finishes
+        #[arg(short, long)]
+        follow: bool,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+        /// Arguments passed to the module, after `--`
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum RegionCommands {
+    /// List the regions, marking the default
+    #[command(alias = "ls")]
+    List {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+        /// Print each item through a template, e.g. '{{.name}}\t{{.location}}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Filter the JSON output through a jq expression, e.g. '.[].name'
+        #[arg(long, value_name = "EXPR", conflicts_with = "format")]
+        jq: Option<String>,
+    },
+    /// Measure the round trip from here to each region's edge, fastest first
+    Ping {
+        /// Round trips to time per region; the median is reported
+        #[arg(short = 'c', long, default_value_t = commands::regions::ping::DEFAULT_SAMPLES)]
+        count: u32,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+        /// Print each region through a template, e.g. '{{.region}} {{.median_ms}}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Filter the JSON output through a jq expression, e.g. '.[0].region'
+        #[arg(long, value_name = "EXPR", conflicts_with = "format")]
+        jq: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum AccountCommands {
+    /// List the accounts logged into, marking the one in use
+    #[command(alias = "ls")]
+    List {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+        /// Print each item through a template, e.g. '{{.username}}\t{{.api_host}}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Filter the JSON output through a jq expression, e.g. '.[].username'
+        #[arg(long, value_name = "EXPR", conflicts_with = "format")]
+        jq: Option<String>,
+    },
+    /// Run commands as another account logged into on this API host
+    Switch {
+        /// The account's username, as given to `unisrv login`
+        username: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum AuthCommands {
+    /// Print a valid access token to stdout
+    Token {
+        /// Output as JSON with expiry information
+        #[arg(short, long)]
+        json: bool,
+        /// Filter the JSON output through a jq expression, e.g. '.[].name'
+        #[arg(long, value_name = "EXPR")]
+        jq: Option<String>,
+    },
+    /// Create a long-lived API token for CI; set UNISRV_TOKEN to it there
+    CreateToken {
+        /// What the token is for, e.g. ci
+        #[arg(long)]
+        name: String,
+        /// How long until it expires, e.g. 90d, or never
+        #[arg(long, value_name = "DURATION", default_value = "90d")]
+        expires: commands::auth::Expiry,
+        /// What it may do: full, or read-only
+        #[arg(long, value_parser = commands::auth::parse_scope, default_value = "full")]
+        scope: unisrv_api::models::TokenScope,
+        /// Output as JSON, secret included
+        #[arg(long)]
+        json: bool,
+    },
+    /// Manage API tokens
+    Tokens {
+        #[command(subcommand)]
+        command: TokensCommands,
+    },
+    /// Revoke an API token, by name or id
+    Revoke {
+        #[arg(value_name = "NAME_OR_ID")]
+        reference: String,
+    },
+    /// Manage service accounts: scoped tokens for deployment pipelines
+    ServiceAccount {
+        #[command(subcommand)]
+        command: ServiceAccountCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum ServiceAccountCommands {
+    /// Create a service account and print its token, once
+    Create {
+        /// What it's for, e.g. deploy
+        #[arg(long)]
+        name: String,
+        /// A permission it holds, e.g. instances:write or services:read;
+        /// repeat or comma-separate for several
+        #[arg(
+            long = "scope",
+            value_name = "SCOPE",
+            required = true,
+            value_delimiter = ',',
+            value_parser = commands::service_account::parse_scope
+        )]
+        scopes: Vec<String>,
+        /// How long until its token expires, e.g. 90d, or never
+        #[arg(long, value_name = "DURATION", default_value = "90d")]
+        expires: commands::auth::Expiry,
+        /// Output as JSON, token included
+        #[arg(long)]
+        json: bool,
+    },
+    /// List service accounts, without their tokens
+    #[command(alias = "ls")]
+    List {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+        /// Print each item through a template, e.g. '{{.id}}\t{{.name}}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Filter the JSON output through a jq expression, e.g. '.[].name'
+        #[arg(long, value_name = "EXPR", conflicts_with = "format")]
+        jq: Option<String>,
+    },
+    /// Delete a service account, by name or id, revoking its token
+    #[command(alias = "rm")]
+    Delete {
+        #[arg(value_name = "NAME_OR_ID")]
+        reference: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum TokensCommands {
+    /// List API tokens, without their secrets
+    #[command(alias = "ls")]
+    List {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+        /// Print each item through a template, e.g. '{{.id}}\t{{.name}}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Filter the JSON output through a jq expression, e.g. '.[].name'
+        #[arg(long, value_name = "EXPR", conflicts_with = "format")]
+        jq: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ServiceCommands {
+    /// List the services in the selected environment
+    #[command(alias = "ls")]
+    List {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+        /// Print each item through a template, e.g. '{{.name}}\t{{.base_host}}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Filter the JSON output through a jq expression, e.g. '.[].name'
+        #[arg(long, value_name = "EXPR", conflicts_with = "format")]
+        jq: Option<String>,
+        /// Print only each item's full id, one per line
+        #[arg(short, long, conflicts_with_all = ["json", "format", "jq"])]
+        quiet: bool,
+        #[command(flatten)]
+        table: TableFlags,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Show a service's hosts and locations
+    Show {
+        /// Service name or UUID
+        #[arg(value_name = "NAME_OR_UUID")]
+        reference: String,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+        /// Print through a template, e.g. '{{.id}}\t{{.name}}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Filter the JSON output through a jq expression, e.g. '.custom_hosts'
+        #[arg(long, value_name = "EXPR", conflicts_with = "format")]
+        jq: Option<String>,
+        /// Redraw every INTERVAL (2s without one) until Ctrl-C, highlighting
+        /// what changed
+        #[arg(short = 'w', long, value_name = "INTERVAL", num_args = 0..=1,
+              default_missing_value = commands::watch::DEFAULT_INTERVAL,
+              value_parser = commands::up::ready::parse_duration,
+              conflicts_with_all = ["json", "format", "jq"])]
+        watch: Option<std::time::Duration>,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Create a new service with another service's locations and configuration
+    Clone {
+        /// Service to copy, by name or UUID
+        #[arg(value_name = "NAME_OR_UUID")]
+        reference: String,
+        /// Name for the new service [default: <source>-copy]
+        #[arg(long)]
+        name: Option<String>,
+        /// Claimed host to attach to the new service (repeatable)
+        #[arg(long = "host", value_name = "HOST")]
+        hosts: Vec<String>,
+        /// Also copy the source's instance targets
+        #[arg(long)]
+        with_targets: bool,
+        /// Region to run in [default: `region` in ~/.unisrv/config.json,
+        /// else dev]; see `unisrv regions`
+        #[arg(long)]
+        region: Option<String>,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Show or change a service's TLS policy
+    Tls {
+        /// Service name or UUID
+        #[arg(value_name = "NAME_OR_UUID")]
+        reference: String,
+        /// Lowest TLS version the edge accepts (1.2 or 1.3)
+        #[arg(long, value_name = "VERSION")]
+        min_version: Option<TlsVersion>,
+        /// Send Strict-Transport-Security on HTTPS responses
+        #[arg(long, conflicts_with = "no_hsts")]
+        hsts: bool,
+        /// Stop sending Strict-Transport-Security
+        #[arg(long)]
+        no_hsts: bool,
+        /// Redirect plain HTTP requests to HTTPS
+        #[arg(long, conflicts_with = "allow_http")]
+        redirect_http: bool,
+        /// Serve plain HTTP as well as HTTPS
+        #[arg(long)]
+        allow_http: bool,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Show or change who may reach a service: HTTP basic auth and an IP
+    /// allowlist at the edge
+    Protect {
+        /// Service name or UUID
+        #[arg(value_name = "NAME_OR_UUID")]
+        reference: String,
+        /// Require this login, replacing the current ones (repeatable)
+        #[arg(long = "basic-auth", value_name = "USER:PASSWORD", value_parser = commands::service::protect::parse_credential, conflicts_with = "no_basic_auth")]
+        basic_auth: Vec<unisrv_api::models::BasicAuthCredential>,
+        /// Stop requiring a login
+        #[arg(long)]
+        no_basic_auth: bool,
+        /// Only let this address or CIDR block in, replacing the current
+        /// allowlist (repeatable)
+        #[arg(long = "allow-ip", value_name = "CIDR", value_parser = commands::service::protect::parse_allow_ip, conflicts_with = "no_allow_ip")]
+        allow_ips: Vec<String>,
+        /// Let every address in again
+        #[arg(long)]
+        no_allow_ip: bool,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Change a service's settings
+    Update {
+        /// Service name or UUID
+        #[arg(value_name = "NAME_OR_UUID")]
+        reference: String,
+        /// Compress text responses at the edge (on|off)
+        #[arg(long, value_name = "on|off", value_parser = commands::service::update::parse_on_off)]
+        compression: Option<bool>,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Create a service
+    New {
+        #[command(subcommand)]
+        command: ServiceNewCommands,
+    },
+    /// Attach or detach claimed hosts
+    Host {
+        #[command(subcommand)]
+        command: ServiceHostCommands,
+    },
+    /// Route a service's instance group to an instance
+    Target {
+        #[command(subcommand)]
+        command: ServiceTargetCommands,
+    },
+    /// Adjust one of a service's locations
+    Location {
+        #[command(subcommand)]
+        command: ServiceLocationCommands,
+    },
+    /// List a service's instance targets and their health at the edge
+    Targets {
+        /// Service name or UUID
+        #[arg(value_name = "NAME_OR_UUID")]
+        reference: String,
+        /// Only show targets the edge isn't routing to (unhealthy or draining)
+        #[arg(long)]
+        unhealthy: bool,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+        /// Print each item through a template, e.g. '{{.instance_id}}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Filter the JSON output through a jq expression, e.g. '.[].instance_id'
+        #[arg(long, value_name = "EXPR", conflicts_with = "format")]
+        jq: Option<String>,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Show request rate, error rate and latency per location and target
+    Stats {
+        /// Service name or UUID
+        #[arg(value_name = "NAME_OR_UUID")]
+        reference: String,
+        /// Trailing window to report on, e.g. 15m, 1h, 7d
+        #[arg(long, default_value = "1h")]
+        window: String,
+        /// Keep refreshing the view until interrupted
+        #[arg(short = 'w', long)]
+        watch: bool,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum NetworkCommands {
+    /// List the private networks in the selected environment
+    #[command(alias = "ls")]
+    List {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+        /// Print each item through a template, e.g. '{{.name}}\t{{.ipv4_cidr}}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Filter the JSON output through a jq expression, e.g. '.[].name'
+        #[arg(long, value_name = "EXPR", conflicts_with = "format")]
+        jq: Option<String>,
+        /// Print only each item's full id, one per line
+        #[arg(short, long, conflicts_with_all = ["json", "format", "jq"])]
+        quiet: bool,
+        #[command(flatten)]
+        table: TableFlags,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Create a private network
+    New {
+        /// Network name
+        name: String,
+        /// Address range [default: 10.0.0.0/16]
+        #[arg(long, value_name = "CIDR", conflicts_with = "auto_cidr")]
+        cidr: Option<cidr::Ipv4Cidr>,
+        /// Pick the first /24 no existing network overlaps
+        #[arg(long)]
+        auto_cidr: bool,
+        /// Private range --auto-cidr picks from [env: UNISRV_NETWORK_RANGE]
+        /// [default: 172.16.0.0/12]
+        #[arg(long, value_name = "CIDR", requires = "auto_cidr")]
+        range: Option<cidr::Ipv4Cidr>,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Show a network's members, reservations, policy rules and peerings
+    Show {
+        /// Network name
+        network: String,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+        /// Print through a template, e.g. '{{.name}}\t{{.ipv4_cidr}}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Filter the JSON output through a jq expression, e.g. '.policies'
+        #[arg(long, value_name = "EXPR", conflicts_with = "format")]
+        jq: Option<String>,
+        /// Redraw every INTERVAL (2s without one) until Ctrl-C, highlighting
+        /// what changed
+        #[arg(short = 'w', long, value_name = "INTERVAL", num_args = 0..=1,
+              default_missing_value = commands::watch::DEFAULT_INTERVAL,
+              value_parser = commands::up::ready::parse_duration,
+              conflicts_with_all = ["json", "format", "jq"])]
+        watch: Option<std::time::Duration>,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// List the addresses in use on a network and how much room is left
+    Ips {
+        /// Network name
+        network: String,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+        /// Print each address through a template, e.g. '{{.ip}}\t{{.name}}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Filter the JSON output through a jq expression, e.g. '.free'
+        #[arg(long, value_name = "EXPR", conflicts_with = "format")]
+        jq: Option<String>,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Join a running instance to a network
+    Attach {
+        /// Network name
+        network: String,
+        /// Instance UUID, name, or UUID prefix
+        #[arg(value_name = "INSTANCE")]
+        instance: String,
+        /// Address to join at [default: the next free address]
+        #[arg(long)]
+        ip: Option<std::net::Ipv4Addr>,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Take a running instance off a network
+    Detach {
+        /// Network name
+        network: String,
+        /// Instance UUID, name, or UUID prefix
+        #[arg(value_name = "INSTANCE")]
+        instance: String,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Hold an address on a network so it's never auto-assigned
+    Reserve {
+        /// Network name
+        network: String,
+        /// Address to hold
+        ip: std::net::Ipv4Addr,
+        /// Label for the reservation, e.g. db-vip
+        #[arg(long)]
+        name: Option<String>,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// List the addresses held on a network
+    Reservations {
+        /// Network name
+        network: String,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+        /// Print each item through a template, e.g. '{{.ip}}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Filter the JSON output through a jq expression, e.g. '.[].ip'
+        #[arg(long, value_name = "EXPR", conflicts_with = "format")]
+        jq: Option<String>,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Route traffic between two networks whose address ranges don't overlap
+    Peer {
+        /// Network name
+        network: String,
+        /// Network to peer it with
+        peer: String,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Remove the peering between two networks
+    Unpeer {
+        /// Network name
+        network: String,
+        /// Network it's peered with
+        peer: String,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Manage firewall rules between a network's instances
+    Policy {
+        #[command(subcommand)]
+        command: NetworkPolicyCommands,
+    },
+    /// Reach a network's instances at their internal addresses over WireGuard
+    Connect {
+        /// Network name
+        network: String,
+        /// Name this machine is listed under [default: the hostname]
+        #[arg(long)]
+        name: Option<String>,
+        /// Only write the config; don't bring the tunnel up with wg-quick
+        #[arg(long)]
+        no_apply: bool,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Take the tunnel down and remove this machine from a network
+    Disconnect {
+        /// Network name
+        network: String,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// List this machine's WireGuard connections to networks
+    Status {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+        /// Print each connection through a template, e.g. '{{.network}}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Filter the JSON output through a jq expression, e.g. '.[].address'
+        #[arg(long, value_name = "EXPR", conflicts_with = "format")]
+        jq: Option<String>,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum NetworkPolicyCommands {
+    /// Allow or deny traffic from a CIDR or instance to an instance or port
+    Add {
+        /// Network name
+        network: String,
+        /// Where traffic comes from: a CIDR, an address, or an instance
+        #[arg(long, value_name = "CIDR|INSTANCE")]
+        from: String,
+        /// Where it goes: an instance, a port on every instance, or INSTANCE:PORT
+        #[arg(long, value_name = "INSTANCE|PORT")]
+        to: String,
+        /// Let matching traffic through
+        #[arg(long, conflicts_with = "deny", required_unless_present = "deny")]
+        allow: bool,
+        /// Block matching traffic
+        #[arg(long)]
+        deny: bool,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// List a network's policy rules
+    #[command(alias = "ls")]
+    List {
+        /// Network name
+        network: String,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+        /// Print each item through a template, e.g. '{{.id}}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Filter the JSON output through a jq expression, e.g. '.[].id'
+        #[arg(long, value_name = "EXPR", conflicts_with = "format")]
+        jq: Option<String>,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Delete a policy rule
+    #[command(alias = "rm")]
+    Delete {
+        /// Network name
+        network: String,
+        /// Rule id, or a unique prefix of it
+        rule: String,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ServiceNewCommands {
+    /// Create an HTTP service serving a claimed host that has its certificate
+    Http {
+        /// Service name [default: the host's first label]
+        name: Option<String>,
+        /// Claimed host with a valid certificate to attach, e.g.
+        /// api.example.com
+        #[arg(long, value_name = "HOST")]
+        from_cert_host: String,
+        /// Region to run in [default: `region` in ~/.unisrv/config.json,
+        /// else dev]; see `unisrv regions`
+        #[arg(long)]
+        region: Option<String>,
+        /// Compress text responses at the edge (on|off) [default: the
+        /// platform's]
+        #[arg(long, value_name = "on|off", value_parser = commands::service::update::parse_on_off)]
+        compression: Option<bool>,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ServiceHostCommands {
+    /// Attach a claimed host to a service
+    Add {
+        /// Service name or UUID
+        #[arg(value_name = "SERVICE")]
+        reference: String,
+        /// Claimed hostname, e.g. shop.example.com
+        host: String,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Detach a host from a service
+    #[command(alias = "rm")]
+    Remove {
+        /// Service name or UUID
+        #[arg(value_name = "SERVICE")]
+        reference: String,
+        /// Attached hostname
+        host: String,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ServiceLocationCommands {
+    /// Route another path prefix on a service
+    Add {
+        /// Service name or UUID
+        #[arg(value_name = "SERVICE")]
+        reference: String,
+        /// Path prefix to route, e.g. /api
+        path: String,
+        /// Instance group to send requests to [default: default]
+        #[arg(long, conflicts_with_all = ["url", "redirect_to"])]
+        group: Option<String>,
+        /// Absolute URL to proxy requests to
+        #[arg(long, conflicts_with = "redirect_to")]
+        url: Option<String>,
+        /// Absolute URL the edge redirects requests to, without proxying them
+        #[arg(long, value_name = "URL")]
+        redirect_to: Option<String>,
+        /// Replace the matched prefix with this path before proxying to the
+        /// instance group, e.g. /v2
+        #[arg(long, value_name = "PATH", conflicts_with_all = ["url", "redirect_to"])]
+        rewrite: Option<String>,
+        /// Drop the matched prefix before proxying; the same as --rewrite /
+        #[arg(long, conflicts_with_all = ["rewrite", "url", "redirect_to"])]
+        strip_prefix: bool,
+        /// Proxy WebSocket upgrades on this location
+        #[arg(long, conflicts_with = "redirect_to")]
+        websocket: bool,
+        /// Proxy gRPC (HTTP/2 end to end) on this location
+        #[arg(long, conflicts_with = "redirect_to")]
+        grpc: bool,
+        /// Header to set on every response, e.g. X-Frame-Options=DENY
+        /// (repeatable)
+        #[arg(long = "set-header", value_name = "NAME=VALUE",
+              value_parser = commands::service::location::parse_header)]
+        headers: Vec<(String, String)>,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Cache a location's responses at the edge, or stop caching them
+    Cache {
+        /// Service name or UUID
+        #[arg(value_name = "SERVICE")]
+        reference: String,
+        /// The location's path, as `service show` lists it, e.g. /static
+        path: String,
+        /// How long a response is served from the cache, e.g. 1h
+        #[arg(long, value_name = "DURATION", value_parser = commands::up::ready::parse_duration,
+              required_unless_present = "off")]
+        ttl: Option<std::time::Duration>,
+        /// How much longer an expired response is served while it's fetched
+        /// again, e.g. 10m
+        #[arg(long, value_name = "DURATION", value_parser = commands::up::ready::parse_duration,
+              requires = "ttl")]
+        stale_while_revalidate: Option<std::time::Duration>,
+        /// Stop caching the location
+        #[arg(long, conflicts_with = "ttl")]
+        off: bool,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ServiceTargetCommands {
+    /// Add an instance as a target, once the edge is known to reach it
+    Add {
+        /// Service name or UUID
+        #[arg(value_name = "SERVICE")]
+        reference: String,
+        /// Instance UUID, name, or UUID prefix
+        #[arg(value_name = "INSTANCE")]
+        instance: String,
+        /// Port on the instance to send traffic to
+        #[arg(long)]
+        port: u16,
+        /// Instance group the service's locations route to
+        #[arg(long)]
+        group: String,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum RolloutCommands {
+    /// Roll a deployment out to a new image: all at once, by canary, or staged
+    /// blue/green
+    Deploy {
+        /// Deployment name
+        deployment: String,
+        /// The image to roll out, e.g. registry.example.com/api:1.4.0
+        image: String,
+        /// replace (update the deployment in place), canary (shift its
+        /// service's traffic over in steps, rolling back on failure) or
+        /// blue-green (stage the new image next to it for `rollout promote`)
+        #[arg(long, value_name = "STRATEGY", default_value = "replace")]
+        strategy: commands::rollout::Strategy,
+        /// Canary traffic shares in percent, rising to 100
+        #[arg(long, value_name = "PERCENTS", default_value = commands::rollout::canary::DEFAULT_STEPS, value_parser = commands::rollout::canary::parse_steps)]
+        steps: commands::rollout::canary::Steps,
+        /// How long each canary step is held while its health is checked
+        #[arg(long, value_name = "DURATION", default_value = commands::rollout::canary::DEFAULT_INTERVAL, value_parser = commands::up::ready::parse_duration)]
+        interval: std::time::Duration,
+        /// Abort a canary rollout when more canaries than this are unhealthy
+        #[arg(long, value_name = "COUNT", default_value_t = 0)]
+        max_unhealthy: usize,
+        /// How long to wait for new instances to be running, e.g. 10m
+        #[arg(long, value_name = "DURATION", default_value = commands::rollout::DEFAULT_TIMEOUT, value_parser = commands::up::ready::parse_duration)]
+        timeout: std::time::Duration,
+        /// Don't merge in the account's default environment variables (see
+        /// `unisrv env set-default`)
+        #[arg(long)]
+        no_default_env: bool,
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Switch a service over to the generation a blue/green rollout staged,
+    /// and roll its deployment out to the new image
+    Promote {
+        /// Service name
+        service: String,
+        /// How long to wait for the deployment's new replicas, e.g. 10m
+        #[arg(long, value_name = "DURATION", default_value = commands::rollout::DEFAULT_TIMEOUT, value_parser = commands::up::ready::parse_duration)]
+        timeout: std::time::Duration,
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Drop the generation a blue/green rollout staged, leaving the service
+    /// as it was
+    Abort {
+        /// Service name
+        service: String,
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// The images a deployment was rolled out with from this machine, newest
+    /// first
+    History {
+        /// Deployment name
+        deployment: String,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Redeploy the image a deployment ran before its latest rollout, and wait
+    /// for its replicas to be running
+    Rollback {
+        /// Deployment name
+        deployment: String,
+        /// Roll back to this revision (or a unique prefix of it) from
+        /// `rollout history` instead of the previous one
+        #[arg(long, value_name = "REVISION")]
+        to: Option<String>,
+        /// How long to wait for the rolled-back replicas, e.g. 10m
+        #[arg(long, value_name = "DURATION", default_value = commands::rollout::DEFAULT_TIMEOUT, value_parser = commands::up::ready::parse_duration)]
+        timeout: std::time::Duration,
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum EnvCommands {
+    /// Set environment variables every instance run or rolled out under this
+    /// account gets, unless the command sets them itself
+    SetDefault {
+        /// e.g. LOG_FORMAT=json (one or more)
+        #[arg(value_name = "KEY=VALUE", required = true)]
+        vars: Vec<String>,
+        /// Only in this project, over the account-wide defaults
+        #[arg(long)]
+        project: Option<String>,
+    },
+    /// Stop setting environment variables by default
+    UnsetDefault {
+        /// Variable names (one or more)
+        #[arg(value_name = "KEY", required = true)]
+        keys: Vec<String>,
+        /// The project the defaults were set in
+        #[arg(long)]
+        project: Option<String>,
+    },
+    /// List the default environment variables and the projects they apply in
+    Defaults,
+}
+
+#[derive(Subcommand)]
+enum StateCommands {
+    /// Delete all local state
+    Clear,
+    /// Print the local state directory
+    Path,
+}
+
+#[derive(Subcommand)]
+enum StatsCommands {
+    /// Your own command usage, failure rates and timings, from local metrics
+    #[command(name = "self")]
+    Me {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+        /// Print each command through a template, e.g. '{{.command}} {{.runs}}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Filter the JSON output through a jq expression, e.g. '.[0].command'
+        #[arg(long, value_name = "EXPR", conflicts_with = "format")]
+        jq: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ImportCommands {
+    /// Adopt a standalone instance as a one-replica deployment, appending its
+    /// block to unisrv.hcl
+    Instance {
+        /// Instance UUID, name, or UUID prefix
+        #[arg(value_name = "NAME_OR_UUID")]
+        reference: String,
+        /// Name of the deployment to create for it
+        #[arg(long = "as", value_name = "NAME")]
+        name: String,
+        /// Target a specific environment by name
+        #[arg(long)]
+        env: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Print the settings in ~/.unisrv/config.json
+    Show {
+        /// Print every setting after merging defaults, the config file,
+        /// environment variables and flags, with where each value came from
+        #[arg(long)]
+        effective: bool,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+        /// Print each setting through a template, e.g. '{{.key}}={{.value}}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Filter the JSON output through a jq expression, e.g. '.[].key'
+        #[arg(long, value_name = "EXPR", conflicts_with = "format")]
+        jq: Option<String>,
     },
 }
 
 #[derive(Subcommand)]
-enum AuthCommands {
-    /// Print a valid access token to stdout
-    Token {
-        /// Output as JSON with expiry information
-        #[arg(short, long)]
+enum HostRecordCommands {
+    /// Publish a record under a claimed host
+    Add {
+        /// Claimed hostname, e.g. example.com
+        hostname: String,
+        /// Record name relative to the host, e.g. api, or @ for the host itself
+        name: String,
+        /// Record type: A, AAAA, CNAME, MX or TXT
+        #[arg(long = "type", value_name = "TYPE")]
+        record_type: String,
+        /// Record value, e.g. srvedge.net for a CNAME
+        #[arg(long)]
+        value: String,
+        /// Time to live in seconds; the server's default if omitted
+        #[arg(long)]
+        ttl: Option<u32>,
+    },
+    /// List the records under a claimed host
+    #[command(alias = "ls")]
+    List {
+        /// Claimed hostname, e.g. example.com
+        hostname: String,
+        /// Output as JSON
+        #[arg(long)]
         json: bool,
+        /// Print each record through a template, e.g. '{{.name}} {{.type}} {{.value}}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Filter the JSON output through a jq expression, e.g. '.[].value'
+        #[arg(long, value_name = "EXPR", conflicts_with = "format")]
+        jq: Option<String>,
+    },
+    /// Remove a record from under a claimed host
+    #[command(alias = "rm")]
+    Remove {
+        /// Claimed hostname, e.g. example.com
+        hostname: String,
+        /// Record name, or its id or a unique prefix of it
+        record: String,
+        /// Only consider records of this type, when a name has several
+        #[arg(long = "type", value_name = "TYPE")]
+        record_type: Option<String>,
     },
 }
 
@@ -120,12 +1588,76 @@ enum HostCommands {
         /// Hostname to claim, e.g. example.com
         hostname: String,
     },
+    /// Issue or renew a claimed host's TLS certificate
+    Cert {
+        /// Hostname, e.g. example.com
+        hostname: String,
+        /// Request a *.HOSTNAME certificate, validated with a DNS TXT record
+        #[arg(long)]
+        wildcard: bool,
+    },
+    /// Check that a host's DNS records point at unisrv
+    Check {
+        /// Hostname to check, e.g. example.com
+        hostname: String,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+        /// Filter the JSON output through a jq expression, e.g. '.passed'
+        #[arg(long, value_name = "EXPR")]
+        jq: Option<String>,
+    },
+    /// Manage DNS records under a claimed host
+    Record {
+        #[command(subcommand)]
+        command: HostRecordCommands,
+    },
+    /// Show a claimed host's service, certificate and validation status
+    Show {
+        /// Hostname, e.g. example.com
+        hostname: String,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+        /// Print through a template, e.g. '{{.certificate.not_after}}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Filter the JSON output through a jq expression, e.g. '.certificate.sans'
+        #[arg(long, value_name = "EXPR", conflicts_with = "format")]
+        jq: Option<String>,
+    },
+    /// List hosts' certificates by expiry; fails if any expire soon
+    CertStatus {
+        /// Fail when a certificate expires within this long, e.g. 12h, 30d or 2w
+        #[arg(long, value_name = "DURATION", default_value = "30d")]
+        expiring_within: String,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+        /// Print each certificate through a template, e.g. '{{.host}} {{.valid_until}}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Filter the JSON output through a jq expression, e.g. '.[] | select(.expiring)'
+        #[arg(long, value_name = "EXPR", conflicts_with = "format")]
+        jq: Option<String>,
+    },
     /// List claimed hosts
     #[command(alias = "ls")]
     List {
         /// Output as JSON
         #[arg(long)]
         json: bool,
+        /// Print each item through a template, e.g. '{{.id}}\t{{.name}}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Filter the JSON output through a jq expression, e.g. '.[].name'
+        #[arg(long, value_name = "EXPR", conflicts_with = "format")]
+        jq: Option<String>,
+        /// Print only each item's full id, one per line
+        #[arg(short, long, conflicts_with_all = ["json", "format", "jq"])]
+        quiet: bool,
+        #[command(flatten)]
+        table: TableFlags,
     },
 }
 
@@ -151,6 +1683,12 @@ enum RegistryCommands {
         /// Output as JSON
         #[arg(long)]
         json: bool,
+        /// Print each item through a template, e.g. '{{.id}}\t{{.name}}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Filter the JSON output through a jq expression, e.g. '.[].name'
+        #[arg(long, value_name = "EXPR", conflicts_with = "format")]
+        jq: Option<String>,
     },
     /// Update credentials for a registry
     Update {
@@ -166,20 +1704,93 @@ enum RegistryCommands {
         #[arg(long)]
         no_validate: bool,
     },
-    /// Delete a registry credential
+    /// Delete a registry credential, or a tag from the unisrv registry
     #[command(alias = "rm")]
     Delete {
-        /// Registry hostname
-        hostname: String,
-        /// Skip the confirmation prompt
-        #[arg(short = 'y', long)]
-        yes: bool,
+        /// Registry hostname, or REPO:TAG in the unisrv registry
+        #[arg(value_name = "HOSTNAME|REPO:TAG")]
+        target: String,
     },
     /// Test that stored credentials still work against the upstream registry
     Test {
         /// Registry hostname
         hostname: String,
     },
+    /// Add or update every registry `docker login` saved credentials for,
+    /// including those kept by a credential helper
+    ImportDocker {
+        /// Docker's config file [default: $DOCKER_CONFIG/config.json or
+        /// ~/.docker/config.json]
+        #[arg(long, value_name = "FILE")]
+        path: Option<PathBuf>,
+        /// Skip validating credentials against the upstream registries
+        #[arg(long)]
+        no_validate: bool,
+    },
+    /// List an image's tags, newest version first
+    Tags {
+        /// Image, e.g. ghcr.io/acme/app or nginx (a tag or digest is ignored)
+        image: String,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show the SBOMs attached to an image in its registry
+    Sbom {
+        /// Image, e.g. ghcr.io/acme/app:1.2 (the tag defaults to latest)
+        image: String,
+        /// Print the SBOM documents themselves, as a JSON array
+        #[arg(long)]
+        json: bool,
+        /// Filter the JSON output through a jq expression, e.g. '.[].name'
+        #[arg(long, value_name = "EXPR")]
+        jq: Option<String>,
+    },
+    /// Show the build provenance attached to an image in its registry
+    Provenance {
+        /// Image, e.g. ghcr.io/acme/app:1.2 (the tag defaults to latest)
+        image: String,
+        /// Print the provenance predicates themselves, as a JSON array
+        #[arg(long)]
+        json: bool,
+        /// Filter the JSON output through a jq expression, e.g. '.[].name'
+        #[arg(long, value_name = "EXPR")]
+        jq: Option<String>,
+    },
+    /// Push a local image to a registry, using its stored credentials
+    Push {
+        /// An image in the local Docker daemon, or an OCI layout directory
+        /// (`<dir>:<ref>` picks one of several images in it)
+        local: String,
+        /// Where to push it, e.g. ghcr.io/acme/app:1.2 (the tag defaults to latest)
+        remote: String,
+    },
+    /// List the repositories in the unisrv registry, with their storage
+    Repos {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+        /// Print each item through a template, e.g. '{{.name}}\t{{.size_bytes}}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Filter the JSON output through a jq expression, e.g. '.[].name'
+        #[arg(long, value_name = "EXPR", conflicts_with = "format")]
+        jq: Option<String>,
+    },
+    /// List a unisrv registry repository's tags, newest push first
+    Images {
+        /// Repository, e.g. team/app
+        repository: String,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+        /// Print each item through a template, e.g. '{{.tag}}\t{{.digest}}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Filter the JSON output through a jq expression, e.g. '.[].tag'
+        #[arg(long, value_name = "EXPR", conflicts_with = "format")]
+        jq: Option<String>,
+    },
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -193,20 +1804,285 @@ async fn main() {
         .without_time()
         .init();
 
-    let cli = Cli::parse();
-    let client = HttpApiClient::from_env();
+    let matches = Cli::command()
+        .try_get_matches()
+        .unwrap_or_else(|err| exit_usage(&err));
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| exit_usage(&err));
+    let path = settings::Settings::default_path();
+    let settings = match &path {
+        Some(path) => settings::Settings::load(path),
+        None => Ok(settings::Settings::default()),
+    };
+    let settings = match settings {
+        Ok(settings) => settings,
+        Err(err) => {
+            eprintln!("Error: {err:#}");
+            std::process::exit(1);
+        }
+    };
+    let flags = commands::config::Flags {
+        api_version: cli.api_version,
+        trace: cli.trace,
+        read_only: cli.read_only,
+        no_spinner: cli.no_spinner,
+        no_color: cli.no_color,
+        yes: cli.yes,
+        locale: cli.locale,
+    };
+    let config = Effective::resolve(path, &settings, flags, |name| std::env::var(name).ok());
+    if config.no_color.value {
+        commands::ui::disable_colors();
+    }
+    progress::set_narrated(config.no_spinner.value || config.no_color.value);
+    commands::prompt::set_non_interactive(config.non_interactive.value);
+    locale::set(config.locale.value.clone());
+    commands::registry::set_credential_helper(config.credential_helper.value.clone());
+    commands::regions::set_default(config.region.value.clone());
+    commands::registry::policy::set_allowed_registries(config.allowed_registries.clone());
+
+    let tape = match (&cli.record, &cli.replay) {
+        (Some(path), _) => Some(Tape::record(path)),
+        (None, Some(path)) => Some(Tape::replay(path)),
+        (None, None) => None,
+    };
+    let client = match tape.transpose() {
+        Ok(Some(tape)) => HttpApiClient::from_env().with_tape(tape),
+        Ok(None) => HttpApiClient::from_env(),
+        Err(err) => {
+            eprintln!("Error: {err:#}");
+            std::process::exit(1);
+        }
+    };
+    let trace = TraceContext::from_env()
+        .map(|parent| parent.child())
+        .or_else(|| config.trace.value.then(TraceContext::generate));
+    let client = match trace {
+        Some(trace) => client.with_trace(trace),
+        None => client,
+    };
+    let trace_id = client.trace_id();
+    let client = client
+        .with_api_version(config.api_version.value)
+        .with_read_only(config.read_only.value);
 
     let client: &dyn ApiClient = &client;
+    // Run on every TAB: never prompt, and leave it out of the metrics.
+    if let Commands::Complete { words } = &cli.command {
+        commands::prompt::set_non_interactive(true);
+        commands::completions::complete(client, Cli::command(), words).await;
+        return;
+    }
+    let started = std::time::Instant::now();
+    let at = chrono::Utc::now();
     let result = match cli.command {
+        Commands::Shell => shell(client, &config).await,
+        command => dispatch(client, &config, command, config.non_interactive.value).await,
+    };
+    let run = state::CommandRun {
+        at,
+        command: telemetry::command_path(&matches),
+        duration_ms: started.elapsed().as_millis() as u64,
+        success: result.is_ok(),
+    };
+    telemetry::report(&telemetry::reporters(&config), &run).await;
+    if let Err(err) = result {
+        let code = failure::report(&err, cli.error_format, &run.command, trace_id.as_deref());
+        std::process::exit(code);
+    }
+}
+
+/// Run one parsed command. `yes` is the global `--yes`, for the commands that skip their own question
+/// with it.
+async fn dispatch(
+    client: &dyn ApiClient,
+    config: &Effective,
+    command: Commands,
+    yes: bool,
+) -> anyhow::Result<()> {
+    match command {
         Commands::Login { username, password } => {
             commands::login::run(client, username.as_deref(), password.as_deref()).await
         }
+        Commands::Region { command } => {
+            // Bare `unisrv regions` is shorthand for `list`.
+            let command = command.unwrap_or(RegionCommands::List {
+                json: false,
+                format: None,
+                jq: None,
+            });
+            match command {
+                RegionCommands::List { json, format, jq } => {
+                    match Output::from_flags(json, format.as_deref(), jq.as_deref()) {
+                        Ok(output) => commands::regions::list(client, &output).await,
+                        Err(e) => Err(e),
+                    }
+                }
+                RegionCommands::Ping {
+                    count,
+                    json,
+                    format,
+                    jq,
+                } => match Output::from_flags(json, format.as_deref(), jq.as_deref()) {
+                    Ok(output) => {
+                        commands::regions::ping::ping(
+                            client,
+                            count,
+                            &commands::regions::ping::TcpProber,
+                            &output,
+                        )
+                        .await
+                    }
+                    Err(e) => Err(e),
+                },
+            }
+        }
+        Commands::Account { command } => {
+            let store = unisrv_api::AuthStore::new(&config.api_host.value);
+            match command {
+                AccountCommands::List { json, format, jq } => {
+                    match Output::from_flags(json, format.as_deref(), jq.as_deref()) {
+                        Ok(output) => commands::account::list(&store, &output),
+                        Err(e) => Err(e),
+                    }
+                }
+                AccountCommands::Switch { username } => {
+                    commands::account::switch(&store, &username)
+                }
+            }
+        }
         Commands::Auth { command } => match command {
-            AuthCommands::Token { json } => commands::auth::token(client, json).await,
+            AuthCommands::Token { json, jq } => match Output::from_flags(json, None, jq.as_deref())
+            {
+                Ok(output) => commands::auth::token(client, &output).await,
+                Err(e) => Err(e),
+            },
+            AuthCommands::CreateToken {
+                name,
+                expires,
+                scope,
+                json,
+            } => {
+                let output = if json { Output::Json } else { Output::Table };
+                commands::auth::create_token(client, &name, expires, scope, &output).await
+            }
+            AuthCommands::Tokens {
+                command: TokensCommands::List { json, format, jq },
+            } => match Output::from_flags(json, format.as_deref(), jq.as_deref()) {
+                Ok(output) => commands::auth::list_tokens(client, &output).await,
+                Err(e) => Err(e),
+            },
+            AuthCommands::Revoke { reference } => {
+                commands::auth::revoke(client, &reference, yes).await
+            }
+            AuthCommands::ServiceAccount { command } => match command {
+                ServiceAccountCommands::Create {
+                    name,
+                    scopes,
+                    expires,
+                    json,
+                } => {
+                    let output = if json { Output::Json } else { Output::Table };
+                    commands::service_account::create(client, &name, &scopes, expires, &output)
+                        .await
+                }
+                ServiceAccountCommands::List { json, format, jq } => {
+                    match Output::from_flags(json, format.as_deref(), jq.as_deref()) {
+                        Ok(output) => commands::service_account::list(client, &output).await,
+                        Err(e) => Err(e),
+                    }
+                }
+                ServiceAccountCommands::Delete { reference } => {
+                    commands::service_account::delete(client, &reference, yes).await
+                }
+            },
         },
         Commands::Host { command } => match command {
             HostCommands::Claim { hostname } => commands::host::claim(client, &hostname).await,
-            HostCommands::List { json } => commands::host::list(client, json).await,
+            HostCommands::Cert { hostname, wildcard } => {
+                commands::host::cert::cert(
+                    client,
+                    &hostname,
+                    wildcard,
+                    &commands::host::dns::SystemResolver,
+                    &commands::up::apply::RealWaiter,
+                    &progress::SpinnerProgress::new(),
+                )
+                .await
+            }
+            HostCommands::Check { hostname, json, jq } => {
+                match Output::from_flags(json, None, jq.as_deref()) {
+                    Ok(output) => {
+                        use commands::host::dns::SystemResolver;
+                        commands::host::check::check(client, &SystemResolver, &hostname, &output)
+                            .await
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+            HostCommands::Record { command } => match command {
+                HostRecordCommands::Add {
+                    hostname,
+                    name,
+                    record_type,
+                    value,
+                    ttl,
+                } => {
+                    commands::host::record::add(client, &hostname, &name, &record_type, &value, ttl)
+                        .await
+                }
+                HostRecordCommands::List {
+                    hostname,
+                    json,
+                    format,
+                    jq,
+                } => match Output::from_flags(json, format.as_deref(), jq.as_deref()) {
+                    Ok(output) => commands::host::record::list(client, &hostname, &output).await,
+                    Err(e) => Err(e),
+                },
+                HostRecordCommands::Remove {
+                    hostname,
+                    record,
+                    record_type,
+                } => {
+                    commands::host::record::remove(
+                        client,
+                        &hostname,
+                        &record,
+                        record_type.as_deref(),
+                    )
+                    .await
+                }
+            },
+            HostCommands::Show {
+                hostname,
+                json,
+                format,
+                jq,
+            } => match Output::from_flags(json, format.as_deref(), jq.as_deref()) {
+                Ok(output) => commands::host::show::show(client, &hostname, &output).await,
+                Err(e) => Err(e),
+            },
+            HostCommands::CertStatus {
+                expiring_within,
+                json,
+                format,
+                jq,
+            } => match Output::from_flags(json, format.as_deref(), jq.as_deref()) {
+                Ok(output) => {
+                    commands::host::expiry::cert_status(client, &expiring_within, &output).await
+                }
+                Err(e) => Err(e),
+            },
+            HostCommands::List {
+                json,
+                format,
+                jq,
+                quiet,
+                table,
+            } => match list_output(json, format, jq, quiet) {
+                Ok(output) => commands::host::list(client, &output, &table.options()).await,
+                Err(e) => Err(e),
+            },
         },
         Commands::Registry { command } => match command {
             RegistryCommands::Add {
@@ -224,7 +2100,12 @@ async fn main() {
                 )
                 .await
             }
-            RegistryCommands::List { json } => commands::registry::list(client, json).await,
+            RegistryCommands::List { json, format, jq } => {
+                match Output::from_flags(json, format.as_deref(), jq.as_deref()) {
+                    Ok(output) => commands::registry::list(client, &output).await,
+                    Err(e) => Err(e),
+                }
+            }
             RegistryCommands::Update {
                 hostname,
                 username,
@@ -240,57 +2121,1007 @@ async fn main() {
                 )
                 .await
             }
-            RegistryCommands::Delete { hostname, yes } => {
-                commands::registry::delete(client, &hostname, yes).await
+            RegistryCommands::Delete { target } => {
+                if commands::registry::hosted::is_tag_reference(&target) {
+                    commands::registry::hosted::delete_tag(client, &target, yes).await
+                } else {
+                    commands::registry::delete(client, &target, yes).await
+                }
             }
             RegistryCommands::Test { hostname } => {
                 commands::registry::test(client, &hostname).await
             }
+            RegistryCommands::ImportDocker { path, no_validate } => {
+                commands::registry::docker::import(client, path.as_deref(), !no_validate).await
+            }
+            RegistryCommands::Tags { image, json } => {
+                commands::registry::tags::tags(client, &image, json).await
+            }
+            RegistryCommands::Sbom { image, json, jq } => {
+                match Output::from_flags(json, None, jq.as_deref()) {
+                    Ok(output) => {
+                        let kind = commands::registry::attestations::Kind::Sbom;
+                        commands::registry::attestations::show(client, &image, kind, &output).await
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+            RegistryCommands::Provenance { image, json, jq } => {
+                match Output::from_flags(json, None, jq.as_deref()) {
+                    Ok(output) => {
+                        let kind = commands::registry::attestations::Kind::Provenance;
+                        commands::registry::attestations::show(client, &image, kind, &output).await
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+            RegistryCommands::Push { local, remote } => {
+                commands::registry::push::push(client, &local, &remote).await
+            }
+            RegistryCommands::Repos { json, format, jq } => {
+                match Output::from_flags(json, format.as_deref(), jq.as_deref()) {
+                    Ok(output) => commands::registry::hosted::repos(client, &output).await,
+                    Err(e) => Err(e),
+                }
+            }
+            RegistryCommands::Images {
+                repository,
+                json,
+                format,
+                jq,
+            } => match Output::from_flags(json, format.as_deref(), jq.as_deref()) {
+                Ok(output) => {
+                    commands::registry::hosted::images(client, &repository, &output).await
+                }
+                Err(e) => Err(e),
+            },
         },
         Commands::Up {
             env,
             vars,
             var_files,
-        } => commands::up::run(client, env.as_deref(), &vars, &var_files).await,
+            timeout,
+            rollback_on_failure,
+            update_images,
+            adopt,
+            replace,
+            targets,
+            excludes,
+        } => {
+            let gates = commands::up::run::Gates {
+                timeout,
+                rollback_on_failure,
+                update_images,
+                collisions: commands::up::collisions::Resolution::from_flags(adopt, replace),
+                targeting: commands::up::targeting::Targeting { targets, excludes },
+                yes,
+            };
+            commands::up::run(client, env.as_deref(), &vars, &var_files, &gates).await
+        }
+        Commands::Plan {
+            env,
+            vars,
+            var_files,
+            update_images,
+            adopt,
+            replace,
+            targets,
+            excludes,
+            json,
+            jq,
+        } => {
+            let output = Output::from_flags(json, None, jq.as_deref())?;
+            commands::up::preview::plan(
+                client,
+                env.as_deref(),
+                &vars,
+                &var_files,
+                update_images,
+                commands::up::collisions::Resolution::from_flags(adopt, replace),
+                &commands::up::targeting::Targeting { targets, excludes },
+                &output,
+            )
+            .await
+        }
+        Commands::Output { name, env, json } => {
+            commands::up::outputs::show(name.as_deref(), env.as_deref(), json)
+        }
         Commands::Destroy { env } => commands::destroy::run(client, env.as_deref()).await,
         Commands::Instance { command } => {
+            use commands::instance::launch::{LaunchOptions, parse_container, parse_metadata};
+            use commands::instance::logs::parse_between;
             use commands::instance::run::{InstanceAction, run};
+            use commands::instance::sizing::SizingFlags;
+            use commands::networks::NetworkSpec;
+            use commands::up::vars::parse_assignment;
             // Bare `unisrv instance` is shorthand for an unfiltered `list`.
             let command = command.unwrap_or(InstanceCommands::List {
                 all: false,
                 json: false,
+                format: None,
+                jq: None,
+                quiet: false,
+                table: TableFlags::default(),
+                watch: None,
                 env: None,
             });
             match command {
-                InstanceCommands::List { all, json, env } => {
-                    run(client, env.as_deref(), InstanceAction::List { all, json }).await
-                }
-                InstanceCommands::Logs {
+                InstanceCommands::List {
+                    all,
+                    json,
+                    format,
+                    jq,
+                    quiet,
+                    table,
+                    watch,
+                    env,
+                } => match list_output(json, format, jq, quiet) {
+                    Ok(output) => {
+                        let table = table.options();
+                        let action = InstanceAction::List {
+                            all,
+                            output,
+                            table,
+                            watch,
+                        };
+                        run(client, env.as_deref(), action).await
+                    }
+                    Err(e) => Err(e),
+                },
+                InstanceCommands::Show {
                     reference,
-                    follow,
+                    json,
+                    format,
+                    jq,
+                    env,
+                } => match Output::from_flags(json, format.as_deref(), jq.as_deref()) {
+                    Ok(output) => {
+                        run(
+                            client,
+                            env.as_deref(),
+                            InstanceAction::Show { reference, output },
+                        )
+                        .await
+                    }
+                    Err(e) => Err(e),
+                },
+                InstanceCommands::Stop {
+                    reference,
+                    keep,
                     env,
                 } => {
                     run(
                         client,
                         env.as_deref(),
-                        InstanceAction::Logs { reference, follow },
+                        InstanceAction::Stop {
+                            reference,
+                            keep,
+                            yes,
+                        },
+                    )
+                    .await
+                }
+                InstanceCommands::ConnectionString {
+                    reference,
+                    port,
+                    json,
+                    format,
+                    jq,
+                    env,
+                } => match Output::from_flags(json, format.as_deref(), jq.as_deref()) {
+                    Ok(output) => {
+                        let action = InstanceAction::ConnectionString {
+                            reference,
+                            port,
+                            output,
+                        };
+                        run(client, env.as_deref(), action).await
+                    }
+                    Err(e) => Err(e),
+                },
+                InstanceCommands::Restore { reference, env } => {
+                    run(
+                        client,
+                        env.as_deref(),
+                        InstanceAction::Restore { reference },
                     )
                     .await
                 }
+                InstanceCommands::Logs {
+                    reference,
+                    previous,
+                    follow,
+                    between,
+                    grep,
+                    table,
+                    env,
+                } => {
+                    let search = (|| -> anyhow::Result<Option<LogSearch>> {
+                        if between.is_none() && grep.is_none() {
+                            return Ok(None);
+                        }
+                        let window = between.as_deref().map(parse_between).transpose()?;
+                        Ok(Some(LogSearch {
+                            from_ms: window.map(|(from, _)| from),
+                            to_ms: window.map(|(_, to)| to),
+                            grep,
+                        }))
+                    })();
+                    match search {
+                        Ok(search) => {
+                            run(
+                                client,
+                                env.as_deref(),
+                                InstanceAction::Logs {
+                                    reference,
+                                    previous,
+                                    follow,
+                                    search,
+                                    table,
+                                },
+                            )
+                            .await
+                        }
+                        Err(e) => Err(e),
+                    }
+                }
+                InstanceCommands::Run {
+                    image,
+                    name,
+                    env_vars,
+                    network,
+                    ip_from_pool,
+                    vcpus,
+                    cpu_request,
+                    memory,
+                    memory_request,
+                    hostname,
+                    metadata,
+                    init_images,
+                    sidecars,
+                    platform,
+                    region,
+                    skip_image_verify,
+                    verify_signature,
+                    certificate_identity,
+                    certificate_oidc_issuer,
+                    wait,
+                    no_default_env,
+                    env,
+                    args,
+                } => {
+                    let parsed = (|| -> anyhow::Result<LaunchOptions> {
+                        let env_vars = env_vars
+                            .iter()
+                            .map(|s| parse_assignment(s))
+                            .collect::<anyhow::Result<_>>()?;
+                        let metadata = metadata
+                            .iter()
+                            .map(|s| parse_metadata(s))
+                            .collect::<anyhow::Result<_>>()?;
+                        let init_containers = init_images
+                            .iter()
+                            .map(|s| parse_container(s))
+                            .collect::<anyhow::Result<_>>()?;
+                        let sidecars = sidecars
+                            .iter()
+                            .map(|s| parse_container(s))
+                            .collect::<anyhow::Result<_>>()?;
+                        let network = network.as_deref().map(NetworkSpec::parse).transpose()?;
+                        Ok(LaunchOptions {
+                            image,
+                            name,
+                            env_vars,
+                            args,
+                            network,
+                            ip_from_pool,
+                            sizing: SizingFlags {
+                                cpu_limit: vcpus,
+                                cpu_request,
+                                memory_limit_mb: memory,
+                                memory_request_mb: memory_request,
+                            },
+                            hostname,
+                            metadata,
+                            init_containers,
+                            sidecars,
+                            platform: platform.or_else(|| {
+                                // Only a configured default is asked for;
+                                // the built-in one is the server's own.
+                                (config.platform.source != commands::config::Source::Default)
+                                    .then(|| config.platform.value.clone())
+                            }),
+                            region: region.unwrap_or_else(|| config.region.value.clone()),
+                            skip_image_verify,
+                            signer: certificate_identity
+                                .zip(certificate_oidc_issuer)
+                                .filter(|_| verify_signature)
+                                .map(|(identity, issuer)| commands::instance::signature::Signer {
+                                    identity,
+                                    issuer,
+                                }),
+                            wait,
+                            default_env: (!no_default_env)
+                                .then(|| default_env::for_active_account(&config.api_host.value))
+                                .transpose()?,
+                        })
+                    })();
+                    match parsed {
+                        Ok(opts) => {
+                            run(client, env.as_deref(), InstanceAction::Run(Box::new(opts))).await
+                        }
+                        Err(e) => Err(e),
+                    }
+                }
             }
         }
-    };
+        Commands::Worker {
+            command:
+                WorkerCommands::Run {
+                    module,
+                    name,
+                    env_vars,
+                    memory,
+                    cpu_time_ms,
+                    region,
+                    follow,
+                    env,
+                    args,
+                },
+        } => {
+            use commands::instance::run::{announce_environment, current_environment};
+            use commands::worker::WorkerOptions;
+            let env_vars = env_vars
+                .iter()
+                .map(|s| commands::up::vars::parse_assignment(s))
+                .collect::<anyhow::Result<_>>();
+            match env_vars {
+                Ok(env_vars) => match current_environment(client, env.as_deref()).await {
+                    Ok(resolved) => {
+                        announce_environment(&resolved);
+                        let opts = WorkerOptions {
+                            module,
+                            name,
+                            env_vars,
+                            args,
+                            memory_mb: memory,
+                            cpu_time_ms,
+                            region: region.unwrap_or_else(|| config.region.value.clone()),
+                            follow,
+                        };
+                        commands::worker::run(client, &resolved, opts).await
+                    }
+                    Err(e) => Err(e),
+                },
+                Err(e) => Err(e),
+            }
+        }
+        Commands::Service { command } => {
+            use commands::service::clone::CloneOptions;
+            use commands::service::location::{CacheChange, new_location};
+            use commands::service::new::NewHttpOptions;
+            use commands::service::run::{ServiceAction, run};
+            use commands::service::tls::TlsChange;
+            use commands::service::update::UpdateChange;
+            use unisrv_api::models::HTTPLocationTarget;
+            let selected = match command {
+                ServiceCommands::List {
+                    json,
+                    format,
+                    jq,
+                    quiet,
+                    table,
+                    env,
+                } => list_output(json, format, jq, quiet).map(|output| {
+                    let table = table.options();
+                    (env, ServiceAction::List { output, table })
+                }),
+                ServiceCommands::Show {
+                    reference,
+                    json,
+                    format,
+                    jq,
+                    watch,
+                    env,
+                } => Output::from_flags(json, format.as_deref(), jq.as_deref()).map(|output| {
+                    (
+                        env,
+                        ServiceAction::Show {
+                            reference,
+                            output,
+                            watch,
+                        },
+                    )
+                }),
+                ServiceCommands::Targets {
+                    reference,
+                    unhealthy,
+                    json,
+                    format,
+                    jq,
+                    env,
+                } => Output::from_flags(json, format.as_deref(), jq.as_deref()).map(|output| {
+                    (
+                        env,
+                        ServiceAction::Targets {
+                            reference,
+                            unhealthy,
+                            output,
+                        },
+                    )
+                }),
+                ServiceCommands::Stats {
+                    reference,
+                    window,
+                    watch,
+                    env,
+                } => Ok((
+                    env,
+                    ServiceAction::Stats {
+                        reference,
+                        window,
+                        watch,
+                    },
+                )),
+                ServiceCommands::Clone {
+                    reference,
+                    name,
+                    hosts,
+                    with_targets,
+                    region,
+                    env,
+                } => Ok((
+                    env,
+                    ServiceAction::Clone {
+                        reference,
+                        options: CloneOptions {
+                            name,
+                            hosts,
+                            with_targets,
+                            region: region.unwrap_or_else(|| config.region.value.clone()),
+                        },
+                    },
+                )),
+                ServiceCommands::Tls {
+                    reference,
+                    min_version,
+                    hsts,
+                    no_hsts,
+                    redirect_http,
+                    allow_http,
+                    env,
+                } => {
+                    // A flag pair maps to "set on", "set off" or "leave as is".
+                    let toggle = |on: bool, off: bool| (on || off).then_some(on);
+                    Ok((
+                        env,
+                        ServiceAction::Tls {
+                            reference,
+                            change: TlsChange {
+                                min_version,
+                                hsts: toggle(hsts, no_hsts),
+                                redirect_http: toggle(redirect_http, allow_http),
+                            },
+                        },
+                    ))
+                }
+                ServiceCommands::Protect {
+                    reference,
+                    basic_auth,
+                    no_basic_auth,
+                    allow_ips,
+                    no_allow_ip,
+                    env,
+                } => {
+                    // Values replace the list, the --no- flag clears it, and
+                    // neither leaves it as it is.
+                    fn replace<T>(values: Vec<T>, clear: bool) -> Option<Vec<T>> {
+                        (clear || !values.is_empty()).then_some(values)
+                    }
+                    Ok((
+                        env,
+                        ServiceAction::Protect {
+                            reference,
+                            change: commands::service::protect::ProtectChange {
+                                basic_auth: replace(basic_auth, no_basic_auth),
+                                allow_ips: replace(allow_ips, no_allow_ip),
+                            },
+                        },
+                    ))
+                }
+                ServiceCommands::Update {
+                    reference,
+                    compression,
+                    env,
+                } => Ok((
+                    env,
+                    ServiceAction::Update {
+                        reference,
+                        change: UpdateChange { compression },
+                    },
+                )),
+                ServiceCommands::New { command } => match command {
+                    ServiceNewCommands::Http {
+                        name,
+                        from_cert_host,
+                        region,
+                        compression,
+                        env,
+                    } => Ok((
+                        env,
+                        ServiceAction::NewHttp {
+                            options: NewHttpOptions {
+                                name,
+                                from_cert_host,
+                                region: region.unwrap_or_else(|| config.region.value.clone()),
+                                compression,
+                            },
+                        },
+                    )),
+                },
+                ServiceCommands::Host { command } => match command {
+                    ServiceHostCommands::Add {
+                        reference,
+                        host,
+                        env,
+                    } => Ok((env, ServiceAction::HostAdd { reference, host })),
+                    ServiceHostCommands::Remove {
+                        reference,
+                        host,
+                        env,
+                    } => Ok((env, ServiceAction::HostRemove { reference, host })),
+                },
+                ServiceCommands::Location { command } => match command {
+                    ServiceLocationCommands::Add {
+                        reference,
+                        path,
+                        group,
+                        url,
+                        redirect_to,
+                        rewrite,
+                        strip_prefix,
+                        websocket,
+                        grpc,
+                        headers,
+                        env,
+                    } => {
+                        let target = match (url, redirect_to) {
+                            (Some(url), _) => HTTPLocationTarget::Url { url },
+                            (_, Some(url)) => HTTPLocationTarget::Redirect { url },
+                            (None, None) => HTTPLocationTarget::Instance {
+                                group: group.unwrap_or_else(|| {
+                                    commands::up::defaults::DEFAULT_TARGET_GROUP.to_string()
+                                }),
+                                rewrite: rewrite.or(strip_prefix.then(|| "/".to_string())),
+                            },
+                        };
+                        let mut location =
+                            new_location(path, target, headers.into_iter().collect());
+                        location.websocket = websocket;
+                        location.grpc = grpc;
+                        Ok((
+                            env,
+                            ServiceAction::LocationAdd {
+                                reference,
+                                location,
+                            },
+                        ))
+                    }
+                    ServiceLocationCommands::Cache {
+                        reference,
+                        path,
+                        ttl,
+                        stale_while_revalidate,
+                        off: _,
+                        env,
+                    } => {
+                        let change = match ttl {
+                            Some(ttl) => CacheChange::Set {
+                                ttl,
+                                stale_while_revalidate,
+                            },
+                            None => CacheChange::Off,
+                        };
+                        Ok((
+                            env,
+                            ServiceAction::LocationCache {
+                                reference,
+                                path,
+                                change,
+                            },
+                        ))
+                    }
+                },
+                ServiceCommands::Target { command } => match command {
+                    ServiceTargetCommands::Add {
+                        reference,
+                        instance,
+                        port,
+                        group,
+                        env,
+                    } => Ok((
+                        env,
+                        ServiceAction::TargetAdd {
+                            reference,
+                            instance,
+                            port,
+                            group,
+                        },
+                    )),
+                },
+            };
+            match selected {
+                Ok((env, action)) => run(client, env.as_deref(), action).await,
+                Err(e) => Err(e),
+            }
+        }
+        Commands::Network { command } => {
+            use commands::networks::create::CidrChoice;
+            use commands::networks::run::{NetworkAction, run};
+            let selected = match command {
+                NetworkCommands::List {
+                    json,
+                    format,
+                    jq,
+                    quiet,
+                    table,
+                    env,
+                } => list_output(json, format, jq, quiet).map(|output| {
+                    let table = table.options();
+                    (env, NetworkAction::List { output, table })
+                }),
+                NetworkCommands::New {
+                    name,
+                    cidr,
+                    auto_cidr,
+                    range,
+                    env,
+                } => {
+                    let cidr = match (cidr, auto_cidr) {
+                        (Some(cidr), _) => CidrChoice::Explicit(cidr),
+                        (None, true) => CidrChoice::Auto { range },
+                        (None, false) => CidrChoice::Default,
+                    };
+                    Ok((env, NetworkAction::New { name, cidr }))
+                }
+                NetworkCommands::Show {
+                    network,
+                    json,
+                    format,
+                    jq,
+                    watch,
+                    env,
+                } => Output::from_flags(json, format.as_deref(), jq.as_deref()).map(|output| {
+                    (
+                        env,
+                        NetworkAction::Show {
+                            network,
+                            output,
+                            watch,
+                        },
+                    )
+                }),
+                NetworkCommands::Ips {
+                    network,
+                    json,
+                    format,
+                    jq,
+                    env,
+                } => Output::from_flags(json, format.as_deref(), jq.as_deref())
+                    .map(|output| (env, NetworkAction::Ips { network, output })),
+                NetworkCommands::Attach {
+                    network,
+                    instance,
+                    ip,
+                    env,
+                } => Ok((
+                    env,
+                    NetworkAction::Attach {
+                        network,
+                        instance,
+                        ip,
+                    },
+                )),
+                NetworkCommands::Detach {
+                    network,
+                    instance,
+                    env,
+                } => Ok((env, NetworkAction::Detach { network, instance })),
+                NetworkCommands::Reserve {
+                    network,
+                    ip,
+                    name,
+                    env,
+                } => Ok((env, NetworkAction::Reserve { network, ip, name })),
+                NetworkCommands::Reservations {
+                    network,
+                    json,
+                    format,
+                    jq,
+                    env,
+                } => Output::from_flags(json, format.as_deref(), jq.as_deref())
+                    .map(|output| (env, NetworkAction::Reservations { network, output })),
+                NetworkCommands::Peer { network, peer, env } => {
+                    Ok((env, NetworkAction::Peer { network, peer }))
+                }
+                NetworkCommands::Unpeer { network, peer, env } => {
+                    Ok((env, NetworkAction::Unpeer { network, peer }))
+                }
+                NetworkCommands::Policy { command } => match command {
+                    NetworkPolicyCommands::Add {
+                        network,
+                        from,
+                        to,
+                        allow,
+                        deny: _,
+                        env,
+                    } => Ok((
+                        env,
+                        NetworkAction::PolicyAdd {
+                            network,
+                            from,
+                            to,
+                            // clap requires exactly one of --allow / --deny.
+                            action: if allow {
+                                PolicyAction::Allow
+                            } else {
+                                PolicyAction::Deny
+                            },
+                        },
+                    )),
+                    NetworkPolicyCommands::List {
+                        network,
+                        json,
+                        format,
+                        jq,
+                        env,
+                    } => Output::from_flags(json, format.as_deref(), jq.as_deref())
+                        .map(|output| (env, NetworkAction::PolicyList { network, output })),
+                    NetworkPolicyCommands::Delete { network, rule, env } => {
+                        Ok((env, NetworkAction::PolicyDelete { network, rule }))
+                    }
+                },
+                NetworkCommands::Connect {
+                    network,
+                    name,
+                    no_apply,
+                    env,
+                } => Ok((
+                    env,
+                    NetworkAction::Connect {
+                        network,
+                        name,
+                        apply: !no_apply,
+                    },
+                )),
+                NetworkCommands::Disconnect { network, env } => {
+                    Ok((env, NetworkAction::Disconnect { network }))
+                }
+                NetworkCommands::Status {
+                    json,
+                    format,
+                    jq,
+                    env,
+                } => Output::from_flags(json, format.as_deref(), jq.as_deref())
+                    .map(|output| (env, NetworkAction::Status { output })),
+            };
+            match selected {
+                Ok((env, action)) => run(client, env.as_deref(), action).await,
+                Err(e) => Err(e),
+            }
+        }
+        Commands::Logs {
+            app,
+            service,
+            follow,
+            env,
+        } => {
+            use commands::logs::{Source, run};
+            // clap guarantees exactly one of the two.
+            let source = match (app, service) {
+                (Some(app), _) => Source::App(app),
+                (None, Some(service)) => Source::Service(service),
+                (None, None) => unreachable!("--app or --service is required"),
+            };
+            run(client, env.as_deref(), source, follow).await
+        }
+        Commands::Rollout { command } => match command {
+            RolloutCommands::Deploy {
+                deployment,
+                image,
+                strategy,
+                steps,
+                interval,
+                max_unhealthy,
+                timeout,
+                no_default_env,
+                yes,
+                env,
+            } => {
+                let canary = commands::rollout::canary::Canary {
+                    steps,
+                    interval,
+                    max_unhealthy,
+                };
+                match (!no_default_env)
+                    .then(|| default_env::for_active_account(&config.api_host.value))
+                    .transpose()
+                {
+                    Ok(defaults) => {
+                        commands::rollout::deploy(
+                            client,
+                            env.as_deref(),
+                            &deployment,
+                            &image,
+                            strategy,
+                            &canary,
+                            defaults.as_ref(),
+                            timeout,
+                            yes,
+                        )
+                        .await
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+            RolloutCommands::Promote {
+                service,
+                timeout,
+                yes,
+                env,
+            } => {
+                commands::rollout::blue_green::promote(
+                    client,
+                    env.as_deref(),
+                    &service,
+                    timeout,
+                    yes,
+                )
+                .await
+            }
+            RolloutCommands::Abort { service, yes, env } => {
+                commands::rollout::blue_green::abort(client, env.as_deref(), &service, yes).await
+            }
+            RolloutCommands::History { deployment, env } => {
+                commands::rollout::history(client, env.as_deref(), &deployment).await
+            }
+            RolloutCommands::Rollback {
+                deployment,
+                to,
+                timeout,
+                yes,
+                env,
+            } => {
+                commands::rollout::rollback(
+                    client,
+                    env.as_deref(),
+                    &deployment,
+                    to.as_deref(),
+                    timeout,
+                    yes,
+                )
+                .await
+            }
+        },
+        Commands::Env { command } => {
+            match (
+                default_env::DefaultEnvStore::default_path(),
+                default_env::active_account(&config.api_host.value),
+            ) {
+                (None, _) => Err(anyhow::anyhow!(
+                    "can't determine the home directory to keep default environment variables in"
+                )),
+                (_, Err(e)) => Err(e),
+                (Some(path), Ok(account)) => {
+                    let store = default_env::DefaultEnvStore::new(path);
+                    match command {
+                        EnvCommands::SetDefault { vars, project } => {
+                            commands::env::set_default(&store, &account, project.as_deref(), &vars)
+                        }
+                        EnvCommands::UnsetDefault { keys, project } => {
+                            commands::env::unset_default(
+                                &store,
+                                &account,
+                                project.as_deref(),
+                                &keys,
+                            )
+                        }
+                        EnvCommands::Defaults => commands::env::list_defaults(&store, &account),
+                    }
+                }
+            }
+        }
+        Commands::State { command } => match command {
+            StateCommands::Clear => commands::state::clear(),
+            StateCommands::Path => commands::state::path(),
+        },
+        Commands::Report { since, output } => commands::report::report(client, since, output).await,
+        Commands::Stats { command } => match command {
+            StatsCommands::Me { json, format, jq } => {
+                match Output::from_flags(json, format.as_deref(), jq.as_deref()) {
+                    Ok(output) => commands::stats::show_self(&output),
+                    Err(e) => Err(e),
+                }
+            }
+        },
+        Commands::Import { command } => match command {
+            ImportCommands::Instance {
+                reference,
+                name,
+                env,
+            } => commands::import::instance(client, env.as_deref(), &reference, &name).await,
+        },
+        Commands::Config { command } => match command {
+            ConfigCommands::Show {
+                effective,
+                json,
+                format,
+                jq,
+            } => match Output::from_flags(json, format.as_deref(), jq.as_deref()) {
+                Ok(output) => commands::config::show(config, effective, &output),
+                Err(e) => Err(e),
+            },
+        },
+        Commands::Shell => anyhow::bail!("already in `unisrv shell`"),
+        Commands::Completions { shell } => commands::completions::print(shell, Cli::command()),
+        Commands::Complete { words } => {
+            commands::completions::complete(client, Cli::command(), &words).await;
+            Ok(())
+        }
+    }
+}
 
-    if let Err(err) = result {
-        if let Some(parse_err) = err.downcast_ref::<ConfigParseError>() {
-            eprint!("{parse_err}");
-        } else if let Some(ApiError::AuthRequired(msg)) = err.downcast_ref::<ApiError>() {
-            eprintln!("Error: {msg}");
-        } else if let Some(ApiError::Server { status, reason }) = err.downcast_ref::<ApiError>() {
-            eprintln!("Error ({status}): {reason}");
-        } else {
-            eprintln!("Error: {err:#}");
+/// Read commands until the user leaves, reporting each one's error without
+/// ending the session.
+#[cfg(feature = "interactive")]
+async fn shell(client: &dyn ApiClient, config: &Effective) -> anyhow::Result<()> {
+    use commands::shell::{CACHE_MAX_AGE, Shell};
+    use unisrv_api::CachingClient;
+    let client = CachingClient::new(client, CACHE_MAX_AGE);
+    let mut shell = Shell::start(&client, ShellLine::command()).await?;
+    while let Some(words) = shell.next_command(&client).await {
+        match ShellLine::try_parse_from(words) {
+            Ok(line) => {
+                let yes = config.non_interactive.value || line.yes;
+                if let Err(err) = dispatch(&client, config, line.command, yes).await {
+                    failure::print_text(&err);
+                }
+            }
+            // Includes `help` and `--help`, which clap reports as errors.
+            Err(e) => {
+                let _ = e.print();
+            }
         }
-        std::process::exit(1);
     }
+    Ok(())
+}
+
+#[cfg(not(feature = "interactive"))]
+async fn shell(_client: &dyn ApiClient, _config: &Effective) -> anyhow::Result<()> {
+    anyhow::bail!("`unisrv shell` isn't in this build, which has no interactive prompts")
+}
+
+/// End on a command line that didn't parse, or on `--help`/`--version`,
+/// which clap reports the same way but print to stdout and succeed.
+fn exit_usage(err: &clap::Error) -> ! {
+    let _ = err.print();
+    let code = if err.use_stderr() {
+        failure::USAGE_EXIT_CODE
+    } else {
+        0
+    };
+    std::process::exit(code)
+}
+
+/// How a list command prints: `-q` lists bare ids, else the usual flags.
+fn list_output(
+    json: bool,
+    format: Option<String>,
+    jq: Option<String>,
+    quiet: bool,
+) -> anyhow::Result<Output> {
+    if quiet {
+        return Ok(Output::ids());
+    }
+    Output::from_flags(json, format.as_deref(), jq.as_deref())
 }
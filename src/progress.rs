@@ -2,19 +2,54 @@
 //!
 //! A [`Progress`] is injected like the [`Prompter`](crate::commands::up::env_resolve::Prompter)
 //! and `Waiter` seams so tests run silent. Each network step opens a
-//! [`Step`]: an animated spinner on a TTY, plain lines when piped, nothing in
-//! tests. The [`Step`] owns both the transient spinner *and* the permanent
+//! [`Step`]: an animated spinner on a TTY, nothing in tests, and otherwise a
+//! numbered status line — `step 2: Creating service web…` — repeated at most
+//! every [`NARRATE_INTERVAL`] while it waits. Status lines replace the spinner
+//! when stderr isn't a terminal or `CI` is set, timestamped so a CI log shows
+//! when each step started, and with `--no-spinner` or `--no-color`
+//! ([`set_narrated`]), where no line carries an emoji either since a screen
+//! reader reads those out. Result lines also drop the emoji when stdout has
+//! no colour, e.g. piped to a file.
+//! The [`Step`] owns both the transient spinner *and* the permanent
 //! result line — drop it without a terminal call and it reports a failure, so
 //! any `?` early-return self-reports which step was in flight.
 //!
-//! Streams: spinner → stderr (auto-hidden off-TTY); result lines → stdout
+//! Streams: spinner and status lines → stderr; result lines → stdout
 //! (so a piped run still gets the `+`/`~`/`-` audit log). Colour is gated on
 //! stdout, animation on stderr — they can differ.
+//!
+//! Steps may overlap — `up` creates independent resources concurrently — so
+//! spinners stack in one `MultiProgress`, and result lines are printed with
+//! it suspended so they land above the spinners still running.
+//!
+//! Spinners come from indicatif, behind the `progress` feature; a build
+//! without it always narrates.
 
-use std::time::Duration;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
+use chrono::{DateTime, Utc};
 use console::style;
-use indicatif::{ProgressBar, ProgressStyle};
+#[cfg(feature = "progress")]
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+
+/// How often a narrated step repeats its status while it waits.
+pub const NARRATE_INTERVAL: Duration = Duration::from_secs(10);
+
+static NARRATED: AtomicBool = AtomicBool::new(false);
+
+/// Narrate steps as plain status lines instead of animating them, for the
+/// rest of the process. Set from `--no-spinner` and `--no-color`.
+pub fn set_narrated(on: bool) {
+    NARRATED.store(on, Ordering::Relaxed);
+}
+
+/// Whether [`set_narrated`] was, so other animations (transfer bars) stay
+/// still too.
+pub fn narrated() -> bool {
+    NARRATED.load(Ordering::Relaxed)
+}
 
 /// The resource a step acts on. Picks the leading emoji.
 #[derive(Clone, Copy)]
@@ -66,10 +101,19 @@ impl Tone {
     }
 }
 
+/// `{emoji} ` for `icon`, or nothing when narrating.
+fn lead(icon: Icon, emoji: bool) -> String {
+    if emoji {
+        format!("{} ", icon.emoji())
+    } else {
+        String::new()
+    }
+}
+
 /// Render the permanent success line: `  {sigil} {emoji} {summary}`. Pure so it
 /// can be unit-tested without a terminal; the sigil is coloured per [`Tone`]
 /// only when `color` is set.
-fn success_line(icon: Icon, tone: Tone, summary: &str, color: bool) -> String {
+fn success_line(icon: Icon, tone: Tone, summary: &str, emoji: bool, color: bool) -> String {
     let sigil = if color {
         let s = style(tone.sigil());
         let s = match tone {
@@ -83,20 +127,57 @@ fn success_line(icon: Icon, tone: Tone, summary: &str, color: bool) -> String {
     } else {
         tone.sigil().to_string()
     };
-    format!("  {sigil} {} {summary}", icon.emoji())
+    format!("  {sigil} {}{summary}", lead(icon, emoji))
 }
 
 /// Render the failure line for a step dropped before completion: a red `✗`,
 /// the resource emoji, and the step's in-flight (active) message.
-fn failure_line(icon: Icon, active: &str, color: bool) -> String {
+fn failure_line(icon: Icon, active: &str, emoji: bool, color: bool) -> String {
     let mark = if color {
         style("✗").red().to_string()
     } else {
         "✗".to_string()
     };
-    format!("  {mark} {} {active}", icon.emoji())
+    format!("  {mark} {}{active}", lead(icon, emoji))
 }
 
+/// A narrated step's status line, e.g. `step 2: Creating service web…`,
+/// behind the time when `at` is given.
+fn status_line(number: usize, active: &str, at: Option<DateTime<Utc>>) -> String {
+    let line = format!("step {number}: {active}\u{2026}");
+    match at {
+        Some(at) => format!("{} {line}", at.format("%Y-%m-%dT%H:%M:%SZ")),
+        None => line,
+    }
+}
+
+/// How steps show: animated, or narrated with or without timestamps.
+#[derive(Debug, PartialEq)]
+struct Narration {
+    animate: bool,
+    timestamps: bool,
+    emoji: bool,
+}
+
+/// Animate only on an attended terminal outside CI and without
+/// `--no-spinner`. Timestamps are for logs, emoji-free lines for
+/// `--no-spinner`.
+fn narration(no_spinner: bool, ci: bool, stderr_tty: bool) -> Narration {
+    let logging = ci || !stderr_tty;
+    Narration {
+        animate: !no_spinner && !logging,
+        timestamps: logging,
+        emoji: !no_spinner,
+    }
+}
+
+/// Whether a `CI` variable with this value means we're running in CI, as
+/// GitHub Actions, GitLab and most others set it.
+fn is_ci(value: Option<&str>) -> bool {
+    value.is_some_and(|v| !v.is_empty() && v != "false" && v != "0")
+}
+
+#[cfg(feature = "progress")]
 fn spinner_style() -> ProgressStyle {
     // Trailing space is the "finished" frame; we clear before it shows anyway.
     ProgressStyle::with_template("{spinner:.cyan} {msg}")
@@ -114,6 +195,8 @@ pub struct Step {
     /// dropped before a terminal call.
     active: String,
     color: bool,
+    /// Whether result/failure lines lead with the resource emoji.
+    emoji: bool,
     /// Whether result/failure lines are printed at all. `false` only for the
     /// silent test channel, which suppresses everything.
     emit: bool,
@@ -122,19 +205,46 @@ pub struct Step {
 }
 
 enum StepState {
-    /// No animation, but result lines still print (piped/non-TTY, and the
-    /// silent test channel which additionally sets `emit = false`).
+    /// No animation or status lines (the silent test channel, which also sets
+    /// `emit = false`).
+    #[cfg(test)]
     Plain,
-    /// TTY: an animated stderr spinner backs the step.
-    Animated(ProgressBar),
+    /// TTY: an animated stderr spinner backs the step, one of the progress's
+    /// stack of them.
+    #[cfg(feature = "progress")]
+    Animated(ProgressBar, MultiProgress),
+    /// Status lines on stderr, the last printed at `said`.
+    Narrated {
+        number: usize,
+        said: Mutex<Instant>,
+        timestamps: bool,
+    },
 }
 
 impl Step {
-    /// Replace the spinner message mid-flight (e.g. a draining counter). No-op
-    /// when not animating.
+    /// Replace the spinner message mid-flight (e.g. a draining counter). When
+    /// narrating, repeat it as a status line if [`NARRATE_INTERVAL`] has passed
+    /// since the last; otherwise a no-op when not animating.
     pub fn update(&self, active: &str) {
-        if let StepState::Animated(bar) = &self.state {
-            bar.set_message(format!("{} {active}", self.icon.emoji()));
+        match &self.state {
+            #[cfg(feature = "progress")]
+            StepState::Animated(bar, _) => {
+                bar.set_message(format!("{} {active}", self.icon.emoji()));
+            }
+            StepState::Narrated {
+                number,
+                said,
+                timestamps,
+            } => {
+                let mut said = said.lock().unwrap_or_else(|e| e.into_inner());
+                if said.elapsed() >= NARRATE_INTERVAL {
+                    let at = timestamps.then(Utc::now);
+                    eprintln!("{}", status_line(*number, active, at));
+                    *said = Instant::now();
+                }
+            }
+            #[cfg(test)]
+            StepState::Plain => {}
         }
     }
 
@@ -143,7 +253,8 @@ impl Step {
     pub fn finish(mut self, tone: Tone, summary: &str) {
         self.clear_spinner();
         if self.emit {
-            println!("{}", success_line(self.icon, tone, summary, self.color));
+            let line = success_line(self.icon, tone, summary, self.emoji, self.color);
+            self.above_spinners(|| println!("{line}"));
         }
         self.done = true;
     }
@@ -156,8 +267,19 @@ impl Step {
     }
 
     fn clear_spinner(&self) {
-        if let StepState::Animated(bar) = &self.state {
+        #[cfg(feature = "progress")]
+        if let StepState::Animated(bar, multi) = &self.state {
             bar.finish_and_clear();
+            multi.remove(bar);
+        }
+    }
+
+    /// Print with any other steps' spinners out of the way.
+    fn above_spinners(&self, print: impl FnOnce()) {
+        match &self.state {
+            #[cfg(feature = "progress")]
+            StepState::Animated(_, multi) => multi.suspend(print),
+            _ => print(),
         }
     }
 }
@@ -172,7 +294,8 @@ impl Drop for Step {
         // anyhow context and prints at the top level).
         self.clear_spinner();
         if self.emit {
-            eprintln!("{}", failure_line(self.icon, &self.active, self.color));
+            let line = failure_line(self.icon, &self.active, self.emoji, self.color);
+            self.above_spinners(|| eprintln!("{line}"));
         }
     }
 }
@@ -185,23 +308,58 @@ pub trait Progress {
     fn step(&self, icon: Icon, active: &str) -> Step;
 }
 
-/// Terminal-aware progress: spinner on a TTY, plain lines when piped.
+/// Terminal-aware progress: spinner on a TTY, status lines otherwise.
 pub struct SpinnerProgress {
-    animate: bool,
+    narration: Narration,
     color: bool,
+    /// Steps opened so far, to number narrated ones.
+    steps: AtomicUsize,
+    /// The spinners of the steps in flight.
+    #[cfg(feature = "progress")]
+    spinners: MultiProgress,
 }
 
 impl SpinnerProgress {
-    /// Spinner on stderr, colour gated on stdout. They can differ (e.g. stdout
-    /// piped, stderr a terminal).
+    /// Spinner on stderr, colour and emoji gated on stdout. They can differ
+    /// (e.g. stdout piped, stderr a terminal).
     pub fn new() -> Self {
+        let mut narration = narration(
+            narrated(),
+            is_ci(std::env::var("CI").ok().as_deref()),
+            console::user_attended_stderr(),
+        );
+        narration.animate &= cfg!(feature = "progress");
+        let color = crate::commands::ui::colors_enabled();
+        narration.emoji &= color;
         Self {
-            animate: console::user_attended_stderr(),
-            color: console::Term::stdout().features().colors_supported(),
+            narration,
+            color,
+            steps: AtomicUsize::new(0),
+            #[cfg(feature = "progress")]
+            spinners: MultiProgress::new(),
         }
     }
 }
 
+impl SpinnerProgress {
+    /// A spinner for the step, when animating.
+    #[cfg(feature = "progress")]
+    fn spinner(&self, icon: Icon, active: &str) -> Option<StepState> {
+        self.narration.animate.then(|| {
+            let bar = self.spinners.add(ProgressBar::new_spinner());
+            bar.set_style(spinner_style());
+            bar.enable_steady_tick(Duration::from_millis(80));
+            bar.set_message(format!("{} {active}", icon.emoji()));
+            StepState::Animated(bar, self.spinners.clone())
+        })
+    }
+
+    #[cfg(not(feature = "progress"))]
+    fn spinner(&self, _icon: Icon, _active: &str) -> Option<StepState> {
+        None
+    }
+}
+
 impl Default for SpinnerProgress {
     fn default() -> Self {
         Self::new()
@@ -210,20 +368,24 @@ impl Default for SpinnerProgress {
 
 impl Progress for SpinnerProgress {
     fn step(&self, icon: Icon, active: &str) -> Step {
-        let state = if self.animate {
-            let bar = ProgressBar::new_spinner();
-            bar.set_style(spinner_style());
-            bar.enable_steady_tick(Duration::from_millis(80));
-            bar.set_message(format!("{} {active}", icon.emoji()));
-            StepState::Animated(bar)
-        } else {
-            StepState::Plain
-        };
+        let number = self.steps.fetch_add(1, Ordering::Relaxed) + 1;
+        let Narration {
+            timestamps, emoji, ..
+        } = self.narration;
+        let state = self.spinner(icon, active).unwrap_or_else(|| {
+            eprintln!("{}", status_line(number, active, timestamps.then(Utc::now)));
+            StepState::Narrated {
+                number,
+                said: Mutex::new(Instant::now()),
+                timestamps,
+            }
+        });
         Step {
             state,
             icon,
             active: active.to_string(),
             color: self.color,
+            emoji,
             emit: true,
             done: false,
         }
@@ -242,6 +404,7 @@ impl Progress for SilentProgress {
             icon,
             active: active.to_string(),
             color: false,
+            emoji: true,
             emit: false,
             done: false,
         }
@@ -255,11 +418,62 @@ mod tests {
     #[test]
     fn success_line_uncolored_uses_sigil_emoji_and_summary() {
         assert_eq!(
-            success_line(Icon::Service, Tone::Add, "service web created", false),
+            success_line(Icon::Service, Tone::Add, "service web created", true, false),
             "  + 📦 service web created"
         );
     }
 
+    #[test]
+    fn narrated_lines_leave_out_the_emoji() {
+        assert_eq!(
+            success_line(
+                Icon::Service,
+                Tone::Add,
+                "service web created",
+                false,
+                false
+            ),
+            "  + service web created"
+        );
+        assert_eq!(
+            failure_line(Icon::Service, "Creating service api", false, false),
+            "  ✗ Creating service api"
+        );
+        assert_eq!(
+            status_line(2, "Creating service web", None),
+            "step 2: Creating service web…"
+        );
+    }
+
+    #[test]
+    fn status_lines_for_logs_carry_the_time() {
+        let at = "2025-03-14T15:30:02Z".parse().unwrap();
+        assert_eq!(
+            status_line(1, "Resolving environment", Some(at)),
+            "2025-03-14T15:30:02Z step 1: Resolving environment…"
+        );
+    }
+
+    #[test]
+    fn spinners_only_on_an_attended_terminal_outside_ci() {
+        let animated = narration(false, false, true);
+        assert!(animated.animate && !animated.timestamps && animated.emoji);
+        for (ci, tty) in [(true, true), (false, false), (true, false)] {
+            let logged = narration(false, ci, tty);
+            assert!(!logged.animate && logged.timestamps, "ci={ci} tty={tty}");
+        }
+        assert_eq!(
+            narration(true, false, true),
+            Narration {
+                animate: false,
+                timestamps: false,
+                emoji: false
+            }
+        );
+        assert!(is_ci(Some("true")) && is_ci(Some("1")));
+        assert!(!is_ci(Some("false")) && !is_ci(Some("")) && !is_ci(None));
+    }
+
     #[test]
     fn success_line_sigils_match_the_diff_vocabulary() {
         let cases = [
@@ -270,7 +484,7 @@ mod tests {
             (Tone::Warn, "!"),
         ];
         for (tone, sigil) in cases {
-            let line = success_line(Icon::Deployment, tone, "x", false);
+            let line = success_line(Icon::Deployment, tone, "x", true, false);
             assert_eq!(line, format!("  {sigil} 🚀 x"));
         }
     }
@@ -278,7 +492,7 @@ mod tests {
     #[test]
     fn failure_line_uncolored_marks_the_active_step() {
         assert_eq!(
-            failure_line(Icon::Service, "Creating service api", false),
+            failure_line(Icon::Service, "Creating service api", true, false),
             "  ✗ 📦 Creating service api"
         );
     }
@@ -288,8 +502,8 @@ mod tests {
         // Whether `console` actually emits ANSI depends on the runtime terminal
         // (off in tests), but the colour path must never change the *visible*
         // text — stripping any codes yields exactly the uncolored line.
-        let colored = success_line(Icon::Service, Tone::Add, "service web created", true);
-        let plain = success_line(Icon::Service, Tone::Add, "service web created", false);
+        let colored = success_line(Icon::Service, Tone::Add, "service web created", true, true);
+        let plain = success_line(Icon::Service, Tone::Add, "service web created", true, false);
         assert_eq!(console::strip_ansi_codes(&colored), plain);
     }
 }
@@ -0,0 +1,171 @@
+//! User settings, read from `~/.unisrv/config.json`:
+//!
+//! ```json
+//! {
+//!   "read_only": true,
+//!   "no_spinner": true,
+//!   "locale": "de-DE",
+//!   "platform": "linux/arm64",
+//!   "region": "eu-central",
+//!   "credential_helper": "osxkeychain",
+//!   "allowed_registries": ["ghcr.io/acme"],
+//!   "telemetry": true,
+//!   "environments": { "prod": { "protected": true } }
+//! }
+//! ```
+//!
+//! An environment is named on its own (any project's `prod`) or as
+//! `project/env`.
+//!
+//! Unlike preferences, which the CLI writes for itself, this file is written
+//! by hand, so one that doesn't parse is an error rather than read as empty —
+//! a typo mustn't quietly turn off `read_only`.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::commands::instance::platform::Platform;
+use crate::commands::locale::Locale;
+
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// Refuse any change, as if every command were run with `--read-only`.
+    pub read_only: bool,
+    /// Plain status lines instead of spinners, as with `--no-spinner`.
+    pub no_spinner: bool,
+    /// Dates and numbers in tables, unless `--locale` says otherwise;
+    /// otherwise the system locale.
+    pub locale: Option<Locale>,
+    /// The image platform `instance run` asks for, unless `--platform` says
+    /// otherwise; otherwise the server's `linux/amd64`.
+    pub platform: Option<Platform>,
+    /// Where instances, workers and services are provisioned, unless
+    /// `--region` says otherwise; otherwise `dev`. See `unisrv regions`.
+    pub region: Option<String>,
+    /// Authenticate the CLI's own registry requests (`registry tags`,
+    /// `registry push`, `--platform` checks) with
+    /// `docker-credential-<name>`, rather than credentials stored in unisrv.
+    pub credential_helper: Option<String>,
+    /// The only registries `instance run` and `up` take images from; any
+    /// registry when empty. See [`crate::commands::registry::policy`].
+    pub allowed_registries: Vec<String>,
+    /// Send anonymous command usage and timings to unisrv. Off unless set;
+    /// see [`crate::telemetry`].
+    pub telemetry: bool,
+    /// Per-environment settings, by `env` or `project/env`.
+    pub environments: BTreeMap<String, EnvironmentSettings>,
+}
+
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnvironmentSettings {
+    /// Destructive commands in this environment ask for the resource's name
+    /// to be typed back before going ahead.
+    pub protected: bool,
+}
+
+impl Settings {
+    /// The default location, `~/.unisrv/config.json`. `None` if the home
+    /// directory can't be determined.
+    pub fn default_path() -> Option<PathBuf> {
+        Some(unisrv_api::config_dir()?.join("config.json"))
+    }
+
+    /// The settings at `path`; all defaults if there's no file.
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json)
+                .with_context(|| format!("invalid settings in {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("failed to read {}", path.display())),
+        }
+    }
+
+    /// Whether `project`'s environment `env` is marked protected, either as
+    /// `project/env` or by name alone.
+    pub fn is_protected(&self, project: &str, env: &str) -> bool {
+        [format!("{project}/{env}"), env.to_string()]
+            .iter()
+            .filter_map(|key| self.environments.get(key))
+            .any(|e| e.protected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_file_means_defaults() {
+        let tmp = tempfile::tempdir().unwrap();
+        let settings = Settings::load(&tmp.path().join("config.json")).unwrap();
+        assert_eq!(settings, Settings::default());
+    }
+
+    #[test]
+    fn reads_read_only_and_rejects_typos() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.json");
+        std::fs::write(&path, r#"{ "read_only": true }"#).unwrap();
+        assert!(Settings::load(&path).unwrap().read_only);
+
+        std::fs::write(&path, r#"{ "readonly": true }"#).unwrap();
+        let err = Settings::load(&path).unwrap_err();
+        assert!(format!("{err:#}").contains("unknown field"), "{err:#}");
+    }
+
+    #[test]
+    fn reads_the_locale_and_rejects_unknown_ones() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.json");
+        std::fs::write(&path, r#"{ "locale": "de_DE" }"#).unwrap();
+        let locale = Settings::load(&path).unwrap().locale.unwrap();
+        assert_eq!(locale.to_string(), "de-DE");
+
+        std::fs::write(&path, r#"{ "locale": "xx" }"#).unwrap();
+        let err = Settings::load(&path).unwrap_err();
+        assert!(format!("{err:#}").contains("unsupported locale"), "{err:#}");
+    }
+
+    #[test]
+    fn reads_the_platform_and_rejects_malformed_ones() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.json");
+        std::fs::write(&path, r#"{ "platform": "linux/arm64" }"#).unwrap();
+        let platform = Settings::load(&path).unwrap().platform.unwrap();
+        assert_eq!(platform.to_string(), "linux/arm64");
+
+        std::fs::write(&path, r#"{ "platform": "arm64" }"#).unwrap();
+        let err = Settings::load(&path).unwrap_err();
+        assert!(format!("{err:#}").contains("invalid platform"), "{err:#}");
+    }
+
+    #[test]
+    fn reads_allowed_registries() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.json");
+        std::fs::write(&path, r#"{ "allowed_registries": ["ghcr.io/acme"] }"#).unwrap();
+        let settings = Settings::load(&path).unwrap();
+        assert_eq!(settings.allowed_registries, ["ghcr.io/acme"]);
+    }
+
+    #[test]
+    fn protects_environments_by_name_or_project() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.json");
+        std::fs::write(
+            &path,
+            r#"{ "environments": { "prod": { "protected": true }, "shop/staging": { "protected": true } } }"#,
+        )
+        .unwrap();
+        let settings = Settings::load(&path).unwrap();
+
+        assert!(settings.is_protected("demo", "prod"));
+        assert!(settings.is_protected("shop", "staging"));
+        assert!(!settings.is_protected("demo", "staging"));
+    }
+}
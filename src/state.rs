@@ -0,0 +1,752 @@
+//! Local CLI state: deployment history, command metrics, recently used images,
+//! a resolution cache, `up` outputs, and failure reports.
+//!
+//! Everything lives under one directory so `unisrv state clear` can wipe it in
+//! one go: `$UNISRV_STATE_DIR` if set, otherwise the platform state dir
+//! (`$XDG_STATE_HOME/unisrv`, default `~/.local/state/unisrv`; the local data
+//! dir on platforms without one, `%LOCALAPPDATA%\unisrv` on Windows). Unlike
+//! `~/.unisrv` this holds nothing the user configured — only what the CLI
+//! observed. Deleting it is safe except while a blue/green rollout is
+//! pending: its record is what `rollout promote` and `abort` work from, so
+//! `state clear` asks first.
+//!
+//! Like preferences, state is best-effort: a missing or corrupt file reads as
+//! empty, and every store is capped so the directory can't grow unbounded.
+//!
+//! ```text
+//! history.jsonl         one HistoryEntry per line, oldest first
+//! metrics.jsonl         one CommandRun per line, oldest first
+//! recent_images.json    most recently deployed images, newest first
+//! resolution_cache.json name → id lookups keyed by caller-chosen strings
+//! shell_history         `unisrv shell` command lines, one per line, oldest first
+//! completion_cache.json resource names offered by shell completion, briefly
+//! outputs.json          each environment's `output` values from its last `up`
+//! pending_rollouts.json blue/green rollouts awaiting `rollout promote` or `abort`
+//! failures/             one report per failed apply, newest kept
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use unisrv_api::models::DeploymentConfiguration;
+use uuid::Uuid;
+
+/// Overrides the state directory location (useful for CI and tests).
+pub const STATE_DIR_ENV: &str = "UNISRV_STATE_DIR";
+
+/// Oldest history entries are dropped beyond this many.
+pub const MAX_HISTORY_ENTRIES: usize = 500;
+/// Oldest command runs are dropped beyond this many.
+pub const MAX_COMMAND_RUNS: usize = 2000;
+/// Length of the recently-used image list.
+pub const MAX_RECENT_IMAGES: usize = 50;
+/// Least recently stored resolutions are evicted beyond this many.
+pub const MAX_CACHED_RESOLUTIONS: usize = 1000;
+/// Oldest shell command lines are dropped beyond this many.
+#[cfg_attr(not(feature = "interactive"), allow(dead_code))]
+pub const MAX_SHELL_HISTORY: usize = 1000;
+/// Least recently stored completion entries are evicted beyond this many.
+pub const MAX_COMPLETION_ENTRIES: usize = 20;
+/// Only the newest failure reports are kept.
+pub const MAX_FAILURE_REPORTS: usize = 20;
+/// A failure report is truncated to this many bytes.
+pub const MAX_FAILURE_REPORT_BYTES: usize = 64 * 1024;
+
+const HISTORY_FILE: &str = "history.jsonl";
+const METRICS_FILE: &str = "metrics.jsonl";
+const RECENT_IMAGES_FILE: &str = "recent_images.json";
+const RESOLUTION_CACHE_FILE: &str = "resolution_cache.json";
+#[cfg_attr(not(feature = "interactive"), allow(dead_code))]
+const SHELL_HISTORY_FILE: &str = "shell_history";
+const COMPLETION_CACHE_FILE: &str = "completion_cache.json";
+const OUTPUTS_FILE: &str = "outputs.json";
+const PENDING_ROLLOUTS_FILE: &str = "pending_rollouts.json";
+const FAILURES_DIR: &str = "failures";
+
+/// Whether a recorded `up` went through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Applied,
+    Failed,
+}
+
+/// One recorded `up` against an environment.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub at: DateTime<Utc>,
+    pub project: String,
+    pub environment: String,
+    /// Deployment name → container image as applied.
+    #[serde(default)]
+    pub deployments: BTreeMap<String, String>,
+    pub outcome: Outcome,
+}
+
+/// One finished command, as `unisrv stats self` reports on it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandRun {
+    pub at: DateTime<Utc>,
+    /// The subcommand path, e.g. `instance run`; never its arguments.
+    pub command: String,
+    pub duration_ms: u64,
+    pub success: bool,
+}
+
+/// The `output` values an `up` resolved for one environment.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredOutputs {
+    pub at: DateTime<Utc>,
+    pub values: BTreeMap<String, serde_json::Value>,
+}
+
+/// A blue/green rollout staged next to a service's live instances, waiting
+/// to be promoted or aborted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingRollout {
+    pub at: DateTime<Utc>,
+    pub deployment: String,
+    pub deployment_id: Uuid,
+    pub service_id: Uuid,
+    /// The image the new generation runs.
+    pub image: String,
+    /// The target group the deployment serves from.
+    pub group: String,
+    /// The target group the new generation serves from.
+    pub preview_group: String,
+    /// The service location that reaches the new generation meanwhile.
+    pub preview_path: String,
+    pub instances: Vec<Uuid>,
+    pub targets: Vec<Uuid>,
+    /// The configuration the new generation was started with, account
+    /// defaults merged in, which promoting rolls the deployment out with.
+    /// Absent for a rollout staged before it was recorded.
+    #[serde(default)]
+    pub configuration: Option<DeploymentConfiguration>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedResolution {
+    id: Uuid,
+    at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedCompletion {
+    at: DateTime<Utc>,
+    value: serde_json::Value,
+}
+
+/// Handle on the state directory. Nothing is created until the first write.
+#[derive(Debug, Clone)]
+pub struct StateDir {
+    root: PathBuf,
+}
+
+impl StateDir {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// The default location (see the module docs). `None` if neither the
+    /// override nor a platform directory is available.
+    pub fn locate() -> Option<Self> {
+        if let Some(dir) = std::env::var_os(STATE_DIR_ENV).filter(|v| !v.is_empty()) {
+            return Some(Self::new(PathBuf::from(dir)));
+        }
+        let base = dirs::state_dir().or_else(dirs::data_local_dir)?;
+        Some(Self::new(base.join("unisrv")))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    // ── History ──
+
+    /// Every recorded entry, oldest first. Unparseable lines are skipped.
+    pub fn history(&self) -> Vec<HistoryEntry> {
+        std::fs::read_to_string(self.root.join(HISTORY_FILE))
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()
+    }
+
+    /// Append an entry, dropping the oldest beyond [`MAX_HISTORY_ENTRIES`].
+    pub fn record_history(&self, entry: &HistoryEntry) -> Result<()> {
+        let mut entries = self.history();
+        entries.push(entry.clone());
+        let skip = entries.len().saturating_sub(MAX_HISTORY_ENTRIES);
+        let mut out = String::new();
+        for entry in &entries[skip..] {
+            out.push_str(&serde_json::to_string(entry)?);
+            out.push('\n');
+        }
+        self.write(HISTORY_FILE, &out)
+    }
+
+    // ── Command metrics ──
+
+    /// Every recorded command run, oldest first. Unparseable lines are
+    /// skipped.
+    pub fn command_runs(&self) -> Vec<CommandRun> {
+        std::fs::read_to_string(self.root.join(METRICS_FILE))
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()
+    }
+
+    /// Append a run, dropping the oldest beyond [`MAX_COMMAND_RUNS`].
+    pub fn record_command_run(&self, run: &CommandRun) -> Result<()> {
+        let mut runs = self.command_runs();
+        runs.push(run.clone());
+        let skip = runs.len().saturating_sub(MAX_COMMAND_RUNS);
+        let mut out = String::new();
+        for run in &runs[skip..] {
+            out.push_str(&serde_json::to_string(run)?);
+            out.push('\n');
+        }
+        self.write(METRICS_FILE, &out)
+    }
+
+    // ── Recent images ──
+
+    /// Recently deployed images, newest first.
+    pub fn recent_images(&self) -> Vec<String> {
+        self.read_json(RECENT_IMAGES_FILE)
+    }
+
+    /// Move `images` to the front of the recent list (the first given ends up
+    /// newest), keeping at most [`MAX_RECENT_IMAGES`].
+    pub fn remember_images<'a>(&self, images: impl IntoIterator<Item = &'a str>) -> Result<()> {
+        let mut recent = self.recent_images();
+        for image in images {
+            recent.retain(|i| i != image);
+            recent.insert(0, image.to_string());
+        }
+        recent.truncate(MAX_RECENT_IMAGES);
+        self.write_json(RECENT_IMAGES_FILE, &recent)
+    }
+
+    // ── Resolution cache ──
+
+    /// A previously cached id for `key`. Callers must treat it as a hint and
+    /// revalidate — the resource may have been deleted or renamed since.
+    pub fn cached_resolution(&self, key: &str) -> Option<Uuid> {
+        let cache: BTreeMap<String, CachedResolution> = self.read_json(RESOLUTION_CACHE_FILE);
+        cache.get(key).map(|c| c.id)
+    }
+
+    /// Cache `key → id`, evicting the oldest beyond [`MAX_CACHED_RESOLUTIONS`].
+    pub fn cache_resolution(&self, key: &str, id: Uuid) -> Result<()> {
+        let mut cache: BTreeMap<String, CachedResolution> = self.read_json(RESOLUTION_CACHE_FILE);
+        cache.insert(key.to_string(), CachedResolution { id, at: Utc::now() });
+        while cache.len() > MAX_CACHED_RESOLUTIONS {
+            let oldest = cache
+                .iter()
+                .min_by_key(|(_, c)| c.at)
+                .map(|(k, _)| k.clone())
+                .expect("cache is non-empty");
+            cache.remove(&oldest);
+        }
+        self.write_json(RESOLUTION_CACHE_FILE, &cache)
+    }
+
+    // ── Completion cache ──
+
+    /// What was cached for `key`, if it was stored less than `max_age` ago.
+    pub fn cached_completion(&self, key: &str, max_age: Duration) -> Option<serde_json::Value> {
+        let mut cache: BTreeMap<String, CachedCompletion> = self.read_json(COMPLETION_CACHE_FILE);
+        let cached = cache.remove(key)?;
+        (Utc::now() - cached.at < max_age).then_some(cached.value)
+    }
+
+    /// Cache `value` under `key`, evicting the oldest beyond
+    /// [`MAX_COMPLETION_ENTRIES`].
+    pub fn cache_completion(&self, key: &str, value: serde_json::Value) -> Result<()> {
+        let mut cache: BTreeMap<String, CachedCompletion> = self.read_json(COMPLETION_CACHE_FILE);
+        cache.insert(
+            key.to_string(),
+            CachedCompletion {
+                at: Utc::now(),
+                value,
+            },
+        );
+        while cache.len() > MAX_COMPLETION_ENTRIES {
+            let oldest = cache
+                .iter()
+                .min_by_key(|(_, c)| c.at)
+                .map(|(k, _)| k.clone())
+                .expect("cache is non-empty");
+            cache.remove(&oldest);
+        }
+        self.write_json(COMPLETION_CACHE_FILE, &cache)
+    }
+
+    // ── Outputs ──
+
+    /// The outputs last saved for `project`/`environment`.
+    pub fn outputs(&self, project: &str, environment: &str) -> Option<StoredOutputs> {
+        let mut all: BTreeMap<String, StoredOutputs> = self.read_json(OUTPUTS_FILE);
+        all.remove(&format!("{project}/{environment}"))
+    }
+
+    /// Environments of `project` with saved outputs, by name.
+    pub fn output_environments(&self, project: &str) -> Vec<String> {
+        let all: BTreeMap<String, StoredOutputs> = self.read_json(OUTPUTS_FILE);
+        all.keys()
+            .filter_map(|key| key.strip_prefix(project)?.strip_prefix('/'))
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Replace the outputs saved for `project`/`environment`.
+    pub fn save_outputs(
+        &self,
+        project: &str,
+        environment: &str,
+        values: BTreeMap<String, serde_json::Value>,
+    ) -> Result<()> {
+        let mut all: BTreeMap<String, StoredOutputs> = self.read_json(OUTPUTS_FILE);
+        all.insert(
+            format!("{project}/{environment}"),
+            StoredOutputs {
+                at: Utc::now(),
+                values,
+            },
+        );
+        self.write_json(OUTPUTS_FILE, &all)
+    }
+
+    // ── Pending rollouts ──
+
+    /// The blue/green rollout pending for `service` in `project`/`environment`.
+    pub fn pending_rollout(
+        &self,
+        project: &str,
+        environment: &str,
+        service: &str,
+    ) -> Option<PendingRollout> {
+        let mut all: BTreeMap<String, PendingRollout> = self.read_json(PENDING_ROLLOUTS_FILE);
+        all.remove(&format!("{project}/{environment}/{service}"))
+    }
+
+    /// Every pending rollout's `project/environment/service`, sorted.
+    pub fn pending_rollouts(&self) -> Vec<String> {
+        let all: BTreeMap<String, PendingRollout> = self.read_json(PENDING_ROLLOUTS_FILE);
+        all.into_keys().collect()
+    }
+
+    /// Note `rollout` as pending for `service`, replacing any before it.
+    pub fn save_pending_rollout(
+        &self,
+        project: &str,
+        environment: &str,
+        service: &str,
+        rollout: &PendingRollout,
+    ) -> Result<()> {
+        let mut all: BTreeMap<String, PendingRollout> = self.read_json(PENDING_ROLLOUTS_FILE);
+        all.insert(
+            format!("{project}/{environment}/{service}"),
+            rollout.clone(),
+        );
+        self.write_json(PENDING_ROLLOUTS_FILE, &all)
+    }
+
+    /// Forget `service`'s pending rollout, once promoted or aborted.
+    pub fn clear_pending_rollout(
+        &self,
+        project: &str,
+        environment: &str,
+        service: &str,
+    ) -> Result<()> {
+        let mut all: BTreeMap<String, PendingRollout> = self.read_json(PENDING_ROLLOUTS_FILE);
+        if all
+            .remove(&format!("{project}/{environment}/{service}"))
+            .is_some()
+        {
+            self.write_json(PENDING_ROLLOUTS_FILE, &all)?;
+        }
+        Ok(())
+    }
+
+    // ── Shell history ──
+
+    /// Command lines entered in `unisrv shell`, oldest first.
+    #[cfg_attr(not(feature = "interactive"), allow(dead_code))]
+    pub fn shell_history(&self) -> Vec<String> {
+        std::fs::read_to_string(self.root.join(SHELL_HISTORY_FILE))
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Append a command line, skipping an immediate repeat and dropping the
+    /// oldest beyond [`MAX_SHELL_HISTORY`].
+    #[cfg_attr(not(feature = "interactive"), allow(dead_code))]
+    pub fn record_shell_command(&self, line: &str) -> Result<()> {
+        let mut lines = self.shell_history();
+        if lines.last().is_some_and(|last| last == line) {
+            return Ok(());
+        }
+        lines.push(line.to_string());
+        let skip = lines.len().saturating_sub(MAX_SHELL_HISTORY);
+        let mut out = lines[skip..].join("\n");
+        out.push('\n');
+        self.write(SHELL_HISTORY_FILE, &out)
+    }
+
+    // ── Failure reports ──
+
+    /// Save a failure report for `command`, truncated to
+    /// [`MAX_FAILURE_REPORT_BYTES`], pruning all but the newest
+    /// [`MAX_FAILURE_REPORTS`]. Returns the report's path.
+    pub fn save_failure_report(&self, command: &str, report: &str) -> Result<PathBuf> {
+        let dir = self.root.join(FAILURES_DIR);
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+        let mut body = report;
+        if body.len() > MAX_FAILURE_REPORT_BYTES {
+            let mut end = MAX_FAILURE_REPORT_BYTES;
+            while !body.is_char_boundary(end) {
+                end -= 1;
+            }
+            body = &body[..end];
+        }
+        // The timestamp prefix makes name order chronological order.
+        let name = format!("{}-{command}.log", Utc::now().format("%Y%m%dT%H%M%S%.3fZ"));
+        let path = dir.join(name);
+        std::fs::write(&path, body)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+
+        let reports = self.failure_reports();
+        for old in &reports[..reports.len().saturating_sub(MAX_FAILURE_REPORTS)] {
+            let _ = std::fs::remove_file(old);
+        }
+        Ok(path)
+    }
+
+    /// Saved failure reports, oldest first.
+    pub fn failure_reports(&self) -> Vec<PathBuf> {
+        let mut reports: Vec<PathBuf> = std::fs::read_dir(self.root.join(FAILURES_DIR))
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "log"))
+            .collect();
+        reports.sort();
+        reports
+    }
+
+    // ── Housekeeping ──
+
+    /// Total size of everything stored, in bytes.
+    pub fn size(&self) -> u64 {
+        fn walk(path: &Path) -> u64 {
+            match std::fs::symlink_metadata(path) {
+                Ok(meta) if meta.is_dir() => std::fs::read_dir(path)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| walk(&entry.path()))
+                    .sum(),
+                Ok(meta) => meta.len(),
+                Err(_) => 0,
+            }
+        }
+        walk(&self.root)
+    }
+
+    /// Delete everything. Returns the number of bytes freed.
+    pub fn clear(&self) -> Result<u64> {
+        let freed = self.size();
+        match std::fs::remove_dir_all(&self.root) {
+            Ok(()) => Ok(freed),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e).with_context(|| format!("failed to remove {}", self.root.display())),
+        }
+    }
+
+    fn read_json<T: serde::de::DeserializeOwned + Default>(&self, file: &str) -> T {
+        std::fs::read_to_string(self.root.join(file))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    fn write_json<T: Serialize>(&self, file: &str, value: &T) -> Result<()> {
+        self.write(file, &serde_json::to_string_pretty(value)?)
+    }
+
+    fn write(&self, file: &str, contents: &str) -> Result<()> {
+        std::fs::create_dir_all(&self.root)
+            .with_context(|| format!("failed to create {}", self.root.display()))?;
+        let path = self.root.join(file);
+        std::fs::write(&path, contents)
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state_at(tmp: &tempfile::TempDir) -> StateDir {
+        StateDir::new(tmp.path().join("state"))
+    }
+
+    fn entry(environment: &str, outcome: Outcome) -> HistoryEntry {
+        HistoryEntry {
+            at: Utc::now(),
+            project: "demo".into(),
+            environment: environment.into(),
+            deployments: BTreeMap::from([("web".into(), "nginx:1".into())]),
+            outcome,
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_state_lives_under_local_app_data_not_the_config_dir() {
+        if std::env::var_os(STATE_DIR_ENV).is_some() {
+            return;
+        }
+        let local = PathBuf::from(std::env::var_os("LOCALAPPDATA").unwrap());
+        let state = StateDir::locate().unwrap();
+        assert_eq!(state.root(), local.join("unisrv"));
+        let config = unisrv_api::config_dir().unwrap();
+        assert!(config.ends_with(".unisrv"));
+        assert!(!state.root().starts_with(&config));
+    }
+
+    #[test]
+    fn fresh_state_reads_as_empty() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = state_at(&tmp);
+        assert!(state.history().is_empty());
+        assert!(state.recent_images().is_empty());
+        assert!(state.cached_resolution("x").is_none());
+        assert!(state.failure_reports().is_empty());
+        assert_eq!(state.size(), 0);
+    }
+
+    #[test]
+    fn history_appends_and_keeps_only_the_newest() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = state_at(&tmp);
+        for i in 0..MAX_HISTORY_ENTRIES + 3 {
+            state
+                .record_history(&entry(&format!("env{i}"), Outcome::Applied))
+                .unwrap();
+        }
+        let history = state.history();
+        assert_eq!(history.len(), MAX_HISTORY_ENTRIES);
+        assert_eq!(history[0].environment, "env3");
+        assert_eq!(
+            history.last().unwrap().environment,
+            format!("env{}", MAX_HISTORY_ENTRIES + 2)
+        );
+    }
+
+    #[test]
+    fn command_runs_append_and_keep_only_the_newest() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = state_at(&tmp);
+        let run = |i: usize| CommandRun {
+            at: Utc::now(),
+            command: format!("cmd{i}"),
+            duration_ms: 10,
+            success: true,
+        };
+        // Start from a full file rather than recording each run.
+        let full: String = (0..MAX_COMMAND_RUNS + 1)
+            .map(|i| serde_json::to_string(&run(i)).unwrap() + "\n")
+            .collect();
+        state.write(METRICS_FILE, &full).unwrap();
+
+        state
+            .record_command_run(&run(MAX_COMMAND_RUNS + 1))
+            .unwrap();
+        let runs = state.command_runs();
+        assert_eq!(runs.len(), MAX_COMMAND_RUNS);
+        assert_eq!(runs[0].command, "cmd2");
+        assert_eq!(
+            runs.last().unwrap().command,
+            format!("cmd{}", MAX_COMMAND_RUNS + 1)
+        );
+    }
+
+    #[test]
+    fn corrupt_history_lines_are_skipped() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = state_at(&tmp);
+        state
+            .record_history(&entry("prod", Outcome::Failed))
+            .unwrap();
+        let path = state.root().join(HISTORY_FILE);
+        let mut raw = std::fs::read_to_string(&path).unwrap();
+        raw.push_str("{ truncated\n");
+        std::fs::write(&path, raw).unwrap();
+
+        let history = state.history();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].outcome, Outcome::Failed);
+    }
+
+    #[test]
+    fn shell_history_skips_repeats_and_keeps_only_the_newest() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = state_at(&tmp);
+        state.record_shell_command("instance ls").unwrap();
+        state.record_shell_command("instance ls").unwrap();
+        state.record_shell_command("network ips backend").unwrap();
+        assert_eq!(
+            state.shell_history(),
+            vec!["instance ls", "network ips backend"]
+        );
+
+        for i in 0..MAX_SHELL_HISTORY {
+            state
+                .record_shell_command(&format!("instance show {i}"))
+                .unwrap();
+        }
+        let history = state.shell_history();
+        assert_eq!(history.len(), MAX_SHELL_HISTORY);
+        assert_eq!(history[0], "instance show 0");
+    }
+
+    #[test]
+    fn recent_images_are_deduplicated_newest_first() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = state_at(&tmp);
+        state.remember_images(["nginx:1", "redis:7"]).unwrap();
+        state.remember_images(["nginx:1"]).unwrap();
+        assert_eq!(state.recent_images(), vec!["nginx:1", "redis:7"]);
+
+        let many: Vec<String> = (0..MAX_RECENT_IMAGES + 5)
+            .map(|i| format!("img:{i}"))
+            .collect();
+        state
+            .remember_images(many.iter().map(String::as_str))
+            .unwrap();
+        assert_eq!(state.recent_images().len(), MAX_RECENT_IMAGES);
+    }
+
+    #[test]
+    fn resolution_cache_round_trips() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = state_at(&tmp);
+        let id = Uuid::new_v4();
+        state.cache_resolution("env/prod", id).unwrap();
+        assert_eq!(state.cached_resolution("env/prod"), Some(id));
+        assert_eq!(state.cached_resolution("env/dev"), None);
+    }
+
+    #[test]
+    fn completion_cache_expires() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = state_at(&tmp);
+        let names = serde_json::json!({"instances": ["api-1"]});
+        state.cache_completion("/src/shop", names.clone()).unwrap();
+        assert_eq!(
+            state.cached_completion("/src/shop", Duration::seconds(30)),
+            Some(names)
+        );
+        assert_eq!(state.cached_completion("/src/shop", Duration::zero()), None);
+        assert_eq!(
+            state.cached_completion("/src/other", Duration::seconds(30)),
+            None
+        );
+    }
+
+    #[test]
+    fn outputs_are_kept_per_environment() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = state_at(&tmp);
+        let url = BTreeMap::from([("url".to_string(), serde_json::json!("https://a"))]);
+        state.save_outputs("demo", "prod", url.clone()).unwrap();
+        state
+            .save_outputs("demo", "staging", BTreeMap::new())
+            .unwrap();
+        state
+            .save_outputs("other", "prod", BTreeMap::new())
+            .unwrap();
+
+        assert_eq!(state.outputs("demo", "prod").unwrap().values, url);
+        assert_eq!(state.outputs("demo", "dev"), None);
+        assert_eq!(state.output_environments("demo"), ["prod", "staging"]);
+    }
+
+    #[test]
+    fn pending_rollouts_are_kept_per_service_until_cleared() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = state_at(&tmp);
+        let pending = PendingRollout {
+            at: Utc::now(),
+            deployment: "api".into(),
+            deployment_id: Uuid::new_v4(),
+            service_id: Uuid::new_v4(),
+            image: "api:2".into(),
+            group: "web".into(),
+            preview_group: "web-green".into(),
+            preview_path: "/_preview/api".into(),
+            instances: vec![Uuid::new_v4()],
+            targets: vec![Uuid::new_v4()],
+            configuration: None,
+        };
+        state
+            .save_pending_rollout("demo", "prod", "web", &pending)
+            .unwrap();
+
+        assert_eq!(state.pending_rollout("demo", "prod", "web"), Some(pending));
+        assert_eq!(state.pending_rollout("demo", "staging", "web"), None);
+        assert_eq!(state.pending_rollouts(), ["demo/prod/web"]);
+        state.clear_pending_rollout("demo", "prod", "web").unwrap();
+        assert_eq!(state.pending_rollout("demo", "prod", "web"), None);
+    }
+
+    #[test]
+    fn failure_reports_are_truncated_and_pruned() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = state_at(&tmp);
+        let huge = "x".repeat(MAX_FAILURE_REPORT_BYTES * 2);
+        let path = state.save_failure_report("up", &huge).unwrap();
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().len() as usize,
+            MAX_FAILURE_REPORT_BYTES
+        );
+
+        // Pre-seed old reports; saving one more prunes down to the cap.
+        let dir = state.root().join(FAILURES_DIR);
+        for i in 0..MAX_FAILURE_REPORTS {
+            std::fs::write(dir.join(format!("2000{i:04}-up.log")), "old").unwrap();
+        }
+        state.save_failure_report("up", "boom").unwrap();
+        let reports = state.failure_reports();
+        assert_eq!(reports.len(), MAX_FAILURE_REPORTS);
+        assert_eq!(
+            std::fs::read_to_string(reports.last().unwrap()).unwrap(),
+            "boom"
+        );
+    }
+
+    #[test]
+    fn clear_removes_everything_and_reports_bytes_freed() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = state_at(&tmp);
+        state.remember_images(["nginx:1"]).unwrap();
+        state.save_failure_report("up", "boom").unwrap();
+        let before = state.size();
+        assert!(before > 0);
+
+        assert_eq!(state.clear().unwrap(), before);
+        assert!(!state.root().exists());
+        assert_eq!(state.clear().unwrap(), 0, "clearing twice is fine");
+    }
+}
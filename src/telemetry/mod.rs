@@ -0,0 +1,119 @@
+//! What the CLI records about its own use.
+//!
+//! Each finished command — its subcommand path, how long it took and whether
+//! it succeeded — goes to every [`Reporter`]: always [`LocalMetrics`] in the
+//! state directory, which `unisrv stats self` reads back, and, only with
+//! `"telemetry": true` in `~/.unisrv/config.json`, an anonymous report to
+//! unisrv. Nothing identifies the user: no arguments, account, token or
+//! machine id, only the path, timing, outcome, CLI version and OS.
+//!
+//! The remote reporter is behind the `telemetry` Cargo feature, on by
+//! default. Air-gapped builds use `--no-default-features` to leave it and its
+//! HTTP client out of the binary; the setting then does nothing.
+//!
+//! Reporting is best-effort: a reporter that fails logs at debug level and
+//! never changes the command's outcome. An interactive `unisrv shell` session
+//! counts as one `shell` run.
+
+#[cfg(feature = "telemetry")]
+mod remote;
+
+use async_trait::async_trait;
+use clap::ArgMatches;
+
+use crate::commands::config::Effective;
+use crate::state::{CommandRun, StateDir};
+
+/// Somewhere finished commands are reported.
+#[async_trait]
+pub trait Reporter: Send + Sync {
+    async fn report(&self, run: &CommandRun);
+}
+
+/// The command runs kept in the state directory.
+pub struct LocalMetrics {
+    state: StateDir,
+}
+
+impl LocalMetrics {
+    pub fn new(state: StateDir) -> Self {
+        LocalMetrics { state }
+    }
+}
+
+#[async_trait]
+impl Reporter for LocalMetrics {
+    async fn report(&self, run: &CommandRun) {
+        if let Err(err) = self.state.record_command_run(run) {
+            tracing::debug!("couldn't record command metrics: {err:#}");
+        }
+    }
+}
+
+/// The reporters `config` enables.
+pub fn reporters(config: &Effective) -> Vec<Box<dyn Reporter>> {
+    let mut reporters: Vec<Box<dyn Reporter>> = Vec::new();
+    if let Some(root) = &config.state_dir.value {
+        reporters.push(Box::new(LocalMetrics::new(StateDir::new(root.clone()))));
+    }
+    #[cfg(feature = "telemetry")]
+    if config.telemetry.value {
+        reporters.push(Box::new(remote::Remote::new(&config.api_host.value)));
+    }
+    reporters
+}
+
+/// Report `run` to each of `reporters`.
+pub async fn report(reporters: &[Box<dyn Reporter>], run: &CommandRun) {
+    for reporter in reporters {
+        reporter.report(run).await;
+    }
+}
+
+/// The subcommand path `matches` was parsed from, e.g. `instance run`.
+pub fn command_path(matches: &ArgMatches) -> String {
+    let mut path = Vec::new();
+    let mut matches = matches;
+    while let Some((name, sub)) = matches.subcommand() {
+        path.push(name);
+        matches = sub;
+    }
+    path.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use clap::{Arg, Command};
+
+    #[test]
+    fn the_command_path_leaves_out_arguments() {
+        let cli = Command::new("unisrv").subcommand(
+            Command::new("instance").subcommand(
+                Command::new("run")
+                    .arg(Arg::new("image"))
+                    .arg(Arg::new("env").long("env")),
+            ),
+        );
+        let matches = cli
+            .try_get_matches_from(["unisrv", "instance", "run", "nginx:1", "--env", "prod"])
+            .unwrap();
+        assert_eq!(command_path(&matches), "instance run");
+    }
+
+    #[tokio::test]
+    async fn local_metrics_land_in_the_state_directory() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = StateDir::new(tmp.path().join("state"));
+        let run = CommandRun {
+            at: Utc::now(),
+            command: "up".into(),
+            duration_ms: 1200,
+            success: false,
+        };
+        let reporters: Vec<Box<dyn Reporter>> = vec![Box::new(LocalMetrics::new(state.clone()))];
+        report(&reporters, &run).await;
+        assert_eq!(state.command_runs(), [run]);
+    }
+}
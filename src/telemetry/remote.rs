@@ -0,0 +1,101 @@
+//! The anonymous usage report sent to unisrv when `telemetry` is on.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::Serialize;
+
+use super::Reporter;
+use crate::state::CommandRun;
+
+/// Long enough for a healthy API, short enough that a slow one doesn't hold
+/// up the prompt coming back.
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+/// What's sent for one run. No timestamp: the server records when it
+/// arrived.
+#[derive(Debug, Serialize)]
+struct Event<'a> {
+    command: &'a str,
+    duration_ms: u64,
+    success: bool,
+    version: &'static str,
+    os: &'static str,
+    arch: &'static str,
+}
+
+impl<'a> Event<'a> {
+    fn new(run: &'a CommandRun) -> Self {
+        Event {
+            command: &run.command,
+            duration_ms: run.duration_ms,
+            success: run.success,
+            version: env!("CARGO_PKG_VERSION"),
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+        }
+    }
+}
+
+/// `POST {api_host}/telemetry/events`, unauthenticated.
+pub struct Remote {
+    url: String,
+    http: reqwest::Client,
+}
+
+impl Remote {
+    pub fn new(api_host: &str) -> Self {
+        Remote {
+            url: format!("{}/telemetry/events", api_host.trim_end_matches('/')),
+            http: reqwest::Client::builder()
+                .timeout(TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[async_trait]
+impl Reporter for Remote {
+    async fn report(&self, run: &CommandRun) {
+        let sent = self
+            .http
+            .post(&self.url)
+            .json(&Event::new(run))
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status);
+        if let Err(err) = sent {
+            tracing::debug!("couldn't send usage telemetry: {err}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn an_event_carries_nothing_but_the_run() {
+        let run = CommandRun {
+            at: Utc::now(),
+            command: "instance run".into(),
+            duration_ms: 830,
+            success: true,
+        };
+        let mut event = serde_json::to_value(Event::new(&run)).unwrap();
+        let object = event.as_object_mut().unwrap();
+        assert!(object.remove("version").is_some());
+        assert!(object.remove("os").is_some());
+        assert!(object.remove("arch").is_some());
+        assert_eq!(
+            event,
+            serde_json::json!({"command": "instance run", "duration_ms": 830, "success": true})
+        );
+        assert_eq!(
+            Remote::new("https://api.unisrv.io/").url,
+            "https://api.unisrv.io/telemetry/events"
+        );
+    }
+}